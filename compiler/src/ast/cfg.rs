@@ -0,0 +1,149 @@
+//! Conditional compilation of items and attributes
+//!
+//! `#[cfg(cond)]` keeps an item only when `cond` holds against the active
+//! cfg flags. `#[cfg_attr(cond, attr, ...)]` expands to the listed
+//! attributes when `cond` holds, and to nothing otherwise. Conditions are
+//! flag names (`release`), key-value pairs (`feature = "gpu"`), or
+//! `all(...)`, `any(...)` and `not(...)` of them.
+
+use super::{Ast, Attribute, ImplItem, Item};
+use crate::common::Span;
+
+/// Apply conditional compilation against `flags`: expand `cfg_attr`, then
+/// drop the items and impl methods whose `cfg` condition does not hold
+pub fn configure(ast: &mut Ast, flags: &[String]) {
+    expand_cfg_attrs(ast, flags);
+    strip_cfg_items(ast, flags);
+}
+
+/// Remove the items and impl methods disabled by `#[cfg(...)]`
+pub fn strip_cfg_items(ast: &mut Ast, flags: &[String]) {
+    ast.items
+        .retain(|item| is_enabled(item.attributes(), flags));
+    for item in &mut ast.items {
+        if let Item::Impl(i) = item {
+            i.items.retain(|impl_item| match impl_item {
+                ImplItem::Fn(f) => is_enabled(&f.attributes, flags),
+                _ => true,
+            });
+        }
+    }
+}
+
+/// Whether every `cfg` attribute in `attributes` holds
+fn is_enabled(attributes: &[Attribute], flags: &[String]) -> bool {
+    attributes
+        .iter()
+        .filter(|a| a.is("cfg"))
+        .all(|a| a.args.iter().all(|cond| eval_cfg(cond, flags)))
+}
+
+/// Expand every `#[cfg_attr(...)]` in the AST against `flags`
+pub fn expand_cfg_attrs(ast: &mut Ast, flags: &[String]) {
+    for item in &mut ast.items {
+        expand_attributes(item.attributes_mut(), flags);
+        if let Item::Impl(i) = item {
+            for impl_item in &mut i.items {
+                if let ImplItem::Fn(f) = impl_item {
+                    expand_attributes(&mut f.attributes, flags);
+                }
+            }
+        }
+    }
+}
+
+/// Replace each `cfg_attr` in `attributes` with its attributes if the
+/// condition holds, keeping the original order
+fn expand_attributes(attributes: &mut Vec<Attribute>, flags: &[String]) {
+    if !attributes.iter().any(|a| a.is("cfg_attr")) {
+        return;
+    }
+    *attributes = std::mem::take(attributes)
+        .into_iter()
+        .flat_map(|attr| {
+            if !attr.is("cfg_attr") {
+                return vec![attr];
+            }
+            match attr.args.split_first() {
+                Some((cond, inner)) if eval_cfg(cond, flags) => inner
+                    .iter()
+                    .filter_map(|a| parse_attribute(a, attr.span))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        })
+        .collect();
+}
+
+/// Evaluate a cfg condition against the active flags
+pub fn eval_cfg(cond: &str, flags: &[String]) -> bool {
+    let cond = cond.trim();
+    let combinator = |name: &str| {
+        cond.strip_prefix(name)
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix('('))
+            .and_then(|rest| rest.strip_suffix(')'))
+    };
+
+    if let Some(args) = combinator("all") {
+        split_top_level(args).iter().all(|c| eval_cfg(c, flags))
+    } else if let Some(args) = combinator("any") {
+        split_top_level(args).iter().any(|c| eval_cfg(c, flags))
+    } else if let Some(arg) = combinator("not") {
+        !eval_cfg(arg, flags)
+    } else {
+        let cond = normalize(cond);
+        flags.iter().any(|flag| normalize(flag) == cond)
+    }
+}
+
+/// Parse attribute text such as `inline(always)` into an `Attribute`,
+/// located at the `cfg_attr` it came from
+fn parse_attribute(text: &str, span: Span) -> Option<Attribute> {
+    let text = text.trim();
+    let (name, args) = match text.find('(') {
+        Some(open) => {
+            let args = text[open + 1..].strip_suffix(')')?;
+            (&text[..open], split_top_level(args))
+        }
+        None => (text, Vec::new()),
+    };
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(Attribute {
+        name: name.to_string(),
+        args,
+        span,
+    })
+}
+
+/// Split `text` at commas that are not nested inside parentheses
+fn split_top_level(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut current = String::new();
+    for c in text.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(std::mem::take(&mut current).trim().to_string());
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    let last = current.trim();
+    if !last.is_empty() {
+        parts.push(last.to_string());
+    }
+    parts
+}
+
+/// Drop whitespace so `feature = "gpu"` and `feature="gpu"` compare equal
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
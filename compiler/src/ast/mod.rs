@@ -2,14 +2,32 @@
 //!
 //! This module defines the AST types produced by the parser.
 
+pub mod cfg;
+
 use crate::common::{NodeId, Span};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Top-level AST
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Ast {
     pub module_name: Option<Path>,
     pub items: Vec<Item>,
+    /// Text of the module's `//!` doc comments
+    pub doc: Option<String>,
+    /// Source spans of expression and binding pattern nodes
+    #[serde(skip)]
+    pub node_spans: HashMap<NodeId, Span>,
+}
+
+impl Ast {
+    /// Span of an expression node, or a dummy span if none was recorded
+    pub fn span_of(&self, id: NodeId) -> Span {
+        self.node_spans
+            .get(&id)
+            .copied()
+            .unwrap_or_else(Span::dummy)
+    }
 }
 
 /// Item visibility
@@ -43,6 +61,27 @@ pub struct FnModifiers {
     pub is_kernel: bool,
 }
 
+/// Attribute on an item: `#[name]` or `#[name(arg, ...)]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attribute {
+    pub name: String,
+    /// Arguments as written, split at top-level commas
+    pub args: Vec<String>,
+    /// Span of the whole `#[...]`
+    pub span: Span,
+}
+
+impl Attribute {
+    pub fn is(&self, name: &str) -> bool {
+        self.name == name
+    }
+
+    /// Whether this is a `#[derive(...)]` listing `trait_name`
+    pub fn derives(&self, trait_name: &str) -> bool {
+        self.is("derive") && self.args.iter().any(|a| a == trait_name)
+    }
+}
+
 /// Top-level item
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Item {
@@ -54,17 +93,56 @@ pub enum Item {
     TypeAlias(TypeAliasDef),
     Effect(EffectDef),
     Handler(HandlerDef),
+    UnitDef(UnitDef),
     Import(ImportDef),
     Extern(ExternBlock),
     Global(GlobalDef),
 }
 
+impl Item {
+    /// Attributes written before the item
+    pub fn attributes(&self) -> &[Attribute] {
+        match self {
+            Item::Function(f) => &f.attributes,
+            Item::Struct(s) => &s.attributes,
+            Item::Enum(e) => &e.attributes,
+            Item::Trait(t) => &t.attributes,
+            Item::Impl(i) => &i.attributes,
+            Item::TypeAlias(t) => &t.attributes,
+            Item::Effect(e) => &e.attributes,
+            Item::Handler(h) => &h.attributes,
+            Item::UnitDef(u) => &u.attributes,
+            Item::Import(i) => &i.attributes,
+            Item::Extern(e) => &e.attributes,
+            Item::Global(g) => &g.attributes,
+        }
+    }
+
+    pub fn attributes_mut(&mut self) -> &mut Vec<Attribute> {
+        match self {
+            Item::Function(f) => &mut f.attributes,
+            Item::Struct(s) => &mut s.attributes,
+            Item::Enum(e) => &mut e.attributes,
+            Item::Trait(t) => &mut t.attributes,
+            Item::Impl(i) => &mut i.attributes,
+            Item::TypeAlias(t) => &mut t.attributes,
+            Item::Effect(e) => &mut e.attributes,
+            Item::Handler(h) => &mut h.attributes,
+            Item::UnitDef(u) => &mut u.attributes,
+            Item::Import(i) => &mut i.attributes,
+            Item::Extern(e) => &mut e.attributes,
+            Item::Global(g) => &mut g.attributes,
+        }
+    }
+}
+
 // ==================== FUNCTIONS ====================
 
 /// Function definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FnDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub modifiers: FnModifiers,
     pub name: String,
@@ -75,6 +153,8 @@ pub struct FnDef {
     pub where_clause: Vec<WherePredicate>,
     pub body: Block,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
 }
 
 /// Function parameter
@@ -92,13 +172,54 @@ pub struct Param {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StructDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub modifiers: TypeModifiers,
     pub name: String,
     pub generics: Generics,
     pub where_clause: Vec<WherePredicate>,
+    pub kind: StructKind,
+    /// Named fields; empty for a tuple struct
     pub fields: Vec<FieldDef>,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
+}
+
+impl StructDef {
+    /// Whether this is a tuple struct such as `struct Meters(f64)`
+    pub fn is_tuple(&self) -> bool {
+        matches!(self.kind, StructKind::Tuple(_))
+    }
+
+    /// Field names and types in declaration order. Tuple struct fields are
+    /// named by position: `0`, `1`, ...
+    pub fn field_types(&self) -> Vec<(String, &TypeExpr)> {
+        match &self.kind {
+            StructKind::Named => self
+                .fields
+                .iter()
+                .map(|f| (f.name.clone(), &f.ty))
+                .collect(),
+            StructKind::Tuple(types) => types
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| (i.to_string(), ty))
+                .collect(),
+            StructKind::Unit => Vec::new(),
+        }
+    }
+}
+
+/// Shape of a struct definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StructKind {
+    /// `struct Point { x: f64, y: f64 }`
+    Named,
+    /// `struct Meters(f64)`, with fields accessed as `.0`, `.1`, ...
+    Tuple(Vec<TypeExpr>),
+    /// `struct Meters;`, a zero-sized marker with no fields
+    Unit,
 }
 
 /// Field definition
@@ -116,6 +237,7 @@ pub struct FieldDef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnumDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub modifiers: TypeModifiers,
     pub name: String,
@@ -123,6 +245,8 @@ pub struct EnumDef {
     pub where_clause: Vec<WherePredicate>,
     pub variants: Vec<VariantDef>,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
 }
 
 /// Enum variant definition
@@ -131,6 +255,8 @@ pub struct VariantDef {
     pub id: NodeId,
     pub name: String,
     pub data: VariantData,
+    /// Explicit discriminant of a unit variant: `A = 1`
+    pub discriminant: Option<Expr>,
 }
 
 /// Variant data representation
@@ -147,6 +273,7 @@ pub enum VariantData {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TraitDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub name: String,
     pub generics: Generics,
@@ -154,6 +281,8 @@ pub struct TraitDef {
     pub where_clause: Vec<WherePredicate>,
     pub items: Vec<TraitItem>,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
 }
 
 /// Trait item
@@ -174,6 +303,8 @@ pub struct TraitFnDef {
     pub effects: Vec<EffectRef>,
     pub where_clause: Vec<WherePredicate>,
     pub default_body: Option<Block>,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
 }
 
 /// Trait associated type definition
@@ -191,6 +322,7 @@ pub struct TraitTypeDef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImplDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub generics: Generics,
     pub trait_ref: Option<Path>,
     pub target_type: TypeExpr,
@@ -220,11 +352,29 @@ pub struct ImplTypeDef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeAliasDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub name: String,
     pub generics: Generics,
     pub ty: TypeExpr,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
+}
+
+/// Unit of measure declaration: `unit Bq;` declares a base unit and
+/// `unit Gy = J/kg;` one derived from other units
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitDef {
+    pub id: NodeId,
+    pub attributes: Vec<Attribute>,
+    pub visibility: Visibility,
+    pub name: String,
+    /// Unit expression of a derived unit, in the form written after `@`
+    pub definition: Option<String>,
+    pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
 }
 
 // ==================== EFFECTS ====================
@@ -233,11 +383,28 @@ pub struct TypeAliasDef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EffectDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub name: String,
     pub generics: Generics,
     pub operations: Vec<EffectOpDef>,
+    /// Member effects of an alias such as `effect IO = Alloc + Net;`
+    pub members: Vec<EffectRef>,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
+}
+
+impl EffectDef {
+    /// Whether this defines an effect alias rather than operations
+    pub fn is_alias(&self) -> bool {
+        !self.members.is_empty()
+    }
+
+    /// Whether `#[transparent]` asks for the alias to be shown expanded
+    pub fn is_transparent(&self) -> bool {
+        self.attributes.iter().any(|a| a.is("transparent"))
+    }
 }
 
 /// Effect operation definition
@@ -261,37 +428,131 @@ pub struct EffectRef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandlerDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub name: String,
     pub generics: Generics,
     pub effect: Path,
+    /// Type arguments of a generic effect, as in `for Yield<i64>`
+    pub effect_args: Vec<TypeExpr>,
     pub cases: Vec<HandlerCase>,
+    /// `return x => ...` clause applied to the handled block's result
+    pub return_clause: Option<HandlerReturn>,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
 }
 
-/// Handler case
+/// Handler case; the body may call `resume(v)` to continue the
+/// computation with `v` as the operation's result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandlerCase {
     pub id: NodeId,
     pub name: String,
+    /// Parameter types may be omitted and are then taken from the operation
     pub params: Vec<Param>,
     pub body: Expr,
 }
 
+/// Handler return clause
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandlerReturn {
+    pub id: NodeId,
+    pub param: String,
+    pub body: Expr,
+}
+
 // ==================== IMPORTS & EXTERN ====================
 
 /// Import definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportDef {
     pub id: NodeId,
-    pub path: Path,
+    pub attributes: Vec<Attribute>,
+    pub tree: ImportTree,
     pub span: Span,
 }
 
+/// What an import brings into scope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ImportTree {
+    /// A single path, optionally renamed: `a::b` or `a::b as c`
+    Path { path: Path, alias: Option<String> },
+    /// Every name under a path: `a::*`
+    Glob(Path),
+    /// Several imports sharing a prefix: `a::{b, c::d}`
+    Group {
+        prefix: Path,
+        items: Vec<ImportTree>,
+    },
+}
+
+impl ImportTree {
+    /// The single and glob imports in this tree, with each group's prefix
+    /// joined onto the paths inside it
+    pub fn flatten(&self) -> Vec<ImportTree> {
+        match self {
+            ImportTree::Path { .. } | ImportTree::Glob(_) => vec![self.clone()],
+            ImportTree::Group { prefix, items } => items
+                .iter()
+                .flat_map(|item| item.flatten())
+                .map(|item| item.with_prefix(prefix))
+                .collect(),
+        }
+    }
+
+    fn with_prefix(self, prefix: &Path) -> ImportTree {
+        let join = |path: Path| Path {
+            segments: prefix
+                .segments
+                .iter()
+                .cloned()
+                .chain(path.segments)
+                .collect(),
+            segment_args: Vec::new(),
+        };
+        match self {
+            ImportTree::Path { path, alias } => ImportTree::Path {
+                path: join(path),
+                alias,
+            },
+            ImportTree::Glob(path) => ImportTree::Glob(join(path)),
+            ImportTree::Group { prefix, items } => ImportTree::Group {
+                prefix: join(prefix),
+                items,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ImportTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportTree::Path { path, alias: None } => write!(f, "{}", path),
+            ImportTree::Path {
+                path,
+                alias: Some(alias),
+            } => write!(f, "{} as {}", path, alias),
+            ImportTree::Glob(path) => write!(f, "{}::*", path),
+            ImportTree::Group { prefix, items } => {
+                write!(f, "{}::{{", prefix)?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
 /// Extern block
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExternBlock {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub abi: String,
     pub items: Vec<ExternFn>,
     pub span: Span,
@@ -304,6 +565,8 @@ pub struct ExternFn {
     pub name: String,
     pub params: Vec<Param>,
     pub return_type: Option<TypeExpr>,
+    /// Declared with a trailing `...`, accepting extra C varargs
+    pub variadic: bool,
 }
 
 // ==================== GLOBALS ====================
@@ -312,6 +575,7 @@ pub struct ExternFn {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalDef {
     pub id: NodeId,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub is_const: bool,
     pub is_mut: bool,
@@ -319,6 +583,8 @@ pub struct GlobalDef {
     pub ty: Option<TypeExpr>,
     pub value: Expr,
     pub span: Span,
+    /// Text of the `///` doc comments preceding the item
+    pub doc: Option<String>,
 }
 
 // ==================== GENERICS ====================
@@ -341,6 +607,10 @@ pub enum GenericParam {
         name: String,
         ty: TypeExpr,
     },
+    /// Lifetime parameter, named with its quote: `'a`
+    Lifetime {
+        name: String,
+    },
 }
 
 /// Where predicate
@@ -348,6 +618,8 @@ pub enum GenericParam {
 pub struct WherePredicate {
     pub ty: TypeExpr,
     pub bounds: Vec<Path>,
+    /// Callable bound `F: Fn(A) -> R with E`, as a function type
+    pub signature: Option<TypeExpr>,
 }
 
 // ==================== TYPES ====================
@@ -365,8 +637,12 @@ pub enum TypeExpr {
         args: Vec<TypeExpr>,
         unit: Option<String>,
     },
-    /// Reference type: &T or &mut T
-    Reference { mutable: bool, inner: Box<TypeExpr> },
+    /// Reference type: &T or &mut T, with an optional lifetime as in &'a T
+    Reference {
+        mutable: bool,
+        lifetime: Option<String>,
+        inner: Box<TypeExpr>,
+    },
     /// Array type: [T] or [T; N]
     Array {
         element: Box<TypeExpr>,
@@ -374,7 +650,9 @@ pub enum TypeExpr {
     },
     /// Tuple type: (T1, T2, ...)
     Tuple(Vec<TypeExpr>),
-    /// Function type: Fn(A) -> B
+    /// Trait object type: `dyn Trait`
+    Dyn { path: Path, span: Span },
+    /// Function type: `fn(A) -> B with E` or `A -> B`
     Function {
         params: Vec<TypeExpr>,
         return_type: Box<TypeExpr>,
@@ -382,6 +660,9 @@ pub enum TypeExpr {
     },
     /// Infer type: _
     Infer,
+    /// Const generic argument: the `3` in `zeros::<3>()`, or a braced
+    /// constant expression `{ LEN * 2 }`
+    Const(Box<Expr>),
 }
 
 // ==================== EXPRESSIONS ====================
@@ -410,6 +691,8 @@ pub enum Expr {
     Call {
         id: NodeId,
         callee: Box<Expr>,
+        /// Explicit generic arguments given with a turbofish: `f::<T>(...)`
+        type_args: Vec<TypeExpr>,
         args: Vec<Expr>,
     },
     /// Method call
@@ -473,6 +756,13 @@ pub enum Expr {
         iter: Box<Expr>,
         body: Block,
     },
+    /// Integer range `start..end` or `start..=end`, the iterable of a `for` loop
+    Range {
+        id: NodeId,
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
     /// Return expression
     Return {
         id: NodeId,
@@ -496,6 +786,12 @@ pub enum Expr {
     Tuple { id: NodeId, elements: Vec<Expr> },
     /// Array expression
     Array { id: NodeId, elements: Vec<Expr> },
+    /// Array repeat expression: [value; count]
+    ArrayRepeat {
+        id: NodeId,
+        value: Box<Expr>,
+        count: Box<Expr>,
+    },
     /// Struct literal
     StructLit {
         id: NodeId,
@@ -523,12 +819,53 @@ pub enum Expr {
     Await { id: NodeId, expr: Box<Expr> },
 }
 
+impl Expr {
+    /// The node ID of this expression
+    pub fn id(&self) -> NodeId {
+        match self {
+            Expr::Literal { id, .. }
+            | Expr::Path { id, .. }
+            | Expr::Binary { id, .. }
+            | Expr::Unary { id, .. }
+            | Expr::Call { id, .. }
+            | Expr::MethodCall { id, .. }
+            | Expr::Field { id, .. }
+            | Expr::TupleField { id, .. }
+            | Expr::Index { id, .. }
+            | Expr::Cast { id, .. }
+            | Expr::Block { id, .. }
+            | Expr::If { id, .. }
+            | Expr::Match { id, .. }
+            | Expr::Loop { id, .. }
+            | Expr::While { id, .. }
+            | Expr::For { id, .. }
+            | Expr::Range { id, .. }
+            | Expr::Return { id, .. }
+            | Expr::Break { id, .. }
+            | Expr::Continue { id, .. }
+            | Expr::Closure { id, .. }
+            | Expr::Tuple { id, .. }
+            | Expr::Array { id, .. }
+            | Expr::ArrayRepeat { id, .. }
+            | Expr::StructLit { id, .. }
+            | Expr::Try { id, .. }
+            | Expr::Perform { id, .. }
+            | Expr::Handle { id, .. }
+            | Expr::Sample { id, .. }
+            | Expr::Await { id, .. } => *id,
+        }
+    }
+}
+
 /// Literal values
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Literal {
     Unit,
     Bool(bool),
     Int(i64),
+    /// Integer above `i64::MAX`, valid only where an unsigned type wide
+    /// enough to hold it is expected
+    UInt(u128),
     Float(f64),
     Char(char),
     String(String),
@@ -595,6 +932,14 @@ pub enum Stmt {
         ty: Option<TypeExpr>,
         value: Option<Expr>,
     },
+    /// Refutable binding `let pattern = value else { diverge };`
+    LetElse {
+        is_mut: bool,
+        pattern: Pattern,
+        ty: Option<TypeExpr>,
+        value: Expr,
+        else_block: Block,
+    },
     /// Expression statement
     Expr { expr: Expr, has_semi: bool },
     /// Assignment
@@ -639,7 +984,19 @@ pub enum Pattern {
     /// Literal pattern
     Literal(Literal),
     /// Variable binding
-    Binding { name: String, mutable: bool },
+    Binding {
+        id: NodeId,
+        name: String,
+        mutable: bool,
+    },
+    /// Binding with a subpattern: name @ p, binding the whole value
+    /// matched by `p`
+    At {
+        id: NodeId,
+        name: String,
+        mutable: bool,
+        pattern: Box<Pattern>,
+    },
     /// Tuple pattern: (p1, p2, ...)
     Tuple(Vec<Pattern>),
     /// Struct pattern: S { field: pattern, ... }
@@ -654,6 +1011,12 @@ pub enum Pattern {
     },
     /// Or pattern: p1 | p2
     Or(Vec<Pattern>),
+    /// Range pattern: lo..hi or lo..=hi
+    Range {
+        start: Literal,
+        end: Literal,
+        inclusive: bool,
+    },
 }
 
 // ==================== PATHS ====================
@@ -662,15 +1025,27 @@ pub enum Pattern {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Path {
     pub segments: Vec<String>,
+    /// Turbofish arguments given on a segment before the last, by segment
+    /// index: the `<i64>` of `Vec::<i64>::new`
+    pub segment_args: Vec<(usize, Vec<TypeExpr>)>,
 }
 
 impl Path {
     pub fn simple(name: &str) -> Self {
         Path {
             segments: vec![name.to_string()],
+            segment_args: Vec::new(),
         }
     }
 
+    /// Turbofish arguments given on segment `index`
+    pub fn args_of(&self, index: usize) -> &[TypeExpr] {
+        self.segment_args
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map_or(&[], |(_, args)| args)
+    }
+
     pub fn is_simple(&self) -> bool {
         self.segments.len() == 1
     }
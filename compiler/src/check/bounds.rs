@@ -0,0 +1,164 @@
+//! Bounds-check elimination
+//!
+//! While checking a function body the checker records integer facts about
+//! locals whose value cannot change: the range of a `for` loop variable,
+//! the condition guarding an `if` branch and the length of fixed-size
+//! arrays. Indexing whose `0 <= i < len(arr)` obligation follows from those
+//! facts is marked in bounds, and HLIR omits its runtime check.
+
+use crate::ast::{BinaryOp, Expr, Literal};
+use crate::hir::HirType;
+use crate::types::refinement::{ArithOp, Predicate, array};
+use crate::types::{ArrayLen, Type};
+
+use super::TypeChecker;
+
+impl TypeChecker {
+    /// Refinement variable standing for the local `name`. Variables are
+    /// qualified with their declaration offset so that shadowed bindings
+    /// stay distinct. Mutable locals have none unless `any_binding` is set.
+    fn fact_var(&self, name: &str, any_binding: bool) -> Option<String> {
+        let binding = self.env.lookup(name)?;
+        let span = binding.span?;
+        (any_binding || !binding.mutable).then(|| format!("{}@{}", name, span.start))
+    }
+
+    /// Translate an integer expression over immutable locals into a
+    /// refinement term
+    pub(super) fn refinement_term(&self, expr: &Expr) -> Option<Predicate> {
+        match expr {
+            Expr::Literal {
+                value: Literal::Int(n),
+                ..
+            } => Some(Predicate::Int(*n)),
+            Expr::Path { path, .. } if path.is_simple() => {
+                self.fact_var(&path.segments[0], false).map(Predicate::Var)
+            }
+            Expr::Binary {
+                op: op @ (BinaryOp::Add | BinaryOp::Sub),
+                left,
+                right,
+                ..
+            } => {
+                let op = if *op == BinaryOp::Add {
+                    ArithOp::Add
+                } else {
+                    ArithOp::Sub
+                };
+                Some(Predicate::Arith(
+                    op,
+                    Box::new(self.refinement_term(left)?),
+                    Box::new(self.refinement_term(right)?),
+                ))
+            }
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+                ..
+            } if method == "len" && args.is_empty() => {
+                let arr = self.array_var(receiver)?;
+                Some(len(&arr))
+            }
+            _ => None,
+        }
+    }
+
+    /// Translate a condition into a refinement predicate
+    pub(super) fn refinement_fact(&self, expr: &Expr) -> Option<Predicate> {
+        let Expr::Binary {
+            op, left, right, ..
+        } = expr
+        else {
+            return None;
+        };
+        if *op == BinaryOp::And {
+            return match (self.refinement_fact(left), self.refinement_fact(right)) {
+                (Some(left), Some(right)) => Some(Predicate::and(left, right)),
+                (fact, None) | (None, fact) => fact,
+            };
+        }
+        let compare = match op {
+            BinaryOp::Eq => Predicate::eq,
+            BinaryOp::Lt => Predicate::lt,
+            BinaryOp::Le => Predicate::le,
+            BinaryOp::Gt => Predicate::gt,
+            BinaryOp::Ge => Predicate::ge,
+            _ => return None,
+        };
+        Some(compare(
+            self.refinement_term(left)?,
+            self.refinement_term(right)?,
+        ))
+    }
+
+    /// Assume that the loop variable `name` lies in `start..end` (or
+    /// `start..=end`). The bounds are translated before the variable is
+    /// bound, as it may shadow them.
+    pub(super) fn assume_in_range(
+        &mut self,
+        name: &str,
+        start: Option<Predicate>,
+        end: Option<Predicate>,
+        inclusive: bool,
+    ) {
+        let Some(var) = self.fact_var(name, false).map(Predicate::Var) else {
+            return;
+        };
+        if let Some(start) = start {
+            self.refinements.assume(Predicate::le(start, var.clone()));
+        }
+        if let Some(end) = end {
+            self.refinements.assume(if inclusive {
+                Predicate::le(var, end)
+            } else {
+                Predicate::lt(var, end)
+            });
+        }
+    }
+
+    /// Whether indexing `base` (of type `base_ty`) with `index` is provably
+    /// within bounds under the facts in scope
+    pub(super) fn index_in_bounds(&mut self, base: &Expr, base_ty: &HirType, index: &Expr) -> bool {
+        let (Some(arr), Some(idx)) = (self.array_var(base), self.refinement_term(index)) else {
+            return false;
+        };
+
+        let depth = self.refinements.path_depth();
+        if let HirType::Array {
+            size: Some(ArrayLen::Fixed(size)),
+            ..
+        } = base_ty
+        {
+            self.refinements
+                .assume(Predicate::eq(len(&arr), Predicate::Int(*size as i64)));
+        }
+        let goal = array::valid_index(&arr, INDEX_VAR).substitute(INDEX_VAR, &idx);
+        let valid = self.refinements.check(&goal).is_valid();
+        self.refinements.restore_path(depth);
+        valid
+    }
+
+    /// Refinement variable of an array local. The length of a fixed-size
+    /// array cannot change, so those qualify even when mutable.
+    fn array_var(&self, expr: &Expr) -> Option<String> {
+        let Expr::Path { path, .. } = expr else {
+            return None;
+        };
+        if !path.is_simple() {
+            return None;
+        }
+        let name = &path.segments[0];
+        match &self.env.lookup(name)?.ty {
+            Type::Array { size, .. } => self.fact_var(name, size.is_some()),
+            _ => None,
+        }
+    }
+}
+
+/// Placeholder for the index in the `valid_index` obligation
+const INDEX_VAR: &str = "?index";
+
+fn len(arr: &str) -> Predicate {
+    Predicate::App("len".to_string(), vec![Predicate::var(arr)])
+}
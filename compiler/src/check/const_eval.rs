@@ -0,0 +1,256 @@
+//! Compile-time evaluation of constant expressions
+//!
+//! Used by every const context: `const` globals, array lengths and enum
+//! discriminants. Failures
+//! carry the span of the offending sub-expression, so `1 + 1 / 0` points at
+//! the division rather than the whole initializer.
+
+use std::collections::HashMap;
+
+use crate::ast::{BinaryOp, Expr, Literal, UnaryOp};
+use crate::common::{NodeId, Span};
+use crate::diagnostics::{CompileError, SourceFile};
+
+/// Value of an evaluated constant expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl ConstValue {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            ConstValue::Int(_) => "integer",
+            ConstValue::Float(_) => "float",
+            ConstValue::Bool(_) => "bool",
+        }
+    }
+}
+
+/// A constant expression that could not be evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstEvalError {
+    pub message: String,
+    /// Span of the sub-expression that failed
+    pub span: Span,
+}
+
+impl ConstEvalError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Attach source code to produce a reportable diagnostic
+    pub fn to_diagnostic(&self, source: &SourceFile) -> CompileError {
+        CompileError::ConstEval {
+            message: self.message.clone(),
+            span: self.span.into(),
+            src: source.to_named_source(),
+        }
+    }
+}
+
+/// Evaluates constant expressions against the constants defined so far
+pub struct ConstEvaluator<'a> {
+    spans: &'a HashMap<NodeId, Span>,
+    consts: &'a HashMap<String, ConstValue>,
+}
+
+impl<'a> ConstEvaluator<'a> {
+    pub fn new(spans: &'a HashMap<NodeId, Span>, consts: &'a HashMap<String, ConstValue>) -> Self {
+        Self { spans, consts }
+    }
+
+    pub fn eval(&self, expr: &Expr) -> Result<ConstValue, ConstEvalError> {
+        let span = self.span_of(expr);
+        match expr {
+            Expr::Literal { value, .. } => match value {
+                Literal::Int(n) | Literal::IntUnit(n, _) => Ok(ConstValue::Int(*n)),
+                Literal::Float(f) | Literal::FloatUnit(f, _) => Ok(ConstValue::Float(*f)),
+                Literal::Bool(b) => Ok(ConstValue::Bool(*b)),
+                Literal::UInt(_) => Err(ConstEvalError::new(
+                    "integer literal too large for i64",
+                    span,
+                )),
+                _ => Err(ConstEvalError::new(
+                    "literal is not supported in constant expressions",
+                    span,
+                )),
+            },
+            Expr::Path { path, .. } => {
+                let name = path.segments.join("::");
+                self.consts.get(&name).copied().ok_or_else(|| {
+                    ConstEvalError::new(format!("`{}` is not a constant", name), span)
+                })
+            }
+            Expr::Unary {
+                op, expr: inner, ..
+            } => {
+                let value = self.eval(inner)?;
+                match (op, value) {
+                    (UnaryOp::Neg, ConstValue::Int(n)) => {
+                        n.checked_neg().map(ConstValue::Int).ok_or_else(|| {
+                            ConstEvalError::new(format!("overflow evaluating `-{}`", n), span)
+                        })
+                    }
+                    (UnaryOp::Neg, ConstValue::Float(f)) => Ok(ConstValue::Float(-f)),
+                    (UnaryOp::Not, ConstValue::Bool(b)) => Ok(ConstValue::Bool(!b)),
+                    (UnaryOp::Not, ConstValue::Int(n)) => Ok(ConstValue::Int(!n)),
+                    (UnaryOp::Neg | UnaryOp::Not, _) => Err(ConstEvalError::new(
+                        format!(
+                            "operator `{}` cannot be applied to a {} constant",
+                            if *op == UnaryOp::Neg { "-" } else { "!" },
+                            value.kind()
+                        ),
+                        span,
+                    )),
+                    _ => Err(ConstEvalError::new(
+                        "references cannot be taken in constant expressions",
+                        span,
+                    )),
+                }
+            }
+            Expr::Binary {
+                op, left, right, ..
+            } => {
+                let lhs = self.eval(left)?;
+                let rhs = self.eval(right)?;
+                self.eval_binary(*op, lhs, rhs, span)
+            }
+            Expr::Call { .. } | Expr::MethodCall { .. } => Err(ConstEvalError::new(
+                "function calls are not allowed in constant expressions",
+                span,
+            )),
+            _ => Err(ConstEvalError::new(
+                "expression cannot be evaluated at compile time",
+                span,
+            )),
+        }
+    }
+
+    fn eval_binary(
+        &self,
+        op: BinaryOp,
+        lhs: ConstValue,
+        rhs: ConstValue,
+        span: Span,
+    ) -> Result<ConstValue, ConstEvalError> {
+        use ConstValue::*;
+        match (lhs, rhs) {
+            (Int(a), Int(b)) => {
+                let result = match op {
+                    BinaryOp::Add => a.checked_add(b),
+                    BinaryOp::Sub => a.checked_sub(b),
+                    BinaryOp::Mul => a.checked_mul(b),
+                    BinaryOp::Div | BinaryOp::Rem if b == 0 => {
+                        let what = if op == BinaryOp::Div {
+                            "division"
+                        } else {
+                            "remainder"
+                        };
+                        return Err(ConstEvalError::new(
+                            format!("{} by zero in constant expression", what),
+                            span,
+                        ));
+                    }
+                    BinaryOp::Div => a.checked_div(b),
+                    BinaryOp::Rem => a.checked_rem(b),
+                    BinaryOp::BitAnd => Some(a & b),
+                    BinaryOp::BitOr => Some(a | b),
+                    BinaryOp::BitXor => Some(a ^ b),
+                    BinaryOp::Shl => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+                    BinaryOp::Shr => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+                    BinaryOp::Eq => return Ok(Bool(a == b)),
+                    BinaryOp::Ne => return Ok(Bool(a != b)),
+                    BinaryOp::Lt => return Ok(Bool(a < b)),
+                    BinaryOp::Le => return Ok(Bool(a <= b)),
+                    BinaryOp::Gt => return Ok(Bool(a > b)),
+                    BinaryOp::Ge => return Ok(Bool(a >= b)),
+                    BinaryOp::And | BinaryOp::Or => return Err(self.unsupported(op, lhs, span)),
+                };
+                result.map(Int).ok_or_else(|| {
+                    ConstEvalError::new(
+                        format!("overflow evaluating `{} {} {}`", a, op_symbol(op), b),
+                        span,
+                    )
+                })
+            }
+            (Float(a), Float(b)) => match op {
+                BinaryOp::Add => Ok(Float(a + b)),
+                BinaryOp::Sub => Ok(Float(a - b)),
+                BinaryOp::Mul => Ok(Float(a * b)),
+                BinaryOp::Div => Ok(Float(a / b)),
+                BinaryOp::Rem => Ok(Float(a % b)),
+                BinaryOp::Eq => Ok(Bool(a == b)),
+                BinaryOp::Ne => Ok(Bool(a != b)),
+                BinaryOp::Lt => Ok(Bool(a < b)),
+                BinaryOp::Le => Ok(Bool(a <= b)),
+                BinaryOp::Gt => Ok(Bool(a > b)),
+                BinaryOp::Ge => Ok(Bool(a >= b)),
+                _ => Err(self.unsupported(op, lhs, span)),
+            },
+            (Bool(a), Bool(b)) => match op {
+                BinaryOp::And => Ok(Bool(a && b)),
+                BinaryOp::Or => Ok(Bool(a || b)),
+                BinaryOp::Eq => Ok(Bool(a == b)),
+                BinaryOp::Ne => Ok(Bool(a != b)),
+                _ => Err(self.unsupported(op, lhs, span)),
+            },
+            _ => Err(ConstEvalError::new(
+                format!(
+                    "mismatched operands for `{}`: {} and {}",
+                    op_symbol(op),
+                    lhs.kind(),
+                    rhs.kind()
+                ),
+                span,
+            )),
+        }
+    }
+
+    fn unsupported(&self, op: BinaryOp, value: ConstValue, span: Span) -> ConstEvalError {
+        ConstEvalError::new(
+            format!(
+                "operator `{}` cannot be applied to {} constants",
+                op_symbol(op),
+                value.kind()
+            ),
+            span,
+        )
+    }
+
+    fn span_of(&self, expr: &Expr) -> Span {
+        self.spans
+            .get(&expr.id())
+            .copied()
+            .unwrap_or_else(Span::dummy)
+    }
+}
+
+pub(super) fn op_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Rem => "%",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitOr => "|",
+        BinaryOp::BitXor => "^",
+        BinaryOp::Shl => "<<",
+        BinaryOp::Shr => ">>",
+    }
+}
@@ -8,7 +8,8 @@ use crate::hlir::HlirModule;
 
 #[cfg(feature = "jit")]
 use crate::hlir::{
-    BinaryOp, BlockId, HlirConstant, HlirFunction, HlirTerminator, HlirType, Op, UnaryOp, ValueId,
+    BinaryOp, BlockId, HlirBlock, HlirConstant, HlirFunction, HlirGlobal, HlirTerminator, HlirType,
+    HlirTypeDef, HlirTypeDefKind, Layout, Layouts, Op, UnaryOp, ValueId,
 };
 use std::collections::HashMap;
 
@@ -16,18 +17,22 @@ use std::collections::HashMap;
 use cranelift_codegen::Context;
 #[cfg(feature = "jit")]
 use cranelift_codegen::ir::{
-    AbiParam, Function, InstBuilder, MemFlags, Signature, UserFuncName, types,
+    AbiParam, FuncRef, GlobalValue, InstBuilder, MemFlags, Signature, UserFuncName, types,
 };
 #[cfg(feature = "jit")]
 use cranelift_codegen::isa::CallConv;
 #[cfg(feature = "jit")]
+use cranelift_codegen::isa::OwnedTargetIsa;
+#[cfg(feature = "jit")]
 use cranelift_codegen::settings::{self, Configurable};
 #[cfg(feature = "jit")]
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
 #[cfg(feature = "jit")]
 use cranelift_jit::{JITBuilder, JITModule};
 #[cfg(feature = "jit")]
-use cranelift_module::{DataDescription, Linkage, Module};
+use cranelift_module::{DataDescription, DataId, Linkage, Module};
+#[cfg(feature = "jit")]
+use cranelift_object::{ObjectBuilder, ObjectModule};
 
 /// Cranelift JIT compiler
 pub struct CraneliftJit {
@@ -69,6 +74,20 @@ impl CraneliftJit {
     pub fn compile(&self, _module: &HlirModule) -> Result<CompiledModule, String> {
         Err("JIT backend not enabled. Compile with --features jit".to_string())
     }
+
+    /// Compile the module ahead of time into a relocatable object file for
+    /// the host platform
+    #[cfg(feature = "jit")]
+    pub fn emit_object(&self, module: &HlirModule) -> Result<Vec<u8>, String> {
+        let mut compiler = JitCompiler::new_object(self.optimize)?;
+        compiler.compile_module(module)?;
+        compiler.emit()
+    }
+
+    #[cfg(not(feature = "jit"))]
+    pub fn emit_object(&self, _module: &HlirModule) -> Result<Vec<u8>, String> {
+        Err("Cranelift backend not enabled. Compile with --features jit".to_string())
+    }
 }
 
 impl Default for CraneliftJit {
@@ -172,45 +191,95 @@ impl JitSettings {
 // ==================== JIT Compiler Implementation ====================
 
 #[cfg(feature = "jit")]
-struct JitCompiler {
-    jit_module: JITModule,
+struct JitCompiler<M: Module> {
+    module: M,
     ctx: Context,
     func_ctx: FunctionBuilderContext,
     /// Map from HLIR function names to Cranelift function IDs
     func_ids: HashMap<String, cranelift_module::FuncId>,
+    /// Map from HLIR global names to Cranelift data objects
+    data_ids: HashMap<String, DataId>,
+}
+
+/// Build the ISA for the host machine
+#[cfg(feature = "jit")]
+fn host_isa(optimize: bool, pic: bool) -> Result<OwnedTargetIsa, String> {
+    let mut flag_builder = settings::builder();
+    flag_builder.set("use_colocated_libcalls", "false").unwrap();
+    flag_builder
+        .set("is_pic", if pic { "true" } else { "false" })
+        .unwrap();
+
+    if optimize {
+        flag_builder.set("opt_level", "speed").unwrap();
+    } else {
+        flag_builder.set("opt_level", "none").unwrap();
+    }
+
+    let isa_builder =
+        cranelift_native::builder().map_err(|e| format!("Failed to create ISA builder: {}", e))?;
+
+    isa_builder
+        .finish(settings::Flags::new(flag_builder))
+        .map_err(|e| format!("Failed to create ISA: {}", e))
 }
 
 #[cfg(feature = "jit")]
-impl JitCompiler {
+impl JitCompiler<JITModule> {
     fn new(optimize: bool) -> Result<Self, String> {
-        let mut flag_builder = settings::builder();
-        flag_builder.set("use_colocated_libcalls", "false").unwrap();
-        flag_builder.set("is_pic", "false").unwrap();
+        let isa = host_isa(optimize, false)?;
+        let jit_builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        Ok(Self::with_module(JITModule::new(jit_builder)))
+    }
 
-        if optimize {
-            flag_builder.set("opt_level", "speed").unwrap();
-        } else {
-            flag_builder.set("opt_level", "none").unwrap();
-        }
+    fn finalize(mut self) -> Result<CompiledModule, String> {
+        self.module
+            .finalize_definitions()
+            .map_err(|e| format!("Failed to finalize: {}", e))?;
 
-        let isa_builder = cranelift_native::builder()
-            .map_err(|e| format!("Failed to create ISA builder: {}", e))?;
+        let mut functions = HashMap::new();
+        for (name, func_id) in &self.func_ids {
+            let ptr = self.module.get_finalized_function(*func_id);
+            functions.insert(name.clone(), ptr);
+        }
 
-        let isa = isa_builder
-            .finish(settings::Flags::new(flag_builder))
-            .map_err(|e| format!("Failed to create ISA: {}", e))?;
+        Ok(CompiledModule {
+            jit_module: self.module,
+            functions,
+        })
+    }
+}
 
-        let jit_builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+#[cfg(feature = "jit")]
+impl JitCompiler<ObjectModule> {
+    /// Compiler that emits a relocatable object file for the host
+    fn new_object(optimize: bool) -> Result<Self, String> {
+        let isa = host_isa(optimize, true)?;
+        let builder = ObjectBuilder::new(isa, "main", cranelift_module::default_libcall_names())
+            .map_err(|e| format!("Failed to create object module: {}", e))?;
+        Ok(Self::with_module(ObjectModule::new(builder)))
+    }
 
-        let jit_module = JITModule::new(jit_builder);
-        let ctx = jit_module.make_context();
+    /// Serialize the compiled functions as an object file
+    fn emit(self) -> Result<Vec<u8>, String> {
+        self.module
+            .finish()
+            .emit()
+            .map_err(|e| format!("Failed to emit object file: {}", e))
+    }
+}
 
-        Ok(Self {
-            jit_module,
+#[cfg(feature = "jit")]
+impl<M: Module> JitCompiler<M> {
+    fn with_module(module: M) -> Self {
+        let ctx = module.make_context();
+        Self {
+            module,
             ctx,
             func_ctx: FunctionBuilderContext::new(),
             func_ids: HashMap::new(),
-        })
+            data_ids: HashMap::new(),
+        }
     }
 
     fn compile_module(&mut self, module: &HlirModule) -> Result<(), String> {
@@ -218,22 +287,81 @@ impl JitCompiler {
         for func in &module.functions {
             let sig = self.create_signature(func);
             let func_id = self
-                .jit_module
+                .module
                 .declare_function(&func.name, Linkage::Export, &sig)
                 .map_err(|e| format!("Failed to declare function {}: {}", func.name, e))?;
             self.func_ids.insert(func.name.clone(), func_id);
         }
 
+        // Globals may hold the addresses of the functions declared above,
+        // as vtables do
+        for global in &module.globals {
+            self.define_global(global)?;
+        }
+
         // Second pass: compile all functions
+        let layouts = struct_layouts(&module.types);
         for func in &module.functions {
-            self.compile_function(func)?;
+            self.compile_function(func, &layouts)?;
         }
 
         Ok(())
     }
 
+    /// Define `global` as a data object laid out from its initializer: a
+    /// scalar, or an array of scalars stored back to back
+    fn define_global(&mut self, global: &HlirGlobal) -> Result<(), String> {
+        let entries = match &global.init {
+            Some(HlirConstant::Array(entries)) => entries.as_slice(),
+            Some(init) => std::slice::from_ref(init),
+            None => &[],
+        };
+        let mut data = DataDescription::new();
+        let mut bytes = Vec::new();
+        for entry in entries {
+            match entry {
+                HlirConstant::FunctionRef(name) => {
+                    let func_id = self.func_ids.get(name).ok_or_else(|| {
+                        format!("Global {} refers to unknown function {}", global.name, name)
+                    })?;
+                    let func_ref = self.module.declare_func_in_data(*func_id, &mut data);
+                    data.write_function_addr(bytes.len() as u32, func_ref);
+                    bytes.extend(0u64.to_le_bytes());
+                }
+                HlirConstant::Null(_) | HlirConstant::Unit => bytes.extend(0u64.to_le_bytes()),
+                HlirConstant::Bool(b) => bytes.push(*b as u8),
+                HlirConstant::Int(n, ty) => {
+                    bytes.extend(&(*n as i128).to_le_bytes()[..ty.size_bits() / 8]);
+                }
+                HlirConstant::Float(f, HlirType::F32) => bytes.extend((*f as f32).to_le_bytes()),
+                HlirConstant::Float(f, _) => bytes.extend(f.to_le_bytes()),
+                other => {
+                    return Err(format!(
+                        "Unsupported initializer for global {}: {:?}",
+                        global.name, other
+                    ));
+                }
+            }
+        }
+        if bytes.is_empty() {
+            bytes.resize(global.ty.size_bits().div_ceil(8).max(1), 0);
+        }
+        data.define(bytes.into_boxed_slice());
+        data.set_align(8);
+
+        let data_id = self
+            .module
+            .declare_data(&global.name, Linkage::Local, !global.is_const, false)
+            .map_err(|e| format!("Failed to declare global {}: {}", global.name, e))?;
+        self.module
+            .define_data(data_id, &data)
+            .map_err(|e| format!("Failed to define global {}: {}", global.name, e))?;
+        self.data_ids.insert(global.name.clone(), data_id);
+        Ok(())
+    }
+
     fn create_signature(&self, func: &HlirFunction) -> Signature {
-        let call_conv = self.jit_module.isa().default_call_conv();
+        let call_conv = self.module.isa().default_call_conv();
         let mut sig = Signature::new(call_conv);
 
         for param in &func.params {
@@ -269,53 +397,126 @@ impl JitCompiler {
         }
     }
 
-    fn compile_function(&mut self, func: &HlirFunction) -> Result<(), String> {
+    fn compile_function(
+        &mut self,
+        func: &HlirFunction,
+        layouts: &HashMap<String, Layout>,
+    ) -> Result<(), String> {
         let func_id = self.func_ids[&func.name];
 
         // Create function signature
         self.ctx.func.signature = self.create_signature(func);
         self.ctx.func.name = UserFuncName::user(0, func_id.as_u32());
 
+        // Import every callee so direct calls and function references
+        // resolve to the callee's own FuncId and signature
+        let mut func_refs = HashMap::new();
+        for name in referenced_functions(func) {
+            if let Some(&callee) = self.func_ids.get(name) {
+                let func_ref = self.module.declare_func_in_func(callee, &mut self.ctx.func);
+                func_refs.insert(name.to_string(), func_ref);
+            }
+        }
+        let mut global_refs = HashMap::new();
+        for name in referenced_globals(func) {
+            if let Some(&data) = self.data_ids.get(name) {
+                let global = self.module.declare_data_in_func(data, &mut self.ctx.func);
+                global_refs.insert(name.to_string(), global);
+            }
+        }
+
         // Build function body
         {
-            let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.func_ctx);
-            let mut translator = FunctionTranslator::new(&mut builder, &self.func_ids, func);
+            let builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.func_ctx);
+            let mut translator =
+                FunctionTranslator::new(builder, func_refs, global_refs, func, layouts);
             translator.translate(func)?;
-            builder.finalize();
+            translator.builder.finalize();
         }
 
         // Compile the function
-        self.jit_module
+        self.module
             .define_function(func_id, &mut self.ctx)
             .map_err(|e| format!("Failed to define function {}: {}", func.name, e))?;
 
-        self.jit_module.clear_context(&mut self.ctx);
+        self.module.clear_context(&mut self.ctx);
 
         Ok(())
     }
+}
 
-    fn finalize(mut self) -> Result<CompiledModule, String> {
-        self.jit_module
-            .finalize_definitions()
-            .map_err(|e| format!("Failed to finalize: {}", e))?;
+/// Layouts of the module's structs as the JIT stores them. Aggregates and
+/// unit are held as pointer-sized values, so a nested struct occupies one
+/// pointer in its parent rather than being stored inline.
+#[cfg(feature = "jit")]
+fn struct_layouts(types: &[HlirTypeDef]) -> HashMap<String, Layout> {
+    let boxed = |ty: &HlirType| match ty {
+        HlirType::Void | HlirType::Struct(_) | HlirType::Tuple(_) | HlirType::Array(..) => {
+            HlirType::Ptr(Box::new(HlirType::U8))
+        }
+        ty => ty.clone(),
+    };
+    let stored: Vec<_> = types
+        .iter()
+        .filter_map(|def| match &def.kind {
+            HlirTypeDefKind::Struct(fields) => Some(HlirTypeDef {
+                name: def.name.clone(),
+                kind: HlirTypeDefKind::Struct(
+                    fields
+                        .iter()
+                        .map(|(n, ty)| (n.clone(), boxed(ty)))
+                        .collect(),
+                ),
+                packed: def.packed,
+            }),
+            HlirTypeDefKind::Enum(_) => None,
+        })
+        .collect();
+    let layouts = Layouts::new(&stored);
+    stored
+        .iter()
+        .map(|def| (def.name.clone(), layouts.struct_layout(def)))
+        .collect()
+}
 
-        let mut functions = HashMap::new();
-        for (name, func_id) in &self.func_ids {
-            let ptr = self.jit_module.get_finalized_function(*func_id);
-            functions.insert(name.clone(), ptr);
+/// Names of the functions a body calls directly or takes the address of
+#[cfg(feature = "jit")]
+fn referenced_functions(func: &HlirFunction) -> Vec<&str> {
+    let mut names = Vec::new();
+    for instr in func.blocks.iter().flat_map(|b| &b.instructions) {
+        let name = match &instr.op {
+            Op::CallDirect { name, .. } => name,
+            Op::Const(HlirConstant::FunctionRef(name)) => name,
+            _ => continue,
+        };
+        if !names.contains(&name.as_str()) {
+            names.push(name.as_str());
         }
+    }
+    names
+}
 
-        Ok(CompiledModule {
-            jit_module: self.jit_module,
-            functions,
-        })
+/// Names of the globals a body takes the address of
+#[cfg(feature = "jit")]
+fn referenced_globals(func: &HlirFunction) -> Vec<&str> {
+    let mut names = Vec::new();
+    for instr in func.blocks.iter().flat_map(|b| &b.instructions) {
+        if let Op::Const(HlirConstant::GlobalRef(name)) = &instr.op
+            && !names.contains(&name.as_str())
+        {
+            names.push(name.as_str());
+        }
     }
+    names
 }
 
 #[cfg(feature = "jit")]
 struct FunctionTranslator<'a> {
-    builder: &'a mut FunctionBuilder<'a>,
-    func_ids: &'a HashMap<String, cranelift_module::FuncId>,
+    builder: FunctionBuilder<'a>,
+    /// Callees imported into the function being built
+    func_refs: HashMap<String, FuncRef>,
+    /// Globals imported into the function being built
+    global_refs: HashMap<String, GlobalValue>,
     /// Map from HLIR ValueId to Cranelift Value
     values: HashMap<ValueId, cranelift_codegen::ir::Value>,
     /// Map from HLIR BlockId to Cranelift Block
@@ -323,25 +524,50 @@ struct FunctionTranslator<'a> {
     /// Variables for mutable locals
     variables: HashMap<ValueId, Variable>,
     next_var: usize,
+    /// The HLIR block whose body is being translated
+    current_block: Option<BlockId>,
     /// The HLIR function being compiled
     hlir_func: &'a HlirFunction,
+    /// Type of every HLIR value, for finding the struct behind a field access
+    value_types: HashMap<ValueId, HlirType>,
+    /// Stored layout of each struct, by name
+    struct_layouts: &'a HashMap<String, Layout>,
 }
 
 #[cfg(feature = "jit")]
 impl<'a> FunctionTranslator<'a> {
     fn new(
-        builder: &'a mut FunctionBuilder<'a>,
-        func_ids: &'a HashMap<String, cranelift_module::FuncId>,
+        builder: FunctionBuilder<'a>,
+        func_refs: HashMap<String, FuncRef>,
+        global_refs: HashMap<String, GlobalValue>,
         hlir_func: &'a HlirFunction,
+        struct_layouts: &'a HashMap<String, Layout>,
     ) -> Self {
+        let mut value_types: HashMap<_, _> = hlir_func
+            .params
+            .iter()
+            .map(|p| (p.value, p.ty.clone()))
+            .collect();
+        for block in &hlir_func.blocks {
+            value_types.extend(block.params.iter().cloned());
+            for instr in &block.instructions {
+                if let Some(result) = instr.result {
+                    value_types.insert(result, instr.ty.clone());
+                }
+            }
+        }
         Self {
             builder,
-            func_ids,
+            func_refs,
+            global_refs,
             values: HashMap::new(),
             blocks: HashMap::new(),
             variables: HashMap::new(),
             next_var: 0,
+            current_block: None,
             hlir_func,
+            value_types,
+            struct_layouts,
         }
     }
 
@@ -356,7 +582,6 @@ impl<'a> FunctionTranslator<'a> {
         if let Some(entry) = func.blocks.first() {
             let entry_block = self.blocks[&entry.id];
             self.builder.switch_to_block(entry_block);
-            self.builder.seal_block(entry_block);
 
             // Add function parameters
             for (i, param) in func.params.iter().enumerate() {
@@ -366,16 +591,33 @@ impl<'a> FunctionTranslator<'a> {
             }
         }
 
+        // Phi nodes become block parameters; each predecessor passes its
+        // incoming value as a branch argument
+        for block in &func.blocks {
+            let cl_block = self.blocks[&block.id];
+            for instr in &block.instructions {
+                if let (Op::Phi { .. }, Some(result)) = (&instr.op, instr.result) {
+                    let ty = self.hlir_to_type(&instr.ty);
+                    let param = self.builder.append_block_param(cl_block, ty);
+                    self.values.insert(result, param);
+                }
+            }
+        }
+
         // Translate each block
         for block in &func.blocks {
             self.translate_block(block)?;
         }
 
+        // Back edges are only known once every block has been translated
+        self.builder.seal_all_blocks();
+
         Ok(())
     }
 
     fn translate_block(&mut self, block: &HlirBlock) -> Result<(), String> {
         let cl_block = self.blocks[&block.id];
+        self.current_block = Some(block.id);
 
         // Only switch if not already on this block
         if self.builder.current_block() != Some(cl_block) {
@@ -393,9 +635,6 @@ impl<'a> FunctionTranslator<'a> {
         // Translate terminator
         self.translate_terminator(&block.terminator)?;
 
-        // Seal the block if all predecessors are known
-        self.builder.seal_block(cl_block);
-
         Ok(())
     }
 
@@ -435,16 +674,8 @@ impl<'a> FunctionTranslator<'a> {
                     .map(|a| self.get_value(*a))
                     .collect::<Result<_, _>>()?;
 
-                if let Some(&func_id) = self.func_ids.get(name) {
-                    let func_ref = self.builder.ins().func_addr(types::I64, func_id.into());
-                    // For now, use direct call syntax
-                    let sig = self
-                        .builder
-                        .func
-                        .dfg
-                        .signatures
-                        .push(self.builder.func.signature.clone());
-                    let call = self.builder.ins().call_indirect(sig, func_ref, &arg_vals);
+                if let Some(&func_ref) = self.func_refs.get(name) {
+                    let call = self.builder.ins().call(func_ref, &arg_vals);
                     let results = self.builder.inst_results(call);
                     if results.is_empty() {
                         Ok(None)
@@ -466,12 +697,8 @@ impl<'a> FunctionTranslator<'a> {
                     .collect::<Result<_, _>>()?;
 
                 // Indirect call
-                let sig = self
-                    .builder
-                    .func
-                    .dfg
-                    .signatures
-                    .push(self.builder.func.signature.clone());
+                let caller_sig = self.builder.func.signature.clone();
+                let sig = self.builder.func.dfg.signatures.push(caller_sig);
                 let call = self.builder.ins().call_indirect(sig, func_val, &arg_vals);
                 let results = self.builder.inst_results(call);
                 if results.is_empty() {
@@ -510,7 +737,10 @@ impl<'a> FunctionTranslator<'a> {
 
             Op::GetFieldPtr { base, field } => {
                 let base_val = self.get_value(*base)?;
-                let offset = (*field * 8) as i32; // Assume 8-byte fields
+                let offset = match self.value_types.get(base) {
+                    Some(HlirType::Ptr(pointee)) => self.field_offset(pointee, *field),
+                    _ => *field as i32 * 8,
+                };
                 let ptr = self.builder.ins().iadd_imm(base_val, offset as i64);
                 Ok(Some(ptr))
             }
@@ -531,6 +761,11 @@ impl<'a> FunctionTranslator<'a> {
 
                 if val_ty == target_ty {
                     Ok(Some(val))
+                } else if val_ty.is_int() && target_ty.is_float() {
+                    Ok(Some(self.builder.ins().fcvt_from_sint(target_ty, val)))
+                } else if val_ty.is_float() && target_ty.is_int() {
+                    // Saturating, like `as` in the interpreter
+                    Ok(Some(self.builder.ins().fcvt_to_sint_sat(target_ty, val)))
                 } else if val_ty.bits() < target_ty.bits() {
                     // Extend
                     let extended = if target_ty.is_int() {
@@ -550,23 +785,29 @@ impl<'a> FunctionTranslator<'a> {
                 }
             }
 
-            Op::Phi { incoming } => {
-                // Phi nodes should be handled as block parameters in Cranelift
-                // For now, return first incoming value if available
-                if let Some((_, first_val)) = incoming.first() {
-                    let val = self.get_value(*first_val)?;
+            Op::Bitcast { value, target } => {
+                let val = self.get_value(*value)?;
+                let target_ty = self.hlir_to_type(target);
+                if self.builder.func.dfg.value_type(val) == target_ty {
                     Ok(Some(val))
                 } else {
-                    let zero = self.builder.ins().iconst(ty, 0);
-                    Ok(Some(zero))
+                    let cast = self.builder.ins().bitcast(target_ty, MemFlags::new(), val);
+                    Ok(Some(cast))
                 }
             }
 
+            Op::Phi { .. } => {
+                // Already bound to a block parameter in `translate`
+                Ok(None)
+            }
+
             Op::ExtractValue { base, index } => {
                 // For tuples/structs stored as aggregates
                 let base_val = self.get_value(*base)?;
-                // Simplified: treat as field access
-                let offset = (*index * 8) as i32;
+                let offset = self
+                    .value_types
+                    .get(base)
+                    .map_or(*index as i32 * 8, |ty| self.field_offset(ty, *index));
                 let ptr = self.builder.ins().iadd_imm(base_val, offset as i64);
                 let loaded = self.builder.ins().load(ty, MemFlags::new(), ptr, 0);
                 Ok(Some(loaded))
@@ -575,8 +816,10 @@ impl<'a> FunctionTranslator<'a> {
             Op::InsertValue { base, value, index } => {
                 let base_val = self.get_value(*base)?;
                 let val = self.get_value(*value)?;
-                // Simplified: treat as field store
-                let offset = (*index * 8) as i32;
+                let offset = self
+                    .value_types
+                    .get(base)
+                    .map_or(*index as i32 * 8, |ty| self.field_offset(ty, *index));
                 let ptr = self.builder.ins().iadd_imm(base_val, offset as i64);
                 self.builder.ins().store(MemFlags::new(), val, ptr, 0);
                 Ok(Some(base_val))
@@ -584,7 +827,7 @@ impl<'a> FunctionTranslator<'a> {
 
             Op::Tuple(vals) | Op::Array(vals) => {
                 // Allocate space and store values
-                let size = (vals.len() * 8) as u32;
+                let size = self.aggregate_size(&instr.ty, vals.len());
                 let slot = self.builder.create_sized_stack_slot(
                     cranelift_codegen::ir::StackSlotData::new(
                         cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
@@ -596,16 +839,17 @@ impl<'a> FunctionTranslator<'a> {
 
                 for (i, v) in vals.iter().enumerate() {
                     let val = self.get_value(*v)?;
-                    let offset = (i * 8) as i32;
+                    let offset = self.field_offset(&instr.ty, i);
                     self.builder.ins().store(MemFlags::new(), val, base, offset);
                 }
 
                 Ok(Some(base))
             }
 
-            Op::Struct { name: _, fields } => {
+            Op::Struct { name, fields } => {
                 // Similar to tuple
-                let size = (fields.len() * 8) as u32;
+                let struct_ty = HlirType::Struct(name.clone());
+                let size = self.aggregate_size(&struct_ty, fields.len());
                 let slot = self.builder.create_sized_stack_slot(
                     cranelift_codegen::ir::StackSlotData::new(
                         cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
@@ -617,7 +861,7 @@ impl<'a> FunctionTranslator<'a> {
 
                 for (i, (_, v)) in fields.iter().enumerate() {
                     let val = self.get_value(*v)?;
-                    let offset = (i * 8) as i32;
+                    let offset = self.field_offset(&struct_ty, i);
                     self.builder.ins().store(MemFlags::new(), val, base, offset);
                 }
 
@@ -657,13 +901,19 @@ impl<'a> FunctionTranslator<'a> {
             HlirConstant::Null(_) => Ok(self.builder.ins().iconst(types::I64, 0)),
             HlirConstant::Undef(_) => Ok(self.builder.ins().iconst(cl_ty, 0)),
             HlirConstant::FunctionRef(name) => {
-                if let Some(&func_id) = self.func_ids.get(name) {
-                    Ok(self.builder.ins().func_addr(types::I64, func_id.into()))
+                if let Some(&func_ref) = self.func_refs.get(name) {
+                    Ok(self.builder.ins().func_addr(types::I64, func_ref))
                 } else {
                     Ok(self.builder.ins().iconst(types::I64, 0))
                 }
             }
-            HlirConstant::GlobalRef(_) => Ok(self.builder.ins().iconst(types::I64, 0)),
+            HlirConstant::GlobalRef(name) => {
+                let global = self
+                    .global_refs
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown global: {}", name))?;
+                Ok(self.builder.ins().symbol_value(types::I64, *global))
+            }
             HlirConstant::Array(_) | HlirConstant::Struct(_) => {
                 // Complex constants - return null for now
                 Ok(self.builder.ins().iconst(types::I64, 0))
@@ -776,7 +1026,8 @@ impl<'a> FunctionTranslator<'a> {
             UnaryOp::FNeg => Ok(self.builder.ins().fneg(val)),
             UnaryOp::Not => {
                 // Logical not: xor with all 1s
-                let ones = self.builder.ins().iconst(self.hlir_to_type(ty), -1);
+                let cl_ty = self.hlir_to_type(ty);
+                let ones = self.builder.ins().iconst(cl_ty, -1);
                 Ok(self.builder.ins().bxor(val, ones))
             }
         }
@@ -795,18 +1046,24 @@ impl<'a> FunctionTranslator<'a> {
 
             HlirTerminator::Branch(target) => {
                 let target_block = self.blocks[target];
-                self.builder.ins().jump(target_block, &[]);
+                let args = self.branch_args(*target)?;
+                self.builder.ins().jump(target_block, &args);
             }
 
             HlirTerminator::CondBranch {
                 condition,
                 then_block,
                 else_block,
+                ..
             } => {
                 let cond = self.get_value(*condition)?;
                 let then_b = self.blocks[then_block];
                 let else_b = self.blocks[else_block];
-                self.builder.ins().brif(cond, then_b, &[], else_b, &[]);
+                let then_args = self.branch_args(*then_block)?;
+                let else_args = self.branch_args(*else_block)?;
+                self.builder
+                    .ins()
+                    .brif(cond, then_b, &then_args, else_b, &else_args);
             }
 
             HlirTerminator::Switch {
@@ -816,14 +1073,14 @@ impl<'a> FunctionTranslator<'a> {
             } => {
                 let val = self.get_value(*value)?;
                 let default_block = self.blocks[default];
+                let val_ty = self.builder.func.dfg.value_type(val);
 
                 // Build switch using a chain of conditionals
                 // (Cranelift has br_table but it's more complex)
-                let mut current_block = self.builder.current_block().unwrap();
-
                 for (case_val, target) in cases {
                     let target_block = self.blocks[target];
-                    let case_const = self.builder.ins().iconst(types::I64, *case_val);
+                    let args = self.branch_args(*target)?;
+                    let case_const = self.builder.ins().iconst(val_ty, *case_val);
                     let cmp = self.builder.ins().icmp(
                         cranelift_codegen::ir::condcodes::IntCC::Equal,
                         val,
@@ -833,24 +1090,76 @@ impl<'a> FunctionTranslator<'a> {
                     let next_block = self.builder.create_block();
                     self.builder
                         .ins()
-                        .brif(cmp, target_block, &[], next_block, &[]);
+                        .brif(cmp, target_block, &args, next_block, &[]);
                     self.builder.seal_block(next_block);
                     self.builder.switch_to_block(next_block);
                 }
 
-                self.builder.ins().jump(default_block, &[]);
+                let args = self.branch_args(*default)?;
+                self.builder.ins().jump(default_block, &args);
             }
 
             HlirTerminator::Unreachable => {
                 self.builder
                     .ins()
-                    .trap(cranelift_codegen::ir::TrapCode::User(0));
+                    .trap(cranelift_codegen::ir::TrapCode::unwrap_user(1));
+            }
+
+            HlirTerminator::Trap => {
+                self.builder
+                    .ins()
+                    .trap(cranelift_codegen::ir::TrapCode::unwrap_user(2));
             }
         }
 
         Ok(())
     }
 
+    /// Arguments for the phi parameters of `target` along the edge from
+    /// the current block
+    fn branch_args(&self, target: BlockId) -> Result<Vec<cranelift_codegen::ir::Value>, String> {
+        let Some(block) = self.hlir_func.blocks.iter().find(|b| b.id == target) else {
+            return Ok(Vec::new());
+        };
+
+        let mut args = Vec::new();
+        for instr in &block.instructions {
+            if let Op::Phi { incoming } = &instr.op {
+                let value = incoming
+                    .iter()
+                    .find(|(pred, _)| Some(*pred) == self.current_block)
+                    .map(|(_, value)| *value)
+                    .ok_or_else(|| {
+                        format!("Phi in {:?} has no incoming value for this edge", target)
+                    })?;
+                args.push(self.get_value(value)?);
+            }
+        }
+        Ok(args)
+    }
+
+    /// Byte offset of field `index` of an aggregate of type `ty`. Enums,
+    /// arrays and other unnamed aggregates use one 8-byte slot per element.
+    fn field_offset(&self, ty: &HlirType, index: usize) -> i32 {
+        let offset = match ty {
+            HlirType::Struct(name) => self
+                .struct_layouts
+                .get(name)
+                .and_then(|layout| layout.offsets.get(index).copied()),
+            _ => None,
+        };
+        offset.unwrap_or(index as u64 * 8) as i32
+    }
+
+    /// Bytes to allocate for an aggregate of type `ty` with `len` elements
+    fn aggregate_size(&self, ty: &HlirType, len: usize) -> u32 {
+        let size = match ty {
+            HlirType::Struct(name) => self.struct_layouts.get(name).map(|layout| layout.size),
+            _ => None,
+        };
+        size.unwrap_or(len as u64 * 8) as u32
+    }
+
     fn get_value(&self, id: ValueId) -> Result<cranelift_codegen::ir::Value, String> {
         self.values
             .get(&id)
@@ -664,6 +664,110 @@ impl GpuBlock {
     }
 }
 
+impl GpuOp {
+    /// Values read by this operation
+    pub fn operands(&self) -> Vec<ValueId> {
+        use GpuOp::*;
+
+        match self {
+            ConstInt(..) | ConstFloat(..) | ConstBool(_) => vec![],
+            ThreadIdX | ThreadIdY | ThreadIdZ | BlockIdX | BlockIdY | BlockIdZ | BlockDimX
+            | BlockDimY | BlockDimZ | GridDimX | GridDimY | GridDimZ | WarpId | LaneId
+            | WarpSize | SyncThreads | SyncWarp(_) | MemoryFence(_) | Param(_) | SharedAddr(_) => {
+                vec![]
+            }
+
+            Neg(a)
+            | FNeg(a)
+            | FastSin(a)
+            | FastCos(a)
+            | FastExp(a)
+            | FastLog(a)
+            | FastSqrt(a)
+            | FastRsqrt(a)
+            | Not(a)
+            | BitNot(a)
+            | PopCount(a)
+            | Clz(a)
+            | Ctz(a)
+            | Trunc(a, _)
+            | ZExt(a, _)
+            | SExt(a, _)
+            | FpTrunc(a, _)
+            | FpExt(a, _)
+            | FpToSi(a, _)
+            | FpToUi(a, _)
+            | SiToFp(a, _)
+            | UiToFp(a, _)
+            | Bitcast(a, _)
+            | Load(a, _)
+            | PtrToInt(a)
+            | IntToPtr(a, _)
+            | WarpVote(_, a)
+            | WarpReduce(_, a)
+            | WarpMatch(a) => vec![*a],
+
+            Add(a, b)
+            | Sub(a, b)
+            | Mul(a, b)
+            | Div(a, b)
+            | Rem(a, b)
+            | FAdd(a, b)
+            | FSub(a, b)
+            | FMul(a, b)
+            | FDiv(a, b)
+            | Eq(a, b)
+            | Ne(a, b)
+            | Lt(a, b)
+            | Le(a, b)
+            | Gt(a, b)
+            | Ge(a, b)
+            | FEq(a, b)
+            | FNe(a, b)
+            | FLt(a, b)
+            | FLe(a, b)
+            | FGt(a, b)
+            | FGe(a, b)
+            | And(a, b)
+            | Or(a, b)
+            | Xor(a, b)
+            | Shl(a, b)
+            | Shr(a, b)
+            | LShr(a, b)
+            | BitAnd(a, b)
+            | BitOr(a, b)
+            | BitXor(a, b)
+            | Store(a, b, _)
+            | AtomicAdd(a, b)
+            | AtomicSub(a, b)
+            | AtomicMin(a, b)
+            | AtomicMax(a, b)
+            | AtomicAnd(a, b)
+            | AtomicOr(a, b)
+            | AtomicXor(a, b)
+            | AtomicExch(a, b)
+            | WarpShuffle(a, b)
+            | WarpShuffleUp(a, b)
+            | WarpShuffleDown(a, b)
+            | WarpShuffleXor(a, b)
+            | TexFetch(a, b)
+            | SurfRead(a, b) => vec![*a, *b],
+
+            FMulAdd(a, b, c)
+            | AtomicCas(a, b, c)
+            | TexFetch2D(a, b, c)
+            | SurfWrite(a, b, c)
+            | Select(a, b, c) => vec![*a, *b, *c],
+
+            GetElementPtr(ptr, indices) => std::iter::once(*ptr)
+                .chain(indices.iter().copied())
+                .collect(),
+            Phi(incoming) => incoming.iter().map(|(_, v)| *v).collect(),
+            Call(_, args) => args.clone(),
+        }
+    }
+}
+
 /// Builder for GPU modules
 pub struct GpuModuleBuilder {
     module: GpuModule,
@@ -0,0 +1,615 @@
+//! HLIR to GPU IR lowering
+//!
+//! Translates `kernel fn`s, and the functions they call, from HLIR into the
+//! GPU IR consumed by the PTX and SPIR-V emitters. Calls to the thread
+//! indexing and synchronization intrinsics (`thread_id_x()`,
+//! `gpu.block_dim.x()`, `sync_threads()`, ...) become the matching
+//! [`GpuOp`]s, and every other function reachable from a kernel becomes a
+//! device function.
+//!
+//! GPU values are numbered in emission order, as the emitters expect.
+//! Operations with no GPU counterpart (indirect calls, stack slots,
+//! aggregates and effects) are dropped, and their results read as zero.
+
+use std::collections::{HashMap, HashSet};
+
+use super::intrinsics::{get_intrinsic, get_intrinsic_by_short_name};
+use super::ir::*;
+use crate::hlir::{
+    self, BinaryOp, HlirConstant, HlirFunction, HlirInstr, HlirModule, HlirTerminator, HlirType,
+    Op, UnaryOp,
+};
+
+/// Lower every kernel of `module`, along with the functions they call
+pub fn lower_hlir_module(module: &HlirModule, target: GpuTarget) -> GpuModule {
+    let mut gpu = GpuModule::new(module.name.clone(), target);
+    let mut pending = Vec::new();
+    for func in module.functions.iter().filter(|f| f.is_kernel) {
+        gpu.add_kernel(lower_hlir_kernel(func));
+        pending.extend(callees(func));
+    }
+
+    let mut seen = HashSet::new();
+    while let Some(name) = pending.pop() {
+        let Some(func) = module.find_function(name) else {
+            continue;
+        };
+        if func.is_kernel || !seen.insert(name) {
+            continue;
+        }
+        gpu.add_device_function(lower_hlir_device_function(func));
+        pending.extend(callees(func));
+    }
+    gpu
+}
+
+/// Lower a `kernel fn` to a GPU kernel
+pub fn lower_hlir_kernel(func: &HlirFunction) -> GpuKernel {
+    let mut kernel = GpuKernel::new(func.name.clone());
+    kernel.params = lower_params(func);
+    kernel.blocks = FunctionLowerer::new(func, true).lower();
+    kernel.entry = entry_block(func);
+    kernel
+}
+
+/// Lower a function called from a kernel to a GPU device function
+pub fn lower_hlir_device_function(func: &HlirFunction) -> GpuFunction {
+    let mut device = GpuFunction::new(func.name.clone(), gpu_type(&func.return_type));
+    device.params = lower_params(func);
+    device.blocks = FunctionLowerer::new(func, false).lower();
+    device.entry = entry_block(func);
+    device
+}
+
+/// GPU type of an HLIR type
+///
+/// Pointers address global memory. Structs carry no fields, as their
+/// layouts live in the module's type definitions.
+pub fn gpu_type(ty: &HlirType) -> GpuType {
+    match ty {
+        HlirType::Void => GpuType::Void,
+        HlirType::Bool => GpuType::Bool,
+        HlirType::I8 => GpuType::I8,
+        HlirType::I16 => GpuType::I16,
+        HlirType::I32 => GpuType::I32,
+        HlirType::I64 | HlirType::I128 => GpuType::I64,
+        HlirType::U8 => GpuType::U8,
+        HlirType::U16 => GpuType::U16,
+        HlirType::U32 => GpuType::U32,
+        HlirType::U64 | HlirType::U128 => GpuType::U64,
+        HlirType::F32 => GpuType::F32,
+        HlirType::F64 => GpuType::F64,
+        HlirType::Ptr(inner) => GpuType::Ptr(Box::new(gpu_type(inner)), MemorySpace::Global),
+        HlirType::Array(elem, len) => GpuType::Array(Box::new(gpu_type(elem)), *len as u32),
+        HlirType::Struct(name) => GpuType::Struct(name.clone(), Vec::new()),
+        HlirType::Tuple(elems) => GpuType::Struct(
+            HlirType::tuple_struct_name(elems),
+            elems
+                .iter()
+                .enumerate()
+                .map(|(i, e)| (i.to_string(), gpu_type(e)))
+                .collect(),
+        ),
+        HlirType::Function { .. } => GpuType::U64,
+    }
+}
+
+/// GPU operation an intrinsic call lowers to, by full or short name
+fn intrinsic_op(name: &str) -> Option<GpuOp> {
+    let intrinsic = get_intrinsic(name).or_else(|| get_intrinsic_by_short_name(name))?;
+    let op = match intrinsic.name {
+        "gpu.thread_id.x" => GpuOp::ThreadIdX,
+        "gpu.thread_id.y" => GpuOp::ThreadIdY,
+        "gpu.thread_id.z" => GpuOp::ThreadIdZ,
+        "gpu.block_id.x" => GpuOp::BlockIdX,
+        "gpu.block_id.y" => GpuOp::BlockIdY,
+        "gpu.block_id.z" => GpuOp::BlockIdZ,
+        "gpu.block_dim.x" => GpuOp::BlockDimX,
+        "gpu.block_dim.y" => GpuOp::BlockDimY,
+        "gpu.block_dim.z" => GpuOp::BlockDimZ,
+        "gpu.grid_dim.x" => GpuOp::GridDimX,
+        "gpu.grid_dim.y" => GpuOp::GridDimY,
+        "gpu.grid_dim.z" => GpuOp::GridDimZ,
+        "gpu.warp_id" => GpuOp::WarpId,
+        "gpu.lane_id" => GpuOp::LaneId,
+        "gpu.warp_size" => GpuOp::WarpSize,
+        "gpu.sync_threads" => GpuOp::SyncThreads,
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Names of the functions `func` calls directly, other than intrinsics
+fn callees(func: &HlirFunction) -> impl Iterator<Item = &str> {
+    func.blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .filter_map(|instr| match &instr.op {
+            Op::CallDirect { name, .. } if intrinsic_op(name).is_none() => Some(name.as_str()),
+            _ => None,
+        })
+}
+
+fn lower_params(func: &HlirFunction) -> Vec<GpuParam> {
+    func.params
+        .iter()
+        .map(|p| {
+            let ty = gpu_type(&p.ty);
+            // Scalars are passed by value to every thread
+            let space = match ty {
+                GpuType::Ptr(_, space) => space,
+                _ => MemorySpace::Local,
+            };
+            GpuParam {
+                name: p.name.clone(),
+                ty,
+                space,
+                restrict: false,
+            }
+        })
+        .collect()
+}
+
+fn entry_block(func: &HlirFunction) -> BlockId {
+    BlockId(func.entry_block().map_or(0, |b| b.id.0))
+}
+
+/// Lowers the blocks of one function
+struct FunctionLowerer<'a> {
+    func: &'a HlirFunction,
+    /// Kernels return nothing, whatever their HLIR return value
+    is_kernel: bool,
+    /// GPU value each HLIR value lowered to
+    values: HashMap<hlir::ValueId, ValueId>,
+    /// HLIR type of each value
+    types: HashMap<hlir::ValueId, HlirType>,
+    blocks: Vec<GpuBlock>,
+    next_value: u32,
+    /// First block ID not used by the HLIR function, for switch lowering
+    next_block: u32,
+    /// Phis to fill in once every block is lowered
+    phis: Vec<PendingPhi>,
+}
+
+/// A phi emitted before all of its incoming values were lowered
+struct PendingPhi {
+    /// Index of the block in `FunctionLowerer::blocks`
+    block: usize,
+    /// Index of the phi within the block
+    index: usize,
+    incoming: Vec<(hlir::BlockId, hlir::ValueId)>,
+}
+
+impl<'a> FunctionLowerer<'a> {
+    fn new(func: &'a HlirFunction, is_kernel: bool) -> Self {
+        let mut types: HashMap<_, _> = func
+            .params
+            .iter()
+            .map(|p| (p.value, p.ty.clone()))
+            .collect();
+        for instr in func.blocks.iter().flat_map(|b| &b.instructions) {
+            if let Some(result) = instr.result {
+                types.insert(result, instr.ty.clone());
+            }
+        }
+        let next_block = func.blocks.iter().map(|b| b.id.0 + 1).max().unwrap_or(0);
+
+        Self {
+            func,
+            is_kernel,
+            values: HashMap::new(),
+            types,
+            blocks: Vec::new(),
+            next_value: 0,
+            next_block,
+            phis: Vec::new(),
+        }
+    }
+
+    fn lower(mut self) -> Vec<GpuBlock> {
+        for (i, block) in self.func.blocks.iter().enumerate() {
+            self.start_block(BlockId(block.id.0));
+            if i == 0 {
+                for (index, param) in self.func.params.iter().enumerate() {
+                    let value = self.emit(GpuOp::Param(index as u32));
+                    self.values.insert(param.value, value);
+                }
+            }
+            for instr in &block.instructions {
+                self.lower_instr(instr);
+            }
+            self.lower_terminator(&block.terminator);
+        }
+
+        for phi in std::mem::take(&mut self.phis) {
+            let incoming = phi
+                .incoming
+                .iter()
+                .filter_map(|(b, v)| Some((BlockId(b.0), *self.values.get(v)?)))
+                .collect();
+            self.blocks[phi.block].instructions[phi.index].1 = GpuOp::Phi(incoming);
+        }
+        self.blocks
+    }
+
+    fn start_block(&mut self, id: BlockId) {
+        // Branches are emitted as `bra BB<n>`, so labels follow the IDs
+        self.blocks.push(GpuBlock::new(id, id.to_string()));
+    }
+
+    fn emit(&mut self, op: GpuOp) -> ValueId {
+        let id = ValueId(self.next_value);
+        self.next_value += 1;
+        self.current().add_instruction(id, op);
+        id
+    }
+
+    fn current(&mut self) -> &mut GpuBlock {
+        self.blocks.last_mut().expect("a block is being lowered")
+    }
+
+    fn type_of(&self, value: hlir::ValueId) -> HlirType {
+        self.types.get(&value).cloned().unwrap_or(HlirType::I64)
+    }
+
+    /// The GPU value for `value`, or a zero when it was not lowered
+    fn value(&mut self, value: hlir::ValueId) -> ValueId {
+        if let Some(&id) = self.values.get(&value) {
+            return id;
+        }
+        let op = match gpu_type(&self.type_of(value)) {
+            GpuType::Bool => GpuOp::ConstBool(false),
+            ty if ty.is_float() => GpuOp::ConstFloat(0.0, ty),
+            ty if ty.is_integer() => GpuOp::ConstInt(0, ty),
+            _ => GpuOp::ConstInt(0, GpuType::U64),
+        };
+        self.emit(op)
+    }
+
+    fn lower_instr(&mut self, instr: &HlirInstr) {
+        let op = match &instr.op {
+            Op::Const(constant) => match constant {
+                HlirConstant::Bool(b) => GpuOp::ConstBool(*b),
+                HlirConstant::Int(n, ty) => GpuOp::ConstInt(*n, gpu_type(ty)),
+                HlirConstant::Float(n, ty) => GpuOp::ConstFloat(*n, gpu_type(ty)),
+                _ => return,
+            },
+            Op::Copy(value) => {
+                let value = self.value(*value);
+                if let Some(result) = instr.result {
+                    self.values.insert(result, value);
+                }
+                return;
+            }
+            Op::Binary { op, left, right } => {
+                let is_bool = self.type_of(*left) == HlirType::Bool;
+                let (l, r) = (self.value(*left), self.value(*right));
+                binary_op(*op, l, r, is_bool)
+            }
+            Op::Unary { op, operand } => {
+                let is_bool = self.type_of(*operand) == HlirType::Bool;
+                let operand = self.value(*operand);
+                match op {
+                    UnaryOp::Neg => GpuOp::Neg(operand),
+                    UnaryOp::FNeg => GpuOp::FNeg(operand),
+                    UnaryOp::Not if is_bool => GpuOp::Not(operand),
+                    UnaryOp::Not => GpuOp::BitNot(operand),
+                }
+            }
+            Op::CallDirect { name, args } => match intrinsic_op(name) {
+                Some(op) => op,
+                None => {
+                    let args = args.iter().map(|a| self.value(*a)).collect();
+                    GpuOp::Call(name.clone(), args)
+                }
+            },
+            Op::Load { ptr } => GpuOp::Load(self.value(*ptr), MemorySpace::Global),
+            Op::Store { ptr, value } => {
+                let (ptr, value) = (self.value(*ptr), self.value(*value));
+                GpuOp::Store(ptr, value, MemorySpace::Global)
+            }
+            Op::GetElementPtr { base, index } => {
+                // A pointer to an array steps into it rather than over it
+                let into_array = matches!(
+                    self.type_of(*base),
+                    HlirType::Ptr(inner) if matches!(*inner, HlirType::Array(..))
+                );
+                let (base, index) = (self.value(*base), self.value(*index));
+                if into_array {
+                    let zero = self.emit(GpuOp::ConstInt(0, GpuType::I64));
+                    GpuOp::GetElementPtr(base, vec![zero, index])
+                } else {
+                    GpuOp::GetElementPtr(base, vec![index])
+                }
+            }
+            Op::GetFieldPtr { base, field } => {
+                let base = self.value(*base);
+                let zero = self.emit(GpuOp::ConstInt(0, GpuType::I64));
+                let field = self.emit(GpuOp::ConstInt(*field as i64, GpuType::I32));
+                GpuOp::GetElementPtr(base, vec![zero, field])
+            }
+            Op::Cast { value, target } => {
+                let from = self.type_of(*value);
+                let value = self.value(*value);
+                match cast_op(value, &from, target) {
+                    Some(op) => op,
+                    None => {
+                        if let Some(result) = instr.result {
+                            self.values.insert(result, value);
+                        }
+                        return;
+                    }
+                }
+            }
+            Op::Bitcast { value, target } => GpuOp::Bitcast(self.value(*value), gpu_type(target)),
+            Op::Phi { incoming } => {
+                let index = self.current().instructions.len();
+                self.phis.push(PendingPhi {
+                    block: self.blocks.len() - 1,
+                    index,
+                    incoming: incoming.clone(),
+                });
+                GpuOp::Phi(Vec::new())
+            }
+            Op::Call { .. }
+            | Op::Alloca { .. }
+            | Op::ExtractValue { .. }
+            | Op::InsertValue { .. }
+            | Op::Tuple(_)
+            | Op::Array(_)
+            | Op::Struct { .. }
+            | Op::PerformEffect { .. } => return,
+        };
+
+        let value = self.emit(op);
+        if let Some(result) = instr.result {
+            self.values.insert(result, value);
+        }
+    }
+
+    fn lower_terminator(&mut self, terminator: &HlirTerminator) {
+        let terminator = match terminator {
+            HlirTerminator::Return(Some(value)) if !self.is_kernel => {
+                GpuTerminator::Return(self.value(*value))
+            }
+            HlirTerminator::Return(_) => GpuTerminator::ReturnVoid,
+            HlirTerminator::Branch(target) => GpuTerminator::Br(BlockId(target.0)),
+            HlirTerminator::CondBranch {
+                condition,
+                then_block,
+                else_block,
+                ..
+            } => GpuTerminator::CondBr(
+                self.value(*condition),
+                BlockId(then_block.0),
+                BlockId(else_block.0),
+            ),
+            HlirTerminator::Switch {
+                value,
+                default,
+                cases,
+            } => {
+                // A chain of comparisons, one block per case
+                let ty = gpu_type(&self.type_of(*value));
+                let scrutinee = self.value(*value);
+                for (case, target) in cases {
+                    let constant = self.emit(GpuOp::ConstInt(*case, ty.clone()));
+                    let matched = self.emit(GpuOp::Eq(scrutinee, constant));
+                    let next = BlockId(self.next_block);
+                    self.next_block += 1;
+                    self.current().set_terminator(GpuTerminator::CondBr(
+                        matched,
+                        BlockId(target.0),
+                        next,
+                    ));
+                    self.start_block(next);
+                }
+                GpuTerminator::Br(BlockId(default.0))
+            }
+            HlirTerminator::Unreachable | HlirTerminator::Trap => GpuTerminator::Unreachable,
+        };
+        self.current().set_terminator(terminator);
+    }
+}
+
+fn binary_op(op: BinaryOp, l: ValueId, r: ValueId, is_bool: bool) -> GpuOp {
+    match op {
+        BinaryOp::Add => GpuOp::Add(l, r),
+        BinaryOp::Sub => GpuOp::Sub(l, r),
+        BinaryOp::Mul => GpuOp::Mul(l, r),
+        BinaryOp::SDiv | BinaryOp::UDiv => GpuOp::Div(l, r),
+        BinaryOp::SRem | BinaryOp::URem | BinaryOp::FRem => GpuOp::Rem(l, r),
+        BinaryOp::FAdd => GpuOp::FAdd(l, r),
+        BinaryOp::FSub => GpuOp::FSub(l, r),
+        BinaryOp::FMul => GpuOp::FMul(l, r),
+        BinaryOp::FDiv => GpuOp::FDiv(l, r),
+        BinaryOp::And if is_bool => GpuOp::And(l, r),
+        BinaryOp::Or if is_bool => GpuOp::Or(l, r),
+        BinaryOp::Xor if is_bool => GpuOp::Xor(l, r),
+        BinaryOp::And => GpuOp::BitAnd(l, r),
+        BinaryOp::Or => GpuOp::BitOr(l, r),
+        BinaryOp::Xor => GpuOp::BitXor(l, r),
+        BinaryOp::Shl => GpuOp::Shl(l, r),
+        BinaryOp::AShr => GpuOp::Shr(l, r),
+        BinaryOp::LShr => GpuOp::LShr(l, r),
+        BinaryOp::Eq => GpuOp::Eq(l, r),
+        BinaryOp::Ne => GpuOp::Ne(l, r),
+        BinaryOp::SLt | BinaryOp::ULt => GpuOp::Lt(l, r),
+        BinaryOp::SLe | BinaryOp::ULe => GpuOp::Le(l, r),
+        BinaryOp::SGt | BinaryOp::UGt => GpuOp::Gt(l, r),
+        BinaryOp::SGe | BinaryOp::UGe => GpuOp::Ge(l, r),
+        BinaryOp::FOEq => GpuOp::FEq(l, r),
+        BinaryOp::FONe => GpuOp::FNe(l, r),
+        BinaryOp::FOLt => GpuOp::FLt(l, r),
+        BinaryOp::FOLe => GpuOp::FLe(l, r),
+        BinaryOp::FOGt => GpuOp::FGt(l, r),
+        BinaryOp::FOGe => GpuOp::FGe(l, r),
+    }
+}
+
+/// Conversion from `from` to `to`, or `None` when the value can be reused
+fn cast_op(value: ValueId, from: &HlirType, to: &HlirType) -> Option<GpuOp> {
+    if from == to {
+        return None;
+    }
+    let target = gpu_type(to);
+    let is_int = |ty: &HlirType| ty.is_integer() || *ty == HlirType::Bool;
+    let op = match (from, to) {
+        (HlirType::Ptr(_), _) if is_int(to) => GpuOp::PtrToInt(value),
+        (_, HlirType::Ptr(_)) if is_int(from) => GpuOp::IntToPtr(value, target),
+        _ if is_int(from) && is_int(to) => match from.size_bits().cmp(&to.size_bits()) {
+            std::cmp::Ordering::Less if from.is_signed() => GpuOp::SExt(value, target),
+            std::cmp::Ordering::Less => GpuOp::ZExt(value, target),
+            std::cmp::Ordering::Greater => GpuOp::Trunc(value, target),
+            std::cmp::Ordering::Equal => GpuOp::Bitcast(value, target),
+        },
+        _ if is_int(from) && to.is_float() => {
+            if from.is_signed() {
+                GpuOp::SiToFp(value, target)
+            } else {
+                GpuOp::UiToFp(value, target)
+            }
+        }
+        _ if from.is_float() && is_int(to) => {
+            if to.is_signed() {
+                GpuOp::FpToSi(value, target)
+            } else {
+                GpuOp::FpToUi(value, target)
+            }
+        }
+        _ if from.is_float() && to.is_float() => {
+            if from.size_bits() < to.size_bits() {
+                GpuOp::FpExt(value, target)
+            } else {
+                GpuOp::FpTrunc(value, target)
+            }
+        }
+        _ => GpuOp::Bitcast(value, target),
+    };
+    Some(op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::gpu::PtxCodegen;
+    use crate::hlir::{FunctionBuilder, FunctionId, ModuleBuilder};
+
+    fn f32_slice() -> HlirType {
+        HlirType::Ptr(Box::new(HlirType::Array(Box::new(HlirType::F32), 0)))
+    }
+
+    /// `kernel fn add(a, b, out) { let i = thread_id_x(); out[i] = scale(a[i] + b[i]) }`
+    fn add_kernel(id: FunctionId) -> HlirFunction {
+        let mut builder = FunctionBuilder::new(id, "add", HlirType::Void);
+        let a = builder.add_param("a", f32_slice());
+        let b = builder.add_param("b", f32_slice());
+        let out = builder.add_param("out", f32_slice());
+        let entry = builder.create_block("entry");
+        builder.switch_to_block(entry);
+
+        let i = builder.build_call("thread_id_x", vec![], HlirType::U32);
+        let a_i = builder.build_elem_ptr(a, i, HlirType::F32);
+        let a_i = builder.build_load(a_i, HlirType::F32);
+        let b_i = builder.build_elem_ptr(b, i, HlirType::F32);
+        let b_i = builder.build_load(b_i, HlirType::F32);
+        let sum = builder.build_fadd(a_i, b_i, HlirType::F32);
+        let scaled = builder.build_call("scale", vec![sum], HlirType::F32);
+        let out_i = builder.build_elem_ptr(out, i, HlirType::F32);
+        builder.build_store(out_i, scaled);
+        builder.build_return(None);
+
+        let mut func = builder.build();
+        func.is_kernel = true;
+        func
+    }
+
+    fn scale_function(id: FunctionId) -> HlirFunction {
+        let mut builder = FunctionBuilder::new(id, "scale", HlirType::F32);
+        let x = builder.add_param("x", HlirType::F32);
+        let entry = builder.create_block("entry");
+        builder.switch_to_block(entry);
+        let two = builder.build_const(HlirConstant::Float(2.0, HlirType::F32), HlirType::F32);
+        let scaled = builder.build_fmul(x, two, HlirType::F32);
+        builder.build_return(Some(scaled));
+        builder.build()
+    }
+
+    #[test]
+    fn test_lower_kernel_recognizes_thread_index() {
+        let kernel = lower_hlir_kernel(&add_kernel(FunctionId(0)));
+
+        assert_eq!(kernel.name, "add");
+        assert_eq!(kernel.param_count(), 3);
+        let ops: Vec<_> = kernel.blocks[0]
+            .instructions
+            .iter()
+            .map(|(_, op)| op)
+            .collect();
+        assert!(matches!(
+            ops[..4],
+            [
+                GpuOp::Param(0),
+                GpuOp::Param(1),
+                GpuOp::Param(2),
+                GpuOp::ThreadIdX
+            ]
+        ));
+        assert!(
+            ops.iter()
+                .any(|op| matches!(op, GpuOp::Call(name, _) if name == "scale"))
+        );
+        assert!(matches!(
+            kernel.blocks[0].terminator,
+            GpuTerminator::ReturnVoid
+        ));
+    }
+
+    #[test]
+    fn test_lower_module_generates_ptx_entry() {
+        let mut module = ModuleBuilder::new("vector");
+        module.add_function(add_kernel(FunctionId(0)));
+        module.add_function(scale_function(FunctionId(1)));
+        let module = lower_hlir_module(&module.build(), GpuTarget::default());
+
+        assert!(module.kernels.contains_key("add"));
+        assert!(module.device_functions.contains_key("scale"));
+
+        let ptx = PtxCodegen::new((7, 5)).generate(&module);
+        assert!(ptx.contains(".visible .entry add("), "{}", ptx);
+        assert!(ptx.contains(".func (.f32 retval) scale("), "{}", ptx);
+        assert!(ptx.contains("mov.u32 r32_0, %tid.x;"), "{}", ptx);
+        assert!(ptx.contains("ld.param.u64 r64_2, [param_out];"), "{}", ptx);
+        assert!(ptx.contains("ld.global.f32"), "{}", ptx);
+        assert!(ptx.contains("st.global"), "{}", ptx);
+    }
+
+    #[test]
+    fn test_lower_switch_chains_comparisons() {
+        let mut builder = FunctionBuilder::new(FunctionId(0), "pick", HlirType::Void);
+        let x = builder.add_param("x", HlirType::I64);
+        let entry = builder.create_block("entry");
+        let one = builder.create_block("one");
+        let other = builder.create_block("other");
+        builder.switch_to_block(entry);
+        builder.build_switch(x, other, vec![(1, one)]);
+        builder.switch_to_block(one);
+        builder.build_return(None);
+        builder.switch_to_block(other);
+        builder.build_return(None);
+        let mut func = builder.build();
+        func.is_kernel = true;
+
+        let kernel = lower_hlir_kernel(&func);
+        let labels: Vec<_> = kernel.blocks.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, ["BB0", "BB3", "BB1", "BB2"]);
+        assert!(matches!(
+            kernel.blocks[0].terminator,
+            GpuTerminator::CondBr(_, BlockId(1), BlockId(3))
+        ));
+        assert!(matches!(
+            kernel.blocks[1].terminator,
+            GpuTerminator::Br(BlockId(2))
+        ));
+    }
+}
@@ -10,6 +10,7 @@
 //! ```
 
 pub mod ir;
+pub mod lower;
 pub mod ptx;
 #[cfg(feature = "gpu")]
 pub mod spirv;
@@ -21,8 +22,11 @@ pub use ir::{
     GpuParam, GpuTarget, GpuTerminator, GpuType, MemorySpace, SharedMemDecl, ValueId,
     WarpReduceOp, WarpVoteOp,
 };
-pub use ptx::PtxCodegen;
+pub use lower::{lower_hlir_device_function, lower_hlir_kernel, lower_hlir_module};
+pub use ptx::{BankConflictWarning, PtxCodegen, PtxWarning, RegisterPressureWarning};
 #[cfg(feature = "gpu")]
 pub use spirv::SpirvCodegen;
-pub use runtime::{DeviceBuffer, GpuBackend, GpuError, GpuRuntime, Kernel, KernelArg, LaunchConfig};
+pub use runtime::{
+    DeviceBuffer, GpuBackend, GpuError, GpuRuntime, Kernel, KernelArg, LaunchConfig, LaunchLimits,
+};
 pub use intrinsics::{all_intrinsics, get_intrinsic, is_gpu_intrinsic, GpuIntrinsic};
@@ -6,7 +6,8 @@
 //! - PTX ISA: https://docs.nvidia.com/cuda/parallel-thread-execution/
 //! - CUDA C Programming Guide
 
-use std::fmt::Write;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
 
 use super::ir::*;
 
@@ -32,6 +33,24 @@ pub struct PtxCodegen {
 
     /// Type tracking for values
     value_types: Vec<GpuType>,
+
+    /// Values defined by integer constants, for struct field indices
+    int_constants: HashMap<ValueId, i64>,
+
+    /// Parameter types of the kernel or function being emitted
+    param_types: Vec<GpuType>,
+
+    /// Parameter names of the kernel or function being emitted
+    param_names: Vec<String>,
+
+    /// Element types of the current kernel's shared memory variables
+    shared_types: HashMap<String, GpuType>,
+
+    /// Performance warnings found while generating the module
+    warnings: Vec<PtxWarning>,
+
+    /// Estimated registers per thread of each kernel generated
+    register_estimates: HashMap<String, u32>,
 }
 
 #[derive(Default)]
@@ -54,12 +73,31 @@ impl PtxCodegen {
             registers: Vec::new(),
             reg_counters: RegCounters::default(),
             value_types: Vec::new(),
+            int_constants: HashMap::new(),
+            param_types: Vec::new(),
+            param_names: Vec::new(),
+            shared_types: HashMap::new(),
+            warnings: Vec::new(),
+            register_estimates: HashMap::new(),
         }
     }
 
+    /// Warnings from the last call to [`generate`](Self::generate)
+    pub fn warnings(&self) -> &[PtxWarning] {
+        &self.warnings
+    }
+
+    /// Estimated 32-bit registers each thread of `kernel` needs, from the
+    /// last call to [`generate`](Self::generate)
+    pub fn register_estimate(&self, kernel: &str) -> Option<u32> {
+        self.register_estimates.get(kernel).copied()
+    }
+
     /// Generate PTX code from GPU module
     pub fn generate(&mut self, module: &GpuModule) -> String {
         self.output.clear();
+        self.warnings.clear();
+        self.register_estimates.clear();
         self.emit_header(module);
 
         // Emit constants
@@ -105,6 +143,36 @@ impl PtxCodegen {
         self.registers.clear();
         self.reg_counters = RegCounters::default();
         self.value_types.clear();
+        self.int_constants.clear();
+        self.param_types = kernel.params.iter().map(|p| p.ty.clone()).collect();
+        self.param_names = kernel.params.iter().map(|p| p.name.clone()).collect();
+        self.shared_types = kernel
+            .shared_memory
+            .iter()
+            .map(|s| (s.name.clone(), s.elem_type.clone()))
+            .collect();
+
+        self.warnings.extend(
+            find_bank_conflicts(kernel)
+                .into_iter()
+                .map(PtxWarning::BankConflict),
+        );
+
+        let registers = estimate_registers(kernel);
+        self.register_estimates
+            .insert(kernel.name.clone(), registers);
+        if let Some(max_threads) = kernel.max_threads {
+            let budget = register_budget(self.sm_version, max_threads);
+            if registers > budget {
+                self.warnings
+                    .push(PtxWarning::RegisterPressure(RegisterPressureWarning {
+                        kernel: kernel.name.clone(),
+                        max_threads,
+                        registers,
+                        budget,
+                    }));
+            }
+        }
 
         // Kernel entry
         writeln!(self.output, ".visible .entry {}(", kernel.name).unwrap();
@@ -156,6 +224,10 @@ impl PtxCodegen {
         self.registers.clear();
         self.reg_counters = RegCounters::default();
         self.value_types.clear();
+        self.int_constants.clear();
+        self.param_types = func.params.iter().map(|p| p.ty.clone()).collect();
+        self.param_names = func.params.iter().map(|p| p.name.clone()).collect();
+        self.shared_types.clear();
 
         let ret_type = self.gpu_type_to_ptx(&func.return_type);
 
@@ -204,12 +276,13 @@ impl PtxCodegen {
         self.emit_terminator(&block.terminator);
     }
 
-    fn emit_instruction(&mut self, _value_id: ValueId, op: &GpuOp) {
+    fn emit_instruction(&mut self, value_id: ValueId, op: &GpuOp) {
         let indent = "\t".repeat(self.indent);
 
         match op {
             // Constants
             GpuOp::ConstInt(n, ty) => {
+                self.int_constants.insert(value_id, *n);
                 let reg = self.alloc_register(ty);
                 self.registers.push(reg.clone());
                 self.value_types.push(ty.clone());
@@ -1051,14 +1124,20 @@ impl PtxCodegen {
             // Memory operations
             GpuOp::Load(ptr, space) => {
                 let p = self.get_register(*ptr);
-                let reg = self.alloc_register(&GpuType::U64);
+                // Loads through typed pointers keep the pointee type
+                let ty = match self.get_value_type(*ptr) {
+                    GpuType::Ptr(pointee, _) if is_scalar(&pointee) => *pointee,
+                    _ => GpuType::U64,
+                };
+                let reg = self.alloc_register(&ty);
                 self.registers.push(reg.clone());
-                self.value_types.push(GpuType::U64);
+                self.value_types.push(ty.clone());
                 let space_str = self.memory_space_to_ptx(*space);
+                let suffix = self.type_suffix(&ty);
                 writeln!(
                     self.output,
-                    "{}ld{}.u64 {}, [{}];",
-                    indent, space_str, reg, p
+                    "{}ld{}.{} {}, [{}];",
+                    indent, space_str, suffix, reg, p
                 )
                 .unwrap();
             }
@@ -1066,10 +1145,21 @@ impl PtxCodegen {
             GpuOp::Store(ptr, val, space) => {
                 let p = self.get_register(*ptr);
                 let v = self.get_register(*val);
+                let ty = self.get_value_type(*val);
+                let suffix = if is_scalar(&ty) {
+                    self.type_suffix(&ty)
+                } else {
+                    "u64"
+                };
                 let space_str = self.memory_space_to_ptx(*space);
                 self.registers.push("_".to_string()); // Dummy for void op
                 self.value_types.push(GpuType::Void);
-                writeln!(self.output, "{}st{}.u64 [{}], {};", indent, space_str, p, v).unwrap();
+                writeln!(
+                    self.output,
+                    "{}st{}.{} [{}], {};",
+                    indent, space_str, suffix, p, v
+                )
+                .unwrap();
             }
 
             // Atomic operations
@@ -1223,18 +1313,10 @@ impl PtxCodegen {
 
             // Address computation
             GpuOp::GetElementPtr(ptr, indices) => {
-                let p = self.get_register(*ptr);
                 let reg = self.alloc_register(&GpuType::U64);
-                self.registers.push(reg.clone());
-                self.value_types
-                    .push(GpuType::Ptr(Box::new(GpuType::U8), MemorySpace::Global));
-                // Simple offset calculation
-                if indices.is_empty() {
-                    writeln!(self.output, "{}mov.u64 {}, {};", indent, reg, p).unwrap();
-                } else {
-                    let idx = self.get_register(indices[0]);
-                    writeln!(self.output, "{}add.u64 {}, {}, {};", indent, reg, p, idx).unwrap();
-                }
+                let ty = self.emit_element_ptr(&indent, &reg, *ptr, indices);
+                self.registers.push(reg);
+                self.value_types.push(ty);
             }
 
             GpuOp::PtrToInt(val) => {
@@ -1613,11 +1695,20 @@ impl PtxCodegen {
             GpuOp::Param(idx) => {
                 let reg = self.alloc_register(&GpuType::U64);
                 self.registers.push(reg.clone());
-                self.value_types.push(GpuType::U64);
+                // Pointers keep their pointee type for address computation
+                match self.param_types.get(*idx as usize) {
+                    Some(ty @ GpuType::Ptr(..)) => self.value_types.push(ty.clone()),
+                    _ => self.value_types.push(GpuType::U64),
+                }
+                let name = self
+                    .param_names
+                    .get(*idx as usize)
+                    .cloned()
+                    .unwrap_or_else(|| idx.to_string());
                 writeln!(
                     self.output,
                     "{}ld.param.u64 {}, [param_{}];",
-                    indent, reg, idx
+                    indent, reg, name
                 )
                 .unwrap();
             }
@@ -1626,8 +1717,9 @@ impl PtxCodegen {
             GpuOp::SharedAddr(name) => {
                 let reg = self.alloc_register(&GpuType::U64);
                 self.registers.push(reg.clone());
+                let elem = self.shared_types.get(name).cloned().unwrap_or(GpuType::U8);
                 self.value_types
-                    .push(GpuType::Ptr(Box::new(GpuType::U8), MemorySpace::Shared));
+                    .push(GpuType::Ptr(Box::new(elem), MemorySpace::Shared));
                 writeln!(self.output, "{}mov.u64 {}, {};", indent, reg, name).unwrap();
             }
 
@@ -1640,6 +1732,92 @@ impl PtxCodegen {
         }
     }
 
+    /// Compute `ptr` offset by `indices` into `reg`, returning the type of
+    /// the resulting pointer.
+    ///
+    /// The first index steps over whole pointees and each later one selects
+    /// an array element or struct field of the type reached so far, so the
+    /// byte offset is the sum of every index scaled by the size of what it
+    /// steps over. Struct field indices must be constants.
+    fn emit_element_ptr(
+        &mut self,
+        indent: &str,
+        reg: &str,
+        ptr: ValueId,
+        indices: &[ValueId],
+    ) -> GpuType {
+        let (mut ty, space) = match self.get_value_type(ptr) {
+            GpuType::Ptr(pointee, space) => (*pointee, space),
+            _ => (GpuType::U8, MemorySpace::Global),
+        };
+        let mut addr = self.get_register(ptr);
+
+        for (i, index) in indices.iter().enumerate() {
+            let stride = if i == 0 {
+                ty.size_bytes()
+            } else {
+                match ty {
+                    GpuType::Array(elem, _) => {
+                        ty = *elem;
+                        ty.size_bytes()
+                    }
+                    GpuType::Struct(_, fields) => {
+                        let field = self.int_constants.get(index).copied().unwrap_or(0) as usize;
+                        let offset: u32 =
+                            fields.iter().take(field).map(|(_, t)| t.size_bytes()).sum();
+                        ty = fields.get(field).map_or(GpuType::U8, |(_, t)| t.clone());
+                        if offset != 0 {
+                            writeln!(
+                                self.output,
+                                "{}add.s64 {}, {}, {};",
+                                indent, reg, addr, offset
+                            )
+                            .unwrap();
+                            addr = reg.to_string();
+                        }
+                        continue;
+                    }
+                    _ => ty.size_bytes(),
+                }
+            };
+
+            let idx = self.get_register(*index);
+            match self.get_value_type(*index) {
+                // 32-bit indices are widened by the multiply itself
+                idx_ty @ (GpuType::I32 | GpuType::U32) => {
+                    let wide = if idx_ty == GpuType::I32 { "s32" } else { "u32" };
+                    let offset = self.alloc_register(&GpuType::I64);
+                    writeln!(
+                        self.output,
+                        "{}mul.wide.{} {}, {}, {};",
+                        indent, wide, offset, idx, stride
+                    )
+                    .unwrap();
+                    writeln!(
+                        self.output,
+                        "{}add.s64 {}, {}, {};",
+                        indent, reg, addr, offset
+                    )
+                    .unwrap();
+                }
+                _ => {
+                    writeln!(
+                        self.output,
+                        "{}mad.lo.s64 {}, {}, {}, {};",
+                        indent, reg, idx, stride, addr
+                    )
+                    .unwrap();
+                }
+            }
+            addr = reg.to_string();
+        }
+
+        if addr != reg {
+            writeln!(self.output, "{}mov.u64 {}, {};", indent, reg, addr).unwrap();
+        }
+        GpuType::Ptr(Box::new(ty), space)
+    }
+
     fn emit_terminator(&mut self, term: &GpuTerminator) {
         let indent = "\t".repeat(self.indent);
 
@@ -1838,6 +2016,331 @@ impl PtxCodegen {
     }
 }
 
+/// Whether `ty` fits in a single register
+fn is_scalar(ty: &GpuType) -> bool {
+    ty.is_integer() || ty.is_float()
+}
+
+/// Performance problem found while generating PTX
+#[derive(Debug, Clone, PartialEq)]
+pub enum PtxWarning {
+    BankConflict(BankConflictWarning),
+    RegisterPressure(RegisterPressureWarning),
+}
+
+impl fmt::Display for PtxWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PtxWarning::BankConflict(w) => w.fmt(f),
+            PtxWarning::RegisterPressure(w) => w.fmt(f),
+        }
+    }
+}
+
+/// Number of shared memory banks, each one 32-bit word wide
+const SHARED_MEM_BANKS: i64 = 32;
+
+/// A shared memory access where every thread of a warp hits the same bank,
+/// serializing the access 32 ways
+#[derive(Debug, Clone, PartialEq)]
+pub struct BankConflictWarning {
+    /// Kernel containing the access
+    pub kernel: String,
+    /// Shared memory variable being accessed
+    pub array: String,
+    /// Distance in elements between the addresses of adjacent threads
+    pub stride: i64,
+}
+
+impl fmt::Display for BankConflictWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "kernel `{}` indexes shared memory `{}` by threadIdx.x * {}, so every thread \
+             of a warp accesses the same bank; pad `{}` so the stride becomes {}",
+            self.kernel,
+            self.array,
+            self.stride,
+            self.array,
+            self.stride + 1
+        )
+    }
+}
+
+/// A `max_threads` hint that leaves each thread fewer registers than the
+/// kernel keeps live, forcing ptxas to spill to local memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterPressureWarning {
+    /// Kernel with the hint
+    pub kernel: String,
+    /// Requested maximum threads per block
+    pub max_threads: u32,
+    /// Estimated 32-bit registers needed per thread
+    pub registers: u32,
+    /// Registers available per thread at `max_threads`
+    pub budget: u32,
+}
+
+impl fmt::Display for RegisterPressureWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "kernel `{}` keeps about {} registers live per thread, but with up to {} \
+             threads per block each thread gets only {}; lower `max_threads` or reduce \
+             the number of values live at once to avoid spilling",
+            self.kernel, self.registers, self.max_threads, self.budget
+        )
+    }
+}
+
+/// Register file size of one SM and the most registers a single thread
+/// may use, for compute capability `sm_version`
+fn register_limits(sm_version: (u32, u32)) -> (u32, u32) {
+    match sm_version {
+        (1, 0..=1) => (8 * 1024, 124),
+        (1, _) => (16 * 1024, 124),
+        (2, _) => (32 * 1024, 63),
+        (3, 0) => (64 * 1024, 63),
+        _ => (64 * 1024, 255),
+    }
+}
+
+/// Registers each thread can have when a block runs `max_threads` threads
+fn register_budget(sm_version: (u32, u32), max_threads: u32) -> u32 {
+    let (register_file, per_thread) = register_limits(sm_version);
+    per_thread.min(register_file / max_threads.max(1))
+}
+
+/// Estimate the 32-bit registers a thread of `kernel` needs as the most
+/// values live at any one point, with 64-bit values taking two. Blocks are
+/// taken in order and a value is live from its definition up to its last
+/// use, where the instruction's result can take over its register.
+fn estimate_registers(kernel: &GpuKernel) -> u32 {
+    // Live range and width of every value that occupies a register
+    let mut ranges: HashMap<ValueId, (usize, usize, u32)> = HashMap::new();
+    let mut wide: HashSet<ValueId> = HashSet::new();
+    let mut position = 0;
+
+    let use_at = |ranges: &mut HashMap<ValueId, (usize, usize, u32)>, v, at| {
+        if let Some((_, end, _)) = ranges.get_mut(&v) {
+            *end = (*end).max(at);
+        }
+    };
+
+    for block in &kernel.blocks {
+        for (id, op) in &block.instructions {
+            for operand in op.operands() {
+                use_at(&mut ranges, operand, position);
+            }
+
+            let is_wide = match op {
+                GpuOp::ConstInt(_, ty)
+                | GpuOp::ConstFloat(_, ty)
+                | GpuOp::Trunc(_, ty)
+                | GpuOp::ZExt(_, ty)
+                | GpuOp::SExt(_, ty)
+                | GpuOp::FpTrunc(_, ty)
+                | GpuOp::FpExt(_, ty)
+                | GpuOp::FpToSi(_, ty)
+                | GpuOp::FpToUi(_, ty)
+                | GpuOp::SiToFp(_, ty)
+                | GpuOp::UiToFp(_, ty)
+                | GpuOp::Bitcast(_, ty) => ty.size_bytes() > 4,
+                GpuOp::Param(i) => kernel
+                    .params
+                    .get(*i as usize)
+                    .is_some_and(|p| p.ty.size_bytes() > 4),
+                GpuOp::GetElementPtr(..)
+                | GpuOp::SharedAddr(_)
+                | GpuOp::PtrToInt(_)
+                | GpuOp::IntToPtr(..) => true,
+                GpuOp::Eq(..)
+                | GpuOp::Ne(..)
+                | GpuOp::Lt(..)
+                | GpuOp::Le(..)
+                | GpuOp::Gt(..)
+                | GpuOp::Ge(..)
+                | GpuOp::FEq(..)
+                | GpuOp::FNe(..)
+                | GpuOp::FLt(..)
+                | GpuOp::FLe(..)
+                | GpuOp::FGt(..)
+                | GpuOp::FGe(..)
+                | GpuOp::Load(..) => false,
+                // Arithmetic is as wide as its operands
+                _ => op.operands().first().is_some_and(|v| wide.contains(v)),
+            };
+            if is_wide {
+                wide.insert(*id);
+            }
+
+            let defines_value = !matches!(
+                op,
+                GpuOp::Store(..)
+                    | GpuOp::SyncThreads
+                    | GpuOp::SyncWarp(_)
+                    | GpuOp::MemoryFence(_)
+                    | GpuOp::SurfWrite(..)
+            );
+            if defines_value {
+                let width = if is_wide { 2 } else { 1 };
+                ranges.insert(*id, (position, position + 1, width));
+            }
+            position += 1;
+        }
+
+        match block.terminator {
+            GpuTerminator::CondBr(v, ..) | GpuTerminator::Return(v) => {
+                use_at(&mut ranges, v, position)
+            }
+            GpuTerminator::Br(_) | GpuTerminator::ReturnVoid | GpuTerminator::Unreachable => {}
+        }
+        position += 1;
+    }
+
+    let mut delta = vec![0i64; position + 1];
+    for &(start, end, width) in ranges.values() {
+        delta[start] += width as i64;
+        delta[end] -= width as i64;
+    }
+    let mut live = 0;
+    let mut peak = 0;
+    for d in delta {
+        live += d;
+        peak = peak.max(live);
+    }
+    peak as u32
+}
+
+/// Value as an affine function of `threadIdx.x`, when it is one
+#[derive(Clone, Copy)]
+struct Affine {
+    /// Coefficient of `threadIdx.x`
+    tid: i64,
+    /// Value when it is a known constant
+    constant: Option<i64>,
+}
+
+impl Affine {
+    fn uniform() -> Self {
+        Self {
+            tid: 0,
+            constant: None,
+        }
+    }
+
+    fn scale(self, k: i64) -> Self {
+        Self {
+            tid: self.tid.wrapping_mul(k),
+            constant: self.constant.map(|c| c.wrapping_mul(k)),
+        }
+    }
+}
+
+/// Find shared memory loads and stores whose address moves by a multiple
+/// of the bank count from one thread to the next
+fn find_bank_conflicts(kernel: &GpuKernel) -> Vec<BankConflictWarning> {
+    let mut values: HashMap<ValueId, Affine> = HashMap::new();
+    // Shared pointers: the variable and the element stride between threads
+    let mut pointers: HashMap<ValueId, (&str, i64)> = HashMap::new();
+    let mut warnings = Vec::new();
+
+    for (id, op) in kernel.blocks.iter().flat_map(|b| &b.instructions) {
+        let affine = |v: &ValueId| values.get(v).copied();
+        let value = match op {
+            GpuOp::ConstInt(n, _) => Some(Affine {
+                tid: 0,
+                constant: Some(*n),
+            }),
+            GpuOp::ThreadIdX => Some(Affine {
+                tid: 1,
+                constant: None,
+            }),
+            GpuOp::BlockIdX
+            | GpuOp::BlockIdY
+            | GpuOp::BlockIdZ
+            | GpuOp::BlockDimX
+            | GpuOp::BlockDimY
+            | GpuOp::BlockDimZ
+            | GpuOp::GridDimX
+            | GpuOp::GridDimY
+            | GpuOp::GridDimZ
+            | GpuOp::Param(_) => Some(Affine::uniform()),
+            GpuOp::Add(a, b) | GpuOp::Sub(a, b) => affine(a).zip(affine(b)).map(|(a, b)| {
+                let b = if matches!(op, GpuOp::Sub(..)) {
+                    b.scale(-1)
+                } else {
+                    b
+                };
+                Affine {
+                    tid: a.tid.wrapping_add(b.tid),
+                    constant: a.constant.zip(b.constant).map(|(a, b)| a.wrapping_add(b)),
+                }
+            }),
+            GpuOp::Mul(a, b) => match (affine(a), affine(b)) {
+                (
+                    Some(x),
+                    Some(Affine {
+                        constant: Some(k), ..
+                    }),
+                )
+                | (
+                    Some(Affine {
+                        constant: Some(k), ..
+                    }),
+                    Some(x),
+                ) => Some(x.scale(k)),
+                _ => None,
+            },
+            GpuOp::Shl(a, b) => match (affine(a), affine(b).and_then(|b| b.constant)) {
+                (Some(x), Some(k @ 0..=62)) => Some(x.scale(1 << k)),
+                _ => None,
+            },
+            GpuOp::Trunc(a, _) | GpuOp::ZExt(a, _) | GpuOp::SExt(a, _) => affine(a),
+            _ => None,
+        };
+        if let Some(value) = value {
+            values.insert(*id, value);
+        }
+
+        match op {
+            GpuOp::SharedAddr(name) => {
+                pointers.insert(*id, (name.as_str(), 0));
+            }
+            GpuOp::GetElementPtr(ptr, indices) => {
+                let stride = indices
+                    .iter()
+                    .map(|i| values.get(i).map(|a| a.tid))
+                    .sum::<Option<i64>>();
+                if let (Some(&(name, base)), Some(stride)) = (pointers.get(ptr), stride) {
+                    pointers.insert(*id, (name, base + stride));
+                }
+            }
+            GpuOp::Load(ptr, MemorySpace::Shared) | GpuOp::Store(ptr, _, MemorySpace::Shared) => {
+                let Some(&(name, stride)) = pointers.get(ptr) else {
+                    continue;
+                };
+                let elem_size = kernel
+                    .shared_memory
+                    .iter()
+                    .find(|s| s.name == name)
+                    .map_or(4, |s| s.elem_type.size_bytes().max(4) as i64);
+                let words = stride * elem_size / 4;
+                let warning = BankConflictWarning {
+                    kernel: kernel.name.clone(),
+                    array: name.to_string(),
+                    stride,
+                };
+                if words != 0 && words % SHARED_MEM_BANKS == 0 && !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+            _ => {}
+        }
+    }
+    warnings
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1926,6 +2429,211 @@ mod tests {
         assert!(ptx.contains("bar.sync 0"));
     }
 
+    /// Kernel loading `tile[threadIdx.x * stride]` from shared memory
+    fn strided_shared_load(stride: i64) -> GpuModule {
+        let mut module = GpuModule::new(
+            "test",
+            GpuTarget::Cuda {
+                compute_capability: (7, 5),
+            },
+        );
+
+        let mut kernel = GpuKernel::new("transpose");
+        kernel.add_shared_memory(SharedMemDecl {
+            name: "tile".to_string(),
+            elem_type: GpuType::F32,
+            size: 32 * 33,
+            align: 4,
+        });
+
+        let mut block = GpuBlock::new(BlockId(0), "entry");
+        block.add_instruction(ValueId(0), GpuOp::ThreadIdX);
+        block.add_instruction(ValueId(1), GpuOp::ConstInt(stride, GpuType::I32));
+        block.add_instruction(ValueId(2), GpuOp::Mul(ValueId(0), ValueId(1)));
+        block.add_instruction(ValueId(3), GpuOp::SharedAddr("tile".to_string()));
+        block.add_instruction(
+            ValueId(4),
+            GpuOp::GetElementPtr(ValueId(3), vec![ValueId(2)]),
+        );
+        block.add_instruction(ValueId(5), GpuOp::Load(ValueId(4), MemorySpace::Shared));
+        block.set_terminator(GpuTerminator::ReturnVoid);
+        kernel.add_block(block);
+
+        module.add_kernel(kernel);
+        module
+    }
+
+    #[test]
+    fn test_ptx_bank_conflict_warning() {
+        let mut codegen = PtxCodegen::new((7, 5));
+        codegen.generate(&strided_shared_load(32));
+
+        assert_eq!(
+            codegen.warnings(),
+            &[PtxWarning::BankConflict(BankConflictWarning {
+                kernel: "transpose".to_string(),
+                array: "tile".to_string(),
+                stride: 32,
+            })]
+        );
+        let message = codegen.warnings()[0].to_string();
+        assert!(message.contains("threadIdx.x * 32"), "{}", message);
+        assert!(message.contains("stride becomes 33"), "{}", message);
+    }
+
+    #[test]
+    fn test_ptx_padded_stride_has_no_bank_conflict() {
+        let mut codegen = PtxCodegen::new((7, 5));
+        codegen.generate(&strided_shared_load(33));
+
+        assert!(codegen.warnings().is_empty());
+    }
+
+    /// Kernel holding `live` f32 constants in registers before summing them
+    fn many_live_values(live: u32, max_threads: u32) -> GpuModule {
+        let mut module = GpuModule::new(
+            "test",
+            GpuTarget::Cuda {
+                compute_capability: (7, 5),
+            },
+        );
+
+        let mut kernel = GpuKernel::new("accumulate");
+        kernel.max_threads = Some(max_threads);
+
+        let mut block = GpuBlock::new(BlockId(0), "entry");
+        for i in 0..live {
+            block.add_instruction(ValueId(i), GpuOp::ConstFloat(i as f64, GpuType::F32));
+        }
+        let mut sum = ValueId(0);
+        for i in 1..live {
+            let next = ValueId(live + i - 1);
+            block.add_instruction(next, GpuOp::FAdd(sum, ValueId(i)));
+            sum = next;
+        }
+        block.set_terminator(GpuTerminator::ReturnVoid);
+        kernel.add_block(block);
+
+        module.add_kernel(kernel);
+        module
+    }
+
+    #[test]
+    fn test_ptx_register_pressure_warning() {
+        let mut codegen = PtxCodegen::new((7, 5));
+        codegen.generate(&many_live_values(100, 1024));
+
+        assert_eq!(codegen.register_estimate("accumulate"), Some(100));
+        assert_eq!(
+            codegen.warnings(),
+            &[PtxWarning::RegisterPressure(RegisterPressureWarning {
+                kernel: "accumulate".to_string(),
+                max_threads: 1024,
+                registers: 100,
+                budget: 64,
+            })]
+        );
+        let message = codegen.warnings()[0].to_string();
+        assert!(message.contains("about 100 registers"), "{}", message);
+    }
+
+    #[test]
+    fn test_ptx_register_pressure_within_budget() {
+        let mut codegen = PtxCodegen::new((7, 5));
+        codegen.generate(&many_live_values(100, 256));
+
+        assert!(codegen.warnings().is_empty());
+    }
+
+    /// Kernel taking a global pointer to `elem` and computing the address
+    /// of `ptr[indices...]`
+    fn element_ptr_kernel(elem: GpuType, indices: &[(i64, GpuType)]) -> GpuModule {
+        let mut module = GpuModule::new(
+            "test",
+            GpuTarget::Cuda {
+                compute_capability: (7, 5),
+            },
+        );
+
+        let mut kernel = GpuKernel::new("index");
+        kernel.add_param(GpuParam {
+            name: "data".to_string(),
+            ty: GpuType::Ptr(Box::new(elem), MemorySpace::Global),
+            space: MemorySpace::Global,
+            restrict: false,
+        });
+
+        let mut block = GpuBlock::new(BlockId(0), "entry");
+        block.add_instruction(ValueId(0), GpuOp::Param(0));
+        let mut ids = Vec::new();
+        for (i, (n, ty)) in indices.iter().enumerate() {
+            let id = ValueId(i as u32 + 1);
+            block.add_instruction(id, GpuOp::ConstInt(*n, ty.clone()));
+            ids.push(id);
+        }
+        let gep = ValueId(ids.len() as u32 + 1);
+        block.add_instruction(gep, GpuOp::GetElementPtr(ValueId(0), ids));
+        block.add_instruction(ValueId(gep.0 + 1), GpuOp::Load(gep, MemorySpace::Global));
+        block.set_terminator(GpuTerminator::ReturnVoid);
+        kernel.add_block(block);
+
+        module.add_kernel(kernel);
+        module
+    }
+
+    #[test]
+    fn test_ptx_gep_scales_index_by_element_size() {
+        let module = element_ptr_kernel(GpuType::F32, &[(3, GpuType::I64)]);
+        let ptx = PtxCodegen::new((7, 5)).generate(&module);
+
+        // data + 3 * 4, not data + 3
+        assert!(
+            ptx.contains("mad.lo.s64 r64_2, r64_1, 4, r64_0;"),
+            "{}",
+            ptx
+        );
+        assert!(!ptx.contains("add.u64"), "{}", ptx);
+    }
+
+    #[test]
+    fn test_ptx_gep_widens_32_bit_index() {
+        let module = element_ptr_kernel(GpuType::F64, &[(2, GpuType::I32)]);
+        let ptx = PtxCodegen::new((7, 5)).generate(&module);
+
+        assert!(ptx.contains("mul.wide.s32 r64_2, r32_0, 8;"), "{}", ptx);
+        assert!(ptx.contains("add.s64 r64_1, r64_0, r64_2;"), "{}", ptx);
+    }
+
+    #[test]
+    fn test_ptx_gep_accumulates_nested_offsets() {
+        // struct Particle { id: i32, pos: [f32; 3] }, data[i].pos[2]
+        let particle = GpuType::Struct(
+            "Particle".to_string(),
+            vec![
+                ("id".to_string(), GpuType::I32),
+                ("pos".to_string(), GpuType::Array(Box::new(GpuType::F32), 3)),
+            ],
+        );
+        let module = element_ptr_kernel(
+            particle,
+            &[(5, GpuType::I64), (1, GpuType::I32), (2, GpuType::I64)],
+        );
+        let ptx = PtxCodegen::new((7, 5)).generate(&module);
+
+        let steps = [
+            "mad.lo.s64 r64_3, r64_1, 16, r64_0;",
+            "add.s64 r64_3, r64_3, 4;",
+            "mad.lo.s64 r64_3, r64_2, 4, r64_3;",
+        ];
+        let mut rest = ptx.as_str();
+        for step in steps {
+            let at = rest
+                .find(step)
+                .unwrap_or_else(|| panic!("missing `{}` in\n{}", step, ptx));
+            rest = &rest[at + step.len()..];
+        }
+    }
+
     #[test]
     fn test_ptx_arithmetic() {
         let mut module = GpuModule::new(
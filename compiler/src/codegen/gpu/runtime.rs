@@ -5,6 +5,7 @@
 //! - Data transfer
 //! - Kernel launch
 
+use super::ir::GpuTarget;
 use std::ffi::c_void;
 use std::fmt;
 use std::ptr;
@@ -171,8 +172,56 @@ impl LaunchConfig {
         self.block.0 as u64 * self.block.1 as u64 * self.block.2 as u64
     }
 
+    /// Validate the launch configuration against the limits of `target`
+    pub fn validate(&self, target: &GpuTarget) -> Result<(), GpuError> {
+        let limits = LaunchLimits::for_target(target);
+        let invalid = |msg: String| Err(GpuError::InvalidConfig(format!("{} on {}", msg, target)));
+
+        let dims = [("x", 0), ("y", 1), ("z", 2)];
+        for (axis, i) in dims {
+            let block = dim(self.block, i);
+            let grid = dim(self.grid, i);
+            if block == 0 || grid == 0 {
+                return invalid(format!("Zero-sized {} dimension", axis));
+            }
+            if block > dim(limits.max_block_dim, i) {
+                return invalid(format!(
+                    "Block {} dimension ({}) exceeds maximum ({})",
+                    axis,
+                    block,
+                    dim(limits.max_block_dim, i)
+                ));
+            }
+            if grid > dim(limits.max_grid_dim, i) {
+                return invalid(format!(
+                    "Grid {} dimension ({}) exceeds maximum ({})",
+                    axis,
+                    grid,
+                    dim(limits.max_grid_dim, i)
+                ));
+            }
+        }
+
+        let threads = self.threads_per_block();
+        if threads > limits.max_threads_per_block as u64 {
+            return invalid(format!(
+                "Threads per block ({}) exceeds maximum ({})",
+                threads, limits.max_threads_per_block
+            ));
+        }
+
+        if self.shared_mem > limits.shared_mem_per_block {
+            return invalid(format!(
+                "Shared memory ({} bytes) exceeds per-block budget ({} bytes)",
+                self.shared_mem, limits.shared_mem_per_block
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Validate the launch configuration against device limits
-    pub fn validate(&self, device_info: &DeviceInfo) -> Result<(), GpuError> {
+    pub fn validate_for_device(&self, device_info: &DeviceInfo) -> Result<(), GpuError> {
         let threads = self.threads_per_block();
         if threads > device_info.max_threads_per_block as u64 {
             return Err(GpuError::InvalidConfig(format!(
@@ -198,6 +247,73 @@ impl Default for LaunchConfig {
     }
 }
 
+/// Component `i` of a three-dimensional extent
+fn dim(extent: (u32, u32, u32), i: usize) -> u32 {
+    match i {
+        0 => extent.0,
+        1 => extent.1,
+        _ => extent.2,
+    }
+}
+
+/// Kernel launch limits of a GPU target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaunchLimits {
+    /// Maximum threads in one block
+    pub max_threads_per_block: u32,
+
+    /// Maximum block size along each dimension
+    pub max_block_dim: (u32, u32, u32),
+
+    /// Maximum grid size along each dimension
+    pub max_grid_dim: (u32, u32, u32),
+
+    /// Shared memory available to one block, in bytes
+    pub shared_mem_per_block: u32,
+}
+
+impl LaunchLimits {
+    /// Limits guaranteed by `target`
+    pub fn for_target(target: &GpuTarget) -> Self {
+        match *target {
+            GpuTarget::Cuda {
+                compute_capability: (major, _),
+            } if major < 2 => Self {
+                max_threads_per_block: 512,
+                max_block_dim: (512, 512, 64),
+                max_grid_dim: (65535, 65535, 1),
+                shared_mem_per_block: 16 * 1024,
+            },
+            GpuTarget::Cuda {
+                compute_capability: (2, _),
+            } => Self {
+                max_threads_per_block: 1024,
+                max_block_dim: (1024, 1024, 64),
+                max_grid_dim: (65535, 65535, 65535),
+                shared_mem_per_block: 48 * 1024,
+            },
+            GpuTarget::Cuda { .. } => Self {
+                max_threads_per_block: 1024,
+                max_block_dim: (1024, 1024, 64),
+                max_grid_dim: (i32::MAX as u32, 65535, 65535),
+                shared_mem_per_block: 48 * 1024,
+            },
+            GpuTarget::Vulkan { .. } | GpuTarget::OpenCL { .. } => Self {
+                max_threads_per_block: 1024,
+                max_block_dim: (1024, 1024, 64),
+                max_grid_dim: (65535, 65535, 65535),
+                shared_mem_per_block: 32 * 1024,
+            },
+            GpuTarget::Rocm | GpuTarget::OneApi => Self {
+                max_threads_per_block: 1024,
+                max_block_dim: (1024, 1024, 1024),
+                max_grid_dim: (i32::MAX as u32, 65535, 65535),
+                shared_mem_per_block: 64 * 1024,
+            },
+        }
+    }
+}
+
 impl GpuRuntime {
     /// Initialize GPU runtime
     pub fn new(backend: GpuBackend, device_id: u32) -> Result<Self, GpuError> {
@@ -225,6 +341,24 @@ impl GpuRuntime {
         &self.device_info
     }
 
+    /// The compilation target matching this runtime's device
+    pub fn target(&self) -> GpuTarget {
+        let capability = self.device_info.compute_capability;
+        match self.backend {
+            GpuBackend::Cuda => GpuTarget::Cuda {
+                compute_capability: capability,
+            },
+            GpuBackend::Vulkan => GpuTarget::Vulkan {
+                version: capability,
+            },
+            GpuBackend::OpenCL => GpuTarget::OpenCL {
+                version: capability,
+            },
+            // Metal and the simulator follow the default CUDA limits
+            GpuBackend::Metal | GpuBackend::Simulated => GpuTarget::default(),
+        }
+    }
+
     /// Allocate device memory
     pub fn alloc(&self, size: usize) -> Result<DeviceBuffer, GpuError> {
         if size == 0 {
@@ -332,8 +466,9 @@ impl GpuRuntime {
         config: &LaunchConfig,
         args: &[KernelArg],
     ) -> Result<(), GpuError> {
-        // Validate configuration
-        config.validate(&self.device_info)?;
+        // Reject impossible configurations before touching the driver
+        config.validate(&self.target())?;
+        config.validate_for_device(&self.device_info)?;
 
         match self.backend {
             GpuBackend::Cuda => self.cuda_launch(kernel, config, args),
@@ -945,14 +1080,71 @@ mod tests {
 
         // Valid config
         let valid = LaunchConfig::new((1, 1, 1), (256, 1, 1));
-        assert!(valid.validate(&info).is_ok());
+        assert!(valid.validate_for_device(&info).is_ok());
 
         // Too many threads
         let invalid = LaunchConfig::new((1, 1, 1), (2048, 1, 1));
-        assert!(invalid.validate(&info).is_err());
+        assert!(invalid.validate_for_device(&info).is_err());
 
         // Too much shared memory
         let invalid = LaunchConfig::new((1, 1, 1), (256, 1, 1)).with_shared_mem(1024 * 1024);
-        assert!(invalid.validate(&info).is_err());
+        assert!(invalid.validate_for_device(&info).is_err());
+    }
+
+    #[test]
+    fn test_target_validation() {
+        let target = GpuTarget::default();
+
+        let valid = LaunchConfig::new_1d(64, 256).with_shared_mem(16 * 1024);
+        assert!(valid.validate(&target).is_ok());
+
+        let err = LaunchConfig::new_1d(1, 2048).validate(&target).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Block x dimension (2048) exceeds maximum (1024)")
+        );
+
+        // Each dimension fits, but the product does not
+        let err = LaunchConfig::new_2d((1, 1), (64, 32))
+            .validate(&target)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Threads per block (2048) exceeds maximum (1024)")
+        );
+
+        let err = LaunchConfig::new((1, 1, 1), (1, 1, 128))
+            .validate(&target)
+            .unwrap_err();
+        assert!(err.to_string().contains("Block z dimension"));
+
+        let err = LaunchConfig::new_1d(1, 256)
+            .with_shared_mem(64 * 1024)
+            .validate(&target)
+            .unwrap_err();
+        assert!(err.to_string().contains("per-block budget"));
+
+        // Older compute capabilities cap blocks at 512 threads
+        let sm_13 = GpuTarget::Cuda {
+            compute_capability: (1, 3),
+        };
+        assert!(LaunchConfig::new_1d(1, 1024).validate(&sm_13).is_err());
+        assert!(LaunchConfig::new_1d(0, 256).validate(&target).is_err());
+    }
+
+    #[test]
+    fn test_launch_rejects_oversized_block() {
+        let runtime = GpuRuntime::new(GpuBackend::Simulated, 0).unwrap();
+        let kernel = runtime.load_ptx("", "kernel").unwrap();
+
+        let err = runtime
+            .launch(&kernel, &LaunchConfig::new_1d(1, 2048), &[])
+            .unwrap_err();
+        assert!(matches!(err, GpuError::InvalidConfig(_)));
+        assert!(
+            runtime
+                .launch(&kernel, &LaunchConfig::new_1d(4, 256), &[])
+                .is_ok()
+        );
     }
 }
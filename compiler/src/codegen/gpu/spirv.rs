@@ -6,7 +6,7 @@
 //! - SPIR-V Specification: https://www.khronos.org/registry/SPIR-V/
 //! - rspirv: https://docs.rs/rspirv/
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use rspirv::binary::Assemble;
 use rspirv::dr::{Builder, Operand};
@@ -32,11 +32,20 @@ pub struct SpirvCodegen {
     functions: HashMap<String, Word>,
 
     /// Value to ID mapping
-    values: Vec<Word>,
+    values: HashMap<ValueId, Word>,
+
+    /// Type of each value, which picks the result type of arithmetic
+    value_types: HashMap<ValueId, GpuType>,
 
     /// Block to ID mapping
     blocks: HashMap<BlockId, Word>,
 
+    /// Capabilities declared so far
+    capabilities: HashSet<spirv::Capability>,
+
+    /// Workgroup size of the kernel being generated
+    local_size: [u32; 3],
+
     /// Execution model
     execution_model: spirv::ExecutionModel,
 
@@ -60,29 +69,43 @@ impl Default for SpirvTarget {
     }
 }
 
+impl SpirvTarget {
+    /// Newest SPIR-V version the target environment accepts
+    pub fn spirv_version(&self) -> (u8, u8) {
+        match self {
+            SpirvTarget::Vulkan1_0 => (1, 0),
+            SpirvTarget::Vulkan1_1 => (1, 3),
+            SpirvTarget::Vulkan1_2 => (1, 5),
+            SpirvTarget::OpenCL1_2 | SpirvTarget::OpenCL2_0 => (1, 0),
+        }
+    }
+}
+
 impl SpirvCodegen {
     pub fn new(execution_model: spirv::ExecutionModel) -> Self {
         let mut builder = Builder::new();
 
-        // Set up capabilities
-        builder.capability(spirv::Capability::Shader);
-        builder.capability(spirv::Capability::Int64);
-        builder.capability(spirv::Capability::Float64);
-
         // Memory model
         builder.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
 
-        Self {
+        let mut codegen = Self {
             builder,
             types: HashMap::new(),
             constants: HashMap::new(),
             variables: HashMap::new(),
             functions: HashMap::new(),
-            values: Vec::new(),
+            values: HashMap::new(),
+            value_types: HashMap::new(),
             blocks: HashMap::new(),
+            capabilities: HashSet::new(),
+            local_size: [1, 1, 1],
             execution_model,
             target_env: SpirvTarget::default(),
-        }
+        };
+
+        // Further capabilities are added as the types that need them are used
+        codegen.require_capability(spirv::Capability::Shader);
+        codegen
     }
 
     /// Create a new generator with a specific target
@@ -93,7 +116,19 @@ impl SpirvCodegen {
     }
 
     /// Generate SPIR-V module from GPU module
-    pub fn generate(mut self, module: &GpuModule) -> Vec<u8> {
+    pub fn generate(self, module: &GpuModule) -> Vec<u8> {
+        // Convert Vec<u32> to Vec<u8>
+        self.generate_words(module)
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect()
+    }
+
+    /// Generate SPIR-V module from GPU module as 32-bit words
+    pub fn generate_words(mut self, module: &GpuModule) -> Vec<u32> {
+        let (major, minor) = self.target_env.spirv_version();
+        self.builder.set_version(major, minor);
+
         // Generate type definitions
         self.define_types();
 
@@ -102,16 +137,24 @@ impl SpirvCodegen {
             self.define_constant(constant);
         }
 
-        // Generate kernels/functions
-        for (_, kernel) in &module.kernels {
-            self.generate_kernel(kernel);
+        // Built-in inputs are shared by every entry point
+        let interface = self.define_builtin_variables();
+
+        // Generate kernels in a stable order
+        let mut kernels: Vec<_> = module.kernels.values().collect();
+        kernels.sort_by(|a, b| a.name.cmp(&b.name));
+        for kernel in kernels {
+            self.generate_kernel(kernel, &interface);
         }
 
         // Build module (consumes the builder)
-        let spirv_module = self.builder.module();
-        let words = spirv_module.assemble();
-        // Convert Vec<u32> to Vec<u8>
-        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+        self.builder.module().assemble()
+    }
+
+    fn require_capability(&mut self, capability: spirv::Capability) {
+        if self.capabilities.insert(capability) {
+            self.builder.capability(capability);
+        }
     }
 
     fn define_types(&mut self) {
@@ -123,61 +166,21 @@ impl SpirvCodegen {
         let bool_ty = self.builder.type_bool();
         self.types.insert("bool".to_string(), bool_ty);
 
-        // Integers
-        let i8_ty = self.builder.type_int(8, 1);
-        self.types.insert("i8".to_string(), i8_ty);
-
-        let u8_ty = self.builder.type_int(8, 0);
-        self.types.insert("u8".to_string(), u8_ty);
-
-        let i16_ty = self.builder.type_int(16, 1);
-        self.types.insert("i16".to_string(), i16_ty);
-
-        let u16_ty = self.builder.type_int(16, 0);
-        self.types.insert("u16".to_string(), u16_ty);
-
+        // 32-bit scalars need no extra capability; narrower and wider
+        // types are defined on first use
         let i32_ty = self.builder.type_int(32, 1);
         self.types.insert("i32".to_string(), i32_ty);
 
         let u32_ty = self.builder.type_int(32, 0);
         self.types.insert("u32".to_string(), u32_ty);
 
-        let i64_ty = self.builder.type_int(64, 1);
-        self.types.insert("i64".to_string(), i64_ty);
-
-        let u64_ty = self.builder.type_int(64, 0);
-        self.types.insert("u64".to_string(), u64_ty);
-
-        // Floats
         let f32_ty = self.builder.type_float(32);
         self.types.insert("f32".to_string(), f32_ty);
 
-        let f64_ty = self.builder.type_float(64);
-        self.types.insert("f64".to_string(), f64_ty);
-
-        // Pointer types for storage buffer
-        let ptr_f32 = self
-            .builder
-            .type_pointer(None, spirv::StorageClass::StorageBuffer, f32_ty);
-        self.types.insert("ptr_f32_storage".to_string(), ptr_f32);
-
-        let ptr_i32 = self
-            .builder
-            .type_pointer(None, spirv::StorageClass::StorageBuffer, i32_ty);
-        self.types.insert("ptr_i32_storage".to_string(), ptr_i32);
-
-        let ptr_u32 = self
-            .builder
-            .type_pointer(None, spirv::StorageClass::StorageBuffer, u32_ty);
-        self.types.insert("ptr_u32_storage".to_string(), ptr_u32);
-
-        // Vector types
+        // Built-in ID vectors
         let vec3_u32 = self.builder.type_vector(u32_ty, 3);
         self.types.insert("vec3_u32".to_string(), vec3_u32);
 
-        let vec4_f32 = self.builder.type_vector(f32_ty, 4);
-        self.types.insert("vec4_f32".to_string(), vec4_f32);
-
         let ptr_vec3_input = self
             .builder
             .type_pointer(None, spirv::StorageClass::Input, vec3_u32);
@@ -187,13 +190,6 @@ impl SpirvCodegen {
         // Function type (void -> void)
         let fn_void = self.builder.type_function(void_ty, vec![]);
         self.types.insert("fn_void".to_string(), fn_void);
-
-        // Workgroup pointer for shared memory
-        let ptr_f32_workgroup =
-            self.builder
-                .type_pointer(None, spirv::StorageClass::Workgroup, f32_ty);
-        self.types
-            .insert("ptr_f32_workgroup".to_string(), ptr_f32_workgroup);
     }
 
     fn define_constant(&mut self, constant: &GpuConstant) {
@@ -224,17 +220,19 @@ impl SpirvCodegen {
             }
             _ => {
                 // Complex constants handled separately
-                self.types["i64"] // Placeholder
+                self.types["i32"] // Placeholder
             }
         };
 
         self.constants.insert(constant.name.clone(), id);
     }
 
-    fn generate_kernel(&mut self, kernel: &GpuKernel) {
+    fn generate_kernel(&mut self, kernel: &GpuKernel, interface: &[Word]) {
         // Reset per-kernel state
         self.values.clear();
+        self.value_types.clear();
         self.blocks.clear();
+        self.local_size = [kernel.max_threads.unwrap_or(256), 1, 1];
 
         // Create function type
         let void_ty = self.types["void"];
@@ -245,35 +243,29 @@ impl SpirvCodegen {
             .builder
             .begin_function(void_ty, None, spirv::FunctionControl::NONE, fn_ty)
             .unwrap();
+        self.builder.name(fn_id, kernel.name.clone());
 
         self.functions.insert(kernel.name.clone(), fn_id);
 
-        // Define built-in variables for compute shader
-        let interface_vars = self.define_builtin_variables();
-
-        // Create entry block
-        let entry_label = self.builder.begin_block(None).unwrap();
-
-        // Generate instructions for each block
+        // Allocate labels up front so branches can refer to later blocks
         for block in &kernel.blocks {
-            if block.id.0 == 0 {
-                // First block uses entry label
-                self.blocks.insert(block.id, entry_label);
-            } else {
-                // Create new block
-                let block_label = self.builder.begin_block(None).unwrap();
-                self.blocks.insert(block.id, block_label);
-            }
+            let label = self.builder.id();
+            self.blocks.insert(block.id, label);
         }
 
-        // Generate instructions
-        for block in &kernel.blocks {
-            let _block_id = self.blocks[&block.id];
-            // Select block would go here if we had multi-block support
+        // The entry block must come first
+        let entry = kernel.blocks.iter().filter(|b| b.id == kernel.entry);
+        let rest = kernel.blocks.iter().filter(|b| b.id != kernel.entry);
+        for block in entry.chain(rest) {
+            self.builder
+                .begin_block(Some(self.blocks[&block.id]))
+                .unwrap();
 
-            for (_value_id, op) in &block.instructions {
+            for (value_id, op) in &block.instructions {
+                let ty = self.result_type(op);
                 let id = self.generate_op(op);
-                self.values.push(id);
+                self.values.insert(*value_id, id);
+                self.value_types.insert(*value_id, ty);
             }
 
             self.generate_terminator(&block.terminator);
@@ -283,18 +275,18 @@ impl SpirvCodegen {
 
         // Add entry point
         self.builder
-            .entry_point(self.execution_model, fn_id, &kernel.name, interface_vars);
+            .entry_point(self.execution_model, fn_id, &kernel.name, interface);
 
         // Add execution mode for compute shaders
         if self.execution_model == spirv::ExecutionModel::GLCompute {
-            let local_size = kernel.max_threads.unwrap_or(256);
             self.builder
-                .execution_mode(fn_id, spirv::ExecutionMode::LocalSize, [local_size, 1, 1]);
+                .execution_mode(fn_id, spirv::ExecutionMode::LocalSize, self.local_size);
         }
     }
 
+    /// Define the built-in ID inputs. The workgroup size is known at compile
+    /// time from the `LocalSize` execution mode, so it is not among them.
     fn define_builtin_variables(&mut self) -> Vec<Word> {
-        let vec3_u32 = self.types["vec3_u32"];
         let ptr_vec3 = self.types["ptr_vec3_input"];
 
         let mut interface = Vec::new();
@@ -349,18 +341,6 @@ impl SpirvCodegen {
         self.variables.insert("NumWorkgroups".to_string(), num_wg);
         interface.push(num_wg);
 
-        // WorkgroupSize
-        let wg_size = self
-            .builder
-            .variable(ptr_vec3, None, spirv::StorageClass::Input, None);
-        self.builder.decorate(
-            wg_size,
-            spirv::Decoration::BuiltIn,
-            vec![Operand::BuiltIn(spirv::BuiltIn::WorkgroupSize)],
-        );
-        self.variables.insert("WorkgroupSize".to_string(), wg_size);
-        interface.push(wg_size);
-
         interface
     }
 
@@ -394,89 +374,97 @@ impl SpirvCodegen {
             }
 
             GpuOp::Add(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.i_add(ty, None, l, r).unwrap()
             }
 
             GpuOp::Sub(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.i_sub(ty, None, l, r).unwrap()
             }
 
             GpuOp::Mul(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.i_mul(ty, None, l, r).unwrap()
             }
 
             GpuOp::Div(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
-                self.builder.s_div(ty, None, l, r).unwrap()
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
+                if self.value_type(*lhs).is_unsigned() {
+                    self.builder.u_div(ty, None, l, r).unwrap()
+                } else {
+                    self.builder.s_div(ty, None, l, r).unwrap()
+                }
             }
 
             GpuOp::Rem(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
-                self.builder.s_rem(ty, None, l, r).unwrap()
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
+                if self.value_type(*lhs).is_unsigned() {
+                    self.builder.u_mod(ty, None, l, r).unwrap()
+                } else {
+                    self.builder.s_rem(ty, None, l, r).unwrap()
+                }
             }
 
             GpuOp::Neg(val) => {
-                let v = self.values[val.0 as usize];
-                let ty = self.types["i32"];
+                let v = self.values[val];
+                let ty = self.operand_type(*val);
                 self.builder.s_negate(ty, None, v).unwrap()
             }
 
             GpuOp::FAdd(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["f32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.f_add(ty, None, l, r).unwrap()
             }
 
             GpuOp::FSub(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["f32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.f_sub(ty, None, l, r).unwrap()
             }
 
             GpuOp::FMul(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["f32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.f_mul(ty, None, l, r).unwrap()
             }
 
             GpuOp::FDiv(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["f32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.f_div(ty, None, l, r).unwrap()
             }
 
             GpuOp::FNeg(val) => {
-                let v = self.values[val.0 as usize];
-                let ty = self.types["f32"];
+                let v = self.values[val];
+                let ty = self.operand_type(*val);
                 self.builder.f_negate(ty, None, v).unwrap()
             }
 
             GpuOp::Load(ptr, _) => {
-                let p = self.values[ptr.0 as usize];
+                let p = self.values[ptr];
                 let ty = self.types["f32"];
                 self.builder.load(ty, None, p, None, vec![]).unwrap()
             }
 
             GpuOp::Store(ptr, val, _) => {
-                let p = self.values[ptr.0 as usize];
-                let v = self.values[val.0 as usize];
+                let p = self.values[ptr];
+                let v = self.values[val];
                 self.builder.store(p, v, None, vec![]).unwrap();
                 0 // Void
             }
@@ -548,36 +536,18 @@ impl SpirvCodegen {
             }
 
             GpuOp::BlockDimX => {
-                let var = self.variables["WorkgroupSize"];
-                let vec3_ty = self.types["vec3_u32"];
                 let u32_ty = self.types["u32"];
-
-                let vec = self.builder.load(vec3_ty, None, var, None, vec![]).unwrap();
-                self.builder
-                    .composite_extract(u32_ty, None, vec, vec![0])
-                    .unwrap()
+                self.builder.constant_bit32(u32_ty, self.local_size[0])
             }
 
             GpuOp::BlockDimY => {
-                let var = self.variables["WorkgroupSize"];
-                let vec3_ty = self.types["vec3_u32"];
                 let u32_ty = self.types["u32"];
-
-                let vec = self.builder.load(vec3_ty, None, var, None, vec![]).unwrap();
-                self.builder
-                    .composite_extract(u32_ty, None, vec, vec![1])
-                    .unwrap()
+                self.builder.constant_bit32(u32_ty, self.local_size[1])
             }
 
             GpuOp::BlockDimZ => {
-                let var = self.variables["WorkgroupSize"];
-                let vec3_ty = self.types["vec3_u32"];
                 let u32_ty = self.types["u32"];
-
-                let vec = self.builder.load(vec3_ty, None, var, None, vec![]).unwrap();
-                self.builder
-                    .composite_extract(u32_ty, None, vec, vec![2])
-                    .unwrap()
+                self.builder.constant_bit32(u32_ty, self.local_size[2])
             }
 
             GpuOp::GridDimX => {
@@ -639,71 +609,71 @@ impl SpirvCodegen {
             }
 
             GpuOp::Lt(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.s_less_than(ty, None, l, r).unwrap()
             }
 
             GpuOp::Le(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.s_less_than_equal(ty, None, l, r).unwrap()
             }
 
             GpuOp::Gt(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.s_greater_than(ty, None, l, r).unwrap()
             }
 
             GpuOp::Ge(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.s_greater_than_equal(ty, None, l, r).unwrap()
             }
 
             GpuOp::Eq(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.i_equal(ty, None, l, r).unwrap()
             }
 
             GpuOp::Ne(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.i_not_equal(ty, None, l, r).unwrap()
             }
 
             GpuOp::FLt(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.f_ord_less_than(ty, None, l, r).unwrap()
             }
 
             GpuOp::FLe(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.f_ord_less_than_equal(ty, None, l, r).unwrap()
             }
 
             GpuOp::FGt(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.f_ord_greater_than(ty, None, l, r).unwrap()
             }
 
             GpuOp::FGe(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder
                     .f_ord_greater_than_equal(ty, None, l, r)
@@ -711,92 +681,92 @@ impl SpirvCodegen {
             }
 
             GpuOp::FEq(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.f_ord_equal(ty, None, l, r).unwrap()
             }
 
             GpuOp::FNe(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.f_ord_not_equal(ty, None, l, r).unwrap()
             }
 
             GpuOp::And(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.logical_and(ty, None, l, r).unwrap()
             }
 
             GpuOp::Or(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
                 let ty = self.types["bool"];
                 self.builder.logical_or(ty, None, l, r).unwrap()
             }
 
             GpuOp::Not(val) => {
-                let v = self.values[val.0 as usize];
+                let v = self.values[val];
                 let ty = self.types["bool"];
                 self.builder.logical_not(ty, None, v).unwrap()
             }
 
             GpuOp::BitAnd(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.bitwise_and(ty, None, l, r).unwrap()
             }
 
             GpuOp::BitOr(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.bitwise_or(ty, None, l, r).unwrap()
             }
 
             GpuOp::BitXor(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.bitwise_xor(ty, None, l, r).unwrap()
             }
 
             GpuOp::BitNot(val) => {
-                let v = self.values[val.0 as usize];
-                let ty = self.types["i32"];
+                let v = self.values[val];
+                let ty = self.operand_type(*val);
                 self.builder.not(ty, None, v).unwrap()
             }
 
             GpuOp::Shl(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.shift_left_logical(ty, None, l, r).unwrap()
             }
 
             GpuOp::Shr(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["i32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.shift_right_arithmetic(ty, None, l, r).unwrap()
             }
 
             GpuOp::LShr(lhs, rhs) => {
-                let l = self.values[lhs.0 as usize];
-                let r = self.values[rhs.0 as usize];
-                let ty = self.types["u32"];
+                let l = self.values[lhs];
+                let r = self.values[rhs];
+                let ty = self.operand_type(*lhs);
                 self.builder.shift_right_logical(ty, None, l, r).unwrap()
             }
 
             GpuOp::Select(cond, t, f) => {
-                let c = self.values[cond.0 as usize];
-                let tv = self.values[t.0 as usize];
-                let fv = self.values[f.0 as usize];
-                let ty = self.types["i32"];
+                let c = self.values[cond];
+                let tv = self.values[t];
+                let fv = self.values[f];
+                let ty = self.operand_type(*t);
                 self.builder.select(ty, None, c, tv, fv).unwrap()
             }
 
@@ -816,7 +786,7 @@ impl SpirvCodegen {
             }
 
             GpuTerminator::CondBr(cond, then_block, else_block) => {
-                let c = self.values[cond.0 as usize];
+                let c = self.values[cond];
                 let then_b = self.blocks[then_block];
                 let else_b = self.blocks[else_block];
                 self.builder
@@ -829,7 +799,7 @@ impl SpirvCodegen {
             }
 
             GpuTerminator::Return(val) => {
-                let v = self.values[val.0 as usize];
+                let v = self.values[val];
                 self.builder.ret_value(v).unwrap();
             }
 
@@ -839,22 +809,108 @@ impl SpirvCodegen {
         }
     }
 
-    fn gpu_type_to_spirv(&self, ty: &GpuType) -> Word {
-        match ty {
-            GpuType::Void => self.types["void"],
-            GpuType::Bool => self.types["bool"],
-            GpuType::I8 => self.types["i8"],
-            GpuType::U8 => self.types["u8"],
-            GpuType::I16 => self.types["i16"],
-            GpuType::U16 => self.types["u16"],
-            GpuType::I32 => self.types["i32"],
-            GpuType::U32 => self.types["u32"],
-            GpuType::I64 => self.types["i64"],
-            GpuType::U64 => self.types["u64"],
-            GpuType::F32 => self.types["f32"],
-            GpuType::F64 => self.types["f64"],
-            _ => self.types["i32"], // Default
+    /// Type of the value an operation produces
+    fn result_type(&self, op: &GpuOp) -> GpuType {
+        match op {
+            GpuOp::ConstInt(_, ty) | GpuOp::ConstFloat(_, ty) => ty.clone(),
+            GpuOp::ConstBool(_)
+            | GpuOp::Eq(..)
+            | GpuOp::Ne(..)
+            | GpuOp::Lt(..)
+            | GpuOp::Le(..)
+            | GpuOp::Gt(..)
+            | GpuOp::Ge(..)
+            | GpuOp::FEq(..)
+            | GpuOp::FNe(..)
+            | GpuOp::FLt(..)
+            | GpuOp::FLe(..)
+            | GpuOp::FGt(..)
+            | GpuOp::FGe(..)
+            | GpuOp::And(..)
+            | GpuOp::Or(..)
+            | GpuOp::Not(_) => GpuType::Bool,
+            GpuOp::Add(v, _)
+            | GpuOp::Sub(v, _)
+            | GpuOp::Mul(v, _)
+            | GpuOp::Div(v, _)
+            | GpuOp::Rem(v, _)
+            | GpuOp::Neg(v)
+            | GpuOp::FAdd(v, _)
+            | GpuOp::FSub(v, _)
+            | GpuOp::FMul(v, _)
+            | GpuOp::FDiv(v, _)
+            | GpuOp::FNeg(v)
+            | GpuOp::BitAnd(v, _)
+            | GpuOp::BitOr(v, _)
+            | GpuOp::BitXor(v, _)
+            | GpuOp::BitNot(v)
+            | GpuOp::Shl(v, _)
+            | GpuOp::Shr(v, _)
+            | GpuOp::LShr(v, _)
+            | GpuOp::Select(_, v, _) => self.value_type(*v),
+            GpuOp::ThreadIdX
+            | GpuOp::ThreadIdY
+            | GpuOp::ThreadIdZ
+            | GpuOp::BlockIdX
+            | GpuOp::BlockIdY
+            | GpuOp::BlockIdZ
+            | GpuOp::BlockDimX
+            | GpuOp::BlockDimY
+            | GpuOp::BlockDimZ
+            | GpuOp::GridDimX
+            | GpuOp::GridDimY
+            | GpuOp::GridDimZ => GpuType::U32,
+            GpuOp::Load(..) => GpuType::F32,
+            GpuOp::Store(..) | GpuOp::SyncThreads | GpuOp::MemoryFence(_) => GpuType::Void,
+            _ => GpuType::I32,
+        }
+    }
+
+    fn value_type(&self, value: ValueId) -> GpuType {
+        self.value_types
+            .get(&value)
+            .cloned()
+            .unwrap_or(GpuType::I32)
+    }
+
+    /// SPIR-V type of an operand, used as the result type of arithmetic
+    fn operand_type(&mut self, value: ValueId) -> Word {
+        let ty = self.value_type(value);
+        self.gpu_type_to_spirv(&ty)
+    }
+
+    fn gpu_type_to_spirv(&mut self, ty: &GpuType) -> Word {
+        let key = ty.to_string();
+        if let Some(&id) = self.types.get(&key) {
+            return id;
         }
+
+        let signedness = ty.is_signed() as u32;
+        let id = match ty {
+            GpuType::I8 | GpuType::U8 => {
+                self.require_capability(spirv::Capability::Int8);
+                self.builder.type_int(8, signedness)
+            }
+            GpuType::I16 | GpuType::U16 => {
+                self.require_capability(spirv::Capability::Int16);
+                self.builder.type_int(16, signedness)
+            }
+            GpuType::I64 | GpuType::U64 => {
+                self.require_capability(spirv::Capability::Int64);
+                self.builder.type_int(64, signedness)
+            }
+            GpuType::F16 => {
+                self.require_capability(spirv::Capability::Float16);
+                self.builder.type_float(16)
+            }
+            GpuType::F64 => {
+                self.require_capability(spirv::Capability::Float64);
+                self.builder.type_float(64)
+            }
+            _ => return self.types["i32"], // Default
+        };
+        self.types.insert(key, id);
+        id
     }
 }
 
@@ -875,7 +931,7 @@ mod tests {
 
         module.add_kernel(kernel);
 
-        let mut codegen = SpirvCodegen::new(spirv::ExecutionModel::GLCompute);
+        let codegen = SpirvCodegen::new(spirv::ExecutionModel::GLCompute);
         let spirv_bytes = codegen.generate(&module);
 
         // SPIR-V magic number: 0x07230203
@@ -901,10 +957,96 @@ mod tests {
 
         module.add_kernel(kernel);
 
-        let mut codegen = SpirvCodegen::new(spirv::ExecutionModel::GLCompute);
+        let codegen = SpirvCodegen::new(spirv::ExecutionModel::GLCompute);
         let spirv_bytes = codegen.generate(&module);
 
         // Should produce valid SPIR-V
         assert!(!spirv_bytes.is_empty());
     }
+
+    fn binary_kernel(name: &str, ty: GpuType) -> GpuModule {
+        let mut module = GpuModule::new("test", GpuTarget::Vulkan { version: (1, 2) });
+
+        let mut kernel = GpuKernel::new(name);
+
+        let mut block = GpuBlock::new(BlockId(0), "entry");
+        block.add_instruction(ValueId(0), GpuOp::ConstInt(10, ty.clone()));
+        block.add_instruction(ValueId(1), GpuOp::ConstInt(20, ty));
+        block.add_instruction(ValueId(2), GpuOp::Add(ValueId(0), ValueId(1)));
+        block.set_terminator(GpuTerminator::ReturnVoid);
+        kernel.add_block(block);
+
+        module.add_kernel(kernel);
+        module
+    }
+
+    fn capabilities(module: &rspirv::dr::Module) -> Vec<spirv::Capability> {
+        module
+            .capabilities
+            .iter()
+            .map(|inst| match inst.operands[0] {
+                Operand::Capability(c) => c,
+                ref other => panic!("unexpected capability operand {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spirv_add_kernel_module() {
+        let module = binary_kernel("add", GpuType::I32);
+        let words = SpirvCodegen::new(spirv::ExecutionModel::GLCompute).generate_words(&module);
+
+        assert_eq!(words[0], spirv::MAGIC_NUMBER);
+
+        let parsed = rspirv::dr::load_words(&words).unwrap();
+        assert_eq!(capabilities(&parsed), vec![spirv::Capability::Shader]);
+        assert!(parsed.memory_model.is_some());
+
+        assert_eq!(parsed.entry_points.len(), 1);
+        let entry = &parsed.entry_points[0];
+        assert_eq!(
+            entry.operands[0],
+            Operand::ExecutionModel(spirv::ExecutionModel::GLCompute)
+        );
+        assert_eq!(entry.operands[2], Operand::LiteralString("add".to_string()));
+
+        assert_eq!(parsed.execution_modes.len(), 1);
+        assert_eq!(
+            &parsed.execution_modes[0].operands[1..],
+            &[
+                Operand::ExecutionMode(spirv::ExecutionMode::LocalSize),
+                Operand::LiteralBit32(256),
+                Operand::LiteralBit32(1),
+                Operand::LiteralBit32(1),
+            ]
+        );
+
+        let block = &parsed.functions[0].blocks[0];
+        let add = block
+            .instructions
+            .iter()
+            .find(|inst| inst.class.opcode == spirv::Op::IAdd)
+            .expect("kernel should contain an OpIAdd");
+        let int_ty = parsed
+            .types_global_values
+            .iter()
+            .find(|inst| inst.result_id == add.result_type)
+            .unwrap();
+        assert_eq!(
+            int_ty.operands,
+            vec![Operand::LiteralBit32(32), Operand::LiteralBit32(1)]
+        );
+    }
+
+    #[test]
+    fn test_spirv_wide_types_declare_capabilities() {
+        let module = binary_kernel("add64", GpuType::I64);
+        let words = SpirvCodegen::new(spirv::ExecutionModel::GLCompute).generate_words(&module);
+
+        let parsed = rspirv::dr::load_words(&words).unwrap();
+        assert_eq!(
+            capabilities(&parsed),
+            vec![spirv::Capability::Shader, spirv::Capability::Int64]
+        );
+    }
 }
@@ -4,22 +4,28 @@
 //! HLIR to LLVM IR.
 
 use inkwell::AddressSpace;
+use inkwell::attributes::AttributeLoc;
 use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
 use inkwell::module::Module;
+use inkwell::types::{BasicType, BasicTypeEnum};
 use inkwell::values::{
-    BasicValue, BasicValueEnum, FloatValue, FunctionValue, IntValue, PointerValue,
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FloatValue, FunctionValue,
+    InstructionValue, IntValue, PointerValue,
 };
 use inkwell::{FloatPredicate, IntPredicate};
 
 use std::collections::HashMap;
+use std::path::Path;
 
+use super::debug::DebugBuilder;
 use super::types::TypeConverter;
 use crate::hlir::{
     BinaryOp, BlockId, HlirBlock, HlirConstant, HlirFunction, HlirInstr, HlirModule,
-    HlirTerminator, HlirType, Op, UnaryOp, ValueId,
+    HlirTerminator, HlirType, HlirTypeDefKind, Layouts, Op, UnaryOp, ValueId,
 };
+use crate::sourcemap::SourceMap;
 
 /// Optimization level for LLVM compilation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -64,6 +70,93 @@ impl OptLevel {
     }
 }
 
+/// Fast-math assumptions allowed on floating-point arithmetic
+///
+/// LLVM 15's C API cannot set fast-math flags on individual instructions, so
+/// each flag is applied as the equivalent function attribute, which the
+/// backend reads when it combines and selects float operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FastMathFlags {
+    /// Assume no operand or result is NaN (`nnan`)
+    pub nnan: bool,
+    /// Assume no operand or result is infinite (`ninf`)
+    pub ninf: bool,
+    /// Allow contracting a multiply and add into an FMA (`contract`)
+    pub contract: bool,
+    /// Allow reassociating operations, ignoring the sign of zero (`reassoc`)
+    pub reassoc: bool,
+}
+
+impl FastMathFlags {
+    /// Strict IEEE semantics
+    pub const NONE: Self = Self {
+        nnan: false,
+        ninf: false,
+        contract: false,
+        reassoc: false,
+    };
+
+    /// Every assumption, LLVM's `fast`
+    pub const FAST: Self = Self {
+        nnan: true,
+        ninf: true,
+        contract: true,
+        reassoc: true,
+    };
+
+    /// Default for an optimization level: `fast` at `-O3`, strict otherwise
+    pub fn for_opt_level(opt_level: OptLevel) -> Self {
+        match opt_level {
+            OptLevel::O3 => Self::FAST,
+            _ => Self::NONE,
+        }
+    }
+
+    /// Parse `fast`, `none`, or a comma-separated list such as
+    /// `nnan,contract`
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "fast" => return Some(Self::FAST),
+            "none" => return Some(Self::NONE),
+            _ => {}
+        }
+        let mut flags = Self::NONE;
+        for flag in s.split(',').map(str::trim) {
+            match flag {
+                "nnan" => flags.nnan = true,
+                "ninf" => flags.ninf = true,
+                "contract" => flags.contract = true,
+                "reassoc" => flags.reassoc = true,
+                _ => return None,
+            }
+        }
+        Some(flags)
+    }
+
+    /// Whether no assumption is allowed
+    pub fn is_empty(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// Function attributes carrying these flags
+    fn function_attributes(&self) -> Vec<&'static str> {
+        let mut attributes = Vec::new();
+        if self.nnan {
+            attributes.push("no-nans-fp-math");
+        }
+        if self.ninf {
+            attributes.push("no-infs-fp-math");
+        }
+        if self.contract {
+            attributes.push("less-precise-fpmad");
+        }
+        if self.reassoc {
+            attributes.extend(["unsafe-fp-math", "no-signed-zeros-fp-math"]);
+        }
+        attributes
+    }
+}
+
 /// LLVM Code Generator
 pub struct LLVMCodegen<'ctx> {
     /// LLVM context
@@ -84,6 +177,10 @@ pub struct LLVMCodegen<'ctx> {
     /// Value map: HLIR ValueId → LLVM Value
     values: HashMap<ValueId, BasicValueEnum<'ctx>>,
 
+    /// HLIR types of the current function's values, for ops such as
+    /// `GetFieldPtr` that need the pointee type of an operand
+    value_types: HashMap<ValueId, HlirType>,
+
     /// Block map: HLIR BlockId → LLVM BasicBlock
     blocks: HashMap<BlockId, BasicBlock<'ctx>>,
 
@@ -96,8 +193,17 @@ pub struct LLVMCodegen<'ctx> {
     /// Optimization level
     opt_level: OptLevel,
 
+    /// Fast-math assumptions for float arithmetic
+    fast_math: FastMathFlags,
+
     /// Generate debug info
     debug: bool,
+
+    /// DWARF builder, present once a source map has been attached
+    debug_info: Option<DebugBuilder<'ctx>>,
+
+    /// Source locations of the AST nodes HLIR instructions were lowered from
+    source_map: SourceMap,
 }
 
 impl<'ctx> LLVMCodegen<'ctx> {
@@ -119,16 +225,49 @@ impl<'ctx> LLVMCodegen<'ctx> {
             types,
             current_function: None,
             values: HashMap::new(),
+            value_types: HashMap::new(),
             blocks: HashMap::new(),
             functions: HashMap::new(),
             strings: HashMap::new(),
             opt_level,
+            fast_math: FastMathFlags::for_opt_level(opt_level),
             debug,
+            debug_info: None,
+            source_map: SourceMap::new(),
+        }
+    }
+
+    /// Override the fast-math assumptions implied by the optimization level
+    pub fn set_fast_math(&mut self, flags: FastMathFlags) {
+        self.fast_math = flags;
+    }
+
+    /// Attach the source map of `path` so that `!dbg` locations point at
+    /// the D source. Does nothing unless debug info was requested.
+    pub fn set_source_map(&mut self, path: &Path, source_map: SourceMap) {
+        if !self.debug {
+            return;
         }
+        self.debug_info = Some(DebugBuilder::from_path(&self.module, self.context, path));
+        self.source_map = source_map;
     }
 
     /// Compile an HLIR module to LLVM IR
     pub fn compile(&mut self, hlir: &HlirModule) -> &Module<'ctx> {
+        // Give named structs, including anonymous tuple structs, their layout
+        let layouts = Layouts::new(&hlir.types);
+        for typedef in &hlir.types {
+            if let HlirTypeDefKind::Struct(fields) = &typedef.kind {
+                let field_types: Vec<_> = fields.iter().map(|(_, ty)| ty.clone()).collect();
+                self.types.create_struct_type(
+                    &typedef.name,
+                    &field_types,
+                    layouts.struct_layout(typedef),
+                    typedef.packed,
+                );
+            }
+        }
+
         // Declare all functions first (for forward references)
         for func in &hlir.functions {
             self.declare_function(func);
@@ -144,6 +283,10 @@ impl<'ctx> LLVMCodegen<'ctx> {
             self.create_main_wrapper();
         }
 
+        if let Some(debug_info) = &self.debug_info {
+            debug_info.finalize();
+        }
+
         &self.module
     }
 
@@ -174,21 +317,65 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
         self.current_function = Some(fn_val);
 
+        for name in self.fast_math.function_attributes() {
+            let attribute = self.context.create_string_attribute(name, "true");
+            fn_val.add_attribute(AttributeLoc::Function, attribute);
+        }
+
         // Clear per-function state
         self.values.clear();
+        self.value_types.clear();
         self.blocks.clear();
 
+        for param in &func.params {
+            self.value_types.insert(param.value, param.ty.clone());
+        }
+        for instr in func.blocks.iter().flat_map(|b| &b.instructions) {
+            if let Some(result) = instr.result {
+                self.value_types.insert(result, instr.ty.clone());
+            }
+        }
+
         // Create basic blocks
         for block in &func.blocks {
             let bb = self.context.append_basic_block(fn_val, &block.label);
             self.blocks.insert(block.id, bb);
         }
 
-        // Map parameters to values
+        // Map parameters to values; structs passed by pointer are loaded
+        // once on entry so the body sees them by value
+        if let Some(entry) = func.blocks.first().and_then(|b| self.blocks.get(&b.id)) {
+            self.builder.position_at_end(*entry);
+        }
         for (i, param) in func.params.iter().enumerate() {
-            if let Some(param_val) = fn_val.get_nth_param(i as u32) {
-                self.values.insert(param.value, param_val);
-            }
+            let Some(param_val) = fn_val.get_nth_param(i as u32) else {
+                continue;
+            };
+            let value = if self.types.passed_by_pointer(&param.ty) {
+                let struct_ty = self.types.convert(&param.ty);
+                match self.builder.build_load(
+                    struct_ty,
+                    param_val.into_pointer_value(),
+                    &param.name,
+                ) {
+                    Ok(loaded) => loaded,
+                    Err(_) => continue,
+                }
+            } else {
+                param_val
+            };
+            self.values.insert(param.value, value);
+        }
+
+        // The subprogram starts at the first instruction with a known location
+        let first_line = func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .find_map(|i| self.source_map.get_node_location(i.span?))
+            .map_or(0, |loc| loc.line as u32);
+        if let Some(debug_info) = &mut self.debug_info {
+            debug_info.create_function(fn_val, &func.name, None, first_line, None, &[]);
         }
 
         // Compile each block
@@ -196,6 +383,10 @@ impl<'ctx> LLVMCodegen<'ctx> {
             self.compile_block(block);
         }
 
+        if let Some(debug_info) = &mut self.debug_info {
+            debug_info.pop_scope();
+            self.builder.unset_current_debug_location();
+        }
         self.current_function = None;
     }
 
@@ -210,6 +401,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
         // Compile instructions
         for instr in &block.instructions {
+            self.set_debug_location(instr);
             if let Some(val) = self.compile_instruction(instr) {
                 if let Some(result_id) = instr.result {
                     self.values.insert(result_id, val);
@@ -221,6 +413,20 @@ impl<'ctx> LLVMCodegen<'ctx> {
         self.compile_terminator(&block.terminator);
     }
 
+    /// Attach the source location of `instr` to the instructions built next
+    fn set_debug_location(&self, instr: &HlirInstr) {
+        let Some(debug_info) = &self.debug_info else {
+            return;
+        };
+        if let Some(loc) = instr
+            .span
+            .and_then(|node| self.source_map.get_node_location(node))
+        {
+            let location = debug_info.create_location(loc.line as u32, loc.column as u32);
+            self.builder.set_current_debug_location(location);
+        }
+    }
+
     /// Compile an instruction
     fn compile_instruction(&mut self, instr: &HlirInstr) -> Option<BasicValueEnum<'ctx>> {
         match &instr.op {
@@ -257,14 +463,16 @@ impl<'ctx> LLVMCodegen<'ctx> {
             }
 
             Op::CallDirect { name, args } => {
-                let fn_val = self.functions.get(name)?;
+                let fn_val = *self.functions.get(name)?;
+                let param_types = fn_val.get_type().get_param_types();
                 let arg_vals: Vec<_> = args
                     .iter()
                     .filter_map(|a| self.get_value(*a))
-                    .map(|v| v.into())
+                    .enumerate()
+                    .map(|(i, v)| self.pass_argument(v, param_types.get(i)))
                     .collect();
 
-                let call = self.builder.build_call(*fn_val, &arg_vals, "call").ok()?;
+                let call = self.builder.build_call(fn_val, &arg_vals, "call").ok()?;
 
                 call.try_as_basic_value().left()
             }
@@ -285,18 +493,15 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
             Op::GetFieldPtr { base, field } => {
                 let ptr_val = self.get_value(*base)?.into_pointer_value();
-                // Need struct type for GEP
-                let zero = self.context.i32_type().const_zero();
-                let field_idx = self.context.i32_type().const_int(*field as u64, false);
-
-                // Using ptr_type GEP
-                let base_ty = self.types.convert(&instr.ty);
-                unsafe {
-                    self.builder
-                        .build_gep(base_ty, ptr_val, &[zero, field_idx], "field_ptr")
-                        .ok()
-                        .map(|v| v.into())
-                }
+                // The GEP indexes into the aggregate the base points to
+                let Some(HlirType::Ptr(pointee)) = self.value_types.get(base).cloned() else {
+                    return None;
+                };
+                let aggregate_ty = self.types.convert(&pointee);
+                self.builder
+                    .build_struct_gep(aggregate_ty, ptr_val, *field as u32, "field_ptr")
+                    .ok()
+                    .map(|v| v.into())
             }
 
             Op::GetElementPtr { base, index } => {
@@ -325,6 +530,12 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 self.compile_cast(val, &instr.ty, target)
             }
 
+            Op::Bitcast { value, target } => {
+                let val = self.get_value(*value)?;
+                let target_ty = self.types.convert(target);
+                self.builder.build_bit_cast(val, target_ty, "bitcast").ok()
+            }
+
             Op::Phi { incoming } => {
                 let phi_ty = self.types.convert(&instr.ty);
                 let phi = self.builder.build_phi(phi_ty, "phi").ok()?;
@@ -420,8 +631,15 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     .filter_map(|(_, v)| self.get_value(*v))
                     .collect();
 
-                let types: Vec<_> = vals.iter().map(|v| v.get_type()).collect();
-                let struct_ty = self.context.struct_type(&types, false);
+                // Build the named type so the value matches declared
+                // parameters, returns and fields of this struct
+                let struct_ty = match self.types.convert(&instr.ty) {
+                    BasicTypeEnum::StructType(named) if !named.is_opaque() => named,
+                    _ => {
+                        let types: Vec<_> = vals.iter().map(|v| v.get_type()).collect();
+                        self.context.struct_type(&types, false)
+                    }
+                };
                 let mut struct_val = struct_ty.get_undef();
 
                 for (i, val) in vals.iter().enumerate() {
@@ -969,6 +1187,17 @@ impl<'ctx> LLVMCodegen<'ctx> {
         }
     }
 
+    /// Attach `!prof !{"branch_weights", i32 then, i32 else}` to a branch
+    fn attach_branch_weights(&self, branch: InstructionValue<'ctx>, then_w: u32, else_w: u32) {
+        let i32_ty = self.context.i32_type();
+        let node = self.context.metadata_node(&[
+            self.context.metadata_string("branch_weights").into(),
+            i32_ty.const_int(then_w as u64, false).into(),
+            i32_ty.const_int(else_w as u64, false).into(),
+        ]);
+        let _ = branch.set_metadata(node, self.context.get_kind_id("prof"));
+    }
+
     /// Compile a terminator
     fn compile_terminator(&mut self, term: &HlirTerminator) {
         match term {
@@ -994,6 +1223,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 condition,
                 then_block,
                 else_block,
+                hint,
             } => {
                 if let (Some(cond), Some(then_bb), Some(else_bb)) = (
                     self.get_value(*condition),
@@ -1001,9 +1231,12 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     self.blocks.get(else_block),
                 ) {
                     let cond_int = cond.into_int_value();
-                    let _ = self
+                    let branch = self
                         .builder
                         .build_conditional_branch(cond_int, *then_bb, *else_bb);
+                    if let (Ok(branch), Some((then_w, else_w))) = (branch, hint.weights()) {
+                        self.attach_branch_weights(branch, then_w, else_w);
+                    }
                 }
             }
 
@@ -1035,7 +1268,46 @@ impl<'ctx> LLVMCodegen<'ctx> {
             HlirTerminator::Unreachable => {
                 let _ = self.builder.build_unreachable();
             }
+
+            HlirTerminator::Trap => {
+                let trap = self.module.get_function("llvm.trap").unwrap_or_else(|| {
+                    let fn_type = self.context.void_type().fn_type(&[], false);
+                    self.module.add_function("llvm.trap", fn_type, None)
+                });
+                let _ = self.builder.build_call(trap, &[], "");
+                let _ = self.builder.build_unreachable();
+            }
+        }
+    }
+
+    /// Adapt an argument to the callee's parameter type. A struct passed
+    /// to a pointer parameter is copied to the stack and passed by address.
+    fn pass_argument(
+        &self,
+        value: BasicValueEnum<'ctx>,
+        param_ty: Option<&BasicTypeEnum<'ctx>>,
+    ) -> BasicMetadataValueEnum<'ctx> {
+        let by_pointer = matches!(param_ty, Some(BasicTypeEnum::PointerType(_)));
+        if by_pointer
+            && value.is_struct_value()
+            && let Some(slot) = self.entry_alloca(value.get_type(), "arg")
+            && self.builder.build_store(slot, value).is_ok()
+        {
+            return slot.into();
         }
+        value.into()
+    }
+
+    /// Allocate a stack slot at the start of the current function, so that
+    /// allocas inside loops do not grow the stack on every iteration
+    fn entry_alloca(&self, ty: BasicTypeEnum<'ctx>, name: &str) -> Option<PointerValue<'ctx>> {
+        let entry = self.current_function?.get_first_basic_block()?;
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => builder.position_before(&first),
+            None => builder.position_at_end(entry),
+        }
+        builder.build_alloca(ty, name).ok()
     }
 
     /// Get a value from the map
@@ -1123,4 +1395,9 @@ impl<'ctx> LLVMCodegen<'ctx> {
     pub fn opt_level(&self) -> OptLevel {
         self.opt_level
     }
+
+    /// Get the fast-math assumptions applied to float arithmetic
+    pub fn fast_math(&self) -> FastMathFlags {
+        self.fast_math
+    }
 }
@@ -6,6 +6,18 @@
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// How libraries are linked into the executable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkMode {
+    /// Whatever the system linker does by default
+    #[default]
+    Default,
+    /// Link everything statically (-static)
+    Static,
+    /// Prefer shared libraries (-Wl,-Bdynamic)
+    Dynamic,
+}
+
 /// Linker configuration
 #[derive(Debug, Clone)]
 pub struct Linker {
@@ -27,6 +39,9 @@ pub struct Linker {
     /// Generate position independent executable
     pie: bool,
 
+    /// Static or dynamic linkage
+    mode: LinkMode,
+
     /// Strip debug symbols
     strip: bool,
 
@@ -44,6 +59,7 @@ impl Linker {
             flags: Vec::new(),
             target: None,
             pie: true,
+            mode: LinkMode::Default,
             strip: false,
             verbose: false,
         }
@@ -116,6 +132,12 @@ impl Linker {
         self
     }
 
+    /// Set static or dynamic linkage
+    pub fn link_mode(mut self, mode: LinkMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     /// Enable/disable stripping
     pub fn strip(mut self, enable: bool) -> Self {
         self.strip = enable;
@@ -128,55 +150,69 @@ impl Linker {
         self
     }
 
-    /// Link object files into an executable
-    pub fn link(&self, objects: &[PathBuf], output: &Path) -> Result<(), LinkError> {
-        let mut cmd = Command::new(&self.command);
-
-        // Add object files
-        for obj in objects {
-            if !obj.exists() {
-                return Err(LinkError::ObjectNotFound(obj.clone()));
-            }
-            cmd.arg(obj);
-        }
+    /// Arguments passed to the linker command when linking an executable
+    pub fn link_args(&self, objects: &[PathBuf], output: &Path) -> Vec<String> {
+        let mut args: Vec<String> = objects.iter().map(|o| o.display().to_string()).collect();
 
         // Output file
-        cmd.arg("-o").arg(output);
+        args.push("-o".to_string());
+        args.push(output.display().to_string());
 
         // Target
         if let Some(ref target) = self.target {
-            cmd.arg("-target").arg(target);
+            args.push("-target".to_string());
+            args.push(target.clone());
         }
 
-        // PIE
-        if self.pie {
-            cmd.arg("-pie");
+        // PIE (a fully static executable cannot also be position independent)
+        if self.pie && self.mode != LinkMode::Static {
+            args.push("-pie".to_string());
         } else {
-            cmd.arg("-no-pie");
+            args.push("-no-pie".to_string());
+        }
+
+        // Linkage mode
+        match self.mode {
+            LinkMode::Default => {}
+            LinkMode::Static => args.push("-static".to_string()),
+            LinkMode::Dynamic => args.push("-Wl,-Bdynamic".to_string()),
         }
 
         // Strip
         if self.strip {
-            cmd.arg("-s");
+            args.push("-s".to_string());
         }
 
         // Library search paths
         for path in &self.lib_paths {
-            cmd.arg("-L").arg(path);
+            args.push(format!("-L{}", path.display()));
         }
 
         // Libraries
         for lib in &self.libs {
-            cmd.arg(format!("-l{}", lib));
+            args.push(format!("-l{}", lib));
         }
 
         // Extra flags
-        for flag in &self.flags {
-            cmd.arg(flag);
+        args.extend(self.flags.iter().cloned());
+
+        if self.verbose {
+            args.push("-v".to_string());
+        }
+
+        args
+    }
+
+    /// Link object files into an executable
+    pub fn link(&self, objects: &[PathBuf], output: &Path) -> Result<(), LinkError> {
+        if let Some(obj) = objects.iter().find(|obj| !obj.exists()) {
+            return Err(LinkError::ObjectNotFound(obj.clone()));
         }
 
+        let mut cmd = Command::new(&self.command);
+        cmd.args(self.link_args(objects, output));
+
         if self.verbose {
-            cmd.arg("-v");
             eprintln!("Running: {:?}", cmd);
         }
 
@@ -190,6 +226,9 @@ impl Linker {
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
             let stdout = String::from_utf8_lossy(&output.stdout);
+            if let Some(lib) = missing_library(&stderr) {
+                return Err(LinkError::LibraryNotFound(lib));
+            }
             Err(LinkError::LinkerFailed(format!("{}\n{}", stderr, stdout)))
         }
     }
@@ -347,6 +386,8 @@ pub enum LinkError {
     ObjectNotFound(PathBuf),
     /// Linker not found
     LinkerNotFound(String),
+    /// A `-l` library could not be found on the search path
+    LibraryNotFound(String),
 }
 
 impl std::fmt::Display for LinkError {
@@ -356,12 +397,38 @@ impl std::fmt::Display for LinkError {
             LinkError::LinkerFailed(e) => write!(f, "Linker failed: {}", e),
             LinkError::ObjectNotFound(p) => write!(f, "Object file not found: {}", p.display()),
             LinkError::LinkerNotFound(c) => write!(f, "Linker not found: {}", c),
+            LinkError::LibraryNotFound(l) => {
+                write!(f, "Library not found: {} (add its directory with -L)", l)
+            }
         }
     }
 }
 
 impl std::error::Error for LinkError {}
 
+/// Extract the library name from a linker's "cannot find -lfoo" diagnostic
+///
+/// Recognizes GNU ld / lld ("cannot find -lfoo", "unable to find library -lfoo")
+/// and Apple ld ("library not found for -lfoo").
+fn missing_library(stderr: &str) -> Option<String> {
+    const PATTERNS: &[&str] = &[
+        "cannot find -l",
+        "unable to find library -l",
+        "library not found for -l",
+    ];
+
+    stderr.lines().find_map(|line| {
+        PATTERNS.iter().find_map(|pattern| {
+            let start = line.find(pattern)? + pattern.len();
+            let name: String = line[start..]
+                .chars()
+                .take_while(|c| !c.is_whitespace() && *c != ':' && *c != '\'')
+                .collect();
+            (!name.is_empty()).then_some(name)
+        })
+    })
+}
+
 /// Quick link function for simple cases
 pub fn link(objects: &[PathBuf], output: &Path) -> Result<(), LinkError> {
     Linker::new().link(objects, output)
@@ -396,6 +463,44 @@ mod tests {
         assert_eq!(linker.get_command(), "clang");
     }
 
+    #[test]
+    fn test_link_args_include_libraries_and_mode() {
+        let linker = Linker::new()
+            .lib_path("/opt/blas/lib")
+            .lib("openblas")
+            .lib("m")
+            .link_mode(LinkMode::Static);
+
+        let args = linker.link_args(&[PathBuf::from("main.o")], Path::new("main"));
+        assert!(args.contains(&"-L/opt/blas/lib".to_string()));
+        assert!(args.contains(&"-lopenblas".to_string()));
+        assert!(args.contains(&"-lm".to_string()));
+        assert!(args.contains(&"-static".to_string()));
+        assert!(args.contains(&"-no-pie".to_string()));
+        assert!(!args.contains(&"-pie".to_string()));
+
+        let dynamic = Linker::new().link_mode(LinkMode::Dynamic);
+        let args = dynamic.link_args(&[PathBuf::from("main.o")], Path::new("main"));
+        assert!(args.contains(&"-Wl,-Bdynamic".to_string()));
+        assert!(!args.contains(&"-static".to_string()));
+    }
+
+    #[test]
+    fn test_missing_library_is_reported() {
+        let gnu = "/usr/bin/ld: cannot find -lopenblas: No such file or directory";
+        assert_eq!(missing_library(gnu), Some("openblas".to_string()));
+
+        let apple = "ld: library not found for -lfoo\nclang: error: linker command failed";
+        assert_eq!(missing_library(apple), Some("foo".to_string()));
+
+        assert_eq!(missing_library("undefined reference to `main'"), None);
+        assert!(
+            LinkError::LibraryNotFound("openblas".to_string())
+                .to_string()
+                .contains("openblas")
+        );
+    }
+
     #[test]
     fn test_link_error_display() {
         let err = LinkError::ObjectNotFound(PathBuf::from("/tmp/test.o"));
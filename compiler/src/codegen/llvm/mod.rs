@@ -13,6 +13,7 @@
 //! - Full type mapping from D types to LLVM types
 //! - SSA-based code generation from HLIR
 //! - Multiple optimization levels (O0-O3, Os, Oz)
+//! - Fast-math float arithmetic, by default at O3
 //! - Debug information generation (DWARF)
 //! - Cross-compilation support via target triples
 //! - Native executable linking
@@ -47,9 +48,9 @@ pub mod target;
 pub mod types;
 
 #[cfg(feature = "llvm")]
-pub use codegen::{LLVMCodegen, OptLevel};
+pub use codegen::{FastMathFlags, LLVMCodegen, OptLevel};
 #[cfg(feature = "llvm")]
-pub use linker::{LinkError, Linker};
+pub use linker::{LinkError, LinkMode, Linker};
 #[cfg(feature = "llvm")]
 pub use target::TargetConfig;
 
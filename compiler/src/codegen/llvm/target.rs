@@ -135,6 +135,13 @@ pub fn create_target_machine(triple: &str, opt_level: OptLevel) -> Result<Target
     TargetConfig::for_triple(triple).create_target_machine(opt_level)
 }
 
+/// Record the target's triple and data layout in the module, so emitted
+/// IR describes the machine it was compiled for
+pub fn configure_module(module: &Module, target: &TargetMachine) {
+    module.set_triple(&target.get_triple());
+    module.set_data_layout(&target.get_target_data().get_data_layout());
+}
+
 /// Compile module to object file
 pub fn compile_to_object(
     module: &Module,
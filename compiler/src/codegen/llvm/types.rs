@@ -10,21 +10,28 @@ use inkwell::types::{
     IntType, PointerType, StructType, VoidType,
 };
 
-use crate::hlir::HlirType;
+use crate::hlir::{HlirType, Layout};
 
 /// Type converter from HLIR types to LLVM types
 pub struct TypeConverter<'ctx> {
     context: &'ctx Context,
     /// Cached struct types by name
     struct_cache: std::collections::HashMap<String, StructType<'ctx>>,
+    /// Layout of each struct with a known body
+    struct_layouts: std::collections::HashMap<String, Layout>,
 }
 
+/// Largest aggregate passed in registers. Bigger structs are passed by
+/// pointer, as the x86-64 System V and AArch64 C ABIs do.
+const MAX_REGISTER_AGGREGATE_BYTES: u64 = 16;
+
 impl<'ctx> TypeConverter<'ctx> {
     /// Create a new type converter
     pub fn new(context: &'ctx Context) -> Self {
         Self {
             context,
             struct_cache: std::collections::HashMap::new(),
+            struct_layouts: std::collections::HashMap::new(),
         }
     }
 
@@ -179,20 +186,27 @@ impl<'ctx> TypeConverter<'ctx> {
             .struct_type(&[ptr_ty.into(), len_ty.into()], false)
     }
 
-    /// Create a struct type with fields
-    pub fn create_struct_type(&mut self, name: &str, field_types: &[HlirType]) -> StructType<'ctx> {
+    /// Create a struct type with fields, laid out as `layout` describes
+    pub fn create_struct_type(
+        &mut self,
+        name: &str,
+        field_types: &[HlirType],
+        layout: Layout,
+        packed: bool,
+    ) -> StructType<'ctx> {
         let fields: Vec<BasicTypeEnum<'ctx>> =
             field_types.iter().map(|t| self.convert(t)).collect();
+        self.struct_layouts.insert(name.to_string(), layout);
 
         if let Some(cached) = self.struct_cache.get(name) {
             // Set body if it was an opaque struct
             if cached.is_opaque() {
-                cached.set_body(&fields, false);
+                cached.set_body(&fields, packed);
             }
             *cached
         } else {
             let struct_ty = self.context.opaque_struct_type(name);
-            struct_ty.set_body(&fields, false);
+            struct_ty.set_body(&fields, packed);
             self.struct_cache.insert(name.to_string(), struct_ty);
             struct_ty
         }
@@ -204,8 +218,16 @@ impl<'ctx> TypeConverter<'ctx> {
         params: &[HlirType],
         return_type: &HlirType,
     ) -> FunctionType<'ctx> {
-        let param_types: Vec<BasicMetadataTypeEnum<'ctx>> =
-            params.iter().map(|t| self.convert_to_metadata(t)).collect();
+        let param_types: Vec<BasicMetadataTypeEnum<'ctx>> = params
+            .iter()
+            .map(|t| {
+                if self.passed_by_pointer(t) {
+                    self.generic_ptr_type().into()
+                } else {
+                    self.convert_to_metadata(t)
+                }
+            })
+            .collect();
 
         match return_type {
             HlirType::Void => self.void_type().fn_type(&param_types, false),
@@ -216,6 +238,13 @@ impl<'ctx> TypeConverter<'ctx> {
         }
     }
 
+    /// Whether a parameter of type `ty` is passed as a pointer to a copy
+    /// rather than by value. Struct returns stay by value; LLVM demotes large
+    /// ones to a hidden return pointer itself.
+    pub fn passed_by_pointer(&self, ty: &HlirType) -> bool {
+        matches!(ty, HlirType::Struct(_)) && self.size_bytes(ty) > MAX_REGISTER_AGGREGATE_BYTES
+    }
+
     /// Get size of a type in bits
     pub fn size_bits(&self, ty: &HlirType) -> u64 {
         match ty {
@@ -228,7 +257,10 @@ impl<'ctx> TypeConverter<'ctx> {
             HlirType::I128 | HlirType::U128 => 128,
             HlirType::Ptr(_) => 64, // Assuming 64-bit pointers
             HlirType::Array(elem, size) => self.size_bits(elem) * (*size as u64),
-            HlirType::Struct(_) => 64, // Conservative estimate
+            HlirType::Struct(name) => self
+                .struct_layouts
+                .get(name)
+                .map_or(64, |layout| layout.size * 8),
             HlirType::Tuple(elems) => elems.iter().map(|e| self.size_bits(e)).sum(),
             HlirType::Function { .. } => 64, // Function pointer
         }
@@ -253,7 +285,10 @@ impl<'ctx> TypeConverter<'ctx> {
             | HlirType::U128
             | HlirType::Ptr(_) => 8,
             HlirType::Array(elem, _) => self.align_bytes(elem),
-            HlirType::Struct(_) => 8, // Conservative
+            HlirType::Struct(name) => self
+                .struct_layouts
+                .get(name)
+                .map_or(8, |layout| layout.align), // Conservative
             HlirType::Tuple(elems) => elems.iter().map(|e| self.align_bytes(e)).max().unwrap_or(1),
             HlirType::Function { .. } => 8,
         }
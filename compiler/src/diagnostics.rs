@@ -9,6 +9,8 @@
 
 use crate::common::Span;
 use miette::{Diagnostic, NamedSource, Severity, SourceSpan};
+use serde::Serialize;
+use std::fmt;
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -30,6 +32,17 @@ impl SourceFile {
     pub fn to_named_source(&self) -> NamedSource<String> {
         NamedSource::new(self.name.clone(), self.content.to_string())
     }
+
+    /// 1-based line and column of the byte `offset`. Columns count bytes.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let before = &self.content.as_bytes()[..offset.min(self.content.len())];
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let line = before.iter().filter(|&&b| b == b'\n').count();
+        (line + 1, before.len() - line_start + 1)
+    }
 }
 
 /// Convert our Span to miette's SourceSpan
@@ -44,7 +57,7 @@ impl From<Span> for SourceSpan {
 pub enum CompileError {
     // === Parse Errors ===
     #[error("Unexpected token: expected {expected}, found {found}")]
-    #[diagnostic(code(parse::unexpected_token))]
+    #[diagnostic(code(D0001))]
     UnexpectedToken {
         expected: String,
         found: String,
@@ -55,7 +68,7 @@ pub enum CompileError {
     },
 
     #[error("Unexpected end of file")]
-    #[diagnostic(code(parse::unexpected_eof))]
+    #[diagnostic(code(D0002))]
     UnexpectedEof {
         #[label("expected more tokens")]
         span: SourceSpan,
@@ -65,10 +78,7 @@ pub enum CompileError {
 
     // === Resolution Errors ===
     #[error("Undefined variable `{name}`")]
-    #[diagnostic(
-        code(resolve::undefined_var),
-        help("did you mean to declare this variable with `let`?")
-    )]
+    #[diagnostic(code(D0101), help("did you mean to declare this variable with `let`?"))]
     UndefinedVariable {
         name: String,
         #[label("not found in this scope")]
@@ -78,7 +88,7 @@ pub enum CompileError {
     },
 
     #[error("Undefined type `{name}`")]
-    #[diagnostic(code(resolve::undefined_type))]
+    #[diagnostic(code(D0102))]
     UndefinedType {
         name: String,
         #[label("type not found")]
@@ -88,7 +98,7 @@ pub enum CompileError {
     },
 
     #[error("Duplicate definition of `{name}`")]
-    #[diagnostic(code(resolve::duplicate_def))]
+    #[diagnostic(code(D0103))]
     DuplicateDefinition {
         name: String,
         #[label("redefined here")]
@@ -100,11 +110,11 @@ pub enum CompileError {
     },
 
     // === Type Errors ===
-    #[error("Type mismatch: expected `{expected}`, found `{found}`")]
-    #[diagnostic(code(typecheck::mismatch))]
+    #[error("{message}")]
+    #[diagnostic(code(D0201))]
     TypeMismatch {
+        message: String,
         expected: String,
-        found: String,
         #[label("expected `{expected}`")]
         span: SourceSpan,
         #[source_code]
@@ -113,22 +123,18 @@ pub enum CompileError {
         help: Option<String>,
     },
 
-    #[error("Cannot unify `{t1}` with `{t2}`")]
-    #[diagnostic(code(typecheck::unification_failed))]
+    #[error("{message}")]
+    #[diagnostic(code(D0202))]
     UnificationFailed {
-        t1: String,
-        t2: String,
-        #[label("type mismatch here")]
+        message: String,
+        #[label("types cannot be unified here")]
         span: SourceSpan,
         #[source_code]
         src: NamedSource<String>,
     },
 
     #[error("Missing type annotation")]
-    #[diagnostic(
-        code(typecheck::annotation_required),
-        help("add a type annotation: `let x: Type = ...`")
-    )]
+    #[diagnostic(code(D0204), help("add a type annotation: `let x: Type = ...`"))]
     AnnotationRequired {
         #[label("cannot infer type")]
         span: SourceSpan,
@@ -139,7 +145,7 @@ pub enum CompileError {
     // === Effect Errors ===
     #[error("Unhandled effect `{effect}`")]
     #[diagnostic(
-        code(effect::unhandled),
+        code(D0301),
         help(
             "either handle this effect with `with handler {{ ... }}` or add it to the function signature"
         )
@@ -153,7 +159,7 @@ pub enum CompileError {
     },
 
     #[error("Effect `{effect}` not declared in function signature")]
-    #[diagnostic(code(effect::undeclared))]
+    #[diagnostic(code(D0302))]
     UndeclaredEffect {
         effect: String,
         #[label("this operation has effect `{effect}`")]
@@ -165,7 +171,7 @@ pub enum CompileError {
     },
 
     #[error("Cannot perform `{effect}` in pure context")]
-    #[diagnostic(code(effect::pure_context))]
+    #[diagnostic(code(D0303))]
     EffectInPureContext {
         effect: String,
         #[label("effectful operation here")]
@@ -176,7 +182,7 @@ pub enum CompileError {
 
     // === Ownership Errors ===
     #[error("Use of moved value `{name}`")]
-    #[diagnostic(code(ownership::use_after_move))]
+    #[diagnostic(code(D0401))]
     UseAfterMove {
         name: String,
         #[label("value used here after move")]
@@ -188,7 +194,7 @@ pub enum CompileError {
     },
 
     #[error("Cannot borrow `{name}` as mutable because it is already borrowed")]
-    #[diagnostic(code(ownership::already_borrowed))]
+    #[diagnostic(code(D0402))]
     AlreadyBorrowed {
         name: String,
         #[label("cannot borrow as mutable")]
@@ -200,7 +206,7 @@ pub enum CompileError {
     },
 
     #[error("Cannot borrow `{name}` as mutable more than once")]
-    #[diagnostic(code(ownership::double_mut_borrow))]
+    #[diagnostic(code(D0403))]
     DoubleMutBorrow {
         name: String,
         #[label("second mutable borrow here")]
@@ -211,12 +217,46 @@ pub enum CompileError {
         src: NamedSource<String>,
     },
 
-    // === Linearity Errors ===
-    #[error("Linear value `{name}` used more than once")]
+    #[error("Cannot borrow `{name}` as immutable because it is also borrowed as mutable")]
+    #[diagnostic(code(D0404))]
+    AlreadyMutablyBorrowed {
+        name: String,
+        #[label("immutable borrow here")]
+        span: SourceSpan,
+        #[label("mutable borrow here, still in use")]
+        mut_span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+    },
+
+    #[error("Cannot move out of `{name}` because it is borrowed")]
+    #[diagnostic(code(D0405))]
+    MoveWhileBorrowed {
+        name: String,
+        #[label("move out of `{name}` here")]
+        span: SourceSpan,
+        #[label("borrow of `{name}` here, still in use")]
+        borrow_span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+    },
+
+    #[error("Reference to local `{name}` outlives the function")]
     #[diagnostic(
-        code(linear::multiple_use),
-        help("linear values must be used exactly once")
+        code(D0406),
+        help("return an owned value, or borrow from a parameter passed by reference")
     )]
+    DanglingReference {
+        name: String,
+        #[label("`{name}` is dropped when the function returns")]
+        span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+    },
+
+    // === Linearity Errors ===
+    #[error("Linear value `{name}` used more than once")]
+    #[diagnostic(code(D0501), help("linear values must be used exactly once"))]
     LinearMultipleUse {
         name: String,
         #[label("second use here")]
@@ -229,7 +269,7 @@ pub enum CompileError {
 
     #[error("Linear value `{name}` not consumed")]
     #[diagnostic(
-        code(linear::not_consumed),
+        code(D0502),
         help("linear values must be explicitly consumed before going out of scope")
     )]
     LinearNotConsumed {
@@ -243,7 +283,7 @@ pub enum CompileError {
     },
 
     #[error("Affine value `{name}` used more than once")]
-    #[diagnostic(code(affine::multiple_use))]
+    #[diagnostic(code(D0503))]
     AffineMultipleUse {
         name: String,
         #[label("second use here")]
@@ -256,7 +296,7 @@ pub enum CompileError {
 
     // === Unit Errors ===
     #[error("Unit mismatch: expected `{expected}`, found `{found}`")]
-    #[diagnostic(code(unit::mismatch))]
+    #[diagnostic(code(D0203))]
     UnitMismatch {
         expected: String,
         found: String,
@@ -270,7 +310,7 @@ pub enum CompileError {
 
     #[error("Cannot add values with different units: `{u1}` and `{u2}`")]
     #[diagnostic(
-        code(unit::incompatible_add),
+        code(D0205),
         help("arithmetic operations require operands with compatible units")
     )]
     IncompatibleUnits {
@@ -285,7 +325,7 @@ pub enum CompileError {
     },
 
     #[error("Unknown unit `{unit}`")]
-    #[diagnostic(code(unit::unknown))]
+    #[diagnostic(code(D0206))]
     UnknownUnit {
         unit: String,
         #[label("unknown unit")]
@@ -297,7 +337,7 @@ pub enum CompileError {
     },
 
     #[error("Cannot convert from `{from}` to `{to}`")]
-    #[diagnostic(code(unit::incompatible_conversion))]
+    #[diagnostic(code(D0207))]
     IncompatibleConversion {
         from: String,
         to: String,
@@ -310,7 +350,7 @@ pub enum CompileError {
     },
 
     #[error("Division by zero in unit computation")]
-    #[diagnostic(code(unit::division_by_zero))]
+    #[diagnostic(code(D0208))]
     UnitDivisionByZero {
         #[label("zero divisor here")]
         span: SourceSpan,
@@ -319,7 +359,7 @@ pub enum CompileError {
     },
 
     #[error("Unit inference failed for `{name}`")]
-    #[diagnostic(code(unit::inference_failed), help("add an explicit unit annotation"))]
+    #[diagnostic(code(D0209), help("add an explicit unit annotation"))]
     UnitInferenceFailed {
         name: String,
         #[label("could not infer unit")]
@@ -330,7 +370,7 @@ pub enum CompileError {
 
     // === Parser Recovery Errors ===
     #[error("Syntax error: {message}")]
-    #[diagnostic(code(parse::syntax_error))]
+    #[diagnostic(code(D0003))]
     SyntaxError {
         message: String,
         #[label("{message}")]
@@ -342,7 +382,7 @@ pub enum CompileError {
     },
 
     #[error("Unclosed delimiter `{open}`")]
-    #[diagnostic(code(parse::unclosed_delimiter))]
+    #[diagnostic(code(D0004))]
     UnclosedDelimiter {
         open: String,
         expected: String,
@@ -355,10 +395,7 @@ pub enum CompileError {
     },
 
     #[error("Missing semicolon")]
-    #[diagnostic(
-        code(parse::missing_semicolon),
-        help("add `;` at the end of the statement")
-    )]
+    #[diagnostic(code(D0005), help("add `;` at the end of the statement"))]
     MissingSemicolon {
         #[label("expected `;` here")]
         span: SourceSpan,
@@ -367,7 +404,7 @@ pub enum CompileError {
     },
 
     #[error("Invalid token in expression")]
-    #[diagnostic(code(parse::invalid_token))]
+    #[diagnostic(code(D0006))]
     InvalidToken {
         found: String,
         #[label("unexpected `{found}`")]
@@ -378,9 +415,71 @@ pub enum CompileError {
         expected_tokens: Option<String>,
     },
 
+    #[error("Comparison operators cannot be chained")]
+    #[diagnostic(code(D0007))]
+    ChainedComparison {
+        #[label("chained comparison")]
+        span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+        #[help]
+        suggestion: String,
+    },
+
+    // === Constant Evaluation Errors ===
+    #[error("Constant evaluation failed: {message}")]
+    #[diagnostic(code(D0210))]
+    ConstEval {
+        message: String,
+        #[label("{message}")]
+        span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+    },
+
+    // === Lint Warnings ===
+    #[error("Unused {kind} `{name}`")]
+    #[diagnostic(
+        code(D0601),
+        severity(Warning),
+        help("if this is intentional, prefix it with an underscore: `_{name}`")
+    )]
+    UnusedBinding {
+        kind: String,
+        name: String,
+        #[label("never used")]
+        span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+    },
+
+    #[error("Unknown attribute `{name}`")]
+    #[diagnostic(code(D0602), severity(Warning), help("the attribute is ignored"))]
+    UnknownAttribute {
+        name: String,
+        #[label("not recognised by the compiler")]
+        span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+    },
+
+    #[error("Effect `{effect}` is declared but never performed")]
+    #[diagnostic(
+        code(D0603),
+        severity(Warning),
+        help("remove `{effect}` from the `with` clause")
+    )]
+    UnusedEffect {
+        effect: String,
+        #[label("not performed by the body or anything it calls")]
+        span: SourceSpan,
+        #[source_code]
+        src: NamedSource<String>,
+    },
+
     // === Generic Errors ===
     #[error("{message}")]
-    #[diagnostic(code(general::error))]
+    #[diagnostic(code(D0000))]
     General {
         message: String,
         #[label("{label}")]
@@ -532,3 +631,449 @@ impl Reporter {
         &self.errors
     }
 }
+
+// ==================== JSON Output ====================
+
+/// A diagnostic in the form printed by `dc check --json`, which writes a
+/// JSON array of them to stdout:
+///
+/// ```json
+/// [
+///   {
+///     "code": "D0203",
+///     "severity": "error",
+///     "message": "unit mismatch: expected `mg`, found `mL`",
+///     "file": "dose.d",
+///     "span": {
+///       "start": { "line": 3, "column": 20 },
+///       "end": { "line": 3, "column": 33 }
+///     },
+///     "help": null
+///   }
+/// ]
+/// ```
+///
+/// The schema is stable: fields keep their names and meaning, and new
+/// fields are only ever added. `code` is one of [`ERROR_CODES`], or `null`
+/// for diagnostics without a code; `span` is `null` for diagnostics that
+/// have no location.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JsonDiagnostic {
+    pub code: Option<String>,
+    pub severity: JsonSeverity,
+    pub message: String,
+    /// Path of the checked file, as given on the command line
+    pub file: String,
+    pub span: Option<JsonSpan>,
+    pub help: Option<String>,
+}
+
+/// Severity of a [`JsonDiagnostic`], serialized in lowercase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonSeverity {
+    Error,
+    Warning,
+    Advice,
+}
+
+/// Source range of a [`JsonDiagnostic`]. `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct JsonSpan {
+    pub start: JsonPosition,
+    pub end: JsonPosition,
+}
+
+/// 1-based line and column. Columns count bytes from the start of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct JsonPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl JsonSpan {
+    /// Range of `span` in `file`
+    pub fn new(file: &SourceFile, span: Span) -> Self {
+        let position = |offset| {
+            let (line, column) = file.line_col(offset);
+            JsonPosition { line, column }
+        };
+        Self {
+            start: position(span.start),
+            end: position(span.end),
+        }
+    }
+}
+
+impl JsonDiagnostic {
+    /// Convert `diagnostic`, reported against `file`. A diagnostic that
+    /// only groups related ones, such as [`crate::resolve::ResolveErrors`],
+    /// is replaced by them.
+    pub fn from_diagnostic(diagnostic: &dyn Diagnostic, file: &SourceFile) -> Vec<Self> {
+        let related: Vec<_> = diagnostic.related().into_iter().flatten().collect();
+        if !related.is_empty() && diagnostic.labels().is_none() {
+            return related
+                .into_iter()
+                .flat_map(|d| Self::from_diagnostic(d, file))
+                .collect();
+        }
+
+        let severity = match diagnostic.severity() {
+            None | Some(Severity::Error) => JsonSeverity::Error,
+            Some(Severity::Warning) => JsonSeverity::Warning,
+            Some(Severity::Advice) => JsonSeverity::Advice,
+        };
+        let span = diagnostic
+            .labels()
+            .and_then(|mut labels| labels.next())
+            .map(|label| {
+                let span = Span::new(label.offset(), label.offset() + label.len());
+                JsonSpan::new(file, span)
+            });
+        vec![Self {
+            code: diagnostic.code().map(|code| code.to_string()),
+            severity,
+            message: diagnostic.to_string(),
+            file: file.name.clone(),
+            span,
+            help: diagnostic.help().map(|help| help.to_string()),
+        }]
+    }
+}
+
+// ==================== Error Codes ====================
+
+/// Stable identifier of a kind of diagnostic, with the long-form explanation
+/// shown by `dc explain`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    /// Code reported with the diagnostic, e.g. `D0101`
+    pub code: &'static str,
+    /// One-line summary
+    pub title: &'static str,
+    /// What the diagnostic means and how to fix it
+    pub explanation: &'static str,
+    /// Program that produces the diagnostic
+    pub example: &'static str,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.code, self.title)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.explanation)?;
+        if self.example.is_empty() {
+            return Ok(());
+        }
+        writeln!(f)?;
+        writeln!(f, "Erroneous code example:")?;
+        writeln!(f)?;
+        for line in self.example.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Look up the explanation of an error code such as `D0203`
+pub fn explain(code: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES
+        .iter()
+        .find(|c| c.code.eq_ignore_ascii_case(code))
+}
+
+/// Every error code, grouped by the pass that reports it: `D00xx` parsing,
+/// `D01xx` name resolution, `D02xx` types and units, `D03xx` effects,
+/// `D04xx` ownership, `D05xx` linearity and `D06xx` lints.
+///
+/// The codes given to `#[diagnostic(code(...))]` must appear here, which
+/// `tests/diagnostics_tests.rs` checks.
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: "D0000",
+        title: "error",
+        explanation: "An error that has no more specific code. The message describes the \
+                      problem.",
+        example: "",
+    },
+    // === Parsing ===
+    ErrorCode {
+        code: "D0001",
+        title: "unexpected token",
+        explanation: "The parser found a token that cannot appear at this point in the \
+                      program. The message names what was expected instead.",
+        example: "fn main() -> i64 { let = 1; 0 }",
+    },
+    ErrorCode {
+        code: "D0002",
+        title: "unexpected end of file",
+        explanation: "The file ended in the middle of an item or expression, usually \
+                      because a construct was left unfinished.",
+        example: "fn main() -> i64 {",
+    },
+    ErrorCode {
+        code: "D0003",
+        title: "syntax error",
+        explanation: "The source does not follow the grammar of the language. The label \
+                      points at the first place the parser could not continue.",
+        example: "fn main() -> i64 { 1 + }",
+    },
+    ErrorCode {
+        code: "D0004",
+        title: "unclosed delimiter",
+        explanation: "A `(`, `[` or `{` was opened but never closed. Add the matching \
+                      closing delimiter.",
+        example: "fn main() -> i64 { (1 + 2 }",
+    },
+    ErrorCode {
+        code: "D0005",
+        title: "missing semicolon",
+        explanation: "Two statements follow each other without a `;` between them.",
+        example: "fn main() -> i64 { let x = 1 let y = 2; x + y }",
+    },
+    ErrorCode {
+        code: "D0006",
+        title: "invalid token in expression",
+        explanation: "A token that cannot start or continue an expression was found where \
+                      an expression was expected.",
+        example: "fn main() -> i64 { 1 + ; }",
+    },
+    ErrorCode {
+        code: "D0007",
+        title: "chained comparison",
+        explanation: "Comparison operators cannot follow one another without parentheses. \
+                      `a < b < c` would compare the `bool` result of `a < b` with `c`; \
+                      write `a < b && b < c` instead, or parenthesize the first comparison \
+                      if comparing its result is intended.",
+        example: "fn main() -> bool { 1 < 2 < 3 }",
+    },
+    // === Name resolution ===
+    ErrorCode {
+        code: "D0101",
+        title: "unknown variable",
+        explanation: "A name was used as a value, but no variable, parameter, function or \
+                      constant with that name is in scope. Check the spelling, or declare \
+                      the variable with `let` before using it.",
+        example: "fn main() -> i64 {\n    let total = 1;\n    totl + 1\n}",
+    },
+    ErrorCode {
+        code: "D0102",
+        title: "unknown type",
+        explanation: "A name was used as a type, but no struct, enum, alias or trait with \
+                      that name is defined.",
+        example: "fn area(s: Shape) -> f64 { 0.0 }",
+    },
+    ErrorCode {
+        code: "D0103",
+        title: "duplicate definition",
+        explanation: "Two items in the same scope have the same name. Rename or remove one \
+                      of them.",
+        example: "fn f() -> i64 { 1 }\nfn f() -> i64 { 2 }",
+    },
+    ErrorCode {
+        code: "D0104",
+        title: "type used as a value",
+        explanation: "A type name appeared where a value was expected. To build a value of \
+                      a struct, use a struct literal such as `Point { x: 0, y: 0 }`.",
+        example: "struct Point { x: i64, y: i64 }\nfn main() -> i64 { let p = Point; 0 }",
+    },
+    ErrorCode {
+        code: "D0105",
+        title: "value used as a type",
+        explanation: "A variable or function name appeared where a type was expected.",
+        example: "fn f() -> i64 { 1 }\nfn g(x: f) -> i64 { 0 }",
+    },
+    // === Types and units ===
+    ErrorCode {
+        code: "D0201",
+        title: "type mismatch",
+        explanation: "An expression has a different type from the one its context \
+                      requires, such as an argument that does not match the parameter or \
+                      a body that does not match the return type. Convert the value with \
+                      `as` or change one of the types.",
+        example: "fn main() -> i64 { true }",
+    },
+    ErrorCode {
+        code: "D0202",
+        title: "cannot unify types",
+        explanation: "Inference would need a type to contain itself, such as `T` being \
+                      equal to `List<T>`, which has no finite solution. Check that the \
+                      value has the type written in its annotation.",
+        example: "enum List<T> { Nil, Cons(T, i64) }\nfn wrap<T>(x: T) -> T {\n    let list: List<T> = List::Nil;\n    list\n}",
+    },
+    ErrorCode {
+        code: "D0203",
+        title: "unit mismatch",
+        explanation: "A quantity has a different unit of measure from the one expected. \
+                      Units are part of a value's type, so a length in `m` cannot be used \
+                      where a mass in `kg` is required. Convert the value to the expected \
+                      unit, or fix the annotation.",
+        example: "fn main() -> f64 {\n    let t: f64@s = 3.0_m;\n    t\n}",
+    },
+    ErrorCode {
+        code: "D0204",
+        title: "type annotation required",
+        explanation: "The type of a binding cannot be inferred from how it is used. Add an \
+                      annotation such as `let x: i64 = ...`.",
+        example: "fn main() -> i64 { let v = Vec::new(); 0 }",
+    },
+    ErrorCode {
+        code: "D0205",
+        title: "incompatible units in arithmetic",
+        explanation: "Quantities can only be added or subtracted when they have the same \
+                      dimension. Convert one operand, or check that the right quantities \
+                      are being combined.",
+        example: "fn main() -> f64 {\n    let a: f64@m = 1.0_m;\n    let b: f64@s = 2.0_s;\n    a + b\n}",
+    },
+    ErrorCode {
+        code: "D0206",
+        title: "unknown unit",
+        explanation: "A unit annotation names a unit that is not defined. The help text \
+                      suggests a similarly spelled unit when there is one.",
+        example: "fn main() -> f64 { let x: f64@meterz = 1.0; x }",
+    },
+    ErrorCode {
+        code: "D0207",
+        title: "incompatible unit conversion",
+        explanation: "A conversion between units of different dimensions was requested, \
+                      such as from a length to a time.",
+        example: "fn main() -> f64 { (3.0_m) as f64@s }",
+    },
+    ErrorCode {
+        code: "D0208",
+        title: "division by zero in unit computation",
+        explanation: "A unit expression divides by a zero constant, so the resulting scale \
+                      factor is undefined.",
+        example: "fn main() -> f64 { 1.0_m / 0.0 }",
+    },
+    ErrorCode {
+        code: "D0209",
+        title: "unit inference failed",
+        explanation: "No unit can be inferred for a binding from its uses. Add an explicit \
+                      unit annotation.",
+        example: "fn main() -> f64 { let x = 1.0; x + 2.0_m - 3.0_kg }",
+    },
+    ErrorCode {
+        code: "D0210",
+        title: "constant evaluation failed",
+        explanation: "The value of a `const` or array length could not be computed at \
+                      compile time, for example because it overflows or divides by zero.",
+        example: "const N: i64 = 1 / 0;",
+    },
+    // === Effects ===
+    ErrorCode {
+        code: "D0301",
+        title: "unhandled effect",
+        explanation: "An operation performs an effect that is neither handled by an \
+                      enclosing `handle` nor declared by the function. Handle it, or add it \
+                      to the signature with `with`.",
+        example: "fn log() -> i64 with IO { 0 }\nfn main() -> i64 { log() }",
+    },
+    ErrorCode {
+        code: "D0302",
+        title: "effect not declared",
+        explanation: "A function performs an effect that its signature does not list. Add \
+                      the effect to the function's `with` clause.",
+        example: "fn divide(a: i64, b: i64) -> i64 { a / b }",
+    },
+    ErrorCode {
+        code: "D0303",
+        title: "effect in pure context",
+        explanation: "A function marked `#[pure]` performs an effect. Remove the effect or \
+                      the attribute.",
+        example: "#[pure]\nfn divide(a: i64, b: i64) -> i64 { a / b }",
+    },
+    // === Ownership ===
+    ErrorCode {
+        code: "D0401",
+        title: "use of moved value",
+        explanation: "A value was used after ownership of it moved elsewhere. Borrow it with \
+                      `&` instead of moving it, or use it before the move.",
+        example: "struct Point { x: i64, y: i64 }\nfn main() -> i64 {\n    \
+                  let p: Point = Point { x: 1, y: 2 };\n    let q: Point = p;\n    p.x\n}",
+    },
+    ErrorCode {
+        code: "D0402",
+        title: "already borrowed",
+        explanation: "A value cannot be borrowed mutably while a shared borrow of it is \
+                      still in use.",
+        example: "fn main() -> i64 {\n    let mut x = 1;\n    let r = &x;\n    let m = &mut x;\n    *r\n}",
+    },
+    ErrorCode {
+        code: "D0403",
+        title: "second mutable borrow",
+        explanation: "Only one mutable borrow of a value may be live at a time.",
+        example: "fn main() -> i64 {\n    let mut x = 1;\n    let a = &mut x;\n    let b = &mut x;\n    *a\n}",
+    },
+    ErrorCode {
+        code: "D0404",
+        title: "already mutably borrowed",
+        explanation: "A value cannot be borrowed while a mutable borrow of it is still in \
+                      use.",
+        example: "fn main() -> i64 {\n    let mut x = 1;\n    let m = &mut x;\n    let r = &x;\n    *m\n}",
+    },
+    ErrorCode {
+        code: "D0405",
+        title: "move while borrowed",
+        explanation: "A value was moved while a borrow of it is still in use, which would \
+                      leave the borrow dangling.",
+        example: "struct Point { x: i64, y: i64 }\nfn main() -> i64 {\n    \
+                  let p: Point = Point { x: 1, y: 2 };\n    let r: &Point = &p;\n    \
+                  let q: Point = p;\n    r.x\n}",
+    },
+    ErrorCode {
+        code: "D0406",
+        title: "reference outlives its referent",
+        explanation: "A function returns a reference to one of its own locals, which is \
+                      dropped when the function returns. Return an owned value instead.",
+        example: "fn f() -> &i64 { let x = 1; &x }",
+    },
+    // === Linearity ===
+    ErrorCode {
+        code: "D0501",
+        title: "linear value used more than once",
+        explanation: "A value of a `linear` type must be used exactly once, but this one is \
+                      used again after being consumed.",
+        example: "linear struct Token { id: i64 }\nfn use_it(t: Token) -> i64 { t.id }\n\
+                  fn main() -> i64 { let t = Token { id: 1 }; use_it(t) + use_it(t) }",
+    },
+    ErrorCode {
+        code: "D0502",
+        title: "linear value not consumed",
+        explanation: "A value of a `linear` type goes out of scope without being used. Pass \
+                      it to a function that consumes it.",
+        example: "linear struct Token { id: i64 }\nfn main() -> i64 { let t = Token { id: 1 }; 0 }",
+    },
+    ErrorCode {
+        code: "D0503",
+        title: "affine value used more than once",
+        explanation: "A value of an `affine` type may be used at most once, but this one is \
+                      used again.",
+        example: "affine struct Handle { fd: i64 }\nfn main() -> i64 {\n    \
+                  let h: Handle = Handle { fd: 3 };\n    let a: Handle = h;\n    \
+                  let b: Handle = h;\n    0\n}",
+    },
+    // === Lints ===
+    ErrorCode {
+        code: "D0601",
+        title: "unused binding",
+        explanation: "A variable or parameter is never read. Remove it, or prefix its name \
+                      with `_` if it is intentionally unused.",
+        example: "fn main() -> i64 { let x = 1; 0 }",
+    },
+    ErrorCode {
+        code: "D0602",
+        title: "unknown attribute",
+        explanation: "An attribute is not recognised by the compiler and has no effect. \
+                      Check its spelling.",
+        example: "#[inlined]\nfn f() -> i64 { 1 }",
+    },
+    ErrorCode {
+        code: "D0603",
+        title: "effect declared but never performed",
+        explanation: "A function lists an effect in its `with` clause that neither its body \
+                      nor any function it calls performs. The annotation misleads readers \
+                      and forces callers to handle an effect that never happens.",
+        example: "fn f() -> i64 with Alloc { 1 }",
+    },
+];
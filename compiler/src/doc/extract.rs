@@ -6,7 +6,7 @@ use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use crate::ast::{
-    self, Ast, EnumDef, FnDef, GenericParam, Generics, GlobalDef, ImplDef, Item, Param,
+    self, Ast, EnumDef, FnDef, GenericParam, Generics, GlobalDef, ImplDef, ImportTree, Item, Param,
     StructDef, TraitDef, TraitFnDef, TraitItem, TypeAliasDef, TypeExpr, VariantData, Visibility as AstVisibility,
 };
 
@@ -62,7 +62,7 @@ impl DocExtractor {
         CrateDoc {
             name: self.crate_name.clone(),
             version: self.version.clone(),
-            doc: None, // Would extract from //! comments at top of lib.d
+            doc: ast.doc.clone(),
             root_module,
             items,
             search_index,
@@ -88,6 +88,7 @@ impl DocExtractor {
         let name = path.rsplit("::").next().unwrap_or(path).to_string();
 
         let mut module = ModuleDoc::new(name.clone(), path.to_string());
+        module.doc = ast.doc.clone();
 
         // Add module to search index
         search_index.add(SearchEntry {
@@ -182,13 +183,22 @@ impl DocExtractor {
                 }
 
                 Item::Import(import) => {
-                    // Record re-exports
-                    module.reexports.push(ReexportDoc {
-                        name: import.path.name().unwrap_or("").to_string(),
-                        original_path: import.path.to_string(),
-                        doc: None,
-                        visibility: Visibility::Public,
-                    });
+                    // Record re-exports, one per imported name
+                    for tree in import.tree.flatten() {
+                        let (name, original_path) = match &tree {
+                            ImportTree::Path { path, alias } => (
+                                alias.as_deref().or(path.name()).unwrap_or("").to_string(),
+                                path.to_string(),
+                            ),
+                            _ => ("*".to_string(), tree.to_string()),
+                        };
+                        module.reexports.push(ReexportDoc {
+                            name,
+                            original_path,
+                            doc: None,
+                            visibility: Visibility::Public,
+                        });
+                    }
                 }
 
                 _ => {}
@@ -204,6 +214,7 @@ impl DocExtractor {
         let sig = self.render_function_signature(f);
 
         let mut func = FunctionDoc::new(f.name.clone(), path);
+        func.doc = f.doc.clone();
         func.visibility = self.convert_visibility(&f.visibility);
         func.signature = sig;
         func.is_unsafe = f.modifiers.is_unsafe;
@@ -235,7 +246,12 @@ impl DocExtractor {
             .iter()
             .map(|w| WhereClause {
                 ty: self.type_expr_to_string(&w.ty),
-                bounds: w.bounds.iter().map(|b| b.to_string()).collect(),
+                bounds: w
+                    .signature
+                    .iter()
+                    .map(|s| self.type_expr_to_string(s).replacen("fn", "Fn", 1))
+                    .chain(w.bounds.iter().map(|b| b.to_string()))
+                    .collect(),
             })
             .collect();
 
@@ -293,6 +309,7 @@ impl DocExtractor {
                         sig.push_str(": ");
                         sig.push_str(&self.type_expr_to_string(ty));
                     }
+                    GenericParam::Lifetime { name } => sig.push_str(name),
                 }
             }
             sig.push('>');
@@ -344,6 +361,7 @@ impl DocExtractor {
         let path = format!("{}::{}", parent_path, s.name);
 
         let mut type_doc = TypeDoc::new(s.name.clone(), path.clone(), TypeKind::Struct);
+        type_doc.doc = s.doc.clone();
         type_doc.visibility = self.convert_visibility(&s.visibility);
         type_doc.type_params = self.extract_generics(&s.generics);
         type_doc.modifiers = TypeModifiers {
@@ -403,6 +421,7 @@ impl DocExtractor {
         let path = format!("{}::{}", parent_path, e.name);
 
         let mut type_doc = TypeDoc::new(e.name.clone(), path.clone(), TypeKind::Enum);
+        type_doc.doc = e.doc.clone();
         type_doc.visibility = self.convert_visibility(&e.visibility);
         type_doc.type_params = self.extract_generics(&e.generics);
         type_doc.modifiers = TypeModifiers {
@@ -483,6 +502,7 @@ impl DocExtractor {
         let path = format!("{}::{}", parent_path, t.name);
 
         let mut trait_doc = TraitDoc::new(t.name.clone(), path.clone());
+        trait_doc.doc = t.doc.clone();
         trait_doc.visibility = self.convert_visibility(&t.visibility);
         trait_doc.type_params = self.extract_generics(&t.generics);
         trait_doc.super_traits = t.supertraits.iter().map(|s| s.to_string()).collect();
@@ -541,6 +561,7 @@ impl DocExtractor {
         let path = format!("{}::{}", parent_path, f.name);
 
         let mut func = FunctionDoc::new(f.name.clone(), path);
+        func.doc = f.doc.clone();
         func.visibility = Visibility::Public;
         func.type_params = self.extract_generics(&f.generics);
         func.params = f.params.iter().map(|p| self.extract_param(p)).collect();
@@ -610,6 +631,7 @@ impl DocExtractor {
         let path = format!("{}::{}", parent_path, t.name);
 
         let mut type_doc = TypeDoc::new(t.name.clone(), path, TypeKind::TypeAlias);
+        type_doc.doc = t.doc.clone();
         type_doc.visibility = self.convert_visibility(&t.visibility);
         type_doc.type_params = self.extract_generics(&t.generics);
         type_doc.source = SourceLocation {
@@ -629,7 +651,7 @@ impl DocExtractor {
         ConstantDoc {
             name,
             path,
-            doc: None,
+            doc: g.doc.clone(),
             visibility: self.convert_visibility(&g.visibility),
             ty: g
                 .ty
@@ -714,6 +736,7 @@ impl DocExtractor {
                         default: default.as_ref().map(|d| self.type_expr_to_info(d)),
                     }),
                     GenericParam::Const { .. } => None, // Handle const generics separately if needed
+                    GenericParam::Lifetime { .. } => None,
                 }
             })
             .collect()
@@ -802,7 +825,7 @@ impl DocExtractor {
                 }
                 s
             }
-            TypeExpr::Reference { mutable, inner } => {
+            TypeExpr::Reference { mutable, inner, .. } => {
                 if *mutable {
                     format!("&!{}", self.type_expr_to_string(inner))
                 } else {
@@ -830,6 +853,7 @@ impl DocExtractor {
                         .join(", ")
                 )
             }
+            TypeExpr::Dyn { path, .. } => format!("dyn {}", path),
             TypeExpr::Function {
                 params,
                 return_type,
@@ -858,6 +882,7 @@ impl DocExtractor {
                 s
             }
             TypeExpr::Infer => "_".to_string(),
+            TypeExpr::Const(value) => self.expr_to_string(value),
         }
     }
 
@@ -865,13 +890,26 @@ impl DocExtractor {
     fn pattern_to_string(&self, pattern: &ast::Pattern) -> String {
         match pattern {
             ast::Pattern::Wildcard => "_".to_string(),
-            ast::Pattern::Binding { name, mutable } => {
+            ast::Pattern::Binding { name, mutable, .. } => {
                 if *mutable {
                     format!("mut {}", name)
                 } else {
                     name.clone()
                 }
             }
+            ast::Pattern::At {
+                name,
+                mutable,
+                pattern,
+                ..
+            } => {
+                let binding = if *mutable {
+                    format!("mut {}", name)
+                } else {
+                    name.clone()
+                };
+                format!("{} @ {}", binding, self.pattern_to_string(pattern))
+            }
             ast::Pattern::Literal(lit) => self.literal_to_string(lit),
             ast::Pattern::Tuple(patterns) => {
                 format!(
@@ -914,6 +952,16 @@ impl DocExtractor {
                 .map(|p| self.pattern_to_string(p))
                 .collect::<Vec<_>>()
                 .join(" | "),
+            ast::Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => format!(
+                "{}{}{}",
+                self.literal_to_string(start),
+                if *inclusive { "..=" } else { ".." },
+                self.literal_to_string(end)
+            ),
         }
 }
 
@@ -923,6 +971,7 @@ impl DocExtractor {
             ast::Literal::Unit => "()".to_string(),
             ast::Literal::Bool(b) => b.to_string(),
             ast::Literal::Int(i) => i.to_string(),
+            ast::Literal::UInt(n) => n.to_string(),
             ast::Literal::Float(f) => f.to_string(),
             ast::Literal::Char(c) => format!("'{}'", c),
             ast::Literal::String(s) => format!("\"{}\"", s),
@@ -1020,4 +1069,18 @@ mod tests {
         let extractor = DocExtractor::new("test", "0.1.0").document_private(true);
         assert!(extractor.document_private);
     }
+
+    #[test]
+    fn test_extract_doc_comments() {
+        let source = "//! Math helpers\n\n/// Adds two numbers\npub fn add(a: i64, b: i64) -> i64 { a + b }";
+        let tokens = crate::lexer::lex(source).unwrap();
+        let ast = crate::parser::parse(&tokens, source).unwrap();
+
+        let doc = DocExtractor::new("math", "0.1.0").extract(&ast);
+        assert_eq!(doc.doc.as_deref(), Some("Math helpers"));
+        let Some(DocItem::Function(add)) = doc.items.get("math::add") else {
+            panic!("Expected documented function");
+        };
+        assert_eq!(add.doc.as_deref(), Some("Adds two numbers"));
+    }
 }
@@ -1,27 +1,74 @@
 //! Effect inference pass
 //!
 //! Infers effects for expressions and checks that all effects are declared
-//! in function signatures.
-
-use crate::ast::{self, Ast, BinaryOp, Expr, Item, Stmt};
-use crate::common::Span;
-use crate::resolve::{DefId, SymbolTable};
+//! in function signatures. Declared effects that nothing in the body
+//! performs are reported as warnings.
+//!
+//! Effect aliases (`effect IO = Alloc + FileSystem + Net;`) are expanded to
+//! their member effects wherever they are named, so `with IO` declares the
+//! whole bundle.
+//!
+//! A parameter of function type, `f: fn() with IO` or `f: F` where
+//! `F: Fn() with IO`, carries an effect row: calling `f` performs at most
+//! those effects, and a callback passed for it may not perform any others.
+
+use crate::ast::{self, Ast, BinaryOp, Expr, Item, Stmt, TypeExpr};
+use crate::common::{NodeId, Span};
+use crate::diagnostics::{CompileError, SourceFile};
+use crate::resolve::{DefId, DefKind, SymbolTable};
 use crate::types::core::{Effect, EffectSet};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 /// Effect inference context
 pub struct EffectChecker<'a> {
     symbols: &'a SymbolTable,
-    /// Inferred effects per function DefId
+    /// Declared effects per function DefId
     fn_effects: HashMap<DefId, EffectSet>,
+    /// Effects each function body performs, per function DefId
+    inferred_effects: HashMap<DefId, InferredEffects>,
+    /// Effect row of each parameter of function type, per function DefId
+    callback_rows: HashMap<DefId, Vec<Option<EffectSet>>>,
+    /// Effect rows of the current function's callback parameters
+    callbacks: HashMap<DefId, EffectSet>,
+    /// Effect aliases by name
+    aliases: HashMap<String, EffectAlias>,
     /// Current function's declared effects
     declared: EffectSet,
     /// Current function's inferred effects
     inferred: EffectSet,
     /// Current function span (for error reporting)
     current_fn_span: Span,
+    /// Current function name (for error reporting)
+    current_fn_name: String,
+    /// Whether the current function is `async`
+    in_async: bool,
+    /// Whether the current function calls something whose effects are unknown
+    opaque_calls: bool,
+    /// Source spans of expression nodes
+    spans: HashMap<NodeId, Span>,
+    /// Effect handled by each handler
+    handler_effects: HashMap<String, String>,
     /// Errors
     errors: Vec<EffectError>,
+    /// Warnings, which do not fail the check
+    warnings: Vec<EffectError>,
+}
+
+/// Effects a function body was found to perform
+#[derive(Debug, Clone)]
+pub struct InferredEffects {
+    pub effects: EffectSet,
+    /// False when the body calls something whose effects are unknown, so
+    /// `effects` may be missing some
+    pub complete: bool,
+}
+
+/// A named bundle of effects
+#[derive(Debug, Clone)]
+struct EffectAlias {
+    members: Vec<String>,
+    /// `#[transparent]` aliases are displayed as their expansion
+    transparent: bool,
 }
 
 /// Effect error
@@ -41,6 +88,18 @@ pub enum EffectErrorKind {
     UnhandledEffect { effect: String },
     /// Effectful operation in pure context
     EffectInPureContext { effect: String },
+    /// Effect alias that expands to itself
+    RecursiveAlias { alias: String, cycle: Vec<String> },
+    /// `await` in a function that is not `async`
+    AwaitOutsideAsync { function: String },
+    /// Callback argument performs an effect its parameter's row leaves out
+    CallbackEffect {
+        callee: String,
+        effect: String,
+        allowed: String,
+    },
+    /// Effect declared in the signature but never performed (a warning)
+    UnusedEffect { effect: String },
 }
 
 impl<'a> EffectChecker<'a> {
@@ -48,19 +107,43 @@ impl<'a> EffectChecker<'a> {
         Self {
             symbols,
             fn_effects: HashMap::new(),
+            inferred_effects: HashMap::new(),
+            callback_rows: HashMap::new(),
+            callbacks: HashMap::new(),
+            aliases: HashMap::new(),
             declared: EffectSet::new(),
             inferred: EffectSet::new(),
             current_fn_span: Span::dummy(),
+            current_fn_name: String::new(),
+            in_async: false,
+            opaque_calls: false,
+            spans: HashMap::new(),
+            handler_effects: HashMap::new(),
             errors: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
+    /// Warnings from the last call to [`check_program`](Self::check_program)
+    pub fn warnings(&self) -> &[EffectError] {
+        &self.warnings
+    }
+
     /// Check effects for entire program
     pub fn check_program(&mut self, ast: &Ast) -> Result<(), Vec<EffectError>> {
+        self.spans = ast.node_spans.clone();
+        self.warnings.clear();
+        self.collect_aliases(ast);
+
         // First pass: collect declared effects for all functions
         for item in &ast.items {
-            if let Item::Function(f) = item {
-                self.collect_function_effects(f);
+            match item {
+                Item::Function(f) => self.collect_function_effects(f),
+                Item::Handler(h) => {
+                    let effect = h.effect.name().unwrap_or("").to_string();
+                    self.handler_effects.insert(h.name.clone(), effect);
+                }
+                _ => {}
             }
         }
 
@@ -78,32 +161,212 @@ impl<'a> EffectChecker<'a> {
         }
     }
 
-    fn collect_function_effects(&mut self, f: &ast::FnDef) {
-        let mut effects = EffectSet::new();
-        for eff_ref in &f.effects {
-            let effect = self.resolve_effect_ref(eff_ref);
-            effects.add(effect);
+    fn collect_aliases(&mut self, ast: &Ast) {
+        for item in &ast.items {
+            if let Item::Effect(e) = item
+                && e.is_alias()
+            {
+                let members = e
+                    .members
+                    .iter()
+                    .map(|m| self.resolve_effect_ref(m).name)
+                    .collect();
+                self.aliases.insert(
+                    e.name.clone(),
+                    EffectAlias {
+                        members,
+                        transparent: e.is_transparent(),
+                    },
+                );
+            }
         }
 
+        for item in &ast.items {
+            if let Item::Effect(e) = item
+                && let Some(cycle) = self.alias_cycle(&e.name, &mut Vec::new())
+            {
+                self.errors.push(EffectError {
+                    kind: EffectErrorKind::RecursiveAlias {
+                        alias: e.name.clone(),
+                        cycle,
+                    },
+                    span: e.span,
+                    fn_span: e.span,
+                });
+            }
+        }
+    }
+
+    /// Find a path of aliases from `name` back to the start of `stack`
+    fn alias_cycle(&self, name: &str, stack: &mut Vec<String>) -> Option<Vec<String>> {
+        if stack.first().is_some_and(|first| first == name) {
+            let mut cycle = stack.clone();
+            cycle.push(name.to_string());
+            return Some(cycle);
+        }
+        if stack.iter().any(|s| s == name) {
+            // A cycle that does not pass through the alias being checked
+            return None;
+        }
+        let alias = self.aliases.get(name)?;
+        stack.push(name.to_string());
+        let cycle = alias
+            .members
+            .iter()
+            .find_map(|member| self.alias_cycle(member, stack));
+        stack.pop();
+        cycle
+    }
+
+    /// Add `effect` to `set`, expanding aliases to their members
+    fn add_expanded(&self, set: &mut EffectSet, effect: Effect) {
+        let mut visiting = Vec::new();
+        self.expand_into(set, effect.name, &mut visiting);
+    }
+
+    fn expand_into(&self, set: &mut EffectSet, name: String, visiting: &mut Vec<String>) {
+        let Some(alias) = self.aliases.get(&name) else {
+            set.effects.insert(name);
+            return;
+        };
+        if visiting.contains(&name) {
+            // Recursive aliases are reported by `collect_aliases`
+            return;
+        }
+        visiting.push(name);
+        for member in &alias.members {
+            self.expand_into(set, member.clone(), visiting);
+        }
+        visiting.pop();
+    }
+
+    /// Expand an effect name to the set of effects it stands for
+    pub fn expand_effect(&self, name: &str) -> EffectSet {
+        let mut set = EffectSet::new();
+        self.expand_into(&mut set, name.to_string(), &mut Vec::new());
+        set
+    }
+
+    /// Render an effect set for display, folding the members of each
+    /// opaque alias back into its name. Transparent aliases stay expanded.
+    pub fn display_effects(&self, effects: &EffectSet) -> String {
+        let mut remaining: BTreeSet<String> = effects.effects.iter().cloned().collect();
+        let mut shown = BTreeSet::new();
+
+        // Prefer the largest aliases so nested bundles fold into the outer one
+        let mut opaque: Vec<(&String, EffectSet)> = self
+            .aliases
+            .iter()
+            .filter(|(_, alias)| !alias.transparent)
+            .map(|(name, _)| (name, self.expand_effect(name)))
+            .collect();
+        opaque.sort_by(|a, b| b.1.effects.len().cmp(&a.1.effects.len()).then(a.0.cmp(b.0)));
+
+        for (name, expansion) in opaque {
+            if !expansion.effects.is_empty()
+                && expansion.effects.iter().all(|e| remaining.contains(e))
+            {
+                for e in &expansion.effects {
+                    remaining.remove(e);
+                }
+                shown.insert(name.clone());
+            }
+        }
+        shown.extend(remaining);
+
+        if shown.is_empty() {
+            "pure".to_string()
+        } else {
+            shown.into_iter().collect::<Vec<_>>().join(", ")
+        }
+    }
+
+    fn collect_function_effects(&mut self, f: &ast::FnDef) {
+        let effects = self.declared_effects(f);
         if let Some(def_id) = self.symbols.def_for_node(f.id) {
             self.fn_effects.insert(def_id, effects);
+            let rows = f
+                .params
+                .iter()
+                .map(|p| self.callback_row(f, &p.ty))
+                .collect();
+            self.callback_rows.insert(def_id, rows);
         }
     }
 
-    fn check_function(&mut self, f: &ast::FnDef) {
-        // Set declared effects for this function
-        self.declared = EffectSet::new();
+    /// Effects a parameter of type `ty` may perform when called, if it is a
+    /// function type or a generic parameter with an `Fn(..)` bound
+    fn callback_row(&self, f: &ast::FnDef, ty: &TypeExpr) -> Option<EffectSet> {
+        let signature = match ty {
+            TypeExpr::Function { .. } => ty,
+            TypeExpr::Named { path, args, .. } if path.is_simple() && args.is_empty() => f
+                .where_clause
+                .iter()
+                .find_map(|w| match (&w.ty, &w.signature) {
+                    (TypeExpr::Named { path: bounded, .. }, Some(signature))
+                        if bounded.segments == path.segments =>
+                    {
+                        Some(signature)
+                    }
+                    _ => None,
+                })?,
+            _ => return None,
+        };
+        let TypeExpr::Function { effects, .. } = signature else {
+            return None;
+        };
+        let mut row = EffectSet::new();
+        for eff_ref in effects {
+            let effect = self.resolve_effect_ref(eff_ref);
+            self.add_expanded(&mut row, effect);
+        }
+        Some(row)
+    }
+
+    /// Effects in the signature of `f`; `async` functions implicitly
+    /// declare `Async`
+    fn declared_effects(&self, f: &ast::FnDef) -> EffectSet {
+        let mut effects = EffectSet::new();
         for eff_ref in &f.effects {
             let effect = self.resolve_effect_ref(eff_ref);
-            self.declared.add(effect);
+            self.add_expanded(&mut effects, effect);
+        }
+        if f.modifiers.is_async {
+            effects.add(async_effect());
         }
+        effects
+    }
+
+    fn check_function(&mut self, f: &ast::FnDef) {
+        // Set declared effects for this function
+        self.declared = self.declared_effects(f);
 
         // Reset inferred effects
         self.inferred = EffectSet::new();
         self.current_fn_span = f.span;
+        self.in_async = f.modifiers.is_async;
+        self.opaque_calls = false;
+        self.current_fn_name = f.name.clone();
+        self.callbacks = f
+            .params
+            .iter()
+            .filter_map(|p| {
+                let def_id = self.symbols.def_for_node(p.id)?;
+                Some((def_id, self.callback_row(f, &p.ty)?))
+            })
+            .collect();
 
         // Infer effects from body
         self.infer_block(&f.body);
+        if let Some(def_id) = self.symbols.def_for_node(f.id) {
+            self.inferred_effects.insert(
+                def_id,
+                InferredEffects {
+                    effects: self.inferred.clone(),
+                    complete: !self.opaque_calls,
+                },
+            );
+        }
 
         // Check that all inferred effects are declared
         for effect_name in &self.inferred.effects.clone() {
@@ -117,6 +380,30 @@ impl<'a> EffectChecker<'a> {
                 });
             }
         }
+
+        // An effect may be performed inside a call we know nothing about
+        if !self.opaque_calls {
+            self.check_unused_effects(f);
+        }
+    }
+
+    /// Warn about effects in the `with` clause of `f` that its body does not
+    /// perform. An alias counts as used when any of its members is.
+    fn check_unused_effects(&mut self, f: &ast::FnDef) {
+        for eff_ref in &f.effects {
+            let effect = self.resolve_effect_ref(eff_ref);
+            let expansion = self.expand_effect(&effect.name);
+            if expansion.effects.iter().any(|e| self.inferred.contains(e)) {
+                continue;
+            }
+            self.warnings.push(EffectError {
+                kind: EffectErrorKind::UnusedEffect {
+                    effect: effect.name,
+                },
+                span: self.spans.get(&eff_ref.id).copied().unwrap_or(f.span),
+                fn_span: f.span,
+            });
+        }
     }
 
     fn infer_block(&mut self, block: &ast::Block) -> EffectSet {
@@ -140,6 +427,9 @@ impl<'a> EffectChecker<'a> {
                     EffectSet::new()
                 }
             }
+            Stmt::LetElse {
+                value, else_block, ..
+            } => self.infer_expr(value).union(&self.infer_block(else_block)),
             Stmt::Expr { expr, .. } => self.infer_expr(expr),
             Stmt::Assign { target, value, .. } => {
                 let mut effects = self.infer_expr(target);
@@ -178,14 +468,25 @@ impl<'a> EffectChecker<'a> {
 
             Expr::Call { callee, args, .. } => {
                 let mut effects = self.infer_expr(callee);
+                if !self.has_known_effects(callee) {
+                    self.opaque_calls = true;
+                }
 
                 // Get callee's declared effects
                 let callee_effects = self.get_callee_effects(callee);
                 effects = effects.union(&callee_effects);
 
                 // Infer argument effects
-                for arg in args {
-                    effects = effects.union(&self.infer_expr(arg));
+                let rows = self
+                    .callee_def(callee)
+                    .and_then(|d| self.callback_rows.get(&d));
+                let rows = rows.cloned().unwrap_or_default();
+                for (i, arg) in args.iter().enumerate() {
+                    let arg_effects = self.infer_expr(arg);
+                    if let Some(Some(row)) = rows.get(i) {
+                        self.check_callback(callee, arg, &arg_effects, row);
+                    }
+                    effects = effects.union(&arg_effects);
                 }
 
                 effects
@@ -197,6 +498,7 @@ impl<'a> EffectChecker<'a> {
                     effects = effects.union(&self.infer_expr(arg));
                 }
                 // TODO: look up method effects
+                self.opaque_calls = true;
                 effects
             }
 
@@ -275,6 +577,11 @@ impl<'a> EffectChecker<'a> {
                 effects
             }
 
+            Expr::Range { start, end, .. } => {
+                let effects = self.infer_expr(start);
+                effects.union(&self.infer_expr(end))
+            }
+
             Expr::Return { value, .. } => {
                 if let Some(val) = value {
                     self.infer_expr(val)
@@ -314,6 +621,10 @@ impl<'a> EffectChecker<'a> {
                 effects
             }
 
+            Expr::ArrayRepeat { value, count, .. } => {
+                self.infer_expr(value).union(&self.infer_expr(count))
+            }
+
             Expr::StructLit { fields, .. } => {
                 let mut effects = EffectSet::new();
                 for (_, expr) in fields {
@@ -348,9 +659,17 @@ impl<'a> EffectChecker<'a> {
             }
 
             Expr::Handle { expr, handler, .. } => {
+                // Effects of a handled block only escape through the result
+                let outer = self.inferred.clone();
                 let body_effects = self.infer_expr(expr);
+                self.inferred = outer;
                 // Handler removes the handled effect
-                let handled_name = handler.name().unwrap_or("").to_string();
+                let handler_name = handler.name().unwrap_or("");
+                let handled_name = self
+                    .handler_effects
+                    .get(handler_name)
+                    .cloned()
+                    .unwrap_or_else(|| handler_name.to_string());
                 let mut result = EffectSet::new();
                 for eff in &body_effects.effects {
                     if eff != &handled_name {
@@ -370,30 +689,99 @@ impl<'a> EffectChecker<'a> {
                 effects
             }
 
-            Expr::Await { expr, .. } => {
+            Expr::Await { id, expr } => {
                 let mut effects = self.infer_expr(expr);
-                // Await has Async effect
-                effects.add(Effect {
-                    name: "Async".to_string(),
-                    args: Vec::new(),
-                });
+                if self.in_async {
+                    effects.add(async_effect());
+                } else {
+                    self.errors.push(EffectError {
+                        kind: EffectErrorKind::AwaitOutsideAsync {
+                            function: self.current_fn_name.clone(),
+                        },
+                        span: self.spans.get(id).copied().unwrap_or(self.current_fn_span),
+                        fn_span: self.current_fn_span,
+                    });
+                }
                 effects
             }
         }
     }
 
     fn get_callee_effects(&self, callee: &Expr) -> EffectSet {
-        if let Expr::Path { path, id } = callee {
-            if path.is_simple() {
-                // Look up the function by NodeId reference
-                if let Some(def_id) = self.symbols.ref_for_node(*id) {
-                    if let Some(effects) = self.fn_effects.get(&def_id) {
-                        return effects.clone();
-                    }
-                }
-            }
+        // Look up the function or callback parameter by NodeId reference
+        self.callee_def(callee)
+            .and_then(|def_id| {
+                self.fn_effects
+                    .get(&def_id)
+                    .or_else(|| self.callbacks.get(&def_id))
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether calling `callee` performs only the effects
+    /// [`get_callee_effects`](Self::get_callee_effects) reports: a function
+    /// or callback parameter in scope, or a constructor
+    fn has_known_effects(&self, callee: &Expr) -> bool {
+        let Some(def_id) = self.callee_def(callee) else {
+            return false;
+        };
+        self.fn_effects.contains_key(&def_id)
+            || self.callbacks.contains_key(&def_id)
+            || self
+                .symbols
+                .get(def_id)
+                .is_some_and(|s| matches!(s.kind, DefKind::Variant | DefKind::Struct { .. }))
+    }
+
+    /// Definition a simple path callee refers to
+    fn callee_def(&self, callee: &Expr) -> Option<DefId> {
+        match callee {
+            Expr::Path { path, id } if path.is_simple() => self.symbols.ref_for_node(*id),
+            _ => None,
+        }
+    }
+
+    /// Report effects of a callback argument outside its parameter's `row`.
+    /// A closure's effects are those of its body; a named function or
+    /// callback parameter performs its declared effects.
+    fn check_callback(
+        &mut self,
+        callee: &Expr,
+        arg: &Expr,
+        arg_effects: &EffectSet,
+        row: &EffectSet,
+    ) {
+        let performed = match arg {
+            Expr::Closure { .. } => arg_effects.clone(),
+            _ => self.get_callee_effects(arg),
+        };
+        let callee_name = match callee {
+            Expr::Path { path, .. } => path.to_string(),
+            _ => String::new(),
+        };
+        let mut undeclared: Vec<_> = performed
+            .effects
+            .iter()
+            .filter(|effect| !row.contains(effect))
+            .cloned()
+            .collect();
+        undeclared.sort();
+        for effect in undeclared {
+            self.errors.push(EffectError {
+                kind: EffectErrorKind::CallbackEffect {
+                    callee: callee_name.clone(),
+                    effect,
+                    allowed: self.display_effects(row),
+                },
+                span: self
+                    .spans
+                    .get(&arg.id())
+                    .copied()
+                    .unwrap_or(self.current_fn_span),
+                fn_span: self.current_fn_span,
+            });
         }
-        EffectSet::new()
     }
 
     fn resolve_effect_ref(&self, eff_ref: &ast::EffectRef) -> Effect {
@@ -404,10 +792,60 @@ impl<'a> EffectChecker<'a> {
         }
     }
 
-    /// Get the inferred effects for a function
+    /// Get the declared effects for a function
     pub fn get_function_effects(&self, def_id: DefId) -> Option<&EffectSet> {
         self.fn_effects.get(&def_id)
     }
+
+    /// Get the effects a function's body performs
+    pub fn get_inferred_effects(&self, def_id: DefId) -> Option<&InferredEffects> {
+        self.inferred_effects.get(&def_id)
+    }
+}
+
+/// The effect of suspending on a future
+fn async_effect() -> Effect {
+    Effect {
+        name: "Async".to_string(),
+        args: Vec::new(),
+    }
+}
+
+impl EffectError {
+    /// Attach source code to produce a reportable diagnostic
+    pub fn to_diagnostic(&self, source: &SourceFile) -> CompileError {
+        let src = source.to_named_source();
+        match &self.kind {
+            EffectErrorKind::UndeclaredEffect { effect } => CompileError::UndeclaredEffect {
+                effect: effect.clone(),
+                span: self.span.into(),
+                fn_span: self.fn_span.into(),
+                src,
+            },
+            EffectErrorKind::UnhandledEffect { effect } => CompileError::UnhandledEffect {
+                effect: effect.clone(),
+                span: self.span.into(),
+                src,
+            },
+            EffectErrorKind::EffectInPureContext { effect } => CompileError::EffectInPureContext {
+                effect: effect.clone(),
+                span: self.span.into(),
+                src,
+            },
+            EffectErrorKind::UnusedEffect { effect } => CompileError::UnusedEffect {
+                effect: effect.clone(),
+                span: self.span.into(),
+                src,
+            },
+            _ => CompileError::General {
+                message: self.to_string(),
+                span: self.span.into(),
+                label: "effect error".to_string(),
+                src,
+                help: None,
+            },
+        }
+    }
 }
 
 impl std::fmt::Display for EffectError {
@@ -422,6 +860,33 @@ impl std::fmt::Display for EffectError {
             EffectErrorKind::EffectInPureContext { effect } => {
                 write!(f, "Cannot perform `{}` in pure context", effect)
             }
+            EffectErrorKind::RecursiveAlias { alias, cycle } => {
+                write!(
+                    f,
+                    "Effect alias `{}` is recursive: {}",
+                    alias,
+                    cycle.join(" -> ")
+                )
+            }
+            EffectErrorKind::AwaitOutsideAsync { function } => {
+                write!(
+                    f,
+                    "`await` is only allowed in async functions, but `{}` is not async",
+                    function
+                )
+            }
+            EffectErrorKind::CallbackEffect {
+                callee,
+                effect,
+                allowed,
+            } => write!(
+                f,
+                "Callback passed to `{}` performs `{}`, but its parameter only allows {}",
+                callee, effect, allowed
+            ),
+            EffectErrorKind::UnusedEffect { effect } => {
+                write!(f, "Effect `{}` is declared but never performed", effect)
+            }
         }
     }
 }
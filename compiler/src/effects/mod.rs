@@ -6,7 +6,7 @@
 pub mod inference;
 
 pub use crate::types::effects::*;
-pub use inference::{EffectChecker, EffectError, EffectErrorKind};
+pub use inference::{EffectChecker, EffectError, EffectErrorKind, InferredEffects};
 
 /// Runtime effect handler trait
 pub trait Handler<E> {
@@ -0,0 +1,1875 @@
+//! Source formatter for `dc fmt`
+//!
+//! Pretty-prints a parsed [`Ast`] as canonical D source: four-space
+//! indentation, single spaces around binary operators and opening braces on
+//! the line of the construct they belong to. Comments, doc comments
+//! included, are carried over verbatim and placed before the item or
+//! statement that follows them; a comment that trailed code on its line
+//! stays at the end of the preceding output line.
+//!
+//! The output is reparsed and compared with the original AST, so a file is
+//! never rewritten into something that means a different program.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path as FsPath, PathBuf};
+
+use miette::{Result, miette};
+use serde_json::Value;
+
+use crate::ast::*;
+use crate::common::{NodeId, Span};
+use crate::lexer::{self, Token, TokenKind};
+use crate::parser;
+
+const INDENT: &str = "    ";
+
+/// Binding strength of prefix operators, above every binary operator
+const PREFIX_PREC: u8 = 11;
+/// Binding strength of postfix operators and primary expressions
+const POSTFIX_PREC: u8 = 12;
+
+/// Format D source code
+pub fn format_source(source: &str) -> Result<String> {
+    let tokens = lexer::lex(source)?;
+    let ast = parser::parse(&tokens, source)?;
+    let formatted = Printer::new(source, &tokens, &ast.node_spans).print(&ast);
+
+    let reparsed = crate::parse(&formatted)
+        .map_err(|e| miette!("formatter produced code that does not parse: {}", e))?;
+    if !equivalent(&ast, &reparsed) {
+        return Err(miette!(
+            "formatting would change the meaning of the program; leaving it unchanged"
+        ));
+    }
+    Ok(formatted)
+}
+
+/// Pretty-print an AST. Without the source there are no comments to keep.
+pub fn format_ast(ast: &Ast) -> String {
+    Printer::new("", &[], &ast.node_spans).print(ast)
+}
+
+/// Whether two ASTs describe the same program, ignoring node ids, spans
+/// and empty statements
+pub fn equivalent(a: &Ast, b: &Ast) -> bool {
+    normalize(serde_json::to_value(a).ok()) == normalize(serde_json::to_value(b).ok())
+}
+
+fn normalize(value: Option<Value>) -> Option<Value> {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("id");
+                map.remove("span");
+                if let Some(Value::Array(stmts)) = map.get_mut("stmts") {
+                    stmts.retain(|s| s.as_str() != Some("Empty"));
+                }
+                map.values_mut().for_each(strip);
+            }
+            Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    value.map(|mut value| {
+        strip(&mut value);
+        value
+    })
+}
+
+/// The `.d` files under `path`, or `path` itself if it is a file
+pub fn source_files(path: &FsPath) -> std::io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(source_files(&entry)?);
+        } else if entry.extension().is_some_and(|e| e == "d") {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+/// Line diff from `original` to `formatted` in unified format, with three
+/// lines of context around each change
+pub fn diff(original: &str, formatted: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let old: Vec<&str> = original.lines().collect();
+    let new: Vec<&str> = formatted.lines().collect();
+    let ops = diff_lines(&old, &new);
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < ops.len() {
+        if ops[i] == DiffOp::Equal {
+            i += 1;
+            continue;
+        }
+        // Extend the hunk while changes are close enough to share context
+        let start = i.saturating_sub(CONTEXT);
+        let mut end = i;
+        let mut equal_run = 0;
+        while end < ops.len() && equal_run <= 2 * CONTEXT {
+            equal_run = if ops[end] == DiffOp::Equal {
+                equal_run + 1
+            } else {
+                0
+            };
+            end += 1;
+        }
+        let end = end - equal_run.saturating_sub(CONTEXT);
+
+        let (mut old_line, mut new_line) = (0, 0);
+        for op in &ops[..start] {
+            old_line += usize::from(*op != DiffOp::Insert);
+            new_line += usize::from(*op != DiffOp::Delete);
+        }
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|op| **op != DiffOp::Insert).count();
+        let new_len = hunk.iter().filter(|op| **op != DiffOp::Delete).count();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_line + 1,
+            old_len,
+            new_line + 1,
+            new_len
+        ));
+        for op in hunk {
+            let (marker, line) = match op {
+                DiffOp::Equal => {
+                    old_line += 1;
+                    new_line += 1;
+                    (' ', old[old_line - 1])
+                }
+                DiffOp::Delete => {
+                    old_line += 1;
+                    ('-', old[old_line - 1])
+                }
+                DiffOp::Insert => {
+                    new_line += 1;
+                    ('+', new[new_line - 1])
+                }
+            };
+            out.push(marker);
+            out.push_str(line);
+            out.push('\n');
+        }
+        i = end;
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Edit script turning `old` into `new`, from a longest common subsequence
+/// of the lines between their common prefix and suffix
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j]: length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = vec![DiffOp::Equal; prefix];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(DiffOp::Equal);
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(DiffOp::Delete);
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert);
+            j += 1;
+        }
+    }
+    ops.extend(std::iter::repeat_n(DiffOp::Equal, suffix));
+    ops
+}
+
+/// A comment taken from the source
+struct Comment {
+    text: String,
+    span: Span,
+    /// Whether code precedes the comment on its line
+    trailing: bool,
+}
+
+/// Collect the comments of `source`: plain comments from the gaps between
+/// tokens, and doc comments, which the lexer keeps as tokens
+fn collect_comments(source: &str, tokens: &[Token]) -> VecDeque<Comment> {
+    let mut comments = VecDeque::new();
+    let mut push = |start: usize, end: usize, doc: bool| {
+        let line = &source[source[..start].rfind('\n').map_or(0, |i| i + 1)..start];
+        comments.push_back(Comment {
+            text: source[start..end].trim_end().to_string(),
+            span: Span::new(start, end),
+            // Doc comments always get a line of their own
+            trailing: !doc && !line.trim().is_empty(),
+        });
+    };
+
+    let mut gap_start = 0;
+    for token in tokens {
+        let mut pos = gap_start;
+        while pos < token.span.start {
+            let rest = &source[pos..token.span.start];
+            let len = if rest.starts_with("//") {
+                rest.find('\n').unwrap_or(rest.len())
+            } else if rest.starts_with("/*") {
+                rest.find("*/").map_or(rest.len(), |i| i + 2)
+            } else {
+                pos += rest.chars().next().map_or(1, char::len_utf8);
+                continue;
+            };
+            push(pos, pos + len, false);
+            pos += len;
+        }
+        if is_doc_comment(token.kind) {
+            push(token.span.start, token.span.end, true);
+        }
+        gap_start = token.span.end;
+    }
+    comments
+}
+
+fn is_doc_comment(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::DocCommentOuter
+            | TokenKind::DocCommentInner
+            | TokenKind::DocBlockOuter
+            | TokenKind::DocBlockInner
+    )
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    /// Tokens other than doc comments, for locating nodes without a span
+    tokens: Vec<&'a Token>,
+    spans: &'a HashMap<NodeId, Span>,
+    /// Comments not yet emitted, in source order
+    comments: VecDeque<Comment>,
+    out: String,
+    indent: usize,
+    at_line_start: bool,
+    /// Source position of the last node placed; later nodes are searched
+    /// for from here
+    cursor: usize,
+    /// Start of the last node placed, which searches skip
+    anchor: Option<usize>,
+    /// Whether the next line must be preceded by a blank line
+    separate: bool,
+    /// Whether a struct literal here would be read as the start of a block
+    /// (in the head of `if`, `while`, `match` and `for`)
+    no_struct: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, tokens: &'a [Token], spans: &'a HashMap<NodeId, Span>) -> Self {
+        Self {
+            source,
+            tokens: tokens.iter().filter(|t| !is_doc_comment(t.kind)).collect(),
+            spans,
+            comments: collect_comments(source, tokens),
+            out: String::new(),
+            indent: 0,
+            at_line_start: true,
+            cursor: 0,
+            anchor: None,
+            separate: false,
+            no_struct: false,
+        }
+    }
+
+    fn print(mut self, ast: &Ast) -> String {
+        if let Some(module) = &ast.module_name {
+            let start = self.find_token(TokenKind::Module);
+            self.begin(start);
+            self.write(&format!("module {}", module));
+            self.newline();
+        }
+
+        let mut previous: Option<&Item> = None;
+        for item in &ast.items {
+            // Imports and globals may be grouped; everything else is set apart
+            let grouped = matches!(
+                (previous, item),
+                (Some(Item::Import(_)), Item::Import(_)) | (Some(Item::Global(_)), Item::Global(_))
+            );
+            self.separate = !self.out.is_empty() && !grouped;
+            self.begin(Some(item_start(item)));
+            self.item(item);
+            previous = Some(item);
+        }
+
+        self.flush_comments(usize::MAX);
+        let mut out = self.out.trim_end().to_string();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    // ==================== OUTPUT ====================
+
+    fn write(&mut self, text: &str) {
+        if self.at_line_start {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.at_line_start = false;
+        }
+        self.out.push_str(text);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    /// Emit a blank line unless one would be redundant: at the start of
+    /// the file or a body, or after another blank line
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("{\n") && !self.out.ends_with("\n\n") {
+            self.newline();
+        }
+    }
+
+    /// Start a line for a node beginning at `start` in the source, after
+    /// the comments that precede it
+    fn begin(&mut self, start: Option<usize>) {
+        match start {
+            Some(start) if start >= self.cursor => {
+                self.flush_comments(start);
+                self.cursor = start;
+                self.anchor = Some(start);
+                self.own_line(start);
+            }
+            _ => self.own_line(usize::MAX),
+        }
+    }
+
+    /// Prepare a line for something starting at `start` in the source,
+    /// keeping a blank line that precedes it there
+    fn own_line(&mut self, start: usize) {
+        if std::mem::take(&mut self.separate) || self.blank_line_before(start) {
+            self.blank_line();
+        }
+    }
+
+    /// Whether the source line holding `pos` follows a blank line
+    fn blank_line_before(&self, pos: usize) -> bool {
+        let Some(before) = self.source.get(..pos) else {
+            return false;
+        };
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        if !before[line_start..].trim().is_empty() {
+            return false;
+        }
+        before[..line_start]
+            .strip_suffix('\n')
+            .map(|prev| prev.trim_end_matches([' ', '\t', '\r']))
+            .is_some_and(|prev| prev.is_empty() || prev.ends_with('\n'))
+    }
+
+    /// Emit the comments that end before `pos`
+    fn flush_comments(&mut self, pos: usize) {
+        while self.comments.front().is_some_and(|c| c.span.end <= pos) {
+            let comment = self.comments.pop_front().unwrap();
+            if comment.trailing && self.out.ends_with('\n') && !self.out.ends_with("\n\n") {
+                self.out.pop();
+                self.out.push(' ');
+                self.out.push_str(&comment.text);
+                self.out.push('\n');
+            } else {
+                self.own_line(comment.span.start);
+                self.write(&comment.text);
+                self.newline();
+            }
+        }
+    }
+
+    /// Emit the comments left before the `}` closing the body whose last
+    /// element was placed last
+    fn close_body(&mut self) {
+        if let Some(close) = self.closing_brace() {
+            self.flush_comments(close);
+            self.cursor = close + 1;
+        }
+    }
+
+    /// The first `}` after the cursor that closes a brace opened before it
+    fn closing_brace(&self) -> Option<usize> {
+        let first = self.tokens.partition_point(|t| t.span.start < self.cursor);
+        let mut depth = 0usize;
+        for token in &self.tokens[first..] {
+            match token.kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth == 0 => return Some(token.span.start),
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // ==================== SOURCE POSITIONS ====================
+
+    fn span_start(&self, id: NodeId) -> Option<usize> {
+        self.spans.get(&id).map(|s| s.start)
+    }
+
+    /// The first token after the last node placed that satisfies `pred`
+    fn find(&self, pred: impl Fn(&Token) -> bool) -> Option<usize> {
+        let first = self.tokens.partition_point(|t| t.span.start < self.cursor);
+        self.tokens[first..]
+            .iter()
+            .find(|t| Some(t.span.start) != self.anchor && pred(t))
+            .map(|t| t.span.start)
+    }
+
+    fn find_token(&self, kind: TokenKind) -> Option<usize> {
+        self.find(|t| t.kind == kind)
+    }
+
+    fn find_ident(&self, name: &str) -> Option<usize> {
+        self.find(|t| matches!(t.kind, TokenKind::Ident | TokenKind::SelfLower) && t.text == name)
+    }
+
+    /// Start of the first binding in `pattern`
+    fn pattern_start(&self, pattern: &Pattern) -> Option<usize> {
+        match pattern {
+            Pattern::Binding { id, .. } | Pattern::At { id, .. } => self.span_start(*id),
+            Pattern::Tuple(patterns) | Pattern::Or(patterns) => {
+                patterns.iter().find_map(|p| self.pattern_start(p))
+            }
+            Pattern::Enum {
+                patterns: Some(patterns),
+                ..
+            } => patterns.iter().find_map(|p| self.pattern_start(p)),
+            Pattern::Struct { fields, .. } => {
+                fields.iter().find_map(|(_, p)| self.pattern_start(p))
+            }
+            _ => None,
+        }
+    }
+
+    /// Source text of a node, if the source is available
+    fn source_text(&self, id: NodeId) -> Option<&'a str> {
+        let span = self.spans.get(&id)?;
+        self.source
+            .get(span.start..span.end)
+            .filter(|text| !text.is_empty())
+    }
+
+    // ==================== ITEMS ====================
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Function(f) => self.function(f),
+            Item::Struct(s) => self.struct_def(s),
+            Item::Enum(e) => self.enum_def(e),
+            Item::Trait(t) => self.trait_def(t),
+            Item::Impl(i) => self.impl_def(i),
+            Item::TypeAlias(t) => {
+                self.attributes(&t.attributes);
+                self.visibility(t.visibility);
+                self.write(&format!("type {}", t.name));
+                self.generics(&t.generics);
+                self.write(" = ");
+                self.ty(&t.ty);
+                self.write(";");
+                self.newline();
+            }
+            Item::Effect(e) => self.effect_def(e),
+            Item::Handler(h) => self.handler_def(h),
+            Item::UnitDef(u) => {
+                self.attributes(&u.attributes);
+                self.visibility(u.visibility);
+                self.write(&format!("unit {}", u.name));
+                if let Some(definition) = &u.definition {
+                    self.write(&format!(" = {}", definition));
+                }
+                self.write(";");
+                self.newline();
+            }
+            Item::Import(i) => {
+                self.attributes(&i.attributes);
+                self.write(&format!("import {};", i.tree));
+                self.newline();
+            }
+            Item::Extern(e) => self.extern_block(e),
+            Item::Global(g) => {
+                self.attributes(&g.attributes);
+                self.visibility(g.visibility);
+                self.write(if g.is_const { "const " } else { "let " });
+                if g.is_mut {
+                    self.write("mut ");
+                }
+                self.pattern(&g.pattern);
+                if let Some(ty) = &g.ty {
+                    self.write(": ");
+                    self.ty(ty);
+                }
+                self.write(" = ");
+                self.expr(&g.value);
+                self.write(";");
+                self.newline();
+            }
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        for attr in attributes {
+            if attr.args.is_empty() {
+                self.write(&format!("#[{}]", attr.name));
+            } else {
+                self.write(&format!("#[{}({})]", attr.name, attr.args.join(", ")));
+            }
+            self.newline();
+        }
+    }
+
+    fn visibility(&mut self, visibility: Visibility) {
+        if visibility == Visibility::Public {
+            self.write("pub ");
+        }
+    }
+
+    fn function(&mut self, f: &FnDef) {
+        self.attributes(&f.attributes);
+        self.visibility(f.visibility);
+        if f.modifiers.is_async {
+            self.write("async ");
+        }
+        if f.modifiers.is_unsafe {
+            self.write("unsafe ");
+        }
+        if f.modifiers.is_kernel {
+            self.write("kernel ");
+        }
+        self.signature(
+            &f.name,
+            &f.generics,
+            &f.params,
+            f.return_type.as_ref(),
+            &f.effects,
+            &f.where_clause,
+        );
+        self.write(" ");
+        self.fn_body(&f.body);
+        self.newline();
+    }
+
+    fn signature(
+        &mut self,
+        name: &str,
+        generics: &Generics,
+        params: &[Param],
+        return_type: Option<&TypeExpr>,
+        effects: &[EffectRef],
+        where_clause: &[WherePredicate],
+    ) {
+        self.write(&format!("fn {}", name));
+        self.generics(generics);
+        self.params(params, false);
+        self.return_type(return_type);
+        if !effects.is_empty() {
+            self.write(" with ");
+            self.effect_refs(effects, ", ");
+        }
+        self.where_clause(where_clause);
+    }
+
+    /// A parameter list. Handler parameters may leave out their types.
+    fn params(&mut self, params: &[Param], handler: bool) {
+        self.write("(");
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.param(param, handler);
+        }
+        self.write(")");
+    }
+
+    fn param(&mut self, param: &Param, handler: bool) {
+        let is_self = matches!(&param.pattern, Pattern::Binding { name, .. } if name == "self");
+        match &param.ty {
+            TypeExpr::SelfType if is_self => {
+                self.write(if param.is_mut { "mut self" } else { "self" });
+            }
+            TypeExpr::Reference {
+                mutable,
+                lifetime,
+                inner,
+            } if is_self && matches!(**inner, TypeExpr::SelfType) => {
+                self.reference(*mutable, lifetime.as_deref());
+                self.write("self");
+            }
+            ty => {
+                if param.is_mut {
+                    self.write("mut ");
+                }
+                self.pattern(&param.pattern);
+                if !(handler && matches!(ty, TypeExpr::Infer)) {
+                    self.write(": ");
+                    self.ty(ty);
+                }
+            }
+        }
+    }
+
+    fn return_type(&mut self, return_type: Option<&TypeExpr>) {
+        if let Some(ty) = return_type {
+            self.write(" -> ");
+            self.ty(ty);
+        }
+    }
+
+    fn effect_refs(&mut self, effects: &[EffectRef], separator: &str) {
+        for (i, effect) in effects.iter().enumerate() {
+            if i > 0 {
+                self.write(separator);
+            }
+            self.write(&effect.name.to_string());
+            self.type_args(&effect.args);
+        }
+    }
+
+    fn generics(&mut self, generics: &Generics) {
+        if generics.params.is_empty() {
+            return;
+        }
+        self.write("<");
+        for (i, param) in generics.params.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            match param {
+                GenericParam::Type {
+                    name,
+                    bounds,
+                    default,
+                } => {
+                    self.write(name);
+                    self.bounds(bounds);
+                    if let Some(default) = default {
+                        self.write(" = ");
+                        self.ty(default);
+                    }
+                }
+                GenericParam::Const { name, ty } => {
+                    self.write(&format!("const {}: ", name));
+                    self.ty(ty);
+                }
+                GenericParam::Lifetime { name } => self.write(name),
+            }
+        }
+        self.write(">");
+    }
+
+    /// `&`, `&mut ` or `&'a mut ` before a referenced type
+    fn reference(&mut self, mutable: bool, lifetime: Option<&str>) {
+        self.write("&");
+        if let Some(lifetime) = lifetime {
+            self.write(lifetime);
+            self.write(" ");
+        }
+        if mutable {
+            self.write("mut ");
+        }
+    }
+
+    /// `: A + B`, or nothing without bounds
+    fn bounds(&mut self, bounds: &[Path]) {
+        if !bounds.is_empty() {
+            let bounds: Vec<String> = bounds.iter().map(Path::to_string).collect();
+            self.write(&format!(": {}", bounds.join(" + ")));
+        }
+    }
+
+    fn where_clause(&mut self, predicates: &[WherePredicate]) {
+        if predicates.is_empty() {
+            return;
+        }
+        self.write(" where ");
+        for (i, predicate) in predicates.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.ty(&predicate.ty);
+            if let Some(signature) = &predicate.signature {
+                self.write(": ");
+                self.fn_type("Fn", signature);
+                for bound in &predicate.bounds {
+                    self.write(&format!(" + {}", bound));
+                }
+            } else {
+                self.bounds(&predicate.bounds);
+            }
+        }
+    }
+
+    /// Open a braced body, or write `{}` and return false if it is empty
+    fn open_body(&mut self, empty: bool) -> bool {
+        if empty {
+            self.write(" {}");
+            self.newline();
+            return false;
+        }
+        self.write(" {");
+        self.newline();
+        self.indent += 1;
+        true
+    }
+
+    fn close_body_brace(&mut self) {
+        self.close_body();
+        self.indent -= 1;
+        self.write("}");
+        self.newline();
+    }
+
+    fn struct_def(&mut self, s: &StructDef) {
+        self.attributes(&s.attributes);
+        self.visibility(s.visibility);
+        self.type_modifiers(&s.modifiers);
+        self.write(&format!("struct {}", s.name));
+        self.generics(&s.generics);
+        if let StructKind::Tuple(types) = &s.kind {
+            self.write("(");
+            self.types(types);
+            self.write(")");
+            self.where_clause(&s.where_clause);
+            self.newline();
+            return;
+        }
+        self.where_clause(&s.where_clause);
+        if let StructKind::Unit = s.kind {
+            self.write(";");
+            self.newline();
+            return;
+        }
+        if self.open_body(s.fields.is_empty()) {
+            self.fields(&s.fields);
+            self.close_body_brace();
+        }
+    }
+
+    fn type_modifiers(&mut self, modifiers: &TypeModifiers) {
+        if modifiers.linear {
+            self.write("linear ");
+        }
+        if modifiers.affine {
+            self.write("affine ");
+        }
+    }
+
+    fn fields(&mut self, fields: &[FieldDef]) {
+        for field in fields {
+            let start = self.find_ident(&field.name);
+            self.begin(start);
+            self.visibility(field.visibility);
+            self.write(&format!("{}: ", field.name));
+            self.ty(&field.ty);
+            self.write(",");
+            self.newline();
+        }
+    }
+
+    fn enum_def(&mut self, e: &EnumDef) {
+        self.attributes(&e.attributes);
+        self.visibility(e.visibility);
+        self.type_modifiers(&e.modifiers);
+        self.write(&format!("enum {}", e.name));
+        self.generics(&e.generics);
+        self.where_clause(&e.where_clause);
+        if !self.open_body(e.variants.is_empty()) {
+            return;
+        }
+        for variant in &e.variants {
+            let start = self.find_ident(&variant.name);
+            self.begin(start);
+            self.write(&variant.name);
+            match &variant.data {
+                VariantData::Unit => {}
+                VariantData::Tuple(types) => {
+                    self.write("(");
+                    self.types(types);
+                    self.write(")");
+                }
+                VariantData::Struct(fields) => {
+                    self.write(" { ");
+                    for (i, field) in fields.iter().enumerate() {
+                        if i > 0 {
+                            self.write(", ");
+                        }
+                        self.visibility(field.visibility);
+                        self.write(&format!("{}: ", field.name));
+                        self.ty(&field.ty);
+                    }
+                    self.write(" }");
+                }
+            }
+            if let Some(discriminant) = &variant.discriminant {
+                self.write(" = ");
+                self.expr(discriminant);
+            }
+            self.write(",");
+            self.newline();
+        }
+        self.close_body_brace();
+    }
+
+    fn trait_def(&mut self, t: &TraitDef) {
+        self.attributes(&t.attributes);
+        self.visibility(t.visibility);
+        self.write(&format!("trait {}", t.name));
+        self.generics(&t.generics);
+        self.bounds(&t.supertraits);
+        self.where_clause(&t.where_clause);
+        if !self.open_body(t.items.is_empty()) {
+            return;
+        }
+        let mut previous_fn = false;
+        for item in &t.items {
+            match item {
+                TraitItem::Fn(f) => {
+                    let start = self.find_ident(&f.name);
+                    self.separate = previous_fn || f.default_body.is_some();
+                    self.begin(start);
+                    self.signature(
+                        &f.name,
+                        &f.generics,
+                        &f.params,
+                        f.return_type.as_ref(),
+                        &f.effects,
+                        &f.where_clause,
+                    );
+                    match &f.default_body {
+                        Some(body) => {
+                            self.write(" ");
+                            self.fn_body(body);
+                        }
+                        None => self.write(";"),
+                    }
+                    previous_fn = f.default_body.is_some();
+                }
+                TraitItem::Type(ty) => {
+                    let start = self.find_ident(&ty.name);
+                    self.separate = previous_fn;
+                    self.begin(start);
+                    self.write(&format!("type {}", ty.name));
+                    self.bounds(&ty.bounds);
+                    if let Some(default) = &ty.default {
+                        self.write(" = ");
+                        self.ty(default);
+                    }
+                    self.write(";");
+                    previous_fn = false;
+                }
+            }
+            self.newline();
+        }
+        self.close_body_brace();
+    }
+
+    fn impl_def(&mut self, i: &ImplDef) {
+        self.attributes(&i.attributes);
+        self.write("impl");
+        self.generics(&i.generics);
+        self.write(" ");
+        if let Some(trait_ref) = &i.trait_ref {
+            self.write(&format!("{} for ", trait_ref));
+        }
+        self.ty(&i.target_type);
+        self.where_clause(&i.where_clause);
+        if !self.open_body(i.items.is_empty()) {
+            return;
+        }
+        let mut previous_fn = false;
+        for item in &i.items {
+            match item {
+                ImplItem::Fn(f) => {
+                    self.separate = true;
+                    self.begin(Some(f.span.start));
+                    self.function(f);
+                    previous_fn = true;
+                }
+                ImplItem::Type(ty) => {
+                    let start = self.find_ident(&ty.name);
+                    self.separate = previous_fn;
+                    self.begin(start);
+                    self.write(&format!("type {} = ", ty.name));
+                    self.ty(&ty.ty);
+                    self.write(";");
+                    self.newline();
+                    previous_fn = false;
+                }
+            }
+        }
+        self.close_body_brace();
+    }
+
+    fn effect_def(&mut self, e: &EffectDef) {
+        self.attributes(&e.attributes);
+        self.visibility(e.visibility);
+        self.write(&format!("effect {}", e.name));
+        self.generics(&e.generics);
+        if e.is_alias() {
+            self.write(" = ");
+            self.effect_refs(&e.members, " + ");
+            self.write(";");
+            self.newline();
+            return;
+        }
+        if !self.open_body(e.operations.is_empty()) {
+            return;
+        }
+        for op in &e.operations {
+            let start = self.find_ident(&op.name);
+            self.begin(start);
+            self.write(&format!("fn {}", op.name));
+            self.params(&op.params, false);
+            self.return_type(op.return_type.as_ref());
+            self.write(";");
+            self.newline();
+        }
+        self.close_body_brace();
+    }
+
+    fn handler_def(&mut self, h: &HandlerDef) {
+        self.attributes(&h.attributes);
+        self.visibility(h.visibility);
+        self.write(&format!("handler {}", h.name));
+        self.generics(&h.generics);
+        self.write(&format!(" for {}", h.effect));
+        self.type_args(&h.effect_args);
+        if !self.open_body(h.cases.is_empty() && h.return_clause.is_none()) {
+            return;
+        }
+        for case in &h.cases {
+            let start = self.find_ident(&case.name);
+            self.begin(start);
+            self.write(&case.name);
+            self.params(&case.params, true);
+            self.write(" => ");
+            self.expr(&case.body);
+            self.write(",");
+            self.newline();
+        }
+        if let Some(ret) = &h.return_clause {
+            let start = self.find_token(TokenKind::Return);
+            self.begin(start);
+            self.write(&format!("return {} => ", ret.param));
+            self.expr(&ret.body);
+            self.write(",");
+            self.newline();
+        }
+        self.close_body_brace();
+    }
+
+    fn extern_block(&mut self, e: &ExternBlock) {
+        self.attributes(&e.attributes);
+        self.write(&format!("extern {:?}", e.abi));
+        if !self.open_body(e.items.is_empty()) {
+            return;
+        }
+        for f in &e.items {
+            let start = self.find_ident(&f.name);
+            self.begin(start);
+            self.write(&format!("fn {}(", f.name));
+            for (i, param) in f.params.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                self.param(param, false);
+            }
+            if f.variadic {
+                self.write(if f.params.is_empty() { "..." } else { ", ..." });
+            }
+            self.write(")");
+            self.return_type(f.return_type.as_ref());
+            self.write(";");
+            self.newline();
+        }
+        self.close_body_brace();
+    }
+
+    // ==================== TYPES ====================
+
+    fn ty(&mut self, ty: &TypeExpr) {
+        match ty {
+            TypeExpr::Unit => self.write("()"),
+            TypeExpr::SelfType => self.write("Self"),
+            TypeExpr::Named { path, args, unit } => {
+                self.write(&path.to_string());
+                self.type_args(args);
+                if let Some(unit) = unit {
+                    self.write(&format!("@{}", unit));
+                }
+            }
+            TypeExpr::Reference {
+                mutable,
+                lifetime,
+                inner,
+            } => {
+                self.reference(*mutable, lifetime.as_deref());
+                self.ty(inner);
+            }
+            TypeExpr::Array { element, size } => {
+                self.write("[");
+                self.ty(element);
+                if let Some(size) = size {
+                    self.write("; ");
+                    self.expr(size);
+                }
+                self.write("]");
+            }
+            TypeExpr::Tuple(elements) => {
+                self.write("(");
+                self.types(elements);
+                if elements.len() == 1 {
+                    self.write(",");
+                }
+                self.write(")");
+            }
+            TypeExpr::Dyn { path, .. } => self.write(&format!("dyn {}", path)),
+            TypeExpr::Function { .. } => self.fn_type("fn", ty),
+            TypeExpr::Infer => self.write("_"),
+            // `{ N + 1 }` stays on one line inside the argument list
+            TypeExpr::Const(value) => match value.as_ref() {
+                Expr::Block { block, .. } => match block.stmts.as_slice() {
+                    [
+                        Stmt::Expr {
+                            expr,
+                            has_semi: false,
+                        },
+                    ] => {
+                        self.write("{ ");
+                        self.expr(expr);
+                        self.write(" }");
+                    }
+                    _ => self.expr(value),
+                },
+                _ => self.expr(value),
+            },
+        }
+    }
+
+    /// `fn(A) -> R with E`, or `Fn(..)` in a where clause. A `()` return
+    /// type is left out.
+    fn fn_type(&mut self, keyword: &str, ty: &TypeExpr) {
+        let TypeExpr::Function {
+            params,
+            return_type,
+            effects,
+        } = ty
+        else {
+            return self.ty(ty);
+        };
+        self.write(&format!("{}(", keyword));
+        self.types(params);
+        self.write(")");
+        if !matches!(**return_type, TypeExpr::Unit) {
+            self.write(" -> ");
+            self.ty(return_type);
+        }
+        if !effects.is_empty() {
+            self.write(" with ");
+            self.effect_refs(effects, ", ");
+        }
+    }
+
+    fn types(&mut self, types: &[TypeExpr]) {
+        for (i, ty) in types.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.ty(ty);
+        }
+    }
+
+    /// A path used as a value, with turbofish arguments on its segments
+    fn value_path(&mut self, path: &Path) {
+        for (i, segment) in path.segments.iter().enumerate() {
+            if i > 0 {
+                self.write("::");
+            }
+            self.write(segment);
+            let args = path.args_of(i);
+            if !args.is_empty() {
+                self.write("::");
+                self.type_args(args);
+            }
+        }
+    }
+
+    fn type_args(&mut self, args: &[TypeExpr]) {
+        if !args.is_empty() {
+            self.write("<");
+            self.types(args);
+            self.write(">");
+        }
+    }
+
+    // ==================== STATEMENTS ====================
+
+    /// A function body. Signatures hold no braces, so the first `{` opens
+    /// it, which lets an otherwise empty body keep the comments inside it.
+    fn fn_body(&mut self, body: &Block) {
+        if body.stmts.iter().all(|s| matches!(s, Stmt::Empty))
+            && let Some(open) = self.find_token(TokenKind::LBrace)
+        {
+            self.cursor = open + 1;
+            if let Some(close) = self.closing_brace()
+                && self.comments.front().is_some_and(|c| c.span.end <= close)
+            {
+                self.write("{");
+                self.newline();
+                self.indent += 1;
+                self.flush_comments(close);
+                self.cursor = close + 1;
+                self.indent -= 1;
+                self.write("}");
+                return;
+            }
+        }
+        self.block(body);
+    }
+
+    fn block(&mut self, block: &Block) {
+        let stmts: Vec<&Stmt> = block
+            .stmts
+            .iter()
+            .filter(|s| !matches!(s, Stmt::Empty))
+            .collect();
+        if stmts.is_empty() {
+            self.write("{}");
+            return;
+        }
+
+        // Statements start fresh, whatever context the block sits in
+        let no_struct = std::mem::replace(&mut self.no_struct, false);
+        self.write("{");
+        self.newline();
+        self.indent += 1;
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.close_body();
+        self.indent -= 1;
+        self.write("}");
+        self.no_struct = no_struct;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let {
+                is_mut,
+                pattern,
+                ty,
+                value,
+            } => {
+                let start = self.find_token(TokenKind::Let);
+                self.begin(start);
+                self.write(if *is_mut { "let mut " } else { "let " });
+                self.pattern(pattern);
+                if let Some(ty) = ty {
+                    self.write(": ");
+                    self.ty(ty);
+                }
+                if let Some(value) = value {
+                    self.write(" = ");
+                    self.expr(value);
+                }
+                self.write(";");
+            }
+            Stmt::LetElse {
+                is_mut,
+                pattern,
+                ty,
+                value,
+                else_block,
+            } => {
+                let start = self.find_token(TokenKind::Let);
+                self.begin(start);
+                self.write(if *is_mut { "let mut " } else { "let " });
+                self.pattern(pattern);
+                if let Some(ty) = ty {
+                    self.write(": ");
+                    self.ty(ty);
+                }
+                self.write(" = ");
+                self.expr(value);
+                self.write(" else ");
+                self.block(else_block);
+                self.write(";");
+            }
+            Stmt::Expr { expr, has_semi } => {
+                self.begin(self.span_start(expr.id()));
+                self.expr(expr);
+                if *has_semi {
+                    self.write(";");
+                }
+            }
+            Stmt::Assign { target, op, value } => {
+                self.begin(self.span_start(target.id()));
+                self.expr(target);
+                self.write(&format!(" {} ", assign_op_symbol(*op)));
+                self.expr(value);
+                self.write(";");
+            }
+            Stmt::Empty => return,
+        }
+        self.newline();
+    }
+
+    // ==================== EXPRESSIONS ====================
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { id, value } => match self.source_text(*id) {
+                Some(text) => self.write(text),
+                None => self.write(&literal_text(value)),
+            },
+            Expr::Path { path, .. } => self.value_path(path),
+            Expr::Binary {
+                op, left, right, ..
+            } => {
+                let prec = binary_precedence(*op);
+                // `x as T < y` would read `T<` as the start of type arguments
+                let cast_before_angle =
+                    matches!(op, BinaryOp::Lt | BinaryOp::Shl) && ends_with_cast(left);
+                self.operand(left, prec, cast_before_angle || is_open(left));
+                self.write(&format!(" {} ", binary_op_symbol(*op)));
+                self.operand(right, prec + 1, false);
+            }
+            Expr::Unary {
+                op, expr: inner, ..
+            } => {
+                self.write(match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                    UnaryOp::Ref => "&",
+                    UnaryOp::RefMut => "&mut ",
+                    UnaryOp::Deref => "*",
+                });
+                // Keep `& &x` and `- -x` from lexing as `&&` and `--`
+                if let Expr::Unary { op: inner_op, .. } = &**inner
+                    && matches!(
+                        (op, inner_op),
+                        (UnaryOp::Ref, UnaryOp::Ref | UnaryOp::RefMut)
+                            | (UnaryOp::Neg, UnaryOp::Neg)
+                    )
+                {
+                    self.write(" ");
+                }
+                self.operand(inner, PREFIX_PREC, false);
+            }
+            Expr::Await { expr: inner, .. } => {
+                self.write("await ");
+                self.operand(inner, PREFIX_PREC, false);
+            }
+            Expr::Call {
+                callee,
+                type_args,
+                args,
+                ..
+            } => {
+                if type_args.is_empty()
+                    && matches!(&**callee, Expr::Path { path, .. } if path.segments == ["vec"])
+                {
+                    self.write("vec![");
+                    self.exprs(args);
+                    self.write("]");
+                    return;
+                }
+                // `a.f(x)` would be a method call rather than a call of the field
+                if matches!(&**callee, Expr::Field { .. }) {
+                    self.operand(callee, POSTFIX_PREC, true);
+                } else {
+                    self.postfix_base(callee);
+                }
+                if !type_args.is_empty() {
+                    self.write("::");
+                    self.type_args(type_args);
+                }
+                self.write("(");
+                self.exprs(args);
+                self.write(")");
+            }
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+                ..
+            } => {
+                self.postfix_base(receiver);
+                self.write(&format!(".{}(", method));
+                self.exprs(args);
+                self.write(")");
+            }
+            Expr::Field { base, field, .. } => {
+                self.postfix_base(base);
+                self.write(&format!(".{}", field));
+            }
+            Expr::TupleField { base, index, .. } => {
+                // `x.0.1` would lex `0.1` as a float
+                if matches!(&**base, Expr::TupleField { .. }) {
+                    self.operand(base, POSTFIX_PREC, true);
+                } else {
+                    self.postfix_base(base);
+                }
+                self.write(&format!(".{}", index));
+            }
+            Expr::Index { base, index, .. } => {
+                self.postfix_base(base);
+                self.write("[");
+                self.delimited(index);
+                self.write("]");
+            }
+            Expr::Cast {
+                expr: inner, ty, ..
+            } => {
+                self.operand(inner, POSTFIX_PREC, is_open(inner));
+                self.write(" as ");
+                self.ty(ty);
+            }
+            Expr::Try { expr: inner, .. } => {
+                self.postfix_base(inner);
+                self.write("?");
+            }
+            Expr::Block { block, .. } => self.block(block),
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.write("if ");
+                self.head(condition);
+                self.write(" ");
+                self.block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.write(" else ");
+                    self.expr(else_branch);
+                }
+            }
+            Expr::Match {
+                scrutinee, arms, ..
+            } => self.match_expr(scrutinee, arms),
+            Expr::Loop { body, .. } => {
+                self.write("loop ");
+                self.block(body);
+            }
+            Expr::While {
+                condition, body, ..
+            } => {
+                self.write("while ");
+                self.head(condition);
+                self.write(" ");
+                self.block(body);
+            }
+            Expr::For {
+                pattern,
+                iter,
+                body,
+                ..
+            } => {
+                self.write("for ");
+                self.pattern(pattern);
+                self.write(" in ");
+                self.head(iter);
+                self.write(" ");
+                self.block(body);
+            }
+            Expr::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                self.expr(start);
+                self.write(if *inclusive { "..=" } else { ".." });
+                self.expr(end);
+            }
+            Expr::Return { value, .. } => {
+                self.write("return");
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.expr(value);
+                }
+            }
+            Expr::Break { value, .. } => {
+                self.write("break");
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.expr(value);
+                }
+            }
+            Expr::Continue { .. } => self.write("continue"),
+            Expr::Closure {
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                // `||` would lex as the or operator
+                self.write(if params.is_empty() { "| |" } else { "|" });
+                for (i, (name, ty)) in params.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.write(name);
+                    if let Some(ty) = ty {
+                        self.write(": ");
+                        self.ty(ty);
+                    }
+                }
+                if !params.is_empty() {
+                    self.write("|");
+                }
+                if let Some(ty) = return_type {
+                    self.write(" -> ");
+                    self.ty(ty);
+                }
+                self.write(" ");
+                self.expr(body);
+            }
+            Expr::Tuple { elements, .. } => {
+                self.write("(");
+                self.exprs(elements);
+                if elements.len() == 1 {
+                    self.write(",");
+                }
+                self.write(")");
+            }
+            Expr::Array { elements, .. } => {
+                self.write("[");
+                self.exprs(elements);
+                self.write("]");
+            }
+            Expr::ArrayRepeat { value, count, .. } => {
+                self.write("[");
+                self.expr(value);
+                self.write("; ");
+                self.expr(count);
+                self.write("]");
+            }
+            Expr::StructLit { path, fields, .. } => {
+                let parenthesize = std::mem::replace(&mut self.no_struct, false);
+                if parenthesize {
+                    self.write("(");
+                }
+                self.write(&path.to_string());
+                if fields.is_empty() {
+                    self.write(" {}");
+                } else {
+                    self.write(" { ");
+                    for (i, (name, value)) in fields.iter().enumerate() {
+                        if i > 0 {
+                            self.write(", ");
+                        }
+                        self.write(name);
+                        let shorthand = matches!(value, Expr::Path { path, .. } if path.segments == [name.as_str()]);
+                        if !shorthand {
+                            self.write(": ");
+                            self.expr(value);
+                        }
+                    }
+                    self.write(" }");
+                }
+                if parenthesize {
+                    self.write(")");
+                }
+                self.no_struct = parenthesize;
+            }
+            Expr::Perform {
+                effect, op, args, ..
+            } => {
+                self.write(&format!("perform {}.{}(", effect, op));
+                self.exprs(args);
+                self.write(")");
+            }
+            Expr::Handle {
+                expr: inner,
+                handler,
+                ..
+            } => {
+                self.write("handle ");
+                self.expr(inner);
+                self.write(&format!(" with {}", handler));
+            }
+            Expr::Sample { distribution, .. } => {
+                self.write("sample(");
+                self.delimited(distribution);
+                self.write(")");
+            }
+        }
+    }
+
+    /// An operand that binds at least as tightly as `min_prec`
+    fn operand(&mut self, expr: &Expr, min_prec: u8, force_parens: bool) {
+        if force_parens || precedence(expr) < min_prec {
+            self.write("(");
+            self.delimited(expr);
+            self.write(")");
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    /// The expression a postfix operator applies to
+    fn postfix_base(&mut self, expr: &Expr) {
+        let parens = is_open(expr) || matches!(expr, Expr::Cast { .. });
+        self.operand(expr, POSTFIX_PREC, parens);
+    }
+
+    /// An expression inside parentheses or brackets, where struct literals
+    /// are unambiguous
+    fn delimited(&mut self, expr: &Expr) {
+        let no_struct = std::mem::replace(&mut self.no_struct, false);
+        self.expr(expr);
+        self.no_struct = no_struct;
+    }
+
+    /// The head of `if`, `while`, `match` or `for`, where a struct literal
+    /// would be read as the body
+    fn head(&mut self, expr: &Expr) {
+        let no_struct = std::mem::replace(&mut self.no_struct, true);
+        self.expr(expr);
+        self.no_struct = no_struct;
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.delimited(expr);
+        }
+    }
+
+    fn match_expr(&mut self, scrutinee: &Expr, arms: &[MatchArm]) {
+        self.write("match ");
+        self.head(scrutinee);
+        if arms.is_empty() {
+            self.write(" {}");
+            return;
+        }
+        let no_struct = std::mem::replace(&mut self.no_struct, false);
+        self.write(" {");
+        self.newline();
+        self.indent += 1;
+        for (i, arm) in arms.iter().enumerate() {
+            let start = self
+                .pattern_start(&arm.pattern)
+                .or_else(|| self.span_start(arm.body.id()));
+            self.begin(start);
+            self.pattern(&arm.pattern);
+            if let Some(guard) = &arm.guard {
+                self.write(" if ");
+                self.expr(guard);
+            }
+            self.write(" => ");
+            self.expr(&arm.body);
+            // A block-like body ends the arm by itself, unless the next
+            // pattern would continue it as a call or a subtraction
+            let continues = arms
+                .get(i + 1)
+                .is_some_and(|next| starts_with_paren_or_minus(&next.pattern));
+            if !is_block_like(&arm.body) || continues {
+                self.write(",");
+            }
+            self.newline();
+        }
+        self.close_body();
+        self.indent -= 1;
+        self.write("}");
+        self.no_struct = no_struct;
+    }
+
+    // ==================== PATTERNS ====================
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard => self.write("_"),
+            Pattern::Literal(value) => self.write(&literal_text(value)),
+            Pattern::Binding { name, mutable, .. } => {
+                if *mutable {
+                    self.write("mut ");
+                }
+                self.write(name);
+            }
+            Pattern::At {
+                name,
+                mutable,
+                pattern,
+                ..
+            } => {
+                if *mutable {
+                    self.write("mut ");
+                }
+                self.write(&format!("{} @ ", name));
+                self.pattern(pattern);
+            }
+            Pattern::Tuple(patterns) => {
+                self.write("(");
+                self.patterns(patterns, ", ");
+                if patterns.len() == 1 {
+                    self.write(",");
+                }
+                self.write(")");
+            }
+            Pattern::Struct { path, fields } => {
+                self.write(&path.to_string());
+                if fields.is_empty() {
+                    self.write(" {}");
+                    return;
+                }
+                self.write(" { ");
+                for (i, (name, pattern)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.write(name);
+                    let shorthand = matches!(pattern, Pattern::Binding { name: bound, mutable: false, .. } if bound == name);
+                    if !shorthand {
+                        self.write(": ");
+                        self.pattern(pattern);
+                    }
+                }
+                self.write(" }");
+            }
+            Pattern::Enum { path, patterns } => {
+                self.write(&path.to_string());
+                if let Some(patterns) = patterns {
+                    self.write("(");
+                    self.patterns(patterns, ", ");
+                    self.write(")");
+                }
+            }
+            Pattern::Or(patterns) => self.patterns(patterns, " | "),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.write(&literal_text(start));
+                self.write(if *inclusive { "..=" } else { ".." });
+                self.write(&literal_text(end));
+            }
+        }
+    }
+
+    fn patterns(&mut self, patterns: &[Pattern], separator: &str) {
+        for (i, pattern) in patterns.iter().enumerate() {
+            if i > 0 {
+                self.write(separator);
+            }
+            self.pattern(pattern);
+        }
+    }
+}
+
+/// Where an item starts in the source, after its attributes
+fn item_start(item: &Item) -> usize {
+    match item {
+        Item::Function(f) => f.span.start,
+        Item::Struct(s) => s.span.start,
+        Item::Enum(e) => e.span.start,
+        Item::Trait(t) => t.span.start,
+        Item::Impl(i) => i.span.start,
+        Item::TypeAlias(t) => t.span.start,
+        Item::Effect(e) => e.span.start,
+        Item::Handler(h) => h.span.start,
+        Item::UnitDef(u) => u.span.start,
+        Item::Import(i) => i.span.start,
+        Item::Extern(e) => e.span.start,
+        Item::Global(g) => g.span.start,
+    }
+}
+
+/// Binding strength of `expr` as an operand, matching the parser
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Binary { op, .. } => binary_precedence(*op),
+        Expr::Unary { .. } | Expr::Await { .. } => PREFIX_PREC,
+        _ => POSTFIX_PREC,
+    }
+}
+
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Or => 1,
+        BinaryOp::And => 2,
+        BinaryOp::Eq | BinaryOp::Ne => 3,
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 4,
+        BinaryOp::BitOr => 5,
+        BinaryOp::BitXor => 6,
+        BinaryOp::BitAnd => 7,
+        BinaryOp::Shl | BinaryOp::Shr => 8,
+        BinaryOp::Add | BinaryOp::Sub => 9,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 10,
+    }
+}
+
+/// Whether `expr` ends in a closure, `return` or `break`, which would take
+/// an operator written after them as part of their own operand
+fn is_open(expr: &Expr) -> bool {
+    match expr {
+        Expr::Closure { .. } | Expr::Return { .. } | Expr::Break { .. } => true,
+        Expr::Unary { expr, .. } | Expr::Await { expr, .. } => is_open(expr),
+        Expr::Binary { right, .. } => is_open(right),
+        _ => false,
+    }
+}
+
+/// Whether `expr` ends in an `as` cast
+fn ends_with_cast(expr: &Expr) -> bool {
+    match expr {
+        Expr::Cast { .. } => true,
+        Expr::Unary { expr, .. } | Expr::Await { expr, .. } => ends_with_cast(expr),
+        Expr::Binary { right, .. } => ends_with_cast(right),
+        _ => false,
+    }
+}
+
+fn is_block_like(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Block { .. }
+            | Expr::If { .. }
+            | Expr::Match { .. }
+            | Expr::Loop { .. }
+            | Expr::While { .. }
+            | Expr::For { .. }
+    )
+}
+
+fn starts_with_paren_or_minus(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Tuple(_) | Pattern::Literal(Literal::Unit) => true,
+        Pattern::Literal(value) | Pattern::Range { start: value, .. } => {
+            literal_text(value).starts_with('-')
+        }
+        Pattern::Or(patterns) => patterns.first().is_some_and(starts_with_paren_or_minus),
+        _ => false,
+    }
+}
+
+/// Source text for a literal value
+fn literal_text(value: &Literal) -> String {
+    match value {
+        Literal::Unit => "()".to_string(),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Int(n) => n.to_string(),
+        Literal::UInt(n) => n.to_string(),
+        Literal::Float(f) => float_text(*f),
+        Literal::Char(c) => format!("'{}'", escape(&c.to_string(), '\'')),
+        Literal::String(s) => format!("\"{}\"", escape(s, '"')),
+        Literal::IntUnit(n, unit) => format!("{}_{}", n, unit),
+        Literal::FloatUnit(f, unit) => format!("{}_{}", float_text(*f), unit),
+    }
+}
+
+/// A float that lexes as a float again, so always with a decimal point
+fn float_text(f: f64) -> String {
+    let text = f.to_string();
+    if text.contains('.') {
+        text
+    } else {
+        format!("{}.0", text)
+    }
+}
+
+fn escape(text: &str, quote: char) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '\\' => out.push_str("\\\\"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn binary_op_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Rem => "%",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitOr => "|",
+        BinaryOp::BitXor => "^",
+        BinaryOp::Shl => "<<",
+        BinaryOp::Shr => ">>",
+    }
+}
+
+fn assign_op_symbol(op: AssignOp) -> &'static str {
+    match op {
+        AssignOp::Assign => "=",
+        AssignOp::AddAssign => "+=",
+        AssignOp::SubAssign => "-=",
+        AssignOp::MulAssign => "*=",
+        AssignOp::DivAssign => "/=",
+        AssignOp::RemAssign => "%=",
+        AssignOp::BitAndAssign => "&=",
+        AssignOp::BitOrAssign => "|=",
+        AssignOp::BitXorAssign => "^=",
+        AssignOp::ShlAssign => "<<=",
+        AssignOp::ShrAssign => ">>=",
+    }
+}
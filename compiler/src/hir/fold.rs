@@ -0,0 +1,142 @@
+//! Constant folding over HIR
+//!
+//! Binary and unary operations whose operands are literals are replaced by
+//! the literal they evaluate to, innermost first, so `2 + 3 * 4` becomes
+//! `14`. An operation is only folded when its result is the same as at run
+//! time: integer results must fit the expression's type, and anything that
+//! would overflow or divide by zero is left for the runtime to report.
+
+use super::visit::{HirVisitorMut, walk_expr_mut, walk_hir_mut};
+use super::*;
+
+/// Fold operations on literal operands throughout `hir`
+pub fn fold_constants(hir: &mut Hir) {
+    walk_hir_mut(&mut ConstantFolder, hir);
+}
+
+struct ConstantFolder;
+
+impl HirVisitorMut for ConstantFolder {
+    fn visit_expr_mut(&mut self, expr: &mut HirExpr) {
+        walk_expr_mut(self, expr);
+
+        let folded = match &expr.kind {
+            HirExprKind::Binary { op, left, right } => match (&left.kind, &right.kind) {
+                (HirExprKind::Literal(l), HirExprKind::Literal(r)) => {
+                    fold_binary(*op, l, r, &left.ty, &expr.ty)
+                }
+                _ => None,
+            },
+            HirExprKind::Unary { op, expr: operand } => match &operand.kind {
+                HirExprKind::Literal(value) => fold_unary(*op, value, &expr.ty),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(literal) = folded {
+            expr.kind = HirExprKind::Literal(literal);
+        }
+    }
+}
+
+/// Evaluate a binary operation on literals of type `operand_ty`, or `None`
+/// if it would overflow, divide by zero, or the operands aren't foldable
+fn fold_binary(
+    op: HirBinaryOp,
+    left: &HirLiteral,
+    right: &HirLiteral,
+    operand_ty: &HirType,
+    ty: &HirType,
+) -> Option<HirLiteral> {
+    use HirBinaryOp::*;
+
+    match (left, right) {
+        (HirLiteral::Int(a), HirLiteral::Int(b)) => {
+            let (a, b) = (*a, *b);
+            int_range(operand_ty)?;
+            let int = |v: Option<i64>| v.filter(|v| fits(*v, ty)).map(HirLiteral::Int);
+            match op {
+                Add => int(a.checked_add(b)),
+                Sub => int(a.checked_sub(b)),
+                Mul => int(a.checked_mul(b)),
+                Div => int(a.checked_div(b)),
+                Rem => int(a.checked_rem(b)),
+                BitAnd => int(Some(a & b)),
+                BitOr => int(Some(a | b)),
+                BitXor => int(Some(a ^ b)),
+                // Shifts past the width or that lose bits are left alone
+                Shl => int(u32::try_from(b)
+                    .ok()
+                    .and_then(|b| a.checked_shl(b))
+                    .filter(|v| v >> b == a)),
+                Shr => int(u32::try_from(b).ok().and_then(|b| a.checked_shr(b))),
+                Eq => Some(HirLiteral::Bool(a == b)),
+                Ne => Some(HirLiteral::Bool(a != b)),
+                Lt => Some(HirLiteral::Bool(a < b)),
+                Le => Some(HirLiteral::Bool(a <= b)),
+                Gt => Some(HirLiteral::Bool(a > b)),
+                Ge => Some(HirLiteral::Bool(a >= b)),
+                And | Or => None,
+            }
+        }
+        // `f32` arithmetic rounds differently, so only `f64` is folded
+        (HirLiteral::Float(a), HirLiteral::Float(b)) if *operand_ty == HirType::F64 => {
+            let (a, b) = (*a, *b);
+            match op {
+                Add => Some(HirLiteral::Float(a + b)),
+                Sub => Some(HirLiteral::Float(a - b)),
+                Mul => Some(HirLiteral::Float(a * b)),
+                Div => Some(HirLiteral::Float(a / b)),
+                Eq => Some(HirLiteral::Bool(a == b)),
+                Ne => Some(HirLiteral::Bool(a != b)),
+                Lt => Some(HirLiteral::Bool(a < b)),
+                Le => Some(HirLiteral::Bool(a <= b)),
+                Gt => Some(HirLiteral::Bool(a > b)),
+                Ge => Some(HirLiteral::Bool(a >= b)),
+                _ => None,
+            }
+        }
+        (HirLiteral::Bool(a), HirLiteral::Bool(b)) => match op {
+            And | BitAnd => Some(HirLiteral::Bool(*a && *b)),
+            Or | BitOr => Some(HirLiteral::Bool(*a || *b)),
+            BitXor | Ne => Some(HirLiteral::Bool(a != b)),
+            Eq => Some(HirLiteral::Bool(a == b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fold_unary(op: HirUnaryOp, value: &HirLiteral, ty: &HirType) -> Option<HirLiteral> {
+    match (op, value) {
+        (HirUnaryOp::Neg, HirLiteral::Int(v)) => v
+            .checked_neg()
+            .filter(|v| fits(*v, ty))
+            .map(HirLiteral::Int),
+        (HirUnaryOp::Neg, HirLiteral::Float(v)) if *ty == HirType::F64 => {
+            Some(HirLiteral::Float(-v))
+        }
+        (HirUnaryOp::Not, HirLiteral::Bool(b)) => Some(HirLiteral::Bool(!b)),
+        _ => None,
+    }
+}
+
+/// Range of the integer type `ty`. Types wider than the folder's `i64`
+/// arithmetic have none and are never folded.
+pub fn int_range(ty: &HirType) -> Option<(i64, i64)> {
+    Some(match ty {
+        HirType::I8 => (i8::MIN as i64, i8::MAX as i64),
+        HirType::I16 => (i16::MIN as i64, i16::MAX as i64),
+        HirType::I32 => (i32::MIN as i64, i32::MAX as i64),
+        HirType::I64 => (i64::MIN, i64::MAX),
+        HirType::U8 => (0, u8::MAX as i64),
+        HirType::U16 => (0, u16::MAX as i64),
+        HirType::U32 => (0, u32::MAX as i64),
+        _ => return None,
+    })
+}
+
+/// Whether `value` is representable in the integer type `ty`
+fn fits(value: i64, ty: &HirType) -> bool {
+    int_range(ty).is_some_and(|(min, max)| (min..=max).contains(&value))
+}
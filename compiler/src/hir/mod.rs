@@ -6,7 +6,15 @@
 //! - Desugared constructs
 //! - Ownership and borrowing information
 
+pub mod fold;
+pub mod visit;
+
+use std::fmt;
+
 use crate::common::NodeId;
+use crate::types::ArrayLen;
+
+pub use fold::fold_constants;
 
 /// HIR root
 #[derive(Debug, Clone)]
@@ -37,6 +45,26 @@ pub struct HirFn {
     pub name: String,
     pub ty: HirFnType,
     pub body: HirBlock,
+    /// Marked `#[pure]`: no effects, result depends only on the arguments
+    pub is_pure: bool,
+    /// Declared `async`: a call yields a future that runs when awaited
+    pub is_async: bool,
+    /// Inlining requested with `#[inline]` or `#[noinline]`
+    pub inline: InlineHint,
+    /// Declared `kernel` or marked `#[gpu]`: an entry point launched on a GPU
+    pub is_kernel: bool,
+}
+
+/// Inlining preference declared on a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InlineHint {
+    /// No attribute: the optimizer decides by size
+    #[default]
+    Auto,
+    /// `#[inline]` or `#[inline(always)]`: inline regardless of size
+    Always,
+    /// `#[noinline]` or `#[inline(never)]`: never inline
+    Never,
 }
 
 /// Function type in HIR
@@ -66,6 +94,8 @@ pub struct HirStruct {
     pub fields: Vec<HirField>,
     pub is_linear: bool,
     pub is_affine: bool,
+    /// `#[packed]`: fields are laid out without padding
+    pub is_packed: bool,
 }
 
 /// HIR field
@@ -92,6 +122,8 @@ pub struct HirVariant {
     pub id: NodeId,
     pub name: String,
     pub fields: Vec<HirType>,
+    /// Tag value: the explicit `= n`, or one more than the previous variant
+    pub discriminant: i64,
 }
 
 /// HIR trait
@@ -120,6 +152,13 @@ pub struct HirImpl {
     pub methods: Vec<HirFn>,
 }
 
+impl HirImpl {
+    /// Symbol a method of this impl is emitted under: `Type::method`
+    pub fn method_symbol(&self, method: &str) -> Option<String> {
+        self.self_ty.method_symbol(method)
+    }
+}
+
 /// HIR type alias
 #[derive(Debug, Clone)]
 pub struct HirTypeAlias {
@@ -164,6 +203,7 @@ pub struct HirHandler {
     pub name: String,
     pub effect: String,
     pub cases: Vec<HirHandlerCase>,
+    pub return_clause: Option<HirHandlerReturn>,
 }
 
 /// HIR handler case
@@ -175,6 +215,13 @@ pub struct HirHandlerCase {
     pub body: HirExpr,
 }
 
+/// HIR handler return clause
+#[derive(Debug, Clone)]
+pub struct HirHandlerReturn {
+    pub param: String,
+    pub body: HirExpr,
+}
+
 // ==================== TYPES ====================
 
 /// HIR type (fully resolved)
@@ -213,7 +260,7 @@ pub enum HirType {
     /// Array
     Array {
         element: Box<HirType>,
-        size: Option<usize>,
+        size: Option<ArrayLen>,
     },
     /// Tuple
     Tuple(Vec<HirType>),
@@ -222,6 +269,8 @@ pub enum HirType {
         name: String,
         args: Vec<HirType>,
     },
+    /// Trait object, used behind a reference
+    Dyn(String),
     /// Function type
     Fn {
         params: Vec<HirType>,
@@ -236,6 +285,51 @@ pub enum HirType {
 }
 
 impl HirType {
+    /// Name of the struct or enum this type refers to, seeing through references
+    pub fn nominal_name(&self) -> Option<&str> {
+        match self {
+            HirType::Named { name, .. } => Some(name),
+            HirType::Ref { inner, .. } => inner.nominal_name(),
+            _ => None,
+        }
+    }
+
+    /// Size in bytes of a type with a fixed machine representation
+    pub fn size_bytes(&self) -> Option<usize> {
+        match self {
+            HirType::Bool | HirType::I8 | HirType::U8 => Some(1),
+            HirType::I16 | HirType::U16 => Some(2),
+            HirType::I32 | HirType::U32 | HirType::F32 | HirType::Char => Some(4),
+            HirType::I64
+            | HirType::U64
+            | HirType::Isize
+            | HirType::Usize
+            | HirType::F64
+            | HirType::Ref { .. } => Some(8),
+            HirType::I128 | HirType::U128 => Some(16),
+            HirType::Array {
+                element,
+                size: Some(ArrayLen::Fixed(n)),
+            } => element.size_bytes().map(|size| size * n),
+            _ => None,
+        }
+    }
+
+    /// Trait of a trait object, seen through any references
+    pub fn dyn_trait(&self) -> Option<&str> {
+        match self {
+            HirType::Dyn(name) => Some(name),
+            HirType::Ref { inner, .. } => inner.dyn_trait(),
+            _ => None,
+        }
+    }
+
+    /// Symbol of `method` defined in an impl for this type
+    pub fn method_symbol(&self, method: &str) -> Option<String> {
+        self.nominal_name()
+            .map(|name| format!("{}::{}", name, method))
+    }
+
     pub fn is_primitive(&self) -> bool {
         matches!(
             self,
@@ -302,6 +396,78 @@ impl HirType {
     }
 }
 
+/// Renders types in surface syntax, e.g. `Vec<i64>` or `fn(i64) -> bool`
+impl fmt::Display for HirType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HirType::Unit => write!(f, "()"),
+            HirType::Bool => write!(f, "bool"),
+            HirType::I8 => write!(f, "i8"),
+            HirType::I16 => write!(f, "i16"),
+            HirType::I32 => write!(f, "i32"),
+            HirType::I64 => write!(f, "i64"),
+            HirType::I128 => write!(f, "i128"),
+            HirType::Isize => write!(f, "isize"),
+            HirType::U8 => write!(f, "u8"),
+            HirType::U16 => write!(f, "u16"),
+            HirType::U32 => write!(f, "u32"),
+            HirType::U64 => write!(f, "u64"),
+            HirType::U128 => write!(f, "u128"),
+            HirType::Usize => write!(f, "usize"),
+            HirType::F32 => write!(f, "f32"),
+            HirType::F64 => write!(f, "f64"),
+            HirType::Char => write!(f, "char"),
+            HirType::String => write!(f, "String"),
+            HirType::Ref { mutable, inner } => {
+                write!(f, "&{}{}", if *mutable { "mut " } else { "" }, inner)
+            }
+            HirType::Array { element, size } => match size {
+                Some(n) => write!(f, "[{}; {}]", element, n),
+                None => write!(f, "[{}]", element),
+            },
+            HirType::Tuple(elems) => {
+                write!(f, "(")?;
+                write_list(f, elems)?;
+                if elems.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            HirType::Named { name, args } => {
+                write!(f, "{}", name)?;
+                if !args.is_empty() {
+                    write!(f, "<")?;
+                    write_list(f, args)?;
+                    write!(f, ">")?;
+                }
+                Ok(())
+            }
+            HirType::Dyn(name) => write!(f, "dyn {}", name),
+            HirType::Fn {
+                params,
+                return_type,
+            } => {
+                write!(f, "fn(")?;
+                write_list(f, params)?;
+                write!(f, ") -> {}", return_type)
+            }
+            HirType::Var(v) => write!(f, "?T{}", v),
+            HirType::Never => write!(f, "!"),
+            HirType::Error => write!(f, "{{error}}"),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, types: &[HirType]) -> fmt::Result {
+    for (i, ty) in types.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", ty)?;
+    }
+    Ok(())
+}
+
 // ==================== EXPRESSIONS ====================
 
 /// HIR expression (with type information)
@@ -348,9 +514,19 @@ pub enum HirExprKind {
     Index {
         base: Box<HirExpr>,
         index: Box<HirExpr>,
+        /// The checker proved the index within bounds, so no runtime check
+        /// is needed
+        in_bounds: bool,
     },
     /// Type cast
     Cast { expr: Box<HirExpr>, target: HirType },
+    /// Reinterpret the bits of a value as another type of the same size
+    Transmute { expr: Box<HirExpr>, target: HirType },
+    /// Reference to a concrete type used as a `&dyn Trait`
+    DynCoerce {
+        expr: Box<HirExpr>,
+        trait_name: String,
+    },
     /// Block
     Block(HirBlock),
     /// If expression
@@ -381,6 +557,8 @@ pub enum HirExprKind {
     Tuple(Vec<HirExpr>),
     /// Array
     Array(Vec<HirExpr>),
+    /// Array of `len` copies of `value`
+    ArrayRepeat { value: Box<HirExpr>, len: ArrayLen },
     /// Struct literal
     Struct {
         name: String,
@@ -406,6 +584,8 @@ pub enum HirExprKind {
     Handle { expr: Box<HirExpr>, handler: String },
     /// Sample from distribution
     Sample(Box<HirExpr>),
+    /// Wait for a future to complete
+    Await(Box<HirExpr>),
 }
 
 /// HIR literal
@@ -414,6 +594,8 @@ pub enum HirLiteral {
     Unit,
     Bool(bool),
     Int(i64),
+    /// Integer literal above `u64::MAX`, typed as `u128`
+    U128(u128),
     Float(f64),
     Char(char),
     String(String),
@@ -471,6 +653,12 @@ pub enum HirPattern {
         name: String,
         mutable: bool,
     },
+    /// `name @ pattern`
+    At {
+        name: String,
+        mutable: bool,
+        pattern: Box<HirPattern>,
+    },
     Tuple(Vec<HirPattern>),
     Struct {
         name: String,
@@ -482,6 +670,11 @@ pub enum HirPattern {
         patterns: Vec<HirPattern>,
     },
     Or(Vec<HirPattern>),
+    Range {
+        start: HirLiteral,
+        end: HirLiteral,
+        inclusive: bool,
+    },
 }
 
 // ==================== BLOCKS & STATEMENTS ====================
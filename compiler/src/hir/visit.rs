@@ -0,0 +1,475 @@
+//! HIR traversal
+//!
+//! `HirVisitor` and `HirVisitorMut` walk items, statements, expressions and
+//! patterns. Every `visit_*` method defaults to the matching `walk_*`
+//! function, so a pass only overrides the nodes it cares about and calls
+//! `walk_*` itself when it still wants to descend into children.
+
+use super::*;
+
+/// Read-only HIR visitor
+pub trait HirVisitor {
+    fn visit_item(&mut self, item: &HirItem) {
+        walk_item(self, item);
+    }
+
+    fn visit_fn(&mut self, func: &HirFn) {
+        walk_fn(self, func);
+    }
+
+    fn visit_block(&mut self, block: &HirBlock) {
+        walk_block(self, block);
+    }
+
+    fn visit_stmt(&mut self, stmt: &HirStmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &HirExpr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &HirPattern) {
+        walk_pattern(self, pattern);
+    }
+}
+
+/// Visit every item of a HIR module
+pub fn walk_hir<V: HirVisitor + ?Sized>(visitor: &mut V, hir: &Hir) {
+    for item in &hir.items {
+        visitor.visit_item(item);
+    }
+}
+
+/// Visit the bodies contained in an item
+pub fn walk_item<V: HirVisitor + ?Sized>(visitor: &mut V, item: &HirItem) {
+    match item {
+        HirItem::Function(f) => visitor.visit_fn(f),
+        HirItem::Impl(i) => {
+            for method in &i.methods {
+                visitor.visit_fn(method);
+            }
+        }
+        HirItem::Handler(h) => {
+            for case in &h.cases {
+                visitor.visit_expr(&case.body);
+            }
+            if let Some(ret) = &h.return_clause {
+                visitor.visit_expr(&ret.body);
+            }
+        }
+        HirItem::Global(g) => visitor.visit_expr(&g.value),
+        HirItem::Struct(_)
+        | HirItem::Enum(_)
+        | HirItem::Trait(_)
+        | HirItem::TypeAlias(_)
+        | HirItem::Effect(_) => {}
+    }
+}
+
+/// Visit a function body
+pub fn walk_fn<V: HirVisitor + ?Sized>(visitor: &mut V, func: &HirFn) {
+    visitor.visit_block(&func.body);
+}
+
+/// Visit the statements of a block
+pub fn walk_block<V: HirVisitor + ?Sized>(visitor: &mut V, block: &HirBlock) {
+    for stmt in &block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+/// Visit the expressions of a statement
+pub fn walk_stmt<V: HirVisitor + ?Sized>(visitor: &mut V, stmt: &HirStmt) {
+    match stmt {
+        HirStmt::Let { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        HirStmt::Expr(expr) => visitor.visit_expr(expr),
+        HirStmt::Assign { target, value } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(value);
+        }
+    }
+}
+
+/// Visit the sub-expressions, blocks and patterns of an expression
+pub fn walk_expr<V: HirVisitor + ?Sized>(visitor: &mut V, expr: &HirExpr) {
+    match &expr.kind {
+        HirExprKind::Literal(_)
+        | HirExprKind::Local(_)
+        | HirExprKind::Global(_)
+        | HirExprKind::Continue => {}
+        HirExprKind::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        HirExprKind::Unary { expr, .. }
+        | HirExprKind::Field { base: expr, .. }
+        | HirExprKind::TupleField { base: expr, .. }
+        | HirExprKind::Cast { expr, .. }
+        | HirExprKind::Transmute { expr, .. }
+        | HirExprKind::DynCoerce { expr, .. }
+        | HirExprKind::Ref { expr, .. }
+        | HirExprKind::Deref(expr)
+        | HirExprKind::Handle { expr, .. }
+        | HirExprKind::Sample(expr)
+        | HirExprKind::Await(expr) => visitor.visit_expr(expr),
+        HirExprKind::Call { func, args } => {
+            visitor.visit_expr(func);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        HirExprKind::MethodCall { receiver, args, .. } => {
+            visitor.visit_expr(receiver);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        HirExprKind::Index { base, index, .. } => {
+            visitor.visit_expr(base);
+            visitor.visit_expr(index);
+        }
+        HirExprKind::Block(block) | HirExprKind::Loop(block) => visitor.visit_block(block),
+        HirExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_block(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_expr(else_branch);
+            }
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            visitor.visit_expr(scrutinee);
+            for arm in arms {
+                visitor.visit_pattern(&arm.pattern);
+                if let Some(guard) = &arm.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_expr(&arm.body);
+            }
+        }
+        HirExprKind::Return(value) | HirExprKind::Break(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        HirExprKind::Closure { body, .. } => visitor.visit_expr(body),
+        HirExprKind::Tuple(elements) | HirExprKind::Array(elements) => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        HirExprKind::ArrayRepeat { value, .. } => visitor.visit_expr(value),
+        HirExprKind::Struct { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
+            }
+        }
+        HirExprKind::Variant { fields, .. } => {
+            for field in fields {
+                visitor.visit_expr(field);
+            }
+        }
+        HirExprKind::Perform { args, .. } => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+    }
+}
+
+/// Visit the sub-patterns of a pattern
+pub fn walk_pattern<V: HirVisitor + ?Sized>(visitor: &mut V, pattern: &HirPattern) {
+    match pattern {
+        HirPattern::Wildcard
+        | HirPattern::Literal(_)
+        | HirPattern::Binding { .. }
+        | HirPattern::Range { .. } => {}
+        HirPattern::Tuple(patterns)
+        | HirPattern::Variant { patterns, .. }
+        | HirPattern::Or(patterns) => {
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        HirPattern::At { pattern, .. } => visitor.visit_pattern(pattern),
+        HirPattern::Struct { fields, .. } => {
+            for (_, pattern) in fields {
+                visitor.visit_pattern(pattern);
+            }
+        }
+    }
+}
+
+/// Mutating HIR visitor
+pub trait HirVisitorMut {
+    fn visit_item_mut(&mut self, item: &mut HirItem) {
+        walk_item_mut(self, item);
+    }
+
+    fn visit_fn_mut(&mut self, func: &mut HirFn) {
+        walk_fn_mut(self, func);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut HirBlock) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut HirStmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut HirExpr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut HirPattern) {
+        walk_pattern_mut(self, pattern);
+    }
+}
+
+/// Visit every item of a HIR module mutably
+pub fn walk_hir_mut<V: HirVisitorMut + ?Sized>(visitor: &mut V, hir: &mut Hir) {
+    for item in &mut hir.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+/// Visit the bodies contained in an item mutably
+pub fn walk_item_mut<V: HirVisitorMut + ?Sized>(visitor: &mut V, item: &mut HirItem) {
+    match item {
+        HirItem::Function(f) => visitor.visit_fn_mut(f),
+        HirItem::Impl(i) => {
+            for method in &mut i.methods {
+                visitor.visit_fn_mut(method);
+            }
+        }
+        HirItem::Handler(h) => {
+            for case in &mut h.cases {
+                visitor.visit_expr_mut(&mut case.body);
+            }
+            if let Some(ret) = &mut h.return_clause {
+                visitor.visit_expr_mut(&mut ret.body);
+            }
+        }
+        HirItem::Global(g) => visitor.visit_expr_mut(&mut g.value),
+        HirItem::Struct(_)
+        | HirItem::Enum(_)
+        | HirItem::Trait(_)
+        | HirItem::TypeAlias(_)
+        | HirItem::Effect(_) => {}
+    }
+}
+
+/// Visit a function body mutably
+pub fn walk_fn_mut<V: HirVisitorMut + ?Sized>(visitor: &mut V, func: &mut HirFn) {
+    visitor.visit_block_mut(&mut func.body);
+}
+
+/// Visit the statements of a block mutably
+pub fn walk_block_mut<V: HirVisitorMut + ?Sized>(visitor: &mut V, block: &mut HirBlock) {
+    for stmt in &mut block.stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+/// Visit the expressions of a statement mutably
+pub fn walk_stmt_mut<V: HirVisitorMut + ?Sized>(visitor: &mut V, stmt: &mut HirStmt) {
+    match stmt {
+        HirStmt::Let { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        HirStmt::Expr(expr) => visitor.visit_expr_mut(expr),
+        HirStmt::Assign { target, value } => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(value);
+        }
+    }
+}
+
+/// Visit the sub-expressions, blocks and patterns of an expression mutably
+pub fn walk_expr_mut<V: HirVisitorMut + ?Sized>(visitor: &mut V, expr: &mut HirExpr) {
+    match &mut expr.kind {
+        HirExprKind::Literal(_)
+        | HirExprKind::Local(_)
+        | HirExprKind::Global(_)
+        | HirExprKind::Continue => {}
+        HirExprKind::Binary { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        HirExprKind::Unary { expr, .. }
+        | HirExprKind::Field { base: expr, .. }
+        | HirExprKind::TupleField { base: expr, .. }
+        | HirExprKind::Cast { expr, .. }
+        | HirExprKind::Transmute { expr, .. }
+        | HirExprKind::DynCoerce { expr, .. }
+        | HirExprKind::Ref { expr, .. }
+        | HirExprKind::Deref(expr)
+        | HirExprKind::Handle { expr, .. }
+        | HirExprKind::Sample(expr)
+        | HirExprKind::Await(expr) => visitor.visit_expr_mut(expr),
+        HirExprKind::Call { func, args } => {
+            visitor.visit_expr_mut(func);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        HirExprKind::MethodCall { receiver, args, .. } => {
+            visitor.visit_expr_mut(receiver);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        HirExprKind::Index { base, index, .. } => {
+            visitor.visit_expr_mut(base);
+            visitor.visit_expr_mut(index);
+        }
+        HirExprKind::Block(block) | HirExprKind::Loop(block) => visitor.visit_block_mut(block),
+        HirExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_block_mut(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_expr_mut(else_branch);
+            }
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            visitor.visit_expr_mut(scrutinee);
+            for arm in arms {
+                visitor.visit_pattern_mut(&mut arm.pattern);
+                if let Some(guard) = &mut arm.guard {
+                    visitor.visit_expr_mut(guard);
+                }
+                visitor.visit_expr_mut(&mut arm.body);
+            }
+        }
+        HirExprKind::Return(value) | HirExprKind::Break(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        HirExprKind::Closure { body, .. } => visitor.visit_expr_mut(body),
+        HirExprKind::Tuple(elements) | HirExprKind::Array(elements) => {
+            for element in elements {
+                visitor.visit_expr_mut(element);
+            }
+        }
+        HirExprKind::ArrayRepeat { value, .. } => visitor.visit_expr_mut(value),
+        HirExprKind::Struct { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        HirExprKind::Variant { fields, .. } => {
+            for field in fields {
+                visitor.visit_expr_mut(field);
+            }
+        }
+        HirExprKind::Perform { args, .. } => {
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+    }
+}
+
+/// Visit the sub-patterns of a pattern mutably
+pub fn walk_pattern_mut<V: HirVisitorMut + ?Sized>(visitor: &mut V, pattern: &mut HirPattern) {
+    match pattern {
+        HirPattern::Wildcard
+        | HirPattern::Literal(_)
+        | HirPattern::Binding { .. }
+        | HirPattern::Range { .. } => {}
+        HirPattern::Tuple(patterns)
+        | HirPattern::Variant { patterns, .. }
+        | HirPattern::Or(patterns) => {
+            for pattern in patterns {
+                visitor.visit_pattern_mut(pattern);
+            }
+        }
+        HirPattern::At { pattern, .. } => visitor.visit_pattern_mut(pattern),
+        HirPattern::Struct { fields, .. } => {
+            for (_, pattern) in fields {
+                visitor.visit_pattern_mut(pattern);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CallCounter {
+        calls: usize,
+    }
+
+    impl HirVisitor for CallCounter {
+        fn visit_expr(&mut self, expr: &HirExpr) {
+            if let HirExprKind::Call { .. } = expr.kind {
+                self.calls += 1;
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    struct IntDoubler;
+
+    impl HirVisitorMut for IntDoubler {
+        fn visit_expr_mut(&mut self, expr: &mut HirExpr) {
+            if let HirExprKind::Literal(HirLiteral::Int(n)) = &mut expr.kind {
+                *n *= 2;
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    fn check(source: &str) -> Hir {
+        crate::typecheck(source).unwrap()
+    }
+
+    #[test]
+    fn test_visitor_counts_calls() {
+        let hir = check(
+            r#"
+            fn one() -> i64 { 1 }
+            fn add(a: i64, b: i64) -> i64 { a + b }
+            fn main() -> i64 {
+                let x = add(one(), one());
+                if x > 1 { add(x, one()) } else { x }
+            }
+        "#,
+        );
+
+        let mut counter = CallCounter { calls: 0 };
+        walk_hir(&mut counter, &hir);
+        assert_eq!(counter.calls, 5);
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_literals() {
+        let mut hir = check("fn main() -> i64 { 21 }");
+        walk_hir_mut(&mut IntDoubler, &mut hir);
+
+        let HirItem::Function(f) = &hir.items[0] else {
+            panic!("Expected function");
+        };
+        let HirStmt::Expr(expr) = &f.body.stmts[0] else {
+            panic!("Expected expression statement");
+        };
+        assert!(matches!(expr.kind, HirExprKind::Literal(HirLiteral::Int(42))));
+    }
+}
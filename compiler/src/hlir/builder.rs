@@ -4,6 +4,8 @@
 //! and basic blocks, managing SSA value numbering automatically.
 
 use super::ir::*;
+use crate::common::NodeId;
+use crate::hir::InlineHint;
 use std::collections::HashMap;
 
 /// Builder for constructing HLIR modules
@@ -53,6 +55,8 @@ pub struct FunctionBuilder {
     var_values: HashMap<String, ValueId>,
     /// Map from variable names to their stack slots (for mutable variables)
     var_slots: HashMap<String, ValueId>,
+    /// Source node attached to emitted instructions
+    current_span: Option<NodeId>,
 }
 
 impl FunctionBuilder {
@@ -66,6 +70,8 @@ impl FunctionBuilder {
                 effects: Vec::new(),
                 blocks: Vec::new(),
                 is_kernel: false,
+                is_pure: false,
+                inline: InlineHint::Auto,
                 locals: HashMap::new(),
             },
             next_block_id: 0,
@@ -73,6 +79,7 @@ impl FunctionBuilder {
             current_block: None,
             var_values: HashMap::new(),
             var_slots: HashMap::new(),
+            current_span: None,
         }
     }
 
@@ -157,23 +164,38 @@ impl FunctionBuilder {
             .map(|slot| self.build_load(slot, ty))
     }
 
+    /// Source node attached to instructions emitted from now on
+    pub fn current_span(&self) -> Option<NodeId> {
+        self.current_span
+    }
+
+    /// Attach `span` to subsequently emitted instructions, returning the
+    /// previous one so callers can restore it
+    pub fn set_span(&mut self, span: Option<NodeId>) -> Option<NodeId> {
+        std::mem::replace(&mut self.current_span, span)
+    }
+
     // ==================== Instruction Builders ====================
 
     fn emit(&mut self, op: Op, ty: HlirType) -> ValueId {
         let result = self.fresh_value();
+        let span = self.current_span;
         self.current_block_mut().instructions.push(HlirInstr {
             result: Some(result),
             op,
             ty,
+            span,
         });
         result
     }
 
     fn emit_void(&mut self, op: Op) {
+        let span = self.current_span;
         self.current_block_mut().instructions.push(HlirInstr {
             result: None,
             op,
             ty: HlirType::Void,
+            span,
         });
     }
 
@@ -393,6 +415,17 @@ impl FunctionBuilder {
         )
     }
 
+    /// Build a bit reinterpretation between same-sized types
+    pub fn build_bitcast(&mut self, value: ValueId, target: HlirType) -> ValueId {
+        self.emit(
+            Op::Bitcast {
+                value,
+                target: target.clone(),
+            },
+            target,
+        )
+    }
+
     /// Build a phi node
     pub fn build_phi(&mut self, incoming: Vec<(BlockId, ValueId)>, ty: HlirType) -> ValueId {
         self.emit(Op::Phi { incoming }, ty)
@@ -463,11 +496,23 @@ impl FunctionBuilder {
         condition: ValueId,
         then_block: BlockId,
         else_block: BlockId,
+    ) {
+        self.build_cond_branch_with_hint(condition, then_block, else_block, BranchHint::None);
+    }
+
+    /// Build a conditional branch carrying a static prediction
+    pub fn build_cond_branch_with_hint(
+        &mut self,
+        condition: ValueId,
+        then_block: BlockId,
+        else_block: BlockId,
+        hint: BranchHint,
     ) {
         self.set_terminator(HlirTerminator::CondBranch {
             condition,
             then_block,
             else_block,
+            hint,
         });
     }
 
@@ -485,6 +530,11 @@ impl FunctionBuilder {
         self.set_terminator(HlirTerminator::Unreachable);
     }
 
+    /// Build a trap that aborts execution
+    pub fn build_trap(&mut self) {
+        self.set_terminator(HlirTerminator::Trap);
+    }
+
     /// Finish building the function
     pub fn build(self) -> HlirFunction {
         self.func
@@ -3,7 +3,9 @@
 //! This module defines the core IR types for HLIR, which uses SSA form
 //! with explicit basic blocks and control flow.
 
-use crate::hir::HirType;
+use crate::common::NodeId;
+use crate::hir::{HirType, InlineHint};
+use crate::types::ArrayLen;
 use std::collections::HashMap;
 
 /// HLIR module - top-level compilation unit
@@ -40,6 +42,10 @@ pub struct HlirFunction {
     pub effects: Vec<String>,
     pub blocks: Vec<HlirBlock>,
     pub is_kernel: bool,
+    /// Declared `#[pure]`, so calls with constant arguments may be folded
+    pub is_pure: bool,
+    /// Declared inlining preference
+    pub inline: InlineHint,
     /// Local variable types (for stack allocation)
     pub locals: HashMap<ValueId, HlirType>,
 }
@@ -101,6 +107,8 @@ pub struct HlirGlobal {
 pub struct HlirTypeDef {
     pub name: String,
     pub kind: HlirTypeDefKind,
+    /// Fields are laid out without padding (`#[packed]`)
+    pub packed: bool,
 }
 
 /// Type definition kind
@@ -158,14 +166,27 @@ impl HlirType {
             HirType::F64 => HlirType::F64,
             HirType::Char => HlirType::U32,
             HirType::String => HlirType::Ptr(Box::new(HlirType::U8)),
+            // A reference to a trait object is a fat pointer, the data pointer
+            // paired with the vtable of the concrete type
+            HirType::Ref { inner, .. } if matches!(**inner, HirType::Dyn(_)) => {
+                Self::from_hir(inner)
+            }
             HirType::Ref { inner, .. } => HlirType::Ptr(Box::new(Self::from_hir(inner))),
             HirType::Array { element, size } => {
                 let elem = Self::from_hir(element);
-                HlirType::Array(Box::new(elem), size.unwrap_or(0))
+                HlirType::Array(
+                    Box::new(elem),
+                    size.as_ref().and_then(ArrayLen::fixed).unwrap_or(0),
+                )
             }
             HirType::Tuple(elems) if elems.is_empty() => HlirType::Void,
-            HirType::Tuple(elems) => HlirType::Tuple(elems.iter().map(Self::from_hir).collect()),
+            // Tuples are anonymous structs; lowering registers their layout
+            HirType::Tuple(elems) => {
+                let elems: Vec<_> = elems.iter().map(Self::from_hir).collect();
+                HlirType::Struct(Self::tuple_struct_name(&elems))
+            }
             HirType::Named { name, .. } => HlirType::Struct(name.clone()),
+            HirType::Dyn(name) => HlirType::Struct(Self::dyn_struct_name(name)),
             HirType::Fn {
                 params,
                 return_type,
@@ -173,10 +194,22 @@ impl HlirType {
                 params: params.iter().map(Self::from_hir).collect(),
                 return_type: Box::new(Self::from_hir(return_type)),
             },
+            // Diverging expressions and unconstrained type variables carry
+            // no data. Error types are rejected by `lower::lower`.
             HirType::Var(_) | HirType::Error | HirType::Never => HlirType::Void,
         }
     }
 
+    /// Name of the anonymous struct a tuple of `elems` lowers to
+    pub fn tuple_struct_name(elems: &[HlirType]) -> String {
+        format!("tuple{:?}", elems)
+    }
+
+    /// Name of the `{ data, vtable }` struct a `&dyn Trait` lowers to
+    pub fn dyn_struct_name(trait_name: &str) -> String {
+        format!("dyn {}", trait_name)
+    }
+
     pub fn is_integer(&self) -> bool {
         matches!(
             self,
@@ -250,6 +283,9 @@ pub struct HlirInstr {
     pub result: Option<ValueId>,
     pub op: Op,
     pub ty: HlirType,
+    /// Source expression this instruction was lowered from, resolved to a
+    /// line through `SourceMap::get_node_location`
+    pub span: Option<NodeId>,
 }
 
 /// HLIR operation
@@ -283,6 +319,8 @@ pub enum Op {
     Alloca { ty: HlirType },
     /// Type cast
     Cast { value: ValueId, target: HlirType },
+    /// Reinterpret the bits of a value as a type of the same size
+    Bitcast { value: ValueId, target: HlirType },
     /// Phi node (SSA)
     Phi { incoming: Vec<(BlockId, ValueId)> },
     /// Extract value from aggregate
@@ -378,6 +416,34 @@ pub enum UnaryOp {
     Not,
 }
 
+/// Static branch prediction for a conditional branch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BranchHint {
+    #[default]
+    None,
+    /// The then-branch is expected to be taken
+    Likely,
+    /// The else-branch is expected to be taken
+    Unlikely,
+}
+
+impl BranchHint {
+    /// Weight given to the predicted edge, matching what clang emits for
+    /// `__builtin_expect`
+    pub const LIKELY_WEIGHT: u32 = 2000;
+    /// Weight given to the unpredicted edge
+    pub const UNLIKELY_WEIGHT: u32 = 1;
+
+    /// `(then, else)` branch weights, or `None` when there is no hint
+    pub fn weights(self) -> Option<(u32, u32)> {
+        match self {
+            BranchHint::None => None,
+            BranchHint::Likely => Some((Self::LIKELY_WEIGHT, Self::UNLIKELY_WEIGHT)),
+            BranchHint::Unlikely => Some((Self::UNLIKELY_WEIGHT, Self::LIKELY_WEIGHT)),
+        }
+    }
+}
+
 /// Block terminator
 #[derive(Debug, Clone)]
 pub enum HlirTerminator {
@@ -390,6 +456,8 @@ pub enum HlirTerminator {
         condition: ValueId,
         then_block: BlockId,
         else_block: BlockId,
+        /// Static prediction from `likely`/`unlikely`
+        hint: BranchHint,
     },
     /// Switch on integer value
     Switch {
@@ -399,4 +467,6 @@ pub enum HlirTerminator {
     },
     /// Unreachable code
     Unreachable,
+    /// Abort execution, e.g. when a step guard's budget is exhausted
+    Trap,
 }
@@ -0,0 +1,227 @@
+//! Memory layout of HLIR types
+//!
+//! Sizes, alignments and field offsets follow the C rules: every field is
+//! placed at the next multiple of its alignment, and a struct is as aligned
+//! as its most aligned field and padded to a multiple of that alignment.
+//! A `#[packed]` struct drops the padding and has alignment 1.
+//!
+//! Backends query this module instead of guessing, so the JIT and LLVM agree
+//! on where each field lives.
+
+use std::collections::HashMap;
+
+use super::ir::{HlirType, HlirTypeDef, HlirTypeDefKind};
+
+/// Size, alignment and field offsets of a type, in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u64,
+    pub align: u64,
+    /// Offset of each field; empty for scalars
+    pub offsets: Vec<u64>,
+}
+
+impl Layout {
+    fn scalar(size: u64) -> Self {
+        Self {
+            size,
+            align: size.max(1),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Lay out `fields` one after another, C style
+    fn sequential(fields: impl IntoIterator<Item = Layout>, packed: bool) -> Self {
+        let mut size = 0u64;
+        let mut align = 1;
+        let mut offsets = Vec::new();
+        for field in fields {
+            let field_align = if packed { 1 } else { field.align };
+            size = size.next_multiple_of(field_align);
+            offsets.push(size);
+            size += field.size;
+            align = align.max(field_align);
+        }
+        Self {
+            size: size.next_multiple_of(align),
+            align,
+            offsets,
+        }
+    }
+}
+
+/// Layouts of the types defined in one module
+pub struct Layouts<'a> {
+    defs: HashMap<&'a str, &'a HlirTypeDef>,
+}
+
+impl<'a> Layouts<'a> {
+    pub fn new(types: &'a [HlirTypeDef]) -> Self {
+        Self {
+            defs: types.iter().map(|def| (def.name.as_str(), def)).collect(),
+        }
+    }
+
+    /// Look up a type definition by name
+    pub fn def(&self, name: &str) -> Option<&'a HlirTypeDef> {
+        self.defs.get(name).copied()
+    }
+
+    /// Layout of a struct or enum definition
+    pub fn struct_layout(&self, def: &HlirTypeDef) -> Layout {
+        self.def_layout(def, &mut Vec::new())
+    }
+
+    /// Layout of any type
+    pub fn layout_of(&self, ty: &HlirType) -> Layout {
+        self.type_layout(ty, &mut Vec::new())
+    }
+
+    pub fn size_of(&self, ty: &HlirType) -> u64 {
+        self.layout_of(ty).size
+    }
+
+    pub fn align_of(&self, ty: &HlirType) -> u64 {
+        self.layout_of(ty).align
+    }
+
+    /// Byte offset of field `index` of the named struct
+    pub fn field_offset(&self, name: &str, index: usize) -> Option<u64> {
+        let def = self.def(name)?;
+        if !matches!(def.kind, HlirTypeDefKind::Struct(_)) {
+            return None;
+        }
+        self.struct_layout(def).offsets.get(index).copied()
+    }
+
+    /// `stack` holds the definitions being laid out, so a struct that
+    /// contains itself does not recurse forever
+    fn type_layout<'s>(&self, ty: &HlirType, stack: &mut Vec<&'s str>) -> Layout
+    where
+        'a: 's,
+    {
+        match ty {
+            HlirType::Void => Layout {
+                size: 0,
+                align: 1,
+                offsets: Vec::new(),
+            },
+            HlirType::Bool | HlirType::I8 | HlirType::U8 => Layout::scalar(1),
+            HlirType::I16 | HlirType::U16 => Layout::scalar(2),
+            HlirType::I32 | HlirType::U32 | HlirType::F32 => Layout::scalar(4),
+            HlirType::I64 | HlirType::U64 | HlirType::F64 => Layout::scalar(8),
+            HlirType::I128 | HlirType::U128 => Layout::scalar(16),
+            HlirType::Ptr(_) | HlirType::Function { .. } => Layout::scalar(8),
+            HlirType::Array(elem, len) => {
+                let elem = self.type_layout(elem, stack);
+                Layout {
+                    size: elem.size * *len as u64,
+                    align: elem.align,
+                    offsets: Vec::new(),
+                }
+            }
+            HlirType::Tuple(elems) => {
+                Layout::sequential(elems.iter().map(|e| self.type_layout(e, stack)), false)
+            }
+            HlirType::Struct(name) => match self.defs.get_key_value(name.as_str()) {
+                Some((&name, def)) if !stack.contains(&name) => {
+                    stack.push(name);
+                    let layout = self.def_layout(def, stack);
+                    stack.pop();
+                    layout
+                }
+                // Unknown or recursive: only reachable through a pointer
+                _ => Layout::scalar(8),
+            },
+        }
+    }
+
+    fn def_layout<'s>(&self, def: &HlirTypeDef, stack: &mut Vec<&'s str>) -> Layout
+    where
+        'a: 's,
+    {
+        match &def.kind {
+            HlirTypeDefKind::Struct(fields) => Layout::sequential(
+                fields.iter().map(|(_, ty)| self.type_layout(ty, stack)),
+                def.packed,
+            ),
+            // An `i64` tag followed by the largest variant payload
+            HlirTypeDefKind::Enum(variants) => {
+                let payloads: Vec<_> = variants
+                    .iter()
+                    .map(|(_, fields)| {
+                        Layout::sequential(
+                            fields.iter().map(|ty| self.type_layout(ty, stack)),
+                            def.packed,
+                        )
+                    })
+                    .collect();
+                let payload = Layout {
+                    size: payloads.iter().map(|p| p.size).max().unwrap_or(0),
+                    align: payloads.iter().map(|p| p.align).max().unwrap_or(1),
+                    offsets: Vec::new(),
+                };
+                Layout::sequential([Layout::scalar(8), payload], def.packed)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(name: &str, fields: Vec<HlirType>, packed: bool) -> HlirTypeDef {
+        HlirTypeDef {
+            name: name.to_string(),
+            kind: HlirTypeDefKind::Struct(
+                fields
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, ty)| (i.to_string(), ty))
+                    .collect(),
+            ),
+            packed,
+        }
+    }
+
+    #[test]
+    fn test_struct_layout_pads_fields() {
+        let types = [def("S", vec![HlirType::I8, HlirType::I64], false)];
+        let layout = Layouts::new(&types).struct_layout(&types[0]);
+        assert_eq!(layout.offsets, vec![0, 8]);
+        assert_eq!(layout.size, 16);
+        assert_eq!(layout.align, 8);
+    }
+
+    #[test]
+    fn test_packed_struct_layout_has_no_padding() {
+        let types = [def("S", vec![HlirType::I8, HlirType::I64], true)];
+        let layout = Layouts::new(&types).struct_layout(&types[0]);
+        assert_eq!(layout.offsets, vec![0, 1]);
+        assert_eq!(layout.size, 9);
+        assert_eq!(layout.align, 1);
+    }
+
+    #[test]
+    fn test_nested_struct_and_tuple_layout() {
+        let types = [
+            def("Inner", vec![HlirType::I32, HlirType::I8], false),
+            def(
+                "Outer",
+                vec![
+                    HlirType::I8,
+                    HlirType::Struct("Inner".to_string()),
+                    HlirType::Tuple(vec![HlirType::I16, HlirType::F64]),
+                ],
+                false,
+            ),
+        ];
+        let layouts = Layouts::new(&types);
+        assert_eq!(layouts.size_of(&HlirType::Struct("Inner".to_string())), 8);
+        let layout = layouts.struct_layout(&types[1]);
+        assert_eq!(layout.offsets, vec![0, 4, 16]);
+        assert_eq!(layout.size, 32);
+        assert_eq!(layouts.field_offset("Outer", 2), Some(16));
+    }
+}
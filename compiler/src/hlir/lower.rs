@@ -5,63 +5,121 @@
 
 use super::builder::{FunctionBuilder, ModuleBuilder};
 use super::ir::*;
+use crate::common::NodeId;
+use crate::hir::visit::{HirVisitor, walk_expr, walk_fn, walk_hir, walk_stmt};
 use crate::hir::*;
+use crate::types::ArrayLen;
+use miette::Result;
 use std::collections::HashMap;
 
-/// Lower HIR to HLIR
-pub fn lower(hir: &Hir) -> HlirModule {
-    let lowering = HirToHlir::new();
+/// Lower HIR to HLIR, reporting types that cannot be represented at runtime
+pub fn lower(hir: &Hir) -> Result<HlirModule> {
+    lower_with(hir, &LowerOptions::default())
+}
+
+/// Lower HIR to HLIR with the given instrumentation options
+pub fn lower_with(hir: &Hir, options: &LowerOptions) -> Result<HlirModule> {
+    let lowering = HirToHlir::new(options.clone());
     lowering.lower_module(hir)
 }
 
+/// Options controlling instrumentation inserted during lowering
+#[derive(Debug, Clone, Default)]
+pub struct LowerOptions {
+    /// Bound runtime by trapping once a function invocation has taken this
+    /// many loop backedges
+    pub guard_steps: Option<u64>,
+}
+
 /// HIR to HLIR lowering context
 struct HirToHlir {
     module_builder: ModuleBuilder,
+    info: ModuleInfo,
+    /// Types that cannot be lowered
+    errors: Vec<String>,
+    options: LowerOptions,
+}
+
+/// What lowering knows about the module's items, shared by every function
+#[derive(Default)]
+struct ModuleInfo {
     /// Map from function names to their signatures (for call resolution)
     functions: HashMap<String, HlirType>,
     /// Map from enum names to their variant info
     enums: HashMap<String, Vec<(String, Vec<HlirType>)>>,
+    /// Map from enum names to the tag value of each variant
+    variant_tags: HashMap<String, HashMap<String, i64>>,
     /// Map from struct names to their field info
     structs: HashMap<String, Vec<(String, HlirType)>>,
     /// Map from effect names to their operations
     effects: HashMap<String, Vec<(String, Vec<HlirType>, HlirType)>>,
     /// Map from handler names to their effect
     handlers: HashMap<String, String>,
+    /// Map from trait names to their methods, in vtable slot order
+    traits: HashMap<String, Vec<String>>,
 }
 
 impl HirToHlir {
-    fn new() -> Self {
+    fn new(options: LowerOptions) -> Self {
         Self {
             module_builder: ModuleBuilder::new("main"),
-            functions: HashMap::new(),
-            enums: HashMap::new(),
-            structs: HashMap::new(),
-            effects: HashMap::new(),
-            handlers: HashMap::new(),
+            info: ModuleInfo::default(),
+            errors: Vec::new(),
+            options,
         }
     }
 
-    fn lower_module(mut self, hir: &Hir) -> HlirModule {
+    fn lower_module(mut self, hir: &Hir) -> Result<HlirModule> {
+        // Register tuple layouts and reject unrepresentable types up front
+        let mut collector = TypeCollector::default();
+        walk_hir(&mut collector, hir);
+        for (what, ty) in &collector.declared {
+            self.declare_type(what, ty);
+        }
+        for ty in &collector.exprs {
+            // Diverging expressions have type `!` and produce no value
+            if !matches!(ty, HirType::Never) {
+                self.declare_type("expression", ty);
+            }
+        }
+
         // First pass: collect function signatures, type definitions, and effects
         for item in &hir.items {
             match item {
                 HirItem::Function(f) => {
                     let ret_ty = HlirType::from_hir(&f.ty.return_type);
-                    self.functions.insert(f.name.clone(), ret_ty);
+                    self.info.functions.insert(f.name.clone(), ret_ty);
+                }
+                HirItem::Impl(i) => {
+                    for method in &i.methods {
+                        if let Some(symbol) = i.method_symbol(&method.name) {
+                            let ret_ty = HlirType::from_hir(&method.ty.return_type);
+                            self.info.functions.insert(symbol, ret_ty);
+                        }
+                    }
                 }
                 HirItem::Struct(s) => {
+                    for f in &s.fields {
+                        self.declare_type(&format!("field `{}` of `{}`", f.name, s.name), &f.ty);
+                    }
                     let fields: Vec<_> = s
                         .fields
                         .iter()
                         .map(|f| (f.name.clone(), HlirType::from_hir(&f.ty)))
                         .collect();
-                    self.structs.insert(s.name.clone(), fields.clone());
+                    self.info.structs.insert(s.name.clone(), fields.clone());
                     self.module_builder.add_type_def(HlirTypeDef {
                         name: s.name.clone(),
                         kind: HlirTypeDefKind::Struct(fields),
+                        packed: s.is_packed,
                     });
                 }
                 HirItem::Enum(e) => {
+                    for v in &e.variants {
+                        for ty in &v.fields {
+                            self.declare_type(&format!("variant `{}::{}`", e.name, v.name), ty);
+                        }
+                    }
                     let variants: Vec<_> = e
                         .variants
                         .iter()
@@ -72,10 +130,18 @@ impl HirToHlir {
                             )
                         })
                         .collect();
-                    self.enums.insert(e.name.clone(), variants.clone());
+                    self.info.enums.insert(e.name.clone(), variants.clone());
+                    self.info.variant_tags.insert(
+                        e.name.clone(),
+                        e.variants
+                            .iter()
+                            .map(|v| (v.name.clone(), v.discriminant))
+                            .collect(),
+                    );
                     self.module_builder.add_type_def(HlirTypeDef {
                         name: e.name.clone(),
                         kind: HlirTypeDefKind::Enum(variants),
+                        packed: false,
                     });
                 }
                 HirItem::Effect(eff) => {
@@ -90,10 +156,14 @@ impl HirToHlir {
                             )
                         })
                         .collect();
-                    self.effects.insert(eff.name.clone(), ops);
+                    self.info.effects.insert(eff.name.clone(), ops);
                 }
                 HirItem::Handler(h) => {
-                    self.handlers.insert(h.name.clone(), h.effect.clone());
+                    self.info.handlers.insert(h.name.clone(), h.effect.clone());
+                }
+                HirItem::Trait(t) => {
+                    let methods = t.methods.iter().map(|m| m.name.clone()).collect();
+                    self.info.traits.insert(t.name.clone(), methods);
                 }
                 HirItem::Global(g) => {
                     let global = HlirGlobal {
@@ -109,15 +179,199 @@ impl HirToHlir {
             }
         }
 
+        for (ty_name, trait_name) in &collector.dyn_coercions {
+            self.emit_vtable(hir, ty_name, trait_name);
+        }
+
         // Second pass: lower functions
         for item in &hir.items {
-            if let HirItem::Function(f) = item {
-                let hlir_func = self.lower_function(f);
-                self.module_builder.add_function(hlir_func);
+            match item {
+                HirItem::Function(f) => {
+                    let hlir_func = self.lower_function(f);
+                    self.module_builder.add_function(hlir_func);
+                }
+                HirItem::Impl(i) => {
+                    for method in &i.methods {
+                        if let Some(symbol) = i.method_symbol(&method.name) {
+                            let method = HirFn {
+                                name: symbol,
+                                ..method.clone()
+                            };
+                            let hlir_func = self.lower_function(&method);
+                            self.module_builder.add_function(hlir_func);
+                        }
+                    }
+                }
+                _ => {}
             }
         }
 
-        self.module_builder.build()
+        if !self.errors.is_empty() {
+            return Err(miette::miette!(
+                "Cannot lower to HLIR:\n{}",
+                self.errors.join("\n")
+            ));
+        }
+        Ok(self.module_builder.build())
+    }
+
+    /// Check that `ty`, the type of `what`, has a runtime representation and
+    /// register the layout of every tuple it contains
+    fn declare_type(&mut self, what: &str, ty: &HirType) {
+        let message = if contains_error(ty) {
+            format!("{} has an unresolved type", what)
+        } else if matches!(ty, HirType::Never) {
+            format!("{} has type `!`, which has no values", what)
+        } else if let Some(len) = const_generic_len(ty) {
+            format!(
+                "{} has an array of length `{}`, a const generic parameter, which compiled code does not support yet",
+                what, len
+            )
+        } else {
+            self.register_tuples(ty);
+            return;
+        };
+        if !self.errors.contains(&message) {
+            self.errors.push(message);
+        }
+    }
+
+    /// Register each tuple in `ty` as an anonymous struct with one field per
+    /// element, and each trait object as its `{ data, vtable }` pair
+    fn register_tuples(&mut self, ty: &HirType) {
+        match ty {
+            HirType::Tuple(elems) if !elems.is_empty() => {
+                for elem in elems {
+                    self.register_tuples(elem);
+                }
+                let fields: Vec<_> = elems
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (i.to_string(), HlirType::from_hir(e)))
+                    .collect();
+                let elem_tys: Vec<_> = fields.iter().map(|(_, t)| t.clone()).collect();
+                let name = HlirType::tuple_struct_name(&elem_tys);
+                if !self.info.structs.contains_key(&name) {
+                    self.info.structs.insert(name.clone(), fields.clone());
+                    self.module_builder.add_type_def(HlirTypeDef {
+                        name,
+                        kind: HlirTypeDefKind::Struct(fields),
+                        packed: false,
+                    });
+                }
+            }
+            HirType::Ref { inner: elem, .. } | HirType::Array { element: elem, .. } => {
+                self.register_tuples(elem)
+            }
+            HirType::Dyn(trait_name) => {
+                let name = HlirType::dyn_struct_name(trait_name);
+                if !self.info.structs.contains_key(&name) {
+                    let byte_ptr = HlirType::Ptr(Box::new(HlirType::U8));
+                    let fields = vec![
+                        ("data".to_string(), byte_ptr.clone()),
+                        ("vtable".to_string(), HlirType::Ptr(Box::new(byte_ptr))),
+                    ];
+                    self.info.structs.insert(name.clone(), fields.clone());
+                    self.module_builder.add_type_def(HlirTypeDef {
+                        name,
+                        kind: HlirTypeDefKind::Struct(fields),
+                        packed: false,
+                    });
+                }
+            }
+            HirType::Named { args, .. } => {
+                for arg in args {
+                    self.register_tuples(arg);
+                }
+            }
+            HirType::Fn {
+                params,
+                return_type,
+            } => {
+                for param in params {
+                    self.register_tuples(param);
+                }
+                self.register_tuples(return_type);
+            }
+            _ => {}
+        }
+    }
+
+    /// Emit the vtable of `ty_name` as a `trait_name`: a constant array with
+    /// one entry per trait method, in trait order. Each entry is a shim that
+    /// takes the erased data pointer and calls the concrete method.
+    fn emit_vtable(&mut self, hir: &Hir, ty_name: &str, trait_name: &str) {
+        let Some(methods) = self.info.traits.get(trait_name).cloned() else {
+            return;
+        };
+        let impl_methods: HashMap<&str, &HirFn> = hir
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                HirItem::Impl(i)
+                    if i.trait_ref.as_deref() == Some(trait_name)
+                        && i.self_ty.nominal_name() == Some(ty_name) =>
+                {
+                    Some(i)
+                }
+                _ => None,
+            })
+            .flat_map(|i| i.methods.iter().map(|m| (m.name.as_str(), m)))
+            .collect();
+
+        let byte_ptr = HlirType::Ptr(Box::new(HlirType::U8));
+        let mut entries = Vec::new();
+        for method in &methods {
+            let Some(f) = impl_methods.get(method.as_str()) else {
+                entries.push(HlirConstant::Null(byte_ptr.clone()));
+                continue;
+            };
+            // The checker rejects `dyn` traits with receiverless methods;
+            // there is nothing to dispatch on
+            let Some((receiver, params)) =
+                f.ty.params
+                    .split_first()
+                    .filter(|(receiver, _)| receiver.name == "self")
+            else {
+                entries.push(HlirConstant::Null(byte_ptr.clone()));
+                continue;
+            };
+            let target = format!("{}::{}", ty_name, method);
+            let shim = format!("{}.dyn", target);
+            let return_type = HlirType::from_hir(&f.ty.return_type);
+            let func_id = self.module_builder.fresh_func_id();
+            let mut builder = FunctionBuilder::new(func_id, &shim, return_type.clone());
+            let data = builder.add_param("self", byte_ptr.clone());
+            let rest: Vec<_> = params
+                .iter()
+                .map(|p| builder.add_param(&p.name, HlirType::from_hir(&p.ty)))
+                .collect();
+            let entry = builder.create_block("entry");
+            builder.switch_to_block(entry);
+
+            // Taking a reference to an aggregate yields the aggregate itself,
+            // so the data pointer is the receiver whether the concrete method
+            // takes it by reference or by value
+            let receiver = builder.build_bitcast(data, HlirType::from_hir(&receiver.ty));
+            let mut args = vec![receiver];
+            args.extend(rest);
+            let result = builder.build_call(&target, args, return_type.clone());
+            if return_type == HlirType::Void {
+                builder.build_return(None);
+            } else {
+                builder.build_return(Some(result));
+            }
+            self.module_builder.add_function(builder.build());
+            entries.push(HlirConstant::FunctionRef(shim));
+        }
+
+        self.module_builder.add_global(HlirGlobal {
+            id: ValueId(0),
+            name: vtable_name(ty_name, trait_name),
+            ty: HlirType::Array(Box::new(byte_ptr), entries.len()),
+            init: Some(HlirConstant::Array(entries)),
+            is_const: true,
+        });
     }
 
     fn lower_function(&mut self, f: &HirFn) -> HlirFunction {
@@ -136,15 +390,17 @@ impl HirToHlir {
         let entry = func_builder.create_block("entry");
         func_builder.switch_to_block(entry);
 
+        // Each invocation gets its own step budget
+        let step_counter = self.options.guard_steps.map(|budget| {
+            let counter = func_builder.build_alloca(HlirType::I64);
+            let initial = func_builder.build_i64(budget.min(i64::MAX as u64) as i64);
+            func_builder.build_store(counter, initial);
+            counter
+        });
+
         // Lower function body
-        let mut ctx = LoweringContext::new(
-            &mut func_builder,
-            &self.functions,
-            &self.enums,
-            &self.structs,
-            &self.effects,
-            &self.handlers,
-        );
+        let mut ctx = LoweringContext::new(&mut func_builder, &self.info);
+        ctx.step_counter = step_counter;
         let result = ctx.lower_block(&f.body);
 
         // Add return if not already terminated
@@ -156,24 +412,153 @@ impl HirToHlir {
             }
         }
 
-        func_builder.build()
+        let mut func = func_builder.build();
+        func.is_pure = f.is_pure;
+        func.inline = f.inline;
+        func.is_kernel = f.is_kernel;
+        func
+    }
+}
+
+/// `cond` when the first statement of a loop body is `if !cond { break }`
+fn loop_exit_condition(body: &HirBlock) -> Option<&HirExpr> {
+    let Some(HirStmt::Expr(HirExpr {
+        kind:
+            HirExprKind::If {
+                condition,
+                then_branch,
+                else_branch: None,
+            },
+        ..
+    })) = body.stmts.first()
+    else {
+        return None;
+    };
+    let HirExprKind::Unary {
+        op: HirUnaryOp::Not,
+        expr: cond,
+    } = &condition.kind
+    else {
+        return None;
+    };
+    match &then_branch.stmts[..] {
+        [
+            HirStmt::Expr(HirExpr {
+                kind: HirExprKind::Break(None),
+                ..
+            }),
+        ] => Some(cond),
+        _ => None,
+    }
+}
+
+/// Name of the global holding the vtable of `ty_name` as a `trait_name`
+fn vtable_name(ty_name: &str, trait_name: &str) -> String {
+    format!("vtable.{}.{}", ty_name, trait_name)
+}
+
+/// Whether an error type occurs anywhere in `ty`
+fn contains_error(ty: &HirType) -> bool {
+    match ty {
+        HirType::Error => true,
+        HirType::Ref { inner, .. } | HirType::Array { element: inner, .. } => contains_error(inner),
+        HirType::Tuple(elems) | HirType::Named { args: elems, .. } => {
+            elems.iter().any(contains_error)
+        }
+        HirType::Fn {
+            params,
+            return_type,
+        } => params.iter().any(contains_error) || contains_error(return_type),
+        _ => false,
+    }
+}
+
+/// The first const generic array length anywhere in `ty`
+fn const_generic_len(ty: &HirType) -> Option<&str> {
+    match ty {
+        HirType::Array {
+            size: Some(ArrayLen::Param(name)),
+            ..
+        } => Some(name),
+        HirType::Ref { inner, .. } | HirType::Array { element: inner, .. } => {
+            const_generic_len(inner)
+        }
+        HirType::Tuple(elems) | HirType::Named { args: elems, .. } => {
+            elems.iter().find_map(const_generic_len)
+        }
+        HirType::Fn {
+            params,
+            return_type,
+        } => params
+            .iter()
+            .find_map(const_generic_len)
+            .or_else(|| const_generic_len(return_type)),
+        _ => None,
+    }
+}
+
+/// Every type reaching lowering: the declared types of parameters, results
+/// and bindings, named for diagnostics, and the types of expressions
+#[derive(Default)]
+struct TypeCollector {
+    declared: Vec<(String, HirType)>,
+    exprs: Vec<HirType>,
+    /// Concrete type and trait of each distinct `&dyn Trait` coercion
+    dyn_coercions: Vec<(String, String)>,
+}
+
+impl HirVisitor for TypeCollector {
+    fn visit_fn(&mut self, func: &HirFn) {
+        for param in &func.ty.params {
+            self.declared.push((
+                format!("parameter `{}` of `{}`", param.name, func.name),
+                param.ty.clone(),
+            ));
+        }
+        self.declared.push((
+            format!("return type of `{}`", func.name),
+            (*func.ty.return_type).clone(),
+        ));
+        walk_fn(self, func);
+    }
+
+    fn visit_stmt(&mut self, stmt: &HirStmt) {
+        if let HirStmt::Let { name, ty, .. } = stmt {
+            self.declared
+                .push((format!("binding `{}`", name), ty.clone()));
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &HirExpr) {
+        self.exprs.push(expr.ty.clone());
+        if let HirExprKind::DynCoerce {
+            expr: inner,
+            trait_name,
+        } = &expr.kind
+            && let Some(ty_name) = inner.ty.nominal_name()
+        {
+            let pair = (ty_name.to_string(), trait_name.clone());
+            if !self.dyn_coercions.contains(&pair) {
+                self.dyn_coercions.push(pair);
+            }
+        }
+        walk_expr(self, expr);
     }
 }
 
 /// Context for lowering expressions within a function
 struct LoweringContext<'a> {
     builder: &'a mut FunctionBuilder,
-    functions: &'a HashMap<String, HlirType>,
-    enums: &'a HashMap<String, Vec<(String, Vec<HlirType>)>>,
-    structs: &'a HashMap<String, Vec<(String, HlirType)>>,
-    effects: &'a HashMap<String, Vec<(String, Vec<HlirType>, HlirType)>>,
-    handlers: &'a HashMap<String, String>,
+    info: &'a ModuleInfo,
     /// Track if current block is terminated
     terminated: bool,
     /// Loop context for break/continue
     loop_stack: Vec<LoopContext>,
     /// Closure environment (captured variables)
     closure_env: Option<ClosureEnv>,
+    /// Stack slot holding the remaining step budget, when guarding steps
+    step_counter: Option<ValueId>,
 }
 
 struct LoopContext {
@@ -192,24 +577,14 @@ struct ClosureEnv {
 }
 
 impl<'a> LoweringContext<'a> {
-    fn new(
-        builder: &'a mut FunctionBuilder,
-        functions: &'a HashMap<String, HlirType>,
-        enums: &'a HashMap<String, Vec<(String, Vec<HlirType>)>>,
-        structs: &'a HashMap<String, Vec<(String, HlirType)>>,
-        effects: &'a HashMap<String, Vec<(String, Vec<HlirType>, HlirType)>>,
-        handlers: &'a HashMap<String, String>,
-    ) -> Self {
+    fn new(builder: &'a mut FunctionBuilder, info: &'a ModuleInfo) -> Self {
         Self {
             builder,
-            functions,
-            enums,
-            structs,
-            effects,
-            handlers,
+            info,
             terminated: false,
             loop_stack: Vec::new(),
             closure_env: None,
+            step_counter: None,
         }
     }
 
@@ -286,9 +661,16 @@ impl<'a> LoweringContext<'a> {
                     self.builder.build_store(field_ptr, value);
                 }
             }
-            HirExprKind::Index { base, index } => {
+            HirExprKind::Index {
+                base,
+                index,
+                in_bounds,
+            } => {
                 if let Some(base_ptr) = self.lower_lvalue(base) {
                     if let Some(idx) = self.lower_expr(index) {
+                        if !in_bounds {
+                            self.build_bounds_check(&base.ty, idx);
+                        }
                         let elem_ty = HlirType::from_hir(&target.ty);
                         let elem_ptr = self.builder.build_elem_ptr(base_ptr, idx, elem_ty);
                         self.builder.build_store(elem_ptr, value);
@@ -309,9 +691,16 @@ impl<'a> LoweringContext<'a> {
                 let field_ty = HlirType::from_hir(&expr.ty);
                 Some(self.builder.build_field_ptr(base_ptr, field_idx, field_ty))
             }
-            HirExprKind::Index { base, index } => {
+            HirExprKind::Index {
+                base,
+                index,
+                in_bounds,
+            } => {
                 let base_ptr = self.lower_lvalue(base)?;
                 let idx = self.lower_expr(index)?;
+                if !in_bounds {
+                    self.build_bounds_check(&base.ty, idx);
+                }
                 let elem_ty = HlirType::from_hir(&expr.ty);
                 Some(self.builder.build_elem_ptr(base_ptr, idx, elem_ty))
             }
@@ -321,7 +710,7 @@ impl<'a> LoweringContext<'a> {
 
     fn get_field_index(&self, ty: &HirType, field: &str) -> usize {
         if let HirType::Named { name, .. } = ty {
-            if let Some(fields) = self.structs.get(name) {
+            if let Some(fields) = self.info.structs.get(name) {
                 for (i, (f_name, _)) in fields.iter().enumerate() {
                     if f_name == field {
                         return i;
@@ -334,19 +723,17 @@ impl<'a> LoweringContext<'a> {
 
     /// Get the variant tag value for an enum variant
     fn get_variant_tag(&self, enum_name: &str, variant: &str) -> i64 {
-        if let Some(variants) = self.enums.get(enum_name) {
-            for (i, (v_name, _)) in variants.iter().enumerate() {
-                if v_name == variant {
-                    return i as i64;
-                }
-            }
-        }
-        0
+        self.info
+            .variant_tags
+            .get(enum_name)
+            .and_then(|tags| tags.get(variant))
+            .copied()
+            .unwrap_or(0)
     }
 
     /// Get the variant fields for an enum variant
     fn get_variant_fields(&self, enum_name: &str, variant: &str) -> Vec<HlirType> {
-        if let Some(variants) = self.enums.get(enum_name) {
+        if let Some(variants) = self.info.enums.get(enum_name) {
             for (v_name, fields) in variants {
                 if v_name == variant {
                     return fields.clone();
@@ -357,6 +744,18 @@ impl<'a> LoweringContext<'a> {
     }
 
     fn lower_expr(&mut self, expr: &HirExpr) -> Option<ValueId> {
+        // Synthesized expressions keep the span of the expression enclosing them
+        let outer = if expr.id == NodeId::dummy() {
+            self.builder.current_span()
+        } else {
+            self.builder.set_span(Some(expr.id))
+        };
+        let value = self.lower_expr_kind(expr);
+        self.builder.set_span(outer);
+        value
+    }
+
+    fn lower_expr_kind(&mut self, expr: &HirExpr) -> Option<ValueId> {
         if self.terminated {
             return None;
         }
@@ -383,7 +782,7 @@ impl<'a> LoweringContext<'a> {
                     }
                 }
                 // Try function reference
-                if self.functions.contains_key(name) {
+                if self.info.functions.contains_key(name) {
                     return Some(self.builder.build_const(
                         HlirConstant::FunctionRef(name.clone()),
                         HlirType::Ptr(Box::new(HlirType::Void)),
@@ -411,11 +810,16 @@ impl<'a> LoweringContext<'a> {
             }
 
             HirExprKind::Call { func, args } => {
+                // Outside of a branch condition the hint intrinsics are identity
+                if branch_hint_intrinsic(func).is_some() && args.len() == 1 {
+                    return self.lower_expr(&args[0]);
+                }
+
                 let arg_vals: Vec<_> = args.iter().filter_map(|a| self.lower_expr(a)).collect();
 
                 // Check if it's a direct function call
                 if let HirExprKind::Local(name) = &func.kind {
-                    if self.functions.contains_key(name) {
+                    if self.info.functions.contains_key(name) {
                         return Some(self.builder.build_call(name, arg_vals, ty));
                     }
                 }
@@ -460,7 +864,7 @@ impl<'a> LoweringContext<'a> {
             HirExprKind::Continue => {
                 if let Some(loop_ctx) = self.loop_stack.last() {
                     let continue_block = loop_ctx.continue_block;
-                    self.builder.build_branch(continue_block);
+                    self.build_backedge(continue_block);
                     self.terminated = true;
                 }
                 None
@@ -476,11 +880,21 @@ impl<'a> LoweringContext<'a> {
                 Some(self.builder.build_array(vals, ty))
             }
 
+            HirExprKind::ArrayRepeat { value, len } => {
+                // Const generic lengths were rejected before lowering
+                let n = len.fixed()?;
+                let val = self.lower_expr(value)?;
+                Some(self.builder.build_array(vec![val; n], ty))
+            }
+
             HirExprKind::Struct { name, fields } => {
-                let field_vals: Vec<_> = fields
+                let mut field_vals: Vec<_> = fields
                     .iter()
                     .filter_map(|(n, e)| self.lower_expr(e).map(|v| (n.clone(), v)))
                     .collect();
+                // Fields are evaluated in source order but laid out in
+                // declaration order, which is what field indices refer to
+                field_vals.sort_by_key(|(n, _)| self.get_field_index(&expr.ty, n));
                 Some(self.builder.build_struct(name, field_vals, ty))
             }
 
@@ -495,10 +909,17 @@ impl<'a> LoweringContext<'a> {
                 Some(self.builder.build_extract(base_val, *index, ty))
             }
 
-            HirExprKind::Index { base, index } => {
+            HirExprKind::Index {
+                base,
+                index,
+                in_bounds,
+            } => {
                 // For arrays, we need pointer arithmetic
                 let base_val = self.lower_expr(base)?;
                 let idx_val = self.lower_expr(index)?;
+                if !in_bounds {
+                    self.build_bounds_check(&base.ty, idx_val);
+                }
                 let elem_ptr = self.builder.build_elem_ptr(base_val, idx_val, ty.clone());
                 Some(self.builder.build_load(elem_ptr, ty))
             }
@@ -525,6 +946,33 @@ impl<'a> LoweringContext<'a> {
                 Some(self.builder.build_cast(val, target_ty))
             }
 
+            HirExprKind::Transmute {
+                expr: inner,
+                target,
+            } => {
+                let val = self.lower_expr(inner)?;
+                Some(self.builder.build_bitcast(val, HlirType::from_hir(target)))
+            }
+
+            HirExprKind::DynCoerce {
+                expr: inner,
+                trait_name,
+            } => {
+                let ptr = self.lower_expr(inner)?;
+                let ty_name = inner.ty.nominal_name()?;
+                let byte_ptr = HlirType::Ptr(Box::new(HlirType::U8));
+                let data = self.builder.build_bitcast(ptr, byte_ptr.clone());
+                let vtable = self.builder.build_const(
+                    HlirConstant::GlobalRef(vtable_name(ty_name, trait_name)),
+                    HlirType::Ptr(Box::new(byte_ptr)),
+                );
+                let fields = vec![("data".to_string(), data), ("vtable".to_string(), vtable)];
+                Some(
+                    self.builder
+                        .build_struct(HlirType::dyn_struct_name(trait_name), fields, ty),
+                )
+            }
+
             HirExprKind::Match { scrutinee, arms } => self.lower_match(scrutinee, arms, &ty),
 
             HirExprKind::Closure { params, body } => self.lower_closure(params, body, &ty),
@@ -534,10 +982,18 @@ impl<'a> LoweringContext<'a> {
                 method,
                 args,
             } => {
+                if let Some(trait_name) = receiver.ty.dyn_trait() {
+                    return self.lower_dyn_call(receiver, trait_name, method, args, ty);
+                }
                 // Desugar to regular function call with receiver as first argument
                 let mut all_args = vec![self.lower_expr(receiver)?];
                 all_args.extend(args.iter().filter_map(|a| self.lower_expr(a)));
-                Some(self.builder.build_call(method, all_args, ty))
+                let callee = receiver
+                    .ty
+                    .method_symbol(method)
+                    .filter(|symbol| self.info.functions.contains_key(symbol))
+                    .unwrap_or_else(|| method.clone());
+                Some(self.builder.build_call(&callee, all_args, ty))
             }
 
             HirExprKind::Variant {
@@ -553,9 +1009,57 @@ impl<'a> LoweringContext<'a> {
             HirExprKind::Handle { expr, handler } => self.lower_effect_handle(expr, handler, &ty),
 
             HirExprKind::Sample(dist) => self.lower_sample(dist, &ty),
+
+            HirExprKind::Await(future) => {
+                // Compiled code runs an async call to completion where it is
+                // made, so the awaited value is the call's own result
+                let call = HirExpr {
+                    ty: expr.ty.clone(),
+                    ..future.as_ref().clone()
+                };
+                self.lower_expr(&call)
+            }
         }
     }
 
+    /// Call `method` on a trait object through its vtable slot, passing the
+    /// data pointer as the receiver
+    fn lower_dyn_call(
+        &mut self,
+        receiver: &HirExpr,
+        trait_name: &str,
+        method: &str,
+        args: &[HirExpr],
+        ty: HlirType,
+    ) -> Option<ValueId> {
+        let slot = self
+            .info
+            .traits
+            .get(trait_name)?
+            .iter()
+            .position(|m| m == method)?;
+        let object = self.lower_expr(receiver)?;
+        let byte_ptr = HlirType::Ptr(Box::new(HlirType::U8));
+        let data = self.builder.build_extract(object, 0, byte_ptr.clone());
+        let vtable =
+            self.builder
+                .build_extract(object, 1, HlirType::Ptr(Box::new(byte_ptr.clone())));
+
+        let mut all_args = vec![data];
+        all_args.extend(args.iter().filter_map(|a| self.lower_expr(a)));
+        let mut params = vec![byte_ptr.clone()];
+        params.extend(args.iter().map(|a| HlirType::from_hir(&a.ty)));
+        let fn_ty = HlirType::Function {
+            params,
+            return_type: Box::new(ty.clone()),
+        };
+
+        let index = self.builder.build_i64(slot as i64);
+        let slot_ptr = self.builder.build_elem_ptr(vtable, index, byte_ptr);
+        let func = self.builder.build_load(slot_ptr, fn_ty);
+        Some(self.builder.build_call_indirect(func, all_args, ty))
+    }
+
     fn lower_literal(&mut self, lit: &HirLiteral, ty: &HlirType) -> ValueId {
         match lit {
             HirLiteral::Unit => self.builder.build_unit(),
@@ -563,6 +1067,22 @@ impl<'a> LoweringContext<'a> {
             HirLiteral::Int(i) => self
                 .builder
                 .build_const(HlirConstant::Int(*i, ty.clone()), ty.clone()),
+            // Constants are 64-bit, so join the two halves
+            HirLiteral::U128(n) => {
+                let half = |lower: &mut Self, bits: u64| {
+                    lower
+                        .builder
+                        .build_const(HlirConstant::Int(bits as i64, ty.clone()), ty.clone())
+                };
+                let high = half(self, (*n >> 64) as u64);
+                let low = half(self, *n as u64);
+                let shift = half(self, 64);
+                let high = self
+                    .builder
+                    .build_binary(BinaryOp::Shl, high, shift, ty.clone());
+                self.builder
+                    .build_binary(BinaryOp::Or, high, low, ty.clone())
+            }
             HirLiteral::Float(f) => self
                 .builder
                 .build_const(HlirConstant::Float(*f, ty.clone()), ty.clone()),
@@ -728,6 +1248,15 @@ impl<'a> LoweringContext<'a> {
         else_branch: Option<&HirExpr>,
         ty: &HlirType,
     ) -> Option<ValueId> {
+        let (condition, hint) = match &condition.kind {
+            HirExprKind::Call { func, args } if args.len() == 1 => {
+                match branch_hint_intrinsic(func) {
+                    Some(hint) => (&args[0], hint),
+                    None => (condition, BranchHint::None),
+                }
+            }
+            _ => (condition, BranchHint::None),
+        };
         let cond_val = self.lower_expr(condition)?;
 
         let then_block = self.builder.create_block("if.then");
@@ -735,7 +1264,7 @@ impl<'a> LoweringContext<'a> {
         let merge_block = self.builder.create_block("if.merge");
 
         self.builder
-            .build_cond_branch(cond_val, then_block, else_block);
+            .build_cond_branch_with_hint(cond_val, then_block, else_block, hint);
 
         // Then branch
         self.builder.switch_to_block(then_block);
@@ -788,28 +1317,57 @@ impl<'a> LoweringContext<'a> {
         None
     }
 
+    /// Lower a loop. A leading `if !cond { break }`, which is how `while`
+    /// and `for` loops are desugared, becomes a header block that tests
+    /// `cond` and branches to the body or the exit; the body then branches
+    /// back to the header.
     fn lower_loop(&mut self, body: &HirBlock, ty: &HlirType) -> Option<ValueId> {
-        let loop_block = self.builder.create_block("loop.body");
+        let exit_test = loop_exit_condition(body);
+        let header = if exit_test.is_some() {
+            self.builder.create_block("loop.header")
+        } else {
+            self.builder.create_block("loop.body")
+        };
+        let body_block = match exit_test {
+            Some(_) => self.builder.create_block("loop.body"),
+            None => header,
+        };
         let exit_block = self.builder.create_block("loop.exit");
 
         // Jump to loop
-        self.builder.build_branch(loop_block);
+        self.builder.build_branch(header);
 
         // Push loop context
         self.loop_stack.push(LoopContext {
-            continue_block: loop_block,
+            continue_block: header,
             break_block: exit_block,
             break_values: Vec::new(),
         });
 
-        // Loop body
-        self.builder.switch_to_block(loop_block);
+        self.builder.switch_to_block(header);
         self.terminated = false;
-        self.lower_block(body);
+        let stmts = match exit_test {
+            Some(condition) => {
+                if let Some(cond) = self.lower_expr(condition) {
+                    self.builder.build_cond_branch(cond, body_block, exit_block);
+                    self.builder.switch_to_block(body_block);
+                }
+                &body.stmts[1..]
+            }
+            None => &body.stmts[..],
+        };
+
+        // Loop body
+        for stmt in stmts {
+            if self.terminated {
+                break;
+            }
+            self.lower_stmt(stmt);
+        }
 
         // If body didn't terminate, loop back
         if !self.terminated {
-            self.builder.build_branch(loop_block);
+            self.build_backedge(header);
         }
 
         // Pop loop context and collect break values
@@ -827,6 +1385,62 @@ impl<'a> LoweringContext<'a> {
         }
     }
 
+    /// Branch back to a loop header, charging one step against the budget
+    /// and trapping once it runs out when step guarding is enabled
+    fn build_backedge(&mut self, header: BlockId) {
+        let Some(counter) = self.step_counter else {
+            self.builder.build_branch(header);
+            return;
+        };
+
+        let steps = self.builder.build_load(counter, HlirType::I64);
+        let one = self.builder.build_i64(1);
+        let remaining = self.builder.build_sub(steps, one, HlirType::I64);
+        self.builder.build_store(counter, remaining);
+        let zero = self.builder.build_i64(0);
+        let exhausted = self.builder.build_slt(remaining, zero);
+
+        let trap_block = self.builder.create_block("guard.trap");
+        self.builder.build_cond_branch_with_hint(
+            exhausted,
+            trap_block,
+            header,
+            BranchHint::Unlikely,
+        );
+        self.builder.switch_to_block(trap_block);
+        self.builder.build_trap();
+    }
+
+    /// Trap unless `idx` is within the length of the fixed-size array type
+    /// `base_ty`. Arrays of unknown length are not checked.
+    fn build_bounds_check(&mut self, base_ty: &HirType, idx: ValueId) {
+        let HirType::Array {
+            size: Some(ArrayLen::Fixed(size)),
+            ..
+        } = base_ty
+        else {
+            return;
+        };
+
+        // Unsigned comparison also catches negative indices
+        let len = self.builder.build_i64(*size as i64);
+        let out_of_bounds = self
+            .builder
+            .build_binary(BinaryOp::UGe, idx, len, HlirType::Bool);
+
+        let trap_block = self.builder.create_block("bounds.trap");
+        let ok_block = self.builder.create_block("bounds.ok");
+        self.builder.build_cond_branch_with_hint(
+            out_of_bounds,
+            trap_block,
+            ok_block,
+            BranchHint::Unlikely,
+        );
+        self.builder.switch_to_block(trap_block);
+        self.builder.build_trap();
+        self.builder.switch_to_block(ok_block);
+    }
+
     fn lower_match(
         &mut self,
         scrutinee: &HirExpr,
@@ -851,9 +1465,19 @@ impl<'a> LoweringContext<'a> {
             return self.lower_match_switch(scrut_val, arms, ty);
         }
 
-        // For enum variant matching, use tag-based switch
+        // For enum variant matching, use tag-based switch. Other patterns,
+        // such as `v @ Some(_)`, need the general case.
         if let HirType::Named { name, .. } = &scrutinee.ty {
-            if self.enums.contains_key(name) {
+            if self.info.enums.contains_key(name)
+                && arms.iter().all(|a| {
+                    matches!(
+                        a.pattern,
+                        HirPattern::Variant { .. }
+                            | HirPattern::Wildcard
+                            | HirPattern::Binding { .. }
+                    )
+                })
+            {
                 return self.lower_match_enum(scrut_val, name, arms, ty);
             }
         }
@@ -1181,19 +1805,48 @@ impl<'a> LoweringContext<'a> {
         match pattern {
             HirPattern::Wildcard => None,
             HirPattern::Binding { .. } => None,
+            HirPattern::At { pattern, .. } => self.lower_pattern_check(pattern, scrut, scrut_ty),
             HirPattern::Literal(lit) => {
                 let lit_val = self.lower_literal(lit, scrut_ty);
                 Some(self.builder.build_eq(scrut, lit_val))
             }
+            HirPattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let (ge, le, lt) = if scrut_ty.is_float() {
+                    (BinaryOp::FOGe, BinaryOp::FOLe, BinaryOp::FOLt)
+                } else if scrut_ty.is_signed() {
+                    (BinaryOp::SGe, BinaryOp::SLe, BinaryOp::SLt)
+                } else {
+                    (BinaryOp::UGe, BinaryOp::ULe, BinaryOp::ULt)
+                };
+                let lo = self.lower_literal(start, scrut_ty);
+                let hi = self.lower_literal(end, scrut_ty);
+                let above = self.builder.build_binary(ge, scrut, lo, HlirType::Bool);
+                let upper = if *inclusive { le } else { lt };
+                let below = self.builder.build_binary(upper, scrut, hi, HlirType::Bool);
+                Some(
+                    self.builder
+                        .build_binary(BinaryOp::And, above, below, HlirType::Bool),
+                )
+            }
             HirPattern::Tuple(patterns) => {
                 // Check all tuple elements
                 let mut combined: Option<ValueId> = None;
                 for (i, p) in patterns.iter().enumerate() {
-                    let elem_ty = if let HlirType::Tuple(elems) = scrut_ty {
-                        elems.get(i).cloned().unwrap_or(HlirType::Void)
-                    } else {
-                        HlirType::Void
-                    };
+                    let elem_ty = match scrut_ty {
+                        HlirType::Struct(name) => self
+                            .info
+                            .structs
+                            .get(name)
+                            .and_then(|fields| fields.get(i))
+                            .map(|(_, ty)| ty.clone()),
+                        HlirType::Tuple(elems) => elems.get(i).cloned(),
+                        _ => None,
+                    }
+                    .unwrap_or(HlirType::Void);
                     let elem = self.builder.build_extract(scrut, i, elem_ty.clone());
                     if let Some(check) = self.lower_pattern_check(p, elem, &elem_ty) {
                         combined = Some(match combined {
@@ -1228,7 +1881,7 @@ impl<'a> LoweringContext<'a> {
             HirPattern::Struct { name, fields } => {
                 // Check struct fields
                 let mut combined: Option<ValueId> = None;
-                if let Some(struct_fields) = self.structs.get(name) {
+                if let Some(struct_fields) = self.info.structs.get(name) {
                     for (field_name, field_pattern) in fields {
                         // Find field index
                         if let Some(idx) = struct_fields.iter().position(|(n, _)| n == field_name) {
@@ -1296,6 +1949,10 @@ impl<'a> LoweringContext<'a> {
             HirPattern::Binding { name, .. } => {
                 self.builder.bind_var(name, value);
             }
+            HirPattern::At { name, pattern, .. } => {
+                self.builder.bind_var(name, value);
+                self.bind_pattern(pattern, value);
+            }
             HirPattern::Tuple(patterns) => {
                 for (i, p) in patterns.iter().enumerate() {
                     let elem = self.builder.build_extract(value, i, HlirType::Void);
@@ -1303,7 +1960,7 @@ impl<'a> LoweringContext<'a> {
                 }
             }
             HirPattern::Struct { name, fields } => {
-                if let Some(struct_fields) = self.structs.get(name).cloned() {
+                if let Some(struct_fields) = self.info.structs.get(name).cloned() {
                     for (field_name, field_pattern) in fields {
                         if let Some(idx) = struct_fields.iter().position(|(n, _)| n == field_name) {
                             let field_ty = struct_fields[idx].1.clone();
@@ -1415,7 +2072,7 @@ impl<'a> LoweringContext<'a> {
         let arg_vals: Vec<_> = args.iter().filter_map(|a| self.lower_expr(a)).collect();
 
         // Look up effect operation return type
-        let ret_ty = if let Some(ops) = self.effects.get(effect) {
+        let ret_ty = if let Some(ops) = self.info.effects.get(effect) {
             ops.iter()
                 .find(|(name, _, _)| name == op)
                 .map(|(_, _, ret)| ret.clone())
@@ -1434,6 +2091,7 @@ impl<'a> LoweringContext<'a> {
                 args: arg_vals,
             },
             ty: ret_ty,
+            span: self.builder.current_span(),
         };
         self.builder
             .func
@@ -1459,7 +2117,7 @@ impl<'a> LoweringContext<'a> {
         // 3. The handler intercepts effect operations
 
         // Look up the handler's effect
-        let _effect = self.handlers.get(handler).cloned();
+        let _effect = self.info.handlers.get(handler).cloned();
 
         // For a simplified implementation, we just evaluate the expression
         // A full implementation would:
@@ -1514,6 +2172,18 @@ impl<'a> LoweringContext<'a> {
     }
 }
 
+/// Recognize a callee naming the `likely`/`unlikely` intrinsics.
+///
+/// The checker resolves intrinsics to globals, so a user function with the
+/// same name (a local) is never mistaken for one.
+fn branch_hint_intrinsic(func: &HirExpr) -> Option<BranchHint> {
+    match &func.kind {
+        HirExprKind::Global(name) if name == "likely" => Some(BranchHint::Likely),
+        HirExprKind::Global(name) if name == "unlikely" => Some(BranchHint::Unlikely),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1563,6 +2233,10 @@ mod tests {
                     })],
                     ty: HirType::I64,
                 },
+                is_pure: false,
+                is_async: false,
+                inline: InlineHint::Auto,
+                is_kernel: false,
             })],
         }
     }
@@ -1570,7 +2244,7 @@ mod tests {
     #[test]
     fn test_lower_simple_function() {
         let hir = make_simple_hir();
-        let hlir = lower(&hir);
+        let hlir = lower(&hir).unwrap();
 
         assert_eq!(hlir.functions.len(), 1);
         let func = &hlir.functions[0];
@@ -1639,6 +2313,10 @@ mod tests {
                     })],
                     ty: HirType::I64,
                 },
+                is_pure: false,
+                is_async: false,
+                inline: InlineHint::Auto,
+                is_kernel: false,
             })],
         }
     }
@@ -1646,7 +2324,7 @@ mod tests {
     #[test]
     fn test_lower_match_with_switch() {
         let hir = make_match_hir();
-        let hlir = lower(&hir);
+        let hlir = lower(&hir).unwrap();
 
         assert_eq!(hlir.functions.len(), 1);
         let func = &hlir.functions[0];
@@ -1734,10 +2412,14 @@ mod tests {
                     })],
                     ty: HirType::I64,
                 },
+                is_pure: false,
+                is_async: false,
+                inline: InlineHint::Auto,
+                is_kernel: false,
             })],
         };
 
-        let hlir = lower(&hir);
+        let hlir = lower(&hir).unwrap();
         assert_eq!(hlir.functions.len(), 1);
         let func = &hlir.functions[0];
 
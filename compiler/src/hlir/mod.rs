@@ -9,12 +9,17 @@
 
 pub mod builder;
 pub mod ir;
+pub mod layout;
 pub mod lower;
+pub mod opt;
+pub mod visit;
 
 // Re-export main types
 pub use builder::{FunctionBuilder, ModuleBuilder};
 pub use ir::*;
-pub use lower::lower;
+pub use layout::{Layout, Layouts};
+pub use lower::{LowerOptions, lower, lower_with};
+pub use opt::{DEFAULT_INLINE_THRESHOLD, OptSettings, optimize, optimize_with};
 
 #[cfg(test)]
 mod tests {
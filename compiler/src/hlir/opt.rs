@@ -0,0 +1,604 @@
+//! HLIR optimizations
+//!
+//! Calls to `#[pure]` functions whose arguments are all constants are
+//! inlined, then constants are folded and dead instructions removed, so a
+//! call like `square(3)` reduces to the constant `9`.
+//!
+//! Independently of purity, small functions are inlined at every call site:
+//! a callee with fewer HLIR instructions than the inline threshold is
+//! inlined unless it is recursive, performs effects, or is marked
+//! `#[inline(never)]`. `#[inline]` inlines a callee regardless of its size.
+
+use std::collections::{HashMap, HashSet};
+
+use super::ir::*;
+use crate::hir::InlineHint;
+
+/// Default instruction count below which functions are inlined
+pub const DEFAULT_INLINE_THRESHOLD: usize = 8;
+
+/// Upper bound on inline/fold rounds per function, which also stops
+/// self-recursive pure functions from being inlined forever
+const MAX_ROUNDS: usize = 8;
+
+/// Tuning for the optimization pipeline
+#[derive(Debug, Clone)]
+pub struct OptSettings {
+    /// Functions with fewer HLIR instructions than this are inlined without
+    /// an attribute; 0 disables size-based inlining
+    pub inline_threshold: usize,
+}
+
+impl Default for OptSettings {
+    fn default() -> Self {
+        Self {
+            inline_threshold: DEFAULT_INLINE_THRESHOLD,
+        }
+    }
+}
+
+/// A function that calls may be inlined to
+struct InlineCandidate {
+    func: HlirFunction,
+    /// Only inline calls whose arguments are all constants, so they fold
+    constant_args_only: bool,
+}
+
+/// Run the HLIR optimization pipeline over a module
+pub fn optimize(module: &mut HlirModule) {
+    optimize_with(module, &OptSettings::default());
+}
+
+/// Run the HLIR optimization pipeline with explicit settings
+pub fn optimize_with(module: &mut HlirModule, settings: &OptSettings) {
+    let inlinable: HashMap<String, InlineCandidate> = module
+        .functions
+        .iter()
+        .filter(|f| f.inline != InlineHint::Never && is_straight_line(f))
+        .filter_map(|f| {
+            let by_size =
+                f.inline == InlineHint::Always || instruction_count(f) < settings.inline_threshold;
+            let auto = by_size && !is_recursive(f) && !is_effectful(f);
+            (auto || f.is_pure).then(|| {
+                let candidate = InlineCandidate {
+                    func: f.clone(),
+                    constant_args_only: !auto,
+                };
+                (f.name.clone(), candidate)
+            })
+        })
+        .collect();
+
+    for func in &mut module.functions {
+        for _ in 0..MAX_ROUNDS {
+            let constants = fold_constants(func);
+            if !inline_calls(func, &inlinable, &constants) {
+                break;
+            }
+        }
+        fold_constants(func);
+        remove_dead_instructions(func);
+    }
+}
+
+/// Number of HLIR instructions in a function body
+pub fn instruction_count(func: &HlirFunction) -> usize {
+    func.blocks.iter().map(|b| b.instructions.len()).sum()
+}
+
+/// Whether the function calls itself directly
+fn is_recursive(func: &HlirFunction) -> bool {
+    func.blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .any(|i| matches!(&i.op, Op::CallDirect { name, .. } if *name == func.name))
+}
+
+/// Whether the function declares or performs effects
+fn is_effectful(func: &HlirFunction) -> bool {
+    !func.effects.is_empty()
+        || func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .any(|i| matches!(i.op, Op::PerformEffect { .. }))
+}
+
+/// A single block ending in a value return
+fn is_straight_line(func: &HlirFunction) -> bool {
+    matches!(
+        func.blocks.as_slice(),
+        [block] if matches!(block.terminator, HlirTerminator::Return(_))
+    )
+}
+
+/// Inline calls to `inlinable` functions, restricted to calls whose
+/// arguments are all in `constants` where the candidate requires it.
+/// Returns whether anything was inlined.
+fn inline_calls(
+    func: &mut HlirFunction,
+    inlinable: &HashMap<String, InlineCandidate>,
+    constants: &HashMap<ValueId, HlirConstant>,
+) -> bool {
+    let caller = func.name.clone();
+    let mut next_value = next_value_id(func);
+    let mut new_locals = HashMap::new();
+    let mut changed = false;
+
+    for block in &mut func.blocks {
+        let mut instructions = Vec::with_capacity(block.instructions.len());
+        for instr in std::mem::take(&mut block.instructions) {
+            let callee = match &instr.op {
+                Op::CallDirect { name, args } if *name != caller => inlinable
+                    .get(name)
+                    .filter(|c| {
+                        !c.constant_args_only || args.iter().all(|a| constants.contains_key(a))
+                    })
+                    .map(|c| (&c.func, args.clone())),
+                _ => None,
+            };
+
+            let Some((callee, args)) = callee else {
+                instructions.push(instr);
+                continue;
+            };
+
+            let mut map: HashMap<ValueId, ValueId> =
+                callee.params.iter().map(|p| p.value).zip(args).collect();
+            let body = &callee.blocks[0];
+            for callee_instr in &body.instructions {
+                let mut op = callee_instr.op.clone();
+                for operand in operands_mut(&mut op) {
+                    *operand = map.get(operand).copied().unwrap_or(*operand);
+                }
+                let result = callee_instr.result.map(|r| {
+                    let fresh = ValueId(next_value);
+                    next_value += 1;
+                    map.insert(r, fresh);
+                    if let Some(ty) = callee.locals.get(&r) {
+                        new_locals.insert(fresh, ty.clone());
+                    }
+                    fresh
+                });
+                instructions.push(HlirInstr {
+                    result,
+                    op,
+                    ty: callee_instr.ty.clone(),
+                    span: callee_instr.span,
+                });
+            }
+
+            // The call's result becomes a copy of the inlined return value
+            if let (Some(result), HlirTerminator::Return(Some(ret))) =
+                (instr.result, &body.terminator)
+            {
+                let ret = map.get(ret).copied().unwrap_or(*ret);
+                instructions.push(HlirInstr {
+                    result: Some(result),
+                    op: Op::Copy(ret),
+                    ty: instr.ty,
+                    span: instr.span,
+                });
+            }
+            changed = true;
+        }
+        block.instructions = instructions;
+    }
+
+    func.locals.extend(new_locals);
+    changed
+}
+
+/// Fold operations on constant operands in place, returning every value
+/// known to be constant
+pub fn fold_constants(func: &mut HlirFunction) -> HashMap<ValueId, HlirConstant> {
+    let mut constants = HashMap::new();
+
+    for block in &mut func.blocks {
+        for instr in &mut block.instructions {
+            let folded = match &instr.op {
+                Op::Const(c) => Some(c.clone()),
+                Op::Copy(v) => constants.get(v).cloned(),
+                Op::Binary { op, left, right } => match (constants.get(left), constants.get(right))
+                {
+                    (Some(l), Some(r)) => fold_binary(*op, l, r, &instr.ty),
+                    _ => None,
+                },
+                Op::Unary { op, operand } => constants
+                    .get(operand)
+                    .and_then(|v| fold_unary(*op, v, &instr.ty)),
+                _ => None,
+            };
+
+            if let (Some(result), Some(c)) = (instr.result, folded) {
+                instr.op = Op::Const(c.clone());
+                constants.insert(result, c);
+            }
+        }
+    }
+
+    constants
+}
+
+/// Evaluate a binary operation, or `None` if it would overflow, divide by
+/// zero, or the operands aren't foldable
+fn fold_binary(
+    op: BinaryOp,
+    left: &HlirConstant,
+    right: &HlirConstant,
+    ty: &HlirType,
+) -> Option<HlirConstant> {
+    use BinaryOp::*;
+
+    match (left, right) {
+        (HlirConstant::Int(a, operand_ty), HlirConstant::Int(b, _)) => {
+            let (a, b) = (*a, *b);
+            let int = |v: Option<i64>| {
+                v.filter(|v| fits(*v, ty))
+                    .map(|v| HlirConstant::Int(v, ty.clone()))
+            };
+            let signed = operand_ty.is_signed();
+            match op {
+                Add => int(a.checked_add(b)),
+                Sub => int(a.checked_sub(b)),
+                Mul => int(a.checked_mul(b)),
+                SDiv => int(a.checked_div(b)),
+                SRem => int(a.checked_rem(b)),
+                And => int(Some(a & b)),
+                Or => int(Some(a | b)),
+                Xor => int(Some(a ^ b)),
+                Eq => Some(HlirConstant::Bool(a == b)),
+                Ne => Some(HlirConstant::Bool(a != b)),
+                SLt if signed => Some(HlirConstant::Bool(a < b)),
+                SLe if signed => Some(HlirConstant::Bool(a <= b)),
+                SGt if signed => Some(HlirConstant::Bool(a > b)),
+                SGe if signed => Some(HlirConstant::Bool(a >= b)),
+                _ => None,
+            }
+        }
+        (HlirConstant::Float(a, _), HlirConstant::Float(b, _)) => {
+            let (a, b) = (*a, *b);
+            let float = |v: f64| Some(HlirConstant::Float(v, ty.clone()));
+            match op {
+                FAdd => float(a + b),
+                FSub => float(a - b),
+                FMul => float(a * b),
+                FDiv => float(a / b),
+                FOEq => Some(HlirConstant::Bool(a == b)),
+                FONe => Some(HlirConstant::Bool(a != b)),
+                FOLt => Some(HlirConstant::Bool(a < b)),
+                FOLe => Some(HlirConstant::Bool(a <= b)),
+                FOGt => Some(HlirConstant::Bool(a > b)),
+                FOGe => Some(HlirConstant::Bool(a >= b)),
+                _ => None,
+            }
+        }
+        (HlirConstant::Bool(a), HlirConstant::Bool(b)) => match op {
+            And => Some(HlirConstant::Bool(*a && *b)),
+            Or => Some(HlirConstant::Bool(*a || *b)),
+            Xor | Ne => Some(HlirConstant::Bool(a != b)),
+            Eq => Some(HlirConstant::Bool(a == b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fold_unary(op: UnaryOp, value: &HlirConstant, ty: &HlirType) -> Option<HlirConstant> {
+    match (op, value) {
+        (UnaryOp::Neg, HlirConstant::Int(v, _)) => v
+            .checked_neg()
+            .filter(|v| fits(*v, ty))
+            .map(|v| HlirConstant::Int(v, ty.clone())),
+        (UnaryOp::FNeg, HlirConstant::Float(v, _)) => Some(HlirConstant::Float(-v, ty.clone())),
+        (UnaryOp::Not, HlirConstant::Bool(b)) => Some(HlirConstant::Bool(!b)),
+        _ => None,
+    }
+}
+
+/// Whether `value` is representable in the integer type `ty`. Types wider
+/// than the folder's `i64` arithmetic are never folded.
+fn fits(value: i64, ty: &HlirType) -> bool {
+    let (min, max) = match ty {
+        HlirType::I8 => (i8::MIN as i64, i8::MAX as i64),
+        HlirType::I16 => (i16::MIN as i64, i16::MAX as i64),
+        HlirType::I32 => (i32::MIN as i64, i32::MAX as i64),
+        HlirType::I64 => (i64::MIN, i64::MAX),
+        HlirType::U8 => (0, u8::MAX as i64),
+        HlirType::U16 => (0, u16::MAX as i64),
+        HlirType::U32 => (0, u32::MAX as i64),
+        _ => return false,
+    };
+    (min..=max).contains(&value)
+}
+
+/// Remove side-effect-free instructions whose results are never used
+pub fn remove_dead_instructions(func: &mut HlirFunction) {
+    loop {
+        let mut used = HashSet::new();
+        for block in &mut func.blocks {
+            for instr in &mut block.instructions {
+                used.extend(operands_mut(&mut instr.op).into_iter().map(|v| *v));
+            }
+            used.extend(terminator_operands(&block.terminator));
+        }
+
+        let mut removed = false;
+        for block in &mut func.blocks {
+            block.instructions.retain(|instr| {
+                let dead = is_side_effect_free(&instr.op)
+                    && instr.result.is_some_and(|r| !used.contains(&r));
+                removed |= dead;
+                !dead
+            });
+        }
+
+        if !removed {
+            break;
+        }
+    }
+}
+
+fn is_side_effect_free(op: &Op) -> bool {
+    matches!(
+        op,
+        Op::Const(_)
+            | Op::Copy(_)
+            | Op::Binary { .. }
+            | Op::Unary { .. }
+            | Op::Cast { .. }
+            | Op::Bitcast { .. }
+            | Op::Phi { .. }
+            | Op::ExtractValue { .. }
+            | Op::InsertValue { .. }
+            | Op::Tuple(_)
+            | Op::Array(_)
+            | Op::Struct { .. }
+    )
+}
+
+/// Every value an operation reads
+fn operands_mut(op: &mut Op) -> Vec<&mut ValueId> {
+    match op {
+        Op::Const(_) | Op::Alloca { .. } => Vec::new(),
+        Op::Copy(v) | Op::Load { ptr: v } => vec![v],
+        Op::Unary { operand, .. } => vec![operand],
+        Op::Cast { value, .. } | Op::Bitcast { value, .. } => vec![value],
+        Op::Binary { left, right, .. } => vec![left, right],
+        Op::Store { ptr, value } => vec![ptr, value],
+        Op::GetFieldPtr { base, .. } | Op::ExtractValue { base, .. } => vec![base],
+        Op::GetElementPtr { base, index } => vec![base, index],
+        Op::InsertValue { base, value, .. } => vec![base, value],
+        Op::Call { func, args } => std::iter::once(func).chain(args.iter_mut()).collect(),
+        Op::CallDirect { args, .. }
+        | Op::Tuple(args)
+        | Op::Array(args)
+        | Op::PerformEffect { args, .. } => args.iter_mut().collect(),
+        Op::Phi { incoming } => incoming.iter_mut().map(|(_, v)| v).collect(),
+        Op::Struct { fields, .. } => fields.iter_mut().map(|(_, v)| v).collect(),
+    }
+}
+
+fn terminator_operands(term: &HlirTerminator) -> Vec<ValueId> {
+    match term {
+        HlirTerminator::Return(Some(v)) => vec![*v],
+        HlirTerminator::CondBranch { condition, .. } => vec![*condition],
+        HlirTerminator::Switch { value, .. } => vec![*value],
+        HlirTerminator::Return(None)
+        | HlirTerminator::Branch(_)
+        | HlirTerminator::Unreachable
+        | HlirTerminator::Trap => Vec::new(),
+    }
+}
+
+/// First value id not used anywhere in `func`
+fn next_value_id(func: &HlirFunction) -> u32 {
+    let params = func.params.iter().map(|p| p.value);
+    let block_params = func
+        .blocks
+        .iter()
+        .flat_map(|b| b.params.iter().map(|(v, _)| *v));
+    let results = func
+        .blocks
+        .iter()
+        .flat_map(|b| b.instructions.iter().filter_map(|i| i.result));
+    params
+        .chain(block_params)
+        .chain(results)
+        .filter(|v| *v != ValueId::UNIT)
+        .map(|v| v.0 + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn optimized(source: &str) -> HlirModule {
+        optimized_with(source, DEFAULT_INLINE_THRESHOLD)
+    }
+
+    fn optimized_with(source: &str, inline_threshold: usize) -> HlirModule {
+        let hir = crate::typecheck(source).unwrap();
+        let mut module = crate::hlir::lower(&hir).unwrap();
+        optimize_with(&mut module, &OptSettings { inline_threshold });
+        module
+    }
+
+    fn calls(func: &HlirFunction) -> usize {
+        func.blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .filter(|i| matches!(i.op, Op::CallDirect { .. }))
+            .count()
+    }
+
+    #[test]
+    fn test_pure_call_with_constant_args_folds() {
+        let module = optimized(
+            r#"
+            #[pure]
+            fn square(x: i64) -> i64 { x * x }
+            fn main() -> i64 { square(3) }
+        "#,
+        );
+
+        let main = module.find_function("main").unwrap();
+        let instrs = &main.blocks[0].instructions;
+        assert_eq!(instrs.len(), 1, "expected a single constant: {:?}", instrs);
+        assert!(matches!(instrs[0].op, Op::Const(HlirConstant::Int(9, _))));
+        assert!(matches!(
+            main.blocks[0].terminator,
+            HlirTerminator::Return(Some(v)) if Some(v) == instrs[0].result
+        ));
+    }
+
+    #[test]
+    fn test_nested_pure_calls_fold() {
+        let module = optimized(
+            r#"
+            #[pure]
+            fn square(x: i64) -> i64 { x * x }
+            fn main() -> i64 { square(square(2)) + 1 }
+        "#,
+        );
+
+        let main = module.find_function("main").unwrap();
+        let instrs = &main.blocks[0].instructions;
+        assert_eq!(instrs.len(), 1);
+        assert!(matches!(instrs[0].op, Op::Const(HlirConstant::Int(17, _))));
+    }
+
+    #[test]
+    fn test_impure_call_is_kept() {
+        let module = optimized_with(
+            r#"
+            fn square(x: i64) -> i64 { x * x }
+            fn main() -> i64 { square(3) }
+        "#,
+            0,
+        );
+        assert_eq!(calls(module.find_function("main").unwrap()), 1);
+    }
+
+    #[test]
+    fn test_non_constant_args_are_kept() {
+        let module = optimized_with(
+            r#"
+            #[pure]
+            fn square(x: i64) -> i64 { x * x }
+            fn apply(n: i64) -> i64 { square(n) }
+        "#,
+            0,
+        );
+        assert_eq!(calls(module.find_function("apply").unwrap()), 1);
+    }
+
+    #[test]
+    fn test_overflow_is_not_folded() {
+        let module = optimized(
+            r#"
+            #[pure]
+            fn square(x: i64) -> i64 { x * x }
+            fn main() -> i64 { square(9223372036854775807) }
+        "#,
+        );
+
+        let main = module.find_function("main").unwrap();
+        assert!(
+            main.blocks[0]
+                .instructions
+                .iter()
+                .any(|i| matches!(i.op, Op::Binary { .. }))
+        );
+    }
+
+    const SMALL_AND_LARGE: &str = r#"
+        fn add(a: i64, b: i64) -> i64 { a * b + a }
+        fn big(a: i64, b: i64) -> i64 {
+            let c = a * b + a - b
+            let d = c * c + a * 3 - b * 5
+            let e = d * d - c * a + b * 7
+            e * e + d * c - a * b
+        }
+        fn main(x: i64, y: i64) -> i64 { add(x, y) + big(x, y) }
+    "#;
+
+    fn calls_to(func: &HlirFunction, callee: &str) -> usize {
+        func.blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .filter(|i| matches!(&i.op, Op::CallDirect { name, .. } if name == callee))
+            .count()
+    }
+
+    #[test]
+    fn test_small_function_is_auto_inlined() {
+        let module = optimized(SMALL_AND_LARGE);
+        assert_eq!(instruction_count(module.find_function("add").unwrap()), 2);
+        assert!(
+            instruction_count(module.find_function("big").unwrap()) >= DEFAULT_INLINE_THRESHOLD
+        );
+
+        let main = module.find_function("main").unwrap();
+        assert_eq!(calls_to(main, "add"), 0);
+        assert_eq!(calls_to(main, "big"), 1);
+    }
+
+    #[test]
+    fn test_inline_threshold_changes_decision() {
+        let module = optimized_with(SMALL_AND_LARGE, 2);
+        assert_eq!(calls_to(module.find_function("main").unwrap(), "add"), 1);
+
+        let module = optimized_with(SMALL_AND_LARGE, 100);
+        let main = module.find_function("main").unwrap();
+        assert_eq!(calls_to(main, "add"), 0);
+        assert_eq!(calls_to(main, "big"), 0);
+    }
+
+    #[test]
+    fn test_inline_attributes() {
+        let module = optimized(
+            r#"
+            #[inline(never)]
+            fn add(a: i64, b: i64) -> i64 { a + b }
+            fn main(x: i64) -> i64 { add(x, 1) }
+        "#,
+        );
+        assert_eq!(calls_to(module.find_function("main").unwrap(), "add"), 1);
+
+        let module = optimized_with(
+            r#"
+            #[inline]
+            fn add(a: i64, b: i64) -> i64 { a + b }
+            fn main(x: i64) -> i64 { add(x, 1) }
+        "#,
+            0,
+        );
+        assert_eq!(calls_to(module.find_function("main").unwrap(), "add"), 0);
+    }
+
+    #[test]
+    fn test_noinline_attribute() {
+        let module = optimized(
+            r#"
+            #[noinline]
+            fn add(a: i64, b: i64) -> i64 { a + b }
+            fn main(x: i64) -> i64 { add(x, 1) }
+        "#,
+        );
+        assert_eq!(calls_to(module.find_function("main").unwrap(), "add"), 1);
+    }
+
+    #[test]
+    fn test_recursive_function_is_not_auto_inlined() {
+        let module = optimized(
+            r#"
+            fn spin(n: i64) -> i64 { spin(n) }
+            fn main(x: i64) -> i64 { spin(x) }
+        "#,
+        );
+        assert_eq!(calls_to(module.find_function("main").unwrap(), "spin"), 1);
+    }
+}
@@ -0,0 +1,153 @@
+//! HLIR traversal
+//!
+//! `HlirVisitor` and `HlirVisitorMut` walk functions, basic blocks,
+//! instructions and terminators. As with the HIR visitors, every `visit_*`
+//! method defaults to the matching `walk_*` function.
+
+use super::ir::*;
+
+/// Read-only HLIR visitor
+pub trait HlirVisitor {
+    fn visit_function(&mut self, func: &HlirFunction) {
+        walk_function(self, func);
+    }
+
+    fn visit_block(&mut self, block: &HlirBlock) {
+        walk_block(self, block);
+    }
+
+    fn visit_instr(&mut self, _instr: &HlirInstr) {}
+
+    fn visit_terminator(&mut self, _terminator: &HlirTerminator) {}
+}
+
+/// Visit every function of an HLIR module
+pub fn walk_module<V: HlirVisitor + ?Sized>(visitor: &mut V, module: &HlirModule) {
+    for func in &module.functions {
+        visitor.visit_function(func);
+    }
+}
+
+/// Visit the blocks of a function in layout order
+pub fn walk_function<V: HlirVisitor + ?Sized>(visitor: &mut V, func: &HlirFunction) {
+    for block in &func.blocks {
+        visitor.visit_block(block);
+    }
+}
+
+/// Visit the instructions and then the terminator of a block
+pub fn walk_block<V: HlirVisitor + ?Sized>(visitor: &mut V, block: &HlirBlock) {
+    for instr in &block.instructions {
+        visitor.visit_instr(instr);
+    }
+    visitor.visit_terminator(&block.terminator);
+}
+
+/// Mutating HLIR visitor
+pub trait HlirVisitorMut {
+    fn visit_function_mut(&mut self, func: &mut HlirFunction) {
+        walk_function_mut(self, func);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut HlirBlock) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_instr_mut(&mut self, _instr: &mut HlirInstr) {}
+
+    fn visit_terminator_mut(&mut self, _terminator: &mut HlirTerminator) {}
+}
+
+/// Visit every function of an HLIR module mutably
+pub fn walk_module_mut<V: HlirVisitorMut + ?Sized>(visitor: &mut V, module: &mut HlirModule) {
+    for func in &mut module.functions {
+        visitor.visit_function_mut(func);
+    }
+}
+
+/// Visit the blocks of a function mutably
+pub fn walk_function_mut<V: HlirVisitorMut + ?Sized>(visitor: &mut V, func: &mut HlirFunction) {
+    for block in &mut func.blocks {
+        visitor.visit_block_mut(block);
+    }
+}
+
+/// Visit the instructions and then the terminator of a block mutably
+pub fn walk_block_mut<V: HlirVisitorMut + ?Sized>(visitor: &mut V, block: &mut HlirBlock) {
+    for instr in &mut block.instructions {
+        visitor.visit_instr_mut(instr);
+    }
+    visitor.visit_terminator_mut(&mut block.terminator);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Stats {
+        calls: usize,
+        returns: usize,
+    }
+
+    impl HlirVisitor for Stats {
+        fn visit_instr(&mut self, instr: &HlirInstr) {
+            if matches!(instr.op, Op::CallDirect { .. } | Op::Call { .. }) {
+                self.calls += 1;
+            }
+        }
+
+        fn visit_terminator(&mut self, terminator: &HlirTerminator) {
+            if let HlirTerminator::Return(_) = terminator {
+                self.returns += 1;
+            }
+        }
+    }
+
+    struct ZeroConstants;
+
+    impl HlirVisitorMut for ZeroConstants {
+        fn visit_instr_mut(&mut self, instr: &mut HlirInstr) {
+            if let Op::Const(HlirConstant::Int(n, _)) = &mut instr.op {
+                *n = 0;
+            }
+        }
+    }
+
+    fn lower(source: &str) -> HlirModule {
+        let hir = crate::typecheck(source).unwrap();
+        crate::hlir::lower(&hir).unwrap()
+    }
+
+    #[test]
+    fn test_hlir_visitor_counts_calls() {
+        let module = lower(
+            r#"
+            fn one() -> i64 { 1 }
+            fn main() -> i64 { one() + one() }
+        "#,
+        );
+
+        let mut stats = Stats::default();
+        walk_module(&mut stats, &module);
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.returns, 2);
+    }
+
+    #[test]
+    fn test_hlir_visitor_mut_rewrites_constants() {
+        let mut module = lower("fn main() -> i64 { 7 }");
+        walk_module_mut(&mut ZeroConstants, &mut module);
+
+        let main = module.find_function("main").unwrap();
+        let consts: Vec<_> = main.blocks[0]
+            .instructions
+            .iter()
+            .filter_map(|i| match i.op {
+                Op::Const(HlirConstant::Int(n, _)) => Some(n),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(consts, vec![0]);
+    }
+}
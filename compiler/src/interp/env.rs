@@ -1,13 +1,16 @@
 //! Lexical environment for variable bindings
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use super::Value;
 
-/// Lexical scope containing variable bindings
+/// Lexical scope containing variable bindings. Each binding lives in its
+/// own cell so references to it see later assignments.
 #[derive(Debug, Clone)]
-struct Scope {
-    bindings: HashMap<String, Value>,
+pub struct Scope {
+    bindings: HashMap<String, Rc<RefCell<Value>>>,
 }
 
 impl Scope {
@@ -46,34 +49,51 @@ impl Environment {
         }
     }
 
+    /// Pop the innermost scope and hand it back, so it can be restored
+    /// later with its bindings intact
+    pub fn take_scope(&mut self) -> Option<Scope> {
+        if self.scopes.len() > 1 {
+            self.scopes.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Push a scope taken earlier with `take_scope`
+    pub fn restore_scope(&mut self, scope: Scope) {
+        self.scopes.push(scope);
+    }
+
     /// Define a variable in the current scope
     pub fn define(&mut self, name: String, value: Value) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.bindings.insert(name, value);
+            scope.bindings.insert(name, Rc::new(RefCell::new(value)));
         }
     }
 
     /// Look up a variable by name
     pub fn get(&self, name: &str) -> Option<Value> {
+        self.cell(name).map(|cell| cell.borrow().clone())
+    }
+
+    /// The cell holding a variable, shared by references to it
+    pub fn cell(&self, name: &str) -> Option<Rc<RefCell<Value>>> {
         // Search from innermost to outermost scope
-        for scope in self.scopes.iter().rev() {
-            if let Some(value) = scope.bindings.get(name) {
-                return Some(value.clone());
-            }
-        }
-        None
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.bindings.get(name).cloned())
     }
 
     /// Assign to an existing variable
     pub fn assign(&mut self, name: &str, value: Value) -> bool {
-        // Search from innermost to outermost scope
-        for scope in self.scopes.iter_mut().rev() {
-            if scope.bindings.contains_key(name) {
-                scope.bindings.insert(name.to_string(), value);
-                return true;
+        match self.cell(name) {
+            Some(cell) => {
+                *cell.borrow_mut() = value;
+                true
             }
+            None => false,
         }
-        false
     }
 
     /// Get all bindings (for capturing closures)
@@ -81,7 +101,7 @@ impl Environment {
         let mut captured = HashMap::new();
         for scope in &self.scopes {
             for (name, value) in &scope.bindings {
-                captured.insert(name.clone(), value.clone());
+                captured.insert(name.clone(), value.borrow().clone());
             }
         }
         captured
@@ -1,15 +1,249 @@
 //! Tree-walking interpreter for HIR
 
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::rc::Rc;
 
 use miette::{Result, miette};
 
+use crate::hir::fold::int_range;
 use crate::hir::*;
+use crate::types::ArrayLen;
+
+use super::env::{Environment, Scope};
+use super::random::{DEFAULT_SEED, Distribution, Rng, Sample};
+use super::value::{ControlFlow, Value, ValueMap};
+
+/// Built-in functions resolvable by name without an import
+pub const INTRINSICS: &[&str] = &[
+    "print",
+    "println",
+    "format",
+    "likely",
+    "unlikely",
+    "parse_int",
+    "parse_float",
+    "to_string",
+    "hash",
+    "map_new",
+    "map_insert",
+    "map_get",
+    "map_contains",
+    "set_new",
+    "set_insert",
+    "set_contains",
+    "vec",
+    "Vec::new",
+    "Normal",
+    "Uniform",
+    "Bernoulli",
+];
+
+/// Check whether `name` refers to a built-in intrinsic
+pub fn is_intrinsic(name: &str) -> bool {
+    INTRINSICS.contains(&name)
+}
+
+/// Interpolate `{}` placeholders in `template` with the `Display` of `args`,
+/// returning the text and the number of placeholders found.
+///
+/// `{{` and `}}` produce literal braces. Placeholders without a matching
+/// argument are left as-is and surplus arguments are ignored.
+pub fn format_values(template: &str, args: &[Value]) -> (String, usize) {
+    let mut out = String::with_capacity(template.len());
+    let mut placeholders = 0;
+    let mut args = args.iter();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                out.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                match args.next() {
+                    Some(arg) => out.push_str(&arg.to_string()),
+                    None => out.push_str("{}"),
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    (out, placeholders)
+}
 
-use super::env::Environment;
-use super::value::{ControlFlow, Value};
+/// Reinterpret the bits of `value`, of type `from`, as type `to`.
+///
+/// Only scalars are supported; `None` is returned for anything else.
+pub fn transmute_value(value: &Value, from: &HirType, to: &HirType) -> Option<Value> {
+    let bits = match (value, from) {
+        (Value::Bool(b), _) => u128::from(*b),
+        (Value::Float(f), HirType::F32) => u128::from((*f as f32).to_bits()),
+        (Value::Float(f), HirType::F64) => u128::from(f.to_bits()),
+        (Value::Int(n), _) if from.is_integer() => *n as i128 as u128,
+        _ => return None,
+    };
+    let width = 8 * to.size_bytes()? as u32;
+    let bits = if width >= 128 {
+        bits
+    } else {
+        bits & ((1u128 << width) - 1)
+    };
+
+    Some(match to {
+        HirType::Bool => Value::Bool(bits != 0),
+        HirType::F32 => Value::Float(f64::from(f32::from_bits(bits as u32))),
+        HirType::F64 => Value::Float(f64::from_bits(bits as u64)),
+        HirType::I8 | HirType::I16 | HirType::I32 | HirType::I64 | HirType::Isize => {
+            // Sign-extend from the target width
+            let shift = 128 - width;
+            Value::Int(((bits << shift) as i128 >> shift) as i64)
+        }
+        _ if to.is_integer() => Value::Int(bits as i64),
+        _ => return None,
+    })
+}
+
+/// Convert `value`, of type `from`, to `to` with `as` semantics.
+///
+/// Integers wrap to the target width, floats truncate toward zero and
+/// saturate, and `bool` and `char` convert to their integer values.
+pub fn cast_value(value: &Value, from: &HirType, to: &HirType) -> Option<Value> {
+    let value = match (value, from) {
+        (Value::String(s), HirType::Char) => Value::Int(s.chars().next()? as i64),
+        (Value::Bool(b), _) => Value::Int(i64::from(*b)),
+        _ => value.clone(),
+    };
+
+    Some(match (value, to) {
+        (Value::Int(n), HirType::F32) => Value::Float(f64::from(n as f32)),
+        (Value::Int(n), HirType::F64) => Value::Float(n as f64),
+        (Value::Float(f), HirType::F32) => Value::Float(f64::from(f as f32)),
+        (Value::Float(f), HirType::F64) => Value::Float(f),
+        (Value::Int(n), HirType::Char) => Value::String(char::from(n as u8).to_string()),
+        (Value::Int(n), _) if to.is_integer() => {
+            transmute_value(&Value::Int(n), &HirType::I64, to)?
+        }
+        (Value::Float(f), _) => Value::Int(match to {
+            HirType::I8 => f as i8 as i64,
+            HirType::I16 => f as i16 as i64,
+            HirType::I32 => f as i32 as i64,
+            HirType::I64 | HirType::Isize | HirType::I128 => f as i64,
+            HirType::U8 => f as u8 as i64,
+            HirType::U16 => f as u16 as i64,
+            HirType::U32 => f as u32 as i64,
+            HirType::U64 | HirType::Usize | HirType::U128 => f as u64 as i64,
+            _ => return None,
+        }),
+        (value, _) if from == to => value,
+        _ => return None,
+    })
+}
+
+/// A copy of `value` whose arrays, at any depth, are fresh and not shared
+/// with the original
+fn copy_value(value: &Value) -> Value {
+    match value {
+        Value::Array(elements) => Value::Array(Rc::new(RefCell::new(
+            elements.borrow().iter().map(copy_value).collect(),
+        ))),
+        other => other.clone(),
+    }
+}
+
+/// Bounds-check `index` into a vector of length `len`
+fn checked_index(index: i64, len: usize) -> Result<usize, ControlFlow> {
+    usize::try_from(index)
+        .ok()
+        .filter(|&i| i < len)
+        .ok_or_else(|| {
+            ControlFlow::Error(format!(
+                "index out of bounds: the len is {} but the index is {}",
+                len, index
+            ))
+        })
+}
+
+/// An index value as an integer
+fn index_int(index: &Value) -> Result<i64, ControlFlow> {
+    index.as_int().ok_or_else(|| {
+        ControlFlow::Error(format!(
+            "index must be an integer, found {}",
+            describe_value(index)
+        ))
+    })
+}
+
+/// Error for a map or set intrinsic called without its collection and key
+fn collection_args_error(name: &str) -> ControlFlow {
+    let collection = if name.starts_with("set_") {
+        "set"
+    } else {
+        "map"
+    };
+    ControlFlow::Error(format!("`{}` expects a {} and a key", name, collection))
+}
+
+/// The statements of a handler case body; a body that isn't a block is a
+/// single expression statement
+fn case_stmts(body: &HirExpr) -> Cow<'_, [HirStmt]> {
+    match &body.kind {
+        HirExprKind::Block(block) => Cow::Borrowed(&block.stmts),
+        _ => Cow::Owned(vec![HirStmt::Expr(body.clone())]),
+    }
+}
+
+/// Whether `expr` is a direct call of the case's `resume`
+fn is_resume_call(expr: &HirExpr) -> bool {
+    matches!(&expr.kind, HirExprKind::Call { func, .. }
+        if matches!(&func.kind, HirExprKind::Local(name) if name == "resume"))
+}
+
+/// A value's type for error messages: the struct or enum name when it has
+/// one
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::Struct { name, .. } => format!("struct `{}`", name),
+        Value::Variant { enum_name, .. } => format!("enum `{}`", enum_name),
+        other => other.type_name().to_string(),
+    }
+}
+
+/// How integer `+`, `-` and `*` behave when the result does not fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowMode {
+    /// Overflow is a runtime error, matching debug builds
+    #[default]
+    Checked,
+    /// Results wrap around in two's complement
+    Wrapping,
+}
+
+/// Checked and wrapping forms of an integer operation
+type IntOp = (fn(i64, i64) -> Option<i64>, fn(i64, i64) -> i64);
+
+/// A `handle` expression being evaluated
+struct HandlerFrame {
+    handler: Rc<HirHandler>,
+    /// Cases that called `resume`, most recent last. Each is continued
+    /// once the handled block finishes.
+    resumed: Vec<Suspended>,
+}
+
+/// A handler case stopped at its `resume` call
+struct Suspended {
+    case: usize,
+    /// The case's parameters and the bindings it made before `resume`
+    scope: Scope,
+    /// Index of the statement that called `resume`
+    stmt: usize,
+}
 
 /// Tree-walking interpreter
 pub struct Interpreter {
@@ -21,8 +255,19 @@ pub struct Interpreter {
     structs: HashMap<String, HirStruct>,
     /// Enum definitions (by name)
     enums: HashMap<String, HirEnum>,
+    /// Effect handler definitions (by name)
+    handlers: HashMap<String, Rc<HirHandler>>,
+    /// Enclosing `handle` expressions, innermost last
+    handler_stack: Vec<HandlerFrame>,
     /// Output buffer for testing
     output: Vec<String>,
+    /// Integer overflow behavior
+    overflow_mode: OverflowMode,
+    /// Source of randomness for `sample`
+    rng: Rng,
+    /// Lengths bound to the const generic parameters of the running calls,
+    /// innermost last
+    const_args: Vec<HashMap<String, usize>>,
 }
 
 impl Interpreter {
@@ -33,10 +278,28 @@ impl Interpreter {
             functions: HashMap::new(),
             structs: HashMap::new(),
             enums: HashMap::new(),
+            handlers: HashMap::new(),
+            handler_stack: Vec::new(),
             output: Vec::new(),
+            overflow_mode: OverflowMode::default(),
+            rng: Rng::new(DEFAULT_SEED),
+            const_args: Vec::new(),
         }
     }
 
+    /// Set how integer arithmetic handles overflow
+    pub fn with_overflow_mode(mut self, mode: OverflowMode) -> Self {
+        self.overflow_mode = mode;
+        self
+    }
+
+    /// Seed the generator `sample` draws from. Runs with the same seed
+    /// produce the same draws.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
     /// Get captured output (for testing)
     pub fn get_output(&self) -> &[String] {
         &self.output
@@ -71,6 +334,16 @@ impl Interpreter {
                 HirItem::Enum(e) => {
                     self.enums.insert(e.name.clone(), e.clone());
                 }
+                HirItem::Handler(h) => {
+                    self.handlers.insert(h.name.clone(), Rc::new(h.clone()));
+                }
+                HirItem::Impl(i) => {
+                    for method in &i.methods {
+                        if let Some(symbol) = i.method_symbol(&method.name) {
+                            self.functions.insert(symbol, Rc::new(method.clone()));
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -103,17 +376,30 @@ impl Interpreter {
             Err(ControlFlow::Return(v)) => Ok(v),
             Err(ControlFlow::Break(_)) => Err(miette!("break outside loop")),
             Err(ControlFlow::Continue) => Err(miette!("continue outside loop")),
+            Err(ControlFlow::Error(message)) => Err(miette!("{}", message)),
+            Err(ControlFlow::Resume(_)) => Err(miette!("resume outside handler case")),
+            Err(ControlFlow::Abort { .. }) => {
+                Err(miette!("handler case finished outside its handle"))
+            }
         }
     }
 
     /// Evaluate a block
     fn eval_block(&mut self, block: &HirBlock) -> Result<Value, ControlFlow> {
         self.env.push_scope();
+        let result = self.eval_stmts(&block.stmts);
+        // Also popped when control leaves early, so the block's bindings
+        // can't shadow names after a `return`, `break` or `resume`
+        self.env.pop_scope();
+        result
+    }
 
+    /// Evaluate the statements of a block in the current scope
+    fn eval_stmts(&mut self, stmts: &[HirStmt]) -> Result<Value, ControlFlow> {
         let mut result = Value::Unit;
 
-        for (i, stmt) in block.stmts.iter().enumerate() {
-            let is_last = i == block.stmts.len() - 1;
+        for (i, stmt) in stmts.iter().enumerate() {
+            let is_last = i == stmts.len() - 1;
 
             match stmt {
                 HirStmt::Let { name, value, .. } => {
@@ -134,7 +420,6 @@ impl Interpreter {
             }
         }
 
-        self.env.pop_scope();
         Ok(result)
     }
 
@@ -196,21 +481,58 @@ impl Interpreter {
                 }
 
                 let rhs = self.eval_expr(right)?;
-                self.eval_binary(*op, lhs, rhs)
+                self.eval_binary(*op, lhs, rhs, &left.ty)
             }
 
+            HirExprKind::Unary {
+                op: HirUnaryOp::Ref | HirUnaryOp::RefMut,
+                expr: inner,
+            } => self.eval_ref(inner),
+
             HirExprKind::Unary { op, expr: inner } => {
                 let val = self.eval_expr(inner)?;
-                self.eval_unary(*op, val)
+                self.eval_unary(*op, val, &inner.ty)
             }
 
             HirExprKind::Call { func, args } => {
-                let callee = self.eval_expr(func)?;
                 let mut arg_values = Vec::new();
+                if let Some(name) = self.intrinsic_callee(func) {
+                    for arg in args {
+                        arg_values.push(self.eval_expr(arg)?);
+                    }
+                    return self.call_builtin(name, arg_values);
+                }
+
+                let callee = self.eval_expr(func)?;
                 for arg in args {
                     arg_values.push(self.eval_expr(arg)?);
                 }
-                self.eval_call(callee, arg_values)
+                let mut lens = HashMap::new();
+                if let Value::Function {
+                    func: callee_fn, ..
+                } = &callee
+                {
+                    let declared = callee_fn.ty.params.iter().map(|p| &p.ty);
+                    let declared = declared.chain([callee_fn.ty.return_type.as_ref()]);
+                    if let HirType::Fn {
+                        params,
+                        return_type,
+                    } = &func.ty
+                    {
+                        for (declared, actual) in
+                            declared.zip(params.iter().chain([&**return_type]))
+                        {
+                            self.bind_array_lens(declared, actual, &mut lens);
+                        }
+                    }
+                }
+                if lens.is_empty() {
+                    return self.eval_call(callee, arg_values);
+                }
+                self.const_args.push(lens);
+                let result = self.eval_call(callee, arg_values);
+                self.const_args.pop();
+                result
             }
 
             HirExprKind::If {
@@ -237,9 +559,7 @@ impl Interpreter {
                     Err(ControlFlow::Break(val)) => {
                         return Ok(val.unwrap_or(Value::Unit));
                     }
-                    Err(ControlFlow::Return(v)) => {
-                        return Err(ControlFlow::Return(v));
-                    }
+                    Err(cf) => return Err(cf),
                 }
             },
 
@@ -279,6 +599,23 @@ impl Interpreter {
                 Ok(Value::Array(Rc::new(RefCell::new(values))))
             }
 
+            HirExprKind::ArrayRepeat { value, len } => {
+                let n = match len {
+                    ArrayLen::Fixed(n) => *n,
+                    ArrayLen::Param(name) => self
+                        .const_args
+                        .last()
+                        .and_then(|lens| lens.get(name))
+                        .copied()
+                        .ok_or_else(|| {
+                            ControlFlow::Error(format!("array length `{}` is not known", name))
+                        })?,
+                };
+                let value = self.eval_expr(value)?;
+                let values = (0..n).map(|_| copy_value(&value)).collect();
+                Ok(Value::Array(Rc::new(RefCell::new(values))))
+            }
+
             HirExprKind::Struct { name, fields } => {
                 let mut field_values = HashMap::new();
                 for (field_name, field_expr) in fields {
@@ -299,11 +636,7 @@ impl Interpreter {
                 for field_expr in fields {
                     field_values.push(self.eval_expr(field_expr)?);
                 }
-                Ok(Value::Variant {
-                    enum_name: enum_name.clone(),
-                    variant_name: variant.clone(),
-                    fields: field_values,
-                })
+                Ok(Value::variant(enum_name, variant, field_values))
             }
 
             HirExprKind::Field { base, field } => {
@@ -339,28 +672,30 @@ impl Interpreter {
                 }
             }
 
-            HirExprKind::Index { base, index } => {
+            HirExprKind::Index { base, index, .. } => {
                 let base_val = self.eval_expr(base)?;
                 let idx_val = self.eval_expr(index)?;
 
-                let idx = idx_val
-                    .as_int()
-                    .ok_or_else(|| ControlFlow::Return(Value::Unit))?
-                    as usize;
+                let idx = index_int(&idx_val)?;
 
                 match base_val {
+                    Value::Vec(items) => {
+                        let items = items.borrow();
+                        checked_index(idx, items.len()).map(|i| items[i].clone())
+                    }
                     Value::Array(arr) => {
                         let arr = arr.borrow();
-                        arr.get(idx)
-                            .cloned()
-                            .ok_or_else(|| ControlFlow::Return(Value::Unit))
-                    }
-                    Value::String(s) => s
-                        .chars()
-                        .nth(idx)
-                        .map(|c| Value::String(c.to_string()))
-                        .ok_or_else(|| ControlFlow::Return(Value::Unit)),
-                    _ => Err(ControlFlow::Return(Value::Unit)),
+                        checked_index(idx, arr.len()).map(|i| arr[i].clone())
+                    }
+                    // Strings index by char, not byte
+                    Value::String(s) => {
+                        let i = checked_index(idx, s.chars().count())?;
+                        Ok(Value::String(s.chars().nth(i).unwrap().to_string()))
+                    }
+                    other => Err(ControlFlow::Error(format!(
+                        "cannot index into {}",
+                        describe_value(&other)
+                    ))),
                 }
             }
 
@@ -391,8 +726,9 @@ impl Interpreter {
                             for (name, value) in &bindings {
                                 self.env.define(name.clone(), value.clone());
                             }
-                            let guard_result = self.eval_expr(guard)?;
+                            let guard_result = self.eval_expr(guard);
                             self.env.pop_scope();
+                            let guard_result = guard_result?;
 
                             if !guard_result.is_truthy() {
                                 continue;
@@ -410,16 +746,34 @@ impl Interpreter {
                     }
                 }
 
-                // No match found - this should be a runtime error
-                Err(ControlFlow::Return(Value::Unit))
+                Err(ControlFlow::Error(format!(
+                    "non-exhaustive match: no arm matches `{}`",
+                    val
+                )))
+            }
+
+            HirExprKind::Cast {
+                expr: inner,
+                target,
+            } => {
+                let value = self.eval_expr(inner)?;
+                cast_value(&value, &inner.ty, target).ok_or_else(|| {
+                    ControlFlow::Error(format!("cannot cast {} to {}", value.type_name(), target))
+                })
             }
 
-            HirExprKind::Cast { expr: inner, .. } => {
-                // For now, just evaluate the inner expression
-                // Real casting would convert types
-                self.eval_expr(inner)
+            HirExprKind::Transmute {
+                expr: inner,
+                target,
+            } => {
+                let value = self.eval_expr(inner)?;
+                transmute_value(&value, &inner.ty, target).ok_or(ControlFlow::Return(Value::Unit))
             }
 
+            // Method calls dispatch on the runtime value, so the reference
+            // itself serves as the trait object
+            HirExprKind::DynCoerce { expr: inner, .. } => self.eval_expr(inner),
+
             HirExprKind::Closure { params, body } => {
                 // Capture current environment
                 let captures = self.env.capture_all();
@@ -437,6 +791,10 @@ impl Interpreter {
                         stmts: vec![HirStmt::Expr(body.as_ref().clone())],
                         ty: body.ty.clone(),
                     },
+                    is_pure: false,
+                    is_async: false,
+                    inline: InlineHint::Auto,
+                    is_kernel: false,
                 };
 
                 Ok(Value::Function {
@@ -456,10 +814,69 @@ impl Interpreter {
                     arg_values.push(self.eval_expr(arg)?);
                 }
 
-                // Handle built-in methods
+                // Methods from impl blocks take the receiver as `self`. A
+                // receiver of generic type dispatches on its runtime type.
+                let func = receiver
+                    .ty
+                    .method_symbol(method)
+                    .and_then(|symbol| self.functions.get(&symbol).cloned())
+                    .or_else(|| {
+                        let symbol = format!("{}::{}", recv.nominal_name()?, method);
+                        self.functions.get(&symbol).cloned()
+                    });
+                if let Some(func) = func {
+                    // `&self` and `&mut self` borrow the receiver. Values
+                    // are copied on read, so a `&mut self` method works on
+                    // a copy that is stored back into the receiver after
+                    // the call.
+                    let borrow = match func.ty.params.first().map(|p| &p.ty) {
+                        Some(HirType::Ref { mutable, .. }) => Some(*mutable),
+                        _ => None,
+                    };
+                    let mut borrowed = None;
+                    if borrow.is_some() && !matches!(recv, Value::Ref(_)) {
+                        let cell = Rc::new(RefCell::new(recv));
+                        arg_values[0] = Value::Ref(cell.clone());
+                        borrowed = Some(cell);
+                    }
+                    let result = self.eval_call(
+                        Value::Function {
+                            func,
+                            captures: HashMap::new(),
+                        },
+                        arg_values,
+                    );
+                    if let (Some(true), Some(cell)) = (borrow, borrowed) {
+                        let updated = cell.borrow().clone();
+                        self.assign_target(receiver, updated)?;
+                    }
+                    return result;
+                }
+
+                // Handle built-in methods, seeing through references
+                let recv = match recv {
+                    Value::Ref(r) => r.borrow().clone(),
+                    other => other,
+                };
                 match (recv, method.as_str()) {
                     (Value::Array(arr), "len") => Ok(Value::Int(arr.borrow().len() as i64)),
-                    (Value::String(s), "len") => Ok(Value::Int(s.len() as i64)),
+                    (Value::String(s), "len") => Ok(Value::Int(s.chars().count() as i64)),
+                    (Value::String(s), "chars") => Ok(Value::Vec(Rc::new(RefCell::new(
+                        s.chars().map(|c| Value::String(c.to_string())).collect(),
+                    )))),
+                    (Value::String(s), "split") => match arg_values.get(1) {
+                        Some(Value::String(sep)) => Ok(Value::Vec(Rc::new(RefCell::new(
+                            s.split(sep.as_str())
+                                .map(|part| Value::String(part.to_string()))
+                                .collect(),
+                        )))),
+                        _ => Err(ControlFlow::Error(
+                            "`split` expects a string separator".to_string(),
+                        )),
+                    },
+                    (Value::String(s), "to_upper") => Ok(Value::String(s.to_uppercase())),
+                    (Value::String(s), "to_lower") => Ok(Value::String(s.to_lowercase())),
+                    (Value::String(s), "trim") => Ok(Value::String(s.trim().to_string())),
                     (Value::Array(arr), "push") => {
                         if let Some(val) = arg_values.get(1) {
                             arr.borrow_mut().push(val.clone());
@@ -467,11 +884,21 @@ impl Interpreter {
                         Ok(Value::Unit)
                     }
                     (Value::Array(arr), "pop") => Ok(arr.borrow_mut().pop().unwrap_or(Value::None)),
+                    (Value::Vec(items), "len") => Ok(Value::Int(items.borrow().len() as i64)),
+                    (Value::Vec(items), "push") => {
+                        let value = arg_values.into_iter().nth(1).unwrap_or(Value::Unit);
+                        items.borrow_mut().push(value);
+                        Ok(Value::Unit)
+                    }
+                    (Value::Vec(items), "pop") => Ok(items
+                        .borrow_mut()
+                        .pop()
+                        .map_or(Value::None, |v| Value::Some(Box::new(v)))),
                     _ => {
                         // Try to find a function with method name
                         if let Some(func) = self.functions.get(method).cloned() {
                             self.call_function(&func, arg_values)
-                                .map_err(|e| ControlFlow::Return(Value::Unit))
+                                .map_err(|e| ControlFlow::Error(e.to_string()))
                         } else {
                             Err(ControlFlow::Return(Value::Unit))
                         }
@@ -479,19 +906,340 @@ impl Interpreter {
                 }
             }
 
-            // Effect operations - not fully implemented
-            HirExprKind::Perform { .. } | HirExprKind::Handle { .. } | HirExprKind::Sample(_) => {
+            HirExprKind::Await(future) => {
+                let future = self.eval_expr(future)?;
+                self.block_on(future)
+            }
+
+            HirExprKind::Perform { effect, op, args } => {
+                let args = args
+                    .iter()
+                    .map(|a| self.eval_expr(a))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.perform(effect, op, args)
+            }
+
+            HirExprKind::Handle { expr, handler } => self.eval_handle(expr, handler),
+
+            // Probabilistic operations - not fully implemented
+            HirExprKind::Sample(dist) => match self.eval_expr(dist)? {
+                Value::Distribution(dist) => Ok(match self.rng.sample(&dist) {
+                    Sample::Float(x) => Value::Float(x),
+                    Sample::Bool(b) => Value::Bool(b),
+                }),
+                other => Err(ControlFlow::Error(format!(
+                    "cannot sample from a value of type {}",
+                    other.type_name()
+                ))),
+            },
+        }
+    }
+
+    /// Run a function body with its captures and arguments bound
+    fn run_body(
+        &mut self,
+        func: &HirFn,
+        captures: HashMap<String, Value>,
+        args: Vec<Value>,
+    ) -> Result<Value, ControlFlow> {
+        // Set up environment with captures
+        self.env.push_scope();
+        for (name, value) in captures {
+            self.env.define(name, value);
+        }
+
+        // Bind parameters
+        for (param, arg) in func.ty.params.iter().zip(args) {
+            self.env.define(param.name.clone(), arg);
+        }
+
+        // Execute body
+        let result = self.eval_block(&func.body);
+
+        self.env.pop_scope();
+
+        match result {
+            Ok(v) => Ok(v),
+            Err(ControlFlow::Return(v)) => Ok(v),
+            Err(cf) => Err(cf),
+        }
+    }
+
+    /// Bind the const generic lengths in `declared`, a type from a callee's
+    /// signature, to the lengths at the same places in `actual`, its type at
+    /// the call. A length that is itself a parameter of the caller takes the
+    /// caller's binding.
+    fn bind_array_lens(
+        &self,
+        declared: &HirType,
+        actual: &HirType,
+        lens: &mut HashMap<String, usize>,
+    ) {
+        match (declared, actual) {
+            (
+                HirType::Array {
+                    element,
+                    size: Some(ArrayLen::Param(name)),
+                },
+                HirType::Array {
+                    element: actual_element,
+                    size: Some(size),
+                },
+            ) => {
+                let len = match size {
+                    ArrayLen::Fixed(n) => Some(*n),
+                    ArrayLen::Param(outer) => self
+                        .const_args
+                        .last()
+                        .and_then(|outer_lens| outer_lens.get(outer))
+                        .copied(),
+                };
+                if let Some(len) = len {
+                    lens.insert(name.clone(), len);
+                }
+                self.bind_array_lens(element, actual_element, lens);
+            }
+            (HirType::Array { element: d, .. }, HirType::Array { element: a, .. })
+            | (HirType::Ref { inner: d, .. }, HirType::Ref { inner: a, .. }) => {
+                self.bind_array_lens(d, a, lens);
+            }
+            (HirType::Tuple(ds), HirType::Tuple(as_))
+            | (HirType::Named { args: ds, .. }, HirType::Named { args: as_, .. }) => {
+                for (d, a) in ds.iter().zip(as_) {
+                    self.bind_array_lens(d, a, lens);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Drive a future to completion on the current thread. Awaits in its
+    /// body block on their own futures in turn, so nested awaits finish
+    /// innermost first before the outer future resumes.
+    fn block_on(&mut self, value: Value) -> Result<Value, ControlFlow> {
+        match value {
+            Value::Future {
+                func,
+                captures,
+                args,
+            } => self.run_body(&func, captures, args),
+            // Already complete
+            other => Ok(other),
+        }
+    }
+
+    /// Perform an effect operation under the innermost handler for it.
+    ///
+    /// The matching case runs at the `perform` site, outside its own
+    /// handler. If it calls `resume(v)`, the operation returns `v` and the
+    /// case is suspended at that statement; once the block finishes, the
+    /// case continues from there with `resume` yielding the rest of the
+    /// block's answer. A case that never resumes aborts the block and its
+    /// value becomes the result of the `handle`.
+    ///
+    /// Handlers that don't handle `op` are skipped, so the operation is
+    /// forwarded to an outer one. A case that performs an operation itself
+    /// forwards it the same way, to the handlers outside its own. Built-in
+    /// `IO` operations that no handler takes go to the default console
+    /// handler.
+    fn perform(&mut self, effect: &str, op: &str, args: Vec<Value>) -> Result<Value, ControlFlow> {
+        let Some(depth) = self.handler_stack.iter().rposition(|frame| {
+            frame.handler.effect == effect && frame.handler.cases.iter().any(|c| c.op_name == op)
+        }) else {
+            if effect == "IO" {
+                return self.perform_io(op, args);
+            }
+            return Err(ControlFlow::Error(format!(
+                "unhandled effect operation `{}.{}`",
+                effect, op
+            )));
+        };
+        let handler = self.handler_stack[depth].handler.clone();
+        let case = handler
+            .cases
+            .iter()
+            .position(|c| c.op_name == op)
+            .expect("handler has a case for the operation");
+
+        let inner = self.handler_stack.split_off(depth);
+        self.env.push_scope();
+        for (param, arg) in handler.cases[case].params.iter().zip(args) {
+            self.env.define(param.clone(), arg);
+        }
+        self.env.define("resume".to_string(), Value::Resume(None));
+        let stmts = case_stmts(&handler.cases[case].body);
+        let outcome = self.eval_case_stmts(&stmts, 0, Value::Unit);
+        let scope = self.env.take_scope();
+        self.handler_stack.extend(inner);
+
+        match outcome {
+            Err((stmt, ControlFlow::Resume(value))) => {
+                let scope = scope
+                    .ok_or_else(|| ControlFlow::Error("handler case lost its scope".to_string()))?;
+                self.handler_stack[depth]
+                    .resumed
+                    .push(Suspended { case, scope, stmt });
+                Ok(value)
+            }
+            Ok(value) => Err(ControlFlow::Abort { depth, value }),
+            Err((_, cf)) => Err(cf),
+        }
+    }
+
+    /// Default handler for the built-in `IO` effect, talking to the
+    /// console and file system
+    fn perform_io(&mut self, op: &str, args: Vec<Value>) -> Result<Value, ControlFlow> {
+        let io_error = |e: std::io::Error| ControlFlow::Error(format!("`IO.{}` failed: {}", op, e));
+        let mut args = args.into_iter().map(|v| match v {
+            Value::String(s) => s,
+            other => other.to_string(),
+        });
+        match op {
+            "print" => {
+                let text = args.next().unwrap_or_default();
+                print!("{}", text);
+                std::io::Write::flush(&mut std::io::stdout()).map_err(io_error)?;
+                self.output.push(text);
+                Ok(Value::Unit)
+            }
+            "read_line" => {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line).map_err(io_error)?;
+                let len = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(len);
+                Ok(Value::String(line))
+            }
+            "read_file" => {
+                let path = args.next().unwrap_or_default();
+                std::fs::read_to_string(path)
+                    .map(Value::String)
+                    .map_err(io_error)
+            }
+            "write_file" => {
+                let path = args.next().unwrap_or_default();
+                let contents = args.next().unwrap_or_default();
+                std::fs::write(path, contents).map_err(io_error)?;
                 Ok(Value::Unit)
             }
+            _ => Err(ControlFlow::Error(format!(
+                "unhandled effect operation `IO.{}`",
+                op
+            ))),
         }
     }
 
+    /// Evaluate `handle expr with handler`
+    fn eval_handle(&mut self, expr: &HirExpr, handler: &str) -> Result<Value, ControlFlow> {
+        let handler = self
+            .handlers
+            .get(handler)
+            .cloned()
+            .ok_or_else(|| ControlFlow::Error(format!("unknown handler `{}`", handler)))?;
+        let depth = self.handler_stack.len();
+        self.handler_stack.push(HandlerFrame {
+            handler: handler.clone(),
+            resumed: Vec::new(),
+        });
+        let result = self.eval_expr(expr);
+        let frame = self
+            .handler_stack
+            .pop()
+            .ok_or_else(|| ControlFlow::Error("handler stack is empty".to_string()))?;
+
+        let mut answer = match result {
+            Ok(value) => match &handler.return_clause {
+                Some(ret) => {
+                    self.env.push_scope();
+                    self.env.define(ret.param.clone(), value);
+                    let result = self.eval_expr(&ret.body);
+                    self.env.pop_scope();
+                    result?
+                }
+                None => value,
+            },
+            Err(ControlFlow::Abort { depth: d, value }) if d == depth => value,
+            Err(cf) => return Err(cf),
+        };
+
+        // Finish the resumed cases, innermost continuation first
+        for suspended in frame.resumed.into_iter().rev() {
+            let body = &handler.cases[suspended.case].body;
+            answer = self.continue_handler_case(body, suspended, answer)?;
+        }
+        Ok(answer)
+    }
+
+    /// Continue a suspended handler case from the statement that called
+    /// `resume`, which now yields `answer`. A bare `resume(..)`, or one
+    /// bound by `let` or assigned, takes the answer directly; a statement
+    /// with `resume` nested deeper is evaluated again as a whole.
+    fn continue_handler_case(
+        &mut self,
+        body: &HirExpr,
+        suspended: Suspended,
+        answer: Value,
+    ) -> Result<Value, ControlFlow> {
+        let stmts = case_stmts(body);
+        let Some(resumed) = stmts.get(suspended.stmt) else {
+            return Ok(answer);
+        };
+        self.env.restore_scope(suspended.scope);
+        self.env.define(
+            "resume".to_string(),
+            Value::Resume(Some(Box::new(answer.clone()))),
+        );
+        let result = match resumed {
+            HirStmt::Expr(expr) if is_resume_call(expr) => Ok(answer),
+            HirStmt::Let {
+                name,
+                value: Some(expr),
+                ..
+            } if is_resume_call(expr) => {
+                self.env.define(name.clone(), answer);
+                Ok(Value::Unit)
+            }
+            HirStmt::Assign { target, value } if is_resume_call(value) => {
+                self.assign_target(target, answer).map(|()| Value::Unit)
+            }
+            stmt => self.eval_stmts(std::slice::from_ref(stmt)),
+        }
+        .and_then(|result| {
+            self.eval_case_stmts(&stmts, suspended.stmt + 1, result)
+                .map_err(|(_, cf)| cf)
+        });
+        self.env.pop_scope();
+        result
+    }
+
+    /// Evaluate handler case statements from `start` in the current scope.
+    /// The result is the value of the last expression statement, starting
+    /// from `result`; on early exit the index of the statement that left
+    /// is returned with the control flow.
+    fn eval_case_stmts(
+        &mut self,
+        stmts: &[HirStmt],
+        start: usize,
+        mut result: Value,
+    ) -> Result<Value, (usize, ControlFlow)> {
+        for (i, stmt) in stmts.iter().enumerate().skip(start) {
+            let value = self
+                .eval_stmts(std::slice::from_ref(stmt))
+                .map_err(|cf| (i, cf))?;
+            if matches!(stmt, HirStmt::Expr(_)) {
+                result = value;
+            }
+        }
+        Ok(result)
+    }
+
     /// Evaluate a literal
     fn eval_literal(&self, lit: &HirLiteral) -> Value {
         match lit {
             HirLiteral::Unit => Value::Unit,
             HirLiteral::Bool(b) => Value::Bool(*b),
             HirLiteral::Int(n) => Value::Int(*n),
+            // Integers are 64-bit here; keep the low bits, as for `u64`
+            HirLiteral::U128(n) => Value::Int(*n as i64),
             HirLiteral::Float(f) => Value::Float(*f),
             HirLiteral::Char(c) => Value::String(c.to_string()),
             HirLiteral::String(s) => Value::String(s.clone()),
@@ -499,10 +1247,16 @@ impl Interpreter {
     }
 
     /// Evaluate a binary operation
-    fn eval_binary(&self, op: HirBinaryOp, lhs: Value, rhs: Value) -> Result<Value, ControlFlow> {
+    fn eval_binary(
+        &self,
+        op: HirBinaryOp,
+        lhs: Value,
+        rhs: Value,
+        ty: &HirType,
+    ) -> Result<Value, ControlFlow> {
         match op {
             HirBinaryOp::Add => match (lhs, rhs) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a + b)),
+                (Value::Int(a), Value::Int(b)) => self.int_arith(op, a, b, ty),
                 (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
                 (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 + b)),
                 (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a + b as f64)),
@@ -510,14 +1264,14 @@ impl Interpreter {
                 _ => Err(ControlFlow::Return(Value::Unit)),
             },
             HirBinaryOp::Sub => match (lhs, rhs) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a - b)),
+                (Value::Int(a), Value::Int(b)) => self.int_arith(op, a, b, ty),
                 (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
                 (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 - b)),
                 (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a - b as f64)),
                 _ => Err(ControlFlow::Return(Value::Unit)),
             },
             HirBinaryOp::Mul => match (lhs, rhs) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a * b)),
+                (Value::Int(a), Value::Int(b)) => self.int_arith(op, a, b, ty),
                 (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
                 (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 * b)),
                 (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a * b as f64)),
@@ -528,7 +1282,7 @@ impl Interpreter {
                     if b == 0 {
                         Err(ControlFlow::Return(Value::Unit)) // Division by zero
                     } else {
-                        Ok(Value::Int(a / b))
+                        self.int_arith(op, a, b, ty)
                     }
                 }
                 (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a / b)),
@@ -541,38 +1295,27 @@ impl Interpreter {
                     if b == 0 {
                         Err(ControlFlow::Return(Value::Unit))
                     } else {
-                        Ok(Value::Int(a % b))
+                        self.int_arith(op, a, b, ty)
                     }
                 }
                 (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a % b)),
                 _ => Err(ControlFlow::Return(Value::Unit)),
             },
-            HirBinaryOp::Eq => Ok(Value::Bool(lhs == rhs)),
-            HirBinaryOp::Ne => Ok(Value::Bool(lhs != rhs)),
-            HirBinaryOp::Lt => match (lhs, rhs) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a < b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Bool(a < b)),
-                (Value::String(a), Value::String(b)) => Ok(Value::Bool(a < b)),
-                _ => Err(ControlFlow::Return(Value::Unit)),
-            },
-            HirBinaryOp::Le => match (lhs, rhs) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a <= b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Bool(a <= b)),
-                (Value::String(a), Value::String(b)) => Ok(Value::Bool(a <= b)),
-                _ => Err(ControlFlow::Return(Value::Unit)),
-            },
-            HirBinaryOp::Gt => match (lhs, rhs) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a > b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Bool(a > b)),
-                (Value::String(a), Value::String(b)) => Ok(Value::Bool(a > b)),
-                _ => Err(ControlFlow::Return(Value::Unit)),
-            },
-            HirBinaryOp::Ge => match (lhs, rhs) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a >= b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Bool(a >= b)),
-                (Value::String(a), Value::String(b)) => Ok(Value::Bool(a >= b)),
-                _ => Err(ControlFlow::Return(Value::Unit)),
-            },
+            HirBinaryOp::Eq => Ok(Value::Bool(
+                self.compare_values(&lhs, &rhs, false)? == Some(Ordering::Equal),
+            )),
+            HirBinaryOp::Ne => Ok(Value::Bool(
+                self.compare_values(&lhs, &rhs, false)? != Some(Ordering::Equal),
+            )),
+            HirBinaryOp::Lt | HirBinaryOp::Le | HirBinaryOp::Gt | HirBinaryOp::Ge => {
+                let ordering = self.compare_values(&lhs, &rhs, true)?;
+                Ok(Value::Bool(ordering.is_some_and(|o| match op {
+                    HirBinaryOp::Lt => o.is_lt(),
+                    HirBinaryOp::Le => o.is_le(),
+                    HirBinaryOp::Gt => o.is_gt(),
+                    _ => o.is_ge(),
+                })))
+            }
             HirBinaryOp::And => Ok(Value::Bool(lhs.is_truthy() && rhs.is_truthy())),
             HirBinaryOp::Or => Ok(Value::Bool(lhs.is_truthy() || rhs.is_truthy())),
             HirBinaryOp::BitAnd => match (lhs, rhs) {
@@ -598,11 +1341,197 @@ impl Interpreter {
         }
     }
 
+    /// Compare two values structurally. Tuples, arrays and struct fields
+    /// compare lexicographically in declaration order, and enum variants by
+    /// declaration order before their fields. `None` means unordered, as
+    /// with NaN. Values of different shapes, or that can only be tested
+    /// for equality when `ordered` is set, are a runtime error.
+    fn compare_values(
+        &self,
+        lhs: &Value,
+        rhs: &Value,
+        ordered: bool,
+    ) -> Result<Option<Ordering>, ControlFlow> {
+        let mismatch = || {
+            ControlFlow::Error(format!(
+                "cannot compare {} with {}",
+                describe_value(lhs),
+                describe_value(rhs)
+            ))
+        };
+        let ordering = match (lhs, rhs) {
+            (Value::Ref(a), _) => return self.compare_values(&a.borrow(), rhs, ordered),
+            (_, Value::Ref(b)) => return self.compare_values(lhs, &b.borrow(), ordered),
+            (Value::Unit, Value::Unit) | (Value::None, Value::None) => Some(Ordering::Equal),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            // `None < Some(_)` and `Ok(_) < Err(_)`, as declared
+            (Value::None, Value::Some(_)) => Some(Ordering::Less),
+            (Value::Some(_), Value::None) => Some(Ordering::Greater),
+            (Value::Ok(_), Value::Err(_)) => Some(Ordering::Less),
+            (Value::Err(_), Value::Ok(_)) => Some(Ordering::Greater),
+            (Value::Some(a), Value::Some(b))
+            | (Value::Ok(a), Value::Ok(b))
+            | (Value::Err(a), Value::Err(b)) => self.compare_values(a, b, ordered)?,
+            (Value::Tuple(a), Value::Tuple(b)) if a.len() == b.len() => {
+                self.compare_sequences(a, b, ordered)?
+            }
+            (Value::Array(a), Value::Array(b)) | (Value::Vec(a), Value::Vec(b)) => {
+                self.compare_sequences(&a.borrow(), &b.borrow(), ordered)?
+            }
+            (
+                Value::Struct {
+                    name: n1,
+                    fields: f1,
+                },
+                Value::Struct {
+                    name: n2,
+                    fields: f2,
+                },
+            ) if n1 == n2 => {
+                let names: Vec<&String> = match self.structs.get(n1) {
+                    Some(def) => def.fields.iter().map(|f| &f.name).collect(),
+                    None => {
+                        let mut names: Vec<_> = f1.keys().collect();
+                        names.sort();
+                        names
+                    }
+                };
+                let field = |fields: &HashMap<String, Value>, name: &String| {
+                    fields.get(name).cloned().ok_or_else(mismatch)
+                };
+                let a = names
+                    .iter()
+                    .map(|n| field(f1, n))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let b = names
+                    .iter()
+                    .map(|n| field(f2, n))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.compare_sequences(&a, &b, ordered)?
+            }
+            (
+                Value::Variant {
+                    enum_name: e1,
+                    variant_name: v1,
+                    fields: f1,
+                },
+                Value::Variant {
+                    enum_name: e2,
+                    variant_name: v2,
+                    fields: f2,
+                },
+            ) if e1 == e2 => {
+                if v1 == v2 {
+                    self.compare_sequences(f1, f2, ordered)?
+                } else {
+                    let index = |variant: &String| {
+                        self.enums
+                            .get(e1)
+                            .and_then(|e| e.variants.iter().position(|v| &v.name == variant))
+                    };
+                    match (index(v1), index(v2)) {
+                        (Some(i), Some(j)) => Some(i.cmp(&j)),
+                        _ => Some(v1.cmp(v2)),
+                    }
+                }
+            }
+            // These can only be tested for equality
+            (Value::Map(_), Value::Map(_))
+            | (Value::Set(_), Value::Set(_))
+            | (Value::Distribution(_), Value::Distribution(_))
+                if !ordered =>
+            {
+                (lhs == rhs).then_some(Ordering::Equal)
+            }
+            _ => return Err(mismatch()),
+        };
+        Ok(ordering)
+    }
+
+    /// Lexicographic comparison of two sequences of values
+    fn compare_sequences(
+        &self,
+        a: &[Value],
+        b: &[Value],
+        ordered: bool,
+    ) -> Result<Option<Ordering>, ControlFlow> {
+        for (x, y) in a.iter().zip(b) {
+            match self.compare_values(x, y, ordered)? {
+                Some(Ordering::Equal) => {}
+                other => return Ok(other),
+            }
+        }
+        Ok(Some(a.len().cmp(&b.len())))
+    }
+
+    /// Integer `+`, `-`, `*`, `/` or `%` on operands of type `ty` under the
+    /// interpreter's overflow mode. The divisor of `/` and `%` is already
+    /// known to be nonzero.
+    fn int_arith(
+        &self,
+        op: HirBinaryOp,
+        a: i64,
+        b: i64,
+        ty: &HirType,
+    ) -> Result<Value, ControlFlow> {
+        let ((checked, wrapping), symbol): (IntOp, _) = match op {
+            HirBinaryOp::Add => ((i64::checked_add, i64::wrapping_add), "+"),
+            HirBinaryOp::Sub => ((i64::checked_sub, i64::wrapping_sub), "-"),
+            HirBinaryOp::Mul => ((i64::checked_mul, i64::wrapping_mul), "*"),
+            HirBinaryOp::Div => ((i64::checked_div, i64::wrapping_div), "/"),
+            HirBinaryOp::Rem => ((i64::checked_rem, i64::wrapping_rem), "%"),
+            _ => unreachable!("int_arith only handles arithmetic operators"),
+        };
+        let result = match self.overflow_mode {
+            OverflowMode::Wrapping => Some(wrapping(a, b)),
+            OverflowMode::Checked => checked(a, b),
+        };
+        self.fit_int(result, ty, || format!("{} {} {}", a, symbol, b))
+    }
+
+    /// The result of an integer operation on type `ty`, or `None` if it
+    /// overflowed `i64`. Checked mode rejects results outside the range of
+    /// `ty`, and wrapping mode wraps them to its width. Types wider than
+    /// 64 bits get `i64` arithmetic.
+    fn fit_int(
+        &self,
+        result: Option<i64>,
+        ty: &HirType,
+        expr: impl FnOnce() -> String,
+    ) -> Result<Value, ControlFlow> {
+        let (ty, (min, max)) = match int_range(ty) {
+            Some(range) => (ty, range),
+            None => (&HirType::I64, (i64::MIN, i64::MAX)),
+        };
+        match (self.overflow_mode, result) {
+            (OverflowMode::Wrapping, Some(n)) => {
+                Ok(transmute_value(&Value::Int(n), &HirType::I64, ty).unwrap_or(Value::Int(n)))
+            }
+            (OverflowMode::Checked, Some(n)) if (min..=max).contains(&n) => Ok(Value::Int(n)),
+            _ => Err(ControlFlow::Error(format!(
+                "integer overflow: `{}` does not fit in {}",
+                expr(),
+                ty
+            ))),
+        }
+    }
+
     /// Evaluate a unary operation
-    fn eval_unary(&self, op: HirUnaryOp, val: Value) -> Result<Value, ControlFlow> {
+    fn eval_unary(&self, op: HirUnaryOp, val: Value, ty: &HirType) -> Result<Value, ControlFlow> {
         match op {
             HirUnaryOp::Neg => match val {
-                Value::Int(n) => Ok(Value::Int(-n)),
+                Value::Int(n) => {
+                    let result = match self.overflow_mode {
+                        OverflowMode::Wrapping => Some(n.wrapping_neg()),
+                        OverflowMode::Checked => n.checked_neg(),
+                    };
+                    self.fit_int(result, ty, || format!("-({})", n))
+                }
                 Value::Float(f) => Ok(Value::Float(-f)),
                 _ => Err(ControlFlow::Return(Value::Unit)),
             },
@@ -619,32 +1548,48 @@ impl Interpreter {
         }
     }
 
-    /// Evaluate a function call
-    fn eval_call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, ControlFlow> {
-        match callee {
-            Value::Function { func, captures } => {
-                // Set up environment with captures
-                self.env.push_scope();
-                for (name, value) in captures {
-                    self.env.define(name, value);
-                }
-
-                // Bind parameters
-                for (param, arg) in func.ty.params.iter().zip(args.into_iter()) {
-                    self.env.define(param.name.clone(), arg);
+    /// Take a reference to `place`. A reference to a variable shares its
+    /// cell, so writes through either are visible to both; `&*r` is `r`
+    /// itself. Any other value is copied into a fresh cell.
+    fn eval_ref(&mut self, place: &HirExpr) -> Result<Value, ControlFlow> {
+        match &place.kind {
+            HirExprKind::Local(name) => {
+                if let Some(cell) = self.env.cell(name) {
+                    return Ok(Value::Ref(cell));
                 }
-
-                // Execute body
-                let result = self.eval_block(&func.body);
-
-                self.env.pop_scope();
-
-                match result {
-                    Ok(v) => Ok(v),
-                    Err(ControlFlow::Return(v)) => Ok(v),
-                    Err(cf) => Err(cf),
+            }
+            HirExprKind::Deref(inner)
+            | HirExprKind::Unary {
+                op: HirUnaryOp::Deref,
+                expr: inner,
+            } => {
+                let val = self.eval_expr(inner)?;
+                if matches!(val, Value::Ref(_)) {
+                    return Ok(val);
                 }
             }
+            _ => {}
+        }
+        let val = self.eval_expr(place)?;
+        Ok(Value::Ref(Rc::new(RefCell::new(val))))
+    }
+
+    /// Evaluate a function call
+    fn eval_call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, ControlFlow> {
+        match callee {
+            // Async calls are suspended until awaited
+            Value::Function { func, captures } if func.is_async => Ok(Value::Future {
+                func,
+                captures,
+                args,
+            }),
+            Value::Function { func, captures } => self.run_body(&func, captures, args),
+            Value::Resume(answer) => match answer {
+                Some(answer) => Ok(*answer),
+                None => Err(ControlFlow::Resume(
+                    args.into_iter().next().unwrap_or(Value::Unit),
+                )),
+            },
             _ => {
                 // Check if it's a builtin by looking at the callee name
                 // For now, handle common cases
@@ -653,6 +1598,48 @@ impl Interpreter {
         }
     }
 
+    /// Resolve a callee to an intrinsic name unless a user definition shadows it
+    fn intrinsic_callee<'a>(&self, func: &'a HirExpr) -> Option<&'a str> {
+        match &func.kind {
+            HirExprKind::Local(name) | HirExprKind::Global(name)
+                if is_intrinsic(name)
+                    && self.env.get(name).is_none()
+                    && !self.functions.contains_key(name) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Render intrinsic arguments, joined with spaces. A leading string is
+    /// a format template: its escapes are always processed, and if it has
+    /// `{}` placeholders it takes exactly one argument per placeholder.
+    fn render_args(args: &[Value]) -> Result<String, ControlFlow> {
+        let (head, rest) = match args.split_first() {
+            Some((Value::String(template), rest)) => {
+                let (text, placeholders) = format_values(template, rest);
+                if placeholders == 0 {
+                    (Some(text), rest)
+                } else if placeholders == rest.len() {
+                    return Ok(text);
+                } else {
+                    return Err(ControlFlow::Error(format!(
+                        "format string has {} placeholder(s) but {} argument(s) were given",
+                        placeholders,
+                        rest.len()
+                    )));
+                }
+            }
+            _ => (None, args),
+        };
+        Ok(head
+            .into_iter()
+            .chain(rest.iter().map(|v| v.to_string()))
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
     /// Try calling a builtin function by examining arguments
     fn call_builtin_by_args(&mut self, args: &[Value]) -> Result<Value, ControlFlow> {
         // Default: return unit
@@ -663,19 +1650,77 @@ impl Interpreter {
     pub fn call_builtin(&mut self, name: &str, args: Vec<Value>) -> Result<Value, ControlFlow> {
         match name {
             "print" => {
-                let output: Vec<String> = args.iter().map(|v| format!("{}", v)).collect();
-                let line = output.join(" ");
+                let line = Self::render_args(&args)?;
                 print!("{}", line);
                 self.output.push(line);
                 Ok(Value::Unit)
             }
             "println" => {
-                let output: Vec<String> = args.iter().map(|v| format!("{}", v)).collect();
-                let line = output.join(" ");
+                let line = Self::render_args(&args)?;
                 println!("{}", line);
                 self.output.push(line);
                 Ok(Value::Unit)
             }
+            "format" => Self::render_args(&args).map(Value::String),
+            "likely" | "unlikely" => Ok(args.into_iter().next().unwrap_or(Value::Unit)),
+            "Normal" | "Uniform" | "Bernoulli" => {
+                let params = args
+                    .iter()
+                    .map(Value::as_float)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        ControlFlow::Error(format!("`{}` expects float parameters", name))
+                    })?;
+                Distribution::new(name, &params)
+                    .map(Value::Distribution)
+                    .map_err(ControlFlow::Error)
+            }
+            "parse_int" => Ok(match args.first() {
+                Some(Value::String(s)) => s
+                    .parse::<i64>()
+                    .map_or(Value::None, |n| Value::Some(Box::new(Value::Int(n)))),
+                _ => Value::None,
+            }),
+            "parse_float" => Ok(match args.first() {
+                Some(Value::String(s)) => s
+                    .parse::<f64>()
+                    .map_or(Value::None, |f| Value::Some(Box::new(Value::Float(f)))),
+                _ => Value::None,
+            }),
+            "to_string" => Ok(Value::String(
+                args.first().map(|v| v.to_string()).unwrap_or_default(),
+            )),
+            "hash" => Ok(Value::Int(
+                args.first().map_or(0, |v| v.hash_value() as i64),
+            )),
+            "vec" => Ok(Value::Vec(Rc::new(RefCell::new(args)))),
+            "Vec::new" => Ok(Value::Vec(Rc::new(RefCell::new(Vec::new())))),
+            "map_new" => Ok(Value::Map(Rc::new(RefCell::new(ValueMap::new())))),
+            "set_new" => Ok(Value::Set(Rc::new(RefCell::new(ValueMap::new())))),
+            "map_insert" | "set_insert" => {
+                let mut args = args.into_iter();
+                match (args.next(), args.next()) {
+                    (Some(Value::Map(m) | Value::Set(m)), Some(key)) => {
+                        m.borrow_mut()
+                            .insert(key, args.next().unwrap_or(Value::Unit));
+                    }
+                    _ => return Err(collection_args_error(name)),
+                }
+                Ok(Value::Unit)
+            }
+            "map_get" => match (args.first(), args.get(1)) {
+                (Some(Value::Map(m)), Some(key)) => Ok(m
+                    .borrow()
+                    .get(key)
+                    .map_or(Value::None, |v| Value::Some(Box::new(v.clone())))),
+                _ => Err(collection_args_error(name)),
+            },
+            "map_contains" | "set_contains" => match (args.first(), args.get(1)) {
+                (Some(Value::Map(m) | Value::Set(m)), Some(key)) => {
+                    Ok(Value::Bool(m.borrow().contains_key(key)))
+                }
+                _ => Err(collection_args_error(name)),
+            },
             "assert" => {
                 if let Some(val) = args.first() {
                     if !val.is_truthy() {
@@ -695,7 +1740,9 @@ impl Interpreter {
             "len" => {
                 if let Some(val) = args.first() {
                     match val {
-                        Value::Array(arr) => Ok(Value::Int(arr.borrow().len() as i64)),
+                        Value::Array(arr) | Value::Vec(arr) => {
+                            Ok(Value::Int(arr.borrow().len() as i64))
+                        }
                         Value::String(s) => Ok(Value::Int(s.len() as i64)),
                         Value::Tuple(t) => Ok(Value::Int(t.len() as i64)),
                         _ => Ok(Value::Int(0)),
@@ -737,7 +1784,7 @@ impl Interpreter {
                 // Try to find function by name
                 if let Some(func) = self.functions.get(name).cloned() {
                     self.call_function(&func, args)
-                        .map_err(|_| ControlFlow::Return(Value::Unit))
+                        .map_err(|e| ControlFlow::Error(e.to_string()))
                 } else {
                     Ok(Value::Unit)
                 }
@@ -752,6 +1799,12 @@ impl Interpreter {
 
             HirPattern::Binding { name, .. } => Some(vec![(name.clone(), value.clone())]),
 
+            HirPattern::At { name, pattern, .. } => {
+                let mut bindings = self.match_pattern(pattern, value)?;
+                bindings.insert(0, (name.clone(), value.clone()));
+                Some(bindings)
+            }
+
             HirPattern::Literal(lit) => {
                 let lit_val = self.eval_literal(lit);
                 if lit_val == *value {
@@ -761,6 +1814,23 @@ impl Interpreter {
                 }
             }
 
+            HirPattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let in_range = match (self.eval_literal(start), value, self.eval_literal(end)) {
+                    (Value::Int(lo), Value::Int(v), Value::Int(hi)) => {
+                        lo <= *v && if *inclusive { *v <= hi } else { *v < hi }
+                    }
+                    (Value::Float(lo), Value::Float(v), Value::Float(hi)) => {
+                        lo <= *v && if *inclusive { *v <= hi } else { *v < hi }
+                    }
+                    _ => false,
+                };
+                in_range.then(Vec::new)
+            }
+
             HirPattern::Tuple(patterns) => {
                 if let Value::Tuple(values) = value {
                     if patterns.len() != values.len() {
@@ -801,12 +1871,7 @@ impl Interpreter {
                 variant,
                 patterns,
             } => {
-                if let Value::Variant {
-                    enum_name: e,
-                    variant_name: v,
-                    fields,
-                } = value
-                {
+                if let Some((e, v, fields)) = value.as_variant() {
                     if enum_name != e || variant != v {
                         return None;
                     }
@@ -842,27 +1907,45 @@ impl Interpreter {
                 Ok(())
             }
             HirExprKind::Field { base, field } => {
-                let base_val = self.eval_expr(base)?;
-                if let Value::Ref(r) = base_val {
-                    if let Value::Struct { ref mut fields, .. } = *r.borrow_mut() {
+                match self.eval_expr(base)? {
+                    Value::Ref(r) => {
+                        if let Value::Struct { ref mut fields, .. } = *r.borrow_mut() {
+                            fields.insert(field.clone(), value);
+                        }
+                    }
+                    // Structs are values: update a copy and store it back
+                    Value::Struct { name, mut fields } => {
                         fields.insert(field.clone(), value);
+                        self.assign_target(base, Value::Struct { name, fields })?;
                     }
+                    _ => {}
                 }
                 Ok(())
             }
-            HirExprKind::Index { base, index } => {
+            HirExprKind::Index { base, index, .. } => {
                 let base_val = self.eval_expr(base)?;
-                let idx = self.eval_expr(index)?.as_int().unwrap_or(0) as usize;
+                let idx = index_int(&self.eval_expr(index)?)?;
 
-                if let Value::Array(arr) = base_val {
-                    let mut arr = arr.borrow_mut();
-                    if idx < arr.len() {
+                match base_val {
+                    Value::Vec(items) => {
+                        let mut items = items.borrow_mut();
+                        let idx = checked_index(idx, items.len())?;
+                        items[idx] = value;
+                    }
+                    Value::Array(arr) => {
+                        let mut arr = arr.borrow_mut();
+                        let idx = checked_index(idx, arr.len())?;
                         arr[idx] = value;
                     }
+                    _ => {}
                 }
                 Ok(())
             }
-            HirExprKind::Deref(inner) => {
+            HirExprKind::Deref(inner)
+            | HirExprKind::Unary {
+                op: HirUnaryOp::Deref,
+                expr: inner,
+            } => {
                 let inner_val = self.eval_expr(inner)?;
                 if let Value::Ref(r) = inner_val {
                     *r.borrow_mut() = value;
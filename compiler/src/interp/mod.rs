@@ -4,8 +4,10 @@
 
 pub mod env;
 pub mod eval;
+pub mod random;
 pub mod value;
 
 pub use env::Environment;
-pub use eval::Interpreter;
+pub use eval::{Interpreter, OverflowMode};
+pub use random::Distribution;
 pub use value::Value;
@@ -0,0 +1,101 @@
+//! Probability distributions and the interpreter's random number generator
+
+use std::f64::consts::TAU;
+use std::fmt;
+
+/// Seed used when none is given, so that unseeded runs are reproducible too
+pub const DEFAULT_SEED: u64 = 0x5eed;
+
+/// Built-in distribution constructed by `Normal`, `Uniform` or `Bernoulli`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// Gaussian with the given mean and standard deviation
+    Normal { mean: f64, std_dev: f64 },
+    /// Continuous uniform over `[low, high)`
+    Uniform { low: f64, high: f64 },
+    /// `true` with probability `p`
+    Bernoulli { p: f64 },
+}
+
+impl Distribution {
+    /// Build a distribution by constructor name, validating its parameters
+    pub fn new(name: &str, params: &[f64]) -> Result<Self, String> {
+        let dist = match (name, params) {
+            ("Normal", &[mean, std_dev]) => Distribution::Normal { mean, std_dev },
+            ("Uniform", &[low, high]) => Distribution::Uniform { low, high },
+            ("Bernoulli", &[p]) => Distribution::Bernoulli { p },
+            _ => return Err(format!("invalid arguments to `{}`", name)),
+        };
+        let valid = match dist {
+            Distribution::Normal { std_dev, .. } => std_dev >= 0.0,
+            Distribution::Uniform { low, high } => low <= high,
+            Distribution::Bernoulli { p } => (0.0..=1.0).contains(&p),
+        };
+        if valid {
+            Ok(dist)
+        } else {
+            Err(format!("invalid parameters for {}", dist))
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distribution::Normal { mean, std_dev } => write!(f, "Normal({}, {})", mean, std_dev),
+            Distribution::Uniform { low, high } => write!(f, "Uniform({}, {})", low, high),
+            Distribution::Bernoulli { p } => write!(f, "Bernoulli({})", p),
+        }
+    }
+}
+
+/// SplitMix64 generator. Small and fast, and a given seed always produces
+/// the same stream of draws.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform draw from `[0, 1)`, using the top 53 bits
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Draw a sample, a float for `Normal` and `Uniform` and a bool for
+    /// `Bernoulli`
+    pub fn sample(&mut self, dist: &Distribution) -> Sample {
+        match *dist {
+            Distribution::Normal { mean, std_dev } => {
+                // Box–Muller; `1 - u` keeps the logarithm finite
+                let u1 = 1.0 - self.next_f64();
+                let u2 = self.next_f64();
+                let z = (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos();
+                Sample::Float(mean + std_dev * z)
+            }
+            Distribution::Uniform { low, high } => {
+                Sample::Float(low + (high - low) * self.next_f64())
+            }
+            Distribution::Bernoulli { p } => Sample::Bool(self.next_f64() < p),
+        }
+    }
+}
+
+/// Outcome of a single draw
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sample {
+    Float(f64),
+    Bool(bool),
+}
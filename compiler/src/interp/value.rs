@@ -3,10 +3,13 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::rc::Rc;
 
 use crate::hir::HirFn;
 
+use super::random::Distribution;
+
 /// Runtime value
 #[derive(Clone)]
 pub enum Value {
@@ -22,6 +25,8 @@ pub enum Value {
     String(String),
     /// Array (mutable interior)
     Array(Rc<RefCell<Vec<Value>>>),
+    /// Growable vector from `vec![...]` or `Vec::new()` (mutable interior)
+    Vec(Rc<RefCell<Vec<Value>>>),
     /// Tuple
     Tuple(Vec<Value>),
     /// Struct instance
@@ -41,6 +46,16 @@ pub enum Value {
         /// Captured environment for closures
         captures: HashMap<String, Value>,
     },
+    /// Pending call to an async function, run when awaited
+    Future {
+        func: Rc<HirFn>,
+        captures: HashMap<String, Value>,
+        args: Vec<Value>,
+    },
+    /// `resume` inside a handler case. Calling it the first time returns
+    /// to the `perform`; when the case is re-run after the handled block
+    /// finishes, it yields the block's final answer.
+    Resume(Option<Box<Value>>),
     /// Reference to a value
     Ref(Rc<RefCell<Value>>),
     /// Option::None
@@ -51,6 +66,12 @@ pub enum Value {
     Ok(Box<Value>),
     /// Result::Err(value)
     Err(Box<Value>),
+    /// Hash map (mutable interior)
+    Map(Rc<RefCell<ValueMap>>),
+    /// Hash set, a map whose values are all unit (mutable interior)
+    Set(Rc<RefCell<ValueMap>>),
+    /// Probability distribution to `sample` from
+    Distribution(Distribution),
 }
 
 impl Value {
@@ -63,15 +84,32 @@ impl Value {
             Value::Float(_) => "float",
             Value::String(_) => "string",
             Value::Array(_) => "array",
+            Value::Vec(_) => "vec",
             Value::Tuple(_) => "tuple",
             Value::Struct { .. } => "struct",
             Value::Variant { .. } => "variant",
             Value::Function { .. } => "function",
+            Value::Future { .. } => "future",
+            Value::Resume(_) => "continuation",
             Value::Ref(_) => "ref",
             Value::None => "None",
             Value::Some(_) => "Some",
             Value::Ok(_) => "Ok",
             Value::Err(_) => "Err",
+            Value::Map(_) => "map",
+            Value::Set(_) => "set",
+            Value::Distribution(_) => "distribution",
+        }
+    }
+
+    /// Name of the struct or enum this value is an instance of, seeing
+    /// through references
+    pub fn nominal_name(&self) -> Option<String> {
+        match self {
+            Value::Struct { name, .. } => Some(name.clone()),
+            Value::Variant { enum_name, .. } => Some(enum_name.clone()),
+            Value::Ref(r) => r.borrow().nominal_name(),
+            _ => None,
         }
     }
 
@@ -117,6 +155,137 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Build an enum variant. The built-in `Option` and `Result` variants
+    /// use their dedicated representations.
+    pub fn variant(enum_name: &str, variant_name: &str, mut fields: Vec<Value>) -> Value {
+        match (enum_name, variant_name, fields.len()) {
+            ("Option", "None", 0) => Value::None,
+            ("Option", "Some", 1) => Value::Some(Box::new(fields.remove(0))),
+            ("Result", "Ok", 1) => Value::Ok(Box::new(fields.remove(0))),
+            ("Result", "Err", 1) => Value::Err(Box::new(fields.remove(0))),
+            _ => Value::Variant {
+                enum_name: enum_name.to_string(),
+                variant_name: variant_name.to_string(),
+                fields,
+            },
+        }
+    }
+
+    /// Try to get as an enum variant: `(enum, variant, fields)`
+    pub fn as_variant(&self) -> Option<(&str, &str, &[Value])> {
+        match self {
+            Value::Variant {
+                enum_name,
+                variant_name,
+                fields,
+            } => Some((enum_name, variant_name, fields)),
+            Value::None => Some(("Option", "None", &[])),
+            Value::Some(v) => Some(("Option", "Some", std::slice::from_ref(&**v))),
+            Value::Ok(v) => Some(("Result", "Ok", std::slice::from_ref(&**v))),
+            Value::Err(v) => Some(("Result", "Err", std::slice::from_ref(&**v))),
+            _ => None,
+        }
+    }
+
+    /// Structural hash of this value
+    pub fn hash_value(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Unit | Value::None => {}
+            Value::Bool(b) => b.hash(state),
+            Value::Int(n) => n.hash(state),
+            Value::Float(f) => f.to_bits().hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Array(arr) | Value::Vec(arr) => arr.borrow().hash(state),
+            Value::Tuple(vals) => vals.hash(state),
+            Value::Struct { name, fields } => {
+                name.hash(state);
+                // Field order in the map is arbitrary; hash in name order
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                fields.hash(state);
+            }
+            Value::Variant {
+                enum_name,
+                variant_name,
+                fields,
+            } => {
+                enum_name.hash(state);
+                variant_name.hash(state);
+                fields.hash(state);
+            }
+            Value::Function { func, .. } | Value::Future { func, .. } => func.name.hash(state),
+            Value::Ref(r) => r.borrow().hash(state),
+            Value::Resume(v) => v.hash(state),
+            Value::Some(v) | Value::Ok(v) | Value::Err(v) => v.hash(state),
+            Value::Map(m) | Value::Set(m) => m.borrow().len().hash(state),
+            Value::Distribution(d) => d.to_string().hash(state),
+        }
+    }
+}
+
+/// Map from values to values, bucketed by structural hash
+#[derive(Clone, Default)]
+pub struct ValueMap {
+    buckets: HashMap<u64, Vec<(Value, Value)>>,
+    len: usize,
+}
+
+impl ValueMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insert `value` under `key`, returning the previous value if any
+    pub fn insert(&mut self, key: Value, value: Value) -> Option<Value> {
+        let bucket = self.buckets.entry(key.hash_value()).or_default();
+        if let Some(entry) = bucket.iter_mut().find(|(k, _)| *k == key) {
+            return Some(std::mem::replace(&mut entry.1, value));
+        }
+        bucket.push((key, value));
+        self.len += 1;
+        None
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.buckets
+            .get(&key.hash_value())?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, key: &Value) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Entries in unspecified order
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.buckets.values().flatten().map(|(k, v)| (k, v))
+    }
+}
+
+impl PartialEq for ValueMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
 }
 
 impl fmt::Debug for Value {
@@ -127,7 +296,7 @@ impl fmt::Debug for Value {
             Value::Int(n) => write!(f, "{}", n),
             Value::Float(n) => write!(f, "{}", n),
             Value::String(s) => write!(f, "{:?}", s),
-            Value::Array(arr) => {
+            Value::Array(arr) | Value::Vec(arr) => {
                 write!(f, "[")?;
                 let arr = arr.borrow();
                 for (i, v) in arr.iter().enumerate() {
@@ -177,11 +346,19 @@ impl fmt::Debug for Value {
                 Ok(())
             }
             Value::Function { func, .. } => write!(f, "<fn {}>", func.name),
+            Value::Future { func, .. } => write!(f, "<future {}>", func.name),
+            Value::Resume(_) => write!(f, "<resume>"),
             Value::Ref(r) => write!(f, "&{:?}", r.borrow()),
             Value::None => write!(f, "None"),
             Value::Some(v) => write!(f, "Some({:?})", v),
             Value::Ok(v) => write!(f, "Ok({:?})", v),
             Value::Err(v) => write!(f, "Err({:?})", v),
+            Value::Map(m) => f.debug_map().entries(m.borrow().iter()).finish(),
+            Value::Set(m) => f
+                .debug_set()
+                .entries(m.borrow().iter().map(|(k, _)| k))
+                .finish(),
+            Value::Distribution(d) => write!(f, "{}", d),
         }
     }
 }
@@ -194,7 +371,7 @@ impl fmt::Display for Value {
             Value::Int(n) => write!(f, "{}", n),
             Value::Float(n) => write!(f, "{}", n),
             Value::String(s) => write!(f, "{}", s),
-            Value::Array(arr) => {
+            Value::Array(arr) | Value::Vec(arr) => {
                 write!(f, "[")?;
                 let arr = arr.borrow();
                 for (i, v) in arr.iter().enumerate() {
@@ -244,11 +421,34 @@ impl fmt::Display for Value {
                 Ok(())
             }
             Value::Function { func, .. } => write!(f, "<fn {}>", func.name),
+            Value::Future { func, .. } => write!(f, "<future {}>", func.name),
+            Value::Resume(_) => write!(f, "<resume>"),
             Value::Ref(r) => write!(f, "{}", r.borrow()),
             Value::None => write!(f, "None"),
             Value::Some(v) => write!(f, "Some({})", v),
             Value::Ok(v) => write!(f, "Ok({})", v),
             Value::Err(v) => write!(f, "Err({})", v),
+            Value::Map(m) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in m.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", k, v)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Set(m) => {
+                write!(f, "{{")?;
+                for (i, (k, _)) in m.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", k)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Distribution(d) => write!(f, "{}", d),
         }
     }
 }
@@ -266,7 +466,13 @@ impl PartialEq for Value {
             (Value::Ok(a), Value::Ok(b)) => a == b,
             (Value::Err(a), Value::Err(b)) => a == b,
             (Value::Tuple(a), Value::Tuple(b)) => a == b,
-            (Value::Array(a), Value::Array(b)) => *a.borrow() == *b.borrow(),
+            (Value::Array(a), Value::Array(b)) | (Value::Vec(a), Value::Vec(b)) => {
+                *a.borrow() == *b.borrow()
+            }
+            (Value::Map(a), Value::Map(b)) | (Value::Set(a), Value::Set(b)) => {
+                *a.borrow() == *b.borrow()
+            }
+            (Value::Distribution(a), Value::Distribution(b)) => a == b,
             (
                 Value::Struct {
                     name: n1,
@@ -303,4 +509,11 @@ pub enum ControlFlow {
     Break(Option<Value>),
     /// Continue to next iteration
     Continue,
+    /// Runtime error that aborts execution
+    Error(String),
+    /// A handler case called `resume` with the operation's result
+    Resume(Value),
+    /// A handler case finished without resuming; its value is the result
+    /// of the `handle` whose frame is at `depth` on the handler stack
+    Abort { depth: usize, value: Value },
 }
@@ -46,6 +46,8 @@ pub enum TokenKind {
     Trait,
     #[token("impl")]
     Impl,
+    #[token("dyn")]
+    Dyn,
     #[token("if")]
     If,
     #[token("else")]
@@ -156,6 +158,9 @@ pub enum TokenKind {
     FloatLit,
     #[regex(r#""([^"\\]|\\.)*""#)]
     StringLit,
+    /// Raw string: r"..." or r#"..."# (no escape processing)
+    #[regex(r##"r#*""##, lex_raw_string)]
+    RawStringLit,
     #[regex(r#"'([^'\\]|\\.)'"#)]
     CharLit,
 
@@ -173,6 +178,10 @@ pub enum TokenKind {
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", priority = 1)]
     Ident,
 
+    // Lifetimes: 'a, 'static. A char literal such as 'a' is the longer match.
+    #[regex(r"'[a-zA-Z_][a-zA-Z0-9_]*")]
+    Lifetime,
+
     // Operators
     #[token("+")]
     Plus,
@@ -324,6 +333,7 @@ impl TokenKind {
                 | TokenKind::Enum
                 | TokenKind::Trait
                 | TokenKind::Impl
+                | TokenKind::Dyn
                 | TokenKind::If
                 | TokenKind::Else
                 | TokenKind::Match
@@ -383,6 +393,7 @@ impl TokenKind {
                 | TokenKind::OctLit
                 | TokenKind::FloatLit
                 | TokenKind::StringLit
+                | TokenKind::RawStringLit
                 | TokenKind::CharLit
                 | TokenKind::IntUnitLit
                 | TokenKind::FloatUnitLit
@@ -434,6 +445,7 @@ impl TokenKind {
             TokenKind::Enum => "enum",
             TokenKind::Trait => "trait",
             TokenKind::Impl => "impl",
+            TokenKind::Dyn => "dyn",
             TokenKind::If => "if",
             TokenKind::Else => "else",
             TokenKind::Match => "match",
@@ -486,10 +498,12 @@ impl TokenKind {
             TokenKind::OctLit => "<oct>",
             TokenKind::FloatLit => "<float>",
             TokenKind::StringLit => "<string>",
+            TokenKind::RawStringLit => "<raw_string>",
             TokenKind::CharLit => "<char>",
             TokenKind::IntUnitLit => "<int_unit>",
             TokenKind::FloatUnitLit => "<float_unit>",
             TokenKind::Ident => "<ident>",
+            TokenKind::Lifetime => "<lifetime>",
             TokenKind::Plus => "+",
             TokenKind::Minus => "-",
             TokenKind::Star => "*",
@@ -552,6 +566,21 @@ impl TokenKind {
     }
 }
 
+/// Consume the rest of a raw string after its `r#*"` opener.
+///
+/// The literal ends at the first `"` followed by as many `#` as the opener had.
+fn lex_raw_string(lex: &mut logos::Lexer<TokenKind>) -> bool {
+    let hashes = lex.slice().len() - 2;
+    let closing = format!("\"{}", "#".repeat(hashes));
+    match lex.remainder().find(&closing) {
+        Some(end) => {
+            lex.bump(end + closing.len());
+            true
+        }
+        None => false,
+    }
+}
+
 impl std::fmt::Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.as_str())
@@ -38,6 +38,7 @@ pub mod common;
 pub mod diagnostics;
 pub mod doc;
 pub mod effects;
+pub mod fmt;
 pub mod hir;
 pub mod hlir;
 pub mod interp;
@@ -53,6 +54,7 @@ pub mod repl;
 pub mod resolve;
 pub mod sourcemap;
 pub mod types;
+pub mod watch;
 
 // Re-export diagnostics for convenience
 pub use diagnostics::{CompileError, Reporter, SourceFile};
@@ -66,15 +68,29 @@ pub use types::Type;
 /// Compiler version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// Compile source code to an executable
+/// Compile source code to an object file for the host platform
+///
+/// Code generation goes through Cranelift and requires the `jit` feature.
 pub fn compile(source: &str) -> miette::Result<Vec<u8>> {
     let tokens = lexer::lex(source)?;
     let ast = parser::parse(&tokens, source)?;
     let hir = check::check(&ast)?;
-    let hlir = hlir::lower(&hir);
+    let hlir = hlir::lower(&hir)?;
 
-    // TODO: Actual code generation
-    Err(miette::miette!("Code generation not yet implemented"))
+    #[cfg(feature = "jit")]
+    {
+        codegen::cranelift::CraneliftJit::new()
+            .emit_object(&hlir)
+            .map_err(|e| miette::miette!("Code generation failed: {}", e))
+    }
+
+    #[cfg(not(feature = "jit"))]
+    {
+        let _ = hlir;
+        Err(miette::miette!(
+            "Code generation requires the Cranelift backend; rebuild with `--features jit`"
+        ))
+    }
 }
 
 /// Type-check source code without compiling
@@ -629,7 +629,7 @@ fn format_type(ty: &crate::ast::TypeExpr) -> String {
                 .unwrap_or_default();
             format!("{}{}{}", base, type_args, unit_suffix)
         }
-        crate::ast::TypeExpr::Reference { mutable, inner } => {
+        crate::ast::TypeExpr::Reference { mutable, inner, .. } => {
             if *mutable {
                 format!("&mut {}", format_type(inner))
             } else {
@@ -656,6 +656,7 @@ fn format_type(ty: &crate::ast::TypeExpr) -> String {
             format!("fn({}) -> {}", p.join(", "), format_type(return_type))
         }
         crate::ast::TypeExpr::Infer => "_".to_string(),
+        crate::ast::TypeExpr::Const(_) => "N".to_string(),
     }
 }
 
@@ -18,10 +18,11 @@ impl DiagnosticsProvider {
         // Extract error message
         let message = err.to_string();
 
-        // Try to extract span information from the error
-        // For now, we'll create a diagnostic at the beginning of the file
-        // In a real implementation, we'd parse the miette error for location info
-        let range = self.extract_range_from_error(&message, source);
+        // Use the first labelled span, falling back to a position in the message
+        let range = match err.labels().and_then(|mut labels| labels.next()) {
+            Some(label) => span_to_range(source, label.offset(), label.len()),
+            None => self.extract_range_from_error(&message, source),
+        };
 
         // Determine severity
         let severity = Some(DiagnosticSeverity::ERROR);
@@ -211,6 +212,27 @@ impl Default for DiagnosticsProvider {
     }
 }
 
+/// Range covering `len` bytes at `offset`, at least one character wide
+fn span_to_range(source: &str, offset: usize, len: usize) -> Range {
+    let (line, col) = offset_to_line_col(source, offset);
+    let (end_line, end_col) = offset_to_line_col(source, offset + len);
+    let end = if (end_line, end_col) == (line, col) {
+        (line, col + 1)
+    } else {
+        (end_line, end_col)
+    };
+    Range {
+        start: Position {
+            line: line as u32,
+            character: col as u32,
+        },
+        end: Position {
+            line: end.0 as u32,
+            character: end.1 as u32,
+        },
+    }
+}
+
 /// Convert byte offset to line/column
 fn offset_to_line_col(source: &str, offset: usize) -> (usize, usize) {
     let offset = offset.min(source.len());
@@ -277,4 +299,14 @@ mod tests {
         assert_eq!(offset_to_line_col(source, 7), (1, 0));
         assert_eq!(offset_to_line_col(source, 14), (2, 0));
     }
+
+    #[test]
+    fn test_parse_error_range_from_label() {
+        let source = "fn f(a: i64\n     b: i64) {}";
+        let tokens = crate::lexer::lex(source).unwrap();
+        let err = crate::parser::parse(&tokens, source).unwrap_err();
+        let diagnostic = DiagnosticsProvider::new().miette_to_diagnostic(&err, source);
+        assert_eq!(diagnostic.range.start, Position::new(1, 5));
+        assert_eq!(diagnostic.range.end, Position::new(1, 6));
+    }
 }
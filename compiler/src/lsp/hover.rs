@@ -127,6 +127,7 @@ impl HoverProvider {
             DefKind::Field => "Field",
             DefKind::Kernel => "GPU kernel function",
             DefKind::BuiltinType => "Built-in type",
+            DefKind::Import => "Import",
         };
 
         content.push_str(&format!("\n*{}*\n", kind_desc));
@@ -183,6 +184,7 @@ impl HoverProvider {
             DefKind::Field => format!("{}: T", name),
             DefKind::Kernel => format!("kernel fn {}(...)", name),
             DefKind::BuiltinType => name.to_string(),
+            DefKind::Import => format!("import {}", name),
         }
     }
 
@@ -115,7 +115,7 @@ impl SemanticTokensProvider {
 
             TokenKind::Unsafe | TokenKind::Extern => Some((TOKEN_KEYWORD, MOD_UNSAFE)),
 
-            TokenKind::Impl => Some((TOKEN_KEYWORD, 0)),
+            TokenKind::Impl | TokenKind::Dyn => Some((TOKEN_KEYWORD, 0)),
 
             TokenKind::Move | TokenKind::Copy | TokenKind::Drop => Some((TOKEN_KEYWORD, 0)),
 
@@ -132,7 +132,7 @@ impl SemanticTokensProvider {
             TokenKind::IntUnitLit | TokenKind::FloatUnitLit => Some((TOKEN_UNIT, 0)),
 
             // String literals
-            TokenKind::StringLit => Some((TOKEN_STRING, 0)),
+            TokenKind::StringLit | TokenKind::RawStringLit => Some((TOKEN_STRING, 0)),
             TokenKind::CharLit => Some((TOKEN_STRING, 0)),
 
             // Identifiers - would need semantic analysis for proper classification
@@ -3,6 +3,7 @@
 //! Main entry point for the `dc` command.
 
 use clap::{Parser, Subcommand};
+use demetrios::diagnostics::{JsonDiagnostic, JsonSeverity};
 use miette::Result;
 use std::path::PathBuf;
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
@@ -40,6 +41,15 @@ enum Commands {
         /// Optimization level (0-3)
         #[arg(short = 'O', default_value = "0")]
         opt_level: u8,
+
+        /// Inline functions with fewer HLIR instructions than N when optimizing
+        #[arg(long, value_name = "N", default_value_t = demetrios::hlir::DEFAULT_INLINE_THRESHOLD)]
+        inline_threshold: usize,
+
+        /// Enable a cfg flag for `#[cfg(...)]` and `#[cfg_attr(...)]`
+        /// (e.g. --cfg 'feature="gpu"')
+        #[arg(long = "cfg", value_name = "FLAG")]
+        cfg: Vec<String>,
     },
 
     /// Build a D source file to native executable (requires --features llvm)
@@ -56,6 +66,15 @@ enum Commands {
         #[arg(short = 'O', default_value = "2")]
         opt_level: String,
 
+        /// Inline functions with fewer HLIR instructions than N when optimizing
+        #[arg(long, value_name = "N", default_value_t = demetrios::hlir::DEFAULT_INLINE_THRESHOLD)]
+        inline_threshold: usize,
+
+        /// Fast-math assumptions for float arithmetic: fast, none, or a list
+        /// of nnan, ninf, contract and reassoc [default: fast at -O3]
+        #[arg(long, value_name = "FLAGS")]
+        fast_math: Option<String>,
+
         /// Generate debug information
         #[arg(short = 'g', long)]
         debug: bool,
@@ -76,6 +95,31 @@ enum Commands {
         #[arg(long)]
         strip: bool,
 
+        /// Link all libraries statically
+        #[arg(long = "static", conflicts_with = "dynamic")]
+        static_link: bool,
+
+        /// Prefer shared libraries when linking
+        #[arg(long)]
+        dynamic: bool,
+
+        /// Link with library NAME (e.g. -lm, -lopenblas)
+        #[arg(short = 'l', value_name = "NAME")]
+        libs: Vec<String>,
+
+        /// Add DIR to the library search path
+        #[arg(short = 'L', value_name = "DIR")]
+        lib_dirs: Vec<PathBuf>,
+
+        /// Enable a cfg flag for `#[cfg(...)]` and `#[cfg_attr(...)]`
+        /// (e.g. --cfg 'feature="gpu"')
+        #[arg(long = "cfg", value_name = "FLAG")]
+        cfg: Vec<String>,
+
+        /// Trap after N loop iterations per function call
+        #[arg(long, value_name = "N")]
+        guard_steps: Option<u64>,
+
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
@@ -106,6 +150,27 @@ enum Commands {
         /// Skip ownership checking
         #[arg(long)]
         skip_ownership: bool,
+
+        /// Re-check whenever the input file changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Enable a cfg flag for `#[cfg(...)]` and `#[cfg_attr(...)]`
+        /// (e.g. --cfg 'feature="gpu"')
+        #[arg(long = "cfg", value_name = "FLAG")]
+        cfg: Vec<String>,
+
+        /// Print diagnostics to stdout as a JSON array instead of rendering
+        /// them. Each entry has `code`, `severity` ("error", "warning" or
+        /// "advice"), `message`, `file`, `span` and `help`; `code`, `span`
+        /// and `help` are null when absent. `span` has `start` and `end`
+        /// positions, each a 1-based `line` and byte `column`, with `end`
+        /// exclusive.
+        #[arg(
+            long,
+            conflicts_with_all = ["show_ast", "show_resolved", "show_types", "show_effects", "watch"]
+        )]
+        json: bool,
     },
 
     /// Run a D program using the interpreter
@@ -114,6 +179,19 @@ enum Commands {
         #[arg(value_name = "FILE")]
         input: PathBuf,
 
+        /// Wrap on integer overflow instead of reporting an error
+        #[arg(long)]
+        wrapping: bool,
+
+        /// Seed for the random number generator used by `sample`
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Enable a cfg flag for `#[cfg(...)]` and `#[cfg_attr(...)]`
+        /// (e.g. --cfg 'feature="gpu"')
+        #[arg(long = "cfg", value_name = "FLAG")]
+        cfg: Vec<String>,
+
         /// Arguments to pass to the program
         #[arg(trailing_var_arg = true)]
         args: Vec<String>,
@@ -129,6 +207,10 @@ enum Commands {
         #[arg(short = 'O', long)]
         optimize: bool,
 
+        /// Trap after N loop iterations per function call
+        #[arg(long, value_name = "N")]
+        guard_steps: Option<u64>,
+
         /// Arguments to pass to the program
         #[arg(trailing_var_arg = true)]
         args: Vec<String>,
@@ -195,12 +277,21 @@ enum Commands {
     /// Show documentation coverage
     DocCoverage,
 
+    /// Explain an error code in detail
+    Explain {
+        /// Error code, e.g. D0203
+        #[arg(value_name = "CODE")]
+        code: String,
+    },
+
     /// Show information about the compiler
     Info,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 enum EmitType {
+    /// Lexer tokens with their spans (JSON)
+    Tokens,
     /// Abstract Syntax Tree (JSON)
     Ast,
     /// High-level IR
@@ -230,27 +321,52 @@ fn main() -> Result<()> {
             output,
             emit,
             opt_level,
-        } => compile(&input, output.as_deref(), emit, opt_level),
+            inline_threshold,
+            cfg,
+        } => compile(
+            &input,
+            output.as_deref(),
+            emit,
+            opt_level,
+            inline_threshold,
+            &cfg,
+        ),
 
         Commands::Build {
             input,
             output,
             opt_level,
+            inline_threshold,
+            fast_math,
             debug,
             emit_llvm,
             emit_asm,
             target,
             strip,
+            static_link,
+            dynamic,
+            libs,
+            lib_dirs,
+            cfg,
+            guard_steps,
             verbose,
         } => build(
             &input,
             output.as_deref(),
             &opt_level,
+            inline_threshold,
+            fast_math.as_deref(),
             debug,
             emit_llvm,
             emit_asm,
             target.as_deref(),
             strip,
+            static_link,
+            dynamic,
+            &libs,
+            &lib_dirs,
+            &cfg,
+            guard_steps,
             verbose,
         ),
 
@@ -261,22 +377,49 @@ fn main() -> Result<()> {
             show_types,
             show_effects,
             skip_ownership,
-        } => check(
-            &input,
-            show_ast,
-            show_resolved,
-            show_types,
-            show_effects,
-            skip_ownership,
-        ),
+            watch,
+            cfg,
+            json,
+        } => {
+            let run_check = || {
+                check(
+                    &input,
+                    show_ast,
+                    show_resolved,
+                    show_types,
+                    show_effects,
+                    skip_ownership,
+                    &cfg,
+                    json,
+                )
+            };
+            if watch {
+                watch_check(&input, run_check)
+            } else {
+                run_check()
+            }
+        }
 
-        Commands::Run { input, args } => run(&input, &args),
+        Commands::Run {
+            input,
+            wrapping,
+            seed,
+            cfg,
+            args,
+        } => {
+            let code = run(&input, wrapping, seed, &cfg, &args, cli.verbose)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+            Ok(())
+        }
 
         Commands::Jit {
             input,
             optimize,
+            guard_steps,
             args,
-        } => jit_run(&input, optimize, &args),
+        } => jit_run(&input, optimize, guard_steps, &args),
 
         Commands::Repl { jit } => repl(jit),
 
@@ -295,6 +438,8 @@ fn main() -> Result<()> {
 
         Commands::DocCoverage => doc_coverage(),
 
+        Commands::Explain { code } => explain(&code),
+
         Commands::Info => info(),
     }
 }
@@ -305,21 +450,29 @@ fn build(
     input: &std::path::Path,
     output: Option<&std::path::Path>,
     opt_level: &str,
+    inline_threshold: usize,
+    fast_math: Option<&str>,
     debug: bool,
     emit_llvm: bool,
     emit_asm: bool,
     target: Option<&str>,
     strip: bool,
+    static_link: bool,
+    dynamic: bool,
+    libs: &[String],
+    lib_dirs: &[PathBuf],
+    cfg: &[String],
+    guard_steps: Option<u64>,
     verbose: bool,
 ) -> Result<()> {
     #[cfg(feature = "llvm")]
     {
         use demetrios::codegen::llvm::{
-            codegen::{LLVMCodegen, OptLevel},
-            linker::Linker,
+            codegen::{FastMathFlags, LLVMCodegen, OptLevel},
+            linker::{LinkMode, Linker},
             passes,
             target::{
-                compile_to_asm, compile_to_object, create_native_target_machine,
+                compile_to_asm, compile_to_object, configure_module, create_native_target_machine,
                 create_target_machine, executable_extension, initialize_native_target,
                 object_extension,
             },
@@ -350,13 +503,20 @@ fn build(
 
         // Lex and parse
         let tokens = demetrios::lexer::lex(&source)?;
-        let ast = demetrios::parser::parse(&tokens, &source)?;
+        let mut ast = demetrios::parser::parse(&tokens, &source)?;
+        let flags = cfg_flags(cfg, opt != OptLevel::O0);
+        demetrios::ast::cfg::configure(&mut ast, &flags);
 
         // Type check
         let hir = demetrios::check::check(&ast)?;
 
         // Lower to HLIR
-        let hlir = demetrios::hlir::lower(&hir);
+        let options = demetrios::hlir::LowerOptions { guard_steps };
+        let mut hlir = demetrios::hlir::lower_with(&hir, &options)?;
+        if opt != OptLevel::O0 {
+            let settings = demetrios::hlir::OptSettings { inline_threshold };
+            demetrios::hlir::optimize_with(&mut hlir, &settings);
+        }
 
         if verbose {
             eprintln!(
@@ -377,6 +537,24 @@ fn build(
             .unwrap_or("module");
 
         let mut codegen = LLVMCodegen::new(&context, module_name, opt, debug);
+        if let Some(flags) = fast_math {
+            codegen.set_fast_math(FastMathFlags::from_str(flags).ok_or_else(|| {
+                miette::miette!(
+                    "Invalid fast-math flags: {}. Use fast, none, or a list of nnan, ninf, contract, reassoc",
+                    flags
+                )
+            })?);
+        }
+        if debug {
+            let file = demetrios::sourcemap::SourceFile::new(
+                demetrios::sourcemap::FileId::new(0),
+                input.to_path_buf(),
+                source.clone(),
+            );
+            let source_map =
+                demetrios::sourcemap::SourceMap::from_node_spans(&file, &ast.node_spans);
+            codegen.set_source_map(input, source_map);
+        }
 
         // Compile to LLVM IR
         let module = codegen.compile(&hlir);
@@ -394,6 +572,7 @@ fn build(
             create_native_target_machine(opt)
                 .map_err(|e| miette::miette!("Failed to create target machine: {}", e))?
         };
+        configure_module(module, &target_machine);
 
         // Run optimization passes
         passes::optimize_module(module, opt, &target_machine);
@@ -453,7 +632,20 @@ fn build(
             p
         });
 
-        let linker = Linker::new().strip(strip).verbose(verbose);
+        let link_mode = if static_link {
+            LinkMode::Static
+        } else if dynamic {
+            LinkMode::Dynamic
+        } else {
+            LinkMode::Default
+        };
+
+        let linker = Linker::new()
+            .strip(strip)
+            .link_mode(link_mode)
+            .lib_paths(lib_dirs.iter().cloned())
+            .libs(libs.iter().cloned())
+            .verbose(verbose);
 
         linker
             .link_with_stdlib(&[obj_path.clone()], &exe_path)
@@ -471,7 +663,23 @@ fn build(
     #[cfg(not(feature = "llvm"))]
     {
         let _ = (
-            input, output, opt_level, debug, emit_llvm, emit_asm, target, strip, verbose,
+            input,
+            output,
+            opt_level,
+            inline_threshold,
+            fast_math,
+            debug,
+            emit_llvm,
+            emit_asm,
+            target,
+            strip,
+            static_link,
+            dynamic,
+            libs,
+            lib_dirs,
+            cfg,
+            guard_steps,
+            verbose,
         );
         Err(miette::miette!(
             "LLVM backend not enabled. Rebuild with: cargo build --features llvm"
@@ -479,11 +687,22 @@ fn build(
     }
 }
 
+/// The active cfg flags: those passed with `--cfg`, plus `release` when optimizing
+fn cfg_flags(cfg: &[String], optimized: bool) -> Vec<String> {
+    let mut flags = cfg.to_vec();
+    if optimized {
+        flags.push("release".to_string());
+    }
+    flags
+}
+
 fn compile(
     input: &std::path::Path,
     output: Option<&std::path::Path>,
     emit: Option<EmitType>,
     opt_level: u8,
+    inline_threshold: usize,
+    cfg: &[String],
 ) -> Result<()> {
     tracing::info!(
         "Compiling {:?} with optimization level {}",
@@ -499,13 +718,27 @@ fn compile(
     let tokens = demetrios::lexer::lex(&source)?;
     tracing::debug!("Lexed {} tokens", tokens.len());
 
+    if emit == Some(EmitType::Tokens) {
+        let listing: Vec<_> = tokens
+            .iter()
+            .map(|t| serde_json::json!({ "kind": t.kind, "text": t.text, "span": t.span }))
+            .collect();
+        let json = serde_json::to_string_pretty(&listing)
+            .map_err(|e| miette::miette!("Failed to serialize tokens: {}", e))?;
+        println!("{}", json);
+        return Ok(());
+    }
+
     // Parse
-    let ast = demetrios::parser::parse(&tokens, &source)?;
+    let mut ast = demetrios::parser::parse(&tokens, &source)?;
     tracing::debug!("Parsed {} items", ast.items.len());
+    demetrios::ast::cfg::configure(&mut ast, &cfg_flags(cfg, opt_level > 0));
+    let settings = demetrios::hlir::OptSettings { inline_threshold };
 
     // Handle emit options
     if let Some(emit_type) = emit {
         match emit_type {
+            EmitType::Tokens => unreachable!("tokens are emitted before parsing"),
             EmitType::Ast => {
                 let json = serde_json::to_string_pretty(&ast)
                     .map_err(|e| miette::miette!("Failed to serialize AST: {}", e))?;
@@ -518,8 +751,14 @@ fn compile(
                 return Ok(());
             }
             EmitType::Hlir => {
-                let hir = demetrios::check::check(&ast)?;
-                let hlir = demetrios::hlir::lower(&hir);
+                let mut hir = demetrios::check::check(&ast)?;
+                if opt_level > 0 {
+                    demetrios::hir::fold_constants(&mut hir);
+                }
+                let mut hlir = demetrios::hlir::lower(&hir)?;
+                if opt_level > 0 {
+                    demetrios::hlir::optimize_with(&mut hlir, &settings);
+                }
                 println!("{:#?}", hlir);
                 return Ok(());
             }
@@ -530,10 +769,16 @@ fn compile(
     }
 
     // Type check
-    let hir = demetrios::check::check(&ast)?;
+    let mut hir = demetrios::check::check(&ast)?;
+    if opt_level > 0 {
+        demetrios::hir::fold_constants(&mut hir);
+    }
 
     // Lower to HLIR
-    let hlir = demetrios::hlir::lower(&hir);
+    let mut hlir = demetrios::hlir::lower(&hir)?;
+    if opt_level > 0 {
+        demetrios::hlir::optimize_with(&mut hlir, &settings);
+    }
 
     // Code generation
     let _output_path = output.unwrap_or_else(|| {
@@ -554,6 +799,29 @@ fn compile(
     Ok(())
 }
 
+/// Where `dc check` sends diagnostics: rendered on stderr, or collected
+/// for printing as JSON with `--json`
+enum CheckOutput<'a> {
+    Human,
+    Json(&'a mut Vec<JsonDiagnostic>),
+}
+
+impl CheckOutput<'_> {
+    fn report(
+        &mut self,
+        diagnostic: impl miette::Diagnostic + Send + Sync + 'static,
+        source: &demetrios::SourceFile,
+    ) {
+        match self {
+            CheckOutput::Human => eprintln!("{:?}", miette::Report::new(diagnostic)),
+            CheckOutput::Json(out) => {
+                out.extend(JsonDiagnostic::from_diagnostic(&diagnostic, source))
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn check(
     input: &std::path::Path,
     show_ast: bool,
@@ -561,6 +829,8 @@ fn check(
     show_types: bool,
     show_effects: bool,
     skip_ownership: bool,
+    cfg: &[String],
+    json: bool,
 ) -> Result<()> {
     tracing::info!("Type-checking {:?}", input);
 
@@ -568,13 +838,72 @@ fn check(
         .map_err(|e| miette::miette!("Failed to read input file: {}", e))?;
 
     let source_file =
-        demetrios::SourceFile::new(input.to_string_lossy().to_string(), source_content.clone());
+        demetrios::SourceFile::new(input.to_string_lossy().to_string(), source_content);
+
+    if !json {
+        check_source(
+            &source_file,
+            show_ast,
+            show_resolved,
+            show_types,
+            show_effects,
+            skip_ownership,
+            cfg,
+            &mut CheckOutput::Human,
+        )?;
+        println!("All checks passed: {}", input.display());
+        return Ok(());
+    }
+
+    let mut diagnostics = Vec::new();
+    let result = check_source(
+        &source_file,
+        false,
+        false,
+        false,
+        false,
+        skip_ownership,
+        cfg,
+        &mut CheckOutput::Json(&mut diagnostics),
+    );
+    let is_error = |d: &JsonDiagnostic| d.severity == JsonSeverity::Error;
+    // Lexing, parsing and resolution stop at their first failure, which is
+    // returned without being reported
+    if let Err(e) = &result
+        && !diagnostics.iter().any(is_error)
+    {
+        diagnostics.extend(JsonDiagnostic::from_diagnostic(&**e, &source_file));
+    }
+    let json = serde_json::to_string_pretty(&diagnostics)
+        .map_err(|e| miette::miette!("Failed to serialize diagnostics: {}", e))?;
+    println!("{}", json);
+
+    match diagnostics.iter().filter(|d| is_error(d)).count() {
+        0 => Ok(()),
+        count => Err(miette::miette!("{} error(s) found", count)),
+    }
+}
+
+/// Run every check on `source_file`, reporting diagnostics to `output`
+#[allow(clippy::too_many_arguments)]
+fn check_source(
+    source_file: &demetrios::SourceFile,
+    show_ast: bool,
+    show_resolved: bool,
+    show_types: bool,
+    show_effects: bool,
+    skip_ownership: bool,
+    cfg: &[String],
+    output: &mut CheckOutput,
+) -> Result<()> {
+    let source_content = &*source_file.content;
 
     // 1. Lex
-    let tokens = demetrios::lexer::lex(&source_content)?;
+    let tokens = demetrios::lexer::lex(source_content)?;
 
     // 2. Parse
-    let ast = demetrios::parser::parse(&tokens, &source_content)?;
+    let mut ast = demetrios::parser::parse(&tokens, source_content)?;
+    demetrios::ast::cfg::configure(&mut ast, cfg);
 
     if show_ast {
         println!("=== AST ===");
@@ -599,12 +928,50 @@ fn check(
     }
 
     // 4. Type check
-    let hir = demetrios::check::check(&resolved.ast)?;
+    let mut checker = demetrios::check::TypeChecker::new();
+    let hir = match checker.check_program(&resolved.ast) {
+        Ok(hir) => hir,
+        Err(e) => {
+            // The human summary in `e` lists every error, so only unit
+            // errors are rendered with their source
+            let errors = match output {
+                CheckOutput::Human => checker
+                    .unit_errors()
+                    .iter()
+                    .map(|err| err.to_diagnostic(source_file))
+                    .collect(),
+                CheckOutput::Json(_) => checker.error_diagnostics(source_file),
+            };
+            for err in errors {
+                output.report(err, source_file);
+            }
+            return Err(e);
+        }
+    };
+    for unused in checker.unused_bindings() {
+        output.report(unused.to_diagnostic(source_file), source_file);
+    }
+    for attr in checker.unknown_attributes() {
+        let warning = demetrios::diagnostics::CompileError::UnknownAttribute {
+            name: attr.name.clone(),
+            span: attr.span.into(),
+            src: source_file.to_named_source(),
+        };
+        output.report(warning, source_file);
+    }
 
     if show_types {
-        println!("=== HIR (with types) ===");
-        println!("{:#?}", hir);
+        println!("=== Types ===");
+        print_types(&hir);
         println!();
+
+        if !checker.inferred_units().is_empty() {
+            println!("=== Inferred Units ===");
+            for inferred in checker.inferred_units() {
+                println!("  {}: {}", inferred.name, inferred.display);
+            }
+            println!();
+        }
     }
 
     // 5. Effect inference
@@ -619,56 +986,283 @@ fn check(
         }
         // Effect errors are warnings for now, not fatal
         for e in &errors {
-            eprintln!("Warning: {}", e);
+            match output {
+                CheckOutput::Human => eprintln!("Warning: {}", e),
+                CheckOutput::Json(out) => out.extend(
+                    JsonDiagnostic::from_diagnostic(&e.to_diagnostic(source_file), source_file)
+                        .into_iter()
+                        .map(|d| JsonDiagnostic {
+                            severity: JsonSeverity::Warning,
+                            ..d
+                        }),
+                ),
+            }
+        }
+    }
+    for warning in effect_checker.warnings() {
+        output.report(warning.to_diagnostic(source_file), source_file);
+    }
+
+    if show_effects {
+        println!("=== Function Effects ===");
+        for item in &resolved.ast.items {
+            if let demetrios::ast::Item::Function(f) = item
+                && let Some(def_id) = resolved.symbols.def_for_node(f.id)
+                && let Some(declared) = effect_checker.get_function_effects(def_id)
+                && let Some(inferred) = effect_checker.get_inferred_effects(def_id)
+            {
+                println!(
+                    "  {}",
+                    effect_row(&effect_checker, &f.name, declared, inferred)
+                );
+            }
         }
-    } else if show_effects {
-        println!("=== Effects ===");
-        println!("  All effects properly declared");
         println!();
     }
 
     // 6. Ownership check
     if !skip_ownership {
         let mut ownership_checker =
-            demetrios::ownership::OwnershipChecker::new(&resolved.symbols, &source_file);
+            demetrios::ownership::OwnershipChecker::new(&resolved.symbols, source_file);
         if let Err(errors) = ownership_checker.check_program(&resolved.ast) {
             for e in &errors {
-                eprintln!("{:?}", miette::Report::new(e.clone()));
+                output.report(e.clone(), source_file);
             }
             return Err(miette::miette!("{} ownership errors found", errors.len()));
         }
     }
 
-    println!("All checks passed: {}", input.display());
     Ok(())
 }
 
-fn run(input: &std::path::Path, args: &[String]) -> Result<()> {
+/// One `--show-effects` line: the effects `name` declares, the effects its
+/// body performs, and any disagreement between the two
+fn effect_row(
+    checker: &demetrios::effects::EffectChecker,
+    name: &str,
+    declared: &demetrios::types::core::EffectSet,
+    inferred: &demetrios::effects::InferredEffects,
+) -> String {
+    use demetrios::types::core::EffectSet;
+
+    let braces = |set: &EffectSet| {
+        if set.effects.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{{}}}", checker.display_effects(set))
+        }
+    };
+    let missing_from = |set: &EffectSet, other: &EffectSet| {
+        let mut missing: Vec<_> = set
+            .effects
+            .iter()
+            .filter(|e| !other.effects.contains(*e))
+            .cloned()
+            .collect();
+        missing.sort();
+        missing.join(", ")
+    };
+
+    let mut row = format!(
+        "fn {}: declared {}, inferred {}",
+        name,
+        braces(declared),
+        braces(&inferred.effects)
+    );
+    let undeclared = missing_from(&inferred.effects, declared);
+    if !undeclared.is_empty() {
+        row.push_str(&format!("  (undeclared: {})", undeclared));
+    }
+    if inferred.complete {
+        let unused = missing_from(declared, &inferred.effects);
+        if !unused.is_empty() {
+            row.push_str(&format!("  (over-declared: {})", unused));
+        }
+    } else {
+        row.push_str("  (calls with unknown effects)");
+    }
+    row
+}
+
+/// Print the signature of every item in `hir`, and the type of each `let`
+/// binding under the function that declares it
+fn print_types(hir: &demetrios::hir::Hir) {
+    use demetrios::hir::visit::{HirVisitor, walk_stmt};
+    use demetrios::hir::{HirFn, HirItem, HirStmt};
+
+    struct Lets(Vec<String>);
+
+    impl HirVisitor for Lets {
+        fn visit_stmt(&mut self, stmt: &HirStmt) {
+            if let HirStmt::Let { name, ty, .. } = stmt {
+                self.0.push(format!("let {}: {}", name, ty));
+            }
+            walk_stmt(self, stmt);
+        }
+    }
+
+    fn print_fn(func: &HirFn, indent: &str) {
+        let params: Vec<_> = func
+            .ty
+            .params
+            .iter()
+            .map(|p| format!("{}: {}", p.name, p.ty))
+            .collect();
+        println!(
+            "{}fn {}({}) -> {}",
+            indent,
+            func.name,
+            params.join(", "),
+            func.ty.return_type
+        );
+        let mut lets = Lets(Vec::new());
+        lets.visit_fn(func);
+        for binding in lets.0 {
+            println!("{}    {}", indent, binding);
+        }
+    }
+
+    let list = |types: &[demetrios::hir::HirType]| {
+        types
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    for item in &hir.items {
+        match item {
+            HirItem::Function(func) => print_fn(func, ""),
+            HirItem::Struct(s) => {
+                let fields: Vec<_> = s
+                    .fields
+                    .iter()
+                    .map(|f| format!("{}: {}", f.name, f.ty))
+                    .collect();
+                println!("struct {} {{ {} }}", s.name, fields.join(", "));
+            }
+            HirItem::Enum(e) => {
+                let variants: Vec<_> = e
+                    .variants
+                    .iter()
+                    .map(|v| match v.fields.as_slice() {
+                        [] => v.name.clone(),
+                        fields => format!("{}({})", v.name, list(fields)),
+                    })
+                    .collect();
+                println!("enum {} {{ {} }}", e.name, variants.join(", "));
+            }
+            HirItem::Impl(i) => {
+                match &i.trait_ref {
+                    Some(tr) => println!("impl {} for {}", tr, i.self_ty),
+                    None => println!("impl {}", i.self_ty),
+                }
+                for method in &i.methods {
+                    print_fn(method, "    ");
+                }
+            }
+            HirItem::TypeAlias(a) => println!("type {} = {}", a.name, a.ty),
+            HirItem::Global(g) => {
+                let keyword = if g.is_const { "const" } else { "static" };
+                println!("{} {}: {}", keyword, g.name, g.ty);
+            }
+            HirItem::Effect(e) => {
+                println!("effect {}", e.name);
+                for op in &e.operations {
+                    println!(
+                        "    fn {}({}) -> {}",
+                        op.name,
+                        list(&op.params),
+                        op.return_type
+                    );
+                }
+            }
+            HirItem::Trait(t) => println!("trait {}", t.name),
+            HirItem::Handler(h) => println!("handler {} for {}", h.name, h.effect),
+        }
+    }
+}
+
+/// Check `input` now and again after every save, clearing the screen
+/// between runs. Diagnostics are printed rather than ending the session.
+fn watch_check(input: &std::path::Path, run_check: impl Fn() -> Result<()>) -> Result<()> {
+    let report = || {
+        if let Err(e) = run_check() {
+            eprintln!("{:?}", e);
+        }
+        println!("Watching {} for changes...", input.display());
+    };
+
+    report();
+    demetrios::watch::watch_files(
+        &[input.to_path_buf()],
+        demetrios::watch::DEFAULT_DEBOUNCE,
+        || {
+            // Clear the screen and move the cursor home
+            print!("\x1B[2J\x1B[H");
+            report();
+        },
+    )
+}
+
+/// Interpret a program and return its exit code. An integer returned by
+/// `main` is the exit code, and a `Result` maps `Ok` to 0 and `Err` to 1;
+/// these are only printed with `--verbose`. Any other value is printed and
+/// the program exits successfully.
+fn run(
+    input: &std::path::Path,
+    wrapping: bool,
+    seed: Option<u64>,
+    cfg: &[String],
+    args: &[String],
+    verbose: bool,
+) -> Result<i32> {
     tracing::info!("Running {:?} with args {:?}", input, args);
 
     let source = std::fs::read_to_string(input)
         .map_err(|e| miette::miette!("Failed to read input file: {}", e))?;
 
     let tokens = demetrios::lexer::lex(&source)?;
-    let ast = demetrios::parser::parse(&tokens, &source)?;
+    let mut ast = demetrios::parser::parse(&tokens, &source)?;
+    demetrios::ast::cfg::configure(&mut ast, cfg);
     let hir = demetrios::check::check(&ast)?;
 
-    // Use tree-walking interpreter
-    let mut interpreter = demetrios::interp::Interpreter::new();
-    match interpreter.interpret(&hir) {
-        Ok(result) => {
-            // Only print non-unit results
-            match &result {
-                demetrios::interp::Value::Unit => {}
-                _ => println!("{}", result),
-            }
-            Ok(())
+    // Use tree-walking interpreter; overflow is an error unless asked otherwise
+    let overflow_mode = if wrapping {
+        demetrios::interp::OverflowMode::Wrapping
+    } else {
+        demetrios::interp::OverflowMode::Checked
+    };
+    let mut interpreter = demetrios::interp::Interpreter::new().with_overflow_mode(overflow_mode);
+    if let Some(seed) = seed {
+        interpreter = interpreter.with_seed(seed);
+    }
+    let result = interpreter.interpret(&hir)?;
+    let code = match &result {
+        demetrios::interp::Value::Int(code) => *code as i32,
+        demetrios::interp::Value::Ok(_) => 0,
+        demetrios::interp::Value::Err(err) => {
+            eprintln!("Error: {}", err);
+            1
+        }
+        demetrios::interp::Value::Unit => return Ok(0),
+        _ => {
+            println!("{}", result);
+            return Ok(0);
         }
-        Err(e) => Err(e),
+    };
+    if verbose {
+        println!("{}", result);
     }
+    Ok(code)
 }
 
-fn jit_run(input: &std::path::Path, optimize: bool, _args: &[String]) -> Result<()> {
+fn jit_run(
+    input: &std::path::Path,
+    optimize: bool,
+    guard_steps: Option<u64>,
+    _args: &[String],
+) -> Result<()> {
     #[cfg(feature = "jit")]
     {
         tracing::info!("JIT compiling {:?} (optimize={})", input, optimize);
@@ -679,7 +1273,8 @@ fn jit_run(input: &std::path::Path, optimize: bool, _args: &[String]) -> Result<
         let tokens = demetrios::lexer::lex(&source)?;
         let ast = demetrios::parser::parse(&tokens, &source)?;
         let hir = demetrios::check::check(&ast)?;
-        let hlir = demetrios::hlir::lower(&hir);
+        let options = demetrios::hlir::LowerOptions { guard_steps };
+        let hlir = demetrios::hlir::lower_with(&hir, &options)?;
 
         let jit = if optimize {
             demetrios::codegen::cranelift::CraneliftJit::new().with_optimization()
@@ -698,7 +1293,7 @@ fn jit_run(input: &std::path::Path, optimize: bool, _args: &[String]) -> Result<
 
     #[cfg(not(feature = "jit"))]
     {
-        let _ = (input, optimize); // Suppress unused warnings
+        let _ = (input, optimize, guard_steps); // Suppress unused warnings
         Err(miette::miette!(
             "JIT backend not enabled. Recompile with --features jit"
         ))
@@ -750,7 +1345,7 @@ fn bench(input: &std::path::Path, iterations: u32) -> Result<()> {
     // Benchmark JIT if available
     #[cfg(feature = "jit")]
     {
-        let hlir = demetrios::hlir::lower(&hir);
+        let hlir = demetrios::hlir::lower(&hir)?;
         let jit = demetrios::codegen::cranelift::CraneliftJit::new();
 
         // Compile once
@@ -799,14 +1394,38 @@ fn bench(input: &std::path::Path, iterations: u32) -> Result<()> {
 }
 
 fn format_code(path: &std::path::Path, check: bool) -> Result<()> {
-    if check {
-        println!("Checking formatting of {:?}", path);
-    } else {
-        println!("Formatting {:?}", path);
+    let files = demetrios::fmt::source_files(path)
+        .map_err(|e| miette::miette!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut unformatted = 0;
+    for file in &files {
+        let source = std::fs::read_to_string(file)
+            .map_err(|e| miette::miette!("Failed to read {}: {}", file.display(), e))?;
+        let formatted = demetrios::fmt::format_source(&source)
+            .map_err(|e| miette::miette!("Failed to format {}: {}", file.display(), e))?;
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("Diff in {}:", file.display());
+            print!("{}", demetrios::fmt::diff(&source, &formatted));
+            unformatted += 1;
+        } else {
+            std::fs::write(file, &formatted)
+                .map_err(|e| miette::miette!("Failed to write {}: {}", file.display(), e))?;
+            println!("Formatted {}", file.display());
+        }
     }
 
-    // TODO: Implement formatter
-    Err(miette::miette!("Formatter not yet implemented"))
+    if unformatted > 0 {
+        return Err(miette::miette!(
+            "{} of {} file(s) need formatting",
+            unformatted,
+            files.len()
+        ));
+    }
+    Ok(())
 }
 
 fn doc(open: bool, document_private: bool) -> Result<()> {
@@ -829,6 +1448,13 @@ fn doc_coverage() -> Result<()> {
         .map_err(|e| miette::miette!("Coverage calculation failed: {}", e))
 }
 
+fn explain(code: &str) -> Result<()> {
+    let entry = demetrios::diagnostics::explain(code)
+        .ok_or_else(|| miette::miette!("Unknown error code `{}`", code))?;
+    print!("{}", entry);
+    Ok(())
+}
+
 fn info() -> Result<()> {
     println!("Demetrios (D) Compiler");
     println!("Version: {}", env!("CARGO_PKG_VERSION"));
@@ -3,12 +3,12 @@
 //! Checks ownership, borrowing, and linearity rules.
 
 use crate::ast::{self, Ast, BinaryOp, Expr, Item, Stmt, TypeExpr, UnaryOp};
-use crate::common::Span;
+use crate::common::{NodeId, Span};
 use crate::diagnostics::{CompileError, SourceFile};
-use crate::resolve::{DefId, SymbolTable};
+use crate::resolve::{DefId, DefKind, SymbolTable};
 
 use super::state::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Ownership and borrow checker
 pub struct OwnershipChecker<'a> {
@@ -18,6 +18,19 @@ pub struct OwnershipChecker<'a> {
     scopes: Vec<ScopeState>,
     /// Type linearity cache (for structs)
     linearity_cache: HashMap<DefId, Linearity>,
+    /// Source spans of expression nodes
+    spans: HashMap<NodeId, Span>,
+    /// Source offset of the last use of each binding, used to decide
+    /// whether a reference stored in it is still live
+    last_use: HashMap<DefId, usize>,
+    /// Bindings whose declared type is not Copy
+    non_copy: HashSet<DefId>,
+    /// Parameters and locals declared with a reference type. Borrowing
+    /// through them does not borrow the current function's frame.
+    ref_bindings: HashSet<DefId>,
+    /// Locals holding a reference to another local, with the span of the
+    /// `&local` expression and the name of the referent
+    local_refs: HashMap<DefId, (Span, String)>,
     /// Errors
     errors: Vec<CompileError>,
 }
@@ -29,6 +42,11 @@ impl<'a> OwnershipChecker<'a> {
             source,
             scopes: vec![ScopeState::new()],
             linearity_cache: HashMap::new(),
+            spans: HashMap::new(),
+            last_use: HashMap::new(),
+            non_copy: HashSet::new(),
+            ref_bindings: HashSet::new(),
+            local_refs: HashMap::new(),
             errors: Vec::new(),
         }
     }
@@ -51,10 +69,27 @@ impl<'a> OwnershipChecker<'a> {
             }
         }
 
+        // Approximate borrow lifetimes by the last use of each binding
+        self.spans = ast.node_spans.clone();
+        for (node, def_id) in self.symbols.refs() {
+            if let Some(span) = self.spans.get(&node) {
+                let last = self.last_use.entry(def_id).or_insert(span.start);
+                *last = (*last).max(span.start);
+            }
+        }
+
         // Check functions
         for item in &ast.items {
-            if let Item::Function(f) = item {
-                self.check_function(f);
+            match item {
+                Item::Function(f) => self.check_function(f),
+                Item::Impl(i) => {
+                    for item in &i.items {
+                        if let ast::ImplItem::Fn(f) = item {
+                            self.check_function(f);
+                        }
+                    }
+                }
+                _ => {}
             }
         }
 
@@ -74,11 +109,24 @@ impl<'a> OwnershipChecker<'a> {
                 let linearity = self.get_type_linearity(&param.ty);
                 let name = self.get_pattern_name(&param.pattern);
                 self.track_value(def_id, name, linearity, get_param_span(param));
+                if !is_copy_type(&param.ty) {
+                    self.non_copy.insert(def_id);
+                }
+                if matches!(param.ty, TypeExpr::Reference { .. }) {
+                    self.ref_bindings.insert(def_id);
+                }
             }
         }
 
         // Check body
         self.check_block(&f.body);
+        if let Some(Stmt::Expr {
+            expr,
+            has_semi: false,
+        }) = f.body.stmts.last()
+        {
+            self.check_escape(expr);
+        }
 
         // Check linear values consumed
         self.check_scope_end(f.span);
@@ -102,10 +150,25 @@ impl<'a> OwnershipChecker<'a> {
                 pattern, ty, value, ..
             } => {
                 // Check initializer first
+                let first_borrow = self.current_scope().borrow_count();
                 if let Some(init) = value {
                     self.check_expr(init, UseKind::Move);
                 }
 
+                // References created by the initializer live as long as the binding
+                if let Some(def_id) = self.binding_def_id(pattern) {
+                    self.current_scope().bind_borrows(first_borrow, def_id);
+                    if ty.as_ref().is_some_and(|t| !is_copy_type(t)) {
+                        self.non_copy.insert(def_id);
+                    }
+                    if matches!(ty, Some(TypeExpr::Reference { .. })) {
+                        self.ref_bindings.insert(def_id);
+                    }
+                    if let Some(origin) = value.as_ref().and_then(|v| self.local_borrow(v)) {
+                        self.local_refs.insert(def_id, origin);
+                    }
+                }
+
                 // Track the binding
                 let name = self.get_pattern_name(pattern);
                 if let Some(def_id) = self.get_pattern_def_id(pattern) {
@@ -118,6 +181,13 @@ impl<'a> OwnershipChecker<'a> {
                 }
             }
 
+            Stmt::LetElse {
+                value, else_block, ..
+            } => {
+                self.check_expr(value, UseKind::Move);
+                self.check_block(else_block);
+            }
+
             Stmt::Expr { expr, .. } => {
                 self.check_expr(expr, UseKind::Move);
             }
@@ -125,10 +195,20 @@ impl<'a> OwnershipChecker<'a> {
             Stmt::Assign { target, value, .. } => {
                 self.check_expr(value, UseKind::Move);
                 // Target is being written to, not consumed
+
+                // Writing through a reference parameter outlives the call
+                if self
+                    .expr_to_place(target)
+                    .is_some_and(|p| self.ref_bindings.contains(&p.base) || p.is_deref())
+                {
+                    self.check_escape(value);
+                }
             }
 
             Stmt::Empty => {}
         }
+
+        self.current_scope().end_temporary_borrows();
     }
 
     fn check_expr(&mut self, expr: &Expr, use_kind: UseKind) {
@@ -138,7 +218,11 @@ impl<'a> OwnershipChecker<'a> {
             Expr::Path { path, id } => {
                 if path.is_simple() {
                     if let Some(def_id) = self.symbols.ref_for_node(*id) {
-                        self.use_value(def_id, use_kind, get_expr_span(expr));
+                        let span = self.expr_span(expr);
+                        if use_kind == UseKind::Move && self.non_copy.contains(&def_id) {
+                            self.check_move_while_borrowed(def_id, span);
+                        }
+                        self.use_value(def_id, use_kind, span);
                     }
                 }
             }
@@ -166,13 +250,13 @@ impl<'a> OwnershipChecker<'a> {
                     UnaryOp::Ref => {
                         // Shared borrow
                         if let Some(place) = self.expr_to_place(inner) {
-                            self.borrow_shared(place, get_expr_span(expr));
+                            self.borrow_shared(place, self.expr_span(expr));
                         }
                     }
                     UnaryOp::RefMut => {
                         // Exclusive borrow (&!)
                         if let Some(place) = self.expr_to_place(inner) {
-                            self.borrow_exclusive(place, get_expr_span(expr));
+                            self.borrow_exclusive(place, self.expr_span(expr));
                         }
                     }
                     UnaryOp::Deref => {
@@ -266,9 +350,15 @@ impl<'a> OwnershipChecker<'a> {
                 self.pop_scope();
             }
 
+            Expr::Range { start, end, .. } => {
+                self.check_expr(start, UseKind::Copy);
+                self.check_expr(end, UseKind::Copy);
+            }
+
             Expr::Return { value, .. } => {
                 if let Some(val) = value {
                     self.check_expr(val, UseKind::Move);
+                    self.check_escape(val);
                 }
             }
 
@@ -297,6 +387,10 @@ impl<'a> OwnershipChecker<'a> {
                 }
             }
 
+            Expr::ArrayRepeat { value, .. } => {
+                self.check_expr(value, use_kind);
+            }
+
             Expr::StructLit { fields, .. } => {
                 for (_, field_expr) in fields {
                     self.check_expr(field_expr, UseKind::Move);
@@ -372,21 +466,38 @@ impl<'a> OwnershipChecker<'a> {
         }
     }
 
+    /// Whether a borrow is still live at `at`: temporaries last until the
+    /// end of their statement, stored references until their holder's last use
+    fn borrow_is_live(&self, borrow: &BorrowState, at: Span) -> bool {
+        match borrow.holder {
+            None => true,
+            Some(holder) => self
+                .last_use
+                .get(&holder)
+                .is_some_and(|&last| last > at.start),
+        }
+    }
+
+    /// First live borrow overlapping `place`, as (span, exclusive)
+    fn live_borrow(&self, place: &Place, at: Span, exclusive_only: bool) -> Option<(Span, bool)> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| scope.overlapping_borrows(place))
+            .filter(|b| !exclusive_only || b.exclusive)
+            .find(|b| self.borrow_is_live(b, at))
+            .map(|b| (b.span, b.exclusive))
+    }
+
     fn borrow_shared(&mut self, place: Place, span: Span) {
-        // Check if exclusively borrowed
-        for scope in &self.scopes {
-            let active = scope.active_borrows(&place);
-            for borrow in active {
-                if borrow.exclusive {
-                    self.errors.push(CompileError::AlreadyBorrowed {
-                        name: place.to_string(),
-                        span: span.into(),
-                        prev_span: borrow.span.into(),
-                        src: self.source.to_named_source(),
-                    });
-                    return;
-                }
-            }
+        // A shared borrow may not overlap a live exclusive borrow
+        if let Some((mut_span, _)) = self.live_borrow(&place, span, true) {
+            self.errors.push(CompileError::AlreadyMutablyBorrowed {
+                name: self.place_name(&place),
+                span: span.into(),
+                mut_span: mut_span.into(),
+                src: self.source.to_named_source(),
+            });
+            return;
         }
 
         // Add borrow
@@ -395,32 +506,30 @@ impl<'a> OwnershipChecker<'a> {
             exclusive: false,
             span,
             active: true,
+            holder: None,
         });
     }
 
     fn borrow_exclusive(&mut self, place: Place, span: Span) {
-        // Check if any borrows exist
-        for scope in &self.scopes {
-            let active = scope.active_borrows(&place);
-            if !active.is_empty() {
-                let prev = active[0];
-                if prev.exclusive {
-                    self.errors.push(CompileError::DoubleMutBorrow {
-                        name: place.to_string(),
-                        span: span.into(),
-                        first_span: prev.span.into(),
-                        src: self.source.to_named_source(),
-                    });
-                } else {
-                    self.errors.push(CompileError::AlreadyBorrowed {
-                        name: place.to_string(),
-                        span: span.into(),
-                        prev_span: prev.span.into(),
-                        src: self.source.to_named_source(),
-                    });
-                }
-                return;
+        // An exclusive borrow may not overlap any live borrow
+        if let Some((prev_span, exclusive)) = self.live_borrow(&place, span, false) {
+            let name = self.place_name(&place);
+            if exclusive {
+                self.errors.push(CompileError::DoubleMutBorrow {
+                    name,
+                    span: span.into(),
+                    first_span: prev_span.into(),
+                    src: self.source.to_named_source(),
+                });
+            } else {
+                self.errors.push(CompileError::AlreadyBorrowed {
+                    name,
+                    span: span.into(),
+                    prev_span: prev_span.into(),
+                    src: self.source.to_named_source(),
+                });
             }
+            return;
         }
 
         // Add borrow
@@ -429,9 +538,99 @@ impl<'a> OwnershipChecker<'a> {
             exclusive: true,
             span,
             active: true,
+            holder: None,
         });
     }
 
+    fn check_move_while_borrowed(&mut self, def_id: DefId, span: Span) {
+        let place = Place::var(def_id);
+        if let Some((borrow_span, _)) = self.live_borrow(&place, span, false) {
+            self.errors.push(CompileError::MoveWhileBorrowed {
+                name: self.place_name(&place),
+                span: span.into(),
+                borrow_span: borrow_span.into(),
+                src: self.source.to_named_source(),
+            });
+        }
+    }
+
+    /// Report `expr` if it is a reference into the current function's
+    /// frame, which would dangle once the function returns
+    fn check_escape(&mut self, expr: &Expr) {
+        if let Some((span, name)) = self.local_borrow(expr) {
+            self.errors.push(CompileError::DanglingReference {
+                name,
+                span: span.into(),
+                src: self.source.to_named_source(),
+            });
+        }
+    }
+
+    /// The `&local` expression `expr` evaluates to, if any, with the name
+    /// of the borrowed local. Conservative: references through parameters
+    /// and locals of reference type are assumed to point outside the frame.
+    fn local_borrow(&self, expr: &Expr) -> Option<(Span, String)> {
+        match expr {
+            Expr::Unary {
+                op: UnaryOp::Ref | UnaryOp::RefMut,
+                expr: inner,
+                ..
+            } => {
+                let place = self.expr_to_place(inner)?;
+                let local = matches!(
+                    self.symbols.get(place.base)?.kind,
+                    DefKind::Variable { .. } | DefKind::Parameter { .. }
+                );
+                (local && !place.is_deref() && !self.ref_bindings.contains(&place.base))
+                    .then(|| (self.expr_span(expr), self.place_name(&place)))
+            }
+            Expr::Path { path, id } if path.is_simple() => {
+                let def_id = self.symbols.ref_for_node(*id)?;
+                self.local_refs.get(&def_id).cloned()
+            }
+            Expr::Block { block, .. } => self.tail_borrow(block),
+            Expr::If {
+                then_branch,
+                else_branch,
+                ..
+            } => self
+                .tail_borrow(then_branch)
+                .or_else(|| else_branch.as_ref().and_then(|e| self.local_borrow(e))),
+            Expr::Match { arms, .. } => arms.iter().find_map(|arm| self.local_borrow(&arm.body)),
+            _ => None,
+        }
+    }
+
+    fn tail_borrow(&self, block: &ast::Block) -> Option<(Span, String)> {
+        match block.stmts.last()? {
+            Stmt::Expr {
+                expr,
+                has_semi: false,
+            } => self.local_borrow(expr),
+            _ => None,
+        }
+    }
+
+    /// Render a place with source names, e.g. `p.x`
+    fn place_name(&self, place: &Place) -> String {
+        let mut name = self
+            .symbols
+            .get(place.base)
+            .map(|sym| sym.name.clone())
+            .unwrap_or_else(|| place.to_string());
+        for proj in &place.projections {
+            match proj {
+                Projection::Field(field) => {
+                    name.push('.');
+                    name.push_str(field);
+                }
+                Projection::Index => name.push_str("[_]"),
+                Projection::Deref => name.insert(0, '*'),
+            }
+        }
+        name
+    }
+
     fn check_scope_end(&mut self, scope_end_span: Span) {
         let errors = self.current_scope().check_all_linear();
 
@@ -464,6 +663,13 @@ impl<'a> OwnershipChecker<'a> {
         }
     }
 
+    fn expr_span(&self, expr: &Expr) -> Span {
+        self.spans
+            .get(&expr.id())
+            .copied()
+            .unwrap_or_else(Span::dummy)
+    }
+
     fn get_type_linearity(&self, ty: &TypeExpr) -> Linearity {
         match ty {
             TypeExpr::Named { path, .. } => {
@@ -521,11 +727,20 @@ impl<'a> OwnershipChecker<'a> {
         }
     }
 
+    /// DefId the resolver assigned to a binding pattern
+    fn binding_def_id(&self, pattern: &ast::Pattern) -> Option<DefId> {
+        match pattern {
+            ast::Pattern::Binding { id, .. } => self.symbols.def_for_node(*id),
+            _ => None,
+        }
+    }
+
     fn get_pattern_def_id(&self, pattern: &ast::Pattern) -> Option<DefId> {
         match pattern {
             ast::Pattern::Binding { name, .. } => {
-                // Look up the binding in symbols
-                // This is a simplification - in practice we'd have NodeId on patterns
+                // Still looked up by name: switching linearity tracking to
+                // `binding_def_id` would enforce must-consume on let-bound
+                // linear values, which is not enabled yet
                 self.symbols.lookup(name)
             }
             _ => None,
@@ -539,9 +754,36 @@ enum UseKind {
     Copy,
 }
 
-fn get_expr_span(_expr: &Expr) -> Span {
-    // TODO: get actual span from expression
-    Span::dummy()
+/// Whether values of a declared type are copied rather than moved
+fn is_copy_type(ty: &TypeExpr) -> bool {
+    match ty {
+        TypeExpr::Unit => true,
+        TypeExpr::Named { path, .. } => matches!(
+            path.name(),
+            Some(
+                "i8" | "i16"
+                    | "i32"
+                    | "i64"
+                    | "i128"
+                    | "isize"
+                    | "u8"
+                    | "u16"
+                    | "u32"
+                    | "u64"
+                    | "u128"
+                    | "usize"
+                    | "f32"
+                    | "f64"
+                    | "bool"
+                    | "char"
+            )
+        ),
+        TypeExpr::Reference { mutable, .. } => !mutable,
+        TypeExpr::Tuple(elems) => elems.iter().all(is_copy_type),
+        TypeExpr::Array { element, size } => size.is_some() && is_copy_type(element),
+        TypeExpr::Function { .. } => true,
+        TypeExpr::SelfType | TypeExpr::Dyn { .. } | TypeExpr::Infer | TypeExpr::Const(_) => false,
+    }
 }
 
 fn get_pattern_span(_pattern: &ast::Pattern) -> Span {
@@ -45,6 +45,22 @@ impl Place {
         self.projections.push(Projection::Deref);
         self
     }
+
+    /// Whether the place is reached through a dereference
+    pub fn is_deref(&self) -> bool {
+        self.projections.contains(&Projection::Deref)
+    }
+
+    /// Whether two places can refer to overlapping memory: the same base,
+    /// with one projection path a prefix of the other (`x` and `x.a`)
+    pub fn overlaps(&self, other: &Place) -> bool {
+        self.base == other.base
+            && self
+                .projections
+                .iter()
+                .zip(&other.projections)
+                .all(|(a, b)| a == b)
+    }
 }
 
 impl std::fmt::Display for Place {
@@ -118,6 +134,9 @@ pub struct BorrowState {
     pub span: Span,
     /// Is the borrow still active?
     pub active: bool,
+    /// Binding the reference is stored in; the borrow lasts until the
+    /// holder's last use. Temporary borrows end with their statement.
+    pub holder: Option<DefId>,
 }
 
 /// Linear/affine tracking
@@ -258,6 +277,39 @@ impl ScopeState {
         }
     }
 
+    /// Number of borrows recorded in this scope
+    pub fn borrow_count(&self) -> usize {
+        self.borrows.len()
+    }
+
+    /// Attach the temporary borrows recorded since `start` to `holder`
+    pub fn bind_borrows(&mut self, start: usize, holder: DefId) {
+        for borrow in &mut self.borrows[start..] {
+            if borrow.holder.is_none() {
+                borrow.holder = Some(holder);
+            }
+        }
+    }
+
+    /// End every borrow that is not held by a binding
+    pub fn end_temporary_borrows(&mut self) {
+        for borrow in &mut self.borrows {
+            if borrow.holder.is_none() {
+                borrow.active = false;
+            }
+        }
+    }
+
+    /// Active borrows of any place overlapping `place`
+    pub fn overlapping_borrows<'s>(
+        &'s self,
+        place: &'s Place,
+    ) -> impl Iterator<Item = &'s BorrowState> + 's {
+        self.borrows
+            .iter()
+            .filter(move |b| b.active && b.place.overlaps(place))
+    }
+
     pub fn active_borrows(&self, place: &Place) -> Vec<&BorrowState> {
         self.borrows
             .iter()
@@ -4,32 +4,148 @@
 
 use crate::ast::*;
 use crate::common::{IdGenerator, NodeId, Span};
+use crate::diagnostics::CompileError;
 use crate::lexer::{Token, TokenKind};
-use miette::Result;
+use miette::{Diagnostic, NamedSource, Result};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// Every malformed item found in one parse, each reported with its own
+/// code and label
+#[derive(Error, Debug, Diagnostic)]
+#[error("{} parse errors", errors.len())]
+pub struct ParseErrors {
+    #[related]
+    pub errors: Vec<miette::Report>,
+}
 
 /// Parse a token stream into an AST
-pub fn parse(tokens: &[Token], _source: &str) -> Result<Ast> {
-    let mut parser = Parser::new(tokens);
-    parser.parse_program()
+///
+/// Parsing recovers at item boundaries, so every malformed item is reported
+/// in one pass rather than only the first.
+pub fn parse(tokens: &[Token], source: &str) -> Result<Ast> {
+    let (tokens, docs, module_doc) = split_doc_comments(tokens);
+    let mut parser = Parser::new(&tokens, source);
+    parser.docs = docs;
+    let mut ast = parser.parse_program()?;
+    ast.doc = module_doc;
+    Ok(ast)
+}
+
+/// Separate doc comments from the token stream.
+///
+/// Returns the remaining tokens, the outer doc text keyed by the index of
+/// the token it precedes, and the joined inner (`//!`) doc text.
+fn split_doc_comments(tokens: &[Token]) -> (Vec<Token>, HashMap<usize, String>, Option<String>) {
+    let mut rest = Vec::with_capacity(tokens.len());
+    let mut docs: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut inner = Vec::new();
+
+    for token in tokens {
+        match token.kind {
+            TokenKind::DocCommentOuter | TokenKind::DocBlockOuter => docs
+                .entry(rest.len())
+                .or_default()
+                .push(doc_text(&token.text)),
+            TokenKind::DocCommentInner | TokenKind::DocBlockInner => {
+                inner.push(doc_text(&token.text))
+            }
+            _ => rest.push(token.clone()),
+        }
+    }
+
+    let docs = docs
+        .into_iter()
+        .map(|(pos, lines)| (pos, lines.join("\n")))
+        .collect();
+    let module_doc = (!inner.is_empty()).then(|| inner.join("\n"));
+    (rest, docs, module_doc)
+}
+
+/// Strip the comment markers from a doc comment
+fn doc_text(comment: &str) -> String {
+    if let Some(line) = comment
+        .strip_prefix("///")
+        .or_else(|| comment.strip_prefix("//!"))
+    {
+        return line.strip_prefix(' ').unwrap_or(line).to_string();
+    }
+
+    let body = comment
+        .strip_prefix("/**")
+        .or_else(|| comment.strip_prefix("/*!"))
+        .and_then(|c| c.strip_suffix("*/"))
+        .unwrap_or(comment);
+    let lines: Vec<&str> = body
+        .lines()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect();
+    // Drop the blank lines left by the opening and closing markers
+    let start = lines
+        .iter()
+        .position(|l| !l.is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(start, |i| i + 1);
+    lines[start..end].join("\n")
+}
+
+/// Attach outer doc text to a parsed item
+fn attach_doc(item: &mut Item, doc: Option<String>) {
+    let slot = match item {
+        Item::Function(f) => &mut f.doc,
+        Item::Struct(s) => &mut s.doc,
+        Item::Enum(e) => &mut e.doc,
+        Item::Trait(t) => &mut t.doc,
+        Item::TypeAlias(t) => &mut t.doc,
+        Item::Effect(e) => &mut e.doc,
+        Item::Handler(h) => &mut h.doc,
+        Item::UnitDef(u) => &mut u.doc,
+        Item::Global(g) => &mut g.doc,
+        _ => return,
+    };
+    *slot = doc;
 }
 
 /// Parser state
 struct Parser<'a> {
     tokens: &'a [Token],
+    /// Source text, used to find item boundaries when recovering from errors
+    source: &'a str,
     pos: usize,
     id_gen: IdGenerator,
     /// When false, don't parse `Ident { ... }` as a struct literal
     /// This is needed to resolve ambiguity in contexts like `match x { ... }`
     allow_struct_literals: bool,
+    /// Source spans of parsed expressions
+    node_spans: HashMap<NodeId, Span>,
+    /// Set when a `>>` closed one generic argument list and its second `>`
+    /// still has to close the enclosing one (`Vec<Vec<i64>>`)
+    split_shr: bool,
+    /// Outer doc comment text, keyed by the index of the token it precedes
+    docs: HashMap<usize, String>,
+    /// Comparisons not wrapped in parentheses, which may not be chained
+    bare_comparisons: HashSet<NodeId>,
 }
 
 impl<'a> Parser<'a> {
-    fn new(tokens: &'a [Token]) -> Self {
+    fn new(tokens: &'a [Token], source: &'a str) -> Self {
         Self {
             tokens,
+            source,
             pos: 0,
             id_gen: IdGenerator::new(),
             allow_struct_literals: true,
+            node_spans: HashMap::new(),
+            split_shr: false,
+            docs: HashMap::new(),
+            bare_comparisons: HashSet::new(),
         }
     }
 
@@ -77,19 +193,107 @@ impl<'a> Parser<'a> {
         if self.at(kind) {
             Ok(self.advance())
         } else {
-            Err(miette::miette!(
-                "Expected {:?}, found {:?} at position {}",
-                kind,
-                self.peek(),
-                self.current().span.start
-            ))
+            Err(self.unexpected(format!("`{}`", kind)))
         }
     }
 
+    /// Error for the current token where `expected` should have been,
+    /// labelling the token in the source
+    fn unexpected(&self, expected: impl Into<String>) -> miette::Report {
+        let token = self.current();
+        let found = if token.kind == TokenKind::Eof {
+            "end of file".to_string()
+        } else {
+            format!("`{}`", token.text)
+        };
+        CompileError::UnexpectedToken {
+            expected: expected.into(),
+            found,
+            span: token.span.into(),
+            src: NamedSource::new("<input>", self.source.to_string()),
+        }
+        .into()
+    }
+
     fn span(&self) -> Span {
         self.current().span
     }
 
+    /// Consume an integer literal token: decimal, or `0x`, `0o` or `0b`
+    /// prefixed, with `_` separators
+    fn int_literal(&mut self) -> Result<u128> {
+        let token = self.advance().clone();
+        let digits = token.text.replace('_', "");
+        let (digits, radix) = match token.kind {
+            TokenKind::HexLit => (&digits[2..], 16),
+            TokenKind::OctLit => (&digits[2..], 8),
+            TokenKind::BinLit => (&digits[2..], 2),
+            _ => (&digits[..], 10),
+        };
+        u128::from_str_radix(digits, radix).map_err(|_| self.literal_too_large(token.span, ""))
+    }
+
+    /// Error for an integer literal that does not fit its type
+    fn literal_too_large(&self, span: Span, context: &str) -> miette::Report {
+        let message = format!("integer literal too large {}", context);
+        CompileError::SyntaxError {
+            message: message.trim_end().to_string(),
+            span: span.into(),
+            src: NamedSource::new("<input>", self.source.to_string()),
+            suggestion: None,
+        }
+        .into()
+    }
+
+    /// Take the doc comments preceding the current token, merged with `doc`
+    /// from earlier in the same item (before its attributes)
+    fn take_doc(&mut self, doc: Option<String>) -> Option<String> {
+        match (doc, self.docs.remove(&self.pos)) {
+            (Some(before), Some(after)) => Some(format!("{}\n{}", before, after)),
+            (before, after) => before.or(after),
+        }
+    }
+
+    /// Record the span of `expr`, from `start` to the end of the last consumed token
+    fn record_span(&mut self, expr: &Expr, start: usize) {
+        self.record_node_span(expr.id(), start);
+    }
+
+    /// Record the span of node `id`, from `start` to the end of the last consumed token
+    fn record_node_span(&mut self, id: NodeId, start: usize) {
+        let end = self.tokens[self.pos.saturating_sub(1)].span.end.max(start);
+        self.node_spans.insert(id, Span::new(start, end));
+    }
+
+    /// Build a binding pattern whose span runs from `start` to the last consumed token
+    fn binding_pattern(&mut self, name: String, mutable: bool, start: usize) -> Pattern {
+        let id = self.next_id();
+        self.record_node_span(id, start);
+        Pattern::Binding { id, name, mutable }
+    }
+
+    /// A binding just parsed, or `name @ pattern` if a subpattern follows
+    fn parse_binding_pattern(
+        &mut self,
+        name: String,
+        mutable: bool,
+        start: usize,
+    ) -> Result<Pattern> {
+        if !self.at(TokenKind::At) {
+            return Ok(self.binding_pattern(name, mutable, start));
+        }
+        let id = self.next_id();
+        self.record_node_span(id, start);
+        self.advance();
+        let pattern = Box::new(self.parse_pattern()?);
+        Ok(Pattern::At {
+            id,
+            name,
+            mutable,
+            pattern,
+        })
+    }
+
     // ==================== PROGRAM ====================
 
     fn parse_program(&mut self) -> Result<Ast> {
@@ -104,40 +308,187 @@ impl<'a> Parser<'a> {
             None
         };
 
-        // Parse items
+        // Parse items, recovering from errors at the next item boundary
+        let mut errors = Vec::new();
         while !self.at(TokenKind::Eof) {
-            items.push(self.parse_item()?);
+            let start = self.pos;
+            match self.parse_item() {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    errors.push(err);
+                    if self.pos == start {
+                        self.advance();
+                    }
+                    self.recover_to_item(start);
+                }
+            }
+        }
+
+        if errors.len() == 1 {
+            return Err(errors.remove(0));
+        }
+        if !errors.is_empty() {
+            return Err(ParseErrors { errors }.into());
         }
 
-        Ok(Ast { module_name, items })
+        Ok(Ast {
+            module_name,
+            items,
+            doc: None,
+            node_spans: std::mem::take(&mut self.node_spans),
+        })
+    }
+
+    /// Skip tokens until one that can start an item at the beginning of a
+    /// line, after closing every brace the failed item (starting at token
+    /// `item_start`) left open
+    fn recover_to_item(&mut self, item_start: usize) {
+        let mut depth =
+            self.tokens[item_start..self.pos]
+                .iter()
+                .fold(0usize, |depth, tok| match tok.kind {
+                    TokenKind::LBrace => depth + 1,
+                    TokenKind::RBrace => depth.saturating_sub(1),
+                    _ => depth,
+                });
+        loop {
+            match self.peek() {
+                TokenKind::Eof => return,
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth = depth.saturating_sub(1),
+                TokenKind::Fn
+                | TokenKind::Kernel
+                | TokenKind::Let
+                | TokenKind::Const
+                | TokenKind::Struct
+                | TokenKind::Enum
+                | TokenKind::Trait
+                | TokenKind::Impl
+                | TokenKind::Type
+                | TokenKind::Effect
+                | TokenKind::Handler
+                | TokenKind::Import
+                | TokenKind::Extern
+                | TokenKind::Pub
+                | TokenKind::Hash
+                    if depth == 0 && self.at_line_start() =>
+                {
+                    return;
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Whether the current token is the first on its line
+    fn at_line_start(&self) -> bool {
+        let before = self.source.get(..self.current().span.start).unwrap_or("");
+        before
+            .chars()
+            .rev()
+            .take_while(|c| *c != '\n')
+            .all(char::is_whitespace)
     }
 
     // ==================== ITEMS ====================
 
     fn parse_item(&mut self) -> Result<Item> {
+        let doc = self.take_doc(None);
+        let attributes = self.parse_attributes()?;
+        let doc = self.take_doc(doc);
+
         // Parse visibility
         let visibility = self.parse_visibility();
 
         // Parse modifiers
         let modifiers = self.parse_modifiers();
 
-        match self.peek() {
-            TokenKind::Fn | TokenKind::Kernel => self.parse_fn(visibility, modifiers),
-            TokenKind::Let | TokenKind::Const => self.parse_global(visibility, modifiers),
-            TokenKind::Struct => self.parse_struct(visibility, modifiers),
-            TokenKind::Enum => self.parse_enum(visibility, modifiers),
-            TokenKind::Trait => self.parse_trait(visibility, modifiers),
-            TokenKind::Impl => self.parse_impl(),
-            TokenKind::Type => self.parse_type_alias(visibility),
-            TokenKind::Effect => self.parse_effect(visibility),
-            TokenKind::Handler => self.parse_handler(visibility),
-            TokenKind::Import => self.parse_import(),
-            TokenKind::Extern => self.parse_extern(),
+        let mut item = match self.peek() {
+            TokenKind::Fn | TokenKind::Kernel => self.parse_fn(attributes, visibility, modifiers),
+            TokenKind::Let | TokenKind::Const => {
+                self.parse_global(attributes, visibility, modifiers)
+            }
+            TokenKind::Struct => self.parse_struct(attributes, visibility, modifiers),
+            TokenKind::Enum => self.parse_enum(attributes, visibility, modifiers),
+            TokenKind::Trait => self.parse_trait(attributes, visibility, modifiers),
+            TokenKind::Impl => self.parse_impl(attributes),
+            TokenKind::Type => self.parse_type_alias(attributes, visibility),
+            TokenKind::Effect => self.parse_effect(attributes, visibility),
+            TokenKind::Handler => self.parse_handler(attributes, visibility),
+            // `unit` is only a keyword at the start of an item
+            TokenKind::Ident
+                if self.current().text == "unit" && self.peek_n(1) == TokenKind::Ident =>
+            {
+                self.parse_unit_def(attributes, visibility)
+            }
+            TokenKind::Import => self.parse_import(attributes),
+            TokenKind::Extern => self.parse_extern(attributes),
             _ => Err(miette::miette!(
                 "Unexpected token {:?} at start of item",
                 self.peek()
             )),
+        }?;
+        attach_doc(&mut item, doc);
+        Ok(item)
+    }
+
+    /// Parse `#[name]` / `#[name(args)]` attributes preceding an item
+    fn parse_attributes(&mut self) -> Result<Vec<Attribute>> {
+        let mut attributes = Vec::new();
+
+        while self.at(TokenKind::Hash) {
+            let start = self.span();
+            self.advance();
+            self.expect(TokenKind::LBracket)?;
+            // Keywords double as attribute names, as in `#[gpu]`
+            let name = if self.peek().is_keyword() {
+                self.advance().text.clone()
+            } else {
+                self.parse_ident()?
+            };
+
+            let mut args = Vec::new();
+            if self.at(TokenKind::LParen) {
+                self.advance();
+                let mut depth = 0usize;
+                let mut current = String::new();
+                loop {
+                    match self.peek() {
+                        TokenKind::Eof => return Err(miette::miette!("Unterminated attribute")),
+                        TokenKind::RParen if depth == 0 => break,
+                        TokenKind::Comma if depth == 0 => {
+                            self.advance();
+                            args.push(std::mem::take(&mut current));
+                            continue;
+                        }
+                        TokenKind::LParen => depth += 1,
+                        TokenKind::RParen => depth -= 1,
+                        _ => {}
+                    }
+                    let text = self.advance().text.clone();
+                    // Keep adjacent words apart, e.g. `feature = "gpu"`
+                    let word = |c: char| c.is_alphanumeric() || c == '_' || c == '"';
+                    if current.ends_with(word) && text.starts_with(word) {
+                        current.push(' ');
+                    }
+                    current.push_str(&text);
+                }
+                self.expect(TokenKind::RParen)?;
+                if !current.is_empty() {
+                    args.push(current);
+                }
+            }
+
+            let end = self.expect(TokenKind::RBracket)?.span;
+            attributes.push(Attribute {
+                name,
+                args,
+                span: start.merge(end),
+            });
         }
+
+        Ok(attributes)
     }
 
     fn parse_visibility(&mut self) -> Visibility {
@@ -179,7 +530,12 @@ impl<'a> Parser<'a> {
 
     // ==================== FUNCTIONS ====================
 
-    fn parse_fn(&mut self, visibility: Visibility, modifiers: Modifiers) -> Result<Item> {
+    fn parse_fn(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+        modifiers: Modifiers,
+    ) -> Result<Item> {
         let start = self.span();
 
         let is_kernel = if self.at(TokenKind::Kernel) {
@@ -197,12 +553,13 @@ impl<'a> Parser<'a> {
         let return_type = self.parse_return_type()?;
         let effects = self.parse_effect_clause()?;
         let where_clause = self.parse_where_clause()?;
-        let body = self.parse_block()?;
+        let body = self.parse_fn_body()?;
 
         let end = self.span();
 
         Ok(Item::Function(FnDef {
             id: self.next_id(),
+            attributes,
             visibility,
             modifiers: FnModifiers {
                 is_async: modifiers.is_async,
@@ -217,9 +574,30 @@ impl<'a> Parser<'a> {
             where_clause,
             body,
             span: start.merge(end),
+            doc: None,
         }))
     }
 
+    /// Function body: a block, or `= expr;`, which desugars to a block whose
+    /// trailing expression is `expr`
+    fn parse_fn_body(&mut self) -> Result<Block> {
+        if !self.at(TokenKind::Eq) {
+            return self.parse_block();
+        }
+        self.advance();
+        if self.at(TokenKind::LBrace) {
+            return Err(self.unexpected("an expression after `=`, not a block body"));
+        }
+        let expr = self.parse_expr()?;
+        self.expect(TokenKind::Semi)?;
+        Ok(Block {
+            stmts: vec![Stmt::Expr {
+                expr,
+                has_semi: false,
+            }],
+        })
+    }
+
     fn parse_params(&mut self) -> Result<Vec<Param>> {
         self.expect(TokenKind::LParen)?;
         let mut params = Vec::new();
@@ -250,6 +628,7 @@ impl<'a> Parser<'a> {
                 id: self.next_id(),
                 is_mut,
                 pattern: Pattern::Binding {
+                    id: self.next_id(),
                     name: "self".to_string(),
                     mutable: false,
                 },
@@ -260,6 +639,7 @@ impl<'a> Parser<'a> {
         // Handle &self and &mut self
         if self.at(TokenKind::Amp) {
             self.advance();
+            let lifetime = self.parse_lifetime_opt();
             let is_ref_mut = if self.at(TokenKind::Mut) {
                 self.advance();
                 true
@@ -272,11 +652,13 @@ impl<'a> Parser<'a> {
                     id: self.next_id(),
                     is_mut: is_ref_mut,
                     pattern: Pattern::Binding {
+                        id: self.next_id(),
                         name: "self".to_string(),
                         mutable: is_ref_mut,
                     },
                     ty: TypeExpr::Reference {
                         mutable: is_ref_mut,
+                        lifetime,
                         inner: Box::new(TypeExpr::SelfType),
                     },
                 });
@@ -321,46 +703,76 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_effect_ref(&mut self) -> Result<EffectRef> {
+        let start = self.span().start;
         let name = self.parse_path()?;
         let args = if self.at(TokenKind::Lt) {
             self.parse_type_args()?
         } else {
             Vec::new()
         };
-        Ok(EffectRef {
-            id: self.next_id(),
-            name,
-            args,
-        })
+        let id = self.next_id();
+        self.record_node_span(id, start);
+        Ok(EffectRef { id, name, args })
     }
 
     // ==================== STRUCTS ====================
 
-    fn parse_struct(&mut self, visibility: Visibility, modifiers: Modifiers) -> Result<Item> {
+    fn parse_struct(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+        modifiers: Modifiers,
+    ) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Struct)?;
 
         let name = self.parse_ident()?;
         let generics = self.parse_generics()?;
-        let where_clause = self.parse_where_clause()?;
 
-        self.expect(TokenKind::LBrace)?;
         let mut fields = Vec::new();
-        while !self.at(TokenKind::RBrace) {
-            fields.push(self.parse_field()?);
-            if !self.at(TokenKind::RBrace) {
-                // Allow optional comma
-                if self.at(TokenKind::Comma) {
-                    self.advance();
+        let (kind, where_clause) = if self.at(TokenKind::LParen) {
+            // Tuple struct: `struct Meters(f64);`, the semicolon is optional
+            self.advance();
+            let mut types = Vec::new();
+            while !self.at(TokenKind::RParen) {
+                types.push(self.parse_type()?);
+                if !self.at(TokenKind::RParen) {
+                    self.expect(TokenKind::Comma)?;
                 }
             }
-        }
-        self.expect(TokenKind::RBrace)?;
+            self.expect(TokenKind::RParen)?;
+            let where_clause = self.parse_where_clause()?;
+            if self.at(TokenKind::Semi) {
+                self.advance();
+            }
+            (StructKind::Tuple(types), where_clause)
+        } else {
+            let where_clause = self.parse_where_clause()?;
+            if self.at(TokenKind::Semi) {
+                // Unit struct: `struct Meters;`
+                self.advance();
+                (StructKind::Unit, where_clause)
+            } else {
+                self.expect(TokenKind::LBrace)?;
+                while !self.at(TokenKind::RBrace) {
+                    fields.push(self.parse_field()?);
+                    if !self.at(TokenKind::RBrace) {
+                        // Allow optional comma
+                        if self.at(TokenKind::Comma) {
+                            self.advance();
+                        }
+                    }
+                }
+                self.expect(TokenKind::RBrace)?;
+                (StructKind::Named, where_clause)
+            }
+        };
 
         let end = self.span();
 
         Ok(Item::Struct(StructDef {
             id: self.next_id(),
+            attributes,
             visibility,
             modifiers: TypeModifiers {
                 linear: modifiers.linear,
@@ -369,8 +781,10 @@ impl<'a> Parser<'a> {
             name,
             generics,
             where_clause,
+            kind,
             fields,
             span: start.merge(end),
+            doc: None,
         }))
     }
 
@@ -390,7 +804,12 @@ impl<'a> Parser<'a> {
 
     // ==================== ENUMS ====================
 
-    fn parse_enum(&mut self, visibility: Visibility, modifiers: Modifiers) -> Result<Item> {
+    fn parse_enum(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+        modifiers: Modifiers,
+    ) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Enum)?;
 
@@ -414,6 +833,7 @@ impl<'a> Parser<'a> {
 
         Ok(Item::Enum(EnumDef {
             id: self.next_id(),
+            attributes,
             visibility,
             modifiers: TypeModifiers {
                 linear: modifiers.linear,
@@ -424,6 +844,7 @@ impl<'a> Parser<'a> {
             where_clause,
             variants,
             span: start.merge(end),
+            doc: None,
         }))
     }
 
@@ -457,16 +878,29 @@ impl<'a> Parser<'a> {
             VariantData::Unit
         };
 
+        let discriminant = if matches!(data, VariantData::Unit) && self.at(TokenKind::Eq) {
+            self.advance();
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
         Ok(VariantDef {
             id: self.next_id(),
             name,
             data,
+            discriminant,
         })
     }
 
     // ==================== TRAITS & IMPL ====================
 
-    fn parse_trait(&mut self, visibility: Visibility, _modifiers: Modifiers) -> Result<Item> {
+    fn parse_trait(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+        _modifiers: Modifiers,
+    ) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Trait)?;
 
@@ -496,6 +930,7 @@ impl<'a> Parser<'a> {
 
         Ok(Item::Trait(TraitDef {
             id: self.next_id(),
+            attributes,
             visibility,
             name,
             generics,
@@ -503,10 +938,12 @@ impl<'a> Parser<'a> {
             where_clause,
             items,
             span: start.merge(end),
+            doc: None,
         }))
     }
 
     fn parse_trait_item(&mut self) -> Result<TraitItem> {
+        let doc = self.take_doc(None);
         let visibility = self.parse_visibility();
         let modifiers = self.parse_modifiers();
 
@@ -520,8 +957,8 @@ impl<'a> Parser<'a> {
                 let effects = self.parse_effect_clause()?;
                 let where_clause = self.parse_where_clause()?;
 
-                let default_body = if self.at(TokenKind::LBrace) {
-                    Some(self.parse_block()?)
+                let default_body = if self.at(TokenKind::LBrace) || self.at(TokenKind::Eq) {
+                    Some(self.parse_fn_body()?)
                 } else {
                     self.expect(TokenKind::Semi)?;
                     None
@@ -536,6 +973,7 @@ impl<'a> Parser<'a> {
                     effects,
                     where_clause,
                     default_body,
+                    doc,
                 }))
             }
             TokenKind::Type => {
@@ -574,7 +1012,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_impl(&mut self) -> Result<Item> {
+    fn parse_impl(&mut self, attributes: Vec<Attribute>) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Impl)?;
 
@@ -603,6 +1041,7 @@ impl<'a> Parser<'a> {
 
         Ok(Item::Impl(ImplDef {
             id: self.next_id(),
+            attributes,
             generics,
             trait_ref,
             target_type,
@@ -613,13 +1052,17 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_impl_item(&mut self) -> Result<ImplItem> {
+        let doc = self.take_doc(None);
+        let attributes = self.parse_attributes()?;
+        let doc = self.take_doc(doc);
         let visibility = self.parse_visibility();
         let modifiers = self.parse_modifiers();
 
         match self.peek() {
             TokenKind::Fn | TokenKind::Kernel => {
-                let item = self.parse_fn(visibility, modifiers)?;
-                if let Item::Function(f) = item {
+                let item = self.parse_fn(attributes, visibility, modifiers)?;
+                if let Item::Function(mut f) = item {
+                    f.doc = doc;
                     Ok(ImplItem::Fn(f))
                 } else {
                     unreachable!()
@@ -646,7 +1089,11 @@ impl<'a> Parser<'a> {
 
     // ==================== TYPE ALIASES ====================
 
-    fn parse_type_alias(&mut self, visibility: Visibility) -> Result<Item> {
+    fn parse_type_alias(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+    ) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Type)?;
 
@@ -660,39 +1107,110 @@ impl<'a> Parser<'a> {
 
         Ok(Item::TypeAlias(TypeAliasDef {
             id: self.next_id(),
+            attributes,
             visibility,
             name,
             generics,
             ty,
             span: start.merge(end),
+            doc: None,
+        }))
+    }
+
+    fn parse_unit_def(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+    ) -> Result<Item> {
+        let start = self.span();
+        self.advance();
+
+        let name = self.parse_ident()?;
+        let definition = if self.at(TokenKind::Eq) {
+            self.advance();
+            Some(self.parse_unit_expr()?)
+        } else {
+            None
+        };
+        self.expect(TokenKind::Semi)?;
+
+        let end = self.span();
+
+        Ok(Item::UnitDef(UnitDef {
+            id: self.next_id(),
+            attributes,
+            visibility,
+            name,
+            definition,
+            span: start.merge(end),
+            doc: None,
         }))
     }
 
+    /// Parse a unit expression such as `kg*m/s^2` into its text
+    fn parse_unit_expr(&mut self) -> Result<String> {
+        let mut unit = String::new();
+        loop {
+            if !self.at(TokenKind::Ident) && !self.at(TokenKind::IntLit) {
+                return Err(self.unexpected("a unit"));
+            }
+            unit.push_str(&self.advance().text);
+            if self.at(TokenKind::Caret) {
+                self.advance();
+                unit.push('^');
+                if self.at(TokenKind::Minus) {
+                    self.advance();
+                    unit.push('-');
+                }
+                unit.push_str(&self.expect(TokenKind::IntLit)?.text);
+            }
+            if !self.at(TokenKind::Slash) && !self.at(TokenKind::Star) {
+                return Ok(unit);
+            }
+            unit.push_str(&self.advance().text);
+        }
+    }
+
     // ==================== EFFECTS ====================
 
-    fn parse_effect(&mut self, visibility: Visibility) -> Result<Item> {
+    fn parse_effect(&mut self, attributes: Vec<Attribute>, visibility: Visibility) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Effect)?;
 
         let name = self.parse_ident()?;
         let generics = self.parse_generics()?;
 
-        self.expect(TokenKind::LBrace)?;
         let mut operations = Vec::new();
-        while !self.at(TokenKind::RBrace) {
-            operations.push(self.parse_effect_op()?);
+        let mut members = Vec::new();
+        if self.at(TokenKind::Eq) {
+            // Effect alias: `effect IO = Alloc + FileSystem + Net;`
+            self.advance();
+            members.push(self.parse_effect_ref()?);
+            while self.at(TokenKind::Plus) {
+                self.advance();
+                members.push(self.parse_effect_ref()?);
+            }
+            self.expect(TokenKind::Semi)?;
+        } else {
+            self.expect(TokenKind::LBrace)?;
+            while !self.at(TokenKind::RBrace) {
+                operations.push(self.parse_effect_op()?);
+            }
+            self.expect(TokenKind::RBrace)?;
         }
-        self.expect(TokenKind::RBrace)?;
 
         let end = self.span();
 
         Ok(Item::Effect(EffectDef {
             id: self.next_id(),
+            attributes,
             visibility,
             name,
             generics,
             operations,
+            members,
             span: start.merge(end),
+            doc: None,
         }))
     }
 
@@ -711,7 +1229,11 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_handler(&mut self, visibility: Visibility) -> Result<Item> {
+    fn parse_handler(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+    ) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Handler)?;
 
@@ -719,11 +1241,28 @@ impl<'a> Parser<'a> {
         let generics = self.parse_generics()?;
         self.expect(TokenKind::For)?;
         let effect = self.parse_path()?;
+        let effect_args = if self.at(TokenKind::Lt) {
+            self.parse_type_args()?
+        } else {
+            Vec::new()
+        };
 
         self.expect(TokenKind::LBrace)?;
         let mut cases = Vec::new();
+        let mut return_clause = None;
         while !self.at(TokenKind::RBrace) {
-            cases.push(self.parse_handler_case()?);
+            if self.at(TokenKind::Return) {
+                if return_clause.is_some() {
+                    return Err(miette::miette!(
+                        "Handler `{}` has more than one return clause at position {}",
+                        name,
+                        self.current().span.start
+                    ));
+                }
+                return_clause = Some(self.parse_handler_return()?);
+            } else {
+                cases.push(self.parse_handler_case()?);
+            }
         }
         self.expect(TokenKind::RBrace)?;
 
@@ -731,18 +1270,22 @@ impl<'a> Parser<'a> {
 
         Ok(Item::Handler(HandlerDef {
             id: self.next_id(),
+            attributes,
             visibility,
             name,
             generics,
             effect,
+            effect_args,
             cases,
+            return_clause,
             span: start.merge(end),
+            doc: None,
         }))
     }
 
     fn parse_handler_case(&mut self) -> Result<HandlerCase> {
         let name = self.parse_ident()?;
-        let params = self.parse_params()?;
+        let params = self.parse_handler_params()?;
         self.expect(TokenKind::FatArrow)?;
         let body = self.parse_expr()?;
         if self.at(TokenKind::Comma) {
@@ -757,23 +1300,104 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parameters of a handler case, where types are optional
+    fn parse_handler_params(&mut self) -> Result<Vec<Param>> {
+        self.expect(TokenKind::LParen)?;
+        let mut params = Vec::new();
+
+        while !self.at(TokenKind::RParen) {
+            let pattern = self.parse_pattern()?;
+            let ty = if self.at(TokenKind::Colon) {
+                self.advance();
+                self.parse_type()?
+            } else {
+                TypeExpr::Infer
+            };
+            params.push(Param {
+                id: self.next_id(),
+                is_mut: false,
+                pattern,
+                ty,
+            });
+            if !self.at(TokenKind::RParen) {
+                self.expect(TokenKind::Comma)?;
+            }
+        }
+
+        self.expect(TokenKind::RParen)?;
+        Ok(params)
+    }
+
+    fn parse_handler_return(&mut self) -> Result<HandlerReturn> {
+        self.expect(TokenKind::Return)?;
+        let param = self.parse_ident()?;
+        self.expect(TokenKind::FatArrow)?;
+        let body = self.parse_expr()?;
+        if self.at(TokenKind::Comma) {
+            self.advance();
+        }
+
+        Ok(HandlerReturn {
+            id: self.next_id(),
+            param,
+            body,
+        })
+    }
+
     // ==================== IMPORTS & EXTERN ====================
 
-    fn parse_import(&mut self) -> Result<Item> {
+    fn parse_import(&mut self, attributes: Vec<Attribute>) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Import)?;
-        let path = self.parse_path()?;
+        let tree = self.parse_import_tree()?;
         self.expect(TokenKind::Semi)?;
         let end = self.span();
 
         Ok(Item::Import(ImportDef {
             id: self.next_id(),
-            path,
+            attributes,
+            tree,
             span: start.merge(end),
         }))
     }
 
-    fn parse_extern(&mut self) -> Result<Item> {
+    /// `a::b`, `a::b as c`, `a::*` or `a::{b, c::d}`
+    fn parse_import_tree(&mut self) -> Result<ImportTree> {
+        let mut path = Path::simple(&self.parse_ident()?);
+        while self.at(TokenKind::ColonColon) {
+            self.advance();
+            if self.at(TokenKind::Star) {
+                self.advance();
+                return Ok(ImportTree::Glob(path));
+            }
+            if self.at(TokenKind::LBrace) {
+                self.advance();
+                let mut items = Vec::new();
+                while !self.at(TokenKind::RBrace) {
+                    items.push(self.parse_import_tree()?);
+                    if !self.at(TokenKind::RBrace) {
+                        self.expect(TokenKind::Comma)?;
+                    }
+                }
+                self.expect(TokenKind::RBrace)?;
+                return Ok(ImportTree::Group {
+                    prefix: path,
+                    items,
+                });
+            }
+            path.segments.push(self.parse_ident()?);
+        }
+
+        let alias = if self.at(TokenKind::As) {
+            self.advance();
+            Some(self.parse_ident()?)
+        } else {
+            None
+        };
+        Ok(ImportTree::Path { path, alias })
+    }
+
+    fn parse_extern(&mut self, attributes: Vec<Attribute>) -> Result<Item> {
         let start = self.span();
         self.expect(TokenKind::Extern)?;
 
@@ -796,6 +1420,7 @@ impl<'a> Parser<'a> {
 
         Ok(Item::Extern(ExternBlock {
             id: self.next_id(),
+            attributes,
             abi,
             items,
             span: start.merge(end),
@@ -805,7 +1430,24 @@ impl<'a> Parser<'a> {
     fn parse_extern_fn(&mut self) -> Result<ExternFn> {
         self.expect(TokenKind::Fn)?;
         let name = self.parse_ident()?;
-        let params = self.parse_params()?;
+
+        self.expect(TokenKind::LParen)?;
+        let mut params = Vec::new();
+        let mut variadic = false;
+        while !self.at(TokenKind::RParen) {
+            // A trailing `...` marks a C variadic function
+            if self.at(TokenKind::DotDotDot) {
+                self.advance();
+                variadic = true;
+                break;
+            }
+            params.push(self.parse_param()?);
+            if !self.at(TokenKind::RParen) {
+                self.expect(TokenKind::Comma)?;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+
         let return_type = self.parse_return_type()?;
         self.expect(TokenKind::Semi)?;
 
@@ -814,12 +1456,18 @@ impl<'a> Parser<'a> {
             name,
             params,
             return_type,
+            variadic,
         })
     }
 
     // ==================== GLOBALS ====================
 
-    fn parse_global(&mut self, visibility: Visibility, modifiers: Modifiers) -> Result<Item> {
+    fn parse_global(
+        &mut self,
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+        modifiers: Modifiers,
+    ) -> Result<Item> {
         let start = self.span();
         let is_const = self.at(TokenKind::Const);
         self.advance(); // let or const
@@ -843,9 +1491,13 @@ impl<'a> Parser<'a> {
         let value = self.parse_expr()?;
 
         let end = self.span();
+        if self.at(TokenKind::Semi) {
+            self.advance();
+        }
 
         Ok(Item::Global(GlobalDef {
             id: self.next_id(),
+            attributes,
             visibility,
             is_const,
             is_mut,
@@ -853,6 +1505,7 @@ impl<'a> Parser<'a> {
             ty,
             value,
             span: start.merge(end),
+            doc: None,
         }))
     }
 
@@ -879,6 +1532,10 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_generic_param(&mut self) -> Result<GenericParam> {
+        if let Some(name) = self.parse_lifetime_opt() {
+            return Ok(GenericParam::Lifetime { name });
+        }
+
         // Check for const generic
         if self.at(TokenKind::Const) {
             self.advance();
@@ -919,17 +1576,61 @@ impl<'a> Parser<'a> {
         self.expect(TokenKind::Lt)?;
         let mut args = Vec::new();
 
-        while !self.at(TokenKind::Gt) {
-            args.push(self.parse_type()?);
-            if !self.at(TokenKind::Gt) {
+        while !self.at_closing_angle() {
+            if self.at(TokenKind::IntLit) || self.at(TokenKind::LBrace) {
+                args.push(TypeExpr::Const(Box::new(self.parse_primary()?)));
+            } else {
+                args.push(self.parse_type()?);
+            }
+            if !self.at_closing_angle() {
                 self.expect(TokenKind::Comma)?;
             }
         }
 
-        self.expect(TokenKind::Gt)?;
+        self.expect_closing_angle()?;
         Ok(args)
     }
 
+    /// Parse turbofish generic arguments: `::<T, U>`
+    fn parse_turbofish(&mut self) -> Result<Vec<TypeExpr>> {
+        self.expect(TokenKind::ColonColon)?;
+        self.parse_type_args()
+    }
+
+    /// Whether a `return` or `break` here has no value: the next token
+    /// ends the enclosing statement, block, argument, element or match arm
+    fn at_valueless_end(&self) -> bool {
+        self.at_any(&[
+            TokenKind::RBrace,
+            TokenKind::Semi,
+            TokenKind::Eof,
+            TokenKind::Comma,
+            TokenKind::RParen,
+            TokenKind::RBracket,
+        ])
+    }
+
+    fn at_turbofish(&self) -> bool {
+        self.at(TokenKind::ColonColon) && self.peek_n(1) == TokenKind::Lt
+    }
+
+    fn at_closing_angle(&self) -> bool {
+        self.split_shr || self.at(TokenKind::Gt) || self.at(TokenKind::Shr)
+    }
+
+    /// Consume the `>` closing a generic argument list, splitting `>>` in two
+    fn expect_closing_angle(&mut self) -> Result<()> {
+        if self.split_shr {
+            self.split_shr = false;
+        } else if self.at(TokenKind::Shr) {
+            self.advance();
+            self.split_shr = true;
+        } else {
+            self.expect(TokenKind::Gt)?;
+        }
+        Ok(())
+    }
+
     fn parse_where_clause(&mut self) -> Result<Vec<WherePredicate>> {
         if !self.at(TokenKind::Where) {
             return Ok(Vec::new());
@@ -941,12 +1642,29 @@ impl<'a> Parser<'a> {
         loop {
             let ty = self.parse_type()?;
             self.expect(TokenKind::Colon)?;
-            let mut bounds = vec![self.parse_path()?];
-            while self.at(TokenKind::Plus) {
+            let mut bounds = Vec::new();
+            let mut signature = None;
+            loop {
+                if self.at(TokenKind::Ident)
+                    && self.current().text == "Fn"
+                    && self.peek_n(1) == TokenKind::LParen
+                    && signature.is_none()
+                {
+                    self.advance();
+                    signature = Some(self.parse_fn_type_rest()?);
+                } else {
+                    bounds.push(self.parse_path()?);
+                }
+                if !self.at(TokenKind::Plus) {
+                    break;
+                }
                 self.advance();
-                bounds.push(self.parse_path()?);
             }
-            predicates.push(WherePredicate { ty, bounds });
+            predicates.push(WherePredicate {
+                ty,
+                bounds,
+                signature,
+            });
 
             if self.at(TokenKind::Comma) {
                 self.advance();
@@ -974,18 +1692,63 @@ impl<'a> Parser<'a> {
             left = TypeExpr::Function {
                 params: vec![left],
                 return_type: Box::new(ret),
-                effects: Vec::new(),
+                effects: self.parse_type_effects()?,
             };
         }
 
         Ok(left)
     }
 
+    /// Parameters, return type and effects of `fn(A, B) -> R with E`, after
+    /// the leading `fn` (or `Fn` in a bound). The return type defaults to `()`.
+    fn parse_fn_type_rest(&mut self) -> Result<TypeExpr> {
+        self.expect(TokenKind::LParen)?;
+        let mut params = Vec::new();
+        while !self.at(TokenKind::RParen) {
+            params.push(self.parse_type()?);
+            if !self.at(TokenKind::RParen) {
+                self.expect(TokenKind::Comma)?;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        let return_type = if self.at(TokenKind::Arrow) {
+            self.advance();
+            self.parse_type_with_precedence(1)?
+        } else {
+            TypeExpr::Unit
+        };
+        Ok(TypeExpr::Function {
+            params,
+            return_type: Box::new(return_type),
+            effects: self.parse_type_effects()?,
+        })
+    }
+
+    /// Effect row of a function type. Inside a parameter list or where
+    /// clause a comma may also start the next item, so the list only
+    /// continues while the name after the comma is not followed by `:`.
+    fn parse_type_effects(&mut self) -> Result<Vec<EffectRef>> {
+        if !self.at(TokenKind::With) {
+            return Ok(Vec::new());
+        }
+        self.advance();
+        let mut effects = vec![self.parse_effect_ref()?];
+        while self.at(TokenKind::Comma)
+            && self.peek_n(1) == TokenKind::Ident
+            && self.peek_n(2) != TokenKind::Colon
+        {
+            self.advance();
+            effects.push(self.parse_effect_ref()?);
+        }
+        Ok(effects)
+    }
+
     fn parse_type_primary(&mut self) -> Result<TypeExpr> {
         match self.peek() {
             // Reference types
             TokenKind::Amp => {
                 self.advance();
+                let lifetime = self.parse_lifetime_opt();
                 let is_mut = if self.at(TokenKind::Mut) {
                     self.advance();
                     true
@@ -995,6 +1758,7 @@ impl<'a> Parser<'a> {
                 let inner = self.parse_type_primary()?;
                 Ok(TypeExpr::Reference {
                     mutable: is_mut,
+                    lifetime,
                     inner: Box::new(inner),
                 })
             }
@@ -1061,18 +1825,9 @@ impl<'a> Parser<'a> {
                 // Check for unit annotation: Type@unit (e.g., f64@kg, i32@m/s)
                 let unit = if self.at(TokenKind::At) {
                     self.advance();
-                    // Parse unit identifier (can include / for compound units)
+                    // Compound units like m/s and kg*m/s^2
                     if self.at(TokenKind::Ident) {
-                        let mut unit_str = self.advance().text.clone();
-                        // Handle compound units like m/s, kg*m/s2
-                        while self.at(TokenKind::Slash) || self.at(TokenKind::Star) {
-                            let op = self.advance().text.clone();
-                            unit_str.push_str(&op);
-                            if self.at(TokenKind::Ident) || self.at(TokenKind::IntLit) {
-                                unit_str.push_str(&self.advance().text);
-                            }
-                        }
-                        Some(unit_str)
+                        Some(self.parse_unit_expr()?)
                     } else {
                         None
                     }
@@ -1083,12 +1838,46 @@ impl<'a> Parser<'a> {
                 Ok(TypeExpr::Named { path, args, unit })
             }
 
+            // `Self`, or an associated type such as `Self::Output`
+            TokenKind::SelfUpper => {
+                self.advance();
+                if self.at(TokenKind::ColonColon) {
+                    self.advance();
+                    let mut path = self.parse_path()?;
+                    path.segments.insert(0, "Self".to_string());
+                    Ok(TypeExpr::Named {
+                        path,
+                        args: Vec::new(),
+                        unit: None,
+                    })
+                } else {
+                    Ok(TypeExpr::SelfType)
+                }
+            }
+
             // Infer type
             TokenKind::Underscore => {
                 self.advance();
                 Ok(TypeExpr::Infer)
             }
 
+            // Function type: fn(A, B) -> R with E
+            TokenKind::Fn => {
+                self.advance();
+                self.parse_fn_type_rest()
+            }
+
+            // Trait object: dyn Trait
+            TokenKind::Dyn => {
+                let start = self.advance().span.start;
+                let path = self.parse_path()?;
+                let end = self.tokens[self.pos - 1].span.end;
+                Ok(TypeExpr::Dyn {
+                    path,
+                    span: Span::new(start, end),
+                })
+            }
+
             _ => Err(miette::miette!("Expected type, found {:?}", self.peek())),
         }
     }
@@ -1109,7 +1898,18 @@ impl<'a> Parser<'a> {
         result
     }
 
+    /// Parse an expression inside parentheses or brackets, where struct
+    /// literals are unambiguous even in a no-struct context
+    fn parse_delimited_expr(&mut self) -> Result<Expr> {
+        let old = self.allow_struct_literals;
+        self.allow_struct_literals = true;
+        let result = self.parse_expr();
+        self.allow_struct_literals = old;
+        result
+    }
+
     fn parse_expr_with_precedence(&mut self, min_prec: u8) -> Result<Expr> {
+        let start = self.span().start;
         let mut left = self.parse_unary()?;
 
         while let Some((op, prec, assoc)) = self.binary_op_info() {
@@ -1117,9 +1917,14 @@ impl<'a> Parser<'a> {
                 break;
             }
 
-            self.advance();
+            let op_text = self.advance().text.clone();
             let next_min = if assoc == Assoc::Left { prec + 1 } else { prec };
             let right = self.parse_expr_with_precedence(next_min)?;
+            if is_comparison(op)
+                && let Some(err) = self.chained_comparison(&left, &op_text, &right)
+            {
+                return Err(err);
+            }
 
             left = Expr::Binary {
                 id: self.next_id(),
@@ -1127,11 +1932,64 @@ impl<'a> Parser<'a> {
                 left: Box::new(left),
                 right: Box::new(right),
             };
+            self.record_span(&left, start);
+            if is_comparison(op) {
+                self.bare_comparisons.insert(left.id());
+            }
         }
 
         Ok(left)
     }
 
+    /// Error for `a < b < c` and the like, where `left` or `right` of the
+    /// comparison `op` is itself a comparison without parentheses
+    fn chained_comparison(&self, left: &Expr, op: &str, right: &Expr) -> Option<miette::Report> {
+        let text = |e: &Expr| {
+            self.node_spans
+                .get(&e.id())
+                .map_or("", |span| &self.source[span.start..span.end])
+        };
+        // The operator of a recorded comparison sits between its operands
+        let op_between =
+            |a: &Expr, b: &Expr| match (self.node_spans.get(&a.id()), self.node_spans.get(&b.id()))
+            {
+                (Some(a), Some(b)) => self.source[a.end..b.start].trim(),
+                _ => "",
+            };
+        let (a, first_op, b, second_op, c) = match (left, right) {
+            (
+                Expr::Binary {
+                    left: a, right: b, ..
+                },
+                c,
+            ) if self.bare_comparisons.contains(&left.id()) => {
+                (text(a), op_between(a, b), text(b), op, text(c))
+            }
+            (
+                a,
+                Expr::Binary {
+                    left: b, right: c, ..
+                },
+            ) if self.bare_comparisons.contains(&right.id()) => {
+                (text(a), op, text(b), op_between(b, c), text(c))
+            }
+            _ => return None,
+        };
+        let start = self.node_spans.get(&left.id())?.start;
+        let end = self.node_spans.get(&right.id())?.end;
+        Some(
+            CompileError::ChainedComparison {
+                span: Span::new(start, end).into(),
+                src: NamedSource::new("<input>", self.source.to_string()),
+                suggestion: format!(
+                    "split the comparison: `{} {} {} && {} {} {}`",
+                    a, first_op, b, b, second_op, c
+                ),
+            }
+            .into(),
+        )
+    }
+
     fn binary_op_info(&self) -> Option<(BinaryOp, u8, Assoc)> {
         let (op, prec, assoc) = match self.peek() {
             TokenKind::PipePipe => (BinaryOp::Or, 1, Assoc::Left),
@@ -1158,6 +2016,13 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_unary(&mut self) -> Result<Expr> {
+        let start = self.span().start;
+        let expr = self.parse_prefix()?;
+        self.record_span(&expr, start);
+        Ok(expr)
+    }
+
+    fn parse_prefix(&mut self) -> Result<Expr> {
         match self.peek() {
             TokenKind::Minus => {
                 self.advance();
@@ -1205,20 +2070,49 @@ impl<'a> Parser<'a> {
                     expr: Box::new(expr),
                 })
             }
+            TokenKind::Await => {
+                self.advance();
+                let expr = self.parse_unary()?;
+                Ok(Expr::Await {
+                    id: self.next_id(),
+                    expr: Box::new(expr),
+                })
+            }
             _ => self.parse_postfix(),
         }
     }
 
     fn parse_postfix(&mut self) -> Result<Expr> {
+        let start = self.span().start;
         let mut expr = self.parse_primary()?;
+        self.record_span(&expr, start);
 
         loop {
             match self.peek() {
-                TokenKind::LParen => {
+                _ if self.at(TokenKind::LParen) || self.at_turbofish() => {
+                    let type_args = if self.at_turbofish() {
+                        let type_args = self.parse_turbofish()?;
+                        if let Expr::Path { path, .. } = &mut expr
+                            && self.at(TokenKind::ColonColon)
+                        {
+                            // `Vec::<i64>::new`: the arguments belong to the
+                            // segment and the path goes on
+                            path.segment_args.push((path.segments.len() - 1, type_args));
+                            self.parse_path_segments(path)?;
+                            self.record_span(&expr, start);
+                            continue;
+                        }
+                        if !self.at(TokenKind::LParen) {
+                            return Err(self.unexpected("argument list after turbofish"));
+                        }
+                        type_args
+                    } else {
+                        Vec::new()
+                    };
                     self.advance();
                     let mut args = Vec::new();
                     while !self.at(TokenKind::RParen) {
-                        args.push(self.parse_expr()?);
+                        args.push(self.parse_delimited_expr()?);
                         if !self.at(TokenKind::RParen) {
                             self.expect(TokenKind::Comma)?;
                         }
@@ -1227,12 +2121,13 @@ impl<'a> Parser<'a> {
                     expr = Expr::Call {
                         id: self.next_id(),
                         callee: Box::new(expr),
+                        type_args,
                         args,
                     };
                 }
                 TokenKind::LBracket => {
                     self.advance();
-                    let index = self.parse_expr()?;
+                    let index = self.parse_delimited_expr()?;
                     self.expect(TokenKind::RBracket)?;
                     expr = Expr::Index {
                         id: self.next_id(),
@@ -1250,13 +2145,43 @@ impl<'a> Parser<'a> {
                             base: Box::new(expr),
                             index,
                         };
+                    } else if let Some((outer, inner)) = self.nested_tuple_index() {
+                        // `t.0.1` lexes its indices as the float `0.1`
+                        self.advance();
+                        for index in [outer, inner] {
+                            expr = Expr::TupleField {
+                                id: self.next_id(),
+                                base: Box::new(expr),
+                                index,
+                            };
+                            self.record_span(&expr, start);
+                        }
                     } else {
                         let field = self.parse_ident()?;
-                        expr = Expr::Field {
-                            id: self.next_id(),
-                            base: Box::new(expr),
-                            field,
-                        };
+                        if self.at(TokenKind::LParen) {
+                            // Method call: `receiver.method(args)`
+                            self.advance();
+                            let mut args = Vec::new();
+                            while !self.at(TokenKind::RParen) {
+                                args.push(self.parse_delimited_expr()?);
+                                if !self.at(TokenKind::RParen) {
+                                    self.expect(TokenKind::Comma)?;
+                                }
+                            }
+                            self.expect(TokenKind::RParen)?;
+                            expr = Expr::MethodCall {
+                                id: self.next_id(),
+                                receiver: Box::new(expr),
+                                method: field,
+                                args,
+                            };
+                        } else {
+                            expr = Expr::Field {
+                                id: self.next_id(),
+                                base: Box::new(expr),
+                                field,
+                            };
+                        }
                     }
                 }
                 TokenKind::Question => {
@@ -1277,20 +2202,40 @@ impl<'a> Parser<'a> {
                 }
                 _ => break,
             }
+            self.record_span(&expr, start);
         }
 
         Ok(expr)
     }
 
+    /// The two indices of a float literal such as `0.1` that follows a `.`,
+    /// which is a chain of tuple field accesses rather than a number
+    fn nested_tuple_index(&self) -> Option<(usize, usize)> {
+        if !self.at(TokenKind::FloatLit) {
+            return None;
+        }
+        let (outer, inner) = self.current().text.split_once('.')?;
+        let index = |part: &str| {
+            if part.bytes().all(|b| b.is_ascii_digit()) {
+                part.parse().ok()
+            } else {
+                None
+            }
+        };
+        Some((index(outer)?, index(inner)?))
+    }
+
     fn parse_primary(&mut self) -> Result<Expr> {
         match self.peek() {
             // Literals
-            TokenKind::IntLit => {
-                let text = self.advance().text.clone();
-                let value: i64 = text.replace('_', "").parse().unwrap_or(0);
+            TokenKind::IntLit | TokenKind::HexLit | TokenKind::OctLit | TokenKind::BinLit => {
+                let value = match self.int_literal()? {
+                    n if n <= i64::MAX as u128 => Literal::Int(n as i64),
+                    n => Literal::UInt(n),
+                };
                 Ok(Expr::Literal {
                     id: self.next_id(),
-                    value: Literal::Int(value),
+                    value,
                 })
             }
             TokenKind::FloatLit => {
@@ -1326,8 +2271,16 @@ impl<'a> Parser<'a> {
             }
             TokenKind::StringLit => {
                 let text = self.advance().text.clone();
-                // Remove quotes
-                let value = text[1..text.len() - 1].to_string();
+                // Remove quotes and process escapes
+                let value = unescape_string(&text[1..text.len() - 1]);
+                Ok(Expr::Literal {
+                    id: self.next_id(),
+                    value: Literal::String(value),
+                })
+            }
+            TokenKind::RawStringLit => {
+                let text = self.advance().text.clone();
+                let value = raw_string_contents(&text).to_string();
                 Ok(Expr::Literal {
                     id: self.next_id(),
                     value: Literal::String(value),
@@ -1335,7 +2288,10 @@ impl<'a> Parser<'a> {
             }
             TokenKind::CharLit => {
                 let text = self.advance().text.clone();
-                let value = text.chars().nth(1).unwrap_or('\0');
+                let value = unescape_string(&text[1..text.len() - 1])
+                    .chars()
+                    .next()
+                    .unwrap_or('\0');
                 Ok(Expr::Literal {
                     id: self.next_id(),
                     value: Literal::Char(value),
@@ -1356,8 +2312,36 @@ impl<'a> Parser<'a> {
                 })
             }
 
+            // `vec![a, b, c]` is a call to the variadic `vec` intrinsic
+            TokenKind::Ident
+                if self.current().text == "vec"
+                    && self.peek_n(1) == TokenKind::Bang
+                    && self.peek_n(2) == TokenKind::LBracket =>
+            {
+                let callee = Expr::Path {
+                    id: self.next_id(),
+                    path: self.parse_path()?,
+                };
+                self.advance();
+                self.advance();
+                let mut args = Vec::new();
+                while !self.at(TokenKind::RBracket) {
+                    args.push(self.parse_delimited_expr()?);
+                    if !self.at(TokenKind::RBracket) {
+                        self.expect(TokenKind::Comma)?;
+                    }
+                }
+                self.expect(TokenKind::RBracket)?;
+                Ok(Expr::Call {
+                    id: self.next_id(),
+                    callee: Box::new(callee),
+                    type_args: Vec::new(),
+                    args,
+                })
+            }
+
             // Identifiers and paths
-            TokenKind::Ident | TokenKind::SelfLower => {
+            TokenKind::Ident | TokenKind::SelfLower | TokenKind::SelfUpper => {
                 let path = self.parse_path()?;
 
                 // Check for struct literal (only if allowed in this context)
@@ -1385,7 +2369,7 @@ impl<'a> Parser<'a> {
                     });
                 }
 
-                let expr = self.parse_expr()?;
+                let expr = self.parse_delimited_expr()?;
 
                 if self.at(TokenKind::Comma) {
                     // Tuple
@@ -1395,7 +2379,7 @@ impl<'a> Parser<'a> {
                         if self.at(TokenKind::RParen) {
                             break;
                         }
-                        elements.push(self.parse_expr()?);
+                        elements.push(self.parse_delimited_expr()?);
                     }
                     self.expect(TokenKind::RParen)?;
                     Ok(Expr::Tuple {
@@ -1404,6 +2388,7 @@ impl<'a> Parser<'a> {
                     })
                 } else {
                     self.expect(TokenKind::RParen)?;
+                    self.bare_comparisons.remove(&expr.id());
                     Ok(expr)
                 }
             }
@@ -1413,7 +2398,17 @@ impl<'a> Parser<'a> {
                 self.advance();
                 let mut elements = Vec::new();
                 while !self.at(TokenKind::RBracket) {
-                    elements.push(self.parse_expr()?);
+                    elements.push(self.parse_delimited_expr()?);
+                    if elements.len() == 1 && self.at(TokenKind::Semi) {
+                        self.advance();
+                        let count = self.parse_delimited_expr()?;
+                        self.expect(TokenKind::RBracket)?;
+                        return Ok(Expr::ArrayRepeat {
+                            id: self.next_id(),
+                            value: Box::new(elements.remove(0)),
+                            count: Box::new(count),
+                        });
+                    }
                     if !self.at(TokenKind::RBracket) {
                         self.expect(TokenKind::Comma)?;
                     }
@@ -1448,7 +2443,7 @@ impl<'a> Parser<'a> {
             // Return
             TokenKind::Return => {
                 self.advance();
-                let value = if self.at_any(&[TokenKind::RBrace, TokenKind::Semi, TokenKind::Eof]) {
+                let value = if self.at_valueless_end() {
                     None
                 } else {
                     Some(Box::new(self.parse_expr()?))
@@ -1462,7 +2457,7 @@ impl<'a> Parser<'a> {
             // Break
             TokenKind::Break => {
                 self.advance();
-                let value = if self.at_any(&[TokenKind::RBrace, TokenKind::Semi, TokenKind::Eof]) {
+                let value = if self.at_valueless_end() {
                     None
                 } else {
                     Some(Box::new(self.parse_expr()?))
@@ -1480,7 +2475,7 @@ impl<'a> Parser<'a> {
             }
 
             // Closure
-            TokenKind::Pipe => self.parse_closure(),
+            TokenKind::Pipe | TokenKind::PipePipe => self.parse_closure(),
 
             // Effect operations
             TokenKind::Perform => {
@@ -1510,6 +2505,15 @@ impl<'a> Parser<'a> {
                 })
             }
 
+            // Inside a handler case, `resume` names the continuation
+            TokenKind::Resume => {
+                self.advance();
+                Ok(Expr::Path {
+                    id: self.next_id(),
+                    path: Path::simple("resume"),
+                })
+            }
+
             TokenKind::Handle => {
                 self.advance();
                 let expr = Box::new(self.parse_expr()?);
@@ -1554,9 +2558,7 @@ impl<'a> Parser<'a> {
                 // Shorthand: name without : means name: name
                 Expr::Path {
                     id: self.next_id(),
-                    path: Path {
-                        segments: vec![name.clone()],
-                    },
+                    path: Path::simple(&name),
                 }
             };
             fields.push((name, value));
@@ -1577,7 +2579,7 @@ impl<'a> Parser<'a> {
 
     fn parse_if(&mut self) -> Result<Expr> {
         self.expect(TokenKind::If)?;
-        let condition = Box::new(self.parse_expr()?);
+        let condition = Box::new(self.parse_expr_no_struct()?);
         let then_branch = self.parse_block()?;
         let else_branch = if self.at(TokenKind::Else) {
             self.advance();
@@ -1649,7 +2651,7 @@ impl<'a> Parser<'a> {
 
     fn parse_while(&mut self) -> Result<Expr> {
         self.expect(TokenKind::While)?;
-        let condition = Box::new(self.parse_expr()?);
+        let condition = Box::new(self.parse_expr_no_struct()?);
         let body = self.parse_block()?;
         Ok(Expr::While {
             id: self.next_id(),
@@ -1662,7 +2664,7 @@ impl<'a> Parser<'a> {
         self.expect(TokenKind::For)?;
         let pattern = self.parse_pattern()?;
         self.expect(TokenKind::In)?;
-        let iter = Box::new(self.parse_expr()?);
+        let iter = Box::new(self.parse_for_iter()?);
         let body = self.parse_block()?;
         Ok(Expr::For {
             id: self.next_id(),
@@ -1672,23 +2674,49 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse the iterable of a `for` loop, which may be a range `a..b` or `a..=b`
+    fn parse_for_iter(&mut self) -> Result<Expr> {
+        let start_pos = self.current().span.start;
+        let start = self.parse_expr_no_struct()?;
+        let inclusive = match self.peek() {
+            TokenKind::DotDot => false,
+            TokenKind::DotDotEq => true,
+            _ => return Ok(start),
+        };
+        self.advance();
+        let end = self.parse_expr_no_struct()?;
+        let range = Expr::Range {
+            id: self.next_id(),
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+        };
+        self.record_span(&range, start_pos);
+        Ok(range)
+    }
+
     fn parse_closure(&mut self) -> Result<Expr> {
-        self.expect(TokenKind::Pipe)?;
         let mut params = Vec::new();
-        while !self.at(TokenKind::Pipe) {
-            let name = self.parse_ident()?;
-            let ty = if self.at(TokenKind::Colon) {
-                self.advance();
-                Some(self.parse_type()?)
-            } else {
-                None
-            };
-            params.push((name, ty));
-            if !self.at(TokenKind::Pipe) {
-                self.expect(TokenKind::Comma)?;
+        // `||` lexes as a single token when there are no parameters
+        if self.at(TokenKind::PipePipe) {
+            self.advance();
+        } else {
+            self.expect(TokenKind::Pipe)?;
+            while !self.at(TokenKind::Pipe) {
+                let name = self.parse_ident()?;
+                let ty = if self.at(TokenKind::Colon) {
+                    self.advance();
+                    Some(self.parse_type()?)
+                } else {
+                    None
+                };
+                params.push((name, ty));
+                if !self.at(TokenKind::Pipe) {
+                    self.expect(TokenKind::Comma)?;
+                }
             }
+            self.expect(TokenKind::Pipe)?;
         }
-        self.expect(TokenKind::Pipe)?;
 
         let return_type = if self.at(TokenKind::Arrow) {
             self.advance();
@@ -1802,7 +2830,24 @@ impl<'a> Parser<'a> {
 
         let value = if self.at(TokenKind::Eq) {
             self.advance();
-            Some(self.parse_expr()?)
+            let value = self.parse_expr()?;
+
+            // `let Some(x) = opt else { return };`
+            if self.at(TokenKind::Else) {
+                self.advance();
+                let else_block = self.parse_block()?;
+                if self.at(TokenKind::Semi) {
+                    self.advance();
+                }
+                return Ok(Stmt::LetElse {
+                    is_mut,
+                    pattern,
+                    ty,
+                    value,
+                    else_block,
+                });
+            }
+            Some(value)
         } else {
             None
         };
@@ -1821,16 +2866,58 @@ impl<'a> Parser<'a> {
 
     // ==================== PATTERNS ====================
 
+    /// An integer or float literal in a pattern, optionally negated
+    fn parse_numeric_pattern_literal(&mut self) -> Result<Literal> {
+        let sign = if self.at(TokenKind::Minus) {
+            self.advance();
+            "-"
+        } else {
+            ""
+        };
+        match self.peek() {
+            TokenKind::IntLit | TokenKind::HexLit | TokenKind::OctLit | TokenKind::BinLit => {
+                let span = self.span();
+                let n = self.int_literal()?;
+                let value = if sign.is_empty() {
+                    i64::try_from(n).ok()
+                } else {
+                    0i64.checked_sub_unsigned(u64::try_from(n).unwrap_or(u64::MAX))
+                };
+                value
+                    .map(Literal::Int)
+                    .ok_or_else(|| self.literal_too_large(span, "for i64"))
+            }
+            TokenKind::FloatLit => {
+                let text = self.advance().text.clone();
+                let value: f64 = format!("{}{}", sign, text.replace('_', ""))
+                    .parse()
+                    .unwrap_or(0.0);
+                Ok(Literal::Float(value))
+            }
+            _ => Err(self.unexpected("numeric literal in pattern")),
+        }
+    }
+
     fn parse_pattern(&mut self) -> Result<Pattern> {
         match self.peek() {
             TokenKind::Underscore => {
                 self.advance();
                 Ok(Pattern::Wildcard)
             }
-            TokenKind::IntLit => {
-                let text = self.advance().text.clone();
-                let value: i64 = text.replace('_', "").parse().unwrap_or(0);
-                Ok(Pattern::Literal(Literal::Int(value)))
+            TokenKind::IntLit | TokenKind::FloatLit | TokenKind::Minus => {
+                let start = self.parse_numeric_pattern_literal()?;
+                let inclusive = match self.peek() {
+                    TokenKind::DotDotEq => true,
+                    TokenKind::DotDot => false,
+                    _ => return Ok(Pattern::Literal(start)),
+                };
+                self.advance();
+                let end = self.parse_numeric_pattern_literal()?;
+                Ok(Pattern::Range {
+                    start,
+                    end,
+                    inclusive,
+                })
             }
             TokenKind::True => {
                 self.advance();
@@ -1842,7 +2929,12 @@ impl<'a> Parser<'a> {
             }
             TokenKind::StringLit => {
                 let text = self.advance().text.clone();
-                let value = text[1..text.len() - 1].to_string();
+                let value = unescape_string(&text[1..text.len() - 1]);
+                Ok(Pattern::Literal(Literal::String(value)))
+            }
+            TokenKind::RawStringLit => {
+                let text = self.advance().text.clone();
+                let value = raw_string_contents(&text).to_string();
                 Ok(Pattern::Literal(Literal::String(value)))
             }
             TokenKind::LParen => {
@@ -1863,6 +2955,7 @@ impl<'a> Parser<'a> {
                 Ok(Pattern::Tuple(elements))
             }
             TokenKind::Ident | TokenKind::SelfLower => {
+                let start = self.current().span.start;
                 let path = self.parse_path()?;
                 if self.at(TokenKind::LParen) {
                     // Enum variant with tuple data
@@ -1884,15 +2977,13 @@ impl<'a> Parser<'a> {
                     self.advance();
                     let mut fields = Vec::new();
                     while !self.at(TokenKind::RBrace) {
+                        let field_start = self.current().span.start;
                         let name = self.parse_ident()?;
                         let pattern = if self.at(TokenKind::Colon) {
                             self.advance();
                             self.parse_pattern()?
                         } else {
-                            Pattern::Binding {
-                                name: name.clone(),
-                                mutable: false,
-                            }
+                            self.binding_pattern(name.clone(), false, field_start)
                         };
                         fields.push((name, pattern));
                         if !self.at(TokenKind::RBrace) {
@@ -1903,10 +2994,8 @@ impl<'a> Parser<'a> {
                     Ok(Pattern::Struct { path, fields })
                 } else if path.segments.len() == 1 {
                     // Simple binding
-                    Ok(Pattern::Binding {
-                        name: path.segments.into_iter().next().unwrap(),
-                        mutable: false,
-                    })
+                    let name = path.segments.into_iter().next().unwrap();
+                    self.parse_binding_pattern(name, false, start)
                 } else {
                     // Path pattern (unit variant)
                     Ok(Pattern::Enum {
@@ -1916,12 +3005,9 @@ impl<'a> Parser<'a> {
                 }
             }
             TokenKind::Mut => {
-                self.advance();
+                let start = self.advance().span.start;
                 let name = self.parse_ident()?;
-                Ok(Pattern::Binding {
-                    name,
-                    mutable: true,
-                })
+                self.parse_binding_pattern(name, true, start)
             }
             _ => Err(miette::miette!("Expected pattern, found {:?}", self.peek())),
         }
@@ -1929,28 +3015,46 @@ impl<'a> Parser<'a> {
 
     // ==================== HELPERS ====================
 
+    /// A lifetime such as `'a`, if one comes next
+    fn parse_lifetime_opt(&mut self) -> Option<String> {
+        if self.at(TokenKind::Lifetime) {
+            Some(self.advance().text.clone())
+        } else {
+            None
+        }
+    }
+
     fn parse_ident(&mut self) -> Result<String> {
         if self.at(TokenKind::Ident) {
             Ok(self.advance().text.clone())
         } else if self.at(TokenKind::SelfLower) {
             Ok(self.advance().text.clone())
         } else {
-            Err(miette::miette!(
-                "Expected identifier, found {:?}",
-                self.peek()
-            ))
+            Err(self.unexpected("identifier"))
         }
     }
 
     fn parse_path(&mut self) -> Result<Path> {
-        let mut segments = vec![self.parse_ident()?];
+        // `Self::new` and `Self { .. }` name the type of the enclosing impl
+        let first = if self.at(TokenKind::SelfUpper) {
+            self.advance().text.clone()
+        } else {
+            self.parse_ident()?
+        };
+        let mut path = Path::simple(&first);
+        self.parse_path_segments(&mut path)?;
+        Ok(path)
+    }
 
-        while self.at(TokenKind::ColonColon) {
+    /// Parse `::name` segments onto `path`. A turbofish stops the path;
+    /// `parse_postfix` decides whether its arguments belong to the call or
+    /// to a segment the path continues after.
+    fn parse_path_segments(&mut self, path: &mut Path) -> Result<()> {
+        while self.at(TokenKind::ColonColon) && !self.at_turbofish() {
             self.advance();
-            segments.push(self.parse_ident()?);
+            path.segments.push(self.parse_ident()?);
         }
-
-        Ok(Path { segments })
+        Ok(())
     }
 }
 
@@ -1961,6 +3065,14 @@ enum Assoc {
     Right,
 }
 
+/// Whether `op` compares its operands, yielding a `bool`
+fn is_comparison(op: BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge
+    )
+}
+
 /// Split a unit literal into its numeric and unit parts.
 /// For example: "500_mg" -> ("500", "mg"), "1_000_kg" -> ("1_000", "kg")
 fn split_unit_literal(text: &str) -> (&str, &str) {
@@ -1975,3 +3087,38 @@ fn split_unit_literal(text: &str) -> (&str, &str) {
     // Fallback: shouldn't happen with valid unit literals
     (text, "")
 }
+
+/// Process escape sequences in the body of a (non-raw) string literal.
+/// Unknown escapes are kept verbatim.
+fn unescape_string(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('\\') => out.push('\\'),
+            Some('"') => out.push('"'),
+            Some('\'') => out.push('\''),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Strip the `r`, hashes and quotes from a raw string literal.
+/// For example: `r#"a "b""#` -> `a "b"`
+fn raw_string_contents(text: &str) -> &str {
+    let hashes = text[1..].chars().take_while(|&c| c == '#').count();
+    &text[2 + hashes..text.len() - 1 - hashes]
+}
@@ -260,6 +260,7 @@ pub fn can_start_expression(kind: TokenKind) -> bool {
             | TokenKind::IntLit
             | TokenKind::FloatLit
             | TokenKind::StringLit
+            | TokenKind::RawStringLit
             | TokenKind::CharLit
             | TokenKind::True
             | TokenKind::False
@@ -348,8 +348,8 @@ impl fmt::Display for Predicate {
                 write!(f, "({})", s.join(" || "))
             }
             Predicate::Implies(p, q) => write!(f, "({} => {})", p, q),
-            Predicate::Forall(x, ty, p) => write!(f, "forall {}: {:?}. {}", x, ty, p),
-            Predicate::Exists(x, ty, p) => write!(f, "exists {}: {:?}. {}", x, ty, p),
+            Predicate::Forall(x, ty, p) => write!(f, "forall {}: {}. {}", x, ty, p),
+            Predicate::Exists(x, ty, p) => write!(f, "exists {}: {}. {}", x, ty, p),
             Predicate::App(name, args) => {
                 let args_str: Vec<_> = args.iter().map(|a| format!("{}", a)).collect();
                 write!(f, "{}({})", name, args_str.join(", "))
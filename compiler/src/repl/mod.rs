@@ -4,10 +4,14 @@
 
 use crate::hir;
 use crate::interp::{Interpreter, Value};
+use crate::lexer::TokenKind;
 use rustyline::error::ReadlineError;
 use rustyline::{DefaultEditor, Result as RlResult};
 use std::collections::HashMap;
 
+/// Binding that `Repl::infer_type` stores an input's value in
+const PROBE: &str = "__repl_value";
+
 /// REPL configuration
 #[derive(Debug, Clone)]
 pub struct ReplConfig {
@@ -35,6 +39,17 @@ impl Default for ReplConfig {
     }
 }
 
+/// What a complete REPL input produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplOutput {
+    /// A function, struct or enum was added to the session
+    Definition { kind: &'static str, name: String },
+    /// A `let` was added to the session, binding `name` to `value`
+    Binding { name: String, value: Value },
+    /// The value of an expression
+    Value(Value),
+}
+
 /// The REPL state
 pub struct Repl {
     config: ReplConfig,
@@ -42,8 +57,13 @@ pub struct Repl {
     functions: HashMap<String, String>,
     /// Accumulated type definitions
     types: HashMap<String, String>,
-    /// Variable bindings from previous expressions
+    /// Accumulated `let` statements. Each input runs after all of them,
+    /// in order, so their initializers are evaluated again every time.
+    lets: Vec<String>,
+    /// Values the `let`s had when they were entered, for `:env`
     bindings: HashMap<String, Value>,
+    /// Lines of an input that is not complete yet
+    pending: Vec<String>,
     /// Line counter
     line_count: usize,
 }
@@ -54,7 +74,9 @@ impl Repl {
             config,
             functions: HashMap::new(),
             types: HashMap::new(),
+            lets: Vec::new(),
             bindings: HashMap::new(),
+            pending: Vec::new(),
             line_count: 0,
         }
     }
@@ -72,35 +94,33 @@ impl Repl {
         println!("Type :help for help, :quit to exit\n");
 
         loop {
-            let prompt = format!("d[{}]> ", self.line_count);
+            let prompt = if self.pending.is_empty() {
+                format!("d[{}]> ", self.line_count)
+            } else {
+                "... ".to_string()
+            };
 
             match rl.readline(&prompt) {
                 Ok(line) => {
                     let line = line.trim();
 
-                    if line.is_empty() {
+                    if line.is_empty() && self.pending.is_empty() {
                         continue;
                     }
 
                     let _ = rl.add_history_entry(line);
 
                     // Handle commands
-                    if line.starts_with(':') {
+                    if line.starts_with(':') && self.pending.is_empty() {
                         if self.handle_command(line) {
                             break;
                         }
                         continue;
                     }
 
-                    // Handle multi-line input for function/type definitions
-                    let input = if line.starts_with("fn ")
-                        || line.starts_with("struct ")
-                        || line.starts_with("enum ")
-                        || line.starts_with("effect ")
-                    {
-                        self.read_multiline(&mut rl, line)?
-                    } else {
-                        line.to_string()
+                    // Keep reading continuation lines until the input is complete
+                    let Some(input) = self.push_line(line) else {
+                        continue;
                     };
 
                     // Evaluate the input
@@ -109,6 +129,7 @@ impl Repl {
                 }
                 Err(ReadlineError::Interrupted) => {
                     println!("^C");
+                    self.pending.clear();
                     continue;
                 }
                 Err(ReadlineError::Eof) => {
@@ -130,23 +151,24 @@ impl Repl {
         Ok(())
     }
 
-    fn read_multiline(&self, rl: &mut DefaultEditor, first_line: &str) -> RlResult<String> {
-        let mut lines = vec![first_line.to_string()];
-        let mut brace_count =
-            first_line.matches('{').count() as i32 - first_line.matches('}').count() as i32;
-
-        while brace_count > 0 {
-            match rl.readline("... ") {
-                Ok(line) => {
-                    brace_count += line.matches('{').count() as i32;
-                    brace_count -= line.matches('}').count() as i32;
-                    lines.push(line);
-                }
-                Err(_) => break,
-            }
+    /// Add a line of input, returning the whole input once it is complete
+    pub fn push_line(&mut self, line: &str) -> Option<String> {
+        self.pending.push(line.to_string());
+        let input = self.pending.join("\n");
+        if is_incomplete(&input) {
+            return None;
         }
+        self.pending.clear();
+        Some(input)
+    }
 
-        Ok(lines.join("\n"))
+    /// Forget all accumulated definitions and bindings
+    pub fn reset(&mut self) {
+        self.functions.clear();
+        self.types.clear();
+        self.lets.clear();
+        self.bindings.clear();
+        self.pending.clear();
     }
 
     fn handle_command(&mut self, cmd: &str) -> bool {
@@ -160,10 +182,8 @@ impl Repl {
             Some(":help") | Some(":h") | Some(":?") => {
                 self.print_help();
             }
-            Some(":clear") => {
-                self.functions.clear();
-                self.types.clear();
-                self.bindings.clear();
+            Some(":clear") | Some(":reset") => {
+                self.reset();
                 println!("Cleared all definitions and bindings.");
             }
             Some(":ast") => {
@@ -209,7 +229,7 @@ impl Repl {
         println!("Demetrios REPL Commands:");
         println!("  :help, :h, :?    Show this help");
         println!("  :quit, :q        Exit the REPL");
-        println!("  :clear           Clear all definitions");
+        println!("  :reset, :clear   Clear all definitions and bindings");
         println!("  :env             Show current bindings");
         println!("  :funcs           Show defined functions");
         println!("  :ast             Toggle AST display");
@@ -261,184 +281,169 @@ impl Repl {
     }
 
     fn show_type(&mut self, expr: &str) {
-        // Build full source with definitions
-        let source = self.build_source(expr);
-
-        // Parse using the library functions
-        match crate::lexer::lex(&source) {
-            Ok(tokens) => {
-                match crate::parser::parse(&tokens, &source) {
-                    Ok(ast) => {
-                        // Type check
-                        match crate::check::check(&ast) {
-                            Ok(hir) => {
-                                // Find the type of the last expression
-                                if let Some(item) = hir.items.last() {
-                                    if let hir::HirItem::Function(f) = item {
-                                        println!("Type: {:?}", f.body.ty);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Type error: {}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Parse error: {:?}", e);
-                    }
+        match self.infer_type(expr) {
+            Ok(ty) => println!("Type: {}", ty),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    fn eval_input(&mut self, input: &str) {
+        match self.eval(input) {
+            Ok(ReplOutput::Definition { kind, name }) => println!("Defined {}: {}", kind, name),
+            Ok(ReplOutput::Binding { name, value }) => println!("{} = {:?}", name, value),
+            Ok(ReplOutput::Value(value)) if value != Value::Unit => {
+                if self.config.show_types {
+                    println!("=> {:?}", value);
+                } else {
+                    println!("{:?}", value);
                 }
             }
-            Err(e) => {
-                eprintln!("Lex error: {:?}", e);
-            }
+            Ok(ReplOutput::Value(_)) => {}
+            Err(e) => eprintln!("{}", e),
         }
     }
 
-    fn eval_input(&mut self, input: &str) {
+    /// Evaluate a complete input. Definitions and `let`s are added to the
+    /// session; expressions are evaluated against everything defined so
+    /// far. Every input re-runs the session's `let`s first, so a binding
+    /// is rebuilt from its initializer each time: side effects in the
+    /// initializer repeat, and changes an expression makes to a bound
+    /// value do not carry over to later inputs.
+    pub fn eval(&mut self, input: &str) -> Result<ReplOutput, String> {
+        let input = input.trim();
+
         // Check if this is a definition
         if input.starts_with("fn ") {
             if let Some(name) = self.extract_fn_name(input) {
                 self.functions.insert(name.clone(), input.to_string());
-                println!("Defined function: {}", name);
-                return;
+                return Ok(ReplOutput::Definition {
+                    kind: "function",
+                    name,
+                });
             }
         }
 
         if input.starts_with("struct ") {
             if let Some(name) = self.extract_type_name(input) {
                 self.types.insert(name.clone(), input.to_string());
-                println!("Defined struct: {}", name);
-                return;
+                return Ok(ReplOutput::Definition {
+                    kind: "struct",
+                    name,
+                });
             }
         }
 
         if input.starts_with("enum ") {
             if let Some(name) = self.extract_type_name(input) {
                 self.types.insert(name.clone(), input.to_string());
-                println!("Defined enum: {}", name);
-                return;
+                return Ok(ReplOutput::Definition { kind: "enum", name });
             }
         }
 
-        // Check if this is a let binding
-        let is_let = input.starts_with("let ");
-
-        // Wrap expression in a main function for evaluation
-        let wrapped = if is_let {
-            // let x = expr -> we need to capture the value
-            format!("{}\n__repl_result__", input)
+        // A let binding evaluates to its bound value
+        let let_name = if input.starts_with("let ") {
+            self.extract_let_name(input)
         } else {
-            input.to_string()
+            None
+        };
+        let body = match &let_name {
+            Some(name) => format!("{}\n{}", input, name),
+            None => input.to_string(),
         };
 
-        let source = self.build_source(&wrapped);
+        // `main` returns the input's value, so it is declared with the
+        // input's type
+        let ty = self.infer_type(&body)?;
+        let source = self.build_source(&body, &ty);
 
         if self.config.show_ast {
             println!("--- Source ---\n{}\n", source);
         }
 
         // Parse using library functions
-        let tokens = match crate::lexer::lex(&source) {
-            Ok(t) => t,
-            Err(e) => {
-                eprintln!("Lex error: {:?}", e);
-                return;
-            }
-        };
-
-        let ast = match crate::parser::parse(&tokens, &source) {
-            Ok(ast) => ast,
-            Err(e) => {
-                eprintln!("Parse error: {:?}", e);
-                return;
-            }
-        };
+        let tokens = crate::lexer::lex(&source).map_err(|e| format!("Lex error: {:?}", e))?;
+        let ast =
+            crate::parser::parse(&tokens, &source).map_err(|e| format!("Parse error: {:?}", e))?;
 
         if self.config.show_ast {
             println!("--- AST ---\n{:#?}\n", ast);
         }
 
         // Type check
-        let hir = match crate::check::check(&ast) {
-            Ok(hir) => hir,
-            Err(e) => {
-                eprintln!("Type error: {}", e);
-                return;
-            }
-        };
+        let hir = crate::check::check(&ast).map_err(|e| format!("Type error: {}", e))?;
 
         if self.config.show_hir {
             println!("--- HIR ---\n{:#?}\n", hir);
         }
 
         // Execute
-        if self.config.use_jit {
-            self.eval_jit(&hir);
+        let value = if self.config.use_jit {
+            self.eval_jit(&hir)?
         } else {
-            self.eval_interp(&hir, is_let, input);
+            self.eval_interp(&hir)?
+        };
+
+        if let Some(name) = let_name {
+            self.lets.push(input.to_string());
+            self.bindings.insert(name.clone(), value.clone());
+            return Ok(ReplOutput::Binding { name, value });
         }
+        Ok(ReplOutput::Value(value))
     }
 
-    fn eval_interp(&mut self, hir: &hir::Hir, is_let: bool, input: &str) {
+    fn eval_interp(&mut self, hir: &hir::Hir) -> Result<Value, String> {
         let mut interp = Interpreter::new();
-
-        // Pre-populate environment with existing bindings
-        for (name, value) in &self.bindings {
-            interp.env_mut().define(name.clone(), value.clone());
-        }
-
-        match interp.run(hir) {
-            Ok(value) => {
-                if is_let {
-                    // Extract binding name and store value
-                    if let Some(name) = self.extract_let_name(input) {
-                        println!("{} = {:?}", name, value);
-                        self.bindings.insert(name, value);
-                    }
-                } else if value != Value::Unit {
-                    if self.config.show_types {
-                        println!("=> {:?}", value);
-                    } else {
-                        println!("{:?}", value);
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Runtime error: {}", e);
-            }
-        }
+        interp.run(hir).map_err(|e| format!("Runtime error: {}", e))
     }
 
-    fn eval_jit(&self, hir: &hir::Hir) {
+    fn eval_jit(&self, hir: &hir::Hir) -> Result<Value, String> {
         #[cfg(feature = "jit")]
         {
             use crate::codegen::cranelift::CraneliftJit;
             use crate::hlir;
 
             // Lower to HLIR
-            let hlir_module = hlir::lower(hir);
+            let hlir_module = hlir::lower(hir).map_err(|e| format!("Lowering error: {}", e))?;
 
             // Compile and run
             let jit = CraneliftJit::new();
-            match jit.compile_and_run(&hlir_module) {
-                Ok(result) => {
-                    println!("=> {}", result);
-                }
-                Err(e) => {
-                    eprintln!("JIT error: {}", e);
-                }
-            }
+            jit.compile_and_run(&hlir_module)
+                .map(Value::Int)
+                .map_err(|e| format!("JIT error: {}", e))
         }
 
         #[cfg(not(feature = "jit"))]
         {
             let _ = hir;
-            eprintln!("JIT not enabled. Compile with --features jit");
+            Err("JIT not enabled. Compile with --features jit".to_string())
         }
     }
 
-    fn build_source(&self, expr: &str) -> String {
+    /// Type of `expr` evaluated after the session's `let`s, found by
+    /// checking it as the initializer of a binding in a unit-returning `main`
+    fn infer_type(&self, expr: &str) -> Result<hir::HirType, String> {
+        let probe = format!("let {} = {{\n{}\n}};", PROBE, expr);
+        let source = self.build_source(&probe, &hir::HirType::Unit);
+        let tokens = crate::lexer::lex(&source).map_err(|e| format!("Lex error: {:?}", e))?;
+        let ast =
+            crate::parser::parse(&tokens, &source).map_err(|e| format!("Parse error: {:?}", e))?;
+        let hir = crate::check::check(&ast).map_err(|e| format!("Type error: {}", e))?;
+
+        hir.items
+            .iter()
+            .filter_map(|item| match item {
+                hir::HirItem::Function(f) if f.name == "main" => Some(&f.body.stmts),
+                _ => None,
+            })
+            .flatten()
+            .find_map(|stmt| match stmt {
+                hir::HirStmt::Let { name, ty, .. } if name == PROBE => Some(ty.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| "Type error: could not infer the type of the input".to_string())
+    }
+
+    fn build_source(&self, expr: &str, ty: &hir::HirType) -> String {
         let mut source = String::new();
 
         // Add type definitions
@@ -453,8 +458,16 @@ impl Repl {
             source.push('\n');
         }
 
-        // Wrap expression in main function
-        source.push_str(&format!("fn main() -> i64 {{\n    {}\n}}\n", expr));
+        // Wrap expression in main function, after the session's bindings
+        if *ty == hir::HirType::Unit {
+            source.push_str("fn main() {\n");
+        } else {
+            source.push_str(&format!("fn main() -> {} {{\n", ty));
+        }
+        for stmt in &self.lets {
+            source.push_str(&format!("    {}\n", stmt));
+        }
+        source.push_str(&format!("    {}\n}}\n", expr));
 
         source
     }
@@ -481,13 +494,32 @@ impl Repl {
     }
 
     fn extract_let_name(&self, input: &str) -> Option<String> {
-        // let name = ...
+        // let name = ... or let mut name = ...
         let input = input.strip_prefix("let ")?.trim_start();
+        let input = input.strip_prefix("mut ").unwrap_or(input).trim_start();
         let end = input.find(|c: char| c == '=' || c == ':' || c.is_whitespace())?;
         Some(input[..end].trim().to_string())
     }
 }
 
+/// Whether `input` has unclosed braces, parentheses or brackets and needs
+/// continuation lines. Input that does not lex is left for evaluation to
+/// report.
+fn is_incomplete(input: &str) -> bool {
+    let Ok(tokens) = crate::lexer::lex(input) else {
+        return false;
+    };
+    let mut depth = 0i32;
+    for token in &tokens {
+        match token.kind {
+            TokenKind::LBrace | TokenKind::LParen | TokenKind::LBracket => depth += 1,
+            TokenKind::RBrace | TokenKind::RParen | TokenKind::RBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
 /// Run the REPL with default configuration
 pub fn run() -> RlResult<()> {
     let mut repl = Repl::new(ReplConfig::default());
@@ -499,3 +531,93 @@ pub fn run_with_config(config: ReplConfig) -> RlResult<()> {
     let mut repl = Repl::new(config);
     repl.run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repl() -> Repl {
+        Repl::new(ReplConfig {
+            history_file: None,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_multiline_definition_persists() {
+        let mut repl = repl();
+        assert_eq!(repl.push_line("fn inc(x:i64)->i64 {"), None);
+        assert_eq!(repl.push_line("x+1"), None);
+        let input = repl.push_line("}").unwrap();
+        assert_eq!(
+            repl.eval(&input),
+            Ok(ReplOutput::Definition {
+                kind: "function",
+                name: "inc".to_string()
+            })
+        );
+
+        let input = repl.push_line("inc(41)").unwrap();
+        assert_eq!(repl.eval(&input), Ok(ReplOutput::Value(Value::Int(42))));
+    }
+
+    #[test]
+    fn test_let_bindings_persist_until_reset() {
+        let mut repl = repl();
+        assert_eq!(
+            repl.eval("let x = 40"),
+            Ok(ReplOutput::Binding {
+                name: "x".to_string(),
+                value: Value::Int(40)
+            })
+        );
+        assert_eq!(repl.eval("x + 2"), Ok(ReplOutput::Value(Value::Int(42))));
+
+        repl.reset();
+        assert!(repl.eval("x + 2").is_err());
+    }
+
+    #[test]
+    fn test_lets_are_replayed_before_each_input() {
+        let mut repl = repl();
+        repl.eval("let x = 1").unwrap();
+        repl.eval("let x = x + 1").unwrap();
+        assert_eq!(repl.eval("x * 10"), Ok(ReplOutput::Value(Value::Int(20))));
+
+        // The binding is rebuilt from its initializer, so an assignment made
+        // by an earlier input is gone
+        repl.eval("let mut n = 5").unwrap();
+        assert_eq!(
+            repl.eval("n = n + 1; n"),
+            Ok(ReplOutput::Value(Value::Int(6)))
+        );
+        assert_eq!(repl.eval("n"), Ok(ReplOutput::Value(Value::Int(5))));
+    }
+
+    #[test]
+    fn test_bindings_of_any_type_persist() {
+        let mut repl = repl();
+        assert_eq!(
+            repl.eval("let x = 1.5"),
+            Ok(ReplOutput::Binding {
+                name: "x".to_string(),
+                value: Value::Float(1.5)
+            })
+        );
+        repl.eval("let s = true").unwrap();
+        assert_eq!(
+            repl.eval("if s { x * 2.0 } else { 0.0 }"),
+            Ok(ReplOutput::Value(Value::Float(3.0)))
+        );
+        assert_eq!(repl.eval("s"), Ok(ReplOutput::Value(Value::Bool(true))));
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        assert!(is_incomplete("fn f() {"));
+        assert!(is_incomplete("foo(1,"));
+        assert!(is_incomplete("[1, 2"));
+        assert!(!is_incomplete("fn f() { 1 }"));
+        assert!(!is_incomplete(r#""{""#));
+    }
+}
@@ -6,5 +6,5 @@
 mod resolver;
 mod symbols;
 
-pub use resolver::{ResolvedAst, Resolver, resolve};
+pub use resolver::{ResolveError, ResolveErrors, ResolvedAst, Resolver, resolve};
 pub use symbols::{DefId, DefKind, Scope, ScopeKind, Symbol, SymbolTable};
@@ -4,26 +4,31 @@ use super::symbols::*;
 use crate::ast::*;
 use crate::common::{NodeId, Span};
 use miette::{Diagnostic, Result, SourceSpan};
+use std::collections::HashMap;
 use thiserror::Error;
 
 /// Resolution error
 #[derive(Error, Debug, Diagnostic)]
 pub enum ResolveError {
     #[error("Undefined variable: {name}")]
+    #[diagnostic(code(D0101))]
     UndefinedVar {
         name: String,
         #[label("not found in scope")]
-        span: SourceSpan,
+        span: Option<SourceSpan>,
     },
 
+    /// Type paths carry no source location, so there is no span
     #[error("Undefined type: {name}")]
+    #[diagnostic(code(D0102))]
     UndefinedType {
         name: String,
         #[label("type not found")]
-        span: SourceSpan,
+        span: Option<SourceSpan>,
     },
 
     #[error("Duplicate definition: {name}")]
+    #[diagnostic(code(D0103))]
     DuplicateDef {
         name: String,
         #[label("already defined")]
@@ -31,6 +36,7 @@ pub enum ResolveError {
     },
 
     #[error("Cannot use {name} as a value")]
+    #[diagnostic(code(D0104))]
     NotAValue {
         name: String,
         #[label("this is a type, not a value")]
@@ -38,6 +44,7 @@ pub enum ResolveError {
     },
 
     #[error("Cannot use {name} as a type")]
+    #[diagnostic(code(D0105))]
     NotAType {
         name: String,
         #[label("this is a value, not a type")]
@@ -45,6 +52,14 @@ pub enum ResolveError {
     },
 }
 
+/// Every error found while resolving a program, each reported with its code
+#[derive(Error, Debug, Diagnostic)]
+#[error("Resolution failed with {} error(s)", errors.len())]
+pub struct ResolveErrors {
+    #[related]
+    pub errors: Vec<ResolveError>,
+}
+
 /// Resolved AST (AST + symbol table)
 #[derive(Debug)]
 pub struct ResolvedAst {
@@ -62,6 +77,11 @@ pub fn resolve(ast: Ast) -> Result<ResolvedAst> {
 pub struct Resolver {
     symbols: SymbolTable,
     errors: Vec<ResolveError>,
+    /// Glob imports from outside this file. The names they bring in can't
+    /// be listed, so undefined names aren't reported while there are any.
+    external_globs: Vec<Path>,
+    /// Source spans of expression nodes, for error labels
+    spans: HashMap<NodeId, Span>,
 }
 
 impl Resolver {
@@ -69,27 +89,37 @@ impl Resolver {
         Self {
             symbols: SymbolTable::new(),
             errors: Vec::new(),
+            external_globs: Vec::new(),
+            spans: HashMap::new(),
         }
     }
 
     /// Resolve all names in the AST
     pub fn resolve(mut self, ast: Ast) -> Result<ResolvedAst> {
+        self.spans = ast.node_spans.clone();
+
         // First pass: collect all top-level definitions
         for item in &ast.items {
             self.collect_item(item);
         }
 
+        // Imports can name any of the collected definitions
+        for item in &ast.items {
+            if let Item::Import(i) = item {
+                self.define_import(i);
+            }
+        }
+
         // Second pass: resolve bodies
         for item in &ast.items {
             self.resolve_item(item);
         }
 
         if !self.errors.is_empty() {
-            let messages: Vec<_> = self.errors.iter().map(|e| e.to_string()).collect();
-            return Err(miette::miette!(
-                "Resolution errors:\n{}",
-                messages.join("\n")
-            ));
+            return Err(ResolveErrors {
+                errors: self.errors,
+            }
+            .into());
         }
 
         Ok(ResolvedAst {
@@ -106,8 +136,10 @@ impl Resolver {
             Item::Enum(e) => self.define_enum(e),
             Item::TypeAlias(t) => self.define_type_alias(t),
             Item::Effect(e) => self.define_effect(e),
+            Item::Handler(h) => self.define_handler(h),
             Item::Trait(t) => self.define_trait(t),
             Item::Global(g) => self.define_global(g),
+            Item::Extern(block) => self.define_extern(block),
             _ => {}
         }
     }
@@ -115,7 +147,7 @@ impl Resolver {
     fn define_function(&mut self, f: &FnDef) {
         let def_id = self.symbols.fresh_def_id();
 
-        if let Err(_) = self.symbols.define(f.name.clone(), def_id) {
+        if self.symbols.define(f.name.clone(), def_id).is_err() {
             self.errors.push(ResolveError::DuplicateDef {
                 name: f.name.clone(),
                 span: self.span_to_source(f.span),
@@ -133,10 +165,106 @@ impl Resolver {
         });
     }
 
+    /// Bring each name of an import into the module scope. Paths to local
+    /// definitions, like `Color::Red`, share their definition; anything
+    /// else is assumed to come from another module.
+    fn define_import(&mut self, i: &ImportDef) {
+        for tree in i.tree.flatten() {
+            match tree {
+                ImportTree::Path { path, alias } => {
+                    let name = alias.unwrap_or_else(|| path.name().unwrap_or("").to_string());
+                    let target = path.to_string();
+                    if let Some(def_id) = self.symbols.lookup(&target) {
+                        self.define_imported_name(name, def_id, i, false);
+                    } else if let Some(def_id) = self.symbols.lookup_type(&target) {
+                        self.define_imported_name(name, def_id, i, true);
+                    } else {
+                        let def_id = self.symbols.fresh_def_id();
+                        let _ = self.symbols.define_type(name.clone(), def_id);
+                        self.define_imported_name(name.clone(), def_id, i, false);
+                        self.symbols.insert(Symbol {
+                            def_id,
+                            name,
+                            kind: DefKind::Import,
+                            node_id: i.id,
+                            span: i.span,
+                            parent: None,
+                        });
+                    }
+                }
+                ImportTree::Glob(path) => {
+                    let prefix = format!("{}::", path);
+                    let mut names: Vec<_> = self
+                        .symbols
+                        .all_symbols()
+                        .filter_map(|s| {
+                            let name = s.name.strip_prefix(&prefix)?;
+                            (!name.contains("::")).then(|| (name.to_string(), s.def_id))
+                        })
+                        .collect();
+                    if names.is_empty() {
+                        self.external_globs.push(path);
+                    }
+                    names.sort_by(|a, b| a.0.cmp(&b.0));
+                    for (name, def_id) in names {
+                        self.define_imported_name(name, def_id, i, false);
+                    }
+                }
+                ImportTree::Group { .. } => unreachable!("flatten removes groups"),
+            }
+        }
+    }
+
+    fn define_imported_name(&mut self, name: String, def_id: DefId, i: &ImportDef, is_type: bool) {
+        // `import foo;` of a local `foo` binds nothing new
+        let existing = if is_type {
+            self.symbols.lookup_type(&name)
+        } else {
+            self.symbols.lookup(&name)
+        };
+        if existing == Some(def_id) {
+            return;
+        }
+        let defined = if is_type {
+            self.symbols.define_type(name.clone(), def_id)
+        } else {
+            self.symbols.define(name.clone(), def_id)
+        };
+        if defined.is_err() {
+            self.errors.push(ResolveError::DuplicateDef {
+                name,
+                span: self.span_to_source(i.span),
+            });
+        }
+    }
+
+    fn define_extern(&mut self, block: &ExternBlock) {
+        for f in &block.items {
+            let def_id = self.symbols.fresh_def_id();
+
+            if self.symbols.define(f.name.clone(), def_id).is_err() {
+                self.errors.push(ResolveError::DuplicateDef {
+                    name: f.name.clone(),
+                    span: self.span_to_source(block.span),
+                });
+                continue;
+            }
+
+            self.symbols.insert(Symbol {
+                def_id,
+                name: f.name.clone(),
+                kind: DefKind::Function,
+                node_id: f.id,
+                span: block.span,
+                parent: None,
+            });
+        }
+    }
+
     fn define_struct(&mut self, s: &StructDef) {
         let def_id = self.symbols.fresh_def_id();
 
-        if let Err(_) = self.symbols.define_type(s.name.clone(), def_id) {
+        if self.symbols.define_type(s.name.clone(), def_id).is_err() {
             self.errors.push(ResolveError::DuplicateDef {
                 name: s.name.clone(),
                 span: self.span_to_source(s.span),
@@ -144,6 +272,11 @@ impl Resolver {
             return;
         }
 
+        // A tuple struct's name is also its constructor: `Meters(1.0)`
+        if s.is_tuple() {
+            let _ = self.symbols.define(s.name.clone(), def_id);
+        }
+
         self.symbols.insert(Symbol {
             def_id,
             name: s.name.clone(),
@@ -221,6 +354,22 @@ impl Resolver {
         });
     }
 
+    fn define_handler(&mut self, h: &HandlerDef) {
+        let def_id = self.symbols.fresh_def_id();
+
+        // Handlers are named after `with`, alongside types
+        let _ = self.symbols.define_type(h.name.clone(), def_id);
+
+        self.symbols.insert(Symbol {
+            def_id,
+            name: h.name.clone(),
+            kind: DefKind::Handler,
+            node_id: h.id,
+            span: h.span,
+            parent: None,
+        });
+    }
+
     fn define_trait(&mut self, t: &TraitDef) {
         let def_id = self.symbols.fresh_def_id();
 
@@ -239,7 +388,7 @@ impl Resolver {
     fn define_global(&mut self, g: &GlobalDef) {
         let def_id = self.symbols.fresh_def_id();
 
-        if let Pattern::Binding { name, mutable } = &g.pattern {
+        if let Pattern::Binding { name, mutable, .. } = &g.pattern {
             let _ = self.symbols.define(name.clone(), def_id);
             self.symbols.insert(Symbol {
                 def_id,
@@ -264,27 +413,80 @@ impl Resolver {
             Item::Enum(e) => self.resolve_enum(e),
             Item::TypeAlias(t) => self.resolve_type_alias(t),
             Item::Global(g) => self.resolve_global(g),
+            Item::Effect(e) => {
+                for member in &e.members {
+                    self.resolve_effect_ref(member);
+                }
+            }
+            Item::Handler(h) => self.resolve_handler(h),
+            Item::Impl(i) => self.resolve_impl(i),
             _ => {}
         }
     }
 
-    fn resolve_function(&mut self, f: &FnDef) {
-        let fn_def_id = self.symbols.def_for_node(f.id);
-        self.symbols.push_scope(ScopeKind::Function, fn_def_id);
+    fn resolve_impl(&mut self, i: &ImplDef) {
+        self.symbols.push_scope(ScopeKind::Impl, None);
 
-        // Resolve generic parameters
-        for param in &f.generics.params {
-            if let GenericParam::Type { name, bounds, .. } = param {
+        for param in &i.generics.params {
+            if let GenericParam::Type { name, .. } = param {
                 let def_id = self.symbols.fresh_def_id();
                 let _ = self.symbols.define_type(name.clone(), def_id);
                 self.symbols.insert(Symbol {
                     def_id,
                     name: name.clone(),
                     kind: DefKind::TypeParam,
-                    node_id: NodeId(0), // No node ID for generic params
+                    node_id: NodeId(0),
                     span: Span::default(),
-                    parent: fn_def_id,
+                    parent: None,
                 });
+            }
+        }
+        self.resolve_type_expr(&i.target_type);
+
+        // `Self` names the target type inside the impl's methods
+        let def_id = self.symbols.fresh_def_id();
+        let _ = self.symbols.define_type("Self".to_string(), def_id);
+        self.symbols.insert(Symbol {
+            def_id,
+            name: "Self".to_string(),
+            kind: DefKind::TypeAlias,
+            node_id: NodeId(0),
+            span: Span::default(),
+            parent: None,
+        });
+
+        for item in &i.items {
+            if let ImplItem::Fn(f) = item {
+                self.resolve_function(f);
+            }
+        }
+
+        self.symbols.pop_scope();
+    }
+
+    fn resolve_function(&mut self, f: &FnDef) {
+        let fn_def_id = self.symbols.def_for_node(f.id);
+        self.symbols.push_scope(ScopeKind::Function, fn_def_id);
+
+        // Resolve generic parameters. Const parameters live in the type
+        // namespace too, so they can be passed on as generic arguments.
+        for param in &f.generics.params {
+            let (name, kind) = match param {
+                GenericParam::Type { name, .. } => (name, DefKind::TypeParam),
+                GenericParam::Const { name, .. } => (name, DefKind::Const),
+                GenericParam::Lifetime { .. } => continue,
+            };
+            let def_id = self.symbols.fresh_def_id();
+            let _ = self.symbols.define_type(name.clone(), def_id);
+            self.symbols.insert(Symbol {
+                def_id,
+                name: name.clone(),
+                kind,
+                node_id: NodeId(0), // No node ID for generic params
+                span: Span::default(),
+                parent: fn_def_id,
+            });
+            if let GenericParam::Type { bounds, .. } = param {
                 for bound in bounds {
                     self.resolve_path_as_type(bound);
                 }
@@ -312,6 +514,44 @@ impl Resolver {
         self.symbols.pop_scope();
     }
 
+    fn resolve_handler(&mut self, h: &HandlerDef) {
+        self.resolve_path_as_type(&h.effect);
+        for arg in &h.effect_args {
+            self.resolve_type_expr(arg);
+        }
+
+        for case in &h.cases {
+            self.symbols.push_scope(ScopeKind::Function, None);
+            for param in &case.params {
+                self.resolve_param(param);
+            }
+            self.resolve_pattern(
+                &Pattern::Binding {
+                    id: case.id,
+                    name: "resume".to_string(),
+                    mutable: false,
+                },
+                false,
+            );
+            self.resolve_expr(&case.body);
+            self.symbols.pop_scope();
+        }
+
+        if let Some(ret) = &h.return_clause {
+            self.symbols.push_scope(ScopeKind::Function, None);
+            self.resolve_pattern(
+                &Pattern::Binding {
+                    id: ret.id,
+                    name: ret.param.clone(),
+                    mutable: false,
+                },
+                false,
+            );
+            self.resolve_expr(&ret.body);
+            self.symbols.pop_scope();
+        }
+    }
+
     fn resolve_struct(&mut self, s: &StructDef) {
         self.symbols.push_scope(ScopeKind::TypeDef, None);
 
@@ -332,8 +572,8 @@ impl Resolver {
         }
 
         // Resolve field types
-        for field in &s.fields {
-            self.resolve_type_expr(&field.ty);
+        for (_, ty) in s.field_types() {
+            self.resolve_type_expr(ty);
         }
 
         self.symbols.pop_scope();
@@ -398,7 +638,7 @@ impl Resolver {
         self.resolve_type_expr(&param.ty);
 
         // Then bind the parameter name
-        if let Pattern::Binding { name, mutable } = &param.pattern {
+        if let Pattern::Binding { name, mutable, .. } = &param.pattern {
             let def_id = self.symbols.fresh_def_id();
             let _ = self.symbols.define(name.clone(), def_id);
             self.symbols.insert(Symbol {
@@ -416,6 +656,12 @@ impl Resolver {
         match ty {
             TypeExpr::Named { path, args, .. } => {
                 self.resolve_path_as_type(path);
+                // The unit of `Quantity<T, U>` may name a unit such as `mg`
+                // rather than a type
+                let args = match path.name() {
+                    Some("Quantity") if args.len() == 2 => &args[..1],
+                    _ => &args[..],
+                };
                 for arg in args {
                     self.resolve_type_expr(arg);
                 }
@@ -431,6 +677,7 @@ impl Resolver {
                     self.resolve_type_expr(t);
                 }
             }
+            TypeExpr::Dyn { path, .. } => self.resolve_path_as_type(path),
             TypeExpr::Function {
                 params,
                 return_type,
@@ -444,6 +691,7 @@ impl Resolver {
                     self.resolve_effect_ref(eff);
                 }
             }
+            TypeExpr::Const(value) => self.resolve_expr(value),
             TypeExpr::Unit | TypeExpr::SelfType | TypeExpr::Infer => {}
         }
     }
@@ -451,10 +699,10 @@ impl Resolver {
     fn resolve_path_as_type(&mut self, path: &Path) {
         if path.is_simple() {
             let name = path.name().unwrap();
-            if self.symbols.lookup_type(name).is_none() {
+            if self.symbols.lookup_type(name).is_none() && self.external_globs.is_empty() {
                 self.errors.push(ResolveError::UndefinedType {
                     name: name.to_string(),
-                    span: SourceSpan::from(0..1),
+                    span: None,
                 });
             }
         }
@@ -497,6 +745,21 @@ impl Resolver {
                 // Now bind the variable
                 self.resolve_pattern(pattern, *is_mut);
             }
+            Stmt::LetElse {
+                is_mut,
+                pattern,
+                ty,
+                value,
+                else_block,
+            } => {
+                self.resolve_expr(value);
+                if let Some(t) = ty {
+                    self.resolve_type_expr(t);
+                }
+                // The else block can't see the bindings it guards
+                self.resolve_block(else_block);
+                self.resolve_pattern(pattern, *is_mut);
+            }
             Stmt::Expr { expr, .. } => {
                 self.resolve_expr(expr);
             }
@@ -517,14 +780,17 @@ impl Resolver {
                     let name = path.name().unwrap();
                     if let Some(def_id) = self.symbols.lookup(name) {
                         self.symbols.record_ref(*id, def_id);
-                    } else {
+                    } else if self.external_globs.is_empty() {
                         self.errors.push(ResolveError::UndefinedVar {
                             name: name.to_string(),
-                            span: SourceSpan::from(0..1),
+                            span: self.spans.get(id).map(|&span| self.span_to_source(span)),
                         });
                     }
                 }
                 // TODO: multi-segment paths
+                for ty in path.segment_args.iter().flat_map(|(_, args)| args) {
+                    self.resolve_type_expr(ty);
+                }
             }
 
             Expr::Binary { left, right, .. } => {
@@ -536,8 +802,16 @@ impl Resolver {
                 self.resolve_expr(expr);
             }
 
-            Expr::Call { callee, args, .. } => {
+            Expr::Call {
+                callee,
+                type_args,
+                args,
+                ..
+            } => {
                 self.resolve_expr(callee);
+                for ty in type_args {
+                    self.resolve_type_expr(ty);
+                }
                 for arg in args {
                     self.resolve_expr(arg);
                 }
@@ -620,6 +894,11 @@ impl Resolver {
                 self.symbols.pop_scope();
             }
 
+            Expr::Range { start, end, .. } => {
+                self.resolve_expr(start);
+                self.resolve_expr(end);
+            }
+
             Expr::Return { value, .. } => {
                 if let Some(val) = value {
                     self.resolve_expr(val);
@@ -661,6 +940,11 @@ impl Resolver {
                 }
             }
 
+            // The count is a constant, evaluated like an array type's length
+            Expr::ArrayRepeat { value, .. } => {
+                self.resolve_expr(value);
+            }
+
             Expr::StructLit { path, fields, .. } => {
                 self.resolve_path_as_type(path);
                 for (_, value) in fields {
@@ -690,23 +974,35 @@ impl Resolver {
         }
     }
 
+    fn define_pattern_binding(&mut self, id: NodeId, name: &str, mutable: bool) {
+        let def_id = self.symbols.fresh_def_id();
+        let _ = self.symbols.define(name.to_string(), def_id);
+        self.symbols.insert(Symbol {
+            def_id,
+            name: name.to_string(),
+            kind: DefKind::Variable { mutable },
+            node_id: id,
+            span: Span::default(),
+            parent: None,
+        });
+    }
+
     fn resolve_pattern(&mut self, pat: &Pattern, is_mut: bool) {
         match pat {
-            Pattern::Wildcard | Pattern::Literal(_) => {}
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => {}
 
-            Pattern::Binding { name, mutable } => {
-                let def_id = self.symbols.fresh_def_id();
-                let _ = self.symbols.define(name.clone(), def_id);
-                self.symbols.insert(Symbol {
-                    def_id,
-                    name: name.clone(),
-                    kind: DefKind::Variable {
-                        mutable: is_mut || *mutable,
-                    },
-                    node_id: NodeId(0),
-                    span: Span::default(),
-                    parent: None,
-                });
+            Pattern::Binding { id, name, mutable } => {
+                self.define_pattern_binding(*id, name, is_mut || *mutable);
+            }
+
+            Pattern::At {
+                id,
+                name,
+                mutable,
+                pattern,
+            } => {
+                self.define_pattern_binding(*id, name, is_mut || *mutable);
+                self.resolve_pattern(pattern, is_mut);
             }
 
             Pattern::Tuple(patterns) => {
@@ -727,10 +1023,12 @@ impl Resolver {
                 if path.segments.len() >= 2 {
                     // Full path like Option::Some
                     let type_name = &path.segments[0];
-                    if self.symbols.lookup_type(type_name).is_none() {
+                    if self.symbols.lookup_type(type_name).is_none()
+                        && self.external_globs.is_empty()
+                    {
                         self.errors.push(ResolveError::UndefinedType {
                             name: type_name.clone(),
-                            span: SourceSpan::from(0..1),
+                            span: None,
                         });
                     }
                 }
@@ -36,6 +36,8 @@ pub enum DefKind {
     Effect,
     /// Effect operation
     EffectOp,
+    /// Effect handler
+    Handler,
     /// Constant
     Const,
     /// Module
@@ -48,6 +50,8 @@ pub enum DefKind {
     Kernel,
     /// Built-in type
     BuiltinType,
+    /// Name imported from outside this file
+    Import,
 }
 
 /// Symbol information
@@ -70,6 +74,8 @@ pub struct Symbol {
 /// Scope level
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ScopeKind {
+    /// Built-in names, which a module may shadow
+    Prelude,
     /// Module/file level
     Module,
     /// Function body
@@ -129,10 +135,10 @@ impl SymbolTable {
             node_to_def: HashMap::new(),
             node_to_ref: HashMap::new(),
         };
-        // Start with module scope
-        table.push_scope(ScopeKind::Module, None);
-        // Register built-in types
+        // Built-ins live below the module scope, so definitions shadow them
+        table.push_scope(ScopeKind::Prelude, None);
         table.register_builtins();
+        table.push_scope(ScopeKind::Module, None);
         table
     }
 
@@ -140,7 +146,7 @@ impl SymbolTable {
         // Built-in types
         let builtins = [
             "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
-            "f32", "f64", "bool", "char", "String", "str",
+            "f32", "f64", "bool", "char", "String", "str", "Quantity",
         ];
 
         for name in builtins {
@@ -186,6 +192,47 @@ impl SymbolTable {
                 },
             );
         }
+
+        // Prelude enums and their variants
+        let prelude_enums = [("Option", ["Some", "None"]), ("Result", ["Ok", "Err"])];
+        for (name, variants) in prelude_enums {
+            let enum_id = self.define_builtin(
+                name,
+                DefKind::Enum {
+                    is_linear: false,
+                    is_affine: false,
+                },
+                None,
+            );
+            let _ = self.define_type(name.to_string(), enum_id);
+            for variant in variants {
+                let variant_id = self.define_builtin(variant, DefKind::Variant, Some(enum_id));
+                let _ = self.define(variant.to_string(), variant_id);
+            }
+        }
+
+        // Distribution constructors for `sample`
+        for name in ["Normal", "Uniform", "Bernoulli"] {
+            let def_id = self.define_builtin(name, DefKind::Function, None);
+            let _ = self.define(name.to_string(), def_id);
+        }
+    }
+
+    /// Add the symbol of a built-in definition, without binding its name
+    fn define_builtin(&mut self, name: &str, kind: DefKind, parent: Option<DefId>) -> DefId {
+        let def_id = self.fresh_def_id();
+        self.symbols.insert(
+            def_id,
+            Symbol {
+                def_id,
+                name: name.to_string(),
+                kind,
+                node_id: NodeId(0),
+                span: Span::default(),
+                parent,
+            },
+        );
+        def_id
     }
 
     /// Generate fresh DefId
@@ -279,6 +326,11 @@ impl SymbolTable {
         self.node_to_ref.get(&node_id).copied()
     }
 
+    /// All recorded references, as (reference node, definition) pairs
+    pub fn refs(&self) -> impl Iterator<Item = (NodeId, DefId)> + '_ {
+        self.node_to_ref.iter().map(|(&node, &def)| (node, def))
+    }
+
     /// Current scope depth
     pub fn depth(&self) -> usize {
         self.scopes.len()
@@ -286,7 +338,7 @@ impl SymbolTable {
 
     /// Check if we're at module level
     pub fn at_module_level(&self) -> bool {
-        self.scopes.len() == 1
+        self.scopes.len() == 2
     }
 
     /// Get all symbols
@@ -356,4 +408,18 @@ mod tests {
         // Unknown type should not exist
         assert!(table.lookup_type("FooBar").is_none());
     }
+
+    #[test]
+    fn test_prelude_enums() {
+        let mut table = SymbolTable::new();
+        let option = table.lookup_type("Option").unwrap();
+        let some = table.lookup("Some").unwrap();
+        assert_eq!(table.get(some).unwrap().parent, Some(option));
+        assert!(table.lookup("Err").is_some());
+
+        // A module's own definition shadows the prelude one
+        let def_id = table.fresh_def_id();
+        assert!(table.define_type("Option".to_string(), def_id).is_ok());
+        assert_eq!(table.lookup_type("Option"), Some(def_id));
+    }
 }
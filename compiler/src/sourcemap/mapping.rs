@@ -2,7 +2,9 @@
 //!
 //! Maps generated code locations back to source locations.
 
-use super::location::Span;
+use super::files::SourceFile;
+use super::location::{SourceLocation, Span};
+use crate::common::NodeId;
 use crate::hlir::ir::{BlockId, ValueId};
 use std::collections::HashMap;
 
@@ -20,6 +22,9 @@ pub struct SourceMap {
 
     /// Instruction index to span (for generated code)
     instr_spans: HashMap<(String, usize), Span>,
+
+    /// AST node to line and column, for nodes carried onto HLIR instructions
+    node_locations: HashMap<NodeId, SourceLocation>,
 }
 
 impl SourceMap {
@@ -27,6 +32,29 @@ impl SourceMap {
         Self::default()
     }
 
+    /// Build a source map from the node spans the parser recorded for `file`
+    pub fn from_node_spans(
+        file: &SourceFile,
+        spans: &HashMap<NodeId, crate::common::Span>,
+    ) -> Self {
+        let mut map = Self::new();
+        for (&node, span) in spans {
+            let (line, column) = file.line_col(span.start);
+            map.record_node(node, SourceLocation::new(file.id, line, column));
+        }
+        map
+    }
+
+    /// Record the line and column an AST node starts at
+    pub fn record_node(&mut self, node: NodeId, location: SourceLocation) {
+        self.node_locations.insert(node, location);
+    }
+
+    /// Get the line and column an AST node starts at
+    pub fn get_node_location(&self, node: NodeId) -> Option<SourceLocation> {
+        self.node_locations.get(&node).copied()
+    }
+
     /// Record a value's source location
     pub fn record_value(&mut self, value: ValueId, span: Span) {
         self.value_spans.insert(value, span);
@@ -73,6 +101,7 @@ impl SourceMap {
         self.block_spans.extend(other.block_spans);
         self.func_spans.extend(other.func_spans);
         self.instr_spans.extend(other.instr_spans);
+        self.node_locations.extend(other.node_locations);
     }
 }
 
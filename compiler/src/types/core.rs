@@ -1,6 +1,7 @@
 //! Core type definitions
 
 use std::collections::HashSet;
+use std::fmt;
 
 /// Type variable for polymorphism
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -40,7 +41,7 @@ pub enum Type {
     /// Array: [T; N] or slice [T]
     Array {
         element: Box<Type>,
-        size: Option<usize>,
+        size: Option<ArrayLen>,
     },
     /// Tuple: (T1, T2, ...)
     Tuple(Vec<Type>),
@@ -55,6 +56,13 @@ pub enum Type {
         name: String,
         args: Vec<Type>,
     },
+    /// Numeric type carrying a unit of measure: f64@kg
+    Quantity {
+        numeric: Box<Type>,
+        unit: String,
+    },
+    /// Trait object: dyn Trait, used behind a reference
+    Dyn(String),
 
     // Polymorphism
     /// Type variable
@@ -188,6 +196,7 @@ impl Type {
                     arg.collect_free_vars(vars);
                 }
             }
+            Type::Quantity { numeric, .. } => numeric.collect_free_vars(vars),
             Type::Forall { vars: bound, inner } => {
                 let mut inner_vars = HashSet::new();
                 inner.collect_free_vars(&mut inner_vars);
@@ -216,7 +225,7 @@ impl Type {
             },
             Type::Array { element, size } => Type::Array {
                 element: Box::new(element.substitute(subst)),
-                size: *size,
+                size: size.clone(),
             },
             Type::Tuple(elems) => Type::Tuple(elems.iter().map(|e| e.substitute(subst)).collect()),
             Type::Function {
@@ -232,6 +241,10 @@ impl Type {
                 name: name.clone(),
                 args: args.iter().map(|a| a.substitute(subst)).collect(),
             },
+            Type::Quantity { numeric, unit } => Type::Quantity {
+                numeric: Box::new(numeric.substitute(subst)),
+                unit: unit.clone(),
+            },
             Type::Forall { vars, inner } => {
                 // Avoid capturing bound variables
                 let mut new_subst = subst.clone();
@@ -248,6 +261,141 @@ impl Type {
     }
 }
 
+/// Renders types in surface syntax, e.g. `&mut [f64]` or `Option<i64>`
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Unit => write!(f, "()"),
+            Type::Bool => write!(f, "bool"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::I128 => write!(f, "i128"),
+            Type::Isize => write!(f, "isize"),
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::U128 => write!(f, "u128"),
+            Type::Usize => write!(f, "usize"),
+            Type::F32 => write!(f, "f32"),
+            Type::F64 => write!(f, "f64"),
+            Type::Char => write!(f, "char"),
+            Type::Str => write!(f, "str"),
+            Type::String => write!(f, "String"),
+            Type::Ref {
+                mutable,
+                lifetime,
+                inner,
+            } => {
+                write!(f, "&")?;
+                if let Some(lt) = lifetime {
+                    write!(f, "{} ", lt.name)?;
+                }
+                if *mutable {
+                    write!(f, "mut ")?;
+                }
+                write!(f, "{}", inner)
+            }
+            Type::Array { element, size } => match size {
+                Some(n) => write!(f, "[{}; {}]", element, n),
+                None => write!(f, "[{}]", element),
+            },
+            Type::Tuple(elems) => {
+                write!(f, "(")?;
+                write_list(f, elems)?;
+                if elems.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Type::Function {
+                params,
+                return_type,
+                effects,
+            } => {
+                write!(f, "fn(")?;
+                write_list(f, params)?;
+                write!(f, ") -> {}", return_type)?;
+                if !effects.effects.is_empty() {
+                    let mut names: Vec<_> = effects.effects.iter().collect();
+                    names.sort();
+                    write!(f, " with ")?;
+                    for (i, name) in names.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", name)?;
+                    }
+                }
+                Ok(())
+            }
+            Type::Named { name, args } => {
+                write!(f, "{}", name)?;
+                if !args.is_empty() {
+                    write!(f, "<")?;
+                    write_list(f, args)?;
+                    write!(f, ">")?;
+                }
+                Ok(())
+            }
+            Type::Quantity { numeric, unit } => write!(f, "{}@{}", numeric, unit),
+            Type::Dyn(name) => write!(f, "dyn {}", name),
+            Type::Var(v) => write!(f, "?T{}", v.0),
+            Type::Forall { vars, inner } => {
+                write!(f, "forall")?;
+                for v in vars {
+                    write!(f, " ?T{}", v.0)?;
+                }
+                write!(f, ". {}", inner)
+            }
+            Type::Never => write!(f, "!"),
+            Type::Unknown => write!(f, "_"),
+            Type::Error => write!(f, "{{error}}"),
+            Type::SelfType => write!(f, "Self"),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, types: &[Type]) -> fmt::Result {
+    for (i, ty) in types.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", ty)?;
+    }
+    Ok(())
+}
+
+/// Length of a fixed-size array type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArrayLen {
+    /// A known length: `[T; 3]`
+    Fixed(usize),
+    /// A const generic parameter: `[T; N]`
+    Param(String),
+}
+
+impl ArrayLen {
+    /// The length, when it is known
+    pub fn fixed(&self) -> Option<usize> {
+        match self {
+            ArrayLen::Fixed(n) => Some(*n),
+            ArrayLen::Param(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ArrayLen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArrayLen::Fixed(n) => write!(f, "{}", n),
+            ArrayLen::Param(name) => write!(f, "{}", name),
+        }
+    }
+}
+
 /// Lifetime for references
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Lifetime {
@@ -404,6 +552,103 @@ mod tests {
         assert!(vars.contains(&v2));
     }
 
+    #[test]
+    fn test_display_primitives() {
+        assert_eq!(Type::Unit.to_string(), "()");
+        assert_eq!(Type::Bool.to_string(), "bool");
+        assert_eq!(Type::I64.to_string(), "i64");
+        assert_eq!(Type::Usize.to_string(), "usize");
+        assert_eq!(Type::F64.to_string(), "f64");
+        assert_eq!(Type::Char.to_string(), "char");
+        assert_eq!(Type::Str.to_string(), "str");
+        assert_eq!(Type::String.to_string(), "String");
+    }
+
+    #[test]
+    fn test_display_compound() {
+        let slice = Type::Array {
+            element: Box::new(Type::F64),
+            size: None,
+        };
+        let mut_slice = Type::Ref {
+            mutable: true,
+            lifetime: None,
+            inner: Box::new(slice.clone()),
+        };
+        assert_eq!(mut_slice.to_string(), "&mut [f64]");
+
+        let static_str = Type::Ref {
+            mutable: false,
+            lifetime: Some(Lifetime::static_lifetime()),
+            inner: Box::new(Type::Str),
+        };
+        assert_eq!(static_str.to_string(), "&'static str");
+
+        let fixed = Type::Array {
+            element: Box::new(Type::I32),
+            size: Some(ArrayLen::Fixed(4)),
+        };
+        assert_eq!(fixed.to_string(), "[i32; 4]");
+
+        let generic = Type::Array {
+            element: Box::new(Type::F64),
+            size: Some(ArrayLen::Param("N".to_string())),
+        };
+        assert_eq!(generic.to_string(), "[f64; N]");
+
+        assert_eq!(
+            Type::Tuple(vec![Type::I32, Type::Bool]).to_string(),
+            "(i32, bool)"
+        );
+        assert_eq!(Type::Tuple(vec![Type::I32]).to_string(), "(i32,)");
+    }
+
+    #[test]
+    fn test_display_named_and_functions() {
+        let option = Type::Named {
+            name: "Option".to_string(),
+            args: vec![Type::I64],
+        };
+        assert_eq!(option.to_string(), "Option<i64>");
+
+        let point = Type::Named {
+            name: "Point".to_string(),
+            args: vec![],
+        };
+        assert_eq!(point.to_string(), "Point");
+
+        let func = Type::Function {
+            params: vec![Type::I64, point],
+            return_type: Box::new(option),
+            effects: EffectSet::single(Effect::io()),
+        };
+        assert_eq!(func.to_string(), "fn(i64, Point) -> Option<i64> with IO");
+    }
+
+    #[test]
+    fn test_display_special() {
+        let mass = Type::Quantity {
+            numeric: Box::new(Type::F64),
+            unit: "kg".to_string(),
+        };
+        assert_eq!(mass.to_string(), "f64@kg");
+
+        let id = Type::Forall {
+            vars: vec![TypeVar(0)],
+            inner: Box::new(Type::Function {
+                params: vec![Type::Var(TypeVar(0))],
+                return_type: Box::new(Type::Var(TypeVar(0))),
+                effects: EffectSet::new(),
+            }),
+        };
+        assert_eq!(id.to_string(), "forall ?T0. fn(?T0) -> ?T0");
+
+        assert_eq!(Type::Never.to_string(), "!");
+        assert_eq!(Type::Unknown.to_string(), "_");
+        assert_eq!(Type::Error.to_string(), "{error}");
+        assert_eq!(Type::SelfType.to_string(), "Self");
+    }
+
     #[test]
     fn test_effect_set() {
         let mut effects = EffectSet::new();
@@ -11,6 +11,7 @@ pub mod core;
 pub mod effects;
 pub mod ownership;
 pub mod refinement;
+pub mod unify;
 pub mod unit_infer;
 pub mod units;
 
@@ -22,5 +23,6 @@ pub use ownership::*;
 pub use refinement::{
     ArithOp, CompareOp, Predicate, RefinedType, RefinementChecker, RefinementResult,
 };
+pub use unify::{Substitution, UnifyError, unify, unify_with};
 pub use unit_infer::{UnitExpr, UnitInference, UnitInferenceError, UnitVar};
 pub use units::{Unit, UnitChecker, UnitError, UnitOp};
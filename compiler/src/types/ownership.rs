@@ -46,6 +46,9 @@ pub fn ownership_of(ty: &Type) -> Ownership {
         | Type::F64
         | Type::Char => Ownership::Copy,
 
+        // Quantities are numbers with a unit attached
+        Type::Quantity { numeric, .. } => ownership_of(numeric),
+
         // References are Copy (the reference itself, not the data)
         Type::Ref { .. } => Ownership::Copy,
 
@@ -80,8 +83,9 @@ pub fn ownership_of(ty: &Type) -> Ownership {
             result
         }
 
-        // Named types default to Affine (could be overridden by type definition)
-        Type::Named { .. } => Ownership::Affine,
+        // Named types default to Affine (could be overridden by type definition),
+        // as do trait objects, whose concrete type is unknown
+        Type::Named { .. } | Type::Dyn(_) => Ownership::Affine,
 
         // Type variables are Affine by default
         Type::Var(_) | Type::Forall { .. } => Ownership::Affine,
@@ -5,6 +5,8 @@
 //! like array bounds, null safety, and domain-specific constraints.
 
 use super::core::Type;
+use crate::refinement as smt;
+use crate::refinement::VerifyResult;
 
 /// Refinement predicate
 #[derive(Debug, Clone)]
@@ -390,17 +392,54 @@ impl RefinementChecker {
         self.path_condition.push(pred);
     }
 
+    /// Number of path conditions currently assumed
+    pub fn path_depth(&self) -> usize {
+        self.path_condition.len()
+    }
+
+    /// Drop the path conditions assumed since the path had `depth` entries
+    pub fn restore_path(&mut self, depth: usize) {
+        self.path_condition.truncate(depth);
+    }
+
     /// Check if a predicate is valid under current path conditions
+    ///
+    /// The query goes to the SMT backend first. When Z3 is not compiled in
+    /// or gives up, difference constraints such as `0 <= i < len(arr)` are
+    /// still decided by a built-in procedure.
     pub fn check(&self, pred: &Predicate) -> RefinementResult {
-        // This would use an SMT solver in a real implementation
-        // For now, just do simple checks
         if pred.is_trivially_true() {
             return RefinementResult::Valid;
         }
         if pred.is_trivially_false() {
             return RefinementResult::Invalid("Predicate is trivially false".to_string());
         }
-        RefinementResult::Unknown
+
+        let query = self
+            .path_condition
+            .iter()
+            .map(smt_predicate)
+            .collect::<Option<Vec<_>>>()
+            .zip(smt_predicate(pred));
+        if let Some((path, goal)) = query {
+            let goal = smt::Predicate::implies(smt::Predicate::and(path), goal);
+            match smt_verify(goal) {
+                VerifyResult::Valid => return RefinementResult::Valid,
+                VerifyResult::Invalid { counterexample, .. } => {
+                    return RefinementResult::Invalid(match counterexample {
+                        Some(counterexample) => counterexample.to_string(),
+                        None => "Predicate can be violated".to_string(),
+                    });
+                }
+                VerifyResult::Unknown { .. } => {}
+            }
+        }
+
+        if difference_logic_valid(&self.path_condition, pred) {
+            RefinementResult::Valid
+        } else {
+            RefinementResult::Unknown
+        }
     }
 
     /// Check subtyping: is `sub` a subtype of `sup`?
@@ -439,6 +478,264 @@ impl Default for RefinementChecker {
     }
 }
 
+/// Translate a predicate into the SMT backend's formula language
+fn smt_predicate(pred: &Predicate) -> Option<smt::Predicate> {
+    Some(match pred {
+        Predicate::Bool(true) => smt::Predicate::True,
+        Predicate::Bool(false) => smt::Predicate::False,
+        Predicate::Compare(op, lhs, rhs) => {
+            let op = match op {
+                CompareOp::Eq => smt::CompareOp::Eq,
+                CompareOp::Ne => smt::CompareOp::Ne,
+                CompareOp::Lt => smt::CompareOp::Lt,
+                CompareOp::Le => smt::CompareOp::Le,
+                CompareOp::Gt => smt::CompareOp::Gt,
+                CompareOp::Ge => smt::CompareOp::Ge,
+            };
+            smt::Predicate::Atom(smt::Atom::new(op, smt_term(lhs)?, smt_term(rhs)?))
+        }
+        Predicate::And(lhs, rhs) => smt::Predicate::and([smt_predicate(lhs)?, smt_predicate(rhs)?]),
+        Predicate::Or(lhs, rhs) => smt::Predicate::or([smt_predicate(lhs)?, smt_predicate(rhs)?]),
+        Predicate::Not(inner) => smt::Predicate::not(smt_predicate(inner)?),
+        Predicate::Implies(lhs, rhs) => {
+            smt::Predicate::implies(smt_predicate(lhs)?, smt_predicate(rhs)?)
+        }
+        Predicate::Ite(cond, then, otherwise) => smt::Predicate::Ite(
+            Box::new(smt_predicate(cond)?),
+            Box::new(smt_predicate(then)?),
+            Box::new(smt_predicate(otherwise)?),
+        ),
+        _ => return None,
+    })
+}
+
+/// Translate an arithmetic predicate into an SMT term
+fn smt_term(pred: &Predicate) -> Option<smt::Term> {
+    Some(match pred {
+        Predicate::Int(n) => smt::Term::Int(*n),
+        Predicate::Float(f) => smt::Term::Float(*f),
+        Predicate::Bool(b) => smt::Term::Bool(*b),
+        Predicate::Var(name) => smt::Term::Var(name.clone()),
+        Predicate::Arith(op, lhs, rhs) => {
+            let op = match op {
+                ArithOp::Add => smt::BinOp::Add,
+                ArithOp::Sub => smt::BinOp::Sub,
+                ArithOp::Mul => smt::BinOp::Mul,
+                ArithOp::Div => smt::BinOp::Div,
+                ArithOp::Mod => smt::BinOp::Mod,
+            };
+            smt::Term::BinOp(op, Box::new(smt_term(lhs)?), Box::new(smt_term(rhs)?))
+        }
+        Predicate::App(name, args) if name == "len" && args.len() == 1 => {
+            smt::Term::len(smt_term(&args[0])?)
+        }
+        Predicate::App(name, args) => smt::Term::App(
+            name.clone(),
+            args.iter().map(smt_term).collect::<Option<_>>()?,
+        ),
+        _ => return None,
+    })
+}
+
+/// Prove `goal` with Z3
+#[cfg(feature = "smt")]
+fn smt_verify(goal: smt::Predicate) -> VerifyResult {
+    let cfg = z3::Config::new();
+    let ctx = z3::Context::new(&cfg);
+    smt::Z3Solver::new(&ctx)
+        .verify(&[smt_constraint(goal)])
+        .remove(0)
+}
+
+/// Without Z3 every query comes back unknown
+#[cfg(not(feature = "smt"))]
+fn smt_verify(goal: smt::Predicate) -> VerifyResult {
+    smt::Z3Solver::new()
+        .verify(&[smt_constraint(goal)])
+        .remove(0)
+}
+
+fn smt_constraint(goal: smt::Predicate) -> smt::Constraint {
+    smt::Constraint::new(
+        Vec::new(),
+        goal,
+        smt::Span::dummy(),
+        smt::ConstraintReason::Assert {
+            message: "refinement check".to_string(),
+        },
+    )
+}
+
+/// `x - y <= k`, where a missing variable stands for zero
+#[derive(Debug, Clone)]
+struct DifferenceBound {
+    x: Option<String>,
+    y: Option<String>,
+    k: i64,
+}
+
+/// Decide `assumptions => goal` when both are built from difference
+/// constraints over integers and array lengths. Assumptions outside that
+/// fragment are ignored, which only loses precision; a goal outside it is
+/// never proven.
+fn difference_logic_valid(assumptions: &[Predicate], goal: &Predicate) -> bool {
+    let mut facts = Vec::new();
+    for assumption in assumptions {
+        collect_bounds(assumption, &mut facts);
+    }
+    bounds_imply(&mut facts, goal)
+}
+
+fn bounds_imply(facts: &mut Vec<DifferenceBound>, goal: &Predicate) -> bool {
+    match goal {
+        Predicate::Bool(b) => *b,
+        Predicate::And(lhs, rhs) => bounds_imply(facts, lhs) && bounds_imply(facts, rhs),
+        Predicate::Implies(premise, conclusion) => {
+            let depth = facts.len();
+            collect_bounds(premise, facts);
+            let valid = bounds_imply(facts, conclusion);
+            facts.truncate(depth);
+            valid
+        }
+        Predicate::Compare(CompareOp::Eq, lhs, rhs) => {
+            bounds_imply(facts, &Predicate::le((**lhs).clone(), (**rhs).clone()))
+                && bounds_imply(facts, &Predicate::ge((**lhs).clone(), (**rhs).clone()))
+        }
+        Predicate::Compare(op, lhs, rhs) => {
+            // The goal holds when its negation contradicts the facts
+            let Some(negated) = negate(*op) else {
+                return false;
+            };
+            let Some(bounds) = difference_bounds(negated, lhs, rhs) else {
+                return false;
+            };
+            let depth = facts.len();
+            facts.extend(bounds);
+            let valid = !satisfiable(facts);
+            facts.truncate(depth);
+            valid
+        }
+        _ => false,
+    }
+}
+
+fn collect_bounds(pred: &Predicate, facts: &mut Vec<DifferenceBound>) {
+    match pred {
+        Predicate::And(lhs, rhs) => {
+            collect_bounds(lhs, facts);
+            collect_bounds(rhs, facts);
+        }
+        Predicate::Compare(op, lhs, rhs) => {
+            facts.extend(difference_bounds(*op, lhs, rhs).unwrap_or_default());
+        }
+        Predicate::Not(inner) => {
+            if let Predicate::Compare(op, lhs, rhs) = &**inner
+                && let Some(op) = negate(*op)
+            {
+                facts.extend(difference_bounds(op, lhs, rhs).unwrap_or_default());
+            }
+        }
+        _ => {}
+    }
+}
+
+fn negate(op: CompareOp) -> Option<CompareOp> {
+    match op {
+        CompareOp::Lt => Some(CompareOp::Ge),
+        CompareOp::Le => Some(CompareOp::Gt),
+        CompareOp::Gt => Some(CompareOp::Le),
+        CompareOp::Ge => Some(CompareOp::Lt),
+        CompareOp::Ne => Some(CompareOp::Eq),
+        // `!=` is not a difference constraint
+        CompareOp::Eq => None,
+    }
+}
+
+/// Express `lhs op rhs` as difference bounds
+fn difference_bounds(
+    op: CompareOp,
+    lhs: &Predicate,
+    rhs: &Predicate,
+) -> Option<Vec<DifferenceBound>> {
+    let (x, a) = linear_term(lhs)?;
+    let (y, b) = linear_term(rhs)?;
+    // x + a <= y + b  <=>  x - y <= b - a
+    let le = |x: &Option<String>, a: i64, y: &Option<String>, b: i64, strict: bool| {
+        let k = b.checked_sub(a)?.checked_sub(strict as i64)?;
+        Some(DifferenceBound {
+            x: x.clone(),
+            y: y.clone(),
+            k,
+        })
+    };
+    Some(match op {
+        CompareOp::Le => vec![le(&x, a, &y, b, false)?],
+        CompareOp::Lt => vec![le(&x, a, &y, b, true)?],
+        CompareOp::Ge => vec![le(&y, b, &x, a, false)?],
+        CompareOp::Gt => vec![le(&y, b, &x, a, true)?],
+        CompareOp::Eq => vec![le(&x, a, &y, b, false)?, le(&y, b, &x, a, false)?],
+        CompareOp::Ne => return None,
+    })
+}
+
+/// Split an integer term into a variable (or array length) plus a constant
+fn linear_term(pred: &Predicate) -> Option<(Option<String>, i64)> {
+    match pred {
+        Predicate::Int(n) => Some((None, *n)),
+        Predicate::Var(name) => Some((Some(name.clone()), 0)),
+        Predicate::App(name, args) if name == "len" && args.len() == 1 => match &args[0] {
+            Predicate::Var(arr) => Some((Some(format!("len({})", arr)), 0)),
+            _ => None,
+        },
+        Predicate::Arith(ArithOp::Add, lhs, rhs) => match (linear_term(lhs)?, linear_term(rhs)?) {
+            ((var, a), (None, b)) | ((None, a), (var, b)) => Some((var, a.checked_add(b)?)),
+            _ => None,
+        },
+        Predicate::Arith(ArithOp::Sub, lhs, rhs) => match (linear_term(lhs)?, linear_term(rhs)?) {
+            ((var, a), (None, b)) => Some((var, a.checked_sub(b)?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the bounds have an integer solution. They don't exactly when
+/// the constraint graph has a negative cycle, found with Bellman-Ford.
+fn satisfiable(facts: &[DifferenceBound]) -> bool {
+    let mut bounds = facts.to_vec();
+    // Array lengths are never negative
+    for var in facts.iter().flat_map(|b| [&b.x, &b.y]).flatten() {
+        if var.starts_with("len(") {
+            bounds.push(DifferenceBound {
+                x: None,
+                y: Some(var.clone()),
+                k: 0,
+            });
+        }
+    }
+
+    let mut dist: std::collections::HashMap<Option<String>, i128> = bounds
+        .iter()
+        .flat_map(|b| [b.x.clone(), b.y.clone()])
+        .map(|var| (var, 0))
+        .collect();
+    for _ in 0..dist.len() {
+        let mut changed = false;
+        for bound in &bounds {
+            // Edge y -> x with weight k
+            let through = dist[&bound.y] + bound.k as i128;
+            if through < dist[&bound.x] {
+                dist.insert(bound.x.clone(), through);
+                changed = true;
+            }
+        }
+        if !changed {
+            return true;
+        }
+    }
+    false
+}
+
 /// Result of refinement checking
 #[derive(Debug, Clone)]
 pub enum RefinementResult {
@@ -495,6 +792,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_check_discharges_index_bounds() {
+        let len = |arr: &str| Predicate::App("len".to_string(), vec![Predicate::var(arr)]);
+        let mut checker = RefinementChecker::new();
+        let goal = array::valid_index("arr", "i");
+        assert!(!checker.check(&goal).is_valid());
+
+        checker.assume(Predicate::le(Predicate::Int(0), Predicate::var("i")));
+        let depth = checker.path_depth();
+        checker.assume(Predicate::le(Predicate::var("i"), len("arr")));
+        assert!(!checker.check(&goal).is_valid());
+
+        checker.restore_path(depth);
+        checker.assume(Predicate::lt(Predicate::var("i"), Predicate::var("n")));
+        checker.assume(Predicate::eq(len("arr"), Predicate::var("n")));
+        assert!(checker.check(&goal).is_valid());
+
+        // `i + 1` may reach the length
+        let next = Predicate::Arith(
+            ArithOp::Add,
+            Box::new(Predicate::var("i")),
+            Box::new(Predicate::Int(1)),
+        );
+        let goal = array::valid_index("arr", "j").substitute("j", &next);
+        assert!(!checker.check(&goal).is_valid());
+    }
+
     #[test]
     fn test_medical_refinements() {
         let dose_type = medical::positive_dose();
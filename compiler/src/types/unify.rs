@@ -0,0 +1,235 @@
+//! Standalone type unification
+//!
+//! First-order unification over [`Type`] with an occurs check. Unlike the
+//! checker's compatibility test this is symmetric: `unify(a, b)` succeeds
+//! exactly when `unify(b, a)` does, and the resulting substitution makes
+//! both sides equal.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::core::{Type, TypeVar};
+
+/// Bindings of type variables to types
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Substitution {
+    bindings: HashMap<TypeVar, Type>,
+}
+
+impl Substitution {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The type `var` is bound to, if any
+    pub fn get(&self, var: TypeVar) -> Option<&Type> {
+        self.bindings.get(&var)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&TypeVar, &Type)> {
+        self.bindings.iter()
+    }
+
+    /// Replace every bound variable in `ty`, following chains of bindings
+    pub fn apply(&self, ty: &Type) -> Type {
+        let mut current = ty.clone();
+        // Each round resolves one link of a chain; bindings are acyclic
+        // thanks to the occurs check, so this terminates
+        for _ in 0..=self.bindings.len() {
+            let next = current.substitute(&self.bindings);
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+        current
+    }
+
+    fn bind(&mut self, var: TypeVar, ty: Type) {
+        self.bindings.insert(var, ty);
+    }
+}
+
+/// Why two types could not be unified. The types are boxed to keep the
+/// error small on the `Ok` path.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum UnifyError {
+    /// The types have different shapes
+    #[error("cannot unify `{left}` with `{right}`")]
+    Mismatch { left: Box<Type>, right: Box<Type> },
+    /// Binding the variable would create an infinite type
+    #[error("cannot construct infinite type: `?T{}` = `{ty}`", .var.0)]
+    OccursCheck { var: TypeVar, ty: Box<Type> },
+}
+
+/// Unify two types, returning the most general substitution that makes
+/// them equal
+pub fn unify(left: &Type, right: &Type) -> Result<Substitution, UnifyError> {
+    let mut subst = Substitution::new();
+    unify_with(left, right, &mut subst)?;
+    Ok(subst)
+}
+
+/// Unify two types under an existing substitution, extending it in place
+pub fn unify_with(left: &Type, right: &Type, subst: &mut Substitution) -> Result<(), UnifyError> {
+    let left = subst.apply(left);
+    let right = subst.apply(right);
+    let mismatch = || UnifyError::Mismatch {
+        left: Box::new(left.clone()),
+        right: Box::new(right.clone()),
+    };
+
+    match (&left, &right) {
+        (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+        (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+            if ty.free_vars().contains(var) {
+                return Err(UnifyError::OccursCheck {
+                    var: *var,
+                    ty: Box::new(ty.clone()),
+                });
+            }
+            subst.bind(*var, ty.clone());
+            Ok(())
+        }
+        // Recovery types unify with anything without binding
+        (Type::Unknown | Type::Error, _) | (_, Type::Unknown | Type::Error) => Ok(()),
+        (
+            Type::Ref {
+                mutable: m1,
+                inner: i1,
+                ..
+            },
+            Type::Ref {
+                mutable: m2,
+                inner: i2,
+                ..
+            },
+        ) if m1 == m2 => unify_with(i1, i2, subst),
+        (
+            Type::Array {
+                element: e1,
+                size: s1,
+            },
+            Type::Array {
+                element: e2,
+                size: s2,
+            },
+        ) if s1 == s2 => unify_with(e1, e2, subst),
+        (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => unify_all(a, b, subst),
+        (
+            Type::Function {
+                params: p1,
+                return_type: r1,
+                ..
+            },
+            Type::Function {
+                params: p2,
+                return_type: r2,
+                ..
+            },
+        ) if p1.len() == p2.len() => {
+            unify_all(p1, p2, subst)?;
+            unify_with(r1, r2, subst)
+        }
+        (Type::Named { name: n1, args: a1 }, Type::Named { name: n2, args: a2 })
+            if n1 == n2 && a1.len() == a2.len() =>
+        {
+            unify_all(a1, a2, subst)
+        }
+        (
+            Type::Quantity {
+                numeric: n1,
+                unit: u1,
+            },
+            Type::Quantity {
+                numeric: n2,
+                unit: u2,
+            },
+        ) if u1 == u2 => unify_with(n1, n2, subst),
+        _ if left == right => Ok(()),
+        _ => Err(mismatch()),
+    }
+}
+
+fn unify_all(left: &[Type], right: &[Type], subst: &mut Substitution) -> Result<(), UnifyError> {
+    left.iter()
+        .zip(right)
+        .try_for_each(|(l, r)| unify_with(l, r, subst))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec_of(ty: Type) -> Type {
+        Type::Named {
+            name: "Vec".to_string(),
+            args: vec![ty],
+        }
+    }
+
+    #[test]
+    fn test_unify_var_binds() {
+        let subst = unify(&Type::Var(TypeVar(0)), &Type::I64).unwrap();
+        assert_eq!(subst.get(TypeVar(0)), Some(&Type::I64));
+        assert_eq!(subst.apply(&Type::Var(TypeVar(0))), Type::I64);
+    }
+
+    #[test]
+    fn test_unify_nested_var_binds() {
+        let subst = unify(&vec_of(Type::Var(TypeVar(0))), &vec_of(Type::Bool)).unwrap();
+        assert_eq!(subst.get(TypeVar(0)), Some(&Type::Bool));
+    }
+
+    #[test]
+    fn test_unify_mismatch() {
+        let err = unify(&Type::I64, &Type::Bool).unwrap_err();
+        assert_eq!(
+            err,
+            UnifyError::Mismatch {
+                left: Box::new(Type::I64),
+                right: Box::new(Type::Bool)
+            }
+        );
+        assert_eq!(err.to_string(), "cannot unify `i64` with `bool`");
+    }
+
+    #[test]
+    fn test_unify_occurs_check() {
+        let var = Type::Var(TypeVar(0));
+        let err = unify(&var, &vec_of(var.clone())).unwrap_err();
+        assert!(matches!(
+            err,
+            UnifyError::OccursCheck {
+                var: TypeVar(0),
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!("cannot construct infinite type: `?T0` = `{}`", vec_of(var))
+        );
+    }
+
+    #[test]
+    fn test_unify_follows_bindings() {
+        // (?0, ?1, ?0) ~ (?1, bool, ?0) binds both variables to bool
+        let (a, b) = (Type::Var(TypeVar(0)), Type::Var(TypeVar(1)));
+        let subst = unify(
+            &Type::Tuple(vec![a.clone(), b.clone(), a.clone()]),
+            &Type::Tuple(vec![b.clone(), Type::Bool, a.clone()]),
+        )
+        .unwrap();
+        assert_eq!(subst.apply(&a), Type::Bool);
+        assert_eq!(subst.apply(&b), Type::Bool);
+    }
+}
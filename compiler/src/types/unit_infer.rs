@@ -4,6 +4,7 @@
 
 use super::units::{Unit, UnitChecker, UnitOp};
 use crate::common::Span;
+use crate::diagnostics::{CompileError, SourceFile};
 use std::collections::{HashMap, VecDeque};
 
 /// Unit variable for inference
@@ -158,6 +159,26 @@ impl UnitInferenceError {
         }
     }
 
+    /// Attach source code to produce a reportable diagnostic
+    pub fn to_diagnostic(&self, source: &SourceFile) -> CompileError {
+        match (&self.expected, &self.found) {
+            (Some(expected), Some(found)) => CompileError::UnitMismatch {
+                expected: expected.clone(),
+                found: found.clone(),
+                span: self.span.into(),
+                src: source.to_named_source(),
+                help: None,
+            },
+            _ => CompileError::General {
+                message: self.message.clone(),
+                span: self.span.into(),
+                label: self.message.clone(),
+                src: source.to_named_source(),
+                help: None,
+            },
+        }
+    }
+
     pub fn cannot_add(u1: &Unit, u2: &Unit, span: Span) -> Self {
         Self {
             message: format!(
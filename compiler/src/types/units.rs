@@ -413,15 +413,32 @@ impl UnitChecker {
         self.aliases.get(name)
     }
 
+    /// Name of `unit`: the shortest registered alias with the same
+    /// dimensions and scale, or its dimensional form if none matches
+    pub fn name_of(&self, unit: &Unit) -> String {
+        let same_scale = |a: f64, b: f64| (a - b).abs() <= 1e-9 * a.abs().max(b.abs());
+        self.aliases
+            .iter()
+            .filter(|(_, alias)| alias.is_compatible(unit) && same_scale(alias.scale, unit.scale))
+            .map(|(name, _)| name)
+            .min_by_key(|name| (name.len(), name.as_str()))
+            .cloned()
+            .unwrap_or_else(|| unit.format())
+    }
+
     /// Parse a unit expression (simple parsing)
     pub fn parse(&self, expr: &str) -> Option<Unit> {
         // Try direct lookup first
         if let Some(unit) = self.lookup(expr) {
             return Some(unit.clone());
         }
+        if expr == "1" {
+            return Some(Unit::dimensionless());
+        }
 
-        // Try parsing compound units like "mg/mL"
-        if let Some(pos) = expr.find('/') {
+        // Try parsing compound units like "mg/mL"; division associates to
+        // the left, so "m/s/s" is "m/s^2"
+        if let Some(pos) = expr.rfind('/') {
             let num = &expr[..pos];
             let den = &expr[pos + 1..];
             let num_unit = self.parse(num)?;
@@ -449,6 +466,16 @@ impl UnitChecker {
         None
     }
 
+    /// First name in a unit expression that is not a known unit
+    pub fn unknown_name<'a>(&self, expr: &'a str) -> Option<&'a str> {
+        if self.lookup(expr).is_some() {
+            return None;
+        }
+        expr.split(['/', '*'])
+            .map(|factor| factor.split('^').next().unwrap_or(factor))
+            .find(|name| *name != "1" && self.lookup(name).is_none())
+    }
+
     /// Check if two units are compatible for an operation
     pub fn check_compatible(&self, u1: &Unit, u2: &Unit) -> bool {
         u1.is_compatible(u2)
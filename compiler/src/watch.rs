@@ -0,0 +1,121 @@
+//! File watching for `dc check --watch`
+//!
+//! Editors often write a file in several steps, so change events are
+//! debounced: a burst of events triggers a single re-check once the file has
+//! been quiet for the debounce delay.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use miette::{Result, miette};
+use notify::{RecursiveMode, Watcher};
+
+/// Quiet period after the last change before re-checking
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Collapses bursts of change events into a single notification
+#[derive(Debug)]
+pub struct Debouncer {
+    delay: Duration,
+    /// Time of the latest change that has not been reported yet
+    pending: Option<Instant>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: None,
+        }
+    }
+
+    /// Record a change seen at `now`
+    pub fn record(&mut self, now: Instant) {
+        self.pending = Some(now);
+    }
+
+    /// Whether a change is waiting to be reported
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Time until the pending change is due, if there is one
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        self.pending
+            .map(|last| (last + self.delay).saturating_duration_since(now))
+    }
+
+    /// Report the pending change once no other change has arrived within
+    /// the delay. Each burst is reported exactly once.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        match self.pending {
+            Some(last) if now.saturating_duration_since(last) >= self.delay => {
+                self.pending = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Call `on_change` once per debounced burst of messages on `events`.
+///
+/// Returns when every sender has been dropped, flushing a burst that is
+/// still pending at that point.
+pub fn debounce_events<T>(events: &Receiver<T>, delay: Duration, mut on_change: impl FnMut()) {
+    let mut debouncer = Debouncer::new(delay);
+    loop {
+        let received = match debouncer.time_left(Instant::now()) {
+            Some(wait) => events.recv_timeout(wait),
+            None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(_) => debouncer.record(Instant::now()),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                if debouncer.is_pending() {
+                    on_change();
+                }
+                return;
+            }
+        }
+        if debouncer.poll(Instant::now()) {
+            on_change();
+        }
+    }
+}
+
+/// Watch `files` and call `on_change` after each debounced burst of changes.
+///
+/// The parent directories are watched rather than the files themselves, so
+/// editors that save by writing a temporary file and renaming it over the
+/// original are still noticed. Blocks for as long as the watcher runs.
+pub fn watch_files(files: &[PathBuf], delay: Duration, on_change: impl FnMut()) -> Result<()> {
+    let targets: Vec<PathBuf> = files
+        .iter()
+        .map(|f| f.canonicalize().unwrap_or_else(|_| f.clone()))
+        .collect();
+
+    let (tx, rx) = mpsc::channel();
+    let watched = targets.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event
+            && !event.kind.is_access()
+            && event.paths.iter().any(|p| watched.contains(p))
+        {
+            let _ = tx.send(());
+        }
+    })
+    .map_err(|e| miette!("Failed to start file watcher: {}", e))?;
+
+    for target in &targets {
+        let dir = target.parent().unwrap_or(Path::new("."));
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| miette!("Failed to watch {}: {}", dir.display(), e))?;
+    }
+
+    debounce_events(&rx, delay, on_change);
+    Ok(())
+}
@@ -0,0 +1,1939 @@
+//! Type checker tests
+
+use demetrios::hir::{Hir, HirExpr, HirExprKind, HirItem, HirLiteral, HirStmt, HirType};
+use demetrios::types::ArrayLen;
+
+fn check(source: &str) -> Result<Hir, String> {
+    demetrios::typecheck(source).map_err(|e| format!("{}", e))
+}
+
+/// The trailing expression of the named function's body
+fn tail_expr<'a>(hir: &'a Hir, name: &str) -> &'a HirExpr {
+    let func = hir
+        .items
+        .iter()
+        .find_map(|item| match item {
+            HirItem::Function(f) if f.name == name => Some(f),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("no function `{}`", name));
+    match func.body.stmts.last() {
+        Some(HirStmt::Expr(expr)) => expr,
+        _ => panic!("`{}` has no tail expression", name),
+    }
+}
+
+fn named(name: &str, args: Vec<HirType>) -> HirType {
+    HirType::Named {
+        name: name.to_string(),
+        args,
+    }
+}
+
+// ==================== Enum Variant Tests ====================
+
+const ENUMS: &str = r#"
+enum Color {
+    Red,
+    Green,
+}
+
+enum Option<T> {
+    Some(T),
+    None,
+}
+"#;
+
+#[test]
+fn test_check_unit_variant() {
+    let source = format!("{}\nfn main() -> Color {{ Color::Red }}", ENUMS);
+    let hir = check(&source).unwrap();
+    let expr = tail_expr(&hir, "main");
+
+    match &expr.kind {
+        HirExprKind::Variant {
+            enum_name,
+            variant,
+            fields,
+        } => {
+            assert_eq!(enum_name, "Color");
+            assert_eq!(variant, "Red");
+            assert!(fields.is_empty());
+        }
+        other => panic!("expected variant, got {:?}", other),
+    }
+    assert_eq!(expr.ty, named("Color", vec![]));
+}
+
+#[test]
+fn test_check_tuple_variant_call_infers_generics() {
+    let source = format!("{}\nfn main() -> Option<i64> {{ Option::Some(5) }}", ENUMS);
+    let hir = check(&source).unwrap();
+    let expr = tail_expr(&hir, "main");
+
+    assert!(matches!(
+        &expr.kind,
+        HirExprKind::Variant { variant, fields, .. } if variant == "Some" && fields.len() == 1
+    ));
+    assert_eq!(expr.ty, named("Option", vec![HirType::I64]));
+}
+
+#[test]
+fn test_check_tuple_variant_as_constructor() {
+    let source = format!("{}\nfn main() {{ Option::Some; }}", ENUMS);
+    let hir = check(&source).unwrap();
+    let expr = tail_expr(&hir, "main");
+
+    match &expr.ty {
+        HirType::Fn {
+            params,
+            return_type,
+        } => {
+            assert!(matches!(params.as_slice(), [HirType::Var(_)]));
+            assert!(matches!(&**return_type, HirType::Named { name, .. } if name == "Option"));
+        }
+        other => panic!("expected constructor function, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_check_unknown_variant() {
+    let source = format!("{}\nfn main() -> Color {{ Color::Purple }}", ENUMS);
+    let err = check(&source).unwrap_err();
+    assert!(err.contains("Color::Purple"), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_check_unknown_enum() {
+    let err = check("fn main() { Shade::Dark }").unwrap_err();
+    assert!(err.contains("Shade::Dark"), "unexpected error: {}", err);
+}
+
+/// Discriminant of each variant of the named enum
+fn discriminants(hir: &Hir, name: &str) -> Vec<i64> {
+    hir.items
+        .iter()
+        .find_map(|item| match item {
+            HirItem::Enum(e) if e.name == name => {
+                Some(e.variants.iter().map(|v| v.discriminant).collect())
+            }
+            _ => None,
+        })
+        .expect("enum not found")
+}
+
+#[test]
+fn test_check_explicit_discriminants() {
+    let hir = check("enum E { A = 1, B = 4, C = -2 }").unwrap();
+    assert_eq!(discriminants(&hir, "E"), vec![1, 4, -2]);
+}
+
+#[test]
+fn test_check_default_discriminants() {
+    let hir = check("enum E { A, B, C = 10, D }").unwrap();
+    assert_eq!(discriminants(&hir, "E"), vec![0, 1, 10, 11]);
+}
+
+#[test]
+fn test_check_duplicate_discriminant() {
+    let err = check("enum E { A = 1, B = 0, C }").unwrap_err();
+    assert!(
+        err.contains("discriminant value `1` assigned to both `E::A` and `E::C`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Diagnostics Tests ====================
+
+#[test]
+fn test_check_mismatch_uses_surface_syntax() {
+    let err = check("fn main() -> i64 { true }").unwrap_err();
+    assert!(
+        err.contains("expected i64, found bool"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_match_arms_must_agree() {
+    use miette::Diagnostic;
+
+    let source = "fn f(n: i64) -> i64 { match n { 0 => 1, _ => true } }";
+    let tokens = demetrios::lexer::lex(source).expect("lex");
+    let ast = demetrios::parser::parse(&tokens, source).expect("parse");
+    let mut checker = demetrios::check::TypeChecker::new();
+    let err = checker.check_program(&ast).unwrap_err().to_string();
+    assert!(
+        err.contains("expected i64, found bool"),
+        "unexpected error: {}",
+        err
+    );
+    let diagnostics = checker.error_diagnostics(&demetrios::SourceFile::new("test.d", source));
+    assert_eq!(diagnostics.len(), 1);
+    let label = diagnostics[0].labels().unwrap().next().unwrap();
+    assert_eq!(label.offset(), source.find("true").unwrap());
+}
+
+// ==================== Numeric Operand Tests ====================
+
+#[test]
+fn test_check_mixed_int_widths_rejected() {
+    let err = check("fn f(a: i32, b: i64) -> i64 { a + b }").unwrap_err();
+    assert!(
+        err.contains("cannot apply `+` to `i32` and `i64`"),
+        "unexpected error: {}",
+        err
+    );
+    assert!(
+        err.contains("`as i32` or `as i64`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_literal_adopts_operand_type() {
+    let hir = check(
+        r#"
+        fn right(a: i32) -> i32 { a + 1 }
+        fn left(a: i32) -> i32 { 1 - a }
+        fn byte(a: u8) -> u8 { a * 2 }
+        fn float(x: f32) -> f32 { -0.5 * x }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(tail_expr(&hir, "right").ty, HirType::I32);
+    assert_eq!(tail_expr(&hir, "left").ty, HirType::I32);
+    assert_eq!(tail_expr(&hir, "byte").ty, HirType::U8);
+    assert_eq!(tail_expr(&hir, "float").ty, HirType::F32);
+}
+
+#[test]
+fn test_check_mixed_float_widths_rejected() {
+    let err = check("fn f(x: f32, y: f64) -> f64 { x * y }").unwrap_err();
+    assert!(
+        err.contains("cannot apply `*` to `f32` and `f64`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Intrinsic Tests ====================
+
+#[test]
+fn test_check_parse_intrinsic_signatures() {
+    let hir = check(
+        r#"
+        fn int() -> Option<i64> { parse_int("42") }
+        fn float() -> Option<f64> { parse_float("4.5") }
+        fn text() -> String { to_string(true) }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        tail_expr(&hir, "int").ty,
+        named("Option", vec![HirType::I64])
+    );
+    assert_eq!(
+        tail_expr(&hir, "float").ty,
+        named("Option", vec![HirType::F64])
+    );
+    assert_eq!(tail_expr(&hir, "text").ty, HirType::String);
+
+    let err = check("fn main() { parse_int(5); }").unwrap_err();
+    assert!(
+        err.contains("expected String, found i64"),
+        "unexpected error: {}",
+        err
+    );
+
+    let err = check("fn main() { to_string(1, 2); }").unwrap_err();
+    assert!(
+        err.contains("`to_string` takes 1"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Hash Tests ====================
+
+#[test]
+fn test_check_map_key_requires_hash() {
+    let hashable = r#"
+        #[derive(Hash, Eq)]
+        struct Node { id: i64 }
+        fn main() {
+            let m = map_new();
+            map_insert(m, Node { id: 1 }, (Node { id: 2 }, 1));
+        }
+    "#;
+    assert!(check(hashable).is_ok());
+
+    let err = check(
+        r#"
+        struct Node { id: i64 }
+        fn main() {
+            let s = set_new();
+            set_insert(s, (1, Node { id: 1 }));
+        }
+    "#,
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("`set_insert` requires a hashable key"),
+        "unexpected error: {}",
+        err
+    );
+
+    let err = check("fn main() { hash(1.5); }").unwrap_err();
+    assert!(
+        err.contains("f64 does not implement Hash"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Vec Tests ====================
+
+#[test]
+fn test_check_vec_element_type() {
+    let hir = check("fn main() -> i64 { let v = vec![1, 2, 3]; v[0] }").unwrap();
+    assert_eq!(tail_expr(&hir, "main").ty, HirType::I64);
+
+    let err = check("fn main() { let v = vec![1, true]; }").unwrap_err();
+    assert!(err.contains("Type mismatch"), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_check_vec_push_argument() {
+    let err = check(r#"fn main() { let v = vec![1]; v.push("two"); }"#).unwrap_err();
+    assert!(err.contains("Type mismatch"), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_check_reports_infinite_type() {
+    let src = r#"
+enum List<T> { Nil, Cons(T, i64) }
+
+fn wrap<T>(x: T) -> T {
+    let list: List<T> = List::Nil;
+    list
+}
+"#;
+    let err = check(src).unwrap_err();
+    assert!(
+        err.contains("cannot construct infinite type: T = List<T>"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Try Operator Tests ====================
+
+const HALF: &str = r#"
+fn half(n: i64) -> Result<i64, String> {
+    if n % 2 == 0 { Ok(n / 2) } else { Err("odd") }
+}
+"#;
+
+#[test]
+fn test_check_try_unwraps_ok_type() {
+    let source = format!(
+        "{}\nfn quarter(n: i64) -> Result<i64, String> {{ let h = half(n)?; Ok(h / 2) }}",
+        HALF
+    );
+    assert!(check(&source).is_ok());
+
+    let err = check("fn f(x: Option<bool>) -> Option<bool> { x? }").unwrap_err();
+    assert!(
+        err.contains("expected Option<bool>, found bool"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_try_in_incompatible_function() {
+    let source = format!("{}\nfn quarter(n: i64) -> i64 {{ half(n)? / 2 }}", HALF);
+    let err = check(&source).unwrap_err();
+    assert!(
+        err.contains("can only be used in a function that returns Result"),
+        "unexpected error: {}",
+        err
+    );
+
+    let source = format!(
+        "{}\nfn quarter(n: i64) -> Result<i64, bool> {{ Ok(half(n)? / 2) }}",
+        HALF
+    );
+    let err = check(&source).unwrap_err();
+    assert!(
+        err.contains("expected bool, found String"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_try_on_non_enum() {
+    let err = check("fn f(n: i64) -> Option<i64> { Some(n?) }").unwrap_err();
+    assert!(
+        err.contains("`?` expects an Option or Result, found i64"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Attribute Tests ====================
+
+#[test]
+fn test_check_unknown_attributes_warn() {
+    let source = "#[frobnicate] fn main() {} #[pure] #[gpu] fn k() {}";
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let mut checker = demetrios::check::TypeChecker::new();
+    checker
+        .check_program(&ast)
+        .expect("unknown attributes are not errors");
+
+    let unknown: Vec<_> = checker
+        .unknown_attributes()
+        .iter()
+        .map(|a| (a.name.as_str(), &source[a.span.start..a.span.end]))
+        .collect();
+    assert_eq!(unknown, vec![("frobnicate", "#[frobnicate]")]);
+}
+
+#[test]
+fn test_check_gpu_attribute_marks_kernel() {
+    let hir = check("#[gpu] fn k() {} fn host() {}").unwrap();
+    let kernels: Vec<_> = hir
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            HirItem::Function(f) => Some((f.name.as_str(), f.is_kernel)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(kernels, vec![("k", true), ("host", false)]);
+}
+
+#[test]
+fn test_check_pure_function_with_effects() {
+    let err = check("#[pure] fn log(x: i64) -> i64 with IO { x }").unwrap_err();
+    assert!(err.contains("#[pure]"), "unexpected error: {}", err);
+}
+
+// ==================== Generic Call Tests ====================
+
+const GENERIC_FNS: &str = r#"
+fn id<T>(x: T) -> T { x }
+fn first<A, B>(a: A, b: B) -> A { a }
+"#;
+
+#[test]
+fn test_check_turbofish_instantiates_generics() {
+    let hir = check(&format!(
+        "{}
+        fn explicit() -> i64 {{ id::<i64>(1) }}
+        fn partial() -> bool {{ first::<bool>(true, 2) }}
+        fn inferred() -> bool {{ id(false) }}",
+        GENERIC_FNS
+    ))
+    .unwrap();
+    assert_eq!(tail_expr(&hir, "explicit").ty, HirType::I64);
+    assert_eq!(tail_expr(&hir, "partial").ty, HirType::Bool);
+    assert_eq!(tail_expr(&hir, "inferred").ty, HirType::Bool);
+
+    let HirExprKind::Call { func, .. } = &tail_expr(&hir, "explicit").kind else {
+        panic!("Expected call");
+    };
+    assert_eq!(
+        func.ty,
+        HirType::Fn {
+            params: vec![HirType::I64],
+            return_type: Box::new(HirType::I64),
+        }
+    );
+}
+
+#[test]
+fn test_check_turbofish_on_vec_new() {
+    let hir = check("fn empty() -> Vec<i64> { Vec::<i64>::new() }").unwrap();
+    assert_eq!(
+        tail_expr(&hir, "empty").ty,
+        HirType::Named {
+            name: "Vec".to_string(),
+            args: vec![HirType::I64],
+        }
+    );
+
+    let err = check("fn empty() -> Vec<bool> { Vec::<i64>::new() }").unwrap_err();
+    assert!(err.contains("expected"), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_check_turbofish_argument_mismatch() {
+    let err = check(&format!("{} fn main() {{ id::<i64>(true); }}", GENERIC_FNS)).unwrap_err();
+    assert!(
+        err.contains("expected i64, found bool"),
+        "unexpected error: {}",
+        err
+    );
+
+    let err = check(&format!(
+        "{} fn main() {{ id::<i64, bool>(1); }}",
+        GENERIC_FNS
+    ))
+    .unwrap_err();
+    assert!(
+        err.contains("`id` takes 1 generic argument(s) but 2 were supplied"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+const CONST_GENERIC_FNS: &str = r#"
+fn same<const N: usize>(xs: [f64; N]) -> [f64; N] { xs }
+fn zeros<const N: usize>() -> [f64; N] { [0.0; N] }
+"#;
+
+fn f64_array(n: usize) -> HirType {
+    HirType::Array {
+        element: Box::new(HirType::F64),
+        size: Some(ArrayLen::Fixed(n)),
+    }
+}
+
+#[test]
+fn test_check_const_generic_instantiated_at_three() {
+    let hir = check(&format!(
+        "{}
+        fn inferred() -> [f64; 3] {{ same([1.0, 2.0, 3.0]) }}
+        fn explicit() -> [f64; 3] {{ zeros::<3>() }}
+        fn braced() -> [f64; 3] {{ zeros::<{{ 1 + 2 }}>() }}",
+        CONST_GENERIC_FNS
+    ))
+    .unwrap();
+    assert_eq!(tail_expr(&hir, "inferred").ty, f64_array(3));
+    assert_eq!(tail_expr(&hir, "explicit").ty, f64_array(3));
+    assert_eq!(tail_expr(&hir, "braced").ty, f64_array(3));
+    // Inside its body the length is the parameter itself
+    let generic = HirType::Array {
+        element: Box::new(HirType::F64),
+        size: Some(ArrayLen::Param("N".to_string())),
+    };
+    assert_eq!(tail_expr(&hir, "same").ty, generic);
+    assert_eq!(tail_expr(&hir, "zeros").ty, generic);
+}
+
+#[test]
+fn test_check_const_generic_length_mismatch() {
+    let err = check(&format!(
+        "{} fn main() {{ let xs: [f64; 4] = same([1.0, 2.0, 3.0]); }}",
+        CONST_GENERIC_FNS
+    ))
+    .unwrap_err();
+    assert!(
+        err.contains("expected [f64; 4], found [f64; 3]"),
+        "unexpected error: {}",
+        err
+    );
+
+    let err = check(&format!(
+        "{} fn main() {{ let xs = zeros(); }}",
+        CONST_GENERIC_FNS
+    ))
+    .unwrap_err();
+    assert!(
+        err.contains("Cannot infer the value of const parameter `N` in the call to `zeros`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_polymorphic_identity_at_two_types() {
+    let hir = check(&format!(
+        "{} fn main() -> String {{ let n = id(1); let s = id(\"x\"); s }}",
+        GENERIC_FNS
+    ))
+    .unwrap();
+    assert_eq!(tail_expr(&hir, "main").ty, HirType::String);
+    // Inside its body `T` is a type variable, not a type named `T`
+    assert!(matches!(tail_expr(&hir, "id").ty, HirType::Var(_)));
+}
+
+#[test]
+fn test_check_generic_swap() {
+    let hir = check(
+        "fn swap<A, B>(p: (A, B)) -> (B, A) { (p.1, p.0) } \
+         fn main() -> (String, i64) { swap((1, \"a\")) }",
+    )
+    .unwrap();
+    assert_eq!(
+        tail_expr(&hir, "main").ty,
+        HirType::Tuple(vec![HirType::String, HirType::I64])
+    );
+}
+
+#[test]
+fn test_check_generic_param_is_rigid_in_body() {
+    let err = check(
+        "fn bad<T>(x: T) -> T { 1 } \
+         fn wrong<A, B>(p: (A, B)) -> (B, A) { p }",
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("expected T, found i64"),
+        "unexpected error: {}",
+        err
+    );
+    assert!(
+        err.contains("expected (B, A), found (A, B)"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Call Signature Tests ====================
+
+const ADD_FN: &str = "fn add(a: i64, b: i64) -> i64 { a + b }";
+
+#[test]
+fn test_check_call_matches_signature() {
+    let hir = check(&format!("{} fn main() -> i64 {{ add(1, 2) }}", ADD_FN)).unwrap();
+    assert_eq!(tail_expr(&hir, "main").ty, HirType::I64);
+}
+
+#[test]
+fn test_check_call_too_few_args() {
+    let err = check(&format!("{} fn main() -> i64 {{ add(1) }}", ADD_FN)).unwrap_err();
+    assert!(
+        err.contains("`add` takes 2 argument(s) but 1 were supplied"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_call_wrong_arg_type() {
+    let err = check(&format!("{} fn main() -> i64 {{ add(1, true) }}", ADD_FN)).unwrap_err();
+    assert!(
+        err.contains("expected i64, found bool"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_variadic_extern_call() {
+    let source = r#"
+        extern "C" { fn printf(fmt: string, ...) -> i32; }
+        fn main() -> i32 { printf("%d %d", 1, 2) }
+    "#;
+    assert!(check(source).is_ok());
+}
+
+// ==================== Impl Method Tests ====================
+
+const POINT: &str = r#"
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+impl Point {
+    type Scalar = f64;
+
+    fn norm(self) -> Self::Scalar {
+        self.x * self.x + self.y * self.y
+    }
+
+    fn scaled(self, k: f64) -> Self {
+        Point { x: self.x * k, y: self.y * k }
+    }
+}
+"#;
+
+#[test]
+fn test_check_inherent_method_call() {
+    let hir = check(&format!(
+        "{} fn main() -> f64 {{ let p = Point {{ x: 3.0, y: 4.0 }}; p.norm() }}",
+        POINT
+    ))
+    .unwrap();
+    let expr = tail_expr(&hir, "main");
+    assert!(matches!(&expr.kind, HirExprKind::MethodCall { method, .. } if method == "norm"));
+    assert_eq!(expr.ty, HirType::F64);
+}
+
+#[test]
+fn test_check_impl_methods_typed_with_self() {
+    let hir = check(POINT).unwrap();
+    let HirItem::Impl(imp) = hir.items.last().unwrap() else {
+        panic!("Expected impl item");
+    };
+    assert_eq!(imp.self_ty, named("Point", vec![]));
+    let scaled = imp.methods.iter().find(|m| m.name == "scaled").unwrap();
+    assert_eq!(scaled.ty.params[0].ty, named("Point", vec![]));
+    assert_eq!(*scaled.ty.return_type, named("Point", vec![]));
+}
+
+#[test]
+fn test_check_self_field_mismatch() {
+    let err = check(
+        r#"
+        struct Point { x: f64, y: f64 }
+        impl Point {
+            fn flag(self) -> bool { self.x }
+        }
+    "#,
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("expected bool, found f64"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_method_call_arity() {
+    let err = check(&format!(
+        "{} fn main() -> f64 {{ let p = Point {{ x: 1.0, y: 1.0 }}; p.scaled(1.0, 2.0).norm() }}",
+        POINT
+    ))
+    .unwrap_err();
+    assert!(
+        err.contains("`Point::scaled` takes 1 argument(s) but 2 were supplied"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_unknown_method() {
+    let err = check(&format!(
+        "{} fn main() {{ let p = Point {{ x: 1.0, y: 1.0 }}; p.length(); }}",
+        POINT
+    ))
+    .unwrap_err();
+    assert!(
+        err.contains("No method `length` on type `Point`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_constructor_returning_self() {
+    let hir = check(
+        r#"
+        struct Point { x: i64, y: i64 }
+        impl Point {
+            fn new(x: i64, y: i64) -> Self { Self { x: x, y: y } }
+            fn origin() -> Self { Self::new(0, 0) }
+        }
+        fn main() -> Point { Point::new(1, 2) }
+    "#,
+    )
+    .unwrap();
+    let expr = tail_expr(&hir, "main");
+    let HirExprKind::Call { func, .. } = &expr.kind else {
+        panic!("Expected call, got {:?}", expr.kind);
+    };
+    assert!(matches!(&func.kind, HirExprKind::Local(name) if name == "Point::new"));
+    assert_eq!(expr.ty, named("Point", vec![]));
+}
+
+#[test]
+fn test_check_method_taking_ref_self() {
+    let hir = check(
+        r#"
+        struct Point { x: i64, y: i64 }
+        impl Point {
+            fn new(x: i64, y: i64) -> Self { Point { x: x, y: y } }
+            fn add(&self, other: &Self) -> Self { Point::new(self.x + other.x, self.y + other.y) }
+        }
+        fn main() -> Point { let p = Point::new(1, 2); p.add(&p) }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(tail_expr(&hir, "main").ty, named("Point", vec![]));
+
+    let err = check(
+        r#"
+        struct Point { x: i64, y: i64 }
+        struct Size { w: i64 }
+        impl Point {
+            fn add(&self, other: &Self) -> Self { Point { x: self.x, y: other.y } }
+        }
+        fn main() -> Point { let p = Point { x: 1, y: 2 }; p.add(&Size { w: 1 }) }
+    "#,
+    )
+    .unwrap_err();
+    assert!(err.contains("expected &Point, found &Size"), "{}", err);
+}
+
+// ==================== Trait Impl Tests ====================
+
+const DISPLAY: &str = r#"
+trait Display {
+    fn fmt(&self) -> String;
+    fn show(&self) -> String { "<" + self.fmt() + ">" }
+}
+
+struct Celsius { degrees: f64 }
+
+impl Display for Celsius {
+    fn fmt(&self) -> String { "celsius" }
+}
+"#;
+
+#[test]
+fn test_check_trait_method_call() {
+    let hir = check(&format!(
+        "{} fn main() -> String {{ let c = Celsius {{ degrees: 21.5 }}; c.fmt() }}",
+        DISPLAY
+    ))
+    .unwrap();
+    let expr = tail_expr(&hir, "main");
+    assert!(matches!(&expr.kind, HirExprKind::MethodCall { method, .. } if method == "fmt"));
+    assert_eq!(expr.ty, HirType::String);
+}
+
+#[test]
+fn test_check_trait_default_method_added_to_impl() {
+    let hir = check(&format!(
+        "{} fn main() -> String {{ Celsius {{ degrees: 0.0 }}.show() }}",
+        DISPLAY
+    ))
+    .unwrap();
+    assert_eq!(tail_expr(&hir, "main").ty, HirType::String);
+    let imp = hir
+        .items
+        .iter()
+        .find_map(|item| match item {
+            HirItem::Impl(imp) => Some(imp),
+            _ => None,
+        })
+        .unwrap();
+    let names: Vec<_> = imp.methods.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, vec!["fmt", "show"]);
+}
+
+#[test]
+fn test_check_trait_impl_missing_method() {
+    let err = check(
+        r#"
+        trait Shape {
+            fn area(&self) -> f64;
+            fn sides(&self) -> i64;
+        }
+        struct Square { side: f64 }
+        impl Shape for Square {
+            fn area(&self) -> f64 { self.side * self.side }
+        }
+    "#,
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("Missing method `sides` in impl of `Shape` for `Square`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_trait_impl_signature_mismatch() {
+    let err = check(&DISPLAY.replace(
+        "fn fmt(&self) -> String { \"celsius\" }",
+        "fn fmt(&self, precision: i64) -> String { \"celsius\" }",
+    ))
+    .unwrap_err();
+    assert!(
+        err.contains(
+            "Method `fmt` in impl of `Display` for `Celsius` has signature \
+             `fn(self, i64) -> String` but the trait declares `fn(self) -> String`"
+        ),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_generic_bound_satisfied() {
+    let hir = check(&format!(
+        "{} fn label<T: Display>(x: T) -> String {{ x.show() }} \
+         fn render<T>(x: T) -> String where T: Display {{ label(x) }} \
+         fn main() -> String {{ render(Celsius {{ degrees: 4.0 }}) }}",
+        DISPLAY
+    ))
+    .unwrap();
+    assert_eq!(tail_expr(&hir, "label").ty, HirType::String);
+    assert_eq!(tail_expr(&hir, "main").ty, HirType::String);
+}
+
+#[test]
+fn test_check_generic_bound_not_satisfied() {
+    let err = check(&format!(
+        "{} struct Kelvin {{ degrees: f64 }} \
+         fn label<T: Display>(x: T) -> String {{ x.fmt() }} \
+         fn relay<U>(u: U) -> String {{ label(u) }} \
+         fn main() -> String {{ label(Kelvin {{ degrees: 4.0 }}) }}",
+        DISPLAY
+    ))
+    .unwrap_err();
+    assert!(
+        err.contains("The trait bound `Kelvin: Display` is not satisfied in the call to `label`"),
+        "unexpected error: {}",
+        err
+    );
+    assert!(
+        err.contains("The trait bound `U: Display` is not satisfied in the call to `label`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Transmute Tests ====================
+
+#[test]
+fn test_check_transmute_same_size() {
+    let hir = check("fn main() -> u32 { transmute::<f32, u32>(1.0) }").unwrap();
+    let expr = tail_expr(&hir, "main");
+    assert!(matches!(expr.kind, HirExprKind::Transmute { .. }));
+    assert_eq!(expr.ty, HirType::U32);
+}
+
+#[test]
+fn test_check_transmute_size_mismatch() {
+    let err = check("fn main() -> u32 { transmute::<f64, u32>(1.0) }").unwrap_err();
+    assert!(
+        err.contains("cannot transmute between types of different sizes"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_transmute_requires_type_arguments() {
+    let err = check("fn main() -> u32 { transmute(1.0) }").unwrap_err();
+    assert!(
+        err.contains("`transmute` takes 2 generic argument(s) but 0 were supplied"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Cast Tests ====================
+
+#[test]
+fn test_check_cast_int_to_float() {
+    let hir = check("fn main() -> f64 { let x: i64 = 3; x as f64 }").unwrap();
+    let expr = tail_expr(&hir, "main");
+    assert!(matches!(expr.kind, HirExprKind::Cast { .. }));
+    assert_eq!(expr.ty, HirType::F64);
+}
+
+#[test]
+fn test_check_cast_char_to_int() {
+    let hir = check("fn main() -> u32 { let c = 'a'; c as u32 }").unwrap();
+    let expr = tail_expr(&hir, "main");
+    assert!(matches!(
+        &expr.kind,
+        HirExprKind::Cast {
+            target: HirType::U32,
+            ..
+        }
+    ));
+    assert_eq!(expr.ty, HirType::U32);
+}
+
+#[test]
+fn test_check_cast_struct_rejected() {
+    let source = r#"
+struct Point { x: i64, y: i64 }
+
+fn main() -> i64 {
+    let p = Point { x: 1, y: 2 }
+    p as i64
+}
+"#;
+    let err = check(source).unwrap_err();
+    assert!(
+        err.contains("non-primitive cast: `Point` as `i64`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_cast_int_to_bool_rejected() {
+    let err = check("fn main() -> bool { 1 as bool }").unwrap_err();
+    assert!(
+        err.contains("cannot cast `i64` as `bool`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// ==================== Constant Evaluation Tests ====================
+
+fn const_errors(source: &str) -> Vec<demetrios::check::ConstEvalError> {
+    let tokens = demetrios::lexer::lex(source).expect("lex");
+    let ast = demetrios::parser::parse(&tokens, source).expect("parse");
+    let mut checker = demetrios::check::TypeChecker::new();
+    let _ = checker.check_program(&ast);
+    checker.const_errors().to_vec()
+}
+
+fn spanned<'a>(source: &'a str, err: &demetrios::check::ConstEvalError) -> &'a str {
+    &source[err.span.start..err.span.end]
+}
+
+#[test]
+fn test_const_division_by_zero_points_at_division() {
+    let source = "const X: i32 = 1 + 1 / 0;\nfn main() -> i32 { 0 }";
+    let errors = const_errors(source);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(
+        errors[0].message.contains("division by zero"),
+        "{}",
+        errors[0].message
+    );
+    assert_eq!(spanned(source, &errors[0]), "1 / 0");
+
+    let err = check(source).unwrap_err();
+    assert!(err.contains("division by zero"), "{}", err);
+}
+
+#[test]
+fn test_const_non_const_call_points_at_call() {
+    let source = "fn f() -> i32 { 1 }\nconst Y: i32 = 2 * f();\nfn main() -> i32 { 0 }";
+    let errors = const_errors(source);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(
+        errors[0].message.contains("function calls"),
+        "{}",
+        errors[0].message
+    );
+    assert_eq!(spanned(source, &errors[0]), "f()");
+}
+
+#[test]
+fn test_const_overflow_for_declared_type() {
+    let source = "const Z: u8 = 200 + 100;\nfn main() -> i32 { 0 }";
+    let errors = const_errors(source);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(
+        errors[0].message.contains("does not fit in `u8`"),
+        "{}",
+        errors[0].message
+    );
+    assert_eq!(spanned(source, &errors[0]), "200 + 100");
+}
+
+#[test]
+fn test_check_prefixed_integer_literals() {
+    let hir = check("fn hex() -> i64 { 0xFF }\nfn other() -> i64 { 0b1010 + 0o1_7 }").unwrap();
+    assert!(matches!(
+        tail_expr(&hir, "hex").kind,
+        HirExprKind::Literal(HirLiteral::Int(255))
+    ));
+    let HirExprKind::Binary { left, right, .. } = &tail_expr(&hir, "other").kind else {
+        panic!("Expected binary expression");
+    };
+    assert!(matches!(
+        left.kind,
+        HirExprKind::Literal(HirLiteral::Int(10))
+    ));
+    assert!(matches!(
+        right.kind,
+        HirExprKind::Literal(HirLiteral::Int(15))
+    ));
+}
+
+#[test]
+fn test_check_u64_literal_above_i64_max() {
+    let hir = check("fn max() -> u64 { 18_446_744_073_709_551_615 }").unwrap();
+    let tail = tail_expr(&hir, "max");
+    assert_eq!(tail.ty, HirType::U64);
+    // Carried as its 64-bit pattern
+    assert!(matches!(
+        tail.kind,
+        HirExprKind::Literal(HirLiteral::Int(-1))
+    ));
+}
+
+#[test]
+fn test_check_u128_literal_above_u64_max() {
+    let hir = check("fn big() -> u128 { let y: u128 = 0xFFFFFFFFFFFFFFFFFFFF; y }").unwrap();
+    let func = hir
+        .items
+        .iter()
+        .find_map(|item| match item {
+            HirItem::Function(f) if f.name == "big" => Some(f),
+            _ => None,
+        })
+        .unwrap();
+    let HirStmt::Let {
+        value: Some(value), ..
+    } = &func.body.stmts[0]
+    else {
+        panic!("expected a let, got {:?}", func.body.stmts[0]);
+    };
+    assert_eq!(value.ty, HirType::U128);
+    assert!(matches!(
+        value.kind,
+        HirExprKind::Literal(HirLiteral::U128(0xFFFF_FFFF_FFFF_FFFF_FFFF))
+    ));
+}
+
+#[test]
+fn test_check_negated_literal_can_be_i64_min() {
+    assert!(check("fn f() -> i64 { let n = -9223372036854775808; n }").is_ok());
+    assert!(check("fn f() -> i64 { let n: i64 = -9223372036854775808; n }").is_ok());
+
+    let err = check("fn f() -> i32 { let n: i32 = -9223372036854775808; n }").unwrap_err();
+    assert!(
+        err.contains("integer literal too large for i32"),
+        "unexpected error: {}",
+        err
+    );
+    let err = check("fn f() -> i64 { let n: i64 = -9223372036854775809; n }").unwrap_err();
+    assert!(
+        err.contains("integer literal too large for i64"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_check_integer_literal_too_large() {
+    let err = check("fn f() -> i64 { 9223372036854775808 }").unwrap_err();
+    assert!(
+        err.contains("integer literal too large for i64"),
+        "unexpected error: {}",
+        err
+    );
+
+    let err = check("fn f() -> u64 { 0x1_0000_0000_0000_0000 }").unwrap_err();
+    assert!(
+        err.contains("integer literal too large for u64"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_const_array_length() {
+    let source = "const N: i64 = 2 * 4;\nfn f(xs: [i64; N - 1]) -> i64 { 0 }";
+    let hir = check(source).unwrap();
+    let func = hir
+        .items
+        .iter()
+        .find_map(|item| match item {
+            HirItem::Function(f) if f.name == "f" => Some(f),
+            _ => None,
+        })
+        .unwrap();
+    assert!(
+        matches!(
+            func.ty.params[0].ty,
+            HirType::Array {
+                size: Some(ArrayLen::Fixed(7)),
+                ..
+            }
+        ),
+        "{:?}",
+        func.ty.params[0].ty
+    );
+
+    let source = "fn g(xs: [i64; 4 / 0]) -> i64 { 0 }";
+    let errors = const_errors(source);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(spanned(source, &errors[0]), "4 / 0");
+}
+
+#[test]
+fn test_const_error_diagnostic_label() {
+    use miette::Diagnostic;
+
+    let source = "const X: i32 = 1 / 0;";
+    let err = const_errors(source).remove(0);
+    let diagnostic = err.to_diagnostic(&demetrios::SourceFile::new("test.d", source));
+    let label = diagnostic.labels().unwrap().next().unwrap();
+    assert_eq!(label.offset(), source.find("1 / 0").unwrap());
+    assert_eq!(label.len(), "1 / 0".len());
+}
+
+// ==================== Unused Binding Tests ====================
+
+fn unused_bindings(source: &str) -> Vec<demetrios::check::UnusedBinding> {
+    let tokens = demetrios::lexer::lex(source).expect("lex");
+    let ast = demetrios::parser::parse(&tokens, source).expect("parse");
+    let mut checker = demetrios::check::TypeChecker::new();
+    checker.check_program(&ast).expect("check");
+    checker.unused_bindings().to_vec()
+}
+
+#[test]
+fn test_unused_let_binding_warns() {
+    let source = "fn main() -> i64 { let y = 2; 1 }";
+    let unused = unused_bindings(source);
+    assert_eq!(unused.len(), 1, "{:?}", unused);
+    assert_eq!(unused[0].name, "y");
+    assert!(!unused[0].is_param);
+    assert_eq!(&source[unused[0].span.start..unused[0].span.end], "y");
+}
+
+#[test]
+fn test_used_binding_does_not_warn() {
+    let unused = unused_bindings("fn main() -> i64 { let y = 2; y }");
+    assert!(unused.is_empty(), "{:?}", unused);
+}
+
+#[test]
+fn test_underscore_binding_does_not_warn() {
+    let unused = unused_bindings("fn f(_x: i64) -> i64 { let _y = 2; 1 }");
+    assert!(unused.is_empty(), "{:?}", unused);
+}
+
+#[test]
+fn test_unused_parameter_warns() {
+    let unused = unused_bindings("fn f(x: i64, y: i64) -> i64 { x }");
+    assert_eq!(unused.len(), 1, "{:?}", unused);
+    assert_eq!(unused[0].name, "y");
+    assert!(unused[0].is_param);
+}
+
+#[test]
+fn test_unused_binding_diagnostic_is_warning() {
+    use miette::Diagnostic;
+
+    let source = "fn main() -> i64 { let y = 2; 1 }";
+    let unused = unused_bindings(source).remove(0);
+    let diagnostic = unused.to_diagnostic(&demetrios::SourceFile::new("test.d", source));
+    assert_eq!(diagnostic.severity(), Some(miette::Severity::Warning));
+    assert_eq!(diagnostic.to_string(), "Unused variable `y`");
+    let label = diagnostic.labels().unwrap().next().unwrap();
+    assert_eq!(label.offset(), source.find('y').unwrap());
+}
+
+// ==================== Subtyping Tests ====================
+
+#[test]
+fn test_if_with_returning_branch_takes_other_branch_type() {
+    let source = r#"
+fn pick(c: bool, x: i64) -> i64 {
+    if c { return x } else { 5 }
+}
+"#;
+    let hir = check(source).unwrap();
+    assert_eq!(tail_expr(&hir, "pick").ty, HirType::I64);
+
+    let source = r#"
+fn pick(c: bool, x: i64) -> bool {
+    let y = if c { return true } else { x }
+    let b: bool = y
+    b
+}
+"#;
+    let err = check(source).unwrap_err();
+    assert!(err.contains("expected bool, found i64"), "{}", err);
+}
+
+#[test]
+fn test_mut_ref_coerces_to_shared_ref() {
+    let source = r#"
+fn read(r: &i64) -> i64 { *r }
+fn main() -> i64 {
+    let mut x: i64 = 1
+    let m: &mut i64 = &mut x
+    read(m)
+}
+"#;
+    assert!(check(source).is_ok(), "{:?}", check(source).err());
+}
+
+#[test]
+fn test_shared_ref_does_not_coerce_to_mut_ref() {
+    let source = r#"
+fn bump(r: &mut i64) -> i64 { *r }
+fn main() -> i64 {
+    let x: i64 = 1
+    bump(&x)
+}
+"#;
+    let err = check(source).unwrap_err();
+    assert!(err.contains("expected &mut i64, found &i64"), "{}", err);
+}
+
+#[test]
+fn test_await_yields_future_output() {
+    let source = r#"
+async fn fetch() -> i64 { 1 }
+async fn run() -> i64 {
+    await fetch() + 1
+}
+"#;
+    let hir = check(source).unwrap();
+    assert_eq!(tail_expr(&hir, "run").ty, HirType::I64);
+}
+
+#[test]
+fn test_await_outside_async_rejected() {
+    let source = r#"
+async fn fetch() -> i64 { 1 }
+fn main() -> i64 {
+    await fetch()
+}
+"#;
+    let err = check(source).unwrap_err();
+    assert!(
+        err.contains("`await` is only allowed inside async functions"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_await_requires_future() {
+    let source = r#"
+async fn run() -> i64 {
+    await 1
+}
+"#;
+    let err = check(source).unwrap_err();
+    assert!(
+        err.contains("`await` expects a future, found i64"),
+        "{}",
+        err
+    );
+}
+
+const LOGGER: &str = r#"
+effect Logger {
+    fn log(msg: String);
+}
+
+handler CollectLog for Logger {
+    log(msg) => {
+        let pair = resume(());
+        (pair.0, msg + pair.1)
+    }
+    return x => (x, "")
+}
+"#;
+
+#[test]
+fn test_handle_typed_by_return_clause() {
+    let source = format!(
+        "{}{}",
+        LOGGER,
+        r#"
+fn work() -> i64 with Logger {
+    perform Logger.log("hi")
+    1
+}
+
+fn main() -> (i64, String) {
+    handle work() with CollectLog
+}
+"#
+    );
+    let hir = check(&source).unwrap();
+    assert_eq!(
+        tail_expr(&hir, "main").ty,
+        HirType::Tuple(vec![HirType::I64, HirType::String])
+    );
+}
+
+#[test]
+fn test_resume_checked_against_operation_result() {
+    let source = r#"
+effect Logger {
+    fn log(msg: String);
+}
+
+handler Bad for Logger {
+    log(msg) => resume(1)
+}
+"#;
+    let err = check(source).unwrap_err();
+    assert!(err.contains("expected (), found i64"), "{}", err);
+}
+
+const STATE: &str = r#"
+effect State {
+    fn get() -> i64;
+    fn put(value: i64);
+}
+"#;
+
+#[test]
+fn test_handler_covering_all_operations() {
+    let source = format!(
+        "{}{}",
+        STATE,
+        r#"
+handler Constant for State {
+    get() => resume(7)
+    put(value: i64) => resume(())
+}
+"#
+    );
+    assert!(check(&source).is_ok(), "{:?}", check(&source));
+}
+
+#[test]
+fn test_handler_missing_operation() {
+    let source = format!(
+        "{}{}",
+        STATE,
+        r#"
+handler ReadOnly for State {
+    get() => resume(7)
+}
+"#
+    );
+    let err = check(&source).unwrap_err();
+    assert!(
+        err.contains("Handler `ReadOnly` does not handle operation(s) `put` of effect `State`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_handler_unknown_operation() {
+    let source = format!(
+        "{}{}",
+        STATE,
+        r#"
+handler Typo for State {
+    get() => resume(7)
+    set(value) => resume(())
+}
+"#
+    );
+    let err = check(&source).unwrap_err();
+    assert!(
+        err.contains("Effect `State` has no operation `set`"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("does not handle operation(s) `put`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_perform_checks_operation_arguments() {
+    let source = format!(
+        "{}{}",
+        LOGGER,
+        r#"
+fn work() with Logger {
+    perform Logger.log(1)
+}
+"#
+    );
+    let err = check(&source).unwrap_err();
+    assert!(err.contains("expected String, found i64"), "{}", err);
+}
+
+const YIELD: &str = r#"
+effect Yield<T> {
+    fn yield(value: T);
+}
+
+handler Sum for Yield<i64> {
+    yield(value) => value + resume(())
+    return x => 0
+}
+"#;
+
+#[test]
+fn test_generic_effect_instantiated_per_use() {
+    let source = format!(
+        "{}{}",
+        YIELD,
+        r#"
+handler Concat for Yield<String> {
+    yield(value) => value + resume(())
+    return x => ""
+}
+
+fn numbers() with Yield<i64> {
+    perform Yield.yield(1)
+}
+
+fn words() with Yield<String> {
+    perform Yield.yield("a")
+}
+
+fn main() -> (i64, String) {
+    (handle numbers() with Sum, handle words() with Concat)
+}
+"#
+    );
+    let hir = check(&source).unwrap();
+    assert_eq!(
+        tail_expr(&hir, "main").ty,
+        HirType::Tuple(vec![HirType::I64, HirType::String])
+    );
+}
+
+#[test]
+fn test_generic_effect_operation_argument_checked() {
+    let source = format!(
+        "{}{}",
+        YIELD,
+        r#"
+fn produce() with Yield<i64> {
+    perform Yield.yield("one")
+}
+"#
+    );
+    let err = check(&source).unwrap_err();
+    assert!(err.contains("expected i64, found String"), "{}", err);
+}
+
+#[test]
+fn test_generic_effect_argument_count() {
+    let source = format!(
+        "{}{}",
+        YIELD,
+        r#"
+fn produce() with Yield<i64, bool> {
+    perform Yield.yield(1)
+}
+"#
+    );
+    let err = check(&source).unwrap_err();
+    assert!(
+        err.contains("Effect `Yield` takes 1 type argument(s) but 2 were supplied"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_empty_range_pattern_rejected() {
+    let source = r#"
+fn f(n: i64) -> i64 {
+    match n {
+        5..5 => 1,
+        _ => 0,
+    }
+}
+"#;
+    let err = check(source).unwrap_err();
+    assert!(
+        err.contains("Range pattern `5..5` matches no values"),
+        "{}",
+        err
+    );
+}
+
+// ==================== Bounds Check Tests ====================
+
+/// Whether each index expression in function `name` was proven in bounds,
+/// in source order
+fn index_checks(hir: &Hir, name: &str) -> Vec<bool> {
+    use demetrios::hir::visit::{HirVisitor, walk_expr, walk_fn};
+
+    struct Indices(Vec<bool>);
+    impl HirVisitor for Indices {
+        fn visit_expr(&mut self, expr: &HirExpr) {
+            if let HirExprKind::Index { in_bounds, .. } = &expr.kind {
+                self.0.push(*in_bounds);
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    let func = hir
+        .items
+        .iter()
+        .find_map(|item| match item {
+            HirItem::Function(f) if f.name == name => Some(f),
+            _ => None,
+        })
+        .unwrap();
+    let mut indices = Indices(Vec::new());
+    walk_fn(&mut indices, func);
+    indices.0
+}
+
+#[test]
+fn test_check_range_loop_index_in_bounds() {
+    let source = r#"
+fn sum() -> i64 {
+    let arr = [1, 2, 3, 4];
+    let mut total = 0;
+    for i in 0..arr.len() {
+        total = total + arr[i];
+    }
+    total
+}
+"#;
+    let hir = check(source).unwrap();
+    assert_eq!(index_checks(&hir, "sum"), vec![true]);
+}
+
+#[test]
+fn test_check_unconstrained_index_keeps_check() {
+    let source = r#"
+fn get(j: i64) -> i64 {
+    let arr = [1, 2, 3, 4];
+    let mut k = 0;
+    for i in 0..arr.len() {
+        k = arr[i + 1];
+    }
+    arr[j] + arr[k]
+}
+"#;
+    let hir = check(source).unwrap();
+    assert_eq!(index_checks(&hir, "get"), vec![false, false, false]);
+}
+
+#[test]
+fn test_check_guarded_and_constant_indices() {
+    let source = r#"
+fn get(j: i64) -> i64 {
+    let arr = [1, 2, 3];
+    let last = arr[2] + arr[3];
+    if j >= 0 && j < arr.len() { arr[j] + last } else { 0 }
+}
+
+fn window(n: i64) -> i64 {
+    let arr = [1, 2, 3, 4];
+    let mut total = 0;
+    for i in 1..=3 {
+        total = total + arr[i] + arr[i - 1];
+    }
+    for i in 0..n {
+        total = total + arr[i];
+    }
+    total
+}
+"#;
+    let hir = check(source).unwrap();
+    assert_eq!(index_checks(&hir, "get"), vec![true, false, true]);
+    assert_eq!(index_checks(&hir, "window"), vec![true, true, false]);
+}
+
+// ==================== Type Display Tests ====================
+
+#[test]
+fn test_display_nested_generics() {
+    let ty = named(
+        "Map",
+        vec![
+            HirType::String,
+            named("Vec", vec![named("Option", vec![HirType::I64])]),
+        ],
+    );
+    assert_eq!(ty.to_string(), "Map<String, Vec<Option<i64>>>");
+}
+
+#[test]
+fn test_display_references_arrays_and_tuples() {
+    let shared = HirType::Ref {
+        mutable: false,
+        inner: Box::new(HirType::Array {
+            element: Box::new(HirType::F64),
+            size: None,
+        }),
+    };
+    let unique = HirType::Ref {
+        mutable: true,
+        inner: Box::new(named("T", vec![])),
+    };
+    let tuple = HirType::Tuple(vec![HirType::I64, HirType::F64]);
+    let single = HirType::Tuple(vec![HirType::Array {
+        element: Box::new(HirType::U8),
+        size: Some(ArrayLen::Fixed(4)),
+    }]);
+    assert_eq!(shared.to_string(), "&[f64]");
+    assert_eq!(unique.to_string(), "&mut T");
+    assert_eq!(tuple.to_string(), "(i64, f64)");
+    assert_eq!(single.to_string(), "([u8; 4],)");
+    assert_eq!(HirType::Unit.to_string(), "()");
+}
+
+#[test]
+fn test_display_function_types() {
+    let pred = HirType::Fn {
+        params: vec![HirType::I64],
+        return_type: Box::new(HirType::Bool),
+    };
+    let higher = HirType::Fn {
+        params: vec![pred.clone(), named("Vec", vec![HirType::I64])],
+        return_type: Box::new(named("Vec", vec![HirType::I64])),
+    };
+    assert_eq!(pred.to_string(), "fn(i64) -> bool");
+    assert_eq!(
+        higher.to_string(),
+        "fn(fn(i64) -> bool, Vec<i64>) -> Vec<i64>"
+    );
+}
+
+#[test]
+fn test_check_mismatch_renders_types() {
+    let source = format!(
+        "{}\nfn main() -> Option<Option<i64>> {{ Option::Some(1) }}",
+        ENUMS
+    );
+    let err = check(&source).unwrap_err();
+    assert!(err.contains("Option<Option<i64>>"), "{}", err);
+    assert!(!err.contains("Named"), "{}", err);
+}
+
+// ==================== Expression Body Tests ====================
+
+#[test]
+fn test_check_expression_body_matches_block_body() {
+    let short = check("fn double(x: i64) -> i64 = x * 2;").unwrap();
+    let braced = check("fn double(x: i64) -> i64 { x * 2 }").unwrap();
+    assert_eq!(format!("{:?}", short), format!("{:?}", braced));
+}
+
+// ==================== Constant Folding Tests ====================
+
+fn folded_tail(source: &str) -> HirExprKind {
+    let mut hir = check(source).unwrap();
+    demetrios::hir::fold_constants(&mut hir);
+    tail_expr(&hir, "main").kind.clone()
+}
+
+#[test]
+fn test_fold_literal_arithmetic() {
+    let kind = folded_tail("fn main() -> i64 { 2 + 3 * 4 }");
+    assert!(
+        matches!(kind, HirExprKind::Literal(HirLiteral::Int(14))),
+        "{:?}",
+        kind
+    );
+
+    let kind = folded_tail("fn main() -> bool { !(1.5 * 2.0 < 3.0) && -4 == 0 - 4 }");
+    assert!(
+        matches!(kind, HirExprKind::Literal(HirLiteral::Bool(true))),
+        "{:?}",
+        kind
+    );
+}
+
+#[test]
+fn test_fold_leaves_non_constant_operands() {
+    let kind = folded_tail("fn main() -> i64 { let x = 5; x + 0 }");
+    match kind {
+        HirExprKind::Binary { left, right, .. } => {
+            assert!(matches!(left.kind, HirExprKind::Local(ref name) if name == "x"));
+            assert!(matches!(
+                right.kind,
+                HirExprKind::Literal(HirLiteral::Int(0))
+            ));
+        }
+        other => panic!("expected `x + 0` to be kept, got {:?}", other),
+    }
+
+    // Only the constant operand of a mixed expression is folded
+    let kind = folded_tail("fn main() -> i64 { let x = 5; x * (2 + 3) }");
+    match kind {
+        HirExprKind::Binary { right, .. } => {
+            assert!(matches!(
+                right.kind,
+                HirExprKind::Literal(HirLiteral::Int(5))
+            ))
+        }
+        other => panic!("expected a multiplication, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_fold_respects_overflow() {
+    let kind = folded_tail("fn main() -> i64 { 9223372036854775807 + 1 }");
+    assert!(matches!(kind, HirExprKind::Binary { .. }), "{:?}", kind);
+
+    let mut hir = check("fn main() -> i64 { let x: i8 = 100 + 100; 0 }").unwrap();
+    demetrios::hir::fold_constants(&mut hir);
+    let HirItem::Function(main) = &hir.items[0] else {
+        panic!("expected main");
+    };
+    match &main.body.stmts[0] {
+        HirStmt::Let {
+            value: Some(value), ..
+        } => assert!(
+            matches!(value.kind, HirExprKind::Binary { .. }),
+            "{:?}",
+            value
+        ),
+        other => panic!("expected a let, got {:?}", other),
+    }
+
+    let kind = folded_tail("fn main() -> i64 { 1 / 0 }");
+    assert!(matches!(kind, HirExprKind::Binary { .. }), "{:?}", kind);
+}
+
+// ==================== Duplicate Name Tests ====================
+
+#[test]
+fn test_duplicate_struct_field_rejected() {
+    let err = check("struct P { x: i64, y: i64, x: f64 }").unwrap_err();
+    assert!(
+        err.contains("Field `x` is declared more than once in struct P"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_duplicate_pattern_binding_rejected() {
+    let err = check("fn f(p: (i64, i64)) -> i64 { match p { (a, a) => a } }").unwrap_err();
+    assert!(
+        err.contains("Identifier `a` is bound more than once in the same pattern"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_repeated_unit_variant_pattern_is_not_a_binding() {
+    let source =
+        "fn f(p: (Option<i64>, Option<i64>)) -> i64 { match p { (None, None) => 0, _ => 1 } }";
+    assert!(check(source).is_ok(), "{:?}", check(source));
+}
+
+// ==================== Let-Else Tests ====================
+
+#[test]
+fn test_let_else_binds_pattern_in_following_statements() {
+    let hir = check("fn f(opt: Option<i64>) -> i64 { let Some(x) = opt else { return 0 }; x + 1 }")
+        .unwrap();
+    let expr = tail_expr(&hir, "f");
+    assert_eq!(expr.ty, HirType::I64);
+    match &expr.kind {
+        HirExprKind::Match { arms, .. } => assert_eq!(arms.len(), 2),
+        other => panic!("Expected let-else to become a match, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_let_else_block_must_diverge() {
+    let err =
+        check("fn f(opt: Option<i64>) -> i64 { let Some(x) = opt else { 5 }; x }").unwrap_err();
+    assert!(
+        err.contains("`let ... else` block must diverge, but it has type i64"),
+        "{}",
+        err
+    );
+}
+
+// ==================== Trait Object Tests ====================
+
+const SHAPES: &str = "trait Shape { fn area(self) -> i64; } \
+    struct Square { side: i64 } \
+    impl Shape for Square { fn area(self) -> i64 { self.side * self.side } } ";
+
+#[test]
+fn test_reference_coerces_to_dyn_trait() {
+    let source = format!(
+        "{}fn f(sq: Square) -> i64 {{ let s: &dyn Shape = &sq; s.area() }}",
+        SHAPES
+    );
+    let hir = check(&source).unwrap();
+    assert_eq!(tail_expr(&hir, "f").ty, HirType::I64);
+}
+
+#[test]
+fn test_dyn_coercion_requires_impl() {
+    let source = format!(
+        "{}struct Circle {{ r: i64 }} fn f(c: Circle) -> i64 {{ let s: &dyn Shape = &c; 0 }}",
+        SHAPES
+    );
+    let err = check(&source).unwrap_err();
+    assert!(
+        err.contains("expected &dyn Shape, found &Circle"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_generic_method_makes_trait_not_object_safe() {
+    let source = "trait Show { fn show<T>(self, x: T) -> i64; } fn f(s: &dyn Show) -> i64 { 0 }";
+    let err = check(source).unwrap_err();
+    assert!(
+        err.contains(
+            "Trait `Show` cannot be used as `dyn Show` because its method `show` is generic"
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_receiverless_method_makes_trait_not_object_safe() {
+    let source = "trait Shape { fn area(&self) -> f64; fn unit() -> f64; } \
+                  fn f(s: &dyn Shape) -> f64 { s.area() }";
+    let err = check(source).unwrap_err();
+    assert!(
+        err.contains(
+            "Trait `Shape` cannot be used as `dyn Shape` because its method `unit` has no `self` receiver"
+        ),
+        "{}",
+        err
+    );
+}
+
+// ==================== Loop Tests ====================
+
+#[test]
+fn test_break_with_value_rejected_in_for() {
+    let err = check("fn f() -> i64 { for i in 0..5 { break i; } 0 }").unwrap_err();
+    assert!(
+        err.contains("`break` with a value is only allowed in `loop`, not `for`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_break_without_value_allowed_in_for_inside_loop() {
+    let source = "fn f() -> i64 { loop { for i in 0..5 { break; } break; } 0 }";
+    assert!(check(source).is_ok(), "{:?}", check(source));
+}
+
+// ==================== Recursive Type Tests ====================
+
+#[test]
+fn test_directly_recursive_struct_rejected() {
+    let err = check("struct Node { value: i64, next: Node }").unwrap_err();
+    assert!(
+        err.contains("Recursive type `Node` has infinite size: Node -> Node"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_mutually_recursive_types_name_the_cycle() {
+    let err = check("struct A { b: B } enum B { Leaf, Wrap((i64, A)) }").unwrap_err();
+    assert!(err.contains("A -> B -> A"), "{}", err);
+}
+
+#[test]
+fn test_recursion_through_box_accepted() {
+    let source = "struct Node { value: i64, next: Option<Box<Node>> } \
+                  struct Tree { children: Vec<Tree>, parent: &Tree }";
+    assert!(check(source).is_ok(), "{:?}", check(source));
+}
@@ -0,0 +1,227 @@
+//! Error code and machine-readable diagnostic tests
+
+use demetrios::SourceFile;
+use demetrios::diagnostics::{ERROR_CODES, JsonDiagnostic, JsonSeverity, explain};
+use demetrios::lexer::lex;
+use demetrios::parser::parse;
+use demetrios::resolve::resolve;
+use std::collections::HashSet;
+use std::process::Command;
+
+#[test]
+fn test_unknown_variable_reports_code() {
+    let src = "fn main() -> i64 { missing + 1 }";
+    let tokens = lex(src).unwrap();
+    let ast = parse(&tokens, src).unwrap();
+    let err = resolve(ast).unwrap_err();
+
+    let codes: Vec<_> = err
+        .related()
+        .expect("resolution errors")
+        .filter_map(|e| e.code().map(|c| c.to_string()))
+        .collect();
+    assert_eq!(codes, ["D0101"]);
+}
+
+#[test]
+fn test_explain_known_code() {
+    let text = explain("D0203").expect("D0203 is a known code").to_string();
+    assert!(text.starts_with("D0203: unit mismatch"), "{}", text);
+    assert!(text.contains("Erroneous code example"), "{}", text);
+}
+
+#[test]
+fn test_explain_unknown_code() {
+    assert!(explain("D9999").is_none());
+    assert!(explain("unit::mismatch").is_none());
+}
+
+#[test]
+fn test_error_codes_are_unique() {
+    let mut seen = HashSet::new();
+    for entry in ERROR_CODES {
+        assert!(seen.insert(entry.code), "duplicate code {}", entry.code);
+    }
+}
+
+/// Every `.rs` file under `dir`
+fn source_files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(source_files(&path));
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    files
+}
+
+#[test]
+fn test_diagnostic_attribute_codes_are_explained() {
+    let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut found = 0;
+    for path in source_files(&src) {
+        let text = std::fs::read_to_string(&path).unwrap();
+        for (start, _) in text.match_indices("code(D") {
+            let code = &text[start + "code(".len()..start + "code(D0000".len()];
+            assert!(
+                explain(code).is_some(),
+                "{} uses {} but ERROR_CODES has no entry for it",
+                path.display(),
+                code
+            );
+            found += 1;
+        }
+    }
+    assert!(
+        found > 0,
+        "no diagnostic codes found under {}",
+        src.display()
+    );
+}
+
+#[test]
+fn test_json_diagnostics_expand_related_errors() {
+    let src = "fn main() -> i64 {\n    a + b\n}";
+    let tokens = lex(src).unwrap();
+    let ast = parse(&tokens, src).unwrap();
+    let err = resolve(ast).unwrap_err();
+
+    let file = SourceFile::new("main.d", src);
+    let diagnostics = JsonDiagnostic::from_diagnostic(&*err, &file);
+    let codes: Vec<_> = diagnostics.iter().map(|d| d.code.as_deref()).collect();
+    assert_eq!(codes, [Some("D0101"); 2]);
+    for diagnostic in &diagnostics {
+        assert_eq!(diagnostic.severity, JsonSeverity::Error);
+        assert_eq!(diagnostic.file, "main.d");
+    }
+    // Each undefined variable is located at its use
+    let starts: Vec<_> = diagnostics
+        .iter()
+        .map(|d| d.span.map(|span| (span.start.line, span.start.column)))
+        .collect();
+    assert_eq!(starts, [Some((2, 5)), Some((2, 9))]);
+}
+
+#[test]
+fn test_json_diagnostic_without_location_has_no_span() {
+    let src = "fn f(x: Missing) -> i64 { 1 }";
+    let tokens = lex(src).unwrap();
+    let ast = parse(&tokens, src).unwrap();
+    let err = resolve(ast).unwrap_err();
+
+    let file = SourceFile::new("main.d", src);
+    let diagnostics = JsonDiagnostic::from_diagnostic(&*err, &file);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code.as_deref(), Some("D0102"));
+    assert_eq!(diagnostics[0].span, None);
+}
+
+#[test]
+fn test_check_json_reports_large_literal_span() {
+    let source = "fn main() -> i64 {\n    99999999999999999999\n}\n";
+    let path = std::env::temp_dir().join(format!("large-literal-{}.d", std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dc"))
+        .args(["check", "--json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostic = &json.as_array().expect("a JSON array")[0];
+    assert_eq!(diagnostic["message"], "integer literal too large for i64");
+    let span = &diagnostic["span"];
+    assert_eq!(span["start"]["line"], 2);
+    assert_eq!(span["start"]["column"], 5);
+    assert_eq!(span["end"]["column"], 25);
+}
+
+#[test]
+fn test_check_json_reports_type_error_span() {
+    let source = "fn main() -> i64 {\n    let x: i64 = true;\n    x\n}\n";
+    let path = std::env::temp_dir().join(format!("check-json-{}.d", std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dc"))
+        .args(["check", "--json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics = json.as_array().expect("a JSON array");
+    assert_eq!(diagnostics.len(), 1, "{:#}", json);
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic["code"], "D0201");
+    assert_eq!(diagnostic["severity"], "error");
+    assert_eq!(diagnostic["file"], path.to_string_lossy().as_ref());
+    assert!(
+        diagnostic["message"]
+            .as_str()
+            .unwrap()
+            .contains("expected i64, found bool"),
+        "{:#}",
+        diagnostic
+    );
+    // The span covers `true`
+    let span = &diagnostic["span"];
+    assert_eq!(span["start"]["line"], 2);
+    assert_eq!(span["start"]["column"], 18);
+    assert_eq!(span["end"]["line"], 2);
+    assert_eq!(span["end"]["column"], 22);
+}
+
+#[test]
+fn test_check_json_blames_infinite_type_on_tail() {
+    let source = "enum List<T> { Nil, Cons(T, i64) }\n\nfn wrap<T>(x: T) -> T {\n    let list: List<T> = List::Nil;\n    list\n}\n";
+    let path = std::env::temp_dir().join(format!("infinite-type-{}.d", std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dc"))
+        .args(["check", "--json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostic = &json.as_array().expect("a JSON array")[0];
+    assert_eq!(diagnostic["code"], "D0202");
+    assert_eq!(
+        diagnostic["message"],
+        "cannot construct infinite type: T = List<T>"
+    );
+    let span = &diagnostic["span"];
+    assert_eq!(span["start"]["line"], 5);
+    assert_eq!(span["start"]["column"], 5);
+    assert_eq!(span["end"]["column"], 9);
+}
+
+#[test]
+fn test_check_json_blames_dyn_type_for_object_safety() {
+    let source =
+        "trait Shape {\n    fn unit() -> f64;\n}\n\nfn f(s: &dyn Shape) -> i64 {\n    0\n}\n";
+    let path = std::env::temp_dir().join(format!("object-safety-{}.d", std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dc"))
+        .args(["check", "--json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostic = &json.as_array().expect("a JSON array")[0];
+    assert_eq!(
+        diagnostic["message"],
+        "Trait `Shape` cannot be used as `dyn Shape` because its method `unit` has no `self` receiver"
+    );
+    let span = &diagnostic["span"];
+    assert_eq!(span["start"]["line"], 5);
+    assert_eq!(span["start"]["column"], 10);
+    assert_eq!(span["end"]["column"], 19);
+}
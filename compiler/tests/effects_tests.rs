@@ -108,3 +108,330 @@ fn test_function_call_propagates_effects() {
     );
     assert!(result.is_ok());
 }
+
+const IO_ALIAS: &str = r#"
+    effect FileSystem {
+        fn read(path: String) -> String;
+    }
+    effect Net {
+        fn send(data: String);
+    }
+    effect IO = Alloc + FileSystem + Net;
+"#;
+
+/// Check `src` and return the displayed effects of each function in order
+fn function_effects(src: &str) -> Vec<(String, Vec<String>, String)> {
+    let tokens = demetrios::lexer::lex(src).unwrap();
+    let ast = parser::parse(&tokens, src).unwrap();
+    let resolved = resolve::resolve(ast).unwrap();
+    let mut checker = EffectChecker::new(&resolved.symbols);
+    checker.check_program(&resolved.ast).unwrap();
+    resolved
+        .ast
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            demetrios::ast::Item::Function(f) => {
+                let def_id = resolved.symbols.def_for_node(f.id)?;
+                let effects = checker.get_function_effects(def_id)?;
+                let mut names: Vec<_> = effects.effects.iter().cloned().collect();
+                names.sort();
+                Some((f.name.clone(), names, checker.display_effects(effects)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_effect_alias_declares_bundle() {
+    let src = format!(
+        "{}{}",
+        IO_ALIAS,
+        r#"
+        fn load() with IO {
+            perform FileSystem.read("config")
+        }
+
+        fn caller() with Alloc, FileSystem, Net {
+            load()
+        }
+    "#
+    );
+    assert!(check_effects(&src).is_ok());
+}
+
+#[test]
+fn test_effect_alias_expands_in_inferred_set() {
+    let src = format!("{}{}", IO_ALIAS, "fn load() with IO {}");
+    let effects = function_effects(&src);
+    assert_eq!(effects.len(), 1);
+    let (name, members, display) = &effects[0];
+    assert_eq!(name, "load");
+    assert_eq!(members, &["Alloc", "FileSystem", "Net"]);
+    assert_eq!(display, "IO");
+}
+
+#[test]
+fn test_effect_alias_missing_member() {
+    let src = format!(
+        "{}{}",
+        IO_ALIAS,
+        r#"
+        fn load() with IO {}
+
+        fn caller() with Alloc {
+            load()
+        }
+    "#
+    );
+    assert!(check_effects(&src).is_err());
+}
+
+#[test]
+fn test_transparent_effect_alias_displays_expansion() {
+    let effects = function_effects(
+        r#"
+        #[transparent]
+        effect Fallible = Panic + Div;
+
+        fn risky(a: i32, b: i32) -> i32 with Fallible {
+            return a / b
+        }
+    "#,
+    );
+    assert_eq!(effects[0].2, "Div, Panic");
+}
+
+#[test]
+fn test_nested_effect_alias() {
+    let src = format!(
+        "{}{}",
+        IO_ALIAS,
+        r#"
+        effect Sys = IO + Panic;
+
+        fn run(a: i32, b: i32) -> i32 with Sys {
+            return a / b
+        }
+    "#
+    );
+    let effects = function_effects(&src);
+    assert_eq!(effects[0].1, ["Alloc", "FileSystem", "Net", "Panic"]);
+    assert_eq!(effects[0].2, "Sys");
+}
+
+#[test]
+fn test_recursive_effect_alias() {
+    let result = check_effects(
+        r#"
+        effect A = B + Panic;
+        effect B = A;
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("RecursiveAlias"), "{}", err);
+}
+
+#[test]
+fn test_await_in_async_function() {
+    let result = check_effects(
+        r#"
+        async fn fetch() -> i64 {
+            1
+        }
+
+        async fn run() -> i64 {
+            await fetch()
+        }
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_await_outside_async_rejected() {
+    let result = check_effects(
+        r#"
+        async fn fetch() -> i64 {
+            1
+        }
+
+        fn run() -> i64 {
+            await fetch()
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("AwaitOutsideAsync"), "{}", err);
+}
+
+#[test]
+fn test_calling_async_requires_async_effect() {
+    let result = check_effects(
+        r#"
+        async fn fetch() -> i64 {
+            1
+        }
+
+        fn run() {
+            fetch()
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("UndeclaredEffect"), "{}", err);
+    assert!(err.contains("Async"), "{}", err);
+}
+
+#[test]
+fn test_callback_within_effect_row() {
+    let result = check_effects(
+        r#"
+        fn run_with(f: fn() -> i64 with IO) -> i64 with IO {
+            f() + 1
+        }
+
+        fn greet() -> i64 with IO {
+            41
+        }
+
+        fn main() -> i64 with IO {
+            run_with(greet)
+        }
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_calling_callback_performs_its_row() {
+    let result = check_effects(
+        r#"
+        fn run_with<F>(f: F) -> i64 where F: Fn() -> i64 with IO {
+            f()
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("UndeclaredEffect"), "{}", err);
+    assert!(err.contains("IO"), "{}", err);
+}
+
+#[test]
+fn test_callback_with_undeclared_effect_rejected() {
+    let result = check_effects(
+        r#"
+        fn run_with(f: fn() -> i64 with IO) -> i64 with IO {
+            f()
+        }
+
+        fn allocate() -> i64 with Alloc {
+            1
+        }
+
+        fn main() -> i64 with IO, Alloc {
+            run_with(allocate)
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("CallbackEffect"), "{}", err);
+    assert!(err.contains("Alloc"), "{}", err);
+}
+
+#[test]
+fn test_closure_callback_checked_against_row() {
+    let result = check_effects(
+        r#"
+        fn pure_map<F>(f: F) -> i64 where F: Fn(i64) -> i64 {
+            f(1)
+        }
+
+        fn main() -> i64 with IO {
+            pure_map(|x: i64| { perform IO.print(x); x })
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("CallbackEffect"), "{}", err);
+    assert!(err.contains("IO"), "{}", err);
+}
+
+/// Warnings from checking `src`, which must otherwise pass
+fn effect_warnings(src: &str) -> Vec<String> {
+    let tokens = demetrios::lexer::lex(src).unwrap();
+    let ast = parser::parse(&tokens, src).unwrap();
+    let resolved = resolve::resolve(ast).unwrap();
+    let mut checker = EffectChecker::new(&resolved.symbols);
+    checker.check_program(&resolved.ast).unwrap();
+    checker.warnings().iter().map(|w| w.to_string()).collect()
+}
+
+#[test]
+fn test_unused_declared_effect_warns() {
+    let warnings = effect_warnings(
+        r#"
+        fn answer() -> i64 with Alloc {
+            42
+        }
+    "#,
+    );
+    assert_eq!(warnings, ["Effect `Alloc` is declared but never performed"]);
+}
+
+#[test]
+fn test_used_effects_do_not_warn() {
+    let warnings = effect_warnings(
+        r#"
+        fn divide(a: i64, b: i64) -> i64 with Panic {
+            a / b
+        }
+
+        fn halve(a: i64) -> i64 with Panic {
+            divide(a, 2)
+        }
+    "#,
+    );
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn test_show_effects_lists_declared_and_inferred_rows() {
+    let source = r#"
+effect IO {
+    fn print(s: String) -> ();
+}
+
+fn over(a: i64) -> i64 with IO, Panic {
+    a / 2
+}
+
+fn exact() -> () with IO {
+    perform IO.print("hi")
+}
+"#;
+    let path = std::env::temp_dir().join(format!("show-effects-{}.d", std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_dc"))
+        .args(["check", "--show-effects"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<_> = stdout
+        .lines()
+        .skip_while(|line| *line != "=== Function Effects ===")
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            "  fn over: declared {IO, Panic}, inferred {Panic}  (over-declared: IO)",
+            "  fn exact: declared {IO}, inferred {IO}",
+        ]
+    );
+}
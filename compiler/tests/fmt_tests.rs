@@ -0,0 +1,215 @@
+//! Source formatter tests for `dc fmt`
+
+use std::process::Command;
+
+use demetrios::fmt::{equivalent, format_source};
+use demetrios::parse;
+
+/// A file exercising most of the syntax, deliberately badly laid out
+const SAMPLE: &str = r#"//! Dosing helpers
+
+module pk::dosing
+
+import std::math;
+
+/// Patient parameters
+pub struct Patient { weight: f64@kg, age: i64 }
+
+enum Route { Oral = 1, IV, Topical = 10 }
+
+trait Dose { fn amount(&self) -> f64; }
+
+impl Dose for Patient {
+    fn amount(&self) -> f64 { self.weight*2.0 }   // per kilogram
+}
+
+effect Log { fn log(msg: String) -> (); }
+
+fn clamp(x: i64, lo: i64, hi: i64) -> i64 {
+  if x<lo { lo } else if x>hi { hi } else { x }
+}
+
+fn main() -> i64 {
+    // leading comment
+    let mut total = 0;
+    let p = Patient { weight: 70.0, age: 40 };
+    for i in [1, 2, 3] { total += i; }
+
+    let route = match clamp(total, 0, 10) {
+        0 => Route::Oral,
+        n if n > 5 => { Route::IV }
+        _ => Route::Topical,
+    };
+    let f = |a: i64, b| (a + b) * 2 - -total;
+    (f(1, 2) as f64) < 3.0;
+    total
+}
+"#;
+
+#[test]
+fn test_format_is_idempotent() {
+    let formatted = format_source(SAMPLE).expect("should format");
+    assert_ne!(formatted, SAMPLE);
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+
+    let original = parse(SAMPLE).unwrap();
+    let reparsed = parse(&formatted).unwrap();
+    assert!(equivalent(&original, &reparsed));
+}
+
+#[test]
+fn test_format_keeps_comments() {
+    let formatted = format_source(SAMPLE).unwrap();
+    assert!(formatted.starts_with("//! Dosing helpers\n"));
+    assert!(formatted.contains("/// Patient parameters\npub struct Patient {"));
+    assert!(formatted.contains("        self.weight * 2.0\n    } // per kilogram\n"));
+    assert!(formatted.contains("    // leading comment\n    let mut total = 0;\n"));
+}
+
+#[test]
+fn test_format_layout() {
+    let source =
+        "fn clamp(x: i64,lo: i64) -> i64 {\n  if x<lo { lo } else { x*(lo+1) }\n}\nfn main() {}\n";
+    let expected = "\
+fn clamp(x: i64, lo: i64) -> i64 {
+    if x < lo {
+        lo
+    } else {
+        x * (lo + 1)
+    }
+}
+
+fn main() {}
+";
+    assert_eq!(format_source(source).unwrap(), expected);
+}
+
+#[test]
+fn test_format_keeps_required_parentheses() {
+    let source = "fn f(a: i64, b: i64) -> bool {\n    let c = (a - (b - 1)) * -(a + b);\n    (a as f64) < 2.0\n}\n";
+    let formatted = format_source(source).unwrap();
+    assert!(formatted.contains("let c = (a - (b - 1)) * -(a + b);"));
+    assert!(formatted.contains("(a as f64) < 2.0"));
+}
+
+#[test]
+fn test_format_tuple_struct() {
+    let source = "struct Meters(f64);\nfn main() -> f64 { Meters( 1.0 ).0 }\n";
+    let expected = "struct Meters(f64)\n\nfn main() -> f64 {\n    Meters(1.0).0\n}\n";
+    assert_eq!(format_source(source).unwrap(), expected);
+}
+
+#[test]
+fn test_format_unit_struct_and_quantity() {
+    let source = "struct Meters ;\nfn f(d: Quantity<f64,Meters>) -> f64 { d }\n";
+    let expected = "struct Meters;\n\nfn f(d: Quantity<f64, Meters>) -> f64 {\n    d\n}\n";
+    assert_eq!(format_source(source).unwrap(), expected);
+}
+
+#[test]
+fn test_format_unit_declarations() {
+    let source = "unit Bq ;\nunit N = kg * m / s ^ 2;\n";
+    let expected = "unit Bq;\n\nunit N = kg*m/s^2;\n";
+    assert_eq!(format_source(source).unwrap(), expected);
+}
+
+#[test]
+fn test_format_lifetimes() {
+    let source = "fn f<'a>(x:&'a mut i64,y:&'a i64)->&'a i64 { y }\n";
+    let expected = "fn f<'a>(x: &'a mut i64, y: &'a i64) -> &'a i64 {\n    y\n}\n";
+    assert_eq!(format_source(source).unwrap(), expected);
+}
+
+#[test]
+fn test_format_grouped_imports() {
+    let source = "import std::{io,fmt::Display as Show};\nimport std::math::* ;\n";
+    let expected = "import std::{io, fmt::Display as Show};\nimport std::math::*;\n";
+    assert_eq!(format_source(source).unwrap(), expected);
+}
+
+#[test]
+fn test_format_const_generic_arguments() {
+    let source = "fn main() { zeros::<3>(); zeros::< {N+1} >() }\n";
+    let formatted = format_source(source).unwrap();
+    assert!(formatted.contains("zeros::<3>();"), "{}", formatted);
+    assert!(formatted.contains("zeros::<{ N + 1 }>()"), "{}", formatted);
+}
+
+#[test]
+fn test_format_turbofish_on_path_segment() {
+    let source = "fn main() { Vec::< i64 >::new() }\n";
+    let formatted = format_source(source).unwrap();
+    assert!(formatted.contains("Vec::<i64>::new()"), "{}", formatted);
+}
+
+#[test]
+fn test_format_at_pattern() {
+    let source = "fn f(x: i64) -> i64 { match x { n@1..=9 => n, _ => 0 } }\n";
+    let formatted = format_source(source).unwrap();
+    assert!(formatted.contains("n @ 1..=9 => n,"), "{}", formatted);
+}
+
+#[test]
+fn test_format_for_range() {
+    let source = "fn main() { for i in 0 .. n+1 { f(i) } for j in 1..=3 {} }\n";
+    let formatted = format_source(source).unwrap();
+    assert!(formatted.contains("for i in 0..n + 1 {"), "{}", formatted);
+    assert!(formatted.contains("for j in 1..=3 {}"), "{}", formatted);
+}
+
+#[test]
+fn test_format_array_repeat() {
+    let source = "fn main() { let grid = [[0;2] ;N+1]; }\n";
+    let formatted = format_source(source).unwrap();
+    assert!(
+        formatted.contains("let grid = [[0; 2]; N + 1];"),
+        "{}",
+        formatted
+    );
+    assert!(equivalent(
+        &parse(source).unwrap(),
+        &parse(&formatted).unwrap()
+    ));
+}
+
+/// Run `dc fmt` with `args` on a temporary copy of `source`, returning the
+/// process output and the file contents afterwards
+fn run_fmt(name: &str, source: &str, args: &[&str]) -> (std::process::Output, String) {
+    let path = std::env::temp_dir().join(format!("{}-{}.d", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dc"))
+        .arg("fmt")
+        .arg(&path)
+        .args(args)
+        .output()
+        .unwrap();
+    let after = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    (output, after)
+}
+
+#[test]
+fn test_fmt_check_detects_unformatted_file() {
+    let (output, after) = run_fmt("fmt_check_unformatted", SAMPLE, &["--check"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Diff in"));
+    assert!(stdout.contains("+    if x < lo {"));
+    // `--check` leaves the file alone
+    assert_eq!(after, SAMPLE);
+}
+
+#[test]
+fn test_fmt_check_accepts_formatted_file() {
+    let formatted = format_source(SAMPLE).unwrap();
+    let (output, after) = run_fmt("fmt_check_formatted", &formatted, &["--check"]);
+    assert!(output.status.success());
+    assert_eq!(after, formatted);
+}
+
+#[test]
+fn test_fmt_rewrites_file() {
+    let (output, after) = run_fmt("fmt_rewrite", SAMPLE, &[]);
+    assert!(output.status.success());
+    assert_eq!(after, format_source(SAMPLE).unwrap());
+}
@@ -95,7 +95,7 @@ fn test_hlir_lower_simple_function() {
     let tokens = demetrios::lexer::lex(source).unwrap();
     let ast = demetrios::parser::parse(&tokens, source).unwrap();
     let hir = demetrios::check::check(&ast).unwrap();
-    let hlir = hlir::lower(&hir);
+    let hlir = hlir::lower(&hir).unwrap();
 
     assert_eq!(hlir.functions.len(), 1);
     let func = &hlir.functions[0];
@@ -109,7 +109,7 @@ fn test_hlir_lower_binary_op() {
     let tokens = demetrios::lexer::lex(source).unwrap();
     let ast = demetrios::parser::parse(&tokens, source).unwrap();
     let hir = demetrios::check::check(&ast).unwrap();
-    let hlir = hlir::lower(&hir);
+    let hlir = hlir::lower(&hir).unwrap();
 
     assert_eq!(hlir.functions.len(), 1);
     let func = &hlir.functions[0];
@@ -124,7 +124,7 @@ fn test_hlir_lower_function_with_params() {
     let tokens = demetrios::lexer::lex(source).unwrap();
     let ast = demetrios::parser::parse(&tokens, source).unwrap();
     let hir = demetrios::check::check(&ast).unwrap();
-    let hlir = hlir::lower(&hir);
+    let hlir = hlir::lower(&hir).unwrap();
 
     assert_eq!(hlir.functions.len(), 1);
     let func = &hlir.functions[0];
@@ -148,7 +148,7 @@ fn test_hlir_lower_conditional() {
     let tokens = demetrios::lexer::lex(source).unwrap();
     let ast = demetrios::parser::parse(&tokens, source).unwrap();
     let hir = demetrios::check::check(&ast).unwrap();
-    let hlir = hlir::lower(&hir);
+    let hlir = hlir::lower(&hir).unwrap();
 
     assert_eq!(hlir.functions.len(), 1);
     let func = &hlir.functions[0];
@@ -156,6 +156,249 @@ fn test_hlir_lower_conditional() {
     assert!(func.blocks.len() >= 3);
 }
 
+#[test]
+fn test_hlir_lower_branch_hints() {
+    use demetrios::hlir::{BranchHint, HlirTerminator};
+
+    let source = r#"
+        fn hot(n: i64) -> i64 {
+            if likely(n > 0) { 1 } else { 2 }
+        }
+        fn cold(n: i64) -> i64 {
+            if unlikely(n > 0) { 1 } else { 2 }
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let hint_of = |name: &str| {
+        let func = hlir.find_function(name).unwrap();
+        func.blocks
+            .iter()
+            .find_map(|b| match b.terminator {
+                HlirTerminator::CondBranch { hint, .. } => Some(hint),
+                _ => None,
+            })
+            .unwrap()
+    };
+    assert_eq!(hint_of("hot"), BranchHint::Likely);
+    assert_eq!(hint_of("cold"), BranchHint::Unlikely);
+}
+
+#[test]
+fn test_hlir_lower_guard_steps() {
+    use demetrios::hlir::{BranchHint, HlirTerminator, LowerOptions};
+
+    let source = r#"
+        fn spin() -> i64 {
+            let mut i = 0;
+            loop {
+                if i > 10 { continue; }
+                i = i + 1;
+            }
+            i
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+
+    let options = LowerOptions {
+        guard_steps: Some(1000),
+    };
+    let guarded = hlir::lower_with(&hir, &options).unwrap();
+    let func = guarded.find_function("spin").unwrap();
+    let traps: Vec<_> = func
+        .blocks
+        .iter()
+        .filter(|b| matches!(b.terminator, HlirTerminator::Trap))
+        .map(|b| b.id)
+        .collect();
+    // One check at the end of the body and one at the `continue`
+    assert_eq!(traps.len(), 2);
+    let checks = func
+        .blocks
+        .iter()
+        .filter(|b| {
+            matches!(
+                b.terminator,
+                HlirTerminator::CondBranch { then_block, hint: BranchHint::Unlikely, .. }
+                    if traps.contains(&then_block)
+            )
+        })
+        .count();
+    assert_eq!(checks, 2);
+
+    let unguarded = hlir::lower(&hir).unwrap();
+    let func = unguarded.find_function("spin").unwrap();
+    assert!(
+        !func
+            .blocks
+            .iter()
+            .any(|b| matches!(b.terminator, HlirTerminator::Trap))
+    );
+}
+
+#[test]
+fn test_hlir_lower_bounds_checks() {
+    use demetrios::hlir::{BranchHint, HlirTerminator};
+
+    let source = r#"
+        fn sum() -> i64 {
+            let arr = [1, 2, 3, 4];
+            let mut total = 0;
+            for i in 0..arr.len() {
+                total = total + arr[i];
+            }
+            total
+        }
+
+        fn get(j: i64) -> i64 {
+            let mut arr = [1, 2, 3, 4];
+            arr[j] = 0;
+            arr[j]
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let module = hlir::lower(&hir).unwrap();
+
+    let traps = |name: &str| -> Vec<_> {
+        module
+            .find_function(name)
+            .unwrap()
+            .blocks
+            .iter()
+            .filter(|b| matches!(b.terminator, HlirTerminator::Trap))
+            .map(|b| b.id)
+            .collect()
+    };
+
+    // The loop index is proven in bounds
+    assert!(traps("sum").is_empty());
+
+    // Both the store and the load are checked
+    let get_traps = traps("get");
+    assert_eq!(get_traps.len(), 2);
+    let checks = module
+        .find_function("get")
+        .unwrap()
+        .blocks
+        .iter()
+        .filter(|b| {
+            matches!(
+                b.terminator,
+                HlirTerminator::CondBranch { then_block, hint: BranchHint::Unlikely, .. }
+                    if get_traps.contains(&then_block)
+            )
+        })
+        .count();
+    assert_eq!(checks, 2);
+}
+
+/// Lower `source` and return the first `Switch` in `func` as
+/// `(case count, default block terminator is unreachable)`
+fn find_switch(source: &str, func: &str) -> (usize, bool) {
+    use demetrios::hlir::HlirTerminator;
+
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let func = hlir.find_function(func).unwrap();
+    let (cases, default) = func
+        .blocks
+        .iter()
+        .find_map(|b| match &b.terminator {
+            HlirTerminator::Switch { cases, default, .. } => Some((cases.len(), *default)),
+            _ => None,
+        })
+        .expect("expected a switch terminator");
+    let default_block = func.blocks.iter().find(|b| b.id == default).unwrap();
+    let unreachable = matches!(default_block.terminator, HlirTerminator::Unreachable);
+    (cases, unreachable)
+}
+
+#[test]
+fn test_hlir_lower_match_int_switch() {
+    let source = r#"
+        fn classify(n: i64) -> i64 {
+            match n {
+                0 => 10,
+                1 => 20,
+                2 => 30,
+                _ => 40,
+            }
+        }
+    "#;
+    let (cases, unreachable) = find_switch(source, "classify");
+    assert_eq!(cases, 3);
+    assert!(!unreachable, "wildcard arm should become the default");
+}
+
+#[test]
+fn test_hlir_lower_match_unit_enum_switch() {
+    let source = r#"
+        enum Color {
+            Red,
+            Green,
+            Blue,
+        }
+
+        fn code(c: Color) -> i64 {
+            match c {
+                Color::Red => 1,
+                Color::Green => 2,
+                Color::Blue => 3,
+            }
+        }
+    "#;
+    let (cases, unreachable) = find_switch(source, "code");
+    assert_eq!(cases, 3);
+    assert!(unreachable, "exhaustive match has no default arm");
+}
+
+#[test]
+fn test_hlir_lower_match_uses_discriminants() {
+    use demetrios::hlir::HlirTerminator;
+
+    let source = r#"
+        enum Level {
+            Low = 1,
+            Mid = 4,
+            High,
+        }
+
+        fn code(l: Level) -> i64 {
+            match l {
+                Level::Low => 1,
+                Level::Mid => 2,
+                Level::High => 3,
+            }
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let func = hlir.find_function("code").unwrap();
+    let mut tags: Vec<i64> = func
+        .blocks
+        .iter()
+        .find_map(|b| match &b.terminator {
+            HlirTerminator::Switch { cases, .. } => Some(cases.iter().map(|(v, _)| *v).collect()),
+            _ => None,
+        })
+        .expect("expected a switch terminator");
+    tags.sort();
+    assert_eq!(tags, vec![1, 4, 5]);
+}
+
 #[test]
 fn test_hlir_lower_struct() {
     let source = r#"
@@ -168,13 +411,306 @@ fn test_hlir_lower_struct() {
     let tokens = demetrios::lexer::lex(source).unwrap();
     let ast = demetrios::parser::parse(&tokens, source).unwrap();
     let hir = demetrios::check::check(&ast).unwrap();
-    let hlir = hlir::lower(&hir);
+    let hlir = hlir::lower(&hir).unwrap();
 
     assert_eq!(hlir.types.len(), 1);
     assert_eq!(hlir.types[0].name, "Point");
 }
 
+#[test]
+fn test_hlir_lower_tuple_struct_fields() {
+    use demetrios::hlir::HlirTypeDefKind;
+
+    let source = r#"
+        struct Meters(f64)
+        fn main() -> f64 { Meters(2.0).0 }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let meters = hlir.types.iter().find(|t| t.name == "Meters").unwrap();
+    let HlirTypeDefKind::Struct(fields) = &meters.kind else {
+        panic!("Expected struct, got {:?}", meters.kind);
+    };
+    assert_eq!(fields, &vec![("0".to_string(), HlirType::F64)]);
+}
+
+#[test]
+fn test_hlir_lower_tuple_as_named_struct() {
+    use demetrios::hlir::HlirTypeDefKind;
+
+    let source = r#"
+        fn main() -> i64 {
+            let p = (1, true);
+            let q = (2, false);
+            let r = (3, 4);
+            p.0 + q.0 + r.1
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let pair = HlirType::tuple_struct_name(&[HlirType::I64, HlirType::Bool]);
+    let ints = HlirType::tuple_struct_name(&[HlirType::I64, HlirType::I64]);
+    assert_ne!(pair, ints);
+
+    let names: Vec<_> = hlir.types.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec![pair.as_str(), ints.as_str()]);
+    assert!(matches!(
+        &hlir.types[0].kind,
+        HlirTypeDefKind::Struct(fields)
+            if fields == &[("0".to_string(), HlirType::I64), ("1".to_string(), HlirType::Bool)]
+    ));
+    assert_eq!(
+        HlirType::from_hir(&demetrios::hir::HirType::Tuple(vec![
+            demetrios::hir::HirType::I64,
+            demetrios::hir::HirType::Bool,
+        ])),
+        HlirType::Struct(pair)
+    );
+}
+
+#[test]
+fn test_hlir_lower_rejects_error_type() {
+    use demetrios::hir::visit::{HirVisitorMut, walk_stmt_mut};
+    use demetrios::hir::{HirStmt, HirType};
+
+    struct PoisonBindings;
+
+    impl HirVisitorMut for PoisonBindings {
+        fn visit_stmt_mut(&mut self, stmt: &mut HirStmt) {
+            if let HirStmt::Let { ty, .. } = stmt {
+                *ty = HirType::Error;
+            }
+            walk_stmt_mut(self, stmt);
+        }
+    }
+
+    let source = "fn main() -> i64 { let x = 1; x }";
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let mut hir = demetrios::check::check(&ast).unwrap();
+    demetrios::hir::visit::walk_hir_mut(&mut PoisonBindings, &mut hir);
+
+    let err = hlir::lower(&hir).unwrap_err().to_string();
+    assert!(
+        err.contains("binding `x` has an unresolved type"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_hlir_instruction_source_line() {
+    use demetrios::hlir::{BinaryOp, Op};
+    use demetrios::sourcemap::{FileId, SourceFile, SourceMap};
+
+    let source = "fn main() -> i64 {\n    let a = 6;\n    let b = a * 7;\n    b + 1\n}";
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let file = SourceFile::new(FileId::new(0), "main.d".into(), source.to_string());
+    let map = SourceMap::from_node_spans(&file, &ast.node_spans);
+
+    let line_of = |wanted: BinaryOp| {
+        let instr = hlir.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .find(|i| matches!(i.op, Op::Binary { op, .. } if op == wanted))
+            .expect("binary instruction");
+        let node = instr.span.expect("instruction span");
+        map.get_node_location(node).expect("node location")
+    };
+
+    let mul = line_of(BinaryOp::Mul);
+    assert_eq!((mul.line, mul.column), (3, 13));
+    assert_eq!(line_of(BinaryOp::Add).line, 4);
+}
+
+
+#[test]
+fn test_hlir_dyn_method_call_goes_through_vtable() {
+    use demetrios::hlir::{HlirConstant, Op};
+
+    let source = r#"
+        trait Shape { fn area(self) -> i64; }
+        struct Circle { r: i64 }
+        struct Square { s: i64 }
+        impl Shape for Circle { fn area(self) -> i64 { 3 * self.r * self.r } }
+        impl Shape for Square { fn area(self) -> i64 { self.s * self.s } }
+        fn area_of(shape: &dyn Shape) -> i64 { shape.area() }
+        fn main() -> i64 {
+            let c = Circle { r: 2 };
+            let s = Square { s: 3 };
+            area_of(&c) + area_of(&s)
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    for ty in ["Circle", "Square"] {
+        let vtable = hlir
+            .globals
+            .iter()
+            .find(|g| g.name == format!("vtable.{}.Shape", ty))
+            .expect("vtable global");
+        assert!(vtable.is_const);
+        let shim = format!("{}::area.dyn", ty);
+        assert!(matches!(
+            &vtable.init,
+            Some(HlirConstant::Array(entries))
+                if matches!(&entries[..], [HlirConstant::FunctionRef(f)] if *f == shim)
+        ));
+    }
+
+    let area_of = hlir.functions.iter().find(|f| f.name == "area_of").unwrap();
+    assert_eq!(area_of.params[0].ty, HlirType::Struct("dyn Shape".into()));
+    let ops: Vec<_> = area_of
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .map(|i| &i.op)
+        .collect();
+    assert!(ops.iter().any(|op| matches!(op, Op::Call { .. })));
+    assert!(!ops.iter().any(|op| matches!(op, Op::CallDirect { .. })));
+}
+#[test]
+fn test_hlir_while_loop_blocks() {
+    use demetrios::hlir::HlirTerminator;
+
+    let source = r#"
+        fn main() -> i64 {
+            let mut i = 0;
+            let mut total = 0;
+            while i < 10 {
+                i = i + 1;
+                if i == 5 { break; }
+                total = total + i;
+            }
+            total
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let blocks = &hlir.functions[0].blocks;
+    let block = |label: &str| blocks.iter().find(|b| b.label == label).unwrap().id;
+    let (header, body, exit) = (block("loop.header"), block("loop.body"), block("loop.exit"));
+
+    // The header tests the condition and leaves the loop when it fails
+    let header_block = blocks.iter().find(|b| b.id == header).unwrap();
+    assert!(matches!(
+        header_block.terminator,
+        HlirTerminator::CondBranch { then_block, else_block, .. }
+            if then_block == body && else_block == exit
+    ));
+    // The end of the body branches back to the header
+    assert!(blocks.iter().any(|b| {
+        b.id != header && matches!(b.terminator, HlirTerminator::Branch(t) if t == header)
+    }));
+    // `break` jumps straight to the exit block
+    let exits = blocks
+        .iter()
+        .filter(|b| matches!(b.terminator, HlirTerminator::Branch(t) if t == exit))
+        .count();
+    assert_eq!(exits, 1);
+}
+
 // JIT tests (only run with jit feature)
+#[test]
+fn test_hlir_transmute_lowers_to_bitcast() {
+    use demetrios::hlir::Op;
+
+    let source = "fn main() -> u32 { transmute::<f32, u32>(1.0) }";
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let bitcast = hlir.functions[0]
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .find(|i| matches!(i.op, Op::Bitcast { .. }))
+        .expect("bitcast instruction");
+    assert_eq!(bitcast.ty, HlirType::U32);
+}
+
+#[test]
+fn test_hlir_u128_literal_joins_halves() {
+    use demetrios::hlir::{BinaryOp, HlirConstant, Op};
+
+    let source = "fn main() -> u128 { 0x1_0000_0000_0000_0002 }";
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+    let hlir = hlir::lower(&hir).unwrap();
+
+    let instrs: Vec<_> = hlir.functions[0]
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .collect();
+    let consts: Vec<i64> = instrs
+        .iter()
+        .filter_map(|i| match i.op {
+            Op::Const(HlirConstant::Int(n, HlirType::U128)) => Some(n),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(consts, vec![1, 2, 64]);
+    let or = instrs
+        .iter()
+        .find(|i| matches!(i.op, Op::Binary { op: BinaryOp::Or, .. }))
+        .expect("or instruction");
+    assert_eq!(or.ty, HlirType::U128);
+}
+
+#[test]
+fn test_hlir_array_repeat() {
+    use demetrios::hlir::Op;
+
+    let lower = |source: &str| {
+        let tokens = demetrios::lexer::lex(source).unwrap();
+        let ast = demetrios::parser::parse(&tokens, source).unwrap();
+        let hir = demetrios::check::check(&ast).unwrap();
+        hlir::lower(&hir)
+    };
+
+    let hlir = lower("fn main() -> i64 { let xs = [7; 3]; xs[2] }").unwrap();
+    let array = hlir.functions[0]
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .find_map(|i| match &i.op {
+            Op::Array(values) => Some(values),
+            _ => None,
+        })
+        .expect("array instruction");
+    assert_eq!(array.len(), 3);
+    assert!(array.iter().all(|v| *v == array[0]));
+
+    let err = lower("fn zeros<const N: usize>() -> [i64; N] { [0; N] }")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("has an array of length `N`, a const generic parameter"),
+        "unexpected error: {}",
+        err
+    );
+}
+
 #[cfg(feature = "jit")]
 mod jit_tests {
     use demetrios::codegen::cranelift::CraneliftJit;
@@ -184,7 +720,7 @@ mod jit_tests {
         let tokens = demetrios::lexer::lex(source).map_err(|e| format!("{}", e))?;
         let ast = demetrios::parser::parse(&tokens, source).map_err(|e| format!("{:?}", e))?;
         let hir = demetrios::check::check(&ast).map_err(|e| format!("{}", e))?;
-        let hlir = hlir::lower(&hir);
+        let hlir = hlir::lower(&hir).unwrap();
 
         let jit = CraneliftJit::new();
         jit.compile_and_run(&hlir)
@@ -196,6 +732,33 @@ mod jit_tests {
         assert_eq!(result.unwrap(), 42);
     }
 
+    #[test]
+    fn test_jit_transmute_float_bits() {
+        let result = compile_and_run("fn main() -> i64 { transmute::<f64, i64>(1.0) }");
+        assert_eq!(result.unwrap(), 1.0f64.to_bits() as i64);
+    }
+
+    #[test]
+    fn test_jit_nested_loops_break_innermost() {
+        let source = r#"
+            fn main() -> i64 {
+                let mut total = 0;
+                for i in 0..4 {
+                    if i == 1 { continue; }
+                    let mut j = 0;
+                    while true {
+                        if j == i { break; }
+                        total = total + 10;
+                        j = j + 1;
+                    }
+                    total = total + 1;
+                }
+                total
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 53);
+    }
+
     #[test]
     fn test_jit_arithmetic() {
         let result = compile_and_run("fn main() -> i64 { 1 + 2 * 3 }");
@@ -265,16 +828,239 @@ mod jit_tests {
         assert_eq!(result.unwrap(), 42);
     }
 
+    #[test]
+    fn test_jit_calls_user_function() {
+        let result = compile_and_run(r#"
+            fn square(x: i64) -> i64 { x * x }
+            fn main() -> i64 { square(5) }
+        "#);
+        assert_eq!(result.unwrap(), 25);
+    }
+
+    #[test]
+    fn test_jit_calls_function_declared_later() {
+        let result = compile_and_run(r#"
+            fn main() -> i64 { add3(1, 2, 3) }
+            fn add3(a: i64, b: i64, c: i64) -> i64 { a + b + c }
+        "#);
+        assert_eq!(result.unwrap(), 6);
+    }
+
+    #[test]
+    fn test_jit_recursion() {
+        let result = compile_and_run(r#"
+            fn fact(n: i64) -> i64 {
+                if n <= 1 { 1 } else { n * fact(n - 1) }
+            }
+            fn main() -> i64 { fact(5) }
+        "#);
+        assert_eq!(result.unwrap(), 120);
+    }
+
+    #[test]
+    fn test_jit_mutual_recursion() {
+        let result = compile_and_run(r#"
+            fn is_even(n: i64) -> i64 {
+                if n == 0 { 1 } else { is_odd(n - 1) }
+            }
+            fn is_odd(n: i64) -> i64 {
+                if n == 0 { 0 } else { is_even(n - 1) }
+            }
+            fn main() -> i64 { is_even(10) + is_odd(7) }
+        "#);
+        assert_eq!(result.unwrap(), 2);
+    }
+
     #[test]
     fn test_jit_with_optimization() {
         let source = "fn main() -> i64 { 1 + 2 + 3 + 4 + 5 }";
         let tokens = demetrios::lexer::lex(source).unwrap();
         let ast = demetrios::parser::parse(&tokens, source).unwrap();
         let hir = demetrios::check::check(&ast).unwrap();
-        let hlir = hlir::lower(&hir);
+        let hlir = hlir::lower(&hir).unwrap();
 
         let jit = CraneliftJit::new().with_optimization();
         let result = jit.compile_and_run(&hlir);
         assert_eq!(result.unwrap(), 15);
     }
+
+    /// Run `source` through `dc jit --guard-steps <steps>` in a child
+    /// process, since a trap takes down the whole process
+    fn jit_with_guard(name: &str, source: &str, steps: u64) -> std::process::Output {
+        let path = std::env::temp_dir().join(format!("{}-{}.d", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_dc"))
+            .arg("jit")
+            .arg(&path)
+            .arg("--guard-steps")
+            .arg(steps.to_string())
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+        output
+    }
+
+    #[test]
+    fn test_jit_guard_steps_traps_infinite_loop() {
+        let source = r#"
+            fn main() -> i64 {
+                let mut i = 0;
+                loop {
+                    i = i + 1;
+                }
+                i
+            }
+        "#;
+        let output = jit_with_guard("guard_infinite", source, 100);
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn test_jit_guard_steps_allows_bounded_loop() {
+        let source = r#"
+            fn main() -> i64 {
+                let mut i = 0;
+                loop {
+                    if i >= 10 { break; }
+                    i = i + 1;
+                }
+                i
+            }
+        "#;
+        let output = jit_with_guard("guard_bounded", source, 100);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "10");
+    }
+
+    #[test]
+    fn test_jit_match_range() {
+        let source = r#"
+            fn bucket(n: i64) -> i64 {
+                match n {
+                    -1 => 5,
+                    0..=9 => 1,
+                    10..100 => 2,
+                    _ => 3,
+                }
+            }
+
+            fn main() -> i64 {
+                bucket(-1) * 1000 + bucket(9) * 100 + bucket(10) * 10 + bucket(100)
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 5123);
+    }
+
+    #[test]
+    fn test_jit_match_at_pattern() {
+        let source = r#"
+            fn bucket(x: i64) -> i64 {
+                match x {
+                    n @ 1..=9 => n,
+                    m @ 10..100 if m % 2 == 0 => m * 10,
+                    _ => 0,
+                }
+            }
+
+            fn main() -> i64 {
+                bucket(7) + bucket(12) + bucket(13)
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 127);
+    }
+
+    #[test]
+    fn test_jit_cast_int_float() {
+        let source = r#"
+            fn main() -> i64 {
+                let x: i64 = 7
+                let f = x as f64 * 1.5
+                f as i64
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_jit_struct_literal_field_order() {
+        let source = r#"
+            struct Point { x: i64, y: i64 }
+
+            fn make(x: i64, y: i64) -> Point {
+                Point { y: y, x: x }
+            }
+
+            fn main() -> i64 {
+                let p = make(1, 20)
+                p.x * 1000 + p.y
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 1020);
+    }
+
+    #[test]
+    fn test_jit_tuple_struct() {
+        let source = r#"
+            struct Meters(f64)
+            struct Pair(i64, i64)
+
+            fn scale(m: Meters, k: f64) -> Meters {
+                Meters(m.0 * k)
+            }
+
+            fn main() -> i64 {
+                let p = Pair(3, 4)
+                let m = scale(Meters(2.5), 4.0)
+                if m.0 == 10.0 { p.0 * 10 + p.1 } else { 0 }
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 34);
+    }
+
+    #[test]
+    fn test_jit_struct_mixed_field_widths() {
+        let source = r#"
+            struct Mixed { flag: bool, small: i32, big: i64 }
+
+            #[packed]
+            struct Packed { tag: i8, value: i64 }
+
+            fn make(small: i32, big: i64) -> Mixed {
+                Mixed { flag: true, small: small, big: big }
+            }
+
+            fn main() -> i64 {
+                let m = make(7, 30)
+                let p = Packed { tag: 2, value: 500 }
+                let f = if m.flag { 1 } else { 0 }
+                f * 1000 + (m.small as i64) * 100 + m.big + p.value + (p.tag as i64)
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 2232);
+    }
+
+    #[test]
+    fn test_jit_dyn_method_call() {
+        let source = r#"
+            trait Shape { fn area(self) -> i64; fn sides(self) -> i64; }
+            struct Rect { w: i64, h: i64 }
+            struct Square { s: i64 }
+            impl Shape for Rect {
+                fn area(self) -> i64 { self.w * self.h }
+                fn sides(self) -> i64 { 4 }
+            }
+            impl Shape for Square {
+                fn area(self) -> i64 { self.s * self.s }
+                fn sides(self) -> i64 { 4 }
+            }
+            fn total(shape: &dyn Shape) -> i64 { shape.area() * 10 + shape.sides() }
+            fn main() -> i64 {
+                let r = Rect { w: 2, h: 3 };
+                let a: &dyn Shape = &r;
+                let s = Square { s: 5 };
+                total(a) + total(&s) * 1000
+            }
+        "#;
+        assert_eq!(compile_and_run(source).unwrap(), 254064);
+    }
 }
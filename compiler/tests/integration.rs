@@ -94,3 +94,34 @@ fn test_full_pipeline_struct() {
     let result = demetrios::typecheck(src);
     assert!(result.is_ok(), "Type check failed: {:?}", result.err());
 }
+
+#[cfg(feature = "jit")]
+#[test]
+fn test_compile_emits_host_object() {
+    let bytes = demetrios::compile("fn main() -> i64 { 42 }").expect("Compilation failed");
+    assert!(!bytes.is_empty());
+
+    let magic: &[u8] = if cfg!(target_os = "macos") {
+        &[0xCF, 0xFA, 0xED, 0xFE]
+    } else if cfg!(target_os = "windows") {
+        if cfg!(target_arch = "aarch64") {
+            &[0x64, 0xAA]
+        } else {
+            &[0x64, 0x86]
+        }
+    } else {
+        b"\x7fELF"
+    };
+    assert!(
+        bytes.starts_with(magic),
+        "Unexpected object header: {:02x?}",
+        &bytes[..bytes.len().min(4)]
+    );
+}
+
+#[cfg(not(feature = "jit"))]
+#[test]
+fn test_compile_requires_jit_feature() {
+    let err = demetrios::compile("fn main() -> i64 { 42 }").unwrap_err();
+    assert!(err.to_string().contains("--features jit"), "{}", err);
+}
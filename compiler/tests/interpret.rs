@@ -2,7 +2,8 @@
 //!
 //! Tests the full pipeline: source → parse → resolve → check → interpret
 
-use demetrios::interp::{Interpreter, Value};
+use demetrios::interp::value::ControlFlow;
+use demetrios::interp::{Interpreter, OverflowMode, Value};
 
 /// Helper to interpret source code and return the result
 fn interpret(source: &str) -> Result<Value, String> {
@@ -42,6 +43,16 @@ fn assert_result_bool(source: &str, expected: bool) {
     }
 }
 
+/// Helper to interpret source code and return the captured output lines
+fn interpret_output(source: &str) -> Vec<String> {
+    let tokens = demetrios::lexer::lex(source).expect("lex error");
+    let ast = demetrios::parser::parse(&tokens, source).expect("parse error");
+    let hir = demetrios::check::check(&ast).expect("type error");
+    let mut interpreter = Interpreter::new();
+    interpreter.interpret(&hir).expect("runtime error");
+    interpreter.get_output().to_vec()
+}
+
 // ==================== Basic Expression Tests ====================
 
 #[test]
@@ -375,6 +386,26 @@ fn main() -> i64 {
     assert_result_int(source, 41);
 }
 
+#[test]
+fn test_interpret_function_passed_by_name() {
+    let source = r#"
+fn square(x: i64) -> i64 {
+    x * x
+}
+
+fn map3(xs: [i64; 3], f: fn(i64) -> i64) -> [i64; 3] {
+    [f(xs[0]), f(xs[1]), f(xs[2])]
+}
+
+fn main() -> i64 {
+    let g = square;
+    let ys = map3([1, 2, 3], square);
+    ys[0] + ys[1] + ys[2] + g(4)
+}
+"#;
+    assert_result_int(source, 30);
+}
+
 // ==================== Struct Tests ====================
 
 #[test]
@@ -412,6 +443,116 @@ fn main() -> i64 {
     assert_result_int(source, 42);
 }
 
+#[test]
+fn test_interpret_tuple_struct() {
+    let source = r#"
+struct Pair(i64, i64)
+
+fn swap(p: Pair) -> Pair {
+    Pair(p.1, p.0)
+}
+
+fn main() -> i64 {
+    let p = swap(Pair(2, 40));
+    p.0 * 10 + p.1
+}
+"#;
+    assert_result_int(source, 402);
+}
+
+#[test]
+fn test_interpret_tuple_struct_newtype() {
+    let source = r#"
+struct Meters(f64);
+
+fn add(a: Meters, b: Meters) -> Meters {
+    Meters(a.0 + b.0)
+}
+
+fn main() -> bool {
+    let total = add(Meters(1.5), Meters(2.25));
+    total.0 == 3.75
+}
+"#;
+    assert_result_bool(source, true);
+}
+
+#[test]
+fn test_tuple_struct_constructor_arity() {
+    let source = r#"
+struct Meters(f64)
+
+fn main() -> f64 {
+    let m = Meters(1.0, 2.0);
+    m.1
+}
+"#;
+    let err = interpret(source).unwrap_err();
+    assert!(
+        err.contains("`Meters` takes 1 argument(s) but 2 were supplied"),
+        "{}",
+        err
+    );
+    assert!(err.contains("no field `1` on type `Meters`"), "{}", err);
+}
+
+#[test]
+fn test_interpret_struct_equality() {
+    assert_result_int(
+        r#"
+        struct Point { x: i64, y: i64 }
+
+        fn main() -> i64 {
+            let a = Point { x: 1, y: 2 };
+            let b = Point { x: 1, y: 2 };
+            let c = Point { x: 1, y: 3 };
+            let mut n = 0;
+            if a == b { n = n + 1; }
+            if a != c { n = n + 10; }
+            if a == c { n = n + 100; }
+            if a < c { n = n + 1000; }
+            n
+        }
+    "#,
+        1011,
+    );
+}
+
+#[test]
+fn test_interpret_enum_variant_inequality() {
+    assert_result_int(
+        r#"
+        enum Shape { Circle(i64), Square(i64) }
+
+        fn main() -> i64 {
+            let mut n = 0;
+            if Shape::Circle(5) != Shape::Square(5) { n = n + 1; }
+            if Shape::Circle(5) == Shape::Circle(5) { n = n + 10; }
+            if Shape::Circle(9) < Shape::Square(1) { n = n + 100; }
+            n
+        }
+    "#,
+        111,
+    );
+}
+
+#[test]
+fn test_interpret_compare_different_shapes_is_error() {
+    let source = r#"
+        struct Point { x: i64 }
+
+        fn main() -> bool {
+            Point { x: 1 } == (1, 2)
+        }
+    "#;
+    let err = interpret(source).unwrap_err();
+    assert!(
+        err.contains("cannot compare struct `Point` with tuple"),
+        "{}",
+        err
+    );
+}
+
 // ==================== Array Tests ====================
 
 #[test]
@@ -447,6 +588,21 @@ fn main() -> i64 {
     assert_result_int(source, 42);
 }
 
+#[test]
+fn test_interpret_array_repeat() {
+    let source = r#"
+fn zeros<const N: usize>() -> [i64; N] { [0; N] }
+fn pad<const N: usize>(xs: [i64; N]) -> [i64; N] { let mut ys = zeros::<N>(); ys[0] = xs[0]; ys }
+fn main() -> i64 {
+    let mut grid = [[1; 2]; 3];
+    grid[0][1] = 5;
+    let ys = pad([7, 8, 9, 10]);
+    grid[0][1] + grid[1][1] + ys[0] + ys[3] + zeros::<3>().len()
+}
+"#;
+    assert_result_int(source, 5 + 1 + 7 + 0 + 3);
+}
+
 // ==================== Tuple Tests ====================
 
 #[test]
@@ -474,6 +630,18 @@ fn main() -> i64 {
     assert_result_int(source, 42);
 }
 
+#[test]
+fn test_interpret_tuple_ordering() {
+    assert_result_bool(
+        r#"
+        fn main() -> bool {
+            (1, 2, 3) < (1, 3, 0) && (2, 0) > (1, 9) && (1, 2) <= (1, 2) && !((1, 2) < (1, 2))
+        }
+    "#,
+        true,
+    );
+}
+
 // ==================== Loop Tests ====================
 
 #[test]
@@ -671,3 +839,1387 @@ fn main() -> i64 {
 "#;
     assert_result_int(source, 42);
 }
+
+// ==================== Intrinsic Tests ====================
+
+#[test]
+fn test_interpret_println_format() {
+    let source = r#"
+fn main() {
+    println("x = {}", 42)
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["x = 42"]);
+}
+
+#[test]
+fn test_interpret_print_multiple_placeholders() {
+    let source = r#"
+fn main() {
+    let a = 1;
+    let b = true;
+    print("{} and {} in {{braces}}", a, b);
+    println(3, 4)
+}
+"#;
+    assert_eq!(
+        interpret_output(source),
+        vec!["1 and true in {braces}", "3 4"]
+    );
+}
+
+#[test]
+fn test_interpret_format_returns_string() {
+    let source = r#"
+fn main() {
+    let s = format("{}-{}", 7, "up");
+    println(s)
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["7-up"]);
+}
+
+#[test]
+fn test_interpret_leading_string_without_placeholders_is_joined() {
+    let source = r#"
+fn main() {
+    println("total:", 3);
+    println("{{x}}");
+    println("{{x}} {}", 1)
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["total: 3", "{x}", "{x} 1"]);
+}
+
+#[test]
+fn test_interpret_format_argument_count_mismatch() {
+    let source = r#"
+fn main() {
+    println("{} and {}", 1)
+}
+"#;
+    let err = interpret(source).unwrap_err();
+    assert!(
+        err.contains("format string has 2 placeholder(s) but 1 argument(s) were given"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_interpret_branch_hints_are_identity() {
+    let source = r#"
+fn main() -> i64 {
+    let n = 5;
+    if likely(n > 0) {
+        if unlikely(n > 10) { 0 } else { n }
+    } else {
+        0
+    }
+}
+"#;
+    assert_result_int(source, 5);
+}
+
+#[test]
+fn test_interpret_parse_int() {
+    let source = r#"
+fn main() {
+    println(parse_int("42"));
+    println(parse_int("xx"));
+    println(parse_float("2.25"))
+}
+"#;
+    assert_eq!(
+        interpret_output(source),
+        vec!["Some(42)", "None", "Some(2.25)"]
+    );
+}
+
+#[test]
+fn test_interpret_to_string() {
+    let source = r#"
+fn main() {
+    let s = to_string(3.5);
+    println("[{}]", s)
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["[3.5]"]);
+}
+
+#[test]
+fn test_interpret_derive_hash_map_key() {
+    let source = r#"
+#[derive(Hash, Eq)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+fn main() -> i64 {
+    let m = map_new();
+    map_insert(m, Point { x: 1, y: 2 }, 10);
+    map_insert(m, Point { x: 3, y: 4 }, 20);
+    map_insert(m, Point { x: 1, y: 2 }, 30);
+
+    let seen = set_new();
+    set_insert(seen, Point { x: 3, y: 4 });
+
+    let mut total = 0;
+    if map_contains(m, Point { x: 1, y: 2 }) { total = total + 1 }
+    if !map_contains(m, Point { x: 2, y: 1 }) { total = total + 10 }
+    if set_contains(seen, Point { x: 3, y: 4 }) { total = total + 100 }
+    if hash(Point { x: 1, y: 2 }) == hash(Point { x: 1, y: 2 }) { total = total + 1000 }
+    total
+}
+"#;
+    assert_result_int(source, 1111);
+}
+
+// ==================== Match Tests ====================
+
+#[test]
+fn test_interpret_match_int() {
+    let source = r#"
+fn classify(n: i64) -> i64 {
+    match n {
+        0 => 10,
+        1 => 20,
+        other => other * 100,
+    }
+}
+
+fn main() -> i64 {
+    classify(1) + classify(3)
+}
+"#;
+    assert_result_int(source, 320);
+}
+
+#[test]
+fn test_interpret_let_else() {
+    let source = r#"
+fn double_or_zero(opt: Option<i64>) -> i64 {
+    let Some(x) = opt else { return 0 };
+    let y = x * 2;
+    y + 1
+}
+
+fn main() -> i64 {
+    double_or_zero(Some(20)) + double_or_zero(None)
+}
+"#;
+    assert_result_int(source, 41);
+}
+
+#[test]
+fn test_interpret_match_negative_literal() {
+    let source = r#"
+fn sign(n: i64) -> i64 {
+    match n {
+        -1 => 7,
+        0 => 0,
+        _ => 1,
+    }
+}
+
+fn main() -> i64 {
+    sign(-1) * 10 + sign(0) + sign(5)
+}
+"#;
+    assert_result_int(source, 71);
+}
+
+#[test]
+fn test_interpret_match_range() {
+    let source = r#"
+fn bucket(n: i64) -> i64 {
+    match n {
+        -9..=-1 => 1,
+        0..=9 => 2,
+        10..100 => 3,
+        _ => 4,
+    }
+}
+
+fn main() -> i64 {
+    bucket(-9) + bucket(0) * 10 + bucket(9) * 100 + bucket(99) * 1000 + bucket(100) * 10000
+}
+"#;
+    assert_result_int(source, 43221);
+}
+
+#[test]
+fn test_interpret_match_at_range() {
+    let source = r#"
+fn digit(x: i64) -> i64 {
+    match x {
+        n @ 1..=9 => n * 10,
+        _ => 0,
+    }
+}
+
+fn main() -> i64 {
+    digit(7) + digit(10) + digit(1)
+}
+"#;
+    assert_result_int(source, 80);
+}
+
+#[test]
+fn test_interpret_match_at_guard_sees_all_bindings() {
+    let source = r#"
+fn ordered(p: (i64, i64)) -> i64 {
+    match p {
+        whole @ (a, b) if a < b && whole.1 == b => whole.0 * 100 + b,
+        (a, b) => a + b,
+    }
+}
+
+fn main() -> i64 {
+    ordered((3, 4)) + ordered((5, 1))
+}
+"#;
+    assert_result_int(source, 310);
+}
+
+#[test]
+fn test_interpret_match_enum() {
+    let source = r#"
+enum Shape {
+    Square(i64),
+    Rect(i64, i64),
+    Empty,
+}
+
+fn area(s: Shape) -> i64 {
+    match s {
+        Shape::Square(side) => side * side,
+        Shape::Rect(w, h) => w * h,
+        Shape::Empty => 0,
+    }
+}
+
+fn main() -> i64 {
+    area(Shape::Square(3)) + area(Shape::Rect(2, 5)) + area(Shape::Empty)
+}
+"#;
+    assert_result_int(source, 19);
+}
+
+#[test]
+fn test_interpret_match_option_with_guard() {
+    let source = r#"
+fn describe(o: Option<i64>) -> i64 {
+    match o {
+        Some(v) if v > 10 => v * 2,
+        Some(v) => v,
+        None => -1,
+    }
+}
+
+fn main() {
+    println(describe(Some(21)));
+    println(describe(Some(3)));
+    println(describe(None));
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["42", "3", "-1"]);
+}
+
+#[test]
+fn test_interpret_match_tuple_pattern() {
+    let source = r#"
+fn pick(t: (i64, i64)) -> i64 {
+    match t {
+        (1, y) => y,
+        (x, _) => x,
+    }
+}
+
+fn main() -> i64 {
+    pick((1, 7)) * 10 + pick((5, 7))
+}
+"#;
+    assert_result_int(source, 75);
+}
+
+#[test]
+fn test_interpret_match_struct_pattern() {
+    let source = r#"
+struct Point { x: i64, y: i64 }
+
+fn sum(p: Point) -> i64 {
+    match p {
+        Point { x, y: 0 } => x,
+        Point { x, y } => x + y,
+    }
+}
+
+fn main() -> i64 {
+    sum(Point { x: 4, y: 0 }) * 100 + sum(Point { x: 4, y: 5 })
+}
+"#;
+    assert_result_int(source, 409);
+}
+
+#[test]
+fn test_interpret_match_fallthrough_is_error() {
+    let source = r#"
+fn main() -> i64 {
+    let n = 3;
+    match n {
+        1 => 10,
+        2 => 20,
+    }
+}
+"#;
+    let err = interpret(source).unwrap_err();
+    assert!(
+        err.contains("non-exhaustive match: no arm matches `3`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_interpret_impl_method() {
+    assert_result_int(
+        r#"
+        struct Point { x: i64, y: i64 }
+
+        impl Point {
+            fn norm(self) -> i64 {
+                self.x * self.x + self.y * self.y
+            }
+
+            fn scaled(self, k: i64) -> Self {
+                Point { x: self.x * k, y: self.y * k }
+            }
+        }
+
+        fn main() -> i64 {
+            let p = Point { x: 3, y: 4 };
+            p.scaled(2).norm()
+        }
+    "#,
+        100,
+    );
+}
+
+#[test]
+fn test_interpret_self_constructor() {
+    assert_result_int(
+        r#"
+        struct Point { x: i64, y: i64 }
+
+        impl Point {
+            fn new(x: i64, y: i64) -> Self {
+                Self { x: x, y: y }
+            }
+
+            fn origin() -> Self {
+                Self::new(0, 0)
+            }
+
+            fn add(&self, other: &Self) -> Self {
+                Point::new(self.x + other.x, self.y + other.y)
+            }
+        }
+
+        fn main() -> i64 {
+            let p = Point::new(1, 2);
+            p.add(&Point::origin()).add(&p).y
+        }
+    "#,
+        4,
+    );
+}
+
+#[test]
+fn test_interpret_ref_self_method() {
+    assert_result_int(
+        r#"
+        struct Point { x: i64, y: i64 }
+
+        impl Point {
+            fn norm(&self) -> i64 {
+                self.x * self.x + self.y * self.y
+            }
+        }
+
+        fn main() -> i64 {
+            let p = Point { x: 3, y: 4 };
+            p.norm() + p.x
+        }
+    "#,
+        28,
+    );
+}
+
+#[test]
+fn test_interpret_trait_method_mutates_receiver() {
+    assert_result_int(
+        r#"
+        struct Counter { value: i64 }
+
+        trait Bump {
+            fn bump(&mut self, by: i64);
+            fn get(&self) -> i64;
+        }
+
+        impl Bump for Counter {
+            fn bump(&mut self, by: i64) {
+                self.value = self.value + by;
+            }
+
+            fn get(&self) -> i64 {
+                self.value
+            }
+        }
+
+        impl Counter {
+            fn reset(&mut self) {
+                self.value = 0;
+            }
+        }
+
+        fn main() -> i64 {
+            let mut c = Counter { value: 1 };
+            c.bump(4);
+            c.bump(5);
+            let before = c.get();
+            c.reset();
+            c.bump(3);
+            before * 100 + c.get()
+        }
+    "#,
+        1003,
+    );
+}
+
+#[test]
+fn test_interpret_mut_self_method_on_field() {
+    assert_result_int(
+        r#"
+        struct Counter { value: i64 }
+
+        impl Counter {
+            fn bump(&mut self) {
+                self.value = self.value + 1;
+            }
+        }
+
+        struct Pair { a: Counter, b: Counter }
+
+        impl Pair {
+            fn bump_b_twice(&mut self) {
+                self.b.bump();
+                self.b.bump();
+            }
+        }
+
+        fn main() -> i64 {
+            let mut p = Pair { a: Counter { value: 0 }, b: Counter { value: 10 } };
+            p.bump_b_twice();
+            p.a.bump();
+            p.a.value * 100 + p.b.value
+        }
+    "#,
+        112,
+    );
+}
+
+#[test]
+fn test_interpret_mutation_through_mut_ref() {
+    assert_result_int(
+        r#"
+        fn main() -> i64 {
+            let mut x = 1;
+            let r = &mut x;
+            *r = 5;
+            let seen = *r;
+            x = x + 1;
+            seen * 10 + *r
+        }
+    "#,
+        56,
+    );
+}
+
+#[test]
+fn test_interpret_mut_ref_argument_mutates_caller() {
+    assert_result_int(
+        r#"
+        struct Point { x: i64, y: i64 }
+
+        fn bump(n: &mut i64) {
+            *n = *n + 10;
+        }
+
+        fn move_right(p: &mut Point) {
+            p.x = p.x + 1;
+        }
+
+        fn main() -> i64 {
+            let mut n = 1;
+            bump(&mut n);
+            bump(&mut n);
+            let mut p = Point { x: 0, y: 0 };
+            move_right(&mut p);
+            n * 10 + p.x
+        }
+    "#,
+        211,
+    );
+}
+
+#[test]
+fn test_interpret_transmute_float_bits() {
+    assert_result_int(
+        "fn main() -> u32 { transmute::<f32, u32>(1.0) }",
+        0x3f80_0000,
+    );
+}
+
+#[test]
+fn test_interpret_transmute_sign_bits() {
+    assert_result_int(
+        "fn main() -> u32 { let x: i32 = 0 - 1; transmute::<i32, u32>(x) }",
+        0xffff_ffff,
+    );
+}
+
+#[test]
+fn test_interpret_cast_numeric() {
+    // 7 / 2 in floating point, truncated back: 3.5 * 2 = 7
+    assert_result_int(
+        "fn main() -> i64 { let x: i64 = 7; let half = x as f64 / 2.0; (half * 2.0) as i64 }",
+        7,
+    );
+}
+
+#[test]
+fn test_interpret_cast_char_and_wrap() {
+    assert_result_int("fn main() -> u32 { 'a' as u32 }", 97);
+    assert_result_int("fn main() -> u8 { 300 as u8 }", 44);
+}
+
+#[test]
+fn test_interpret_vec_push_and_sum() {
+    assert_result_int(
+        r#"
+fn main() -> i64 {
+    let mut squares = Vec::new()
+    let mut i = 0
+    while i < 10 {
+        squares.push(i * i)
+        i = i + 1
+    }
+    let mut sum = 0
+    let mut j = 0
+    while j < squares.len() {
+        sum = sum + squares[j]
+        j = j + 1
+    }
+    sum
+}
+"#,
+        285,
+    );
+}
+
+#[test]
+fn test_interpret_vec_literal_set_and_pop() {
+    let source = r#"
+fn main() {
+    let v = vec![1, 2, 3]
+    v[0] = 10
+    println(v.pop());
+    println(v);
+    let empty = vec![true]
+    empty.pop();
+    println(empty.pop())
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["Some(3)", "[10, 2]", "None"]);
+}
+
+#[test]
+fn test_interpret_vec_index_out_of_bounds() {
+    let err = interpret("fn main() -> i64 { let v = vec![1, 2, 3]; v[3] }").unwrap_err();
+    assert!(
+        err.contains("index out of bounds: the len is 3 but the index is 3"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_interpret_array_index_out_of_bounds() {
+    let err = interpret("fn main() -> i64 { let a = [1, 2]; a[2] }").unwrap_err();
+    assert!(
+        err.contains("index out of bounds: the len is 2 but the index is 2"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_interpret_map_intrinsic_without_map_errors() {
+    let mut interpreter = Interpreter::new();
+    let err = interpreter
+        .call_builtin("map_get", vec![Value::Int(1), Value::Int(2)])
+        .unwrap_err();
+    assert!(
+        matches!(&err, ControlFlow::Error(message) if message == "`map_get` expects a map and a key"),
+        "{:?}",
+        err
+    );
+}
+
+fn interpret_with(source: &str, mode: OverflowMode) -> Result<Value, String> {
+    let tokens = demetrios::lexer::lex(source).map_err(|e| format!("Lex error: {}", e))?;
+    let ast =
+        demetrios::parser::parse(&tokens, source).map_err(|e| format!("Parse error: {}", e))?;
+    let hir = demetrios::check::check(&ast).map_err(|e| format!("Type error: {}", e))?;
+    let mut interpreter = Interpreter::new().with_overflow_mode(mode);
+    interpreter
+        .interpret(&hir)
+        .map_err(|e| format!("Runtime error: {}", e))
+}
+
+#[test]
+fn test_interpret_checked_overflow_errors() {
+    let err =
+        interpret_with("fn main() -> i64 { i64::MAX + 1 }", OverflowMode::Checked).unwrap_err();
+    assert!(
+        err.contains("integer overflow: `9223372036854775807 + 1`"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_interpret_checked_overflow_in_callee() {
+    let source = r#"
+        fn square(x: i64) -> i64 { x * x }
+        fn main() -> i64 { square(i64::MAX) }
+    "#;
+    let err = interpret_with(source, OverflowMode::Checked).unwrap_err();
+    assert!(
+        err.contains("integer overflow"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_interpret_wrapping_overflow() {
+    let result = interpret_with("fn main() -> i64 { i64::MAX + 1 }", OverflowMode::Wrapping);
+    assert!(matches!(result, Ok(Value::Int(i64::MIN))), "{:?}", result);
+    let result = interpret_with("fn main() -> i64 { i64::MIN - 1 }", OverflowMode::Wrapping);
+    assert!(matches!(result, Ok(Value::Int(i64::MAX))), "{:?}", result);
+}
+
+#[test]
+fn test_interpret_i64_min_literal() {
+    let source = r#"
+fn main() -> i64 {
+    let n = -9223372036854775808;
+    n
+}
+"#;
+    assert_result_int(source, i64::MIN);
+}
+
+#[test]
+fn test_interpret_overflow_uses_declared_width() {
+    let add = r#"
+        fn main() -> i32 {
+            let a: i32 = 2147483647;
+            a + 1
+        }
+    "#;
+    let err = interpret_with(add, OverflowMode::Checked).unwrap_err();
+    assert!(
+        err.contains("integer overflow: `2147483647 + 1` does not fit in i32"),
+        "unexpected error: {}",
+        err
+    );
+    let result = interpret_with(add, OverflowMode::Wrapping);
+    assert!(
+        matches!(result, Ok(Value::Int(n)) if n == i64::from(i32::MIN)),
+        "{:?}",
+        result
+    );
+
+    let mul = r#"
+        fn main() -> u8 {
+            let b: u8 = 200;
+            b * 2
+        }
+    "#;
+    assert!(interpret_with(mul, OverflowMode::Checked).is_err());
+    let result = interpret_with(mul, OverflowMode::Wrapping);
+    assert!(matches!(result, Ok(Value::Int(144))), "{:?}", result);
+}
+
+#[test]
+fn test_interpret_negation_and_division_overflow() {
+    let neg = r#"
+        fn neg(x: i64) -> i64 { -x }
+        fn main() -> i64 { neg(i64::MIN) }
+    "#;
+    let div = r#"
+        fn div(a: i64, b: i64) -> i64 { a / b }
+        fn main() -> i64 { div(i64::MIN, -1) }
+    "#;
+    for (source, expected) in [
+        (neg, "`-(-9223372036854775808)`"),
+        (div, "`-9223372036854775808 / -1`"),
+    ] {
+        let err = interpret_with(source, OverflowMode::Checked).unwrap_err();
+        assert!(
+            err.contains(&format!("integer overflow: {}", expected)),
+            "unexpected error: {}",
+            err
+        );
+        let result = interpret_with(source, OverflowMode::Wrapping);
+        assert!(matches!(result, Ok(Value::Int(i64::MIN))), "{:?}", result);
+    }
+}
+
+#[test]
+fn test_interpret_defaults_to_checked_overflow() {
+    assert!(interpret("fn main() -> i64 { i64::MAX * 2 }").is_err());
+}
+
+#[test]
+fn test_interpret_nested_await() {
+    let source = r#"
+        async fn leaf(x: i64) -> i64 { x * 2 }
+
+        async fn middle(x: i64) -> i64 {
+            let a = await leaf(x)
+            let b = await leaf(a + 1)
+            a + b
+        }
+
+        async fn main() -> i64 {
+            await middle(5) + 1
+        }
+    "#;
+    assert!(matches!(interpret(source), Ok(Value::Int(33))));
+}
+
+#[test]
+fn test_interpret_handler_collects_log() {
+    let source = r#"
+        effect Logger {
+            fn log(msg: String);
+        }
+
+        handler CollectLog for Logger {
+            log(msg) => {
+                let pair = resume(());
+                (pair.0, msg + pair.1)
+            }
+            return x => (x, "")
+        }
+
+        fn work(n: i64) -> i64 with Logger {
+            perform Logger.log("start;")
+            let doubled = n * 2
+            perform Logger.log("doubled;")
+            doubled + 1
+        }
+
+        fn main() -> (i64, String) {
+            handle work(20) with CollectLog
+        }
+    "#;
+    let result = interpret(source).unwrap();
+    assert_eq!(
+        result,
+        Value::Tuple(vec![Value::Int(41), Value::String("start;doubled;".into())])
+    );
+}
+
+#[test]
+fn test_interpret_handler_without_resume_aborts() {
+    let source = r#"
+        effect Fail {
+            fn fail(code: i64) -> i64;
+        }
+
+        handler Recover for Fail {
+            fail(code) => code * 100
+        }
+
+        fn risky(n: i64) -> i64 with Fail {
+            if n > 3 {
+                perform Fail.fail(n)
+            } else {
+                n
+            }
+        }
+
+        fn main() -> i64 {
+            let a = handle risky(2) with Recover
+            let b = handle risky(5) with Recover
+            a + b
+        }
+    "#;
+    assert_result_int(source, 502);
+}
+
+#[test]
+fn test_interpret_nested_handlers_forward_operations() {
+    let source = r#"
+        effect State {
+            fn get() -> i64;
+        }
+
+        effect Logger {
+            fn log(msg: String);
+        }
+
+        handler Seven for State {
+            get() => resume(7)
+        }
+
+        handler CollectLog for Logger {
+            log(msg) => {
+                let pair = resume(());
+                (pair.0, msg + pair.1)
+            }
+            return x => (x, "")
+        }
+
+        fn work() -> i64 with State, Logger {
+            perform Logger.log("start;")
+            let n = perform State.get()
+            perform Logger.log("got;")
+            n * 6
+        }
+
+        fn main() -> (i64, String) {
+            handle (handle work() with Seven) with CollectLog
+        }
+    "#;
+    let result = interpret(source).unwrap();
+    assert_eq!(
+        result,
+        Value::Tuple(vec![Value::Int(42), Value::String("start;got;".into())])
+    );
+}
+
+#[test]
+fn test_interpret_handler_case_reperforms_to_outer_handler() {
+    let source = r#"
+        effect State {
+            fn get() -> i64;
+        }
+
+        handler Seven for State {
+            get() => {
+                let value = 7
+                resume(value)
+            }
+        }
+
+        handler Doubled for State {
+            get() => {
+                let outer = perform State.get()
+                resume(outer * 2)
+            }
+        }
+
+        fn work() -> i64 with State {
+            let n = perform State.get()
+            n * 3
+        }
+
+        fn main() -> i64 {
+            handle (handle work() with Doubled) with Seven
+        }
+    "#;
+    assert_result_int(source, 42);
+}
+
+#[test]
+fn test_interpret_handler_runs_code_before_resume_once() {
+    let source = r#"
+        effect Ask {
+            fn ask() -> i64;
+        }
+
+        handler Answer for Ask {
+            ask() => {
+                perform IO.print("asked;")
+                let n = resume(10)
+                perform IO.print("done;")
+                n + 1
+            }
+        }
+
+        fn main() -> i64 with IO {
+            let n = handle (perform Ask.ask()) * 4 with Answer
+            perform IO.print("result;")
+            n
+        }
+    "#;
+    assert_eq!(interpret_output(source), vec!["asked;", "done;", "result;"]);
+    assert_result_int(source, 41);
+}
+
+#[test]
+fn test_interpret_generic_effect_handler() {
+    let source = r#"
+        effect Yield<T> {
+            fn yield(value: T);
+        }
+
+        handler Sum for Yield<i64> {
+            yield(value) => value + resume(())
+            return x => 0
+        }
+
+        fn produce() with Yield<i64> {
+            perform Yield.yield(1)
+            perform Yield.yield(2)
+            perform Yield.yield(39)
+        }
+
+        fn main() -> i64 {
+            handle produce() with Sum
+        }
+    "#;
+    assert_result_int(source, 42);
+}
+
+#[test]
+fn test_interpret_trait_dispatch_through_bound() {
+    let source = r#"
+        trait Area {
+            fn area(&self) -> i64;
+            fn double(&self) -> i64 { self.area() * 2 }
+        }
+
+        struct Square { side: i64 }
+        struct Rect { w: i64, h: i64 }
+
+        impl Area for Square {
+            fn area(&self) -> i64 { self.side * self.side }
+        }
+
+        impl Area for Rect {
+            fn area(&self) -> i64 { self.w * self.h }
+            fn double(&self) -> i64 { 0 }
+        }
+
+        fn total<T: Area>(shape: T) -> i64 {
+            shape.double() + 1
+        }
+
+        fn main() -> i64 {
+            total(Square { side: 3 }) * 100 + total(Rect { w: 2, h: 5 })
+        }
+    "#;
+    assert_result_int(source, 1901);
+}
+
+#[test]
+fn test_interpret_generic_identity_and_swap() {
+    let source = r#"
+        fn id<T>(x: T) -> T { x }
+        fn swap<A, B>(p: (A, B)) -> (B, A) { (p.1, p.0) }
+
+        fn main() -> i64 {
+            let s: String = id("x");
+            let p = swap((s, id(4)));
+            p.0 * 10 + id(2)
+        }
+    "#;
+    assert_result_int(source, 42);
+}
+
+#[test]
+fn test_interpret_builtin_io_print() {
+    let source = r#"
+        fn greet(name: String) with IO {
+            perform IO.print("hello, ")
+            perform IO.print(name)
+        }
+
+        fn main() with IO {
+            greet("world")
+            perform IO.print("!")
+        }
+    "#;
+    assert_eq!(interpret_output(source), vec!["hello, ", "world", "!"]);
+}
+
+#[test]
+fn test_interpret_builtin_io_handler_overrides_default() {
+    let source = r#"
+        handler Silence for IO {
+            print(s) => resume(())
+        }
+
+        fn main() with IO {
+            perform IO.print("shown")
+            handle perform IO.print("hidden") with Silence
+        }
+    "#;
+    assert_eq!(interpret_output(source), vec!["shown"]);
+}
+
+#[test]
+fn test_run_builtin_io_reads_stdin() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let source = r#"
+        fn main() with IO {
+            let name = perform IO.read_line()
+            perform IO.print("hi " + name)
+        }
+    "#;
+    let path = std::env::temp_dir().join(format!("io_read_line-{}.d", std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dc"))
+        .arg("run")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"ada\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("hi ada"));
+}
+
+/// Run `source` with `dc run`, returning its exit code and stdout
+fn run_cli(name: &str, source: &str) -> (Option<i32>, String) {
+    run_cli_with(name, &[], source)
+}
+
+/// Like `run_cli`, passing `flags` to `dc run` before the file
+fn run_cli_with(name: &str, flags: &[&str], source: &str) -> (Option<i32>, String) {
+    use std::process::Command;
+
+    let path = std::env::temp_dir().join(format!("{}-{}.d", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dc"))
+        .arg("run")
+        .args(flags)
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&path);
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    )
+}
+
+#[test]
+fn test_run_cfg_selects_gated_function() {
+    let source = r#"
+        #[cfg(feature = "gpu")]
+        fn backend() -> i64 { 2 }
+        #[cfg(not(feature = "gpu"))]
+        fn backend() -> i64 { 1 }
+        fn main() -> i64 { backend() }
+    "#;
+    let (code, _) = run_cli_with("cfg_off", &[], source);
+    assert_eq!(code, Some(1));
+    let (code, _) = run_cli_with("cfg_on", &["--cfg", r#"feature="gpu""#], source);
+    assert_eq!(code, Some(2));
+}
+
+#[test]
+fn test_run_integer_result_is_exit_code() {
+    let (code, stdout) = run_cli("exit_code", "fn main() -> i64 { 2 }");
+    assert_eq!(code, Some(2));
+    assert!(stdout.is_empty(), "{}", stdout);
+
+    let (code, _) = run_cli("exit_zero", "fn main() -> i64 { 0 }");
+    assert_eq!(code, Some(0));
+}
+
+#[test]
+fn test_run_err_result_exits_with_one() {
+    let (code, _) = run_cli(
+        "exit_err",
+        r#"fn main() -> Result<(), String> { Err("bad input") }"#,
+    );
+    assert_eq!(code, Some(1));
+}
+
+#[test]
+fn test_builtin_io_operation_types_are_checked() {
+    let source = r#"
+        fn main() with IO {
+            perform IO.print(42)
+            perform IO.flush()
+        }
+    "#;
+    let err = interpret(source).unwrap_err();
+    assert!(
+        err.contains("Unknown effect operation `IO.flush`"),
+        "{}",
+        err
+    );
+    assert!(err.contains("expected String, found i64"), "{}", err);
+}
+
+#[test]
+fn test_interpret_try_propagates_err() {
+    let source = r#"
+fn half(n: i64) -> Result<i64, String> {
+    if n % 2 == 0 { Ok(n / 2) } else { Err("odd") }
+}
+
+fn quarter(n: i64) -> Result<i64, String> {
+    let h = half(n)?
+    println(h)
+    Ok(half(h)?)
+}
+
+fn main() {
+    println(quarter(8));
+    println(quarter(6));
+    println(quarter(5))
+}
+"#;
+    assert_eq!(
+        interpret_output(source),
+        vec!["4", "Ok(2)", "3", "Err(odd)", "Err(odd)"]
+    );
+}
+
+#[test]
+fn test_interpret_try_on_option() {
+    let source = r#"
+fn succ(s: String) -> Option<i64> {
+    Some(parse_int(s)? + 1)
+}
+
+fn main() {
+    match succ("41") {
+        Some(n) => println(n),
+        None => println("none"),
+    }
+    println(succ("x"))
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["42", "None"]);
+}
+
+#[test]
+fn test_interpret_for_range() {
+    let source = r#"
+fn main() -> i64 {
+    let mut total = 0;
+    for i in 0..4 {
+        total = total + i;
+    }
+    for i in 1..=3 {
+        if i == 2 {
+            continue;
+        }
+        total = total + i * 100;
+    }
+    for _ in 5..5 {
+        total = total + 1000;
+    }
+    total
+}
+"#;
+    assert_result_int(source, 406);
+}
+
+#[test]
+fn test_interpret_for_over_array_and_vec() {
+    let source = r#"
+fn main() -> i64 {
+    let arr = [1, 2, 3, 4];
+    let mut total = 0;
+    for i in 0..arr.len() {
+        for x in arr {
+            total = total + arr[i] * x;
+        }
+    }
+    let mut v = Vec::new();
+    v.push(100);
+    v.push(200);
+    for x in v {
+        if x > 150 {
+            break;
+        }
+        total = total + x;
+    }
+    total
+}
+"#;
+    assert_result_int(source, 200);
+}
+
+#[test]
+fn test_interpret_for_over_array_of_structs() {
+    let source = r#"
+struct Item { price: i64, count: i64 }
+fn main() -> i64 {
+    let items = [Item { price: 3, count: 2 }, Item { price: 5, count: 0 }, Item { price: 7, count: 1 }];
+    let mut total = 0;
+    for item in items {
+        if item.count == 0 {
+            continue;
+        }
+        total = total + item.price * item.count;
+    }
+    let empty: [Item; 0] = [];
+    for item in empty {
+        total = total + 1000;
+    }
+    total
+}
+"#;
+    assert_result_int(source, 13);
+}
+
+// ==================== Sampling Tests ====================
+
+/// Interpret `source` with the random number generator seeded by `seed`,
+/// returning the captured output lines
+fn interpret_seeded(source: &str, seed: u64) -> Vec<String> {
+    let tokens = demetrios::lexer::lex(source).expect("lex error");
+    let ast = demetrios::parser::parse(&tokens, source).expect("parse error");
+    let hir = demetrios::check::check(&ast).expect("type error");
+    let mut interpreter = Interpreter::new().with_seed(seed);
+    interpreter.interpret(&hir).expect("runtime error");
+    interpreter.get_output().to_vec()
+}
+
+const DRAWS: &str = r#"
+fn main() {
+    let d = Normal(0.0, 0.3);
+    println("{} {} {}", sample(d), sample(d), sample(Bernoulli(0.5)));
+}
+"#;
+
+#[test]
+fn test_interpret_sample_is_deterministic_for_a_seed() {
+    let first = interpret_seeded(DRAWS, 42);
+    assert_eq!(first, interpret_seeded(DRAWS, 42));
+    assert_ne!(first, interpret_seeded(DRAWS, 43));
+}
+
+#[test]
+fn test_interpret_uniform_samples_in_range() {
+    let source = r#"
+fn main() -> i64 {
+    let d = Uniform(0.0, 1.0);
+    let mut inside = 0;
+    for i in 0..1000 {
+        let x = sample(d);
+        if x >= 0.0 && x < 1.0 {
+            inside = inside + 1;
+        }
+    }
+    inside
+}
+"#;
+    assert_result_int(source, 1000);
+}
+
+#[test]
+fn test_interpret_sample_of_non_distribution_errors() {
+    let err = interpret("fn main() -> i64 { sample(3) }").unwrap_err();
+    assert!(
+        err.contains("`sample` expects a distribution, found i64"),
+        "{}",
+        err
+    );
+}
+
+// ==================== String Tests ====================
+
+#[test]
+fn test_interpret_string_concatenation() {
+    let source = r#"
+fn main() {
+    let greeting = "Hello, " + "wörld";
+    println(greeting + "!");
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["Hello, wörld!"]);
+}
+
+#[test]
+fn test_interpret_string_len_counts_chars() {
+    let source = r#"
+fn main() -> i64 {
+    let s = "naïve ☕";
+    s.len() * 10 + "abc".len()
+}
+"#;
+    assert_result_int(source, 73);
+}
+
+#[test]
+fn test_interpret_string_split_and_case() {
+    let source = r#"
+fn main() {
+    let parts = "  Alpha,beta,Gamma ".trim().split(",");
+    println("{}", parts.len());
+    for part in parts {
+        println(part.to_upper() + "/" + part.to_lower());
+    }
+}
+"#;
+    assert_eq!(
+        interpret_output(source),
+        vec!["3", "ALPHA/alpha", "BETA/beta", "GAMMA/gamma"]
+    );
+}
+
+#[test]
+fn test_interpret_string_index_and_chars() {
+    let source = r#"
+fn main() {
+    let s = "día";
+    println("{} {}", s[1], s.chars().len());
+}
+"#;
+    assert_eq!(interpret_output(source), vec!["í 3"]);
+
+    let err = interpret("fn main() -> i64 { let s = \"ab\"; s[5]; 0 }").unwrap_err();
+    assert!(err.contains("index out of bounds"), "{}", err);
+}
+
+#[test]
+fn test_interpret_dyn_trait_dispatch() {
+    let source = r#"
+trait Shape { fn area(self) -> i64; }
+struct Circle { r: i64 }
+struct Square { s: i64 }
+impl Shape for Circle { fn area(self) -> i64 { 3 * self.r * self.r } }
+impl Shape for Square { fn area(self) -> i64 { self.s * self.s } }
+fn total(a: &dyn Shape, b: &dyn Shape) -> i64 { a.area() * 100 + b.area() }
+fn main() -> i64 {
+    let c = Circle { r: 2 };
+    let s = Square { s: 3 };
+    let a: &dyn Shape = &c;
+    let b: &dyn Shape = &s;
+    total(a, b)
+}
+"#;
+    assert_result_int(source, 1209);
+}
@@ -80,6 +80,18 @@ fn test_lex_operators() {
     assert_eq!(tokens[14].kind, TokenKind::FatArrow);
 }
 
+#[test]
+fn test_lex_lifetime_and_char() {
+    let tokens = lex("&'a mut 'b' 'static").unwrap();
+    let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+    assert_eq!(
+        kinds[..5],
+        [TokenKind::Amp, TokenKind::Lifetime, TokenKind::Mut, TokenKind::CharLit, TokenKind::Lifetime]
+    );
+    assert_eq!(tokens[1].text, "'a");
+    assert_eq!(tokens[4].text, "'static");
+}
+
 #[test]
 fn test_lex_literals() {
     let source = r#"42 3.14 "hello" 'c' true false"#;
@@ -159,3 +171,58 @@ fn test_lex_effect_signature() {
     let with_pos = tokens.iter().position(|t| t.kind == TokenKind::With);
     assert!(with_pos.is_some());
 }
+
+#[test]
+fn test_lex_raw_strings() {
+    let tokens = lex(r#"r"C:\path""#).unwrap();
+    assert_eq!(tokens[0].kind, TokenKind::RawStringLit);
+    assert_eq!(tokens[1].kind, TokenKind::Eof);
+
+    // Hash counts must match, so a lone `"#` does not terminate `r##"`
+    let source = r###"r##"a "# b"## x"###;
+    let tokens = lex(source).unwrap();
+    assert_eq!(tokens[0].kind, TokenKind::RawStringLit);
+    assert_eq!(tokens[0].text, r###"r##"a "# b"##"###);
+    assert_eq!(tokens[1].kind, TokenKind::Ident);
+}
+
+#[test]
+fn test_lex_unterminated_raw_string() {
+    assert!(lex(r##"r#"never closed""##).is_err());
+}
+
+#[test]
+fn test_emit_tokens_lists_kinds_and_spans() {
+    let path = std::env::temp_dir().join(format!("emit_tokens-{}.d", std::process::id()));
+    std::fs::write(&path, "let x = 1").unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_dc"))
+        .args(["compile", "--emit", "tokens"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let tokens: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let listed: Vec<_> = tokens
+        .iter()
+        .map(|t| {
+            (
+                t["kind"].as_str().unwrap(),
+                t["text"].as_str().unwrap(),
+                t["span"]["start"].as_u64().unwrap(),
+                t["span"]["end"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            ("Let", "let", 0, 3),
+            ("Ident", "x", 4, 5),
+            ("Eq", "=", 6, 7),
+            ("IntLit", "1", 8, 9),
+            ("Eof", "", 9, 9),
+        ]
+    );
+}
@@ -6,9 +6,11 @@
 #![cfg(feature = "llvm")]
 
 use demetrios::codegen::llvm::{
-    codegen::{LLVMCodegen, OptLevel},
+    codegen::{FastMathFlags, LLVMCodegen, OptLevel},
     passes,
-    target::{TargetConfig, create_native_target_machine, initialize_native_target},
+    target::{
+        TargetConfig, configure_module, create_native_target_machine, initialize_native_target,
+    },
     types::TypeConverter,
 };
 use demetrios::hlir::{HlirModule, HlirType};
@@ -19,7 +21,7 @@ fn compile_to_hlir(source: &str) -> Result<HlirModule, String> {
     let tokens = demetrios::lexer::lex(source).map_err(|e| format!("{:?}", e))?;
     let ast = demetrios::parser::parse(&tokens, source).map_err(|e| format!("{:?}", e))?;
     let hir = demetrios::check::check(&ast).map_err(|e| format!("{:?}", e))?;
-    Ok(demetrios::hlir::lower(&hir))
+    demetrios::hlir::lower(&hir).map_err(|e| format!("{:?}", e))
 }
 
 #[test]
@@ -190,6 +192,59 @@ fn test_opt_level_parsing() {
     assert_eq!(OptLevel::from_str("invalid"), None);
 }
 
+#[test]
+fn test_fast_math_parsing() {
+    assert_eq!(FastMathFlags::from_str("fast"), Some(FastMathFlags::FAST));
+    assert_eq!(FastMathFlags::from_str("none"), Some(FastMathFlags::NONE));
+    assert_eq!(
+        FastMathFlags::from_str("nnan, contract"),
+        Some(FastMathFlags {
+            nnan: true,
+            contract: true,
+            ..FastMathFlags::NONE
+        })
+    );
+    assert_eq!(FastMathFlags::from_str("nnan,fastest"), None);
+}
+
+/// IR for a float kernel compiled at `opt`, optionally overriding its
+/// fast-math flags
+fn float_kernel_ir(opt: OptLevel, fast_math: Option<FastMathFlags>) -> String {
+    let hlir = compile_to_hlir("fn axpy(a: f64, x: f64, y: f64) -> f64 { a * x + y }")
+        .expect("Failed to compile");
+
+    initialize_native_target();
+    let context = Context::create();
+    let mut codegen = LLVMCodegen::new(&context, "fast_math", opt, false);
+    if let Some(flags) = fast_math {
+        codegen.set_fast_math(flags);
+    }
+    codegen.compile(&hlir);
+    assert!(codegen.verify().is_ok());
+    codegen.print_ir()
+}
+
+#[test]
+fn test_fast_math_at_o3_only() {
+    let ir = float_kernel_ir(OptLevel::O3, None);
+    assert!(ir.contains(r#""unsafe-fp-math"="true""#), "{}", ir);
+    assert!(ir.contains(r#""no-nans-fp-math"="true""#), "{}", ir);
+    assert!(ir.contains(r#""less-precise-fpmad"="true""#), "{}", ir);
+
+    let ir = float_kernel_ir(OptLevel::O2, None);
+    assert!(!ir.contains("fp-math"), "{}", ir);
+    assert!(!ir.contains("fpmad"), "{}", ir);
+
+    // An explicit subset applies at any level
+    let contract = FastMathFlags {
+        contract: true,
+        ..FastMathFlags::NONE
+    };
+    let ir = float_kernel_ir(OptLevel::O2, Some(contract));
+    assert!(ir.contains(r#""less-precise-fpmad"="true""#), "{}", ir);
+    assert!(!ir.contains("unsafe-fp-math"), "{}", ir);
+}
+
 #[test]
 fn test_module_verification() {
     initialize_native_target();
@@ -221,6 +276,28 @@ fn test_ir_output() {
     assert!(ir.contains("ir_test"));
 }
 
+#[test]
+fn test_ir_records_native_target() {
+    initialize_native_target();
+    let context = Context::create();
+
+    let hlir = compile_to_hlir("fn main() -> i64 { 42 }").unwrap();
+    let mut codegen = LLVMCodegen::new(&context, "target_test", OptLevel::O0, false);
+    let module = codegen.compile(&hlir);
+    let target = create_native_target_machine(OptLevel::O0).unwrap();
+    configure_module(module, &target);
+
+    let ir = codegen.print_ir();
+    assert!(ir.contains("target datalayout = \""), "{}", ir);
+    let triple = target.get_triple();
+    let triple = triple.as_str().to_str().unwrap();
+    assert!(
+        ir.contains(&format!("target triple = \"{}\"", triple)),
+        "{}",
+        ir
+    );
+}
+
 #[test]
 fn test_type_sizes() {
     let context = Context::create();
@@ -315,6 +392,71 @@ fn test_control_flow() {
     assert!(ir.contains("br") || ir.contains("ret") || ir.contains("icmp"));
 }
 
+#[test]
+fn test_likely_branch_weights() {
+    let source = r#"
+        fn pick(c: bool) -> i64 {
+            if likely(c) {
+                1
+            } else {
+                2
+            }
+        }
+    "#;
+
+    let hlir = compile_to_hlir(source).expect("Failed to compile");
+
+    initialize_native_target();
+    let context = Context::create();
+    let mut codegen = LLVMCodegen::new(&context, "branch_weights", OptLevel::O0, false);
+
+    codegen.compile(&hlir);
+    assert!(codegen.verify().is_ok());
+
+    let ir = codegen.print_ir();
+    // The then-branch carries the heavy weight
+    assert!(ir.contains("!prof"));
+    assert!(ir.contains(r#"!{!"branch_weights", i32 2000, i32 1}"#));
+}
+
+#[test]
+fn test_guard_steps_instrument_loops() {
+    let source = r#"
+        fn spin() -> i64 {
+            let mut i = 0;
+            loop {
+                i = i + 1;
+            }
+            i
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(source).unwrap();
+    let ast = demetrios::parser::parse(&tokens, source).unwrap();
+    let hir = demetrios::check::check(&ast).unwrap();
+
+    initialize_native_target();
+    let context = Context::create();
+
+    let options = demetrios::hlir::LowerOptions {
+        guard_steps: Some(1000),
+    };
+    let hlir = demetrios::hlir::lower_with(&hir, &options).unwrap();
+    let mut codegen = LLVMCodegen::new(&context, "guarded", OptLevel::O0, false);
+    codegen.compile(&hlir);
+    assert!(codegen.verify().is_ok());
+
+    let ir = codegen.print_ir();
+    assert!(ir.contains("store i64 1000"));
+    assert!(ir.contains("icmp slt i64"));
+    assert!(ir.contains("call void @llvm.trap()"));
+
+    // Without the flag, loops are left alone
+    let hlir = demetrios::hlir::lower(&hir).unwrap();
+    let mut codegen = LLVMCodegen::new(&context, "unguarded", OptLevel::O0, false);
+    codegen.compile(&hlir);
+    assert!(!codegen.print_ir().contains("llvm.trap"));
+}
+
 // Test for linker (without actually linking)
 mod linker_tests {
     use demetrios::codegen::llvm::linker::Linker;
@@ -379,3 +521,67 @@ mod target_tests {
         assert!(!host.is_empty());
     }
 }
+
+#[test]
+fn test_struct_construction_and_field_access() {
+    let source = r#"
+        struct Point { x: i64, y: i64 }
+
+        fn make(x: i64, y: i64) -> Point {
+            Point { y: y, x: x }
+        }
+
+        fn sum() -> i64 {
+            let p = make(1, 2)
+            p.x + p.y
+        }
+    "#;
+
+    let hlir = compile_to_hlir(source).expect("Failed to compile");
+
+    initialize_native_target();
+    let context = Context::create();
+    let mut codegen = LLVMCodegen::new(&context, "structs", OptLevel::O0, false);
+
+    codegen.compile(&hlir);
+    assert!(codegen.verify().is_ok(), "{}", codegen.print_ir());
+
+    let ir = codegen.print_ir();
+    assert!(ir.contains("%Point = type { i64, i64 }"));
+    assert!(ir.contains("define %Point @make(i64 %x, i64 %y)"));
+    assert!(ir.contains("insertvalue %Point"));
+    assert!(ir.contains("extractvalue %Point"));
+}
+
+#[test]
+fn test_nested_structs_passed_by_pointer() {
+    let source = r#"
+        struct Point { x: i64, y: i64 }
+        struct Line { a: Point, b: Point }
+
+        fn width(l: Line) -> i64 {
+            l.b.x - l.a.x
+        }
+
+        fn main() -> i64 {
+            let mut l = Line { a: Point { x: 1, y: 2 }, b: Point { x: 5, y: 6 } }
+            l.a.x = 0
+            width(l)
+        }
+    "#;
+
+    let hlir = compile_to_hlir(source).expect("Failed to compile");
+
+    initialize_native_target();
+    let context = Context::create();
+    let mut codegen = LLVMCodegen::new(&context, "nested", OptLevel::O0, false);
+
+    codegen.compile(&hlir);
+    assert!(codegen.verify().is_ok(), "{}", codegen.print_ir());
+
+    // 32-byte structs go by pointer; the field store indexes into %Line
+    let ir = codegen.print_ir();
+    assert!(ir.contains("%Line = type { %Point, %Point }"));
+    assert!(ir.contains("define i64 @width(ptr %l)"));
+    assert!(ir.contains("getelementptr inbounds %Line"));
+}
@@ -1,6 +1,6 @@
 //! Ownership and borrow checking tests
 
-use demetrios::diagnostics::SourceFile;
+use demetrios::diagnostics::{CompileError, SourceFile};
 use demetrios::ownership::OwnershipChecker;
 use demetrios::parser;
 use demetrios::resolve;
@@ -124,3 +124,210 @@ fn test_struct_construction() {
     );
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_two_live_mutable_borrows() {
+    let result = check_ownership(
+        r#"
+        fn main() -> i32 {
+            let mut x: i32 = 1
+            let a: &mut i32 = &mut x;
+            let b: &mut i32 = &mut x;
+            *a = 2
+            return 0
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("DoubleMutBorrow"), "{}", err);
+}
+
+#[test]
+fn test_mutable_borrow_ends_at_last_use() {
+    let result = check_ownership(
+        r#"
+        fn main() -> i32 {
+            let mut x: i32 = 1
+            let a: &mut i32 = &mut x;
+            *a = 2
+            let b: &mut i32 = &mut x;
+            *b = 3
+            return x
+        }
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_shared_borrow_while_mutably_borrowed() {
+    let result = check_ownership(
+        r#"
+        fn main() -> i32 {
+            let mut x: i32 = 1
+            let m: &mut i32 = &mut x;
+            let r: &i32 = &x;
+            let y: i32 = *r;
+            *m = y
+            return 0
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("AlreadyMutablyBorrowed"), "{}", err);
+}
+
+#[test]
+fn test_temporary_mutable_borrows_in_one_call() {
+    let result = check_ownership(
+        r#"
+        fn swap(a: &mut i32, b: &mut i32) -> i32 {
+            return 0
+        }
+
+        fn main() -> i32 {
+            let mut x: i32 = 1
+            swap(&mut x, &mut x)
+            return 0
+        }
+    "#,
+    );
+    assert!(result.unwrap_err().contains("DoubleMutBorrow"));
+
+    let sequential = check_ownership(
+        r#"
+        fn bump(a: &mut i32) -> i32 {
+            return 0
+        }
+
+        fn main() -> i32 {
+            let mut x: i32 = 1
+            bump(&mut x)
+            bump(&mut x)
+            return 0
+        }
+    "#,
+    );
+    assert!(sequential.is_ok(), "{:?}", sequential);
+}
+
+#[test]
+fn test_move_while_borrowed() {
+    let result = check_ownership(
+        r#"
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        fn main() -> i32 {
+            let p: Point = Point { x: 1, y: 2 }
+            let r: &Point = &p
+            let q: Point = p
+            return r.x
+        }
+    "#,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("MoveWhileBorrowed"), "{}", err);
+}
+
+#[test]
+fn test_borrow_conflict_points_at_both_borrows() {
+    let src = r#"
+        fn main() -> i32 {
+            let mut x: i32 = 1
+            let a: &mut i32 = &mut x;
+            let b: &mut i32 = &mut x;
+            *a = 2
+            return 0
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(src).unwrap();
+    let ast = parser::parse(&tokens, src).unwrap();
+    let resolved = resolve::resolve(ast).unwrap();
+    let source = SourceFile::new("test.d", src);
+    let mut checker = OwnershipChecker::new(&resolved.symbols, &source);
+    let errors = checker.check_program(&resolved.ast).unwrap_err();
+
+    let first = src.find("&mut x").unwrap();
+    let second = src.rfind("&mut x").unwrap();
+    match &errors[0] {
+        CompileError::DoubleMutBorrow {
+            span, first_span, ..
+        } => {
+            assert_eq!(first_span.offset(), first);
+            assert_eq!(span.offset(), second);
+            assert_eq!(span.len(), "&mut x".len());
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[test]
+fn test_returning_reference_to_local() {
+    let src = r#"
+        fn dangling() -> &i32 {
+            let x: i32 = 1;
+            &x
+        }
+    "#;
+    let tokens = demetrios::lexer::lex(src).unwrap();
+    let ast = parser::parse(&tokens, src).unwrap();
+    let resolved = resolve::resolve(ast).unwrap();
+    let source = SourceFile::new("test.d", src);
+    let mut checker = OwnershipChecker::new(&resolved.symbols, &source);
+    let errors = checker.check_program(&resolved.ast).unwrap_err();
+
+    match &errors[..] {
+        [CompileError::DanglingReference { name, span, .. }] => {
+            assert_eq!(name, "x");
+            assert_eq!(span.offset(), src.find("&x").unwrap());
+            assert_eq!(span.len(), "&x".len());
+        }
+        other => panic!("unexpected errors: {:?}", other),
+    }
+}
+
+#[test]
+fn test_reference_to_local_escaping_through_binding_or_out_param() {
+    let err = check_ownership(
+        r#"
+        fn through_binding(p: i32) -> &i32 {
+            let r = &p;
+            return r
+        }
+    "#,
+    )
+    .unwrap_err();
+    assert!(err.contains("DanglingReference"), "{}", err);
+
+    let err = check_ownership(
+        r#"
+        fn store(slot: &mut &i32) {
+            let z: i32 = 3;
+            *slot = &z;
+        }
+    "#,
+    )
+    .unwrap_err();
+    assert!(err.contains("DanglingReference"), "{}", err);
+}
+
+#[test]
+fn test_returning_reference_into_borrowed_parameter() {
+    let result = check_ownership(
+        r#"
+        struct Point { x: i32, y: i32 }
+
+        impl Point {
+            fn x(&self) -> &i32 { &self.x }
+        }
+
+        fn y(p: &Point) -> &i32 {
+            &p.y
+        }
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
@@ -61,6 +61,201 @@ fn test_parse_function_with_effects() {
     }
 }
 
+#[test]
+fn test_parse_function_attributes() {
+    let ast = parse_source(
+        r#"
+        #[pure]
+        #[cfg(feature = "gpu")]
+        #[inline(always)]
+        fn square(x: i64) -> i64 { x * x }
+    "#,
+    );
+
+    if let Item::Function(f) = &ast.items[0] {
+        let attrs: Vec<_> = f
+            .attributes
+            .iter()
+            .map(|a| (a.name.as_str(), a.args.clone()))
+            .collect();
+        assert_eq!(
+            attrs,
+            vec![
+                ("pure", vec![]),
+                ("cfg", vec![r#"feature="gpu""#.to_string()]),
+                ("inline", vec!["always".to_string()]),
+            ]
+        );
+    } else {
+        panic!("Expected function");
+    }
+}
+
+fn fn_attributes(ast: &Ast) -> Vec<(String, Vec<String>)> {
+    match &ast.items[0] {
+        Item::Function(f) => f
+            .attributes
+            .iter()
+            .map(|a| (a.name.clone(), a.args.clone()))
+            .collect(),
+        _ => panic!("Expected function"),
+    }
+}
+
+const CFG_ATTR_FN: &str = r#"
+    #[pure]
+    #[cfg_attr(release, inline(always))]
+    #[cfg_attr(all(feature = "gpu", not(release)), kernel_hint, derive(Hash))]
+    fn square(x: i64) -> i64 { x * x }
+"#;
+
+#[test]
+fn test_cfg_attr_applied_when_cfg_matches() {
+    let mut ast = parse_source(CFG_ATTR_FN);
+    demetrios::ast::cfg::expand_cfg_attrs(&mut ast, &["release".to_string()]);
+    assert_eq!(
+        fn_attributes(&ast),
+        vec![
+            ("pure".to_string(), vec![]),
+            ("inline".to_string(), vec!["always".to_string()]),
+        ]
+    );
+
+    let mut ast = parse_source(CFG_ATTR_FN);
+    demetrios::ast::cfg::expand_cfg_attrs(&mut ast, &[r#"feature = "gpu""#.to_string()]);
+    assert_eq!(
+        fn_attributes(&ast),
+        vec![
+            ("pure".to_string(), vec![]),
+            ("kernel_hint".to_string(), vec![]),
+            ("derive".to_string(), vec!["Hash".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn test_cfg_attr_absent_when_cfg_does_not_match() {
+    let mut ast = parse_source(CFG_ATTR_FN);
+    demetrios::ast::cfg::expand_cfg_attrs(&mut ast, &[]);
+    assert_eq!(fn_attributes(&ast), vec![("pure".to_string(), vec![])]);
+}
+
+const CFG_ITEMS: &str = r#"
+    #[cfg(feature = "gpu")]
+    fn gpu_only() -> i64 { 1 }
+    #[cfg(not(feature = "gpu"))]
+    fn cpu_only() -> i64 { 2 }
+    #[cfg(any(feature = "gpu", release))]
+    struct Accel;
+    #[cfg(all(feature = "gpu", release))]
+    fn tuned() -> i64 { 3 }
+    struct Device;
+    impl Device {
+        #[cfg(feature = "gpu")]
+        fn launch(&self) -> i64 { 4 }
+        fn name(&self) -> i64 { 5 }
+    }
+"#;
+
+fn item_names(ast: &Ast) -> Vec<String> {
+    ast.items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(f) => Some(f.name.clone()),
+            Item::Struct(s) => Some(s.name.clone()),
+            Item::Impl(i) => Some(format!(
+                "impl({})",
+                i.items
+                    .iter()
+                    .filter_map(|m| match m {
+                        ImplItem::Fn(f) => Some(f.name.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_cfg_excludes_gated_items_when_inactive() {
+    let mut ast = parse_source(CFG_ITEMS);
+    demetrios::ast::cfg::configure(&mut ast, &[]);
+    assert_eq!(item_names(&ast), ["cpu_only", "Device", "impl(name)"]);
+}
+
+#[test]
+fn test_cfg_includes_gated_items_when_active() {
+    let mut ast = parse_source(CFG_ITEMS);
+    demetrios::ast::cfg::configure(&mut ast, &[r#"feature="gpu""#.to_string()]);
+    assert_eq!(
+        item_names(&ast),
+        ["gpu_only", "Accel", "Device", "impl(launch, name)"]
+    );
+
+    let mut ast = parse_source(CFG_ITEMS);
+    let flags = [r#"feature = "gpu""#.to_string(), "release".to_string()];
+    demetrios::ast::cfg::configure(&mut ast, &flags);
+    assert!(item_names(&ast).contains(&"tuned".to_string()));
+}
+
+#[test]
+fn test_parse_attributes_on_every_item() {
+    let source = r#"
+        #[gpu]
+        fn scale(x: f64) -> f64 { x * 2.0 }
+        #[repr(C)]
+        struct Point { x: f64 }
+        #[marker]
+        trait Shape {}
+        #[doc_hidden]
+        impl Point {
+            #[inline]
+            fn norm(&self) -> f64 { self.x }
+        }
+        #[deprecated("use Point")]
+        type P = Point;
+        #[no_mangle]
+        const ORIGIN: f64 = 0.0;
+    "#;
+    let ast = parse_source(source);
+
+    let names: Vec<_> = ast
+        .items
+        .iter()
+        .map(|item| {
+            let [attr] = item.attributes() else {
+                panic!("expected one attribute on {:?}", item);
+            };
+            (attr.name.as_str(), attr.args.clone())
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("gpu", vec![]),
+            ("repr", vec!["C".to_string()]),
+            ("marker", vec![]),
+            ("doc_hidden", vec![]),
+            ("deprecated", vec![r#""use Point""#.to_string()]),
+            ("no_mangle", vec![]),
+        ]
+    );
+
+    let span = ast.items[1].attributes()[0].span;
+    assert_eq!(&source[span.start..span.end], "#[repr(C)]");
+
+    let Item::Impl(i) = &ast.items[3] else {
+        panic!("Expected impl");
+    };
+    let ImplItem::Fn(f) = &i.items[0] else {
+        panic!("Expected method");
+    };
+    assert!(f.attributes.iter().any(|a| a.is("inline")));
+}
+
 #[test]
 fn test_parse_struct() {
     let ast = parse_source("struct Point { x: f64, y: f64 }");
@@ -75,6 +270,65 @@ fn test_parse_struct() {
     }
 }
 
+#[test]
+fn test_parse_tuple_struct() {
+    let ast = parse_source("struct Meters(f64);\nstruct Pair<T>(T, i64)\nfn main() {}");
+
+    let Item::Struct(s) = &ast.items[0] else {
+        panic!("Expected struct");
+    };
+    assert_eq!(s.name, "Meters");
+    assert!(s.is_tuple());
+    assert!(s.fields.is_empty());
+    let StructKind::Tuple(types) = &s.kind else {
+        panic!("Expected tuple struct");
+    };
+    assert_eq!(types.len(), 1);
+
+    let Item::Struct(s) = &ast.items[1] else {
+        panic!("Expected struct");
+    };
+    let names: Vec<_> = s.field_types().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["0", "1"]);
+    assert!(matches!(ast.items[2], Item::Function(_)));
+}
+
+#[test]
+fn test_parse_unit_struct() {
+    let ast = parse_source("struct Meters;\nstruct Tag<T>;\nfn main() {}");
+
+    let Item::Struct(s) = &ast.items[0] else {
+        panic!("Expected struct");
+    };
+    assert_eq!(s.name, "Meters");
+    assert!(matches!(s.kind, StructKind::Unit));
+    assert!(!s.is_tuple());
+    assert!(s.field_types().is_empty());
+
+    let Item::Struct(s) = &ast.items[1] else {
+        panic!("Expected struct");
+    };
+    assert_eq!(s.generics.params.len(), 1);
+    assert!(matches!(ast.items[2], Item::Function(_)));
+}
+
+#[test]
+fn test_parse_grouped_import() {
+    let ast = parse_source("import a::b::{c, d::e as f, g::*};");
+
+    let Item::Import(import) = &ast.items[0] else {
+        panic!("Expected import");
+    };
+    assert_eq!(import.tree.to_string(), "a::b::{c, d::e as f, g::*}");
+
+    let flat: Vec<_> = import.tree.flatten().iter().map(|t| t.to_string()).collect();
+    assert_eq!(flat, vec!["a::b::c", "a::b::d::e as f", "a::b::g::*"]);
+    assert!(matches!(
+        &import.tree.flatten()[1],
+        ImportTree::Path { alias: Some(alias), .. } if alias == "f"
+    ));
+}
+
 #[test]
 fn test_parse_linear_struct() {
     let ast = parse_source("linear struct FileHandle { fd: i32 }");
@@ -100,6 +354,29 @@ fn test_parse_enum() {
     }
 }
 
+#[test]
+fn test_parse_enum_discriminants() {
+    let ast = parse_source("enum Flag { A = 1, B, C = 4 }");
+
+    if let Item::Enum(e) = &ast.items[0] {
+        let values: Vec<_> = e
+            .variants
+            .iter()
+            .map(|v| match &v.discriminant {
+                Some(Expr::Literal {
+                    value: Literal::Int(n),
+                    ..
+                }) => Some(*n),
+                None => None,
+                other => panic!("unexpected discriminant {:?}", other),
+            })
+            .collect();
+        assert_eq!(values, vec![Some(1), None, Some(4)]);
+    } else {
+        panic!("Expected enum");
+    }
+}
+
 #[test]
 fn test_parse_trait() {
     let ast = parse_source("trait Display { fn fmt(self) -> String; }");
@@ -131,6 +408,23 @@ fn test_parse_let_binding() {
     }
 }
 
+#[test]
+fn test_parse_let_else() {
+    let ast = parse_source("fn f(opt: Option<i64>) -> i64 { let Some(x) = opt else { return 0 }; x }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(f.body.stmts.len(), 2);
+    match &f.body.stmts[0] {
+        Stmt::LetElse {
+            pattern: Pattern::Enum { .. },
+            else_block,
+            ..
+        } => assert_eq!(else_block.stmts.len(), 1),
+        other => panic!("Expected let-else, got {:?}", other),
+    }
+}
+
 #[test]
 fn test_parse_if_expression() {
     let ast = parse_source("fn main() { if true { 1 } else { 2 } }");
@@ -211,3 +505,782 @@ fn test_parse_kernel_function() {
         panic!("Expected function");
     }
 }
+
+fn first_let_string(ast: &Ast) -> String {
+    if let Item::Function(f) = &ast.items[0]
+        && let Stmt::Let {
+            value: Some(Expr::Literal {
+                value: Literal::String(s),
+                ..
+            }),
+            ..
+        } = &f.body.stmts[0]
+    {
+        return s.clone();
+    }
+    panic!("Expected string literal in let binding");
+}
+
+#[test]
+fn test_parse_raw_string_literal() {
+    let ast = parse_source(r###"fn main() { let s = r#"has "quotes" and \n literally"#; }"###);
+    assert_eq!(first_let_string(&ast), r#"has "quotes" and \n literally"#);
+}
+
+#[test]
+fn test_parse_multiline_string_literal() {
+    let ast = parse_source("fn main() { let s = \"line one\n  line two\"; }");
+    assert_eq!(first_let_string(&ast), "line one\n  line two");
+}
+
+#[test]
+fn test_parse_string_escapes() {
+    let ast = parse_source(r#"fn main() { let s = "tab\there \"quoted\"\n"; }"#);
+    assert_eq!(first_let_string(&ast), "tab\there \"quoted\"\n");
+}
+
+/// The trailing expression of the first function's body
+fn tail_expr(ast: &Ast) -> &Expr {
+    if let Item::Function(f) = &ast.items[0]
+        && let Some(Stmt::Expr { expr, .. }) = f.body.stmts.last()
+    {
+        return expr;
+    }
+    panic!("Expected trailing expression");
+}
+
+/// The expression of a block's only or last statement
+fn block_tail(block: &Block) -> &Expr {
+    match block.stmts.last() {
+        Some(Stmt::Expr { expr, .. }) => expr,
+        _ => panic!("Expected trailing expression"),
+    }
+}
+
+#[test]
+fn test_parse_valueless_return_before_closing_tokens() {
+    let ast = parse_source("fn f(x: i64) { match x { 0 => return, _ => return } }");
+    let Expr::Match { arms, .. } = tail_expr(&ast) else {
+        panic!("Expected match");
+    };
+    assert_eq!(arms.len(), 2);
+    for arm in arms {
+        assert!(matches!(arm.body, Expr::Return { value: None, .. }));
+    }
+
+    let ast = parse_source("fn f() { let g = || { return }; g }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    let Some(Stmt::Let {
+        value: Some(Expr::Closure { body, .. }),
+        ..
+    }) = f.body.stmts.first()
+    else {
+        panic!("Expected closure");
+    };
+    let Expr::Block { block, .. } = body.as_ref() else {
+        panic!("Expected block body");
+    };
+    assert!(matches!(block_tail(block), Expr::Return { value: None, .. }));
+
+    let ast = parse_source("fn f(c: bool) -> i64 { if c { return } else { 1 } }");
+    let Expr::If {
+        then_branch,
+        else_branch,
+        ..
+    } = tail_expr(&ast)
+    else {
+        panic!("Expected if");
+    };
+    assert!(matches!(block_tail(then_branch), Expr::Return { value: None, .. }));
+    assert!(else_branch.is_some());
+}
+
+#[test]
+fn test_parse_valueless_break_and_return_as_arguments() {
+    let ast = parse_source("fn f() { g(return, 1) }");
+    let Expr::Call { args, .. } = tail_expr(&ast) else {
+        panic!("Expected call");
+    };
+    assert_eq!(args.len(), 2);
+    assert!(matches!(args[0], Expr::Return { value: None, .. }));
+
+    let ast = parse_source("fn f() { loop { [break] } }");
+    let Expr::Loop { body, .. } = tail_expr(&ast) else {
+        panic!("Expected loop");
+    };
+    let Expr::Array { elements, .. } = block_tail(body) else {
+        panic!("Expected array");
+    };
+    assert!(matches!(elements[..], [Expr::Break { value: None, .. }]));
+}
+
+#[test]
+fn test_parse_condition_block_is_not_a_struct_literal() {
+    let ast = parse_source("fn f() { if ready { 1 } else { 2 } }");
+    let Expr::If { condition, .. } = tail_expr(&ast) else {
+        panic!("Expected if");
+    };
+    assert!(matches!(**condition, Expr::Path { .. }));
+
+    let ast = parse_source("fn f() { while running { step() } }");
+    let Expr::While { condition, .. } = tail_expr(&ast) else {
+        panic!("Expected while");
+    };
+    assert!(matches!(**condition, Expr::Path { .. }));
+
+    let ast = parse_source("fn f() { for p in points { draw(p) } }");
+    let Expr::For { iter, .. } = tail_expr(&ast) else {
+        panic!("Expected for");
+    };
+    assert!(matches!(**iter, Expr::Path { .. }));
+}
+
+#[test]
+fn test_parse_struct_literal_in_delimited_condition() {
+    let conditions = [
+        "(P { x: 1 }).x == 1",
+        "near(P { x: 1 }, 2)",
+        "grid[P { x: 1 }.x] > 0",
+        "points == [P { x: 1 }]",
+        "(P { x: 1 }, 2).1 == 2",
+    ];
+    for condition in conditions {
+        let source = format!("fn f() {{ if {} {{ 1 }} else {{ 2 }} }}", condition);
+        let ast = parse_source(&source);
+        let Expr::If { then_branch, .. } = tail_expr(&ast) else {
+            panic!("Expected if in `{}`", condition);
+        };
+        assert!(
+            matches!(block_tail(then_branch), Expr::Literal { .. }),
+            "then branch of `{}` was not the block",
+            condition
+        );
+    }
+}
+
+#[test]
+fn test_parse_array_repeat() {
+    let ast = parse_source("fn f() { [0.0; N] }");
+    let Expr::ArrayRepeat { value, count, .. } = tail_expr(&ast) else {
+        panic!("Expected array repeat");
+    };
+    assert!(matches!(**value, Expr::Literal { .. }));
+    assert!(matches!(&**count, Expr::Path { path, .. } if path.segments == ["N"]));
+
+    // A single element followed by `;` is a repeat, a trailing comma is not
+    let ast = parse_source("fn f() { [1,] }");
+    assert!(matches!(tail_expr(&ast), Expr::Array { elements, .. } if elements.len() == 1));
+}
+
+#[test]
+fn test_parse_integer_literal_beyond_128_bits() {
+    let source = "fn f() -> u128 { 0x1_0000_0000_0000_0000_0000_0000_0000_0000 }";
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err();
+    assert!(err.to_string().contains("integer literal too large"), "{}", err);
+
+    let source = "fn f(x: i64) -> i64 { match x { -9223372036854775808 => 0, _ => 1 } }";
+    let ast = parse_source(source);
+    let Expr::Match { arms, .. } = tail_expr(&ast) else {
+        panic!("Expected match");
+    };
+    assert!(matches!(arms[0].pattern, Pattern::Literal(Literal::Int(i64::MIN))));
+}
+
+#[test]
+fn test_parse_turbofish_method_call() {
+    let ast = parse_source("fn main() { xs.collect::<Vec<i64>>() }");
+    let Expr::Call {
+        callee, type_args, ..
+    } = tail_expr(&ast)
+    else {
+        panic!("Expected call");
+    };
+    assert!(matches!(callee.as_ref(), Expr::Field { field, .. } if field == "collect"));
+    assert_eq!(type_args.len(), 1);
+    let TypeExpr::Named { path, args, .. } = &type_args[0] else {
+        panic!("Expected named type");
+    };
+    assert_eq!(path.segments, vec!["Vec"]);
+    assert!(matches!(&args[..], [TypeExpr::Named { path, .. }] if path.segments == ["i64"]));
+}
+
+#[test]
+fn test_parse_method_call() {
+    let ast = parse_source("fn main() { p.scale(2, 3) }");
+    let Expr::MethodCall {
+        receiver,
+        method,
+        args,
+        ..
+    } = tail_expr(&ast)
+    else {
+        panic!("Expected method call");
+    };
+    assert!(matches!(receiver.as_ref(), Expr::Path { path, .. } if path.segments == ["p"]));
+    assert_eq!(method, "scale");
+    assert_eq!(args.len(), 2);
+}
+
+#[test]
+fn test_parse_try_between_method_calls() {
+    let ast = parse_source("fn main() { a()?.b()? }");
+    let Expr::Try { expr: outer, .. } = tail_expr(&ast) else {
+        panic!("Expected `?` on the whole chain");
+    };
+    let Expr::MethodCall {
+        receiver, method, ..
+    } = outer.as_ref()
+    else {
+        panic!("Expected method call under `?`");
+    };
+    assert_eq!(method, "b");
+    let Expr::Try { expr: inner, .. } = receiver.as_ref() else {
+        panic!("Expected `?` on the receiver");
+    };
+    assert!(matches!(inner.as_ref(), Expr::Call { callee, .. }
+        if matches!(callee.as_ref(), Expr::Path { path, .. } if path.segments == ["a"])));
+}
+
+#[test]
+fn test_parse_postfix_chain_mixing_try_field_and_index() {
+    let ast = parse_source("fn main() { config.load()?.value[0] }");
+    let Expr::Index { base, index, .. } = tail_expr(&ast) else {
+        panic!("Expected index");
+    };
+    assert!(matches!(index.as_ref(), Expr::Literal { value: Literal::Int(0), .. }));
+    let Expr::Field { base, field, .. } = base.as_ref() else {
+        panic!("Expected field access");
+    };
+    assert_eq!(field, "value");
+    let Expr::Try { expr, .. } = base.as_ref() else {
+        panic!("Expected `?`");
+    };
+    assert!(matches!(expr.as_ref(), Expr::MethodCall { receiver, method, args, .. }
+        if method == "load"
+            && args.is_empty()
+            && matches!(receiver.as_ref(), Expr::Path { path, .. } if path.segments == ["config"])));
+}
+
+#[test]
+fn test_parse_nested_tuple_field() {
+    let ast = parse_source("fn main() { t.0.1 }");
+    let Expr::TupleField { base, index: 1, .. } = tail_expr(&ast) else {
+        panic!("Expected outer tuple field `.1`");
+    };
+    assert!(matches!(base.as_ref(), Expr::TupleField { index: 0, base, .. }
+        if matches!(base.as_ref(), Expr::Path { path, .. } if path.segments == ["t"])));
+}
+
+#[test]
+fn test_parse_turbofish_path_call() {
+    let ast = parse_source("fn main() { convert::into::<i64, bool>(x) }");
+    let Expr::Call {
+        callee,
+        type_args,
+        args,
+        ..
+    } = tail_expr(&ast)
+    else {
+        panic!("Expected call");
+    };
+    assert!(matches!(callee.as_ref(), Expr::Path { path, .. } if path.segments == ["convert", "into"]));
+    assert_eq!(type_args.len(), 2);
+    assert_eq!(args.len(), 1);
+
+    let tokens = lex("fn main() { id::<i64> }").unwrap();
+    assert!(parse(&tokens, "").is_err());
+}
+
+#[test]
+fn test_parse_turbofish_on_path_segment() {
+    let ast = parse_source("fn main() { Vec::<i64>::new() }");
+    let Expr::Call { callee, type_args, .. } = tail_expr(&ast) else {
+        panic!("Expected call");
+    };
+    let Expr::Path { path, .. } = callee.as_ref() else {
+        panic!("Expected path callee");
+    };
+    assert_eq!(path.segments, ["Vec", "new"]);
+    assert!(matches!(path.args_of(0), [TypeExpr::Named { .. }]));
+    assert!(path.args_of(1).is_empty());
+    assert!(type_args.is_empty());
+}
+
+#[test]
+fn test_parse_turbofish_const_arguments() {
+    let ast = parse_source("fn main() { zeros::<3, { N + 1 }, f64>() }");
+    let Expr::Call { type_args, .. } = tail_expr(&ast) else {
+        panic!("Expected call");
+    };
+    assert!(matches!(&type_args[0], TypeExpr::Const(value) if matches!(**value, Expr::Literal { .. })));
+    assert!(matches!(&type_args[1], TypeExpr::Const(value) if matches!(**value, Expr::Block { .. })));
+    assert!(matches!(&type_args[2], TypeExpr::Named { .. }));
+}
+
+#[test]
+fn test_parse_vec_macro() {
+    let ast = parse_source("fn main() { vec![1, 2, 3] }");
+    let Expr::Call { callee, args, .. } = tail_expr(&ast) else {
+        panic!("Expected call");
+    };
+    assert!(matches!(callee.as_ref(), Expr::Path { path, .. } if path.segments == ["vec"]));
+    assert_eq!(args.len(), 3);
+
+    let ast = parse_source("fn main() { vec![] }");
+    assert!(matches!(tail_expr(&ast), Expr::Call { args, .. } if args.is_empty()));
+}
+
+#[test]
+fn test_parse_reports_every_malformed_item() {
+    let source = r#"
+fn first() -> i64 {
+    let a = 1 +
+    let b = 2
+    a
+}
+
+fn ok() -> i64 { 3 }
+
+fn third( -> i64 { 4 }
+"#;
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err();
+    assert_eq!(err.to_string(), "2 parse errors");
+    let related: Vec<_> = err.related().unwrap().map(|e| e.to_string()).collect();
+    assert_eq!(
+        related,
+        [
+            "Unexpected token Let in expression",
+            "Expected pattern, found Arrow"
+        ]
+    );
+}
+
+#[test]
+fn test_parse_single_error_is_reported_alone() {
+    let source = "fn ok() -> i64 { 3 }\nfn broken( -> i64 { 4 }\n";
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err().to_string();
+    assert_eq!(err, "Expected pattern, found Arrow");
+}
+
+#[test]
+fn test_parse_error_labels_offending_token() {
+    let source = "fn dose(weight: f64 rate: f64) -> f64 { weight * rate }";
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unexpected token: expected `,`, found `rate`"
+    );
+
+    let label = err.labels().unwrap().next().unwrap();
+    assert_eq!(label.offset(), source.find("rate").unwrap());
+    assert_eq!(label.len(), "rate".len());
+
+    // The caret underlines the token in the rendered report
+    let mut rendered = String::new();
+    miette::GraphicalReportHandler::new_themed(miette::GraphicalTheme::unicode_nocolor())
+        .render_report(&mut rendered, err.as_ref())
+        .unwrap();
+    assert!(rendered.contains("[<input>:1:21]"), "{}", rendered);
+    let lines: Vec<_> = rendered.lines().collect();
+    let code = lines.iter().position(|l| l.contains(source)).unwrap();
+    let gutter = lines[code].find(source).unwrap();
+    let column = lines[code][..gutter].chars().count() + source.find("rate").unwrap();
+    let underline: String = lines[code + 1].chars().skip(column).take(4).collect();
+    assert_eq!(underline, "──┬─", "{}", rendered);
+}
+
+#[test]
+fn test_parse_errors_in_several_items_keep_positions() {
+    let source = "fn a(x: i64 y: i64) {}\nfn b() {}\nfn c(z i64) {}\n";
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err();
+    let file = demetrios::common::SourceFile::from_str(source);
+    let errors: Vec<_> = err
+        .related()
+        .unwrap()
+        .map(|e| {
+            let label = e.labels().unwrap().next().unwrap();
+            let (line, col) = file.line_col(label.offset());
+            (e.code().unwrap().to_string(), e.to_string(), line, col)
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (
+                "D0001".to_string(),
+                "Unexpected token: expected `,`, found `y`".to_string(),
+                1,
+                13
+            ),
+            (
+                "D0001".to_string(),
+                "Unexpected token: expected `:`, found `i64`".to_string(),
+                3,
+                8
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_handler_with_resume_and_return() {
+    let source = r#"
+handler CollectLog for Logger {
+    log(msg) => {
+        let pair = resume(());
+        (pair.0, msg + pair.1)
+    }
+    return x => (x, "")
+}
+"#;
+    let ast = parse_source(source);
+    let Item::Handler(h) = &ast.items[0] else {
+        panic!("Expected handler definition");
+    };
+    assert_eq!(h.cases.len(), 1);
+    assert_eq!(h.cases[0].name, "log");
+    assert!(matches!(h.cases[0].params[0].ty, TypeExpr::Infer));
+    let ret = h.return_clause.as_ref().expect("return clause");
+    assert_eq!(ret.param, "x");
+    assert!(matches!(ret.body, Expr::Tuple { .. }));
+}
+
+#[test]
+fn test_parse_handler_rejects_second_return_clause() {
+    let source = r#"
+handler H for E {
+    return x => x
+    return y => y
+}
+"#;
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err().to_string();
+    assert!(err.contains("more than one return clause"), "{}", err);
+}
+
+#[test]
+fn test_parse_negative_and_range_patterns() {
+    let source = r#"
+fn main() {
+    match x {
+        -1 => 0,
+        0..=9 => 1,
+        -2.5..10.0 => 2,
+        _ => 3,
+    }
+}
+"#;
+    let ast = parse_source(source);
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    let Some(Stmt::Expr {
+        expr: Expr::Match { arms, .. },
+        ..
+    }) = f.body.stmts.first()
+    else {
+        panic!("Expected match expression");
+    };
+    assert!(matches!(
+        arms[0].pattern,
+        Pattern::Literal(Literal::Int(-1))
+    ));
+    assert!(matches!(
+        arms[1].pattern,
+        Pattern::Range {
+            start: Literal::Int(0),
+            end: Literal::Int(9),
+            inclusive: true,
+        }
+    ));
+    assert!(matches!(
+        arms[2].pattern,
+        Pattern::Range {
+            start: Literal::Float(-2.5),
+            end: Literal::Float(10.0),
+            inclusive: false,
+        }
+    ));
+}
+
+#[test]
+fn test_parse_at_pattern() {
+    let source = "fn main() { match x { n @ 1..=9 => n, mut p @ (a, _) => a, _ => 0 } }";
+    let ast = parse_source(source);
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    let Some(Stmt::Expr {
+        expr: Expr::Match { arms, .. },
+        ..
+    }) = f.body.stmts.first()
+    else {
+        panic!("Expected match expression");
+    };
+    let Pattern::At {
+        name,
+        mutable: false,
+        pattern,
+        ..
+    } = &arms[0].pattern
+    else {
+        panic!("Expected `@` pattern, got {:?}", arms[0].pattern);
+    };
+    assert_eq!(name, "n");
+    assert!(matches!(
+        pattern.as_ref(),
+        Pattern::Range {
+            start: Literal::Int(1),
+            end: Literal::Int(9),
+            inclusive: true,
+        }
+    ));
+    let Pattern::At {
+        name,
+        mutable: true,
+        pattern,
+        ..
+    } = &arms[1].pattern
+    else {
+        panic!("Expected `mut` `@` pattern, got {:?}", arms[1].pattern);
+    };
+    assert_eq!(name, "p");
+    assert!(matches!(pattern.as_ref(), Pattern::Tuple(elems) if elems.len() == 2));
+}
+
+#[test]
+fn test_parse_for_range() {
+    let ast = parse_source("fn main() { for i in 0..=n - 1 { f(i) } }");
+    let Expr::For { pattern, iter, .. } = tail_expr(&ast) else {
+        panic!("Expected for loop");
+    };
+    assert!(matches!(pattern, Pattern::Binding { name, .. } if name == "i"));
+    let Expr::Range {
+        start,
+        end,
+        inclusive,
+        ..
+    } = iter.as_ref()
+    else {
+        panic!("Expected range");
+    };
+    assert!(matches!(start.as_ref(), Expr::Literal { value: Literal::Int(0), .. }));
+    assert!(matches!(end.as_ref(), Expr::Binary { op: BinaryOp::Sub, .. }));
+    assert!(*inclusive);
+}
+
+#[test]
+fn test_parse_doc_comment_on_fn() {
+    let ast = parse_source("/// Adds two numbers\nfn add(a: i64, b: i64) -> i64 { a + b }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(f.doc.as_deref(), Some("Adds two numbers"));
+}
+
+#[test]
+fn test_parse_doc_comments_joined_across_attributes() {
+    let source = "/// First line\n#[inline]\n/// Second line\nstruct Point { x: i64 }\n\nfn undocumented() {}";
+    let ast = parse_source(source);
+    let Item::Struct(s) = &ast.items[0] else {
+        panic!("Expected struct");
+    };
+    assert_eq!(s.doc.as_deref(), Some("First line\nSecond line"));
+    assert_eq!(s.attributes.len(), 1);
+    let Item::Function(f) = &ast.items[1] else {
+        panic!("Expected function");
+    };
+    assert!(f.doc.is_none());
+}
+
+#[test]
+fn test_parse_inner_doc_comments_attach_to_module() {
+    let source = "//! Geometry helpers\n//! for tests\n\n/** Origin\n * of the plane */\nconst ORIGIN: i64 = 0;";
+    let ast = parse_source(source);
+    assert_eq!(ast.doc.as_deref(), Some("Geometry helpers\nfor tests"));
+    let Item::Global(g) = &ast.items[0] else {
+        panic!("Expected global");
+    };
+    assert_eq!(g.doc.as_deref(), Some("Origin\nof the plane"));
+}
+
+#[test]
+fn test_parse_doc_comments_on_impl_and_trait_fns() {
+    let source = "trait Shape {\n    /// Area of the shape\n    fn area(self) -> f64;\n}\n\nimpl Point {\n    /// Make a point\n    fn new() -> Point { Point { x: 0 } }\n}";
+    let ast = parse_source(source);
+    let Item::Trait(t) = &ast.items[0] else {
+        panic!("Expected trait");
+    };
+    let TraitItem::Fn(area) = &t.items[0] else {
+        panic!("Expected trait fn");
+    };
+    assert_eq!(area.doc.as_deref(), Some("Area of the shape"));
+    let Item::Impl(i) = &ast.items[1] else {
+        panic!("Expected impl");
+    };
+    let ImplItem::Fn(new) = &i.items[0] else {
+        panic!("Expected impl fn");
+    };
+    assert_eq!(new.doc.as_deref(), Some("Make a point"));
+}
+
+#[test]
+fn test_parse_expression_bodied_fn() {
+    let ast = parse_source("fn double(x: i64) -> i64 = x * 2;\nfn main() -> i64 { double(21) }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(f.name, "double");
+    match f.body.stmts.as_slice() {
+        [Stmt::Expr { expr: Expr::Binary { op: BinaryOp::Mul, .. }, has_semi: false }] => {}
+        other => panic!("Expected a trailing `x * 2`, got {:?}", other),
+    }
+    assert!(matches!(&ast.items[1], Item::Function(f) if f.name == "main"));
+}
+
+#[test]
+fn test_parse_expression_body_after_effects_and_where() {
+    let source = "fn log_twice<T>(x: T) -> T with IO where T: Clone = x;";
+    let ast = parse_source(source);
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(f.effects.len(), 1);
+    assert_eq!(f.where_clause.len(), 1);
+    assert_eq!(f.body.stmts.len(), 1);
+}
+
+#[test]
+fn test_parse_expression_body_rejects_block() {
+    let source = "fn double(x: i64) -> i64 = { x * 2 }";
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err().to_string();
+    assert_eq!(
+        err,
+        "Unexpected token: expected an expression after `=`, not a block body, found `{`"
+    );
+
+    let source = "fn double(x: i64) -> i64 = x * 2 { x }";
+    let tokens = lex(source).unwrap();
+    assert!(parse(&tokens, source).is_err());
+}
+
+#[test]
+fn test_parse_function_type_with_effects() {
+    let ast = parse_source("fn run_with(f: fn(i64) -> i64 with IO, Alloc) -> i64 { f(1) }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    match &f.params[0].ty {
+        TypeExpr::Function { params, effects, .. } => {
+            assert_eq!(params.len(), 1);
+            let names: Vec<_> = effects.iter().map(|e| e.name.to_string()).collect();
+            assert_eq!(names, ["IO", "Alloc"]);
+        }
+        other => panic!("Expected a function type, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_where_fn_bound_with_effects() {
+    let source = "fn run_with<F>(f: F) -> i64 with IO where F: Fn() -> i64 with IO + Clone { f() }";
+    let ast = parse_source(source);
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    let predicate = &f.where_clause[0];
+    assert_eq!(predicate.bounds.len(), 1);
+    match &predicate.signature {
+        Some(TypeExpr::Function { params, effects, .. }) => {
+            assert!(params.is_empty());
+            assert_eq!(effects.len(), 1);
+        }
+        other => panic!("Expected an `Fn` signature, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_dyn_trait_reference() {
+    let ast = parse_source("fn total(s: &dyn Shape) -> f64 { s.area() }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    match &f.params[0].ty {
+        TypeExpr::Reference { mutable, inner, .. } => {
+            assert!(!mutable);
+            assert!(matches!(&**inner, TypeExpr::Dyn { path, .. } if path.segments == ["Shape"]));
+        }
+        other => panic!("Expected a reference type, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_reference_lifetime() {
+    let ast = parse_source("fn bump(x: &'a mut i64) { }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    match &f.params[0].ty {
+        TypeExpr::Reference { mutable, lifetime, inner } => {
+            assert!(mutable);
+            assert_eq!(lifetime.as_deref(), Some("'a"));
+            assert!(matches!(&**inner, TypeExpr::Named { path, .. } if path.segments == ["i64"]));
+        }
+        other => panic!("Expected a reference type, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_lifetime_generic_function() {
+    let ast = parse_source("fn longest<'a, T>(x: &'a T, y: &'a T) -> &'a T { x }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    assert!(matches!(&f.generics.params[0], GenericParam::Lifetime { name } if name == "'a"));
+    assert!(matches!(&f.generics.params[1], GenericParam::Type { name, .. } if name == "T"));
+    assert!(matches!(
+        &f.return_type,
+        Some(TypeExpr::Reference { mutable: false, lifetime: Some(l), .. }) if l == "'a"
+    ));
+}
+
+#[test]
+fn test_parse_chained_comparison_suggests_conjunction() {
+    let source = "fn between(a: i64, b: i64, c: i64) -> bool { a < b < c }";
+    let tokens = lex(source).unwrap();
+    let err = parse(&tokens, source).unwrap_err();
+    assert_eq!(err.to_string(), "Comparison operators cannot be chained");
+    assert_eq!(err.code().unwrap().to_string(), "D0007");
+    assert_eq!(
+        err.help().unwrap().to_string(),
+        "split the comparison: `a < b && b < c`"
+    );
+
+    let label = err.labels().unwrap().next().unwrap();
+    assert_eq!(label.offset(), source.find("a < b").unwrap());
+    assert_eq!(label.len(), "a < b < c".len());
+}
+
+#[test]
+fn test_parse_parenthesized_comparison_of_comparison() {
+    let ast = parse_source("fn same(a: i64, b: i64, c: bool) -> bool { (a < b) == c }");
+    let Item::Function(f) = &ast.items[0] else {
+        panic!("Expected function");
+    };
+    let Some(Stmt::Expr { expr, .. }) = f.body.stmts.last() else {
+        panic!("Expected a tail expression");
+    };
+    match expr {
+        Expr::Binary { op: BinaryOp::Eq, left, .. } => {
+            assert!(matches!(&**left, Expr::Binary { op: BinaryOp::Lt, .. }));
+        }
+        other => panic!("Expected a comparison, got {:?}", other),
+    }
+}
@@ -214,3 +214,100 @@ fn test_resolve_nested_blocks() {
 
     assert!(resolved.symbols.lookup("test").is_some());
 }
+
+#[test]
+fn test_resolve_import_alias() {
+    let src = r#"
+        import std::io::{println as say, Reader};
+        fn main(r: Reader) {
+            say(1)
+        }
+    "#;
+    let resolved = resolve_source(src).expect("Resolution failed");
+
+    assert!(resolved.symbols.lookup("say").is_some());
+    assert!(resolved.symbols.lookup("println").is_none());
+    assert!(resolved.symbols.lookup_type("Reader").is_some());
+}
+
+#[test]
+fn test_resolve_import_alias_of_local_variant() {
+    let src = r#"
+        enum Color { Red, Green }
+        import Color::Red as Stop;
+        fn main() -> Color {
+            Stop
+        }
+    "#;
+    let resolved = resolve_source(src).expect("Resolution failed");
+
+    assert_eq!(
+        resolved.symbols.lookup("Stop"),
+        resolved.symbols.lookup("Color::Red")
+    );
+}
+
+#[test]
+fn test_resolve_glob_import_of_enum() {
+    let src = r#"
+        enum Color { Red, Green }
+        import Color::*;
+        fn main() -> Color {
+            Green
+        }
+    "#;
+    let resolved = resolve_source(src).expect("Resolution failed");
+
+    assert_eq!(
+        resolved.symbols.lookup("Red"),
+        resolved.symbols.lookup("Color::Red")
+    );
+    assert!(resolved.symbols.lookup("Green").is_some());
+}
+
+#[test]
+fn test_resolve_external_glob_import() {
+    let src = r#"
+        import std::math::*;
+        fn main() -> f64 {
+            sqrt(2.0)
+        }
+    "#;
+    assert!(resolve_source(src).is_ok());
+
+    let err = resolve_source("fn main() -> f64 { sqrt(2.0) }").unwrap_err();
+    assert!(err.contains("sqrt"), "{}", err);
+}
+
+#[test]
+fn test_resolve_prelude_option_and_result() {
+    let src = r#"
+        fn get(o: Option<i64>) -> i64 { match o { Some(v) => v, None => 0 } }
+        fn check(r: Result<i64, String>) -> Result<i64, String> {
+            match r { Ok(v) => Ok(v + 1), Err(e) => Err(e) }
+        }
+    "#;
+    let resolved = resolve_source(src).expect("Resolution failed");
+    assert!(resolved.symbols.lookup_type("Option").is_some());
+}
+
+#[test]
+fn test_resolve_enum_shadows_prelude() {
+    let src = r#"
+        enum Option { Some(i64), None }
+        fn get(o: Option) -> i64 { match o { Option::Some(v) => v, Option::None => 0 } }
+    "#;
+    assert!(resolve_source(src).is_ok());
+}
+
+#[test]
+fn test_resolve_prelude_distributions() {
+    let src = r#"
+        fn main() -> f64 with Prob {
+            let coin = sample(Bernoulli(0.5));
+            let x = sample(Uniform(0.0, 1.0));
+            if coin { x } else { sample(Normal(0.0, 1.0)) }
+        }
+    "#;
+    assert!(resolve_source(src).is_ok());
+}
@@ -5,8 +5,12 @@
 //! - Unit type annotations (f64@kg)
 //! - Unit arithmetic and checking
 //! - Unit inference
+//! - User-declared units (unit Bq; unit Gy = J/kg;)
 
+use demetrios::ast::Item;
+use demetrios::check::TypeChecker;
 use demetrios::common::Span;
+use demetrios::hir::{HirItem, HirType};
 use demetrios::lexer::lex;
 use demetrios::parser::parse;
 use demetrios::types::unit_infer::{UnitExpr, UnitInference};
@@ -110,6 +114,26 @@ fn test_parse_unit_arithmetic() {
     assert_eq!(ast.items.len(), 1);
 }
 
+#[test]
+fn test_parse_unit_declarations() {
+    let source = "unit Bq;\nunit N = kg*m/s^2;\nfn unit(x: i64) -> i64 { x }";
+    let tokens = lex(source).expect("should lex");
+    let ast = parse(&tokens, source).expect("should parse");
+
+    let Item::UnitDef(base) = &ast.items[0] else {
+        panic!("Expected unit declaration");
+    };
+    assert_eq!(base.name, "Bq");
+    assert_eq!(base.definition, None);
+    let Item::UnitDef(derived) = &ast.items[1] else {
+        panic!("Expected unit declaration");
+    };
+    assert_eq!(derived.name, "N");
+    assert_eq!(derived.definition.as_deref(), Some("kg*m/s^2"));
+    // `unit` is still an ordinary identifier elsewhere
+    assert!(matches!(ast.items[2], Item::Function(_)));
+}
+
 // ==================== Unit Type Tests ====================
 
 #[test]
@@ -298,6 +322,234 @@ fn test_unit_inference_mismatch_error() {
     assert!(result.is_err());
 }
 
+// ==================== Checker Unit Inference Tests ====================
+
+/// Type check `source`, returning the checker so inferred units and unit
+/// errors can be inspected
+fn check_units(source: &str) -> (TypeChecker, bool) {
+    let tokens = lex(source).expect("should lex");
+    let ast = parse(&tokens, source).expect("should parse");
+    let mut checker = TypeChecker::new();
+    let ok = checker.check_program(&ast).is_ok();
+    (checker, ok)
+}
+
+fn inferred_unit(checker: &TypeChecker, name: &str) -> String {
+    checker
+        .inferred_units()
+        .iter()
+        .find(|u| u.name == name)
+        .map(|u| u.display.clone())
+        .unwrap_or_else(|| panic!("no unit inferred for `{}`", name))
+}
+
+#[test]
+fn test_check_infers_division_unit() {
+    let source = r#"
+        fn main() {
+            let dose = 500.0_mg;
+            let volume: f64@mL = 10.0_mL;
+            let concentration = dose / volume;
+            let doubled = concentration * 2.0;
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(ok, "{:?}", checker.unit_errors());
+    assert_eq!(inferred_unit(&checker, "dose"), "mg");
+    assert_eq!(inferred_unit(&checker, "concentration"), "mg/mL");
+    assert_eq!(inferred_unit(&checker, "doubled"), "mg/mL");
+}
+
+#[test]
+fn test_check_infers_unit_from_parameters() {
+    let source = r#"
+        fn rate(volume: f64@mL, time: f64@min) -> f64 {
+            let flow = volume / time;
+            flow
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(ok, "{:?}", checker.unit_errors());
+    assert_eq!(inferred_unit(&checker, "flow"), "mL/min");
+}
+
+#[test]
+fn test_check_mixing_incompatible_units_fails() {
+    let source = r#"
+        fn main() {
+            let dose = 500.0_mg;
+            let volume = 10.0_mL;
+            let total = dose + volume;
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(!ok);
+    let errors = checker.unit_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("unit mismatch"), "{:?}", errors);
+    let span = errors[0].span;
+    assert_eq!(&source[span.start..span.end], "dose + volume");
+}
+
+#[test]
+fn test_check_annotation_conflicts_with_inferred_unit() {
+    let source = r#"
+        fn main() {
+            let dose = 500.0_mg;
+            let volume = 10.0_mL;
+            let concentration: f64@mg = dose / volume;
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(!ok);
+    assert_eq!(checker.unit_errors().len(), 1);
+}
+
+#[test]
+fn test_check_quantity_unit_parameters_multiply() {
+    let source = r#"
+        struct Meters;
+        struct Seconds;
+        fn f(d: Quantity<f64, Meters>, t: Quantity<f64, Seconds>) -> f64 {
+            let product = d * t;
+            let checked: f64@Meters*Seconds = product;
+            let rate = d / t;
+            checked
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(ok, "{:?}", checker.unit_errors());
+    assert_eq!(inferred_unit(&checker, "product"), "Meters*Seconds");
+    assert_eq!(inferred_unit(&checker, "rate"), "Meters/Seconds");
+}
+
+#[test]
+fn test_check_quantity_units_are_distinct() {
+    let source = r#"
+        struct Meters;
+        struct Seconds;
+        fn f(d: Quantity<f64, Meters>, t: Quantity<f64, Seconds>) -> f64 {
+            let wrong: Quantity<f64, Meters> = d * t;
+            let sum = d + t;
+            0.0
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(!ok);
+    assert_eq!(
+        checker.unit_errors().len(),
+        2,
+        "{:?}",
+        checker.unit_errors()
+    );
+}
+
+#[test]
+fn test_check_quantity_is_erased_to_its_numeric_type() {
+    let source = r#"
+        struct Meters;
+        fn f(d: Quantity<f64, Meters>, n: Quantity<i64, mg>) -> f64 { d }
+    "#;
+    let tokens = lex(source).expect("should lex");
+    let ast = parse(&tokens, source).expect("should parse");
+    let hir = TypeChecker::new()
+        .check_program(&ast)
+        .expect("should check");
+    let params = hir
+        .items
+        .iter()
+        .find_map(|item| match item {
+            HirItem::Function(f) if f.name == "f" => Some(&f.ty.params),
+            _ => None,
+        })
+        .expect("no function `f`");
+    assert_eq!(params[0].ty, HirType::F64);
+    assert_eq!(params[1].ty, HirType::I64);
+}
+
+#[test]
+fn test_check_declared_base_unit() {
+    let source = r#"
+        unit Bq;
+        fn f(a: f64@Bq, b: f64@Bq, t: f64@s) -> f64 {
+            let total = a + b;
+            let decays = total * t;
+            total
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(ok, "{:?}", checker.unit_errors());
+    assert_eq!(inferred_unit(&checker, "total"), "Bq");
+    assert_eq!(inferred_unit(&checker, "decays"), "Bq*s");
+
+    // A base unit has its own dimension
+    let source = r#"
+        unit Bq;
+        fn f(a: f64@Bq, t: f64@s) -> f64 {
+            let rate: f64@Bq = 1.0 / t;
+            a
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(!ok);
+    assert_eq!(checker.unit_errors().len(), 1);
+}
+
+#[test]
+fn test_check_derived_unit_follows_definition() {
+    let source = r#"
+        unit J = kg*m^2/s^2;
+        unit Gy = J/kg;
+        fn dose(energy: f64@J, mass: f64@kg) -> f64 {
+            let absorbed = energy / mass;
+            let checked: f64@Gy = absorbed;
+            let expanded: f64@m^2/s^2 = checked;
+            checked
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(ok, "{:?}", checker.unit_errors());
+    assert_eq!(inferred_unit(&checker, "absorbed"), "Gy");
+
+    let source = r#"
+        unit J = kg*m^2/s^2;
+        unit Gy = J/kg;
+        fn dose(energy: f64@J) -> f64 {
+            let wrong: f64@Gy = energy;
+            wrong
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(!ok);
+    assert_eq!(checker.unit_errors().len(), 1);
+}
+
+#[test]
+fn test_check_undeclared_units_rejected() {
+    let source = r#"
+        fn f(x: f64@furlong) -> f64 { x }
+    "#;
+    let tokens = lex(source).expect("should lex");
+    let ast = parse(&tokens, source).expect("should parse");
+    let err = TypeChecker::new().check_program(&ast).unwrap_err();
+    assert!(
+        err.to_string().contains("Unknown unit `furlong`"),
+        "{}",
+        err
+    );
+
+    let source = "unit Gy = J/kg;";
+    let tokens = lex(source).expect("should lex");
+    let ast = parse(&tokens, source).expect("should parse");
+    let err = TypeChecker::new().check_program(&ast).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Unknown unit `J` in the definition of `Gy`"),
+        "{}",
+        err
+    );
+}
+
 // ==================== Medical Unit Tests ====================
 
 #[test]
@@ -350,3 +602,42 @@ fn test_medical_time_units() {
     let conversion = h.conversion_factor(&min).expect("should convert");
     assert!((conversion - 60.0).abs() < 0.001); // 1 hour = 60 minutes
 }
+
+#[test]
+fn test_check_units_take_no_part_in_type_compatibility() {
+    // A unit literal is typed as a bare number, and a quantity passes where
+    // a bare number is expected
+    let source = r#"
+        fn volume() -> f64@mL { 10.0_mL }
+        fn bare(dose: f64@mg) -> f64 { dose }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(ok, "{:?}", checker.unit_errors());
+
+    // Converting between units is the unit checker's error, not a type
+    // mismatch
+    let source = r#"
+        fn relabel(dose: f64@mg) -> f64 {
+            let volume: f64@mL = dose;
+            volume
+        }
+    "#;
+    let (checker, ok) = check_units(source);
+    assert!(!ok);
+    assert_eq!(
+        checker.unit_errors().len(),
+        1,
+        "{:?}",
+        checker.unit_errors()
+    );
+
+    // The numeric type still has to match
+    let tokens = lex("fn f(n: i64@mg) -> f64@mg { n }").unwrap();
+    let ast = parse(&tokens, "").unwrap();
+    let err = TypeChecker::new().check_program(&ast).unwrap_err();
+    assert!(
+        err.to_string().contains("expected f64@mg, found i64"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,64 @@
+//! Debounced file watching tests
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use demetrios::watch::{Debouncer, debounce_events};
+
+const DELAY: Duration = Duration::from_millis(50);
+
+#[test]
+fn test_debouncer_waits_for_quiet_period() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(DELAY);
+    assert!(!debouncer.poll(start));
+
+    debouncer.record(start);
+    debouncer.record(start + Duration::from_millis(30));
+    // 40ms after the first change, but only 10ms after the latest
+    assert!(!debouncer.poll(start + Duration::from_millis(40)));
+    assert_eq!(
+        debouncer.time_left(start + Duration::from_millis(40)),
+        Some(Duration::from_millis(40))
+    );
+
+    assert!(debouncer.poll(start + Duration::from_millis(80)));
+    // The burst is reported once
+    assert!(!debouncer.poll(start + Duration::from_millis(200)));
+    assert_eq!(debouncer.time_left(start), None);
+}
+
+#[test]
+fn test_save_triggers_one_recheck() {
+    let (tx, rx) = mpsc::channel();
+    let saver = thread::spawn(move || {
+        // An editor save arriving as several events
+        for _ in 0..3 {
+            tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+        thread::sleep(DELAY * 4);
+    });
+
+    let mut rechecks = 0;
+    debounce_events(&rx, DELAY, || rechecks += 1);
+    saver.join().unwrap();
+    assert_eq!(rechecks, 1);
+}
+
+#[test]
+fn test_separate_saves_recheck_separately() {
+    let (tx, rx) = mpsc::channel();
+    let saver = thread::spawn(move || {
+        tx.send(()).unwrap();
+        thread::sleep(DELAY * 4);
+        tx.send(()).unwrap();
+        thread::sleep(DELAY * 4);
+    });
+
+    let mut rechecks = 0;
+    debounce_events(&rx, DELAY, || rechecks += 1);
+    saver.join().unwrap();
+    assert_eq!(rechecks, 2);
+}